@@ -0,0 +1,68 @@
+//! Register an importer written in code: its entries are loaded with the
+//! workbook sheets, like a `[sources]` bank download, under their own Origem.
+//!
+//! ```text
+//! cargo run --example custom_importer
+//! ```
+
+use chrono::NaiveDate;
+use pdw_rust::{EtlPipeline, Importer, PdwConfig, PdwError, Transaction};
+use rust_decimal::Decimal;
+use std::path::Path;
+
+/// Reads a pipe-separated export (`2024-01-18|LAZER|Cinema|-45.50`) from the input directory
+struct PipeExport {
+    file_name: String,
+}
+
+impl Importer for PipeExport {
+    fn read(&self, dir_in: &Path) -> Result<Vec<Transaction>, PdwError> {
+        let text = std::fs::read_to_string(dir_in.join(&self.file_name))?;
+        Ok(text.lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split('|').collect();
+                let [date, tipo, description, amount] = fields[..] else {
+                    return None;
+                };
+                let amount: Decimal = amount.trim().parse().ok()?;
+                Some(Transaction {
+                    date: NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok(),
+                    transaction_type: Some(tipo.trim().to_string()),
+                    description: Some(description.trim().to_string()),
+                    credit: (amount > Decimal::ZERO).then_some(amount),
+                    debit: (amount < Decimal::ZERO).then_some(-amount),
+                    origin: "Carteira".to_string(),
+                    currency: None,
+                    tags: None,
+                    row: None,
+                    invalid_amount: None,
+                })
+            })
+            .collect())
+    }
+}
+
+fn main() -> Result<(), PdwError> {
+    let work_dir = std::env::temp_dir().join("pdw_example_importer");
+    let input_dir = work_dir.join("PDW");
+    std::fs::create_dir_all(&input_dir)?;
+    std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n05/01/2024;SALARIO;Empresa;5000,00;\n")?;
+    std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nSALARIO;Salário\nLAZER;Lazer\n")?;
+    std::fs::write(work_dir.join("carteira.txt"), "2024-01-18|LAZER|Cinema|-45.50\n2024-01-20|LAZER|Livraria|-89.90\n")?;
+    
+    let mut config = PdwConfig::default();
+    config.directories.dir_in = work_dir.clone();
+    config.file_types.type_in = "csv".to_string();
+    
+    let mut pipeline = EtlPipeline::in_memory(config)?;
+    pipeline.register_importer("carteira", Box::new(PipeExport { file_name: "carteira.txt".to_string() }));
+    pipeline.execute_data_loading()?;
+    
+    for row in pipeline.database().execute_query(
+        "SELECT Origem, COUNT(*), SUM(Debito) FROM LANCAMENTOS_GERAIS GROUP BY Origem ORDER BY Origem"
+    )? {
+        let origin = row[0].as_str().unwrap_or_default();
+        println!("{:<10} {} entries, {:.2} in debits", origin, row[1], row[2].as_f64().unwrap_or_default());
+    }
+    Ok(())
+}
\ No newline at end of file
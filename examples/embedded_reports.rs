@@ -0,0 +1,54 @@
+//! Generate the report workbook from a program: queries are given in code
+//! instead of `PDW_QUERIES.yaml`, and run against the database of an
+//! in-memory load.
+//!
+//! ```text
+//! cargo run --example embedded_reports
+//! ```
+
+use pdw_rust::reporting::{QueryConfig, QueryDefinition};
+use pdw_rust::{EtlPipeline, PdwConfig, PdwError, ReportGenerator};
+
+fn main() -> Result<(), PdwError> {
+    let work_dir = std::env::temp_dir().join("pdw_example_reports");
+    let input_dir = work_dir.join("PDW");
+    std::fs::create_dir_all(&input_dir)?;
+    std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n\
+        05/01/2024;SALARIO;Empresa;5000,00;\n\
+        10/01/2024;ALUGUEL;Apartamento;;1500,00\n\
+        12/02/2024;MERCADO;Supermercado;;320,45\n")?;
+    std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\n\
+        SALARIO;Salário\nALUGUEL;Aluguel\nMERCADO;Mercado\n")?;
+    
+    let mut config = PdwConfig::default();
+    config.directories.dir_in = work_dir.clone();
+    config.directories.dir_out = work_dir.clone();
+    config.file_types.type_in = "csv".to_string();
+    // No dynamic reports were defined by this load
+    config.settings.run_dynamic_report = false;
+    
+    let mut pipeline = EtlPipeline::in_memory(config.clone())?;
+    pipeline.execute_data_loading()?;
+    
+    let queries = QueryConfig {
+        queries_padrao: vec![
+            QueryDefinition {
+                sql: "SELECT TIPO, SUM(Debito) AS Total FROM LANCAMENTOS_GERAIS WHERE Debito > 0 GROUP BY TIPO ORDER BY Total DESC".to_string(),
+                sheet_name: "Gastos por Tipo".to_string(),
+                ..QueryDefinition::default()
+            },
+            QueryDefinition {
+                sql: "SELECT AnoMes, SUM(Credito) - SUM(Debito) AS Saldo FROM LANCAMENTOS_GERAIS GROUP BY AnoMes".to_string(),
+                sheet_name: "Saldo {current_year}".to_string(),
+                ..QueryDefinition::default()
+            },
+        ],
+        ..QueryConfig::default()
+    };
+    ReportGenerator::new(pipeline.into_database(), config.clone())
+        .with_queries(queries)
+        .generate_excel_reports()?;
+    
+    println!("Report written to {}", config.get_report_path().display());
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+//! Load into an in-memory database (nothing is written next to the input) and
+//! read the results back as `Frame`s, by column name.
+//!
+//! ```text
+//! cargo run --example in_memory_frame
+//! ```
+
+use pdw_rust::{EtlPipeline, Frame, PdwConfig, PdwError};
+
+fn main() -> Result<(), PdwError> {
+    let work_dir = std::env::temp_dir().join("pdw_example_frame");
+    let input_dir = work_dir.join("PDW");
+    std::fs::create_dir_all(&input_dir)?;
+    std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n\
+        05/01/2024;SALARIO;Empresa;5000,00;\n\
+        10/01/2024;ALUGUEL;Apartamento;;1500,00\n\
+        15/01/2024;MERCADO;Feira;;210,30\n\
+        05/02/2024;SALARIO;Empresa;5000,00;\n\
+        10/02/2024;ALUGUEL;Apartamento;;1500,00\n\
+        12/02/2024;MERCADO;Supermercado;;320,45\n")?;
+    std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\n\
+        SALARIO;Salário\nALUGUEL;Aluguel\nMERCADO;Mercado\n")?;
+    
+    let mut config = PdwConfig::default();
+    config.directories.dir_in = work_dir.clone();
+    config.file_types.type_in = "csv".to_string();
+    
+    let mut pipeline = EtlPipeline::in_memory(config)?;
+    pipeline.execute_data_loading()?;
+    pipeline.create_pivot_tables()?;
+    
+    let monthly = Frame::query(pipeline.database(),
+        "SELECT AnoMes, SUM(Credito) AS Credito, SUM(Debito) AS Debito FROM LANCAMENTOS_GERAIS GROUP BY AnoMes ORDER BY AnoMes")?;
+    println!("{}", monthly);
+    println!("Debits over {} months: {:.2}", monthly.len(), monthly.sum("Debito").unwrap_or_default());
+    
+    let largest = Frame::query(pipeline.database(),
+        "SELECT Data, TIPO, DESCRICAO, Debito FROM LANCAMENTOS_GERAIS ORDER BY Debito DESC LIMIT 1")?;
+    if let Some(record) = largest.record(0) {
+        let text = |column: &str| record[column].as_str().unwrap_or_default().to_string();
+        println!("Largest debit: {} on {}", text("DESCRICAO"), text("Data"));
+    }
+    Ok(())
+}
\ No newline at end of file
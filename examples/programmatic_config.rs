@@ -0,0 +1,49 @@
+//! Build the configuration in code instead of reading `pdw_config.toml`, then
+//! run the load and pivot steps against a database file in a temporary directory.
+//!
+//! ```text
+//! cargo run --example programmatic_config
+//! ```
+
+use pdw_rust::{EtlPipeline, PdwConfig, PdwError};
+use std::path::Path;
+
+fn main() -> Result<(), PdwError> {
+    let work_dir = std::env::temp_dir().join("pdw_example_config");
+    write_sample_input(&work_dir)?;
+    
+    // Start from the defaults and set what differs, field by field...
+    let mut config = PdwConfig::default();
+    config.directories.dir_in = work_dir.clone();
+    config.directories.dir_out = work_dir.clone();
+    config.directories.database_dir = work_dir.clone();
+    config.directories.log_dir = work_dir.clone();
+    config.file_types.type_in = "csv".to_string();
+    
+    // ...or with the same dotted keys as `pdw --set`
+    let config = config.with_overrides(&[
+        ("settings.create_pivot".to_string(), "true".to_string()),
+        ("csv.delimiter".to_string(), ";".to_string()),
+    ])?;
+    config.validate()?;
+    
+    let mut pipeline = EtlPipeline::new(config.clone())?;
+    pipeline.execute_data_loading()?;
+    pipeline.create_pivot_tables()?;
+    
+    let entries = pipeline.database().execute_query("SELECT COUNT(*) FROM LANCAMENTOS_GERAIS")?;
+    println!("{} entries loaded into {}", entries[0][0], config.get_database_path().display());
+    Ok(())
+}
+
+/// One account sheet and the transaction types, as CSV files (`type_in = "csv"`)
+fn write_sample_input(work_dir: &Path) -> std::io::Result<()> {
+    let input_dir = work_dir.join("PDW");
+    std::fs::create_dir_all(&input_dir)?;
+    std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n\
+        05/01/2024;SALARIO;Empresa;5000,00;\n\
+        10/01/2024;ALUGUEL;Apartamento;;1500,00\n\
+        12/02/2024;MERCADO;Supermercado;;320,45\n")?;
+    std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\n\
+        SALARIO;Salário\nALUGUEL;Aluguel\nMERCADO;Mercado\n")
+}
\ No newline at end of file
@@ -0,0 +1,55 @@
+//! Clean, recategorize and filter entries in code with transform hooks, without
+//! touching the workbook or the categorization rules.
+//!
+//! ```text
+//! cargo run --example transform_hooks
+//! ```
+
+use pdw_rust::{EtlPipeline, PdwConfig, PdwError, TransformAction};
+
+fn main() -> Result<(), PdwError> {
+    let work_dir = std::env::temp_dir().join("pdw_example_hooks");
+    let input_dir = work_dir.join("PDW");
+    std::fs::create_dir_all(&input_dir)?;
+    std::fs::write(input_dir.join("Cartao.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n\
+        03/01/2024;ALIMENTACAO;  ifood *restaurante  ;;58,90\n\
+        08/01/2024;ALIMENTACAO;Padaria do Bairro;;12,50\n\
+        09/01/2024;ALIMENTACAO;Teste de cartao;;0,01\n\
+        20/01/2024;TRANSPORTE;uber *trip;;23,40\n")?;
+    std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\n\
+        ALIMENTACAO;Alimentação\nDELIVERY;Delivery\nTRANSPORTE;Transporte\n")?;
+
+    let mut config = PdwConfig::default();
+    config.directories.dir_in = work_dir.clone();
+    config.file_types.type_in = "csv".to_string();
+
+    let mut pipeline = EtlPipeline::in_memory(config)?;
+    // Card validation charges are not spending
+    pipeline.add_transform(Box::new(|entry| {
+        if entry.description.to_lowercase().starts_with("teste") {
+            TransformAction::Drop
+        } else {
+            TransformAction::Keep
+        }
+    }));
+    // Delivery apps get their own TIPO; descriptions go upper case with single spaces
+    pipeline.add_transform(Box::new(|entry| {
+        if entry.description.to_lowercase().contains("ifood") {
+            entry.transaction_type = "DELIVERY".to_string();
+        }
+        entry.description = entry.description.split_whitespace()
+            .map(|word| word.to_uppercase())
+            .collect::<Vec<_>>()
+            .join(" ");
+        TransformAction::Keep
+    }));
+    pipeline.execute_data_loading()?;
+
+    for row in pipeline.database().execute_query(
+        "SELECT Data, TIPO, DESCRICAO, Debito FROM LANCAMENTOS_GERAIS ORDER BY Data"
+    )? {
+        println!("{} {:<12} {:<20} {:>8.2}", row[0].as_str().unwrap_or_default(), row[1].as_str().unwrap_or_default(),
+                 row[2].as_str().unwrap_or_default(), row[3].as_f64().unwrap_or_default());
+    }
+    Ok(())
+}
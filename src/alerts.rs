@@ -0,0 +1,379 @@
+/*!
+# Alerts Module
+
+Threshold-based alert rules defined in the configuration (`[[alerts]]` tables)
+and evaluated after each load against the most recent month in the warehouse.
+Triggered alerts are logged, persisted to the alerts table for the report and
+forwarded to the notification channels.
+*/
+
+use crate::baselines;
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, PdwError};
+use crate::notifications::Notifier;
+use rusqlite::types::Value as SqlValue;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Alert rule definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+}
+
+/// Supported alert conditions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// Any single debit in the current month above the threshold
+    SingleDebit {
+        threshold: f64,
+        #[serde(default)]
+        tipo: Option<String>,
+    },
+    /// Total debits in the current month above the threshold
+    MonthlyTotal {
+        threshold: f64,
+        #[serde(default)]
+        tipo: Option<String>,
+    },
+    /// Current month debits above `factor` times the average of the previous `months`
+    MonthlyVsAverage {
+        factor: f64,
+        #[serde(default = "default_average_months")]
+        months: u32,
+        #[serde(default)]
+        tipo: Option<String>,
+    },
+    /// Current month debits above `factor` times the seasonal baseline (same month of the
+    /// previous years, `[baselines]`); never triggers without one
+    MonthlyVsSeasonal {
+        factor: f64,
+        #[serde(default)]
+        tipo: Option<String>,
+    },
+}
+
+/// Triggered alert
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub rule: String,
+    pub year_month: String,
+    pub value: f64,
+    pub limit: f64,
+    pub message: String,
+}
+
+/// Evaluates alert rules against the general entries table
+pub struct AlertEngine<'a> {
+    database: &'a DatabaseManager,
+    entries_table: &'a str,
+    baselines: Option<&'a str>,
+}
+
+fn default_average_months() -> u32 {
+    6
+}
+
+impl<'a> AlertEngine<'a> {
+    /// Create new alert engine
+    pub fn new(database: &'a DatabaseManager, entries_table: &'a str) -> Self {
+        Self { database, entries_table, baselines: None }
+    }
+    
+    /// Seasonal baselines table read by `monthly_vs_seasonal` rules
+    pub fn with_baselines(mut self, table: Option<&'a str>) -> Self {
+        self.baselines = table;
+        self
+    }
+    
+    /// Evaluate all rules against the most recent month
+    pub fn evaluate(&self, rules: &[AlertRule]) -> Result<Vec<Alert>, PdwError> {
+        let query = format!("SELECT MAX(AnoMes) FROM {}", self.entries_table);
+        let current_month = match self.database.execute_query(&query)?.first().and_then(|r| r.first()) {
+            Some(Value::String(month)) => month.clone(),
+            _ => return Ok(Vec::new()),
+        };
+        
+        let mut alerts = Vec::new();
+        for rule in rules {
+            alerts.extend(self.evaluate_rule(rule, &current_month)?);
+        }
+        
+        Ok(alerts)
+    }
+    
+    /// Evaluate a single rule for the given month
+    fn evaluate_rule(&self, rule: &AlertRule, month: &str) -> Result<Vec<Alert>, PdwError> {
+        match &rule.condition {
+            AlertCondition::SingleDebit { threshold, tipo } => {
+                let (filter, mut params) = type_filter(tipo);
+                let query = format!(
+                    "SELECT Data, TIPO, DESCRICAO, Debito FROM {} WHERE AnoMes = ? AND Debito > ?{} ORDER BY Debito DESC",
+                    self.entries_table, filter
+                );
+                params.insert(0, SqlValue::Text(month.to_string()));
+                params.insert(1, SqlValue::Real(*threshold));
+                
+                let rows = self.database.execute_query_with_params(&query, rusqlite::params_from_iter(params))?;
+                Ok(rows.iter()
+                    .map(|row| {
+                        let debit = row.get(3).and_then(Value::as_f64).unwrap_or(0.0);
+                        Alert {
+                            rule: rule.name.clone(),
+                            year_month: month.to_string(),
+                            value: debit,
+                            limit: *threshold,
+                            message: format!(
+                                "{} {} '{}' debit {:.2} exceeds {:.2}",
+                                text(row.first()), text(row.get(1)), text(row.get(2)), debit, threshold
+                            ),
+                        }
+                    })
+                    .collect())
+            }
+            AlertCondition::MonthlyTotal { threshold, tipo } => {
+                let total = self.monthly_totals(tipo, "AnoMes = ?", month, 1)?
+                    .first().map(|(_, v)| *v).unwrap_or(0.0);
+                
+                if total > *threshold {
+                    Ok(vec![Alert {
+                        rule: rule.name.clone(),
+                        year_month: month.to_string(),
+                        value: total,
+                        limit: *threshold,
+                        message: format!(
+                            "{} debits in {} total {:.2}, above {:.2}",
+                            tipo.as_deref().unwrap_or("All"), month, total, threshold
+                        ),
+                    }])
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+            AlertCondition::MonthlyVsAverage { factor, months, tipo } => {
+                let current = self.monthly_totals(tipo, "AnoMes = ?", month, 1)?
+                    .first().map(|(_, v)| *v).unwrap_or(0.0);
+                let history = self.monthly_totals(tipo, "AnoMes < ?", month, *months)?;
+                
+                if history.is_empty() {
+                    return Ok(Vec::new());
+                }
+                
+                let average = history.iter().map(|(_, v)| v).sum::<f64>() / history.len() as f64;
+                let limit = average * factor;
+                
+                if average > 0.0 && current > limit {
+                    Ok(vec![Alert {
+                        rule: rule.name.clone(),
+                        year_month: month.to_string(),
+                        value: current,
+                        limit,
+                        message: format!(
+                            "{} debits in {} total {:.2}, {:.1}x the {}-month average of {:.2}",
+                            tipo.as_deref().unwrap_or("All"), month, current, current / average, history.len(), average
+                        ),
+                    }])
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+            AlertCondition::MonthlyVsSeasonal { factor, tipo } => {
+                let Some(table) = self.baselines else {
+                    return Ok(Vec::new());
+                };
+                let expected = match baselines::expected(self.database, table, month, tipo.as_deref())? {
+                    Some(expected) if expected > 0.0 => expected,
+                    _ => return Ok(Vec::new()),
+                };
+                let current = self.monthly_totals(tipo, "AnoMes = ?", month, 1)?
+                    .first().map(|(_, v)| *v).unwrap_or(0.0);
+                let limit = expected * factor;
+                
+                if current > limit {
+                    Ok(vec![Alert {
+                        rule: rule.name.clone(),
+                        year_month: month.to_string(),
+                        value: current,
+                        limit,
+                        message: format!(
+                            "{} debits in {} total {:.2}, {:.1}x the seasonal baseline of {:.2}",
+                            tipo.as_deref().unwrap_or("All"), month, current, current / expected, expected
+                        ),
+                    }])
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+        }
+    }
+    
+    /// Debit totals per AnoMes matching a period condition, most recent first
+    fn monthly_totals(&self, tipo: &Option<String>, period: &str, month: &str, limit: u32) -> Result<Vec<(String, f64)>, PdwError> {
+        let (filter, mut params) = type_filter(tipo);
+        let query = format!(
+            "SELECT AnoMes, COALESCE(SUM(Debito), 0) FROM {} WHERE {}{} GROUP BY AnoMes ORDER BY AnoMes DESC LIMIT {}",
+            self.entries_table, period, filter, limit
+        );
+        params.insert(0, SqlValue::Text(month.to_string()));
+        
+        let rows = self.database.execute_query_with_params(&query, rusqlite::params_from_iter(params))?;
+        Ok(rows.iter()
+            .map(|row| (text(row.first()), row.get(1).and_then(Value::as_f64).unwrap_or(0.0)))
+            .collect())
+    }
+}
+
+/// Optional TIPO filter clause and its parameters
+fn type_filter(tipo: &Option<String>) -> (&'static str, Vec<SqlValue>) {
+    match tipo {
+        Some(t) => (" AND TIPO = ?", vec![SqlValue::Text(t.clone())]),
+        None => ("", Vec::new()),
+    }
+}
+
+/// Render an optional query value as text
+fn text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Persist triggered alerts, replacing the previous run's table
+pub fn write_alerts_table(database: &DatabaseManager, table_name: &str, alerts: &[Alert]) -> Result<(), PdwError> {
+    database.drop_table(table_name)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (Regra TEXT, AnoMes TEXT, Valor REAL, Limite REAL, Mensagem TEXT, Gerado TEXT)",
+        table_name
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let generated = crate::deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6)", table_name);
+    for alert in alerts {
+        database.connection().execute(&insert_query, rusqlite::params![
+            alert.rule,
+            alert.year_month,
+            alert.value,
+            alert.limit,
+            alert.message,
+            generated,
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table_name.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(())
+}
+
+/// Log alerts to the console and forward them to the notification channels
+pub fn dispatch_alerts(alerts: &[Alert], notifier: &Notifier) {
+    for alert in alerts {
+        tracing::warn!("ALERT [{}] {}", alert.rule, alert.message);
+    }
+    
+    if alerts.is_empty() || notifier.is_empty() {
+        return;
+    }
+    
+    let body = alerts.iter()
+        .map(|a| format!("- [{}] {}", a.rule, a.message))
+        .collect::<Vec<_>>()
+        .join("\n");
+    notifier.notify(&format!("PDW: {} alert(s) triggered", alerts.len()), &body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn setup_database(temp_dir: &TempDir) -> DatabaseManager {
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        
+        let rows = [
+            ("2024-01-10", "Mercado", 100.0, "2024/01"),
+            ("2024-02-10", "Mercado", 120.0, "2024/02"),
+            ("2024-03-10", "Mercado", 110.0, "2024/03"),
+            ("2024-04-10", "Mercado", 400.0, "2024/04"),
+            ("2024-04-12", "Casa", 6000.0, "2024/04"),
+        ];
+        for (date, tipo, debit, year_month) in rows {
+            db.connection().execute(
+                "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES (?1, ?2, 0, ?3, ?4)",
+                rusqlite::params![date, tipo, debit, year_month],
+            ).unwrap();
+        }
+        
+        db
+    }
+    
+    #[test]
+    fn test_rule_deserialization() {
+        let toml_content = r#"
+[[alerts]]
+name = "Mercado acima da media"
+kind = "monthly_vs_average"
+tipo = "Mercado"
+factor = 1.5
+
+[[alerts]]
+name = "Debito alto"
+kind = "single_debit"
+threshold = 5000.0
+"#;
+        #[derive(Deserialize)]
+        struct Wrapper { alerts: Vec<AlertRule> }
+        
+        let parsed: Wrapper = toml::from_str(toml_content).unwrap();
+        assert_eq!(parsed.alerts.len(), 2);
+        assert!(matches!(parsed.alerts[0].condition, AlertCondition::MonthlyVsAverage { months: 6, .. }));
+    }
+    
+    #[test]
+    fn test_alert_evaluation() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = setup_database(&temp_dir);
+        let engine = AlertEngine::new(&db, "LANCAMENTOS_GERAIS");
+        
+        let rules = vec![
+            AlertRule {
+                name: "average".to_string(),
+                condition: AlertCondition::MonthlyVsAverage { factor: 1.5, months: 6, tipo: Some("Mercado".to_string()) },
+            },
+            AlertRule {
+                name: "single".to_string(),
+                condition: AlertCondition::SingleDebit { threshold: 5000.0, tipo: None },
+            },
+            AlertRule {
+                name: "total".to_string(),
+                condition: AlertCondition::MonthlyTotal { threshold: 10000.0, tipo: None },
+            },
+            AlertRule {
+                name: "seasonal".to_string(),
+                condition: AlertCondition::MonthlyVsSeasonal { factor: 1.2, tipo: None },
+            },
+        ];
+        
+        let alerts = engine.evaluate(&rules).unwrap();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].rule, "average");
+        assert_eq!(alerts[0].value, 400.0);
+        assert_eq!(alerts[1].rule, "single");
+        
+        write_alerts_table(&db, "ALERTAS", &alerts).unwrap();
+        let stored = db.execute_query("SELECT COUNT(*) FROM ALERTAS").unwrap();
+        assert_eq!(stored[0][0], serde_json::json!(2));
+    }
+}
\ No newline at end of file
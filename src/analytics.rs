@@ -0,0 +1,594 @@
+/*!
+# Analytics Module
+
+Derived analyses over the general entries table. The benchmark comparison
+replays each month's net savings (credits minus debits) as if every month had
+been invested in CDI or SELIC: the invested balance earns the month's rate and
+then receives that month's savings. The monthly series comes from the Banco
+Central SGS API (`--features fx`) or from a CSV file.
+
+The share of income puts each month's expense groups (see the category groups
+module) against that month's income and total expenses, so rules of thumb such
+as "30% on housing" can be checked in the report.
+
+`[analytics.exclusions]` leaves TIPOs (listed directly or through their
+category groups) out of the metrics: transfers, investments or reimbursements
+are not savings or expenses. `all` applies to every metric and `savings`,
+`income_share`, `budgets`, `digest` and `metrics` (MQTT) add to it; the report's
+dictionary sheet lists what each metric leaves out.
+*/
+
+use crate::category_groups::{self, CategoryGroup, GroupKind};
+use crate::database::DatabaseManager;
+use crate::error::{ConfigError, DatabaseError, PdwError};
+use crate::fx;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+/// Benchmark rate series
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BenchmarkSeries {
+    /// CDI accumulated in the month (% a.m.)
+    #[default]
+    Cdi,
+    /// SELIC accumulated in the month (% a.m.)
+    Selic,
+}
+
+/// Savings benchmark settings (`[benchmark]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub series: BenchmarkSeries,
+    /// Monthly rates as `month;rate` lines instead of the BCB API
+    #[serde(default)]
+    pub csv_file: Option<PathBuf>,
+    #[serde(default = "default_benchmark_table")]
+    pub table: String,
+}
+
+/// Share of income settings (`[income_share]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncomeShareConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_income_share_table")]
+    pub table: String,
+}
+
+/// Analytics settings (`[analytics]`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalyticsConfig {
+    #[serde(default)]
+    pub exclusions: Exclusions,
+}
+
+/// Metrics TIPOs can be left out of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    /// Monthly savings of the CDI/SELIC comparison
+    Savings,
+    /// Income and expenses of the share of income
+    IncomeShare,
+    /// Budget vs actual lines
+    Budgets,
+    /// Totals, top TIPOs and largest debits of `pdw digest`
+    Digest,
+    /// Month spend published over MQTT
+    Metrics,
+}
+
+/// TIPOs left out of a metric, directly or by category group
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExcludedTypes {
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// Names of `[[category_groups]]` whose TIPOs are left out
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// `[analytics.exclusions]`: `all` applies to every metric, the others add to it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Exclusions {
+    #[serde(default)]
+    pub all: ExcludedTypes,
+    #[serde(default)]
+    pub savings: ExcludedTypes,
+    #[serde(default)]
+    pub income_share: ExcludedTypes,
+    #[serde(default)]
+    pub budgets: ExcludedTypes,
+    #[serde(default)]
+    pub digest: ExcludedTypes,
+    #[serde(default)]
+    pub metrics: ExcludedTypes,
+}
+
+/// One expense group of one month against income and total expenses
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncomeShareRow {
+    pub year_month: String,
+    pub group: String,
+    pub expense: f64,
+    pub income: f64,
+    /// Fraction of the month's income; None in months without income
+    pub of_income: Option<f64>,
+    /// Fraction of the month's expenses
+    pub of_expenses: Option<f64>,
+}
+
+/// One month of the savings comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkRow {
+    pub year_month: String,
+    pub savings: f64,
+    pub accumulated: f64,
+    pub rate: f64,
+    pub benchmark: f64,
+}
+
+fn default_benchmark_table() -> String {
+    "COMPARATIVO_CDI".to_string()
+}
+
+fn default_income_share_table() -> String {
+    "PERCENTUAL_RENDA".to_string()
+}
+
+impl Default for IncomeShareConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            table: default_income_share_table(),
+        }
+    }
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            series: BenchmarkSeries::default(),
+            csv_file: None,
+            table: default_benchmark_table(),
+        }
+    }
+}
+
+impl Measure {
+    pub const ALL: [Measure; 5] = [Measure::Savings, Measure::IncomeShare, Measure::Budgets, Measure::Digest, Measure::Metrics];
+    
+    /// Name in the report's dictionary sheet
+    pub fn label(&self) -> &'static str {
+        match self {
+            Measure::Savings => "Poupança (comparativo CDI/SELIC)",
+            Measure::IncomeShare => "Percentual da renda",
+            Measure::Budgets => "Orçamento vs real",
+            Measure::Digest => "Resumo (pdw digest)",
+            Measure::Metrics => "Métricas MQTT",
+        }
+    }
+}
+
+impl Exclusions {
+    fn of(&self, measure: Measure) -> &ExcludedTypes {
+        match measure {
+            Measure::Savings => &self.savings,
+            Measure::IncomeShare => &self.income_share,
+            Measure::Budgets => &self.budgets,
+            Measure::Digest => &self.digest,
+            Measure::Metrics => &self.metrics,
+        }
+    }
+    
+    /// TIPOs (trimmed, upper case) left out of a metric, groups expanded
+    pub fn types(&self, measure: Measure, groups: &[CategoryGroup]) -> Vec<String> {
+        let mut excluded = BTreeSet::new();
+        for listed in [&self.all, self.of(measure)] {
+            excluded.extend(listed.types.iter().map(|tipo| tipo.trim().to_uppercase()));
+            let group_types = groups.iter()
+                .filter(|group| listed.groups.iter().any(|name| name.trim().eq_ignore_ascii_case(group.name.trim())))
+                .flat_map(|group| &group.types);
+            excluded.extend(group_types.map(|tipo| tipo.trim().to_uppercase()));
+        }
+        excluded.into_iter().filter(|tipo| !tipo.is_empty()).collect()
+    }
+    
+    /// Reject group names missing from `[[category_groups]]`
+    pub fn check(&self, groups: &[CategoryGroup]) -> Result<(), PdwError> {
+        let listed = [&self.all].into_iter().chain(Measure::ALL.iter().map(|measure| self.of(*measure)));
+        for name in listed.flat_map(|excluded| &excluded.groups) {
+            if !groups.iter().any(|group| group.name.trim().eq_ignore_ascii_case(name.trim())) {
+                return Err(ConfigError::InvalidFormat {
+                    message: format!("analytics.exclusions lists group '{}', which is not in [[category_groups]]", name),
+                }.into());
+            }
+        }
+        Ok(())
+    }
+    
+    /// Each metric that leaves TIPOs out, with the condition applied, for the dictionary sheet
+    pub fn describe(&self, groups: &[CategoryGroup]) -> Vec<(String, String)> {
+        Measure::ALL.iter()
+            .filter_map(|measure| {
+                let excluded = self.types(*measure, groups);
+                (!excluded.is_empty()).then(|| (measure.label().to_string(), exclusion_condition("TIPO", &excluded)))
+            })
+            .collect()
+    }
+}
+
+/// Condition leaving the given TIPOs out of `column`; always true without any
+pub fn exclusion_condition(column: &str, excluded: &[String]) -> String {
+    if excluded.is_empty() {
+        return "1 = 1".to_string();
+    }
+    let list = excluded.iter().map(|tipo| format!("'{}'", tipo.replace('\'', "''"))).collect::<Vec<_>>().join(", ");
+    format!("UPPER(TRIM(COALESCE({}, ''))) NOT IN ({})", column, list)
+}
+
+impl BenchmarkSeries {
+    /// SGS series code at the Banco Central
+    pub fn sgs_code(&self) -> u32 {
+        match self {
+            BenchmarkSeries::Cdi => 4391,
+            BenchmarkSeries::Selic => 4390,
+        }
+    }
+    
+    /// Column label in the report table
+    pub fn label(&self) -> &'static str {
+        match self {
+            BenchmarkSeries::Cdi => "CDI",
+            BenchmarkSeries::Selic => "SELIC",
+        }
+    }
+}
+
+/// Net savings (credits minus debits) per AnoMes, oldest first, leaving the `excluded` TIPOs out
+pub fn monthly_savings(database: &DatabaseManager, entries_table: &str, excluded: &[String]) -> Result<Vec<(String, f64)>, PdwError> {
+    let query = format!(
+        "SELECT AnoMes, SUM(COALESCE(Credito, 0) - COALESCE(Debito, 0)) FROM {}
+         WHERE AnoMes IS NOT NULL AND {} GROUP BY AnoMes ORDER BY AnoMes",
+        entries_table, exclusion_condition("TIPO", excluded)
+    );
+    
+    Ok(database.execute_query(&query)?
+        .iter()
+        .filter_map(|row| match row.first() {
+            Some(Value::String(month)) => Some((month.clone(), row.get(1).and_then(Value::as_f64).unwrap_or(0.0))),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Load the monthly rates for the configured source, keyed by AnoMes
+pub fn load_series(config: &BenchmarkConfig, first_month: &str, last_month: &str) -> Result<BTreeMap<String, f64>, PdwError> {
+    if let Some(path) = &config.csv_file {
+        let content = std::fs::read_to_string(path)?;
+        return parse_series_csv(&content);
+    }
+    
+    let url = format!(
+        "https://api.bcb.gov.br/dados/serie/bcdata.sgs.{}/dados?formato=json&dataInicial={}&dataFinal={}",
+        config.series.sgs_code(),
+        month_start(first_month)?.format("%d/%m/%Y"),
+        month_start(last_month)?.format("%d/%m/%Y"),
+    );
+    parse_sgs(&fx::fetch_json(&url)?)
+}
+
+/// Rates from an SGS JSON response (`[{"data": "01/01/2024", "valor": "0.97"}]`)
+pub fn parse_sgs(response: &Value) -> Result<BTreeMap<String, f64>, PdwError> {
+    let entries = response.as_array()
+        .ok_or_else(|| PdwError::ExchangeRate("Unexpected SGS response".to_string()))?;
+    
+    Ok(entries.iter()
+        .filter_map(|entry| {
+            let date = NaiveDate::parse_from_str(entry.get("data")?.as_str()?, "%d/%m/%Y").ok()?;
+            let rate = parse_rate(entry.get("valor")?.as_str()?)?;
+            Some((date.format("%Y/%m").to_string(), rate))
+        })
+        .collect())
+}
+
+/// Rates from `month;rate` lines; months as dd/mm/yyyy, yyyy-mm or yyyy/mm, decimal comma allowed
+pub fn parse_series_csv(content: &str) -> Result<BTreeMap<String, f64>, PdwError> {
+    let mut series = BTreeMap::new();
+    
+    for (line_number, line) in content.lines().enumerate() {
+        let mut fields = line.split([';', '\t']).map(|f| f.trim().trim_matches('"'));
+        let (Some(month), Some(rate)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        
+        match (normalize_month(month), parse_rate(rate)) {
+            (Some(month), Some(rate)) => {
+                series.insert(month, rate);
+            }
+            // Header line
+            _ if line_number == 0 => {}
+            _ => tracing::warn!("Benchmark CSV line {} ignored: {}", line_number + 1, line),
+        }
+    }
+    
+    Ok(series)
+}
+
+/// Replay the monthly savings against the benchmark rates
+pub fn savings_vs_benchmark(savings: &[(String, f64)], rates: &BTreeMap<String, f64>) -> Vec<BenchmarkRow> {
+    let mut accumulated = 0.0;
+    let mut benchmark = 0.0;
+    let mut missing = 0;
+    
+    let rows = savings.iter()
+        .map(|(month, saved)| {
+            let rate = rates.get(month).copied().unwrap_or_else(|| {
+                missing += 1;
+                0.0
+            });
+            
+            // Last month's balance earns this month's rate, then the month's savings arrive
+            benchmark = benchmark * (1.0 + rate / 100.0) + saved;
+            accumulated += saved;
+            
+            BenchmarkRow { year_month: month.clone(), savings: *saved, accumulated, rate, benchmark }
+        })
+        .collect();
+    
+    if missing > 0 {
+        tracing::warn!("Benchmark rate missing for {} month(s); counted as 0%", missing);
+    }
+    
+    rows
+}
+
+/// Store the comparison, replacing the previous run's table
+pub fn write_benchmark_table(database: &DatabaseManager, table: &str, series: BenchmarkSeries,
+                             rows: &[BenchmarkRow]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let label = series.label();
+    let create_query = format!(
+        "CREATE TABLE {} (AnoMes TEXT, Poupanca REAL, Acumulado REAL, Taxa_{label} REAL, \
+         Acumulado_{label} REAL, Diferenca REAL)",
+        table
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6)", table);
+    for row in rows {
+        database.connection().execute(&insert_query, rusqlite::params![
+            row.year_month,
+            round_cents(row.savings),
+            round_cents(row.accumulated),
+            row.rate,
+            round_cents(row.benchmark),
+            round_cents(row.benchmark - row.accumulated),
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(rows.len())
+}
+
+/// Expense groups of every month with their share of income and of expenses,
+/// the `excluded` TIPOs counting as neither
+pub fn income_shares(database: &DatabaseManager, entries_table: &str, groups: &[CategoryGroup],
+                     excluded: &[String]) -> Result<Vec<IncomeShareRow>, PdwError> {
+    let months: Vec<String> = database.execute_query(&format!(
+        "SELECT DISTINCT AnoMes FROM {} WHERE AnoMes IS NOT NULL ORDER BY AnoMes",
+        entries_table
+    ))?
+        .iter()
+        .filter_map(|row| row.first().and_then(Value::as_str).map(str::to_string))
+        .collect();
+    let mut totals = category_groups::type_totals(database, entries_table, &months)?;
+    totals.retain(|tipo, _| !excluded.contains(&tipo.trim().to_uppercase()));
+    let lines = category_groups::group_lines(groups, &totals, &months);
+    
+    let mut rows = Vec::new();
+    for (idx, month) in months.iter().enumerate() {
+        let side = |kind: GroupKind| -> f64 {
+            lines.iter().filter(|line| line.kind == kind).map(|line| line.amounts[idx]).sum()
+        };
+        let income = round_cents(side(GroupKind::Income));
+        let expenses = side(GroupKind::Expense);
+        let share = |amount: f64, whole: f64| (whole > 0.0).then(|| (amount / whole * 10_000.0).round() / 10_000.0);
+        
+        rows.extend(lines.iter()
+            .filter(|line| line.kind == GroupKind::Expense && line.amounts[idx] != 0.0)
+            .map(|line| IncomeShareRow {
+                year_month: month.clone(),
+                group: line.name.clone(),
+                expense: line.amounts[idx],
+                income,
+                of_income: share(line.amounts[idx], income),
+                of_expenses: share(line.amounts[idx], expenses),
+            }));
+    }
+    Ok(rows)
+}
+
+/// Store the shares, replacing the previous run's table
+pub fn write_income_share_table(database: &DatabaseManager, table: &str, rows: &[IncomeShareRow]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (AnoMes TEXT, Grupo TEXT, Despesa REAL, Receita REAL, PercentualRenda REAL, PercentualDespesas REAL)",
+        table
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6)", table);
+    for row in rows {
+        database.connection().execute(&insert_query, rusqlite::params![
+            row.year_month,
+            row.group,
+            row.expense,
+            row.income,
+            row.of_income,
+            row.of_expenses,
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(rows.len())
+}
+
+/// First day of an AnoMes ("2024/01")
+fn month_start(year_month: &str) -> Result<NaiveDate, PdwError> {
+    NaiveDate::parse_from_str(&format!("{}/01", year_month), "%Y/%m/%d")
+        .map_err(|e| PdwError::ExchangeRate(format!("Invalid AnoMes {}: {}", year_month, e)))
+}
+
+/// AnoMes from dd/mm/yyyy, yyyy-mm(-dd) or yyyy/mm
+fn normalize_month(text: &str) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%d/%m/%Y") {
+        return Some(date.format("%Y/%m").to_string());
+    }
+    
+    let mut parts = text.split(['-', '/']);
+    let year: u32 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    (year >= 1000 && (1..=12).contains(&month)).then(|| format!("{:04}/{:02}", year, month))
+}
+
+/// Percentage with a decimal point or comma
+fn parse_rate(text: &str) -> Option<f64> {
+    text.trim().trim_end_matches('%').replace(',', ".").parse().ok()
+}
+
+fn round_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_series_parsing() {
+        let csv = "data;valor\n01/01/2024;0,97\n2024-02;0.80\n2024/03;\"0,83\"\nlixo;x\n";
+        let series = parse_series_csv(csv).unwrap();
+        assert_eq!(series.get("2024/01"), Some(&0.97));
+        assert_eq!(series.get("2024/02"), Some(&0.80));
+        assert_eq!(series.get("2024/03"), Some(&0.83));
+        assert_eq!(series.len(), 3);
+        
+        let sgs = json!([{"data": "01/01/2024", "valor": "0.97"}, {"data": "01/02/2024", "valor": "0.80"}]);
+        assert_eq!(parse_sgs(&sgs).unwrap(), series.into_iter().take(2).collect());
+    }
+    
+    #[test]
+    fn test_savings_vs_benchmark() {
+        let savings = vec![
+            ("2024/01".to_string(), 1000.0),
+            ("2024/02".to_string(), 500.0),
+            ("2024/03".to_string(), -200.0),
+        ];
+        let rates: BTreeMap<String, f64> = [("2024/02".to_string(), 1.0), ("2024/03".to_string(), 1.0)].into();
+        
+        let rows = savings_vs_benchmark(&savings, &rates);
+        assert_eq!(rows[0].benchmark, 1000.0);
+        assert_eq!(rows[1].benchmark, 1510.0);
+        assert!((rows[2].benchmark - 1325.1).abs() < 1e-9);
+        assert_eq!(rows[2].accumulated, 1300.0);
+    }
+    
+    #[test]
+    fn test_benchmark_table() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, Credito, Debito, AnoMes) VALUES ('2024-01-05', 1000, 400, '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, Credito, Debito, AnoMes) VALUES ('2024-02-05', 1000, 0, '2024/02');"
+        ).unwrap();
+        
+        let savings = monthly_savings(&db, "LANCAMENTOS_GERAIS", &[]).unwrap();
+        assert_eq!(savings, vec![("2024/01".to_string(), 600.0), ("2024/02".to_string(), 1000.0)]);
+        
+        let rates = parse_series_csv("2024/02;0,5").unwrap();
+        let rows = savings_vs_benchmark(&savings, &rates);
+        write_benchmark_table(&db, "COMPARATIVO_CDI", BenchmarkSeries::Cdi, &rows).unwrap();
+        
+        let result = db.execute_query("SELECT Acumulado_CDI, Diferenca FROM COMPARATIVO_CDI WHERE AnoMes = '2024/02'").unwrap();
+        assert_eq!(result, vec![vec![json!(1603.0), json!(3.0)]]);
+    }
+    
+    #[test]
+    fn test_income_share_table() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-01-05', 'SALARIO', 5000, 0, '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-01-10', 'ALUGUEL', 0, 1500, '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-01-12', 'MERCADO', 0, 500, '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-02-10', 'ALUGUEL', 0, 1500, '2024/02');"
+        ).unwrap();
+        let groups = vec![
+            CategoryGroup { name: "Salário".into(), kind: GroupKind::Income, types: vec!["SALARIO".into()] },
+            CategoryGroup { name: "Moradia".into(), kind: GroupKind::Expense, types: vec!["ALUGUEL".into()] },
+        ];
+        
+        let rows = income_shares(&db, "LANCAMENTOS_GERAIS", &groups, &[]).unwrap();
+        assert_eq!(write_income_share_table(&db, "PERCENTUAL_RENDA", &rows).unwrap(), 3);
+        
+        let result = db.execute_query("SELECT AnoMes, Grupo, Receita, PercentualRenda, PercentualDespesas FROM PERCENTUAL_RENDA").unwrap();
+        assert_eq!(result, vec![
+            vec![json!("2024/01"), json!("Moradia"), json!(5000.0), json!(0.3), json!(0.75)],
+            vec![json!("2024/01"), json!("Outras Despesas"), json!(5000.0), json!(0.1), json!(0.25)],
+            // No income that month
+            vec![json!("2024/02"), json!("Moradia"), json!(0.0), json!(null), json!(1.0)],
+        ]);
+    }
+    
+    #[test]
+    fn test_exclusions() {
+        let groups = vec![
+            CategoryGroup { name: "Investimentos".into(), kind: GroupKind::Transfer, types: vec!["APLICACAO".into(), " resgate".into()] },
+        ];
+        let config: AnalyticsConfig = toml::from_str(
+            "[exclusions.all]\ntypes = [\"trf \"]\n[exclusions.savings]\ngroups = [\"investimentos\"]"
+        ).unwrap();
+        let exclusions = &config.exclusions;
+        assert_eq!(exclusions.types(Measure::Savings, &groups), vec!["APLICACAO", "RESGATE", "TRF"]);
+        assert_eq!(exclusions.types(Measure::Digest, &groups), vec!["TRF"]);
+        assert!(exclusions.check(&groups).is_ok());
+        assert!(exclusions.check(&[]).is_err());
+        assert_eq!(exclusions.describe(&groups)[1], ("Percentual da renda".to_string(), "UPPER(TRIM(COALESCE(TIPO, ''))) NOT IN ('TRF')".to_string()));
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-01-05', 'SALARIO', 5000, 0, '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-01-06', 'Aplicacao', 0, 2000, '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-01-20', 'MERCADO', 0, 800, '2024/01');"
+        ).unwrap();
+        let savings = monthly_savings(&db, "LANCAMENTOS_GERAIS", &exclusions.types(Measure::Savings, &groups)).unwrap();
+        assert_eq!(savings, vec![("2024/01".to_string(), 4200.0)]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,272 @@
+/*!
+# Arrow Handoff Module
+
+Exposes query results and processed transactions as Arrow `RecordBatch`es so
+embedders can pass warehouse data to Polars, DataFusion or any Arrow consumer
+without going through `serde_json::Value`. Batches can also be written as
+Arrow IPC files (Feather v2), or as Parquet files with the `parquet` feature.
+Enabled with the `arrow` cargo feature.
+*/
+
+use crate::database::{DatabaseManager, ProcessedTransaction};
+use crate::error::{DatabaseError, PdwError, ReportError};
+use crate::expression::DerivedValue;
+use crate::money;
+use arrow::array::{ArrayRef, Date32Array, Float64Array, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDate;
+use rusqlite::types::Value as SqlValue;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Convert processed transactions into a batch with the LANCAMENTOS_GERAIS layout
+pub fn transactions_to_batch(transactions: &[ProcessedTransaction]) -> Result<RecordBatch, PdwError> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    let text = |f: fn(&ProcessedTransaction) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(transactions.iter().map(f)))
+    };
+    
+    let mut fields = vec![
+        Field::new("Data", DataType::Date32, false),
+        Field::new("DIA_SEMANA", DataType::Utf8, false),
+        Field::new("TIPO", DataType::Utf8, false),
+        Field::new("DESCRICAO", DataType::Utf8, false),
+        Field::new("Credito", DataType::Float64, false),
+        Field::new("Debito", DataType::Float64, false),
+        Field::new("Mes", DataType::Utf8, false),
+        Field::new("Ano", DataType::Utf8, false),
+        Field::new("MES_EXTENSO", DataType::Utf8, false),
+        Field::new("AnoMes", DataType::Utf8, false),
+        Field::new("Origem", DataType::Utf8, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(Date32Array::from_iter_values(
+            transactions.iter().map(|t| (t.date - epoch).num_days() as i32)
+        )),
+        text(|t| &t.day_of_week),
+        text(|t| &t.transaction_type),
+        text(|t| &t.description),
+        Arc::new(Float64Array::from_iter_values(transactions.iter().map(|t| money::to_f64(t.credit)))),
+        Arc::new(Float64Array::from_iter_values(transactions.iter().map(|t| money::to_f64(t.debit)))),
+        text(|t| &t.month),
+        text(|t| &t.year),
+        text(|t| &t.month_name),
+        text(|t| &t.year_month),
+        text(|t| &t.origin),
+    ];
+    
+    // Derived columns share the same order in every transaction
+    if let Some(first) = transactions.first() {
+        for (index, (name, _)) in first.derived.iter().enumerate() {
+            let values: Vec<Option<&DerivedValue>> = transactions.iter()
+                .map(|t| t.derived.get(index).map(|(_, value)| value))
+                .collect();
+            let (data_type, column) = derived_column(&values);
+            fields.push(Field::new(name, data_type, true));
+            columns.push(column);
+        }
+    }
+    
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(arrow_error)
+}
+
+/// Run a query and return its rows as a single batch
+pub fn query_to_batch(database: &DatabaseManager, sql: &str) -> Result<RecordBatch, PdwError> {
+    let sql_error = |e: rusqlite::Error| DatabaseError::SqlExecution {
+        query: sql.to_string(),
+        reason: e.to_string(),
+    };
+    
+    let mut stmt = database.connection().prepare(sql).map_err(sql_error)?;
+    let names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    
+    let mut values: Vec<Vec<SqlValue>> = vec![Vec::new(); names.len()];
+    let mut rows = stmt.query([]).map_err(sql_error)?;
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        for (i, column) in values.iter_mut().enumerate() {
+            column.push(row.get(i).map_err(sql_error)?);
+        }
+    }
+    
+    let mut fields = Vec::with_capacity(names.len());
+    let mut columns = Vec::with_capacity(names.len());
+    for (name, column) in names.iter().zip(&values) {
+        let (data_type, array) = sql_column(column);
+        fields.push(Field::new(name, data_type, true));
+        columns.push(array);
+    }
+    
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(arrow_error)
+}
+
+/// Write a batch as an Arrow IPC file (Feather v2)
+pub fn write_ipc_file(batch: &RecordBatch, path: &Path) -> Result<(), PdwError> {
+    let file = File::create(path)?;
+    let mut writer = FileWriter::try_new(file, &batch.schema()).map_err(arrow_error)?;
+    writer.write(batch).map_err(arrow_error)?;
+    writer.finish().map_err(arrow_error)?;
+    Ok(())
+}
+
+/// Write a batch as a Snappy-compressed Parquet file
+#[cfg(feature = "parquet")]
+pub fn write_parquet_file(batch: &RecordBatch, path: &Path) -> Result<(), PdwError> {
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::metadata::KeyValue;
+    use parquet::file::properties::WriterProperties;
+    use crate::export_schema::{EXPORT_SCHEMA_VERSION, PARQUET_VERSION_KEY};
+    
+    let parquet_error = |e: parquet::errors::ParquetError| PdwError::from(ReportError::OutputGeneration {
+        format: "parquet".to_string(),
+        reason: format!("{}: {}", path.display(), e),
+    });
+    
+    let version = KeyValue::new(PARQUET_VERSION_KEY.to_string(), EXPORT_SCHEMA_VERSION.to_string());
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_key_value_metadata(Some(vec![version]))
+        .build();
+    let file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties)).map_err(parquet_error)?;
+    writer.write(batch).map_err(parquet_error)?;
+    writer.close().map_err(parquet_error)?;
+    Ok(())
+}
+
+/// Pick the narrowest Arrow type holding every SQLite value of a column
+fn sql_column(values: &[SqlValue]) -> (DataType, ArrayRef) {
+    let all = |f: fn(&SqlValue) -> bool| values.iter().all(|v| matches!(v, SqlValue::Null) || f(v));
+    
+    if all(|v| matches!(v, SqlValue::Integer(_))) {
+        let array: Int64Array = values.iter()
+            .map(|v| match v {
+                SqlValue::Integer(i) => Some(*i),
+                _ => None,
+            })
+            .collect();
+        (DataType::Int64, Arc::new(array))
+    } else if all(|v| matches!(v, SqlValue::Integer(_) | SqlValue::Real(_))) {
+        let array: Float64Array = values.iter()
+            .map(|v| match v {
+                SqlValue::Integer(i) => Some(*i as f64),
+                SqlValue::Real(f) => Some(*f),
+                _ => None,
+            })
+            .collect();
+        (DataType::Float64, Arc::new(array))
+    } else {
+        let array: StringArray = values.iter()
+            .map(|v| match v {
+                SqlValue::Null => None,
+                SqlValue::Integer(i) => Some(i.to_string()),
+                SqlValue::Real(f) => Some(f.to_string()),
+                SqlValue::Text(s) => Some(s.clone()),
+                SqlValue::Blob(_) => Some("BLOB".to_string()),
+            })
+            .collect();
+        (DataType::Utf8, Arc::new(array))
+    }
+}
+
+/// Float64 when every derived value is numeric, Utf8 otherwise
+fn derived_column(values: &[Option<&DerivedValue>]) -> (DataType, ArrayRef) {
+    let numeric = values.iter().all(|v| !matches!(v, Some(DerivedValue::Text(_))));
+    
+    if numeric {
+        let array: Float64Array = values.iter()
+            .map(|v| match v {
+                Some(DerivedValue::Number(n)) => Some(*n),
+                _ => None,
+            })
+            .collect();
+        (DataType::Float64, Arc::new(array))
+    } else {
+        let array: StringArray = values.iter()
+            .map(|v| match v {
+                Some(DerivedValue::Number(n)) => Some(n.to_string()),
+                Some(DerivedValue::Text(s)) => Some(s.clone()),
+                _ => None,
+            })
+            .collect();
+        (DataType::Utf8, Arc::new(array))
+    }
+}
+
+fn arrow_error(e: arrow::error::ArrowError) -> PdwError {
+    ReportError::OutputGeneration {
+        format: "arrow".to_string(),
+        reason: e.to_string(),
+    }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Array;
+    use arrow::ipc::reader::FileReader;
+    use tempfile::TempDir;
+    
+    fn transaction(tipo: &str, debit: f64, derived: DerivedValue) -> ProcessedTransaction {
+        ProcessedTransaction {
+            date: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            day_of_week: "Segunda-feira".to_string(),
+            transaction_type: tipo.to_string(),
+            description: "Test".to_string(),
+            credit: money::Decimal::ZERO,
+            debit: money::from_f64(debit).unwrap(),
+            month: "01".to_string(),
+            year: "2024".to_string(),
+            month_name: "01-Janeiro".to_string(),
+            year_month: "2024/01".to_string(),
+            origin: "Conta".to_string(),
+            derived: vec![("Liquido".to_string(), derived)],
+            currency: None,
+        }
+    }
+    
+    #[test]
+    fn test_transactions_to_batch() {
+        let batch = transactions_to_batch(&[
+            transaction("ALM", 10.0, DerivedValue::Number(-10.0)),
+            transaction("LAZ", 5.0, DerivedValue::Null),
+        ]).unwrap();
+        
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.num_columns(), 12);
+        assert_eq!(batch.schema().field(0).data_type(), &DataType::Date32);
+        
+        let liquido = batch.column(11).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(liquido.value(0), -10.0);
+        assert!(liquido.is_null(1));
+        
+        let dates = batch.column(0).as_any().downcast_ref::<Date32Array>().unwrap();
+        assert_eq!(dates.value_as_date(0), NaiveDate::from_ymd_opt(2024, 1, 15));
+    }
+    
+    #[test]
+    fn test_query_to_batch_and_ipc() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.connection().execute_batch(
+            "CREATE TABLE t (n INTEGER, x REAL, s TEXT);
+             INSERT INTO t VALUES (1, 1.5, 'a'), (NULL, 2, 3);"
+        ).unwrap();
+        
+        let batch = query_to_batch(&db, "SELECT n, x, s FROM t").unwrap();
+        let types: Vec<DataType> = batch.schema().fields().iter().map(|f| f.data_type().clone()).collect();
+        assert_eq!(types, vec![DataType::Int64, DataType::Float64, DataType::Utf8]);
+        
+        let s = batch.column(2).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(s.value(1), "3");
+        
+        let path = temp_dir.path().join("t.arrow");
+        write_ipc_file(&batch, &path).unwrap();
+        let reader = FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let read: Vec<RecordBatch> = reader.map(|b| b.unwrap()).collect();
+        assert_eq!(read[0], batch);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,190 @@
+/*!
+# Baselines Module
+
+Seasonal baselines per TIPO: what each month usually costs, taken as the average
+of the same month over the previous years (`[baselines]`, three years by
+default). They are refreshed into the BASELINES table after each load, one row
+per TIPO and AnoMes with the month's debits (Realizado), how many previous years
+the average covers (Anos), the average itself (Esperado) and the share of it
+already spent (Percentual). Months with no entries at all in a year are left out
+of its average; a month with entries but none of a TIPO counts as zero for it.
+
+The budget vs actual table gains the seasonal expectation (Sazonal), the
+`monthly_vs_seasonal` alert compares the latest month with it and the monthly
+digest shows it next to the month's debits.
+*/
+
+use crate::database::DatabaseManager;
+use crate::error::{ConfigError, DatabaseError, PdwError};
+use crate::money::MoneyMode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Seasonal baseline settings (`[baselines]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Table the baselines are stored in
+    #[serde(default = "default_table")]
+    pub table: String,
+    /// Previous years averaged
+    #[serde(default = "default_years")]
+    pub years: u32,
+    /// Previous years with data a month needs to get a baseline
+    #[serde(default = "default_min_years")]
+    pub min_years: u32,
+}
+
+fn default_table() -> String {
+    "BASELINES".to_string()
+}
+
+fn default_years() -> u32 {
+    3
+}
+
+fn default_min_years() -> u32 {
+    1
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            table: default_table(),
+            years: default_years(),
+            min_years: default_min_years(),
+        }
+    }
+}
+
+impl BaselineConfig {
+    /// Reject year counts that cannot produce a baseline
+    pub fn check(&self) -> Result<(), PdwError> {
+        if self.years == 0 || self.min_years == 0 || self.min_years > self.years {
+            return Err(ConfigError::InvalidFormat {
+                message: format!(
+                    "baselines.min_years ({}) must be between 1 and baselines.years ({})",
+                    self.min_years, self.years
+                ),
+            }.into());
+        }
+        Ok(())
+    }
+}
+
+/// Rebuild the baselines table from the general entries; returns the rows written
+pub fn write_baselines(database: &DatabaseManager, entries_table: &str, config: &BaselineConfig) -> Result<usize, PdwError> {
+    let money = database.money_mode();
+    if money == MoneyMode::Decimal {
+        database.add_money_columns(entries_table)?;
+    }
+    
+    database.drop_table(&config.table)?;
+    let query = format!(
+        "CREATE TABLE {table} AS
+         WITH mensal AS (
+             SELECT UPPER(TRIM(TIPO)) AS TIPO, AnoMes, {debit} AS Realizado
+             FROM {entries}
+             WHERE AnoMes IS NOT NULL AND TRIM(COALESCE(TIPO, '')) <> ''
+             GROUP BY UPPER(TRIM(TIPO)), AnoMes
+         ),
+         periodos AS (
+             SELECT DISTINCT AnoMes, CAST(substr(AnoMes, 1, 4) AS INTEGER) AS Ano, substr(AnoMes, 6) AS Mes
+             FROM {entries} WHERE AnoMes IS NOT NULL
+         ),
+         grade AS (
+             SELECT t.TIPO, p.AnoMes, p.Ano, p.Mes, COALESCE(m.Realizado, 0) AS Realizado
+             FROM (SELECT DISTINCT TIPO FROM mensal) t
+             CROSS JOIN periodos p
+             LEFT JOIN mensal m ON m.TIPO = t.TIPO AND m.AnoMes = p.AnoMes
+         )
+         SELECT g.TIPO, g.AnoMes, g.Mes,
+                ROUND(g.Realizado, 2) as Realizado,
+                COUNT(h.Ano) as Anos,
+                ROUND(AVG(h.Realizado), 2) as Esperado,
+                CASE WHEN AVG(h.Realizado) > 0 THEN ROUND(g.Realizado * 100.0 / AVG(h.Realizado), 1) END as Percentual
+         FROM grade g
+         JOIN grade h ON h.TIPO = g.TIPO AND h.Mes = g.Mes AND h.Ano BETWEEN g.Ano - {years} AND g.Ano - 1
+         GROUP BY g.TIPO, g.AnoMes
+         HAVING COUNT(h.Ano) >= {min_years}
+         ORDER BY g.AnoMes DESC, g.TIPO",
+        table = config.table,
+        entries = entries_table,
+        debit = money.sum_sql("Debito", None),
+        years = config.years,
+        min_years = config.min_years,
+    );
+    database.connection().execute(&query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let count = database.execute_query(&format!("SELECT COUNT(*) FROM {}", config.table))?
+        .first()
+        .and_then(|row| row.first())
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as usize;
+    Ok(count)
+}
+
+/// Seasonal expectation of an AnoMes ("2024/03"), of one TIPO or of every TIPO;
+/// None when the table is missing or the month has no baseline
+pub fn expected(database: &DatabaseManager, table: &str, year_month: &str, tipo: Option<&str>) -> Result<Option<f64>, PdwError> {
+    if database.table_columns(table)?.is_empty() {
+        return Ok(None);
+    }
+    let query = format!(
+        "SELECT ROUND(SUM(Esperado), 2) FROM {} WHERE AnoMes = ?1 AND (?2 IS NULL OR TIPO = UPPER(TRIM(?2)))",
+        table
+    );
+    let rows = database.execute_query_with_params(&query, rusqlite::params![year_month, tipo])?;
+    Ok(rows.first().and_then(|row| row.first()).and_then(Value::as_f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_same_month_average() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        let rows = [
+            ("2021/12", "Presentes", 100.0),
+            ("2022/12", "PRESENTES ", 300.0),
+            ("2022/12", "Mercado", 50.0),
+            ("2023/06", "Mercado", 40.0),
+            ("2023/12", "Mercado", 70.0),
+            ("2024/12", "Presentes", 150.0),
+            ("2024/12", "Mercado", 60.0),
+        ];
+        for (year_month, tipo, debit) in rows {
+            db.connection().execute(
+                "INSERT INTO LANCAMENTOS_GERAIS (TIPO, Credito, Debito, AnoMes) VALUES (?1, 0, ?2, ?3)",
+                rusqlite::params![tipo, debit, year_month],
+            ).unwrap();
+        }
+        
+        let config: BaselineConfig = toml::from_str("enabled = true").unwrap();
+        assert_eq!(write_baselines(&db, "LANCAMENTOS_GERAIS", &config).unwrap(), 6);
+        // December 2023 had no gifts, so their 2024 baseline averages 100, 300 and 0
+        assert_eq!(
+            db.execute_query("SELECT TIPO, Realizado, Anos, Esperado, Percentual FROM BASELINES WHERE AnoMes = '2024/12'").unwrap(),
+            vec![
+                vec![json!("MERCADO"), json!(60.0), json!(3), json!(40.0), json!(150.0)],
+                vec![json!("PRESENTES"), json!(150.0), json!(3), json!(133.33), json!(112.5)],
+            ]
+        );
+        assert_eq!(expected(&db, "BASELINES", "2024/12", None).unwrap(), Some(173.33));
+        assert_eq!(expected(&db, "BASELINES", "2024/12", Some("presentes")).unwrap(), Some(133.33));
+        assert_eq!(expected(&db, "BASELINES", "2023/06", None).unwrap(), None);
+        
+        let two_years = BaselineConfig { years: 1, min_years: 2, ..config };
+        assert!(two_years.check().is_err());
+    }
+}
\ No newline at end of file
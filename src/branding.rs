@@ -0,0 +1,220 @@
+/*!
+# Branding Module
+
+Report branding (`[branding]`), so reports handed to an accountant need no
+touch-up:
+
+- a summary sheet opening the report workbook: the title, the logo and the
+  generation info, then the period, entries and origins of the warehouse
+- the title as page header and the footer (with the page number) on every
+  sheet of the report workbooks, shown when printed or exported to PDF
+- the same title and footer on the PDF statements and the HTML digest
+
+The footer takes `{title}`, `{version}` and `{generated_at}`. The logo is a
+PNG, JPEG, GIF or BMP file relative to dir_in; a missing one is skipped with a
+warning.
+*/
+
+use crate::database::DatabaseManager;
+use crate::deterministic;
+use crate::error::{PdwError, ReportError};
+use rust_xlsxwriter::{Format, Image, Workbook};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Characters of the title and footer kept in sheet headers (Excel holds 255 with the codes)
+const MAX_HEADER_TEXT: usize = 100;
+
+/// Box the logo is scaled into, in pixels
+const LOGO_WIDTH: u32 = 240;
+const LOGO_HEIGHT: u32 = 120;
+
+/// Report branding settings (`[branding]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrandingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Title of the summary sheet, sheet headers, statements and digest
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Image on the summary sheet, relative to dir_in
+    #[serde(default)]
+    pub logo: Option<PathBuf>,
+    /// Footer of every sheet and page
+    #[serde(default = "default_footer")]
+    pub footer: String,
+    /// Name of the summary sheet
+    #[serde(default = "default_summary_sheet")]
+    pub summary_sheet: String,
+}
+
+fn default_footer() -> String {
+    "Gerado por PDW {version} em {generated_at}".to_string()
+}
+
+fn default_summary_sheet() -> String {
+    "Sumário".to_string()
+}
+
+impl Default for BrandingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title: None,
+            logo: None,
+            footer: default_footer(),
+            summary_sheet: default_summary_sheet(),
+        }
+    }
+}
+
+impl BrandingConfig {
+    /// Title to show, None while disabled
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref().map(str::trim).filter(|title| self.enabled && !title.is_empty())
+    }
+    
+    /// Footer with its placeholders replaced, None while disabled
+    pub fn footer_text(&self) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        Some(self.footer
+            .replace("{title}", self.title().unwrap_or_default())
+            .replace("{version}", env!("CARGO_PKG_VERSION"))
+            .replace("{generated_at}", &deterministic::now().format("%d/%m/%Y %H:%M").to_string()))
+    }
+    
+    /// Add the summary sheet as the next sheet of the workbook (the first, before any query)
+    pub fn add_summary_sheet(&self, workbook: &mut Workbook, database: &DatabaseManager,
+                             entries_table: &str, dir_in: &Path) -> Result<(), PdwError> {
+        if !self.enabled {
+            return Ok(());
+        }
+        
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(&self.summary_sheet).map_err(ReportError::ExcelWriter)?;
+        let title_format = Format::new().set_bold().set_font_size(18);
+        let label_format = Format::new().set_bold();
+        
+        worksheet.write_string_with_format(0, 0, self.title().unwrap_or("Personal Data Warehouse"), &title_format)
+            .map_err(ReportError::ExcelWriter)?;
+        worksheet.write_string(1, 0, self.footer_text().unwrap_or_default())
+            .map_err(ReportError::ExcelWriter)?;
+        
+        let mut facts = Vec::new();
+        if !database.table_columns(entries_table)?.is_empty() {
+            let rows = database.execute_query(&format!(
+                "SELECT MIN(date(Data)), MAX(date(Data)), COUNT(*), COUNT(DISTINCT TRIM(Origem)) FROM {}",
+                entries_table
+            ))?;
+            if let Some(row) = rows.first() {
+                let text = |index: usize| match row.get(index) {
+                    Some(Value::String(text)) => text.clone(),
+                    Some(Value::Null) | None => "-".to_string(),
+                    Some(value) => value.to_string(),
+                };
+                facts.push(("Período", format!("{} a {}", text(0), text(1))));
+                facts.push(("Lançamentos", text(2)));
+                facts.push(("Origens", text(3)));
+            }
+        }
+        for (index, (label, value)) in facts.iter().enumerate() {
+            let row = 3 + index as u32;
+            worksheet.write_string_with_format(row, 0, *label, &label_format).map_err(ReportError::ExcelWriter)?;
+            worksheet.write_string(row, 1, value).map_err(ReportError::ExcelWriter)?;
+        }
+        worksheet.set_column_width(0, 16.0).map_err(ReportError::ExcelWriter)?;
+        worksheet.set_column_width(1, 28.0).map_err(ReportError::ExcelWriter)?;
+        
+        if let Some(logo) = &self.logo {
+            let path = dir_in.join(logo);
+            match Image::new(&path) {
+                Ok(mut image) => {
+                    image.set_scale_to_size(LOGO_WIDTH, LOGO_HEIGHT, true);
+                    worksheet.insert_image(0, 3, &image).map_err(ReportError::ExcelWriter)?;
+                }
+                Err(e) => tracing::warn!("Logo {} not added to the summary sheet: {}", path.display(), e),
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Put the title header and the footer on every sheet of a workbook
+    pub fn apply_to_workbook(&self, workbook: &mut Workbook) {
+        let Some(footer) = self.footer_text() else {
+            return;
+        };
+        let footer = format!("&L{}&RPágina &P de &N", header_text(&footer));
+        let header = self.title().map(|title| format!("&C{}", header_text(title)));
+        
+        for worksheet in workbook.worksheets_mut() {
+            worksheet.set_footer(&footer);
+            if let Some(header) = &header {
+                worksheet.set_header(header);
+            }
+        }
+    }
+}
+
+/// Text of a sheet header or footer, shortened, with '&' (a control code there) doubled
+fn header_text(text: &str) -> String {
+    text.chars().take(MAX_HEADER_TEXT).collect::<String>().replace('&', "&&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PdwConfig;
+    use crate::reporting::{QueryConfig, ReportGenerator};
+    use calamine::{DataType, Reader};
+    use std::io::Read;
+    
+    #[test]
+    fn test_branded_report_workbook() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let logo_path = temp_dir.path().join("logo.png");
+        let mut encoder = png::Encoder::new(std::fs::File::create(&logo_path).unwrap(), 2, 2);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&[0, 255, 255, 0]).unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.directories.dir_out = temp_dir.path().to_path_buf();
+        config.settings.run_dynamic_report = false;
+        config.branding = toml::from_str(
+            "enabled = true\ntitle = \"Família Silva & Cia\"\nlogo = \"logo.png\"\nfooter = \"{title} - PDW {version}\""
+        ).unwrap();
+        assert_eq!(config.branding.footer_text(), Some(format!("Família Silva & Cia - PDW {}", env!("CARGO_PKG_VERSION"))));
+        assert_eq!(BrandingConfig::default().footer_text(), None);
+        
+        let queries: QueryConfig = serde_yaml::from_str(
+            "queries_padrao:\n  - sql: \"SELECT Origem, Debito FROM LANCAMENTOS_GERAIS\"\n    sheet_name: \"Debitos\"\n"
+        ).unwrap();
+        let database = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        database.create_tables().unwrap();
+        database.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-01-05', 'ALM', 0, 10, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-02-20', 'LAZ', 0, 40, 'Cartao');"
+        ).unwrap();
+        let report_path = config.get_report_path();
+        ReportGenerator::new(database, config).with_queries(queries).generate_excel_reports().unwrap();
+        
+        let mut report: calamine::Xlsx<_> = calamine::open_workbook(&report_path).unwrap();
+        assert_eq!(report.sheet_names()[..2].to_vec(), vec!["Sumário", "Debitos"]);
+        let summary = report.worksheet_range("Sumário").unwrap().unwrap();
+        assert_eq!(summary.get_value((0, 0)), Some(&DataType::String("Família Silva & Cia".to_string())));
+        assert_eq!(summary.get_value((3, 1)), Some(&DataType::String("2024-01-05 a 2024-02-20".to_string())));
+        
+        // Header and footer on every sheet, the logo on the summary
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&report_path).unwrap()).unwrap();
+        let mut sheet = String::new();
+        archive.by_name("xl/worksheets/sheet2.xml").unwrap().read_to_string(&mut sheet).unwrap();
+        assert!(sheet.contains("<oddHeader>&amp;CFamília Silva &amp;&amp; Cia</oddHeader>"));
+        assert!(sheet.contains("<oddFooter>&amp;LFamília Silva &amp;&amp; Cia - PDW"));
+        assert!(archive.by_name("xl/media/image1.png").is_ok());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,236 @@
+/*!
+# Budgets Module
+
+Monthly spending limits per TIPO compared with what was actually spent. Limits
+come from `[budgets.limits]` and from a BUDGETS sheet of the input workbook
+(TIPO and LIMITE columns, the sheet wins over the configuration); they are
+stored in the BUDGETS table at load time. The report phase crosses every AnoMes
+of the general entries with every budgeted TIPO into ORCAMENTO_VS_REAL, with the
+budgeted amount, the actual debits, the remaining amount and the share used,
+plus the seasonal expectation (Sazonal) when `[baselines]` is enabled. TIPOs
+left out of the budgets by `[analytics.exclusions]` get no lines.
+*/
+
+use crate::analytics;
+use crate::collation;
+use crate::database::DatabaseManager;
+use crate::error::{ConfigError, DatabaseError, PdwError};
+use crate::money::MoneyMode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Budget settings (`[budgets]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Input sheet with the limits, read when present
+    #[serde(default = "default_budgets_name")]
+    pub sheet: String,
+    /// Table the limits are stored in
+    #[serde(default = "default_budgets_name")]
+    pub table: String,
+    /// Budget vs actual table built by the report phase
+    #[serde(default = "default_report_table")]
+    pub report_table: String,
+    /// Monthly limit by TIPO
+    #[serde(default)]
+    pub limits: BTreeMap<String, f64>,
+}
+
+fn default_budgets_name() -> String {
+    "BUDGETS".to_string()
+}
+
+fn default_report_table() -> String {
+    "ORCAMENTO_VS_REAL".to_string()
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sheet: default_budgets_name(),
+            table: default_budgets_name(),
+            report_table: default_report_table(),
+            limits: BTreeMap::new(),
+        }
+    }
+}
+
+impl BudgetConfig {
+    /// Limits of the configuration with the rows of a BUDGETS sheet (header first) over them
+    pub fn limits_with_sheet(&self, rows: &[Vec<String>]) -> Result<BTreeMap<String, f64>, PdwError> {
+        let mut limits: BTreeMap<String, f64> = self.limits.iter()
+            .map(|(tipo, limit)| (tipo.trim().to_uppercase(), *limit))
+            .collect();
+        let Some((header, rows)) = rows.split_first() else {
+            return Ok(limits);
+        };
+        
+        let column = |names: &[&str], field: &str| header.iter()
+            .position(|h| names.contains(&collation::fold(h.trim()).as_str()))
+            .ok_or_else(|| ConfigError::MissingField {
+                field: format!("{} column of the {} sheet", field, self.sheet),
+            });
+        let tipo_column = column(&["tipo"], "TIPO")?;
+        let limit_column = column(&["limite", "limit", "orcamento", "budget"], "LIMITE")?;
+        
+        for row in rows {
+            let tipo = row.get(tipo_column).map(|t| t.trim()).unwrap_or_default();
+            if tipo.is_empty() {
+                continue;
+            }
+            let text = row.get(limit_column).map(|l| l.trim()).unwrap_or_default();
+            let limit = parse_limit(text).ok_or_else(|| ConfigError::InvalidFormat {
+                message: format!("Invalid budget '{}' for {} in the {} sheet", text, tipo, self.sheet),
+            })?;
+            limits.insert(tipo.to_uppercase(), limit);
+        }
+        
+        Ok(limits)
+    }
+}
+
+/// Limit typed as a number, with a decimal point or a Brazilian decimal comma
+pub(crate) fn parse_limit(text: &str) -> Option<f64> {
+    let text = text.trim_start_matches("R$").trim();
+    let normalized = if text.contains(',') {
+        text.replace('.', "").replace(',', ".")
+    } else {
+        text.to_string()
+    };
+    normalized.parse().ok()
+}
+
+/// Store the limits, replacing the previous load's table
+pub fn write_budget_table(database: &DatabaseManager, table: &str, limits: &BTreeMap<String, f64>) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!("CREATE TABLE {} (TIPO TEXT PRIMARY KEY, Limite REAL)", table);
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2)", table);
+    for (tipo, limit) in limits {
+        database.connection().execute(&insert_query, rusqlite::params![tipo, limit])
+            .map_err(|e| DatabaseError::DataInsertion {
+                table: table.to_string(),
+                reason: e.to_string(),
+            })?;
+    }
+    
+    Ok(limits.len())
+}
+
+/// Build the budget vs actual table: one row per AnoMes and budgeted TIPO
+pub fn write_budget_report(database: &DatabaseManager, entries_table: &str,
+                           config: &BudgetConfig, baselines: Option<&str>, excluded: &[String]) -> Result<usize, PdwError> {
+    let money = database.money_mode();
+    if money == MoneyMode::Decimal {
+        database.add_money_columns(entries_table)?;
+    }
+    let debit = money.sum_sql("e.Debito", None);
+    // Same-month average of the previous years, when the baselines were computed
+    let (seasonal, seasonal_join) = match baselines {
+        Some(table) if !database.table_columns(table)?.is_empty() => (
+            ",\n                MAX(s.Esperado) as Sazonal".to_string(),
+            format!("\n         LEFT JOIN {} s ON s.AnoMes = m.AnoMes AND s.TIPO = b.TIPO", table),
+        ),
+        _ => (String::new(), String::new()),
+    };
+    
+    database.drop_table(&config.report_table)?;
+    let query = format!(
+        "CREATE TABLE {report} AS
+         SELECT m.AnoMes, b.TIPO,
+                ROUND(b.Limite, 2) as Orcado,
+                ROUND(COALESCE({debit}, 0), 2) as Realizado,
+                ROUND(b.Limite - COALESCE({debit}, 0), 2) as Diferenca,
+                CASE WHEN b.Limite > 0 THEN ROUND(COALESCE({debit}, 0) * 100.0 / b.Limite, 1) END as PercentualUsado{seasonal}
+         FROM (SELECT DISTINCT AnoMes FROM {entries} WHERE AnoMes IS NOT NULL) m
+         CROSS JOIN {budgets} b
+         LEFT JOIN {entries} e ON e.AnoMes = m.AnoMes AND UPPER(TRIM(e.TIPO)) = b.TIPO{seasonal_join}
+         WHERE {excluded}
+         GROUP BY m.AnoMes, b.TIPO
+         ORDER BY m.AnoMes DESC, b.TIPO",
+        report = config.report_table,
+        budgets = config.table,
+        entries = entries_table,
+        debit = debit,
+        seasonal = seasonal,
+        seasonal_join = seasonal_join,
+        excluded = analytics::exclusion_condition("b.TIPO", excluded),
+    );
+    database.connection().execute(&query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let count = database.execute_query(&format!("SELECT COUNT(*) FROM {}", config.report_table))?
+        .first()
+        .and_then(|row| row.first())
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0) as usize;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn rows(cells: &[&[&str]]) -> Vec<Vec<String>> {
+        cells.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect()
+    }
+    
+    #[test]
+    fn test_limits_with_sheet() {
+        let config: BudgetConfig = toml::from_str(
+            "enabled = true\n[limits]\nmercado = 800\nLAZER = 300"
+        ).unwrap();
+        assert_eq!(config.report_table, "ORCAMENTO_VS_REAL");
+        
+        let sheet = rows(&[&["Tipo", "Limite"], &["MERCADO", "1.200,50"], &["SAUDE", "R$ 450"], &["", "10"]]);
+        let limits = config.limits_with_sheet(&sheet).unwrap();
+        assert_eq!(limits, BTreeMap::from([
+            ("LAZER".to_string(), 300.0),
+            ("MERCADO".to_string(), 1200.5),
+            ("SAUDE".to_string(), 450.0),
+        ]));
+        
+        assert!(config.limits_with_sheet(&rows(&[&["TIPO"], &["MERCADO"]])).is_err());
+        assert!(config.limits_with_sheet(&rows(&[&["TIPO", "LIMITE"], &["MERCADO", "muito"]])).is_err());
+    }
+    
+    #[test]
+    fn test_budget_vs_actual() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-05', 'MERCADO', 0, 500, '2024/01', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-20', 'Mercado', 0, 400, '2024/01', 'Cartão');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes, Origem) VALUES ('2024-02-03', 'SALARIO', 5000, 0, '2024/02', 'Conta');"
+        ).unwrap();
+        
+        let config = BudgetConfig::default();
+        let limits = BTreeMap::from([("MERCADO".to_string(), 800.0), ("LAZER".to_string(), 0.0)]);
+        assert_eq!(write_budget_table(&db, &config.table, &limits).unwrap(), 2);
+        assert_eq!(write_budget_report(&db, "LANCAMENTOS_GERAIS", &config, None, &[]).unwrap(), 4);
+        
+        let result = db.execute_query(
+            "SELECT AnoMes, TIPO, Orcado, Realizado, Diferenca, PercentualUsado FROM ORCAMENTO_VS_REAL"
+        ).unwrap();
+        assert_eq!(result, vec![
+            vec![json!("2024/02"), json!("LAZER"), json!(0.0), json!(0.0), json!(0.0), json!(null)],
+            vec![json!("2024/02"), json!("MERCADO"), json!(800.0), json!(0.0), json!(800.0), json!(0.0)],
+            vec![json!("2024/01"), json!("LAZER"), json!(0.0), json!(0.0), json!(0.0), json!(null)],
+            vec![json!("2024/01"), json!("MERCADO"), json!(800.0), json!(900.0), json!(-100.0), json!(112.5)],
+        ]);
+    }
+}
\ No newline at end of file
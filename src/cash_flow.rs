@@ -0,0 +1,228 @@
+/*!
+# Cash Flow Module
+
+Rolling cash-flow statement of the trailing months (`[cash_flow]`, 12 by
+default, ending at the latest AnoMes loaded): the income groups and their total,
+the expense groups and their total, the month's result and the result
+accumulated since the first month shown. Lines come from the category groups
+(see the category groups module); the statement is stored in the FLUXO_CAIXA
+table, one column per month plus a total, and written to the "Fluxo de Caixa"
+report sheet with the subtotal lines in bold.
+*/
+
+use crate::category_groups::{self, CategoryGroup, GroupKind, GroupLine};
+use crate::database::{quote_identifier, DatabaseManager};
+use crate::error::{DatabaseError, PdwError};
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Column holding the line names
+pub const LINE_COLUMN: &str = "Linha";
+
+/// Column summing the months of each line
+pub const TOTAL_COLUMN: &str = "Total";
+
+/// Section header of the income groups
+pub const INCOME_HEADER: &str = "Receitas";
+
+/// Section header of the expense groups
+pub const EXPENSES_HEADER: &str = "Despesas";
+
+pub const TOTAL_INCOME: &str = "Total Receitas";
+pub const TOTAL_EXPENSES: &str = "Total Despesas";
+
+/// Income minus expenses
+pub const NET: &str = "Resultado";
+
+/// Running sum of the result
+pub const CUMULATIVE: &str = "Resultado Acumulado";
+
+/// Cash-flow statement settings (`[cash_flow]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashFlowConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Months shown, ending at the latest loaded month
+    #[serde(default = "default_months")]
+    pub months: u32,
+    #[serde(default = "default_cash_flow_table")]
+    pub table: String,
+}
+
+fn default_months() -> u32 {
+    12
+}
+
+fn default_cash_flow_table() -> String {
+    "FLUXO_CAIXA".to_string()
+}
+
+impl Default for CashFlowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            months: default_months(),
+            table: default_cash_flow_table(),
+        }
+    }
+}
+
+/// Lines written in bold in the report sheet
+pub fn total_rows() -> Vec<String> {
+    [INCOME_HEADER, EXPENSES_HEADER, TOTAL_INCOME, TOTAL_EXPENSES, NET, CUMULATIVE]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// The `count` AnoMes months ending at the latest one loaded, oldest first; empty without entries
+pub fn trailing_months(database: &DatabaseManager, entries_table: &str, count: u32) -> Result<Vec<String>, PdwError> {
+    let latest = database.execute_query(&format!("SELECT MAX(AnoMes) FROM {} WHERE AnoMes IS NOT NULL", entries_table))?
+        .first()
+        .and_then(|row| row.first())
+        .and_then(Value::as_str)
+        .and_then(|year_month| NaiveDate::parse_from_str(&format!("{}/01", year_month.trim()), "%Y/%m/%d").ok());
+    let Some(latest) = latest else {
+        return Ok(Vec::new());
+    };
+    
+    Ok((0..count).rev()
+        .filter_map(|back| latest.checked_sub_months(Months::new(back)))
+        .map(|month| format!("{}/{:02}", month.year(), month.month()))
+        .collect())
+}
+
+/// Statement rows: line name, one amount per month, total
+pub fn statement_rows(lines: &[GroupLine], months: usize) -> Vec<Vec<Value>> {
+    let sum = |kind: GroupKind| -> Vec<f64> {
+        (0..months)
+            .map(|idx| lines.iter().filter(|line| line.kind == kind).map(|line| line.amounts[idx]).sum())
+            .collect()
+    };
+    let income = sum(GroupKind::Income);
+    let expenses = sum(GroupKind::Expense);
+    let net: Vec<f64> = income.iter().zip(&expenses).map(|(income, expense)| income - expense).collect();
+    let cumulative: Vec<f64> = net.iter()
+        .scan(0.0, |running, value| {
+            *running += value;
+            Some(*running)
+        })
+        .collect();
+    
+    let amounts_row = |name: &str, amounts: &[f64], total: f64| {
+        let mut row = vec![Value::from(name)];
+        row.extend(amounts.iter().chain([total].iter()).map(|amount| Value::from(round(*amount))));
+        row
+    };
+    let header_row = |name: &str| {
+        let mut row = vec![Value::from(name)];
+        row.extend(std::iter::repeat_n(Value::Null, months + 1));
+        row
+    };
+    
+    let mut rows = vec![header_row(INCOME_HEADER)];
+    rows.extend(lines.iter().filter(|line| line.kind == GroupKind::Income)
+        .map(|line| amounts_row(&line.name, &line.amounts, line.total())));
+    rows.push(amounts_row(TOTAL_INCOME, &income, income.iter().sum()));
+    rows.push(header_row(EXPENSES_HEADER));
+    rows.extend(lines.iter().filter(|line| line.kind == GroupKind::Expense)
+        .map(|line| amounts_row(&line.name, &line.amounts, line.total())));
+    rows.push(amounts_row(TOTAL_EXPENSES, &expenses, expenses.iter().sum()));
+    rows.push(amounts_row(NET, &net, net.iter().sum()));
+    rows.push(amounts_row(CUMULATIVE, &cumulative, cumulative.last().copied().unwrap_or(0.0)));
+    rows
+}
+
+/// Build the statement of the trailing months from the general entries
+pub fn build_statement(database: &DatabaseManager, entries_table: &str, groups: &[CategoryGroup],
+                       config: &CashFlowConfig) -> Result<(Vec<String>, Vec<Vec<Value>>), PdwError> {
+    let months = trailing_months(database, entries_table, config.months)?;
+    let totals = category_groups::type_totals(database, entries_table, &months)?;
+    let lines = category_groups::group_lines(groups, &totals, &months);
+    let rows = statement_rows(&lines, months.len());
+    Ok((months, rows))
+}
+
+/// Store the statement, replacing the previous run's table
+pub fn write_cash_flow_table(database: &DatabaseManager, table: &str, months: &[String],
+                             rows: &[Vec<Value>]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let month_columns: String = months.iter().map(|month| format!(", {} REAL", quote_identifier(month))).collect();
+    let create_query = format!(
+        "CREATE TABLE {} ({} TEXT{}, {} REAL)",
+        table, LINE_COLUMN, month_columns, TOTAL_COLUMN
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let placeholders = vec!["?"; months.len() + 2].join(", ");
+    let insert_query = format!("INSERT INTO {} VALUES ({})", table, placeholders);
+    for row in rows {
+        let values: Vec<rusqlite::types::Value> = row.iter()
+            .map(|value| match value {
+                Value::String(text) => rusqlite::types::Value::Text(text.clone()),
+                Value::Number(number) => rusqlite::types::Value::Real(number.as_f64().unwrap_or_default()),
+                _ => rusqlite::types::Value::Null,
+            })
+            .collect();
+        database.connection().execute(&insert_query, rusqlite::params_from_iter(values))
+            .map_err(|e| DatabaseError::DataInsertion {
+                table: table.to_string(),
+                reason: e.to_string(),
+            })?;
+    }
+    
+    Ok(rows.len())
+}
+
+fn round(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_cash_flow_statement() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2023-12-05', 'SALARIO', 'Empresa', 4000, 0, '2023/12', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-05', 'SALARIO', 'Empresa', 5000, 0, '2024/01', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-10', 'ALUGUEL', 'Apto', 0, 1500, '2024/01', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-02-10', 'ALUGUEL', 'Apto', 0, 1500, '2024/02', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-02-12', 'LAZER', 'Cinema', 0, 60, '2024/02', 'Cartão');"
+        ).unwrap();
+        let groups = vec![
+            CategoryGroup { name: "Salário".into(), kind: GroupKind::Income, types: vec!["SALARIO".into()] },
+            CategoryGroup { name: "Moradia".into(), kind: GroupKind::Expense, types: vec!["ALUGUEL".into()] },
+        ];
+        let config = CashFlowConfig { enabled: true, months: 2, ..CashFlowConfig::default() };
+        
+        let (months, rows) = build_statement(&db, "LANCAMENTOS_GERAIS", &groups, &config).unwrap();
+        assert_eq!(months, vec!["2024/01", "2024/02"]);
+        assert_eq!(write_cash_flow_table(&db, "FLUXO_CAIXA", &months, &rows).unwrap(), 9);
+        
+        let result = db.execute_query("SELECT * FROM FLUXO_CAIXA").unwrap();
+        assert_eq!(result, vec![
+            vec![json!(INCOME_HEADER), json!(null), json!(null), json!(null)],
+            vec![json!("Salário"), json!(5000.0), json!(0.0), json!(5000.0)],
+            vec![json!(TOTAL_INCOME), json!(5000.0), json!(0.0), json!(5000.0)],
+            vec![json!(EXPENSES_HEADER), json!(null), json!(null), json!(null)],
+            vec![json!("Moradia"), json!(1500.0), json!(1500.0), json!(3000.0)],
+            vec![json!("Outras Despesas"), json!(0.0), json!(60.0), json!(60.0)],
+            vec![json!(TOTAL_EXPENSES), json!(1500.0), json!(1560.0), json!(3060.0)],
+            vec![json!(NET), json!(3500.0), json!(-1560.0), json!(1940.0)],
+            vec![json!(CUMULATIVE), json!(3500.0), json!(1940.0), json!(1940.0)],
+        ]);
+        assert_eq!(db.query_columns("SELECT * FROM FLUXO_CAIXA").unwrap()[1], "2024/01");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,280 @@
+/*!
+# Categorization Module
+
+Rules that fill in the TIPO of an entry from its description, so that rows do
+not need a hand-typed type in the workbook. A rule matches the DESCRICAO by
+substring (ignoring case and accents) or by regular expression and may set the
+TIPO, a category and a tag. Rules come from `[[categorization.rules]]`, a YAML
+rules file and the CATEGORIAS sheet of the input, in that order; the first
+matching rule wins. By default a rule only fills an empty TIPO, with
+`override_types` it replaces the typed one too.
+*/
+
+use crate::collation;
+use crate::error::{ConfigError, PdwError};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Categorization settings (`[categorization]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategorizationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Replace a typed TIPO when a rule matches, not only fill empty ones
+    #[serde(default)]
+    pub override_types: bool,
+    #[serde(default)]
+    pub rules: Vec<CategoryRule>,
+    /// YAML list of rules, relative to the input directory
+    #[serde(default)]
+    pub rules_file: Option<PathBuf>,
+    /// Input sheet with rules (PADRAO, TIPO, CATEGORIA, TAG, REGEX columns)
+    #[serde(default = "default_rules_sheet")]
+    pub sheet: String,
+    #[serde(default = "default_category_column")]
+    pub category_column: String,
+    #[serde(default = "default_tag_column")]
+    pub tag_column: String,
+}
+
+/// Description pattern and what it assigns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoryRule {
+    pub pattern: String,
+    /// Treat the pattern as a (case-insensitive) regular expression
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub tipo: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+/// Compiled rules, first match wins
+#[derive(Debug, Clone)]
+pub struct Categorizer {
+    rules: Vec<(Matcher, CategoryRule)>,
+    override_types: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    /// Folded (accent-free, lowercase) substring
+    Substring(String),
+    Regex(Regex),
+}
+
+fn default_rules_sheet() -> String {
+    "CATEGORIAS".to_string()
+}
+
+fn default_category_column() -> String {
+    "Categoria".to_string()
+}
+
+fn default_tag_column() -> String {
+    "Tag".to_string()
+}
+
+impl Default for CategorizationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            override_types: false,
+            rules: Vec::new(),
+            rules_file: None,
+            sheet: default_rules_sheet(),
+            category_column: default_category_column(),
+            tag_column: default_tag_column(),
+        }
+    }
+}
+
+impl Categorizer {
+    /// Compile the configured rules and those of the rules file, or None when disabled
+    pub fn from_config(config: &CategorizationConfig, rules_file: Option<&Path>) -> Result<Option<Self>, PdwError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        
+        let mut categorizer = Self { rules: Vec::new(), override_types: config.override_types };
+        for rule in &config.rules {
+            categorizer.add_rule(rule.clone())?;
+        }
+        
+        if let Some(path) = rules_file {
+            let content = std::fs::read_to_string(path)?;
+            let rules: Vec<CategoryRule> = serde_yaml::from_str(&content)
+                .map_err(|e| ConfigError::InvalidFormat {
+                    message: format!("Invalid categorization rules file {}: {}", path.display(), e),
+                })?;
+            for rule in rules {
+                categorizer.add_rule(rule)?;
+            }
+        }
+        
+        Ok(Some(categorizer))
+    }
+    
+    /// Compile and append a rule
+    pub fn add_rule(&mut self, rule: CategoryRule) -> Result<(), PdwError> {
+        let matcher = if rule.regex {
+            let regex = RegexBuilder::new(&rule.pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| ConfigError::InvalidFormat {
+                    message: format!("Invalid categorization regex '{}': {}", rule.pattern, e),
+                })?;
+            Matcher::Regex(regex)
+        } else {
+            Matcher::Substring(collation::fold(rule.pattern.trim()))
+        };
+        
+        self.rules.push((matcher, rule));
+        Ok(())
+    }
+    
+    /// Append the rules of a sheet (header row first); returns how many were added
+    pub fn add_sheet_rules(&mut self, rows: &[Vec<String>]) -> Result<usize, PdwError> {
+        let Some((header, rows)) = rows.split_first() else {
+            return Ok(0);
+        };
+        
+        let column = |names: &[&str]| header.iter().position(|h| names.contains(&collation::fold(h.trim()).as_str()));
+        let pattern_column = column(&["padrao", "pattern"]).ok_or_else(|| ConfigError::MissingField {
+            field: "PADRAO column of the categorization sheet".to_string(),
+        })?;
+        let (tipo, category, tag, regex) = (
+            column(&["tipo"]),
+            column(&["categoria", "category"]),
+            column(&["tag"]),
+            column(&["regex"]),
+        );
+        
+        let mut added = 0;
+        for row in rows {
+            let cell = |index: Option<usize>| index
+                .and_then(|i| row.get(i))
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+            let Some(pattern) = cell(Some(pattern_column)) else {
+                continue;
+            };
+            
+            self.add_rule(CategoryRule {
+                pattern,
+                regex: cell(regex).is_some(),
+                tipo: cell(tipo),
+                category: cell(category),
+                tag: cell(tag),
+            })?;
+            added += 1;
+        }
+        
+        Ok(added)
+    }
+    
+    /// First rule matching a description
+    pub fn categorize(&self, description: &str) -> Option<&CategoryRule> {
+        let folded = collation::fold(description);
+        self.rules.iter()
+            .find(|(matcher, _)| match matcher {
+                Matcher::Substring(pattern) => folded.contains(pattern.as_str()),
+                Matcher::Regex(regex) => regex.is_match(description),
+            })
+            .map(|(_, rule)| rule)
+    }
+    
+    /// TIPO of an entry once its matching rule is applied
+    pub fn apply_type(&self, typed: String, rule: Option<&CategoryRule>) -> String {
+        match rule.and_then(|r| r.tipo.as_deref()) {
+            Some(tipo) if typed.is_empty() || self.override_types => tipo.trim().to_string(),
+            _ => typed,
+        }
+    }
+    
+    /// Number of compiled rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+    
+    /// Whether no rule is defined
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn rule(pattern: &str, regex: bool, tipo: &str) -> CategoryRule {
+        CategoryRule {
+            pattern: pattern.to_string(),
+            regex,
+            tipo: Some(tipo.to_string()),
+            category: None,
+            tag: None,
+        }
+    }
+    
+    #[test]
+    fn test_substring_and_regex_rules() {
+        let mut config = CategorizationConfig { enabled: true, ..Default::default() };
+        config.rules.push(rule("Pão de Açúcar", false, "MERCADO"));
+        config.rules.push(rule(r"^UBER\s*\*?\s*TRIP", true, "TRANSPORTE"));
+        config.rules.push(rule("uber", false, "DELIVERY"));
+        
+        let categorizer = Categorizer::from_config(&config, None).unwrap().unwrap();
+        assert_eq!(categorizer.categorize("PAO DE ACUCAR 1234").unwrap().tipo.as_deref(), Some("MERCADO"));
+        assert_eq!(categorizer.categorize("Uber *Trip 15/01").unwrap().tipo.as_deref(), Some("TRANSPORTE"));
+        assert_eq!(categorizer.categorize("UBER EATS").unwrap().tipo.as_deref(), Some("DELIVERY"));
+        assert!(categorizer.categorize("Farmácia").is_none());
+        
+        assert!(Categorizer::from_config(&CategorizationConfig::default(), None).unwrap().is_none());
+        config.rules.push(rule("(unclosed", true, "X"));
+        assert!(Categorizer::from_config(&config, None).is_err());
+    }
+    
+    #[test]
+    fn test_fill_or_override_type() {
+        let mut config = CategorizationConfig { enabled: true, ..Default::default() };
+        config.rules.push(rule("netflix", false, "ASSINATURAS"));
+        let matched = config.rules[0].clone();
+        
+        let categorizer = Categorizer::from_config(&config, None).unwrap().unwrap();
+        assert_eq!(categorizer.apply_type(String::new(), Some(&matched)), "ASSINATURAS");
+        assert_eq!(categorizer.apply_type("LAZER".to_string(), Some(&matched)), "LAZER");
+        assert_eq!(categorizer.apply_type(String::new(), None), "");
+        
+        config.override_types = true;
+        let categorizer = Categorizer::from_config(&config, None).unwrap().unwrap();
+        assert_eq!(categorizer.apply_type("LAZER".to_string(), Some(&matched)), "ASSINATURAS");
+    }
+    
+    #[test]
+    fn test_rules_file_and_sheet() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let rules_file = temp_dir.path().join("PDW_RULES.yaml");
+        std::fs::write(&rules_file, "- pattern: posto\n  tipo: COMBUSTIVEL\n  tag: carro\n").unwrap();
+        
+        let config = CategorizationConfig { enabled: true, ..Default::default() };
+        let mut categorizer = Categorizer::from_config(&config, Some(&rules_file)).unwrap().unwrap();
+        assert_eq!(categorizer.categorize("POSTO SHELL").unwrap().tag.as_deref(), Some("carro"));
+        
+        let sheet = vec![
+            vec!["Padrão".to_string(), "Tipo".to_string(), "Categoria".to_string(), "Regex".to_string()],
+            vec!["SMART ?FIT".to_string(), "SAUDE".to_string(), "Academia".to_string(), "X".to_string()],
+            vec!["".to_string(), "IGNORADA".to_string(), "".to_string(), "".to_string()],
+        ];
+        assert_eq!(categorizer.add_sheet_rules(&sheet).unwrap(), 1);
+        assert_eq!(categorizer.len(), 2);
+        
+        let matched = categorizer.categorize("smartfit mensalidade").unwrap();
+        assert_eq!(matched.tipo.as_deref(), Some("SAUDE"));
+        assert_eq!(matched.category.as_deref(), Some("Academia"));
+    }
+}
\ No newline at end of file
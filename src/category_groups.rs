@@ -0,0 +1,168 @@
+/*!
+# Category Groups Module
+
+Groups of TIPOs used by the built-in statements (`[[category_groups]]`): each
+group is an income or expense line made of some transaction types, in the order
+configured. Income groups total credits minus debits, expense groups debits
+minus credits; transfer groups (between own accounts) are left out of the
+statements. Types in no group fall into "Outras Receitas" or "Outras Despesas",
+by the sign of their total over the period.
+*/
+
+use crate::database::DatabaseManager;
+use crate::error::PdwError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Line of the statements for types in no income group
+pub const OTHER_INCOME: &str = "Outras Receitas";
+
+/// Line of the statements for types in no expense group
+pub const OTHER_EXPENSES: &str = "Outras Despesas";
+
+/// Credits and debits by TIPO, then by AnoMes
+pub type TypeTotals = BTreeMap<String, BTreeMap<String, (f64, f64)>>;
+
+/// Side of the statements a group is shown on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupKind {
+    Income,
+    Expense,
+    /// Moves between own accounts, left out of the statements
+    Transfer,
+}
+
+/// Named group of transaction types (`[[category_groups]]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryGroup {
+    pub name: String,
+    pub kind: GroupKind,
+    /// TIPO values of the group (case ignored)
+    #[serde(default)]
+    pub types: Vec<String>,
+}
+
+/// Monthly amounts of one income or expense line
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupLine {
+    pub name: String,
+    pub kind: GroupKind,
+    /// One amount per requested month, positive for the line's side
+    pub amounts: Vec<f64>,
+}
+
+impl GroupLine {
+    /// Sum over the months
+    pub fn total(&self) -> f64 {
+        self.amounts.iter().sum()
+    }
+}
+
+/// Group a TIPO belongs to, if any
+pub fn group_of<'a>(groups: &'a [CategoryGroup], tipo: &str) -> Option<&'a CategoryGroup> {
+    let tipo = tipo.trim();
+    groups.iter().find(|group| group.types.iter().any(|t| t.trim().eq_ignore_ascii_case(tipo)))
+}
+
+/// Credits and debits per TIPO and AnoMes of the given months
+pub fn type_totals(database: &DatabaseManager, entries_table: &str, months: &[String]) -> Result<TypeTotals, PdwError> {
+    let mut totals = TypeTotals::new();
+    if months.is_empty() {
+        return Ok(totals);
+    }
+    
+    let month_list = months.iter().map(|month| format!("'{}'", month.replace('\'', "''"))).collect::<Vec<_>>().join(", ");
+    let query = format!(
+        "SELECT COALESCE(TIPO, ''), AnoMes, COALESCE(SUM(Credito), 0), COALESCE(SUM(Debito), 0)
+         FROM {} WHERE AnoMes IN ({}) GROUP BY TIPO, AnoMes",
+        entries_table, month_list
+    );
+    for row in database.execute_query(&query)? {
+        let text = |index: usize| row.get(index).and_then(Value::as_str).unwrap_or_default().to_string();
+        let amount = |index: usize| row.get(index).and_then(Value::as_f64).unwrap_or(0.0);
+        totals.entry(text(0)).or_default().insert(text(1), (amount(2), amount(3)));
+    }
+    Ok(totals)
+}
+
+/// Income lines, then expense lines, in the configured order with the "other" lines last
+pub fn group_lines(groups: &[CategoryGroup], totals: &TypeTotals, months: &[String]) -> Vec<GroupLine> {
+    let mut lines: Vec<GroupLine> = groups.iter()
+        .filter(|group| group.kind != GroupKind::Transfer)
+        .map(|group| GroupLine { name: group.name.clone(), kind: group.kind, amounts: vec![0.0; months.len()] })
+        .collect();
+    let mut other_income = GroupLine { name: OTHER_INCOME.to_string(), kind: GroupKind::Income, amounts: vec![0.0; months.len()] };
+    let mut other_expenses = GroupLine { name: OTHER_EXPENSES.to_string(), kind: GroupKind::Expense, amounts: vec![0.0; months.len()] };
+    
+    for (tipo, by_month) in totals {
+        let net: Vec<f64> = months.iter()
+            .map(|month| by_month.get(month).map_or(0.0, |(credit, debit)| credit - debit))
+            .collect();
+        let line = match group_of(groups, tipo) {
+            Some(group) if group.kind == GroupKind::Transfer => continue,
+            Some(group) => lines.iter_mut().find(|line| line.name == group.name && line.kind == group.kind),
+            None if net.iter().sum::<f64>() > 0.0 => Some(&mut other_income),
+            None => Some(&mut other_expenses),
+        };
+        if let Some(line) = line {
+            let sign = if line.kind == GroupKind::Income { 1.0 } else { -1.0 };
+            for (amount, value) in line.amounts.iter_mut().zip(&net) {
+                *amount += sign * value;
+            }
+        }
+    }
+    
+    for other in [other_income, other_expenses] {
+        if other.amounts.iter().any(|amount| *amount != 0.0) {
+            lines.push(other);
+        }
+    }
+    lines.sort_by_key(|line| line.kind != GroupKind::Income);
+    for line in &mut lines {
+        for amount in &mut line.amounts {
+            *amount = (*amount * 100.0).round() / 100.0;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[derive(Deserialize)]
+    struct Groups {
+        category_groups: Vec<CategoryGroup>,
+    }
+    
+    #[test]
+    fn test_group_lines() {
+        let groups = toml::from_str::<Groups>(
+            "[[category_groups]]\nname = \"Moradia\"\nkind = \"expense\"\ntypes = [\"ALUGUEL\", \"luz\"]\n\
+             [[category_groups]]\nname = \"Salário\"\nkind = \"income\"\ntypes = [\"SALARIO\"]\n\
+             [[category_groups]]\nname = \"Transferências\"\nkind = \"transfer\"\ntypes = [\"TRANSF\"]"
+        ).unwrap().category_groups;
+        assert_eq!(group_of(&groups, " Luz ").map(|g| g.name.as_str()), Some("Moradia"));
+        
+        let months = vec!["2024/01".to_string(), "2024/02".to_string()];
+        let mut totals = TypeTotals::new();
+        totals.entry("ALUGUEL".into()).or_default().insert("2024/01".into(), (0.0, 1500.0));
+        totals.entry("LUZ".into()).or_default().insert("2024/02".into(), (20.0, 180.0));
+        totals.entry("SALARIO".into()).or_default().insert("2024/01".into(), (5000.0, 0.0));
+        totals.entry("TRANSF".into()).or_default().insert("2024/01".into(), (0.0, 900.0));
+        totals.entry("RENDIMENTO".into()).or_default().insert("2024/02".into(), (12.5, 0.0));
+        totals.entry("LAZER".into()).or_default().insert("2024/02".into(), (0.0, 80.0));
+        
+        let lines = group_lines(&groups, &totals, &months);
+        let view: Vec<(&str, Vec<f64>)> = lines.iter().map(|line| (line.name.as_str(), line.amounts.clone())).collect();
+        assert_eq!(view, vec![
+            ("Salário", vec![5000.0, 0.0]),
+            (OTHER_INCOME, vec![0.0, 12.5]),
+            ("Moradia", vec![1500.0, 160.0]),
+            (OTHER_EXPENSES, vec![0.0, 80.0]),
+        ]);
+        assert_eq!(lines[2].total(), 1660.0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,442 @@
+/*!
+# Charts Module
+
+Small raster charts rendered from the warehouse. The statement card is a
+black-and-white PNG one-pager (period totals, top spending categories and a
+balance trend sparkline) sized for e-ink dashboards and home automation
+displays; it is written to dir_out at the end of each report run.
+*/
+
+use crate::database::DatabaseManager;
+use crate::error::{PdwError, ReportError};
+use crate::opening_balances::OpeningBalances;
+use embedded_graphics::mono_font::iso_8859_1::{FONT_6X13, FONT_9X15_BOLD};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Outer margin of the card in pixels
+const MARGIN: i32 = 8;
+
+/// Characters kept from category names on the bar chart
+const LABEL_CHARS: usize = 12;
+
+/// Statement card settings (`[statement_card]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementCardConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub period: CardPeriod,
+    #[serde(default = "default_card_file")]
+    pub file: String,
+    #[serde(default = "default_card_width")]
+    pub width: u32,
+    #[serde(default = "default_card_height")]
+    pub height: u32,
+    #[serde(default = "default_top_categories")]
+    pub top_categories: usize,
+    #[serde(default = "default_trend_months")]
+    pub trend_months: usize,
+}
+
+/// Period summarized by the card totals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CardPeriod {
+    /// Most recent AnoMes in the warehouse
+    #[default]
+    Month,
+    /// Seven days ending at the most recent entry date
+    Week,
+}
+
+/// Figures shown on the statement card
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatementCard {
+    pub title: String,
+    pub credits: f64,
+    pub debits: f64,
+    pub top_categories: Vec<(String, f64)>,
+    pub balance_trend: Vec<(String, f64)>,
+}
+
+/// Black-and-white pixel buffer drawn through embedded-graphics
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    ink: Vec<bool>,
+}
+
+fn default_card_file() -> String {
+    "PDW_CARD.png".to_string()
+}
+
+fn default_card_width() -> u32 {
+    400
+}
+
+fn default_card_height() -> u32 {
+    300
+}
+
+fn default_top_categories() -> usize {
+    5
+}
+
+fn default_trend_months() -> usize {
+    12
+}
+
+impl Default for StatementCardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            period: CardPeriod::default(),
+            file: default_card_file(),
+            width: default_card_width(),
+            height: default_card_height(),
+            top_categories: default_top_categories(),
+            trend_months: default_trend_months(),
+        }
+    }
+}
+
+impl StatementCard {
+    /// Collect the card figures from the general entries table, None when it is empty
+    pub fn from_database(database: &DatabaseManager, entries_table: &str, config: &StatementCardConfig,
+                         openings: &OpeningBalances) -> Result<Option<Self>, PdwError> {
+        let (filter, anchor, title) = match config.period {
+            CardPeriod::Month => {
+                let query = format!("SELECT MAX(AnoMes) FROM {}", entries_table);
+                match first_text(&database.execute_query(&query)?) {
+                    Some(month) => ("AnoMes = ?1", month.clone(), format!("Mês {}", month)),
+                    None => return Ok(None),
+                }
+            }
+            CardPeriod::Week => {
+                let query = format!("SELECT MAX(Data) FROM {}", entries_table);
+                match first_text(&database.execute_query(&query)?) {
+                    Some(day) => ("Data > date(?1, '-7 days') AND Data <= ?1", day.clone(), format!("Semana até {}", day)),
+                    None => return Ok(None),
+                }
+            }
+        };
+        
+        let totals_query = format!(
+            "SELECT COALESCE(SUM(Credito), 0), COALESCE(SUM(Debito), 0) FROM {} WHERE {}",
+            entries_table, filter
+        );
+        let totals = database.execute_query_with_params(&totals_query, [&anchor])?;
+        let credits = number(totals.first().and_then(|r| r.first()));
+        let debits = number(totals.first().and_then(|r| r.get(1)));
+        
+        let top_query = format!(
+            "SELECT TIPO, SUM(Debito) AS Total FROM {} WHERE {} AND Debito > 0
+             GROUP BY TIPO ORDER BY Total DESC LIMIT {}",
+            entries_table, filter, config.top_categories
+        );
+        let top_categories = database.execute_query_with_params(&top_query, [&anchor])?
+            .iter()
+            .map(|row| (text(row.first()), number(row.get(1))))
+            .collect();
+        
+        // Running balance over all months, keeping the most recent ones
+        let trend_query = format!(
+            "SELECT AnoMes, SUM(COALESCE(Credito, 0) - COALESCE(Debito, 0)) FROM {}
+             WHERE AnoMes IS NOT NULL GROUP BY AnoMes ORDER BY AnoMes",
+            entries_table
+        );
+        let mut balance = openings.total();
+        let mut balance_trend: Vec<(String, f64)> = database.execute_query(&trend_query)?
+            .iter()
+            .map(|row| {
+                balance += number(row.get(1));
+                (text(row.first()), balance)
+            })
+            .collect();
+        let skipped = balance_trend.len().saturating_sub(config.trend_months);
+        balance_trend.drain(..skipped);
+        
+        Ok(Some(Self { title, credits, debits, top_categories, balance_trend }))
+    }
+    
+    /// Draw the card on a new canvas of the given size
+    pub fn render(&self, width: u32, height: u32) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        if let Err(never) = self.draw(&mut canvas) {
+            match never {}
+        }
+        canvas
+    }
+    
+    /// Title, totals, category bars and sparkline, top to bottom
+    fn draw(&self, canvas: &mut Canvas) -> Result<(), Infallible> {
+        let width = canvas.width as i32;
+        let height = canvas.height as i32;
+        let stroke = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+        
+        let mut y = MARGIN;
+        draw_text(canvas, &format!("PDW · {}", self.title), &FONT_9X15_BOLD, MARGIN, y)?;
+        y += 18;
+        Line::new(Point::new(MARGIN, y), Point::new(width - MARGIN, y)).into_styled(stroke).draw(canvas)?;
+        y += 6;
+        
+        for (label, value) in [("Entradas", self.credits), ("Saídas", self.debits), ("Saldo", self.credits - self.debits)] {
+            draw_text(canvas, &format!("{:<9}{:>14.2}", label, value), &FONT_6X13, MARGIN, y)?;
+            y += 15;
+        }
+        y += 6;
+        
+        if !self.top_categories.is_empty() {
+            draw_text(canvas, "Maiores gastos", &FONT_9X15_BOLD, MARGIN, y)?;
+            y += 18;
+            
+            let bar_left = MARGIN + (LABEL_CHARS as i32 + 1) * 6;
+            let value_left = width - MARGIN - 10 * 6;
+            let bar_room = (value_left - bar_left - 6).max(1) as f64;
+            let largest = self.top_categories.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+            
+            for (category, value) in &self.top_categories {
+                let label: String = category.chars().take(LABEL_CHARS).collect();
+                draw_text(canvas, &label, &FONT_6X13, MARGIN, y)?;
+                
+                let bar_width = if largest > 0.0 { (value / largest * bar_room).round().max(1.0) } else { 1.0 };
+                Rectangle::new(Point::new(bar_left, y + 2), Size::new(bar_width as u32, 9))
+                    .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                    .draw(canvas)?;
+                draw_text(canvas, &format!("{:>10.2}", value), &FONT_6X13, value_left, y)?;
+                y += 15;
+            }
+            y += 6;
+        }
+        
+        if let Some((_, last)) = self.balance_trend.last() {
+            let caption = format!("Saldo acumulado ({} meses): {:.2}", self.balance_trend.len(), last);
+            draw_text(canvas, &caption, &FONT_6X13, MARGIN, y)?;
+            y += 17;
+            
+            let area = Rectangle::new(
+                Point::new(MARGIN, y),
+                Size::new((width - 2 * MARGIN).max(1) as u32, (height - MARGIN - y).max(1) as u32),
+            );
+            self.draw_sparkline(canvas, area)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Balance polyline scaled to the area, with a dotted zero line when it is crossed
+    fn draw_sparkline(&self, canvas: &mut Canvas, area: Rectangle) -> Result<(), Infallible> {
+        let values: Vec<f64> = self.balance_trend.iter().map(|(_, v)| *v).collect();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let span = if max > min { max - min } else { 1.0 };
+        
+        let left = area.top_left.x;
+        let top = area.top_left.y;
+        let inner_width = area.size.width.saturating_sub(1) as f64;
+        let inner_height = area.size.height.saturating_sub(1) as f64;
+        let to_y = |value: f64| top + (inner_height - (value - min) / span * inner_height).round() as i32;
+        
+        if min < 0.0 && max > 0.0 {
+            let zero = to_y(0.0);
+            for x in (left..left + area.size.width as i32).step_by(4) {
+                Pixel(Point::new(x, zero), BinaryColor::On).draw(canvas)?;
+            }
+        }
+        
+        let step = if values.len() > 1 { inner_width / (values.len() - 1) as f64 } else { 0.0 };
+        let points: Vec<Point> = values.iter()
+            .enumerate()
+            .map(|(i, v)| Point::new(left + (i as f64 * step).round() as i32, to_y(*v)))
+            .collect();
+        
+        let stroke = PrimitiveStyle::with_stroke(BinaryColor::On, 2);
+        match points.as_slice() {
+            [single] => {
+                let end = Point::new(left + inner_width as i32, single.y);
+                Line::new(*single, end).into_styled(stroke).draw(canvas)?;
+            }
+            _ => {
+                for pair in points.windows(2) {
+                    Line::new(pair[0], pair[1]).into_styled(stroke).draw(canvas)?;
+                }
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+impl Canvas {
+    /// Create a blank (white) canvas
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, ink: vec![false; (width as usize) * (height as usize)] }
+    }
+    
+    /// True when the pixel is black
+    pub fn is_ink(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height && self.ink[(y * self.width + x) as usize]
+    }
+    
+    /// Number of black pixels
+    pub fn ink_count(&self) -> usize {
+        self.ink.iter().filter(|&&ink| ink).count()
+    }
+    
+    /// Save as an 8-bit grayscale PNG
+    pub fn write_png(&self, path: &Path) -> Result<(), PdwError> {
+        let file = File::create(path)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        
+        let data: Vec<u8> = self.ink.iter().map(|&ink| if ink { 0 } else { 255 }).collect();
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(&data).map_err(png_error)?;
+        writer.finish().map_err(png_error)?;
+        
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = BinaryColor;
+    type Error = Infallible;
+    
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let (Ok(x), Ok(y)) = (u32::try_from(point.x), u32::try_from(point.y)) {
+                if x < self.width && y < self.height {
+                    self.ink[(y * self.width + x) as usize] = color.is_on();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Draw a line of text with its top-left corner at (x, y)
+fn draw_text(canvas: &mut Canvas, content: &str, font: &MonoFont, x: i32, y: i32) -> Result<(), Infallible> {
+    let style = MonoTextStyle::new(font, BinaryColor::On);
+    Text::with_baseline(content, Point::new(x, y), style, Baseline::Top).draw(canvas)?;
+    Ok(())
+}
+
+/// First cell of a single-value query as text
+fn first_text(rows: &[Vec<Value>]) -> Option<String> {
+    match rows.first().and_then(|r| r.first()) {
+        Some(Value::String(s)) if !s.is_empty() => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Render an optional query value as text
+fn text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Numeric query value, 0 for NULL or text
+fn number(value: Option<&Value>) -> f64 {
+    value.and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn png_error(e: png::EncodingError) -> PdwError {
+    ReportError::OutputGeneration {
+        format: "png".to_string(),
+        reason: e.to_string(),
+    }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn setup_database(temp_dir: &TempDir) -> DatabaseManager {
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-01-05', 'SALARIO', 1000, 0, '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-01-10', 'MERCADO', 0, 300, '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-02-01', 'SALARIO', 1000, 0, '2024/02');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-02-03', 'MERCADO', 0, 200, '2024/02');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes) VALUES ('2024-02-20', 'LAZER', 0, 450, '2024/02');"
+        ).unwrap();
+        db
+    }
+    
+    #[test]
+    fn test_card_figures() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = setup_database(&temp_dir);
+        
+        let config = StatementCardConfig { top_categories: 1, ..Default::default() };
+        let card = StatementCard::from_database(&db, "LANCAMENTOS_GERAIS", &config, &OpeningBalances::default()).unwrap().unwrap();
+        assert_eq!(card.title, "Mês 2024/02");
+        assert_eq!((card.credits, card.debits), (1000.0, 650.0));
+        assert_eq!(card.top_categories, vec![("LAZER".to_string(), 450.0)]);
+        assert_eq!(card.balance_trend, vec![
+            ("2024/01".to_string(), 700.0),
+            ("2024/02".to_string(), 1050.0),
+        ]);
+        
+        let config = StatementCardConfig { period: CardPeriod::Week, trend_months: 1, ..Default::default() };
+        let card = StatementCard::from_database(&db, "LANCAMENTOS_GERAIS", &config, &OpeningBalances::default()).unwrap().unwrap();
+        assert_eq!(card.title, "Semana até 2024-02-20");
+        assert_eq!((card.credits, card.debits), (0.0, 450.0));
+        assert_eq!(card.balance_trend.len(), 1);
+    }
+    
+    #[test]
+    fn test_empty_warehouse_has_no_card() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        
+        let card = StatementCard::from_database(&db, "LANCAMENTOS_GERAIS", &StatementCardConfig::default(), &OpeningBalances::default()).unwrap();
+        assert!(card.is_none());
+    }
+    
+    #[test]
+    fn test_render_and_write_png() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = setup_database(&temp_dir);
+        let card = StatementCard::from_database(&db, "LANCAMENTOS_GERAIS", &StatementCardConfig::default(), &OpeningBalances::default())
+            .unwrap()
+            .unwrap();
+        
+        let canvas = card.render(400, 300);
+        assert!(canvas.ink_count() > 0);
+        // Corners stay inside the white margin
+        assert!(!canvas.is_ink(0, 0) && !canvas.is_ink(399, 299));
+        
+        let path = temp_dir.path().join("card.png");
+        canvas.write_png(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    }
+}
\ No newline at end of file
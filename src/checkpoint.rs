@@ -0,0 +1,169 @@
+/*!
+# Checkpoint Module
+
+Phase completion state of the pipeline, kept in the PDW_META table so that
+`pdw --resume` continues a failed run from the first phase that did not finish
+instead of reloading the whole workbook after, say, a report error.
+
+The load phase extracts, transforms and loads the sheets inside one database
+transaction, so it is checkpointed as a whole; pivots and reports follow. A
+checkpoint is only resumed while the inputs it was taken from are unchanged
+(same sizes and modification times), and it is cleared once every phase of a
+run has completed.
+*/
+
+use crate::config::PdwConfig;
+use crate::database::{DatabaseManager, MetaStore};
+use crate::error::PdwError;
+use crate::input_files;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Key of the checkpoint in its PDW_META namespace
+const CHECKPOINT_KEY: &str = "run";
+
+/// Pipeline phase a checkpoint records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// Extract, transform and load of the sheets and sources
+    Load,
+    Pivot,
+    Report,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Load => write!(f, "load"),
+            Phase::Pivot => write!(f, "pivot"),
+            Phase::Report => write!(f, "report"),
+        }
+    }
+}
+
+/// Phases completed by the current (or interrupted) run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub started_at: String,
+    /// Sizes and modification times of the inputs the run read
+    pub inputs: String,
+    pub completed: Vec<Phase>,
+}
+
+impl Checkpoint {
+    /// Checkpoint of a run starting now, nothing completed
+    pub fn start(config: &PdwConfig) -> Self {
+        Self {
+            started_at: crate::deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            inputs: input_fingerprint(config),
+            completed: Vec::new(),
+        }
+    }
+    
+    /// Checkpoint left by an interrupted run, when its inputs are unchanged;
+    /// otherwise a fresh one
+    pub fn resume(database: &DatabaseManager, config: &PdwConfig) -> Result<Self, PdwError> {
+        let fresh = Self::start(config);
+        match database.meta().get::<Self>(MetaStore::CHECKPOINT, CHECKPOINT_KEY)? {
+            Some(checkpoint) if checkpoint.inputs == fresh.inputs => Ok(checkpoint),
+            Some(checkpoint) => {
+                tracing::warn!("Inputs changed since the run of {} - running every phase again", checkpoint.started_at);
+                Ok(fresh)
+            }
+            None => {
+                tracing::info!("No interrupted run to resume in {} - running every phase", database.path().display());
+                Ok(fresh)
+            }
+        }
+    }
+    
+    /// Whether a phase already completed
+    pub fn is_done(&self, phase: Phase) -> bool {
+        self.completed.contains(&phase)
+    }
+    
+    /// Mark a phase completed and save the checkpoint
+    pub fn complete(&mut self, database: &DatabaseManager, phase: Phase) -> Result<(), PdwError> {
+        if !self.is_done(phase) {
+            self.completed.push(phase);
+        }
+        database.meta().set(MetaStore::CHECKPOINT, CHECKPOINT_KEY, self)
+    }
+    
+    /// Forget the checkpoint once the run is over
+    pub fn clear(database: &DatabaseManager) -> Result<(), PdwError> {
+        database.meta().remove(MetaStore::CHECKPOINT, CHECKPOINT_KEY)?;
+        Ok(())
+    }
+}
+
+/// Size and modification time of the input workbook (or CSV files) and the enabled sources
+pub(crate) fn input_fingerprint(config: &PdwConfig) -> String {
+    let mut paths = vec![config.get_input_file_path()];
+    paths.extend(config.sources.values()
+        .filter(|source| source.enabled)
+        .map(|source| config.directories.dir_in.join(&source.path)));
+    
+    let mut parts = Vec::new();
+    for path in &paths {
+        if path.is_dir() {
+            let mut files: Vec<_> = std::fs::read_dir(path)
+                .map(|entries| entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect())
+                .unwrap_or_default();
+            files.sort();
+            // Lock files come and go while Excel has the input open
+            parts.extend(files.iter()
+                .filter(|file| file.is_file() && !input_files::is_temporary(file))
+                .map(|file| file_fingerprint(file)));
+        } else {
+            parts.push(file_fingerprint(path));
+        }
+    }
+    parts.join(";")
+}
+
+fn file_fingerprint(path: &Path) -> String {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return format!("{}:missing", path.display());
+    };
+    let modified = metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_millis());
+    format!("{}:{}:{}", path.display(), metadata.len(), modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_resume_after_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        std::fs::write(temp_dir.path().join("PDW.xlsx"), b"workbook").unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("PDW.db")).unwrap();
+        
+        // Nothing to resume yet
+        assert!(Checkpoint::resume(&db, &config).unwrap().completed.is_empty());
+        
+        // A run that failed after loading
+        let mut checkpoint = Checkpoint::start(&config);
+        checkpoint.complete(&db, Phase::Load).unwrap();
+        checkpoint.complete(&db, Phase::Load).unwrap();
+        
+        let resumed = Checkpoint::resume(&db, &config).unwrap();
+        assert_eq!(resumed.completed, vec![Phase::Load]);
+        assert!(resumed.is_done(Phase::Load) && !resumed.is_done(Phase::Pivot));
+        
+        // An edited workbook invalidates the checkpoint
+        std::fs::write(temp_dir.path().join("PDW.xlsx"), b"edited workbook").unwrap();
+        assert!(Checkpoint::resume(&db, &config).unwrap().completed.is_empty());
+        
+        Checkpoint::clear(&db).unwrap();
+        assert_eq!(db.meta().get::<Checkpoint>(MetaStore::CHECKPOINT, CHECKPOINT_KEY).unwrap(), None);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,122 @@
+/*!
+# Collation Module
+
+Unicode-aware text ordering for Portuguese data. The `PDW_PT` collation compares
+strings by their accent- and case-folded form first ("ação" sorts with "acao",
+before "adega"), then breaks ties by accents and finally by case, so results are
+stable and match dictionary order.
+
+The collation is registered per connection and applied in ORDER BY clauses only;
+column declarations are left untouched so the database stays readable by other
+SQLite tools (DB Browser, the Python PDW) that do not know the collation.
+*/
+
+use crate::error::{DatabaseError, PdwError};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Name of the collation registered on SQLite connections
+pub const COLLATION_NAME: &str = "PDW_PT";
+
+/// Text ordering used for generated ORDER BY clauses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TextCollation {
+    /// SQLite byte order (legacy behaviour)
+    #[default]
+    Binary,
+    /// Accent/case-aware Portuguese dictionary order
+    PtBr,
+}
+
+impl TextCollation {
+    /// ORDER BY expression for a column under this collation
+    pub fn order_by(&self, column: &str) -> String {
+        match self {
+            TextCollation::Binary => column.to_string(),
+            TextCollation::PtBr => format!("{} COLLATE {}", column, COLLATION_NAME),
+        }
+    }
+    
+    /// COLLATE suffix for hand-written SQL (YAML `{collate}` variable)
+    pub fn suffix(&self) -> String {
+        match self {
+            TextCollation::Binary => String::new(),
+            TextCollation::PtBr => format!(" COLLATE {}", COLLATION_NAME),
+        }
+    }
+}
+
+/// Register the PDW collation on a connection
+pub fn register(connection: &Connection) -> Result<(), PdwError> {
+    connection.create_collation(COLLATION_NAME, compare_pt)
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: format!("create_collation({})", COLLATION_NAME),
+            reason: e.to_string(),
+        })?;
+    Ok(())
+}
+
+/// Remove accents, keeping base letters and case ("Ação" -> "Acao")
+pub fn strip_accents(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect()
+}
+
+/// Strip accents and lowercase (primary collation key)
+pub fn fold(text: &str) -> String {
+    strip_accents(text).to_lowercase()
+}
+
+/// Compare two strings in Portuguese dictionary order
+pub fn compare_pt(a: &str, b: &str) -> Ordering {
+    // Primary: base letters only
+    fold(a).cmp(&fold(b))
+        // Secondary: unaccented before accented
+        .then_with(|| {
+            let a_lower: String = a.nfd().flat_map(char::to_lowercase).collect();
+            let b_lower: String = b.nfd().flat_map(char::to_lowercase).collect();
+            a_lower.cmp(&b_lower)
+        })
+        // Tertiary: lowercase before uppercase
+        .then_with(|| b.cmp(a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("Ação"), "acao");
+        assert_eq!(fold("ÓNIBUS"), "onibus");
+    }
+    
+    #[test]
+    fn test_portuguese_ordering() {
+        let mut words = vec!["Zebra", "ágil", "Ação", "abacate", "acao", "Éter", "egua"];
+        words.sort_by(|a, b| compare_pt(a, b));
+        assert_eq!(words, vec!["abacate", "acao", "Ação", "ágil", "egua", "Éter", "Zebra"]);
+    }
+    
+    #[test]
+    fn test_collation_in_sqlite() {
+        let connection = Connection::open_in_memory().unwrap();
+        register(&connection).unwrap();
+        connection.execute_batch(
+            "CREATE TABLE t (name TEXT);
+             INSERT INTO t VALUES ('Óculos'), ('ovo'), ('Mercado'), ('música');"
+        ).unwrap();
+        
+        let query = format!("SELECT name FROM t ORDER BY {}", TextCollation::PtBr.order_by("name"));
+        let mut stmt = connection.prepare(&query).unwrap();
+        let names: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(names, vec!["Mercado", "música", "Óculos", "ovo"]);
+    }
+}
\ No newline at end of file
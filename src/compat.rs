@@ -0,0 +1,408 @@
+/*!
+# Compatibility Module
+
+Keeps the Python and Rust PDW from silently mixing up each other's databases.
+After each load the Rust version stamps the warehouse: `PRAGMA user_version`
+holds its schema version and the `schema` namespace of the metadata store
+(PDW_META) records the producer, the crate and PDW versions and a fingerprint
+of the core tables' columns.
+
+A database without a current stamp that shows the pandas layout of the Python
+PDW (`Data` stored as a timestamp, the origin column named after
+TRANSIENT_DATA_COLUMN), or whose stamp names the Python PDW, is recognized as
+Python-produced; a missing RowHash alone only marks a database loaded before
+incremental loads. With `python_databases = "adapt"` (the default) the quirks
+are fixed in place when a load starts, after a copy of the file is saved as
+`<database>.python.bak`; other commands leave the database as it is. With
+`"refuse"` the run stops without touching the file. Databases stamped by a
+newer schema version are always refused.
+*/
+
+use crate::config::PdwConfig;
+use crate::database::{self, quote_identifier, DatabaseManager, MetaStore};
+use crate::error::{DatabaseError, PdwError};
+use crate::migrations;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// `PRAGMA user_version` of databases written by this version (the last migration)
+pub const SCHEMA_VERSION: i64 = migrations::LATEST_VERSION;
+
+/// Columns every loadable entries table needs
+const REQUIRED_COLUMNS: [&str; 5] = ["Data", "TIPO", "DESCRICAO", "Credito", "Debito"];
+
+/// PDW implementation that wrote a database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Producer {
+    Rust,
+    Python,
+}
+
+/// Handling of databases written by the Python PDW (`settings.python_databases`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PythonDatabasePolicy {
+    /// Fix the Python layout in place and continue
+    #[default]
+    Adapt,
+    /// Stop before modifying the database
+    Refuse,
+}
+
+/// Stamp kept in the `schema` namespace of the metadata store
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompatibilityStamp {
+    pub producer: Producer,
+    pub producer_version: String,
+    pub pdw_version: String,
+    pub schema_version: i64,
+    pub fingerprint: String,
+    pub stamped_at: String,
+    /// Producer of the database before it was adapted, if any
+    pub adapted_from: Option<Producer>,
+}
+
+/// Layout differences of Python-produced entries tables
+#[derive(Debug, Clone, PartialEq)]
+pub enum PythonQuirk {
+    /// Entries loaded without a RowHash column
+    MissingRowHash,
+    /// `Data` stored by pandas as "YYYY-MM-DD HH:MM:SS"
+    TimestampDates,
+    /// Origin column named after TRANSIENT_DATA_COLUMN instead of Origem
+    OriginColumn(String),
+}
+
+/// What is known about the producer of an opened database
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    /// None for a database without an entries table
+    pub producer: Option<Producer>,
+    pub stamp: Option<CompatibilityStamp>,
+    pub user_version: i64,
+    pub quirks: Vec<PythonQuirk>,
+}
+
+/// Identify who produced a database from its stamp and layout
+pub fn detect(database: &DatabaseManager, config: &PdwConfig) -> Result<Detection, PdwError> {
+    let entries_table = &config.settings.general_entries_table;
+    let user_version = user_version(database)?;
+    let stamp = read_stamp(database)?;
+    let columns = database.table_columns(entries_table)?;
+    
+    if columns.is_empty() {
+        return Ok(Detection { producer: None, stamp, user_version, quirks: Vec::new() });
+    }
+    
+    let has_column = |name: &str| columns.iter().any(|c| c.eq_ignore_ascii_case(name));
+    let mut quirks = Vec::new();
+    if !has_column("RowHash") {
+        quirks.push(PythonQuirk::MissingRowHash);
+    }
+    if has_column("Data") && !database.execute_query(&format!(
+        "SELECT 1 FROM {} WHERE length(Data) > 10 AND Data GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9] *' LIMIT 1",
+        entries_table
+    ))?.is_empty() {
+        quirks.push(PythonQuirk::TimestampDates);
+    }
+    let origin_column = &config.settings.transient_data_column;
+    if !has_column("Origem") && has_column(origin_column) {
+        quirks.push(PythonQuirk::OriginColumn(origin_column.clone()));
+    }
+    
+    // A matching stamp means the layout is ours, whatever it looks like
+    let current = stamp.as_ref()
+        .map(|stamp| schema_fingerprint(database, config).map(|fingerprint| fingerprint == stamp.fingerprint))
+        .transpose()?
+        .unwrap_or(false);
+    // Rust databases loaded before RowHash existed lack it too
+    let pandas_layout = quirks.iter().any(|quirk| *quirk != PythonQuirk::MissingRowHash);
+    let stamped_python = stamp.as_ref().is_some_and(|stamp| stamp.producer == Producer::Python);
+    let producer = if !current && (pandas_layout || stamped_python) { Producer::Python } else { Producer::Rust };
+    
+    Ok(Detection { producer: Some(producer), stamp, user_version, quirks })
+}
+
+/// Check an opened database before use: refuse newer schemas, then refuse Python-produced
+/// layouts or, with `adapt` (a load), adapt them according to the policy
+pub fn prepare(database: &DatabaseManager, config: &PdwConfig, adapt: bool) -> Result<Detection, PdwError> {
+    let detection = detect(database, config)?;
+    let path = database.path().display();
+    
+    let schema_version = detection.stamp.as_ref().map_or(detection.user_version, |s| s.schema_version.max(detection.user_version));
+    if schema_version > SCHEMA_VERSION {
+        return Err(DatabaseError::SchemaValidation {
+            reason: format!(
+                "{} was written by a newer PDW (schema {}, this version supports {}) - upgrade pdw before using it",
+                path, schema_version, SCHEMA_VERSION
+            ),
+        }.into());
+    }
+    
+    match (&detection.producer, &detection.stamp) {
+        (Some(Producer::Rust), Some(stamp)) => tracing::debug!(
+            "Database stamped by {:?} {} (PDW {}, schema {})",
+            stamp.producer, stamp.producer_version, stamp.pdw_version, stamp.schema_version
+        ),
+        (Some(Producer::Python), _) if adapt || config.settings.python_databases == PythonDatabasePolicy::Refuse => {
+            adapt_python_database(database, config, &detection)?
+        }
+        (Some(Producer::Python), _) => tracing::warn!(
+            "{} was produced by the Python PDW - it is adapted (after a backup) by the next load", path
+        ),
+        _ => {}
+    }
+    
+    Ok(detection)
+}
+
+/// Fix the quirks of a Python-produced database, or refuse it
+fn adapt_python_database(database: &DatabaseManager, config: &PdwConfig, detection: &Detection) -> Result<(), PdwError> {
+    let entries_table = &config.settings.general_entries_table;
+    let path = database.path().display();
+    
+    if config.settings.python_databases == PythonDatabasePolicy::Refuse {
+        return Err(DatabaseError::SchemaValidation {
+            reason: format!(
+                "{} was produced by the Python PDW and settings.python_databases = \"refuse\" \
+                 - use another database_dir or set it to \"adapt\"",
+                path
+            ),
+        }.into());
+    }
+    
+    let columns = database.table_columns(entries_table)?;
+    let origin_column = detection.quirks.iter().find_map(|quirk| match quirk {
+        PythonQuirk::OriginColumn(column) => Some(column.as_str()),
+        _ => None,
+    });
+    let missing: Vec<&str> = REQUIRED_COLUMNS.iter()
+        .copied()
+        .filter(|required| !columns.iter().any(|c| c.eq_ignore_ascii_case(required)))
+        .collect();
+    if !missing.is_empty() {
+        return Err(DatabaseError::SchemaValidation {
+            reason: format!(
+                "{} looks produced by the Python PDW but {} lacks {} and cannot be adapted",
+                path, entries_table, missing.join(", ")
+            ),
+        }.into());
+    }
+    
+    if let Some(backup) = backup_path(database) {
+        database.backup_to(&backup)?;
+        tracing::info!("   . .. ... Saved the Python database as {}", backup.display());
+    }
+    tracing::warn!("{} was produced by the Python PDW - adapting it ({} quirks)", path, detection.quirks.len());
+    
+    if let Some(column) = origin_column {
+        execute(database, &format!(
+            "ALTER TABLE {} RENAME COLUMN {} TO Origem",
+            entries_table, quote_identifier(column)
+        ))?;
+        tracing::info!("   . .. ... Renamed {}.{} to Origem", entries_table, column);
+    }
+    
+    if detection.quirks.contains(&PythonQuirk::TimestampDates) {
+        let fixed = execute(database, &format!(
+            "UPDATE {} SET Data = substr(Data, 1, 10) WHERE length(Data) > 10 AND Data GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9] *'",
+            entries_table
+        ))?;
+        tracing::info!("   . .. ... Converted {} timestamp dates to YYYY-MM-DD", fixed);
+    }
+    
+    write_stamp(database, config, Some(Producer::Python))?;
+    Ok(())
+}
+
+/// Copy of a database kept before it is adapted; None for in-memory databases
+pub fn backup_path(database: &DatabaseManager) -> Option<PathBuf> {
+    let path = database.path();
+    let file_name = path.file_name().filter(|_| path != std::path::Path::new(":memory:"))?;
+    Some(path.with_file_name(format!("{}.python.bak", file_name.to_string_lossy())))
+}
+
+/// Stamp the database as produced by this version
+pub fn write_stamp(database: &DatabaseManager, config: &PdwConfig,
+                   adapted_from: Option<Producer>) -> Result<CompatibilityStamp, PdwError> {
+    // Keep the origin of an adapted database across later stamps
+    let adapted_from = adapted_from.or(read_stamp(database)?.and_then(|stamp| stamp.adapted_from));
+    let stamp = CompatibilityStamp {
+        producer: Producer::Rust,
+        producer_version: crate::VERSION.to_string(),
+        pdw_version: config.settings.current_version.clone(),
+        schema_version: SCHEMA_VERSION,
+        fingerprint: schema_fingerprint(database, config)?,
+        stamped_at: crate::deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        adapted_from,
+    };
+    
+    let meta = database.meta();
+    meta.set(MetaStore::SCHEMA, "producer", &stamp.producer)?;
+    meta.set(MetaStore::SCHEMA, "producer_version", &stamp.producer_version)?;
+    meta.set(MetaStore::SCHEMA, "pdw_version", &stamp.pdw_version)?;
+    meta.set(MetaStore::SCHEMA, "schema_version", &stamp.schema_version)?;
+    meta.set(MetaStore::SCHEMA, "schema_fingerprint", &stamp.fingerprint)?;
+    meta.set(MetaStore::SCHEMA, "stamped_at", &stamp.stamped_at)?;
+    match stamp.adapted_from {
+        Some(producer) => meta.set(MetaStore::SCHEMA, "adapted_from", &producer)?,
+        None => { meta.remove(MetaStore::SCHEMA, "adapted_from")?; }
+    }
+    
+    database.connection().pragma_update(None, "user_version", SCHEMA_VERSION)
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: "PRAGMA user_version".to_string(),
+            reason: e.to_string(),
+        })?;
+    
+    Ok(stamp)
+}
+
+/// Stamp stored in the metadata store, if any
+pub fn read_stamp(database: &DatabaseManager) -> Result<Option<CompatibilityStamp>, PdwError> {
+    let meta = database.meta();
+    let Some(producer) = meta.get(MetaStore::SCHEMA, "producer")? else {
+        return Ok(None);
+    };
+    let text = |key: &str| meta.get::<String>(MetaStore::SCHEMA, key).map(Option::unwrap_or_default);
+    
+    Ok(Some(CompatibilityStamp {
+        producer,
+        producer_version: text("producer_version")?,
+        pdw_version: text("pdw_version")?,
+        schema_version: meta.get(MetaStore::SCHEMA, "schema_version")?.unwrap_or(0),
+        fingerprint: text("schema_fingerprint")?,
+        stamped_at: text("stamped_at")?,
+        adapted_from: meta.get(MetaStore::SCHEMA, "adapted_from")?,
+    }))
+}
+
+/// Hex FNV-1a over the column names and declared types of the core tables
+/// (entries, types of entries and guiding table)
+pub fn schema_fingerprint(database: &DatabaseManager, config: &PdwConfig) -> Result<String, PdwError> {
+    let settings = &config.settings;
+    let mut layout = String::new();
+    
+    for table in [&settings.general_entries_table, &settings.types_of_entries, &settings.guiding_table] {
+        layout.push_str(&table.to_uppercase());
+        layout.push('(');
+        for row in database.execute_query(&format!("PRAGMA table_info({})", quote_identifier(table)))? {
+            let text = |index: usize| row.get(index).and_then(Value::as_str).unwrap_or_default().to_uppercase();
+            layout.push_str(&format!("{} {},", text(1), text(2)));
+        }
+        layout.push(')');
+    }
+    
+    Ok(format!("{:016x}", database::fnv1a(layout.as_bytes())))
+}
+
+/// Current `PRAGMA user_version`
+pub fn user_version(database: &DatabaseManager) -> Result<i64, PdwError> {
+    database.connection().query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: "PRAGMA user_version".to_string(),
+            reason: e.to_string(),
+        }.into())
+}
+
+fn execute(database: &DatabaseManager, query: &str) -> Result<usize, PdwError> {
+    database.connection().execute(query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: query.to_string(),
+            reason: e.to_string(),
+        }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+    
+    /// Entries table as written by pandas `to_sql` in the Python PDW
+    fn python_database(temp_dir: &TempDir) -> DatabaseManager {
+        let db = DatabaseManager::new(&temp_dir.path().join("python.db")).unwrap();
+        db.connection().execute_batch(
+            "CREATE TABLE LANCAMENTOS_GERAIS (Data TIMESTAMP, DIA_SEMANA TEXT, TIPO TEXT, DESCRICAO TEXT,
+                 Credito REAL, Debito REAL, Mes TEXT, Ano TEXT, MES_EXTENSO TEXT, AnoMes TEXT, Fonte TEXT);
+             INSERT INTO LANCAMENTOS_GERAIS VALUES ('2024-01-15 00:00:00', 'Segunda-feira', 'ALM', 'Mercado',
+                 0, 42.5, '01', '2024', '01-Janeiro', '2024/01', 'Conta');"
+        ).unwrap();
+        db
+    }
+    
+    #[test]
+    fn test_stamp_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        let config = PdwConfig::default();
+        
+        assert_eq!(detect(&db, &config).unwrap().producer, None);
+        db.create_tables().unwrap();
+        // No RowHash alone is an older Rust database, not a Python one
+        db.connection().execute_batch("ALTER TABLE LANCAMENTOS_GERAIS DROP COLUMN RowHash").unwrap();
+        let detection = detect(&db, &config).unwrap();
+        assert_eq!((detection.producer, detection.quirks), (Some(Producer::Rust), vec![PythonQuirk::MissingRowHash]));
+        let stamp = write_stamp(&db, &config, None).unwrap();
+        
+        assert_eq!(read_stamp(&db).unwrap(), Some(stamp.clone()));
+        assert_eq!(user_version(&db).unwrap(), SCHEMA_VERSION);
+        assert_eq!(stamp.pdw_version, "9.11.0");
+        
+        let detection = prepare(&db, &config, false).unwrap();
+        assert_eq!(detection.producer, Some(Producer::Rust));
+    }
+    
+    #[test]
+    fn test_python_database_is_adapted() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = python_database(&temp_dir);
+        let mut config = PdwConfig::default();
+        config.settings.transient_data_column = "Fonte".to_string();
+        
+        // Only a load adapts the database
+        assert_eq!(prepare(&db, &config, false).unwrap().producer, Some(Producer::Python));
+        assert_eq!(db.execute_query("SELECT Data FROM LANCAMENTOS_GERAIS").unwrap()[0][0], json!("2024-01-15 00:00:00"));
+        let backup = backup_path(&db).unwrap();
+        assert!(!backup.exists());
+        
+        let detection = prepare(&db, &config, true).unwrap();
+        assert_eq!(detection.producer, Some(Producer::Python));
+        assert_eq!(detection.quirks, vec![
+            PythonQuirk::MissingRowHash,
+            PythonQuirk::TimestampDates,
+            PythonQuirk::OriginColumn("Fonte".to_string()),
+        ]);
+        
+        let rows = db.execute_query("SELECT Data, Origem FROM LANCAMENTOS_GERAIS").unwrap();
+        assert_eq!(rows, vec![vec![json!("2024-01-15"), json!("Conta")]]);
+        // The file as the Python PDW left it is kept
+        let original = DatabaseManager::new(&backup).unwrap();
+        assert_eq!(original.execute_query("SELECT Data, Fonte FROM LANCAMENTOS_GERAIS").unwrap(),
+                   vec![vec![json!("2024-01-15 00:00:00"), json!("Conta")]]);
+        
+        // The adapted database now carries a stamp remembering where it came from
+        let detection = detect(&db, &config).unwrap();
+        assert_eq!(detection.producer, Some(Producer::Rust));
+        assert_eq!(detection.stamp.unwrap().adapted_from, Some(Producer::Python));
+    }
+    
+    #[test]
+    fn test_refused_databases() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = python_database(&temp_dir);
+        let mut config = PdwConfig::default();
+        config.settings.python_databases = PythonDatabasePolicy::Refuse;
+        
+        assert!(prepare(&db, &config, false).is_err());
+        // Nothing was changed
+        assert_eq!(db.execute_query("SELECT Data FROM LANCAMENTOS_GERAIS").unwrap()[0][0], json!("2024-01-15 00:00:00"));
+        
+        config.settings.python_databases = PythonDatabasePolicy::Adapt;
+        db.connection().pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        let error = prepare(&db, &config, true).unwrap_err().to_string();
+        assert!(error.contains("newer PDW"), "{}", error);
+    }
+}
\ No newline at end of file
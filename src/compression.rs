@@ -0,0 +1,275 @@
+/*!
+# Export Compression Module
+
+Compression of the exports, set in `[export.compression]`:
+
+- `format` - "none", "gzip" (`.gz`, the default), "zstd" (`.zst`, needs a build
+  with `--features zstd`) or "zip" (`.zip` holding the one file)
+- `extensions` - exports compressed, by extension (JSON and XML by default)
+- `level` - gzip and zip 0-9, zstd 1-22; the format's default when unset
+- `keep_original` - keep the uncompressed file next to the compressed one
+- `archive` - also gather every file the report phase wrote to `dir_out` into
+  one dated zip (`PDW_REPORTS_YYYY-MM-DD.zip`, after `out_rpt_file`), removing the files unless
+  `keep_original` is set
+
+Files a YAML query writes through its `output:` keep the name given there and
+are only compressed into the run archive.
+*/
+
+use crate::error::{ConfigError, PdwError, ReportError};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// `[export]` table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportConfig {
+    #[serde(default)]
+    pub compression: CompressionConfig,
+}
+
+/// Compression of each export file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionFormat {
+    None,
+    #[default]
+    Gzip,
+    Zstd,
+    Zip,
+}
+
+/// `[export.compression]` settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    #[serde(default)]
+    pub format: CompressionFormat,
+    /// Extensions of the exports compressed
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    /// Compression level; the format's default when unset
+    #[serde(default)]
+    pub level: Option<u32>,
+    #[serde(default)]
+    pub keep_original: bool,
+    /// Gather the report phase's outputs into one dated zip archive
+    #[serde(default)]
+    pub archive: bool,
+}
+
+fn default_extensions() -> Vec<String> {
+    vec!["json".to_string(), "xml".to_string()]
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            format: CompressionFormat::default(),
+            extensions: default_extensions(),
+            level: None,
+            keep_original: false,
+            archive: false,
+        }
+    }
+}
+
+impl CompressionFormat {
+    /// Extension added to compressed files
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            CompressionFormat::None => None,
+            CompressionFormat::Gzip => Some("gz"),
+            CompressionFormat::Zstd => Some("zst"),
+            CompressionFormat::Zip => Some("zip"),
+        }
+    }
+    
+    /// Levels the format accepts
+    fn levels(&self) -> std::ops::RangeInclusive<u32> {
+        match self {
+            CompressionFormat::Zstd => 1..=22,
+            _ => 0..=9,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Reject levels out of the format's range and formats missing from the build
+    pub fn check(&self) -> Result<(), PdwError> {
+        if self.format == CompressionFormat::Zstd && !cfg!(feature = "zstd") {
+            return Err(ConfigError::InvalidFormat {
+                message: "export.compression.format = \"zstd\" requires a build with --features zstd".to_string(),
+            }.into());
+        }
+        if let Some(level) = self.level.filter(|level| !self.format.levels().contains(level)) {
+            let levels = self.format.levels();
+            return Err(ConfigError::InvalidFormat {
+                message: format!("export.compression.level {} is outside {}-{}", level, levels.start(), levels.end()),
+            }.into());
+        }
+        Ok(())
+    }
+    
+    /// Whether files with this extension are compressed
+    pub fn applies_to(&self, path: &Path) -> bool {
+        self.format != CompressionFormat::None
+            && path.extension().is_some_and(|ext| self.extensions.iter().any(|listed| ext.eq_ignore_ascii_case(listed.as_str())))
+    }
+}
+
+/// Compress an export as configured; returns the compressed file, None when left as is
+pub fn compress_file(path: &Path, config: &CompressionConfig) -> Result<Option<PathBuf>, PdwError> {
+    let Some(extension) = config.format.extension().filter(|_| config.applies_to(path)) else {
+        return Ok(None);
+    };
+    let compressed = path.with_extension(format!(
+        "{}.{}",
+        path.extension().unwrap_or_default().to_string_lossy(),
+        extension
+    ));
+    
+    let mut input = File::open(path)?;
+    let output = File::create(&compressed)?;
+    match config.format {
+        CompressionFormat::None => unreachable!("no extension for uncompressed files"),
+        CompressionFormat::Gzip => {
+            let level = config.level.map_or_else(flate2::Compression::default, flate2::Compression::new);
+            let mut encoder = flate2::write::GzEncoder::new(output, level);
+            std::io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?;
+        }
+        CompressionFormat::Zstd => zstd_copy(&mut input, output, config.level)?,
+        CompressionFormat::Zip => {
+            let mut archive = zip::ZipWriter::new(output);
+            add_to_zip(&mut archive, path, &file_name(path), config.level)?;
+            archive.finish().map_err(zip_error)?;
+        }
+    }
+    
+    if !config.keep_original {
+        std::fs::remove_file(path)?;
+    }
+    tracing::info!("Compressed file created: {}", compressed.display());
+    Ok(Some(compressed))
+}
+
+/// Files of `dir` (and its subdirectories) written since `since`, oldest first
+pub fn written_since(dir: &Path, since: SystemTime) -> Result<Vec<PathBuf>, PdwError> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.modified().is_ok_and(|modified| modified >= since) {
+                files.push((metadata.modified()?, entry.path()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Zip `files` (paths under `dir`, stored relative to it) into `archive`, removing them unless kept
+pub fn archive_files(dir: &Path, files: &[PathBuf], archive: &Path, config: &CompressionConfig) -> Result<usize, PdwError> {
+    let mut writer = zip::ZipWriter::new(File::create(archive)?);
+    let mut count = 0;
+    for file in files.iter().filter(|file| file.as_path() != archive) {
+        let name = file.strip_prefix(dir).unwrap_or(file).to_string_lossy().replace('\\', "/");
+        add_to_zip(&mut writer, file, &name, config.level)?;
+        count += 1;
+    }
+    writer.finish().map_err(zip_error)?;
+    
+    if !config.keep_original {
+        for file in files.iter().filter(|file| file.as_path() != archive) {
+            std::fs::remove_file(file)?;
+        }
+    }
+    Ok(count)
+}
+
+fn add_to_zip(archive: &mut zip::ZipWriter<File>, path: &Path, name: &str, level: Option<u32>) -> Result<(), PdwError> {
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .compression_level(level.map(|level| level as i32));
+    archive.start_file(name, options).map_err(zip_error)?;
+    std::io::copy(&mut File::open(path)?, archive)?;
+    archive.flush()?;
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().to_string()
+}
+
+fn zip_error(e: zip::result::ZipError) -> PdwError {
+    ReportError::OutputGeneration { format: "zip".to_string(), reason: e.to_string() }.into()
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_copy(input: &mut File, output: File, level: Option<u32>) -> Result<(), PdwError> {
+    let level = level.map_or(zstd::DEFAULT_COMPRESSION_LEVEL, |level| level as i32);
+    zstd::stream::copy_encode(input, output, level)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_copy(_input: &mut File, _output: File, _level: Option<u32>) -> Result<(), PdwError> {
+    Err(ReportError::UnsupportedFormat { format: "zstd (build with --features zstd)".to_string() }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    
+    #[test]
+    fn test_compression_formats_and_archive() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let started = SystemTime::now() - std::time::Duration::from_secs(1);
+        let json = temp_dir.path().join("LANCAMENTOS_GERAIS.json");
+        let csv = temp_dir.path().join("LANCAMENTOS_GERAIS.csv");
+        std::fs::write(&json, "[1, 2, 3]").unwrap();
+        std::fs::write(&csv, "a;b").unwrap();
+        
+        // Default: JSON and XML gzipped in place, CSV left alone
+        let config = CompressionConfig::default();
+        let gzipped = compress_file(&json, &config).unwrap().unwrap();
+        assert_eq!(gzipped, temp_dir.path().join("LANCAMENTOS_GERAIS.json.gz"));
+        assert!(!json.exists());
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(File::open(&gzipped).unwrap()).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "[1, 2, 3]");
+        assert_eq!(compress_file(&csv, &config).unwrap(), None);
+        
+        let config = CompressionConfig {
+            format: CompressionFormat::Zip,
+            extensions: vec!["CSV".to_string()],
+            level: Some(9),
+            keep_original: true,
+            archive: true,
+        };
+        let zipped = compress_file(&csv, &config).unwrap().unwrap();
+        assert!(csv.exists());
+        let mut single = zip::ZipArchive::new(File::open(&zipped).unwrap()).unwrap();
+        assert_eq!(single.by_index(0).unwrap().name(), "LANCAMENTOS_GERAIS.csv");
+        
+        // The run archive holds everything written since the phase started, subdirectories included
+        std::fs::create_dir(temp_dir.path().join("ofx")).unwrap();
+        std::fs::write(temp_dir.path().join("ofx").join("Conta.ofx"), "<OFX>").unwrap();
+        let archive = temp_dir.path().join("PDW_REPORTS_2024-01-31.zip");
+        let files = written_since(temp_dir.path(), started).unwrap();
+        assert_eq!(archive_files(temp_dir.path(), &files, &archive, &CompressionConfig::default()).unwrap(), 4);
+        let mut names: Vec<String> = zip::ZipArchive::new(File::open(&archive).unwrap()).unwrap().file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, vec!["LANCAMENTOS_GERAIS.csv", "LANCAMENTOS_GERAIS.csv.zip", "LANCAMENTOS_GERAIS.json.gz", "ofx/Conta.ofx"]);
+        assert!(!csv.exists());
+        
+        assert!(CompressionConfig { level: Some(12), ..CompressionConfig::default() }.check().is_err());
+    }
+}
\ No newline at end of file
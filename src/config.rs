@@ -5,17 +5,195 @@ Handles TOML configuration files with backward compatibility for INI format.
 Provides validation and migration utilities.
 */
 
+use crate::alerts::AlertRule;
+use crate::analytics::{AnalyticsConfig, BenchmarkConfig, IncomeShareConfig};
+use crate::baselines::BaselineConfig;
+use crate::branding::BrandingConfig;
+use crate::budgets::BudgetConfig;
+use crate::cash_flow::CashFlowConfig;
+use crate::categorize::CategorizationConfig;
+use crate::category_groups::CategoryGroup;
+use crate::charts::StatementCardConfig;
+use crate::collation::TextCollation;
+use crate::compat::PythonDatabasePolicy;
+use crate::compression::ExportConfig;
+use crate::csv_input::CsvInputConfig;
+use crate::currency::CurrencyConfig;
+use crate::cycles::StatementCycleConfig;
+use crate::database::{DatabaseBackend, DatabaseConfig, IntegrityCheck};
+use crate::duplicates::DuplicatesConfig;
 use crate::error::{ConfigError, PdwError};
+use crate::etl::LoadMode;
+use crate::export_profiles::ExportProfiles;
+use crate::expression::DerivedColumn;
+use crate::fees::FeeConfig;
+use crate::forecast::ForecastConfig;
+use crate::fx::FxConfig;
+use crate::importers::SourceConfig;
+use crate::locale::Locale;
+use crate::money::MoneyMode;
+use crate::non_data::NonDataRowsConfig;
+use crate::notifications::NotificationConfig;
+use crate::opening_balances::OpeningBalanceConfig;
+use crate::pdf::PdfStatementConfig;
+use crate::publish::PublishConfig;
+use crate::quality::QualityConfig;
+use crate::reconciliation::ReconciliationConfig;
+use crate::reporting::ReportEngine;
+use crate::sanity::SanityConfig;
+use crate::splits::SplitConfig;
+use crate::tags::TagsConfig;
+use crate::telemetry::TelemetryConfig;
+use crate::type_normalization::TypeNormalizationConfig;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::fs;
 
+/// Prefix of environment variables overriding configuration values
+/// (`PDW__SETTINGS__RUN_REPORTS=false` sets `settings.run_reports`)
+pub const ENV_OVERRIDE_PREFIX: &str = "PDW__";
+
+/// Environment variable selecting the profile when `--profile` is not given
+pub const PROFILE_ENV: &str = "PDW_PROFILE";
+
+/// Table of a configuration file holding the profiles (`[profile.prod]`)
+const PROFILE_TABLE: &str = "profile";
+
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PdwConfig {
     pub directories: DirectoryConfig,
     pub file_types: FileTypeConfig,
     pub settings: SettingsConfig,
+    #[serde(default)]
+    pub derived_columns: Vec<DerivedColumnConfig>,
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub type_normalization: TypeNormalizationConfig,
+    #[serde(default)]
+    pub csv: CsvInputConfig,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    #[serde(default)]
+    pub statement_card: StatementCardConfig,
+    #[serde(default)]
+    pub fx: FxConfig,
+    #[serde(default)]
+    pub currency: CurrencyConfig,
+    #[serde(default)]
+    pub benchmark: BenchmarkConfig,
+    #[serde(default)]
+    pub fees: FeeConfig,
+    #[serde(default)]
+    pub duplicates: DuplicatesConfig,
+    #[serde(default)]
+    pub forecast: ForecastConfig,
+    #[serde(default)]
+    pub cash_flow: CashFlowConfig,
+    /// Income, expense and transfer groups of TIPOs (`[[category_groups]]`)
+    #[serde(default)]
+    pub category_groups: Vec<CategoryGroup>,
+    #[serde(default)]
+    pub income_share: IncomeShareConfig,
+    #[serde(default)]
+    pub analytics: AnalyticsConfig,
+    #[serde(default)]
+    pub budgets: BudgetConfig,
+    #[serde(default)]
+    pub baselines: BaselineConfig,
+    #[serde(default)]
+    pub opening_balances: OpeningBalanceConfig,
+    #[serde(default)]
+    pub reconciliation: ReconciliationConfig,
+    #[serde(default)]
+    pub splits: SplitConfig,
+    #[serde(default)]
+    pub tags: TagsConfig,
+    #[serde(default)]
+    pub quality: QualityConfig,
+    #[serde(default)]
+    pub non_data_rows: NonDataRowsConfig,
+    #[serde(default)]
+    pub locale: Locale,
+    #[serde(default)]
+    pub statement_cycles: StatementCycleConfig,
+    #[serde(default)]
+    pub categorization: CategorizationConfig,
+    #[serde(default)]
+    pub pdf_statements: PdfStatementConfig,
+    #[serde(default)]
+    pub branding: BrandingConfig,
+    #[serde(default)]
+    pub publish: PublishConfig,
+    #[serde(default)]
+    pub sanity: SanityConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// Column selections for the exports and YAML queries, by name
+    #[serde(default)]
+    pub export_profiles: ExportProfiles,
+    /// Compression of the exports and the run archive
+    #[serde(default)]
+    pub export: ExportConfig,
+    /// Bank downloads loaded along with the workbook, by source name
+    #[serde(default)]
+    pub sources: BTreeMap<String, SourceConfig>,
+    /// Unused and deprecated keys found in the loaded file
+    #[serde(skip)]
+    pub warnings: Vec<ConfigWarning>,
+}
+
+/// Key of a configuration file that does not do what its author expects
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigWarning {
+    /// Misspelled or unsupported key, ignored by the loader
+    Unused { key: String, suggestion: Option<String> },
+    /// Key still honored but superseded by another
+    Deprecated { key: String, replacement: String },
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unused { key, suggestion: Some(suggestion) } => write!(f, "unused key '{}' - did you mean '{}'?", key, suggestion),
+            Self::Unused { key, suggestion: None } => write!(f, "unused key '{}'", key),
+            Self::Deprecated { key, replacement } => write!(f, "'{}' is deprecated - use {}", key, replacement),
+        }
+    }
+}
+
+/// Keys still read for compatibility, with what replaces them
+const DEPRECATED_KEYS: [(&str, &str); 1] = [
+    ("statement_cycles.group_by_cycle", "statement_cycles.basis = \"statement\""),
+];
+
+/// Keys renamed to fix their spelling: (table, old name, new name); the old
+/// names are still read as aliases and `pdw config-upgrade` rewrites them
+const RENAMED_KEYS: [(&str, &str, &str); 3] = [
+    ("settings", "run_dinamic_report", "run_dynamic_report"),
+    ("settings", "dayly_progress", "daily_progress"),
+    ("settings", "monthly_summaties", "monthly_summaries"),
+];
+
+/// Sections and keys read from INI files
+const INI_KEYS: [(&str, &[&str]); 3] = [
+    ("DIRECTORIES", &["DIR_IN", "DIR_OUT", "DATABASE_DIR", "LOG_DIR"]),
+    ("FILE_TYPES", &["TYPE_IN", "TYPE_OUT", "DB_FILE_TYPE", "LOG_FILE", "INPUT_FILE", "OUT_DB_FILE", "OUT_RPT_FILE"]),
+    ("SETTINGS", &[
+        "CURRENT_VERSION", "GUIDING_TABLE", "TYPES_OF_ENTRIES", "GENERAL_ENTRIES_TABLE", "RUN_DATA_LOADER",
+        "RUN_REPORTS", "OVERWRITE_DB", "CREATE_PIVOT", "MULTITHREADING", "YAML_SQL_FILE",
+    ]),
+];
+
+/// Derived column definition (`[[derived_columns]]` tables)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivedColumnConfig {
+    pub name: String,
+    pub expression: String,
 }
 
 /// Directory configuration
@@ -55,21 +233,118 @@ pub struct SettingsConfig {
     pub rpt_single_file: bool,
     pub parallels: Option<u32>,
     pub multithreading: bool,
+    /// Read for compatibility; rejected entries are always kept in `discarted_data_table`
     pub save_discarted_data: bool,
+    /// Entries the load rejects, with the reason and sheet row (see the quarantine module)
     pub discarted_data_table: String,
     pub anual_pivot_table: String,
     pub full_pivot_table: String,
-    pub run_dinamic_report: bool,
+    #[serde(alias = "run_dinamic_report")]
+    pub run_dynamic_report: bool,
     pub din_report_guiding: String,
     pub export_transient_data: bool,
     pub transient_data_table: Option<String>,
     pub transient_data_column: String,
     pub export_other_types: bool,
-    pub dayly_progress: String,
+    #[serde(alias = "dayly_progress")]
+    pub daily_progress: String,
     pub splt_paymnt_tab: String,
     pub out_res_pmnt_tab: String,
-    pub monthly_summaties: String,
+    #[serde(alias = "monthly_summaties")]
+    pub monthly_summaries: String,
     pub yaml_sql_file: String,
+    #[serde(default = "default_alerts_table")]
+    pub alerts_table: String,
+    #[serde(default)]
+    pub integrity_check: IntegrityCheck,
+    #[serde(default = "default_insert_batch_size")]
+    pub insert_batch_size: usize,
+    /// Entries read, transformed and inserted at a time per accounting sheet (0 = whole workbook at once)
+    #[serde(default)]
+    pub stream_chunk_rows: usize,
+    #[serde(default)]
+    pub collation: TextCollation,
+    #[serde(default = "default_true")]
+    pub check_consistency: bool,
+    #[serde(default = "default_consistency_table")]
+    pub consistency_table: String,
+    #[serde(default)]
+    pub report_engine: ReportEngine,
+    #[serde(default)]
+    pub parquet_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub load_mode: LoadMode,
+    #[serde(default = "default_currency_format")]
+    pub currency_format: String,
+    /// "decimal" (exact cents) or "float" (legacy f64 rounding and sums)
+    #[serde(default)]
+    pub money: MoneyMode,
+    /// Also write the general entries and dynamic reports as Parquet (`parquet` feature)
+    #[serde(default)]
+    pub export_parquet: bool,
+    /// Layout version of the JSON and XML exports (2 for readers of the unversioned layout)
+    #[serde(default = "default_export_schema_version")]
+    pub export_schema_version: u32,
+    /// Native Excel charts in the report workbook (built-in chart sheets and YAML `chart:`)
+    #[serde(default)]
+    pub generate_charts: bool,
+    /// "Dicionário" sheet closing the report workbook: each sheet's SQL, source tables,
+    /// row count and generation time
+    #[serde(default = "default_true")]
+    pub data_dictionary: bool,
+    /// "adapt" or "refuse" databases produced by the Python PDW
+    #[serde(default)]
+    pub python_databases: PythonDatabasePolicy,
+    /// Apply pending schema migrations when the pipeline opens the database
+    #[serde(default = "default_true")]
+    pub auto_migrate: bool,
+    /// Fill an empty types table with the TIPOs found in the accounting sheets
+    #[serde(default = "default_true")]
+    pub seed_types: bool,
+    /// Milliseconds a statement waits for a database locked by another program
+    #[serde(default = "default_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+    /// Check free disk space before the load and report phases
+    #[serde(default = "default_true")]
+    pub check_disk_space: bool,
+    /// Free space kept on top of the estimates, in MB
+    #[serde(default = "default_disk_space_margin_mb")]
+    pub disk_space_margin_mb: u64,
+    /// Export profile of the general entries export (see `[export_profiles]`)
+    #[serde(default)]
+    pub export_profile: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_export_schema_version() -> u32 {
+    crate::export_schema::EXPORT_SCHEMA_VERSION
+}
+
+fn default_currency_format() -> String {
+    "#,##0.00".to_string()
+}
+
+fn default_consistency_table() -> String {
+    "Consistencia".to_string()
+}
+
+fn default_insert_batch_size() -> usize {
+    crate::database::DEFAULT_INSERT_BATCH_SIZE
+}
+
+fn default_busy_timeout_ms() -> u64 {
+    crate::database::DEFAULT_BUSY_TIMEOUT_MS
+}
+
+fn default_disk_space_margin_mb() -> u64 {
+    64
+}
+
+fn default_alerts_table() -> String {
+    "ALERTAS".to_string()
 }
 
 impl Default for PdwConfig {
@@ -108,25 +383,144 @@ impl Default for PdwConfig {
                 discarted_data_table: "discarted_data".to_string(),
                 anual_pivot_table: "HistoricoAnual".to_string(),
                 full_pivot_table: "HistoricoGeral".to_string(),
-                run_dinamic_report: true,
+                run_dynamic_report: true,
                 din_report_guiding: "General_din_reports".to_string(),
                 export_transient_data: false,
                 transient_data_table: Some("Transient_data".to_string()),
                 transient_data_column: "Origem".to_string(),
                 export_other_types: false,
-                dayly_progress: "contagem_diaria".to_string(),
+                daily_progress: "contagem_diaria".to_string(),
                 splt_paymnt_tab: "PARCELAMENTOS".to_string(),
                 out_res_pmnt_tab: "Resumo_Parcelamentos".to_string(),
-                monthly_summaties: "Resumido_In_Out".to_string(),
+                monthly_summaries: "Resumido_In_Out".to_string(),
                 yaml_sql_file: "PDW_QUERIES.yaml".to_string(),
+                alerts_table: default_alerts_table(),
+                integrity_check: IntegrityCheck::Quick,
+                insert_batch_size: default_insert_batch_size(),
+                stream_chunk_rows: 0,
+                collation: TextCollation::Binary,
+                check_consistency: true,
+                consistency_table: default_consistency_table(),
+                report_engine: ReportEngine::Sqlite,
+                parquet_dir: None,
+                load_mode: LoadMode::Replace,
+                currency_format: default_currency_format(),
+                money: MoneyMode::default(),
+                export_parquet: false,
+                export_schema_version: default_export_schema_version(),
+                generate_charts: false,
+                data_dictionary: true,
+                python_databases: PythonDatabasePolicy::default(),
+                auto_migrate: true,
+                seed_types: true,
+                busy_timeout_ms: default_busy_timeout_ms(),
+                check_disk_space: true,
+                disk_space_margin_mb: default_disk_space_margin_mb(),
+                export_profile: None,
             },
+            derived_columns: Vec::new(),
+            alerts: Vec::new(),
+            notifications: NotificationConfig::default(),
+            type_normalization: TypeNormalizationConfig::default(),
+            csv: CsvInputConfig::default(),
+            database: DatabaseConfig::default(),
+            statement_card: StatementCardConfig::default(),
+            fx: FxConfig::default(),
+            currency: CurrencyConfig::default(),
+            benchmark: BenchmarkConfig::default(),
+            fees: FeeConfig::default(),
+            duplicates: DuplicatesConfig::default(),
+            forecast: ForecastConfig::default(),
+            cash_flow: CashFlowConfig::default(),
+            category_groups: Vec::new(),
+            income_share: IncomeShareConfig::default(),
+            analytics: AnalyticsConfig::default(),
+            budgets: BudgetConfig::default(),
+            baselines: BaselineConfig::default(),
+            opening_balances: OpeningBalanceConfig::default(),
+            reconciliation: ReconciliationConfig::default(),
+            splits: SplitConfig::default(),
+            tags: TagsConfig::default(),
+            quality: QualityConfig::default(),
+            non_data_rows: NonDataRowsConfig::default(),
+            locale: Locale::default(),
+            statement_cycles: StatementCycleConfig::default(),
+            categorization: CategorizationConfig::default(),
+            pdf_statements: PdfStatementConfig::default(),
+            branding: BrandingConfig::default(),
+            publish: PublishConfig::default(),
+            sanity: SanityConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            export_profiles: ExportProfiles::new(),
+            export: ExportConfig::default(),
+            sources: BTreeMap::new(),
+            warnings: Vec::new(),
         }
     }
 }
 
 impl PdwConfig {
-    /// Load configuration from TOML file
+    /// Load configuration from TOML file, with `PDW__` environment overrides applied
     pub fn load(path: &Path) -> Result<Self, PdwError> {
+        Self::load_with_overrides(path, &[])
+    }
+    
+    /// Load configuration and layer overrides over the file: `PDW__` environment
+    /// variables first, then `section.key=value` pairs (`--set`), which win
+    pub fn load_with_overrides(path: &Path, overrides: &[String]) -> Result<Self, PdwError> {
+        Self::load_with_profile(path, None, overrides)
+    }
+    
+    /// Load configuration with a profile's `[profile.<name>]` tables merged over the
+    /// base ones before the overrides; without `profile`, `PDW_PROFILE` names it
+    pub fn load_with_profile(path: &Path, profile: Option<&str>, overrides: &[String]) -> Result<Self, PdwError> {
+        let config = Self::load_file(path, selected_profile(profile).as_deref())?;
+        
+        let mut pairs = env_overrides(std::env::vars());
+        for assignment in overrides {
+            pairs.push(parse_override(assignment)?);
+        }
+        
+        config.with_overrides(&pairs)
+    }
+    
+    /// Apply dotted-key overrides (`settings.create_pivot`, `false`); values are read
+    /// as TOML (booleans, numbers, arrays) unless the current value is a string.
+    /// Keys the configuration does not know (typos) are rejected
+    pub fn with_overrides(mut self, overrides: &[(String, String)]) -> Result<Self, PdwError> {
+        if overrides.is_empty() {
+            return Ok(self);
+        }
+        
+        let warnings = std::mem::take(&mut self.warnings);
+        let mut root = toml::Value::try_from(&self).map_err(|e| ConfigError::InvalidFormat {
+            message: format!("Failed to serialize configuration: {}", e),
+        })?;
+        for (key, raw) in overrides {
+            set_override(&mut root, key, raw)?;
+        }
+        
+        let mut config: Self = root.try_into().map_err(|e: toml::de::Error| ConfigError::InvalidFormat {
+            message: format!("Invalid configuration override: {}", e.message()),
+        })?;
+        
+        // Unknown fields are dropped on deserialization, so a key missing from the
+        // round-tripped tree was never a configuration value
+        let known = toml::Value::try_from(&config).map_err(|e| ConfigError::InvalidFormat {
+            message: format!("Failed to serialize configuration: {}", e),
+        })?;
+        if let Some((key, _)) = overrides.iter().find(|(key, _)| !has_key(&known, key)) {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("Invalid override '{}': unknown configuration key", key),
+            }.into());
+        }
+        
+        config.warnings = warnings;
+        Ok(config)
+    }
+    
+    /// Load the configuration file alone (TOML, or INI for older installs)
+    fn load_file(path: &Path, profile: Option<&str>) -> Result<Self, PdwError> {
         if !path.exists() {
             return Err(ConfigError::FileNotFound {
                 path: path.to_string_lossy().to_string(),
@@ -138,12 +532,43 @@ impl PdwConfig {
                 message: format!("Failed to read file: {}", e),
             })?;
         
-        // Try TOML first
-        if let Ok(config) = toml::from_str::<PdwConfig>(&content) {
-            return Ok(config);
+        // Try TOML first, noting the keys serde skipped
+        let document = toml::from_str::<toml::Table>(&content);
+        let mut unused = Vec::new();
+        let parsed: Result<PdwConfig, _> = match (&document, profile) {
+            (Ok(table), Some(profile)) => serde_ignored::deserialize(
+                toml::Value::Table(merge_profile(table.clone(), profile)?),
+                |path| unused.push(path.to_string()),
+            ),
+            _ => serde_ignored::deserialize(
+                toml::Deserializer::new(&content),
+                |path| unused.push(path.to_string()),
+            ),
+        };
+        unused.retain(|key| key.split('.').next() != Some(PROFILE_TABLE));
+        match parsed {
+            Ok(mut config) => {
+                config.warnings = toml_warnings(&content, &unused);
+                return Ok(config);
+            }
+            // A TOML configuration with a wrong or missing key: no point in reading it as INI
+            Err(e) if document.as_ref()
+                .is_ok_and(|table| ["directories", "file_types", "settings"].iter().any(|key| table.contains_key(*key))) => {
+                let mut message = format!("{}: {}", path.display(), e.message().trim());
+                for warning in toml_warnings(&content, &unused) {
+                    message.push_str(&format!("; {}", warning));
+                }
+                return Err(ConfigError::InvalidFormat { message }.into());
+            }
+            Err(_) => {}
         }
         
         // If TOML fails, try INI format for backward compatibility
+        if let Some(profile) = profile {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("Profile '{}' selected, but {} is not a TOML configuration", profile, path.display()),
+            }.into());
+        }
         Self::load_from_ini(path)
     }
     
@@ -152,7 +577,7 @@ impl PdwConfig {
         let ini = ini::Ini::load_from_file(path)
             .map_err(|e| ConfigError::IniParse(e))?;
         
-        let mut config = PdwConfig::default();
+        let mut config = PdwConfig { warnings: ini_warnings(&ini), ..Default::default() };
         
         // Parse DIRECTORIES section
         if let Some(section) = ini.section(Some("DIRECTORIES")) {
@@ -236,6 +661,42 @@ impl PdwConfig {
         Ok(config)
     }
     
+    /// Rewrite a configuration file with the current key names. Renamed keys of a
+    /// TOML file are replaced in place, keeping comments and layout; an INI file is
+    /// converted to TOML, while a TOML file that does not parse is reported and left
+    /// untouched. The original is kept as `<file>.bak`. Returns the changes,
+    /// which are only written when `write` is set
+    pub fn upgrade_file(path: &Path, write: bool) -> Result<Vec<String>, PdwError> {
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::InvalidPath {
+            path: path.to_string_lossy().to_string(),
+            reason: e.to_string(),
+        })?;
+        
+        let (upgraded, changes) = match toml::from_str::<toml::Table>(&content) {
+            Ok(_) => rename_toml_keys(&content),
+            Err(e) if !looks_like_ini(&content) => {
+                return Err(ConfigError::InvalidFormat {
+                    message: format!("{}: {}", path.display(), e.message().trim()),
+                }.into());
+            }
+            Err(_) => {
+                let config = Self::load_from_ini(path)?;
+                let upgraded = toml::to_string_pretty(&config).map_err(|e| ConfigError::InvalidFormat {
+                    message: format!("Failed to serialize TOML: {}", e),
+                })?;
+                (upgraded, vec!["INI format converted to TOML".to_string()])
+            }
+        };
+        
+        if write && !changes.is_empty() {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(".bak");
+            fs::copy(path, &backup)?;
+            fs::write(path, upgraded)?;
+        }
+        Ok(changes)
+    }
+    
     /// Save configuration to TOML file
     pub fn save(&self, path: &Path) -> Result<(), PdwError> {
         let toml_content = toml::to_string_pretty(self)
@@ -263,23 +724,119 @@ impl PdwConfig {
         }
         
         // Validate directories exist or can be created
-        self.validate_directory(&self.directories.dir_in, "DIR_IN")?;
-        self.validate_directory(&self.directories.dir_out, "DIR_OUT")?;
-        self.validate_directory(&self.directories.database_dir, "DATABASE_DIR")?;
-        self.validate_directory(&self.directories.log_dir, "LOG_DIR")?;
+        self.create_directories()?;
+        
+        // Validate derived column expressions
+        self.compile_derived_columns()?;
+        
+        // Statement cycles need a real closing day and their own column
+        if let Some(origin) = self.statement_cycles.invalid_origins().first() {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("statement_cycles.closing_days.{} must be between 1 and 31", origin),
+            }.into());
+        }
+        if let Some(origin) = self.statement_cycles.due_without_closing().first() {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("statement_cycles.due_days.{} needs a closing day in statement_cycles.closing_days", origin),
+            }.into());
+        }
+        if self.statement_cycles.is_enabled()
+            && self.derived_columns.iter().any(|c| c.name.eq_ignore_ascii_case(&self.statement_cycles.column)) {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("Derived column '{}' clashes with statement_cycles.column", self.statement_cycles.column),
+            }.into());
+        }
+        if self.statement_cycles.has_due_dates()
+            && self.derived_columns.iter().any(|c| c.name.eq_ignore_ascii_case(&self.statement_cycles.due_column)) {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("Derived column '{}' clashes with statement_cycles.due_column", self.statement_cycles.due_column),
+            }.into());
+        }
+        
+        // Categorization adds its own category and tag columns
+        if self.categorization.enabled {
+            let columns = [&self.categorization.category_column, &self.categorization.tag_column];
+            if let Some(clash) = self.derived_columns.iter()
+                .find(|c| columns.iter().any(|column| c.name.eq_ignore_ascii_case(column))) {
+                return Err(ConfigError::InvalidFormat {
+                    message: format!("Derived column '{}' clashes with the categorization columns", clash.name),
+                }.into());
+            }
+        }
+        
+        // An external backend cannot be reached without a connection string
+        if self.database.backend == DatabaseBackend::Postgres && self.database.connection_string.is_none() {
+            return Err(ConfigError::MissingField {
+                field: "database.connection_string".to_string(),
+            }.into());
+        }
+        
+        if self.settings.export_parquet && !cfg!(feature = "parquet") {
+            return Err(ConfigError::InvalidFormat {
+                message: "settings.export_parquet requires a build with --features parquet".to_string(),
+            }.into());
+        }
+        
+        crate::export_schema::check_version(self.settings.export_schema_version)?;
+        crate::export_profiles::check(&self.export_profiles)?;
+        if let Some(profile) = &self.settings.export_profile {
+            crate::export_profiles::find(&self.export_profiles, profile)?;
+        }
+        self.export.compression.check()?;
+        self.analytics.exclusions.check(&self.category_groups)?;
+        self.locale.check()?;
+        self.csv.check()?;
+        
+        if self.baselines.enabled {
+            self.baselines.check()?;
+        }
+        
+        if self.publish.enabled {
+            self.publish.check()?;
+        }
+        
+        if self.telemetry.enabled {
+            self.telemetry.check()?;
+        }
+        
+        for (name, source) in self.sources.iter().filter(|(_, source)| source.enabled) {
+            let path = self.directories.dir_in.join(&source.path);
+            if !path.exists() {
+                return Err(ConfigError::InvalidPath {
+                    path: path.to_string_lossy().to_string(),
+                    reason: format!("Downloads of source '{}' do not exist", name),
+                }.into());
+            }
+        }
         
         // Validate input file exists
         let input_file = self.get_input_file_path();
         if !input_file.exists() {
             return Err(ConfigError::InvalidPath {
                 path: input_file.to_string_lossy().to_string(),
-                reason: "Input Excel file does not exist".to_string(),
+                reason: "Input workbook (or CSV directory) does not exist".to_string(),
             }.into());
         }
         
         Ok(())
     }
     
+    /// Compile derived column definitions into evaluable expressions
+    pub fn compile_derived_columns(&self) -> Result<Vec<DerivedColumn>, PdwError> {
+        let mut compiled: Vec<DerivedColumn> = Vec::new();
+        
+        for definition in &self.derived_columns {
+            if compiled.iter().any(|c| c.name.eq_ignore_ascii_case(&definition.name)) {
+                return Err(ConfigError::InvalidFormat {
+                    message: format!("Duplicate derived column '{}'", definition.name),
+                }.into());
+            }
+            compiled.push(DerivedColumn::compile(&definition.name, &definition.expression)?);
+        }
+        
+        Ok(compiled)
+    }
+    
     /// Validate a directory path
     fn validate_directory(&self, path: &Path, name: &str) -> Result<(), PdwError> {
         if !path.exists() {
@@ -305,6 +862,11 @@ impl PdwConfig {
     
     /// Get full input file path
     pub fn get_input_file_path(&self) -> PathBuf {
+        // CSV input is a directory holding one file per sheet
+        if self.file_types.type_in.eq_ignore_ascii_case("csv") {
+            return self.directories.dir_in.join(&self.file_types.input_file);
+        }
+        
         self.directories.dir_in.join(format!(
             "{}.{}",
             self.file_types.input_file,
@@ -312,6 +874,21 @@ impl PdwConfig {
         ))
     }
     
+    /// Get exchange-rate cache path
+    pub fn get_fx_cache_path(&self) -> PathBuf {
+        self.directories.database_dir.join(&self.fx.cache_file)
+    }
+    
+    /// Get the local socket path of `pdw daemon`, next to the database
+    pub fn get_daemon_socket_path(&self) -> PathBuf {
+        self.directories.database_dir.join(format!("{}.sock", self.file_types.out_db_file))
+    }
+    
+    /// Get upload progress file path
+    pub fn get_publish_state_path(&self) -> PathBuf {
+        self.directories.database_dir.join(&self.publish.state_file)
+    }
+    
     /// Get full database file path
     pub fn get_database_path(&self) -> PathBuf {
         let filename = if self.settings.overwrite_db {
@@ -324,21 +901,309 @@ impl PdwConfig {
         self.directories.database_dir.join(filename)
     }
     
+    /// Configuration writing to the database at `path` (overwritten, not timestamped)
+    pub fn for_database(&self, path: &Path) -> Self {
+        let mut config = self.clone();
+        config.directories.database_dir = path.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."));
+        config.file_types.out_db_file = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        if let Some(extension) = path.extension() {
+            config.file_types.db_file_type = extension.to_string_lossy().to_string();
+        }
+        config.settings.overwrite_db = true;
+        config
+    }
+    
+    /// Get path for a database salvaged by `pdw repair`
+    pub fn get_recovered_database_path(&self, source: &Path) -> PathBuf {
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+        source.with_file_name(format!("{}.recovered.{}", stem, self.file_types.db_file_type))
+    }
+    
     /// Get full log file path
     pub fn get_log_file_path(&self) -> PathBuf {
         self.directories.log_dir.join(&self.file_types.log_file)
     }
     
+    /// Get report workbook path
+    pub fn get_report_path(&self) -> PathBuf {
+        self.directories.dir_out.join(format!(
+            "{}.{}",
+            self.file_types.out_rpt_file,
+            self.file_types.type_out
+        ))
+    }
+    
     /// Get YAML queries file path
     pub fn get_yaml_queries_path(&self) -> PathBuf {
         self.directories.dir_in.join(&self.settings.yaml_sql_file)
     }
     
+    /// Get categorization rules file path, if one is configured
+    pub fn get_category_rules_path(&self) -> Option<PathBuf> {
+        self.categorization.rules_file.as_ref().map(|file| self.directories.dir_in.join(file))
+    }
+    
+    /// Get default path for the regenerated master workbook
+    pub fn get_clean_workbook_path(&self) -> PathBuf {
+        self.directories.dir_out.join(format!("{}.clean.xlsx", self.file_types.input_file))
+    }
+    
     /// Create a sample TOML configuration file
     pub fn create_sample_config(path: &Path) -> Result<(), PdwError> {
         let config = PdwConfig::default();
         config.save(path)
     }
+    
+    /// Create the input, output, database and log directories
+    pub fn create_directories(&self) -> Result<(), PdwError> {
+        self.validate_directory(&self.directories.dir_in, "DIR_IN")?;
+        self.validate_directory(&self.directories.dir_out, "DIR_OUT")?;
+        self.validate_directory(&self.directories.database_dir, "DATABASE_DIR")?;
+        self.validate_directory(&self.directories.log_dir, "LOG_DIR")?;
+        Ok(())
+    }
+}
+
+/// Warnings for the keys skipped while reading a TOML file and the deprecated ones it sets
+fn toml_warnings(content: &str, unused: &[String]) -> Vec<ConfigWarning> {
+    let defaults = toml::Value::try_from(PdwConfig::default()).ok();
+    let mut warnings: Vec<ConfigWarning> = unused.iter()
+        .map(|key| {
+            let (parent, name) = key.rsplit_once('.').unwrap_or(("", key));
+            // Valid keys next to the unused one, as far as the defaults spell them out
+            let siblings: Vec<String> = defaults.as_ref()
+                .and_then(|root| parent.split('.').filter(|p| !p.is_empty()).try_fold(root, |value, part| value.get(part)))
+                .and_then(toml::Value::as_table)
+                .map(|table| table.keys().cloned().collect())
+                .unwrap_or_default();
+            let suggestion = nearest_key(name, siblings.iter().map(String::as_str))
+                .map(|nearest| if parent.is_empty() { nearest.to_string() } else { format!("{}.{}", parent, nearest) });
+            ConfigWarning::Unused { key: key.clone(), suggestion }
+        })
+        .collect();
+    
+    if let Ok(root) = toml::from_str::<toml::Value>(content) {
+        for (key, replacement) in DEPRECATED_KEYS {
+            if key.split('.').try_fold(&root, |value, part| value.get(part)).is_some() {
+                warnings.push(ConfigWarning::Deprecated { key: key.to_string(), replacement: replacement.to_string() });
+            }
+        }
+        for (table, old, new) in RENAMED_KEYS {
+            if root.get(table).and_then(|value| value.get(old)).is_some() {
+                warnings.push(ConfigWarning::Deprecated {
+                    key: format!("{}.{}", table, old),
+                    replacement: format!("{}.{} (pdw config-upgrade renames it)", table, new),
+                });
+            }
+        }
+    }
+    
+    warnings
+}
+
+/// Profile to load: the one given, else the one `PDW_PROFILE` names
+pub fn selected_profile(profile: Option<&str>) -> Option<String> {
+    profile.map(str::to_string)
+        .or_else(|| std::env::var(PROFILE_ENV).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Configuration tables of a TOML file with a profile's tables merged over them; the
+/// `[profile.*]` tables themselves are dropped
+fn merge_profile(mut table: toml::Table, name: &str) -> Result<toml::Table, PdwError> {
+    let profiles = table.remove(PROFILE_TABLE);
+    let overlay = profiles.as_ref()
+        .and_then(|profiles| profiles.get(name))
+        .and_then(toml::Value::as_table)
+        .ok_or_else(|| {
+            let available: Vec<&str> = profiles.as_ref()
+                .and_then(toml::Value::as_table)
+                .map(|profiles| profiles.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            ConfigError::InvalidFormat {
+                message: format!("Profile '{}' not found in the configuration (profiles: {})", name,
+                                 if available.is_empty() { "none".to_string() } else { available.join(", ") }),
+            }
+        })?;
+    merge_tables(&mut table, overlay.clone());
+    Ok(table)
+}
+
+/// Merge keys into a table: nested tables key by key, any other value replaced whole
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge_tables(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Whether text that is not TOML reads as an INI file: section headers and
+/// `key = value` lines with bare values (`DIR_OUT = ./output/`, `RUN = True`)
+fn looks_like_ini(content: &str) -> bool {
+    let lines: Vec<&str> = content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .collect();
+    let sections = lines.iter().any(|line| line.starts_with('[') && line.ends_with(']'));
+    let bare_values = lines.iter()
+        .filter_map(|line| line.split_once('='))
+        .map(|(_, value)| value.trim())
+        .any(|value| {
+            !value.starts_with(['"', '\'', '[', '{'])
+                && toml::from_str::<toml::Table>(&format!("value = {}", value)).is_err()
+        });
+    sections && bare_values
+}
+
+/// Replace the renamed keys of a TOML document, line by line within their table
+fn rename_toml_keys(content: &str) -> (String, Vec<String>) {
+    let mut table = String::new();
+    let mut changes = Vec::new();
+    let mut lines = Vec::new();
+    
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            table = trimmed.trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+        }
+        
+        let key = trimmed.split('=').next().unwrap_or_default().trim().trim_matches('"');
+        let renamed = RENAMED_KEYS.iter()
+            .find(|(section, old, _)| *section == table && *old == key && trimmed.contains('='));
+        match renamed {
+            Some((section, old, new)) => {
+                lines.push(line.replacen(old, new, 1));
+                changes.push(format!("{}.{} renamed to {}.{}", section, old, section, new));
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+    
+    (lines.concat(), changes)
+}
+
+/// Warnings for the INI sections and keys the loader does not read
+fn ini_warnings(ini: &ini::Ini) -> Vec<ConfigWarning> {
+    let mut warnings = Vec::new();
+    
+    for (section, properties) in ini.iter() {
+        let Some(section) = section else {
+            continue;
+        };
+        let Some((_, keys)) = INI_KEYS.iter().find(|(name, _)| *name == section) else {
+            let suggestion = nearest_key(section, INI_KEYS.iter().map(|(name, _)| *name)).map(str::to_string);
+            warnings.push(ConfigWarning::Unused { key: format!("[{}]", section), suggestion: suggestion.map(|s| format!("[{}]", s)) });
+            continue;
+        };
+        
+        for (key, _) in properties.iter().filter(|(key, _)| !keys.contains(key)) {
+            let suggestion = nearest_key(key, keys.iter().copied()).map(|nearest| format!("{}.{}", section, nearest));
+            warnings.push(ConfigWarning::Unused { key: format!("{}.{}", section, key), suggestion });
+        }
+    }
+    
+    warnings
+}
+
+/// Candidate closest to a misspelled key, if close enough to be a typo
+fn nearest_key<'a, I: IntoIterator<Item = &'a str>>(key: &str, candidates: I) -> Option<&'a str> {
+    let key = key.to_lowercase();
+    let limit = (key.chars().count() / 3).max(2);
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(&key, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings, by character
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    
+    for (i, left) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, right) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(left != *right);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    
+    previous[b.len()]
+}
+
+/// Dotted-key overrides from `PDW__SECTION__KEY` variables, sorted by key
+fn env_overrides<I: IntoIterator<Item = (String, String)>>(vars: I) -> Vec<(String, String)> {
+    let mut overrides: Vec<(String, String)> = vars.into_iter()
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(ENV_OVERRIDE_PREFIX)?;
+            Some((key.to_lowercase().replace("__", "."), value))
+        })
+        .collect();
+    overrides.sort();
+    overrides
+}
+
+/// Split a `section.key=value` assignment
+fn parse_override(assignment: &str) -> Result<(String, String), PdwError> {
+    match assignment.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(ConfigError::InvalidFormat {
+            message: format!("Invalid override '{}', expected section.key=value", assignment),
+        }.into()),
+    }
+}
+
+/// Whether a dotted key names a value in the configuration tree
+fn has_key(root: &toml::Value, key: &str) -> bool {
+    key.split('.')
+        .try_fold(root, |value, part| value.as_table()?.get(part.trim()))
+        .is_some()
+}
+
+/// Set a dotted key in the configuration tree, creating missing sections
+fn set_override(root: &mut toml::Value, key: &str, raw: &str) -> Result<(), PdwError> {
+    let invalid = |reason: &str| PdwError::from(ConfigError::InvalidFormat {
+        message: format!("Invalid override '{}': {}", key, reason),
+    });
+    
+    let parts: Vec<&str> = key.split('.').map(str::trim).collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(invalid("empty key segment"));
+    }
+    let (name, sections) = parts.split_last().expect("split yields at least one part");
+    
+    let mut table = root.as_table_mut().ok_or_else(|| invalid("configuration is not a table"))?;
+    for section in sections {
+        table = table.entry(section.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::map::Map::new()))
+            .as_table_mut()
+            .ok_or_else(|| invalid(&format!("'{}' is not a section", section)))?;
+    }
+    
+    let value = match table.get(*name) {
+        Some(toml::Value::String(_)) => toml::Value::String(raw.to_string()),
+        _ => toml::from_str::<toml::Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut parsed| parsed.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(raw.to_string())),
+    };
+    table.insert(name.to_string(), value);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -398,6 +1263,75 @@ RUN_DATA_LOADER = True
         let config = PdwConfig::load_from_ini(&ini_path).unwrap();
         assert_eq!(config.settings.current_version, "9.11.0");
         assert!(config.settings.run_data_loader);
+        assert!(config.warnings.is_empty());
+    }
+    
+    #[test]
+    fn test_unused_and_deprecated_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("pdw_config.toml");
+        let mut content = toml::to_string(&PdwConfig::default()).unwrap();
+        content = content.replace("insert_batch_size =", "insert_batch_sise =")
+            .replace("group_by_cycle = false", "group_by_cycle = true");
+        content.push_str("\n[relatorios]\nativo = true\n");
+        fs::write(&config_path, &content).unwrap();
+        
+        let config = PdwConfig::load(&config_path).unwrap();
+        assert_eq!(config.warnings, vec![
+            ConfigWarning::Unused {
+                key: "settings.insert_batch_sise".to_string(),
+                suggestion: Some("settings.insert_batch_size".to_string()),
+            },
+            ConfigWarning::Unused {
+                key: "relatorios".to_string(),
+                suggestion: None,
+            },
+            ConfigWarning::Deprecated {
+                key: "statement_cycles.group_by_cycle".to_string(),
+                replacement: "statement_cycles.basis = \"statement\"".to_string(),
+            },
+        ]);
+        assert_eq!(
+            config.warnings[0].to_string(),
+            "unused key 'settings.insert_batch_sise' - did you mean 'settings.insert_batch_size'?"
+        );
+        // Overrides keep the warnings of the file
+        let overridden = config.with_overrides(&[("settings.create_pivot".to_string(), "false".to_string())]).unwrap();
+        assert_eq!(overridden.warnings.len(), 3);
+        
+        // A misspelled required key fails the load, naming the likely fix
+        fs::write(&config_path, content.replace("run_dynamic_report =", "run_dynamic_reprot =")).unwrap();
+        let error = PdwConfig::load(&config_path).unwrap_err().to_string();
+        assert!(error.contains("missing field `run_dynamic_report`"), "{}", error);
+        assert!(error.contains("did you mean 'settings.run_dynamic_report'?"), "{}", error);
+        
+        let ini_path = temp_dir.path().join("pdw.cfg");
+        fs::write(&ini_path, "[SETTINGS]\nRUN_REPORT = False\nParallels = 4\n[EXTRAS]\nX = 1\n").unwrap();
+        let warnings: Vec<String> = PdwConfig::load_from_ini(&ini_path).unwrap().warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, vec![
+            "unused key 'SETTINGS.RUN_REPORT' - did you mean 'SETTINGS.RUN_REPORTS'?",
+            "unused key 'SETTINGS.Parallels'",
+            "unused key '[EXTRAS]'",
+        ]);
+    }
+    
+    #[test]
+    fn test_derived_columns_parsing() {
+        let mut config = PdwConfig::default();
+        config.derived_columns.push(DerivedColumnConfig {
+            name: "Liquido".to_string(),
+            expression: "Credito - Debito".to_string(),
+        });
+        
+        let toml_str = toml::to_string(&config).unwrap();
+        let parsed: PdwConfig = toml::from_str(&toml_str).unwrap();
+        assert_eq!(parsed.compile_derived_columns().unwrap().len(), 1);
+        
+        config.derived_columns.push(DerivedColumnConfig {
+            name: "liquido".to_string(),
+            expression: "Credito".to_string(),
+        });
+        assert!(config.compile_derived_columns().is_err());
     }
     
     #[test]
@@ -408,5 +1342,121 @@ RUN_DATA_LOADER = True
         
         let db_path = config.get_database_path();
         assert!(db_path.to_string_lossy().contains(".db"));
+        
+        let combined = Path::new("family").join("combined.sqlite");
+        assert_eq!(config.for_database(&combined).get_database_path(), combined);
+        assert_eq!(config.for_database(Path::new("combined.db")).get_database_path(), Path::new(".").join("combined.db"));
+    }
+    
+    #[test]
+    fn test_cli_and_env_overrides() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("pdw_config.toml");
+        PdwConfig::default().save(&config_path).unwrap();
+        
+        let overrides = vec![
+            "settings.create_pivot=false".to_string(),
+            "settings.insert_batch_size = 250".to_string(),
+            "directories.dir_out=./saida".to_string(),
+        ];
+        let config = PdwConfig::load_with_overrides(&config_path, &overrides).unwrap();
+        assert!(!config.settings.create_pivot);
+        assert_eq!(config.settings.insert_batch_size, 250);
+        assert_eq!(config.directories.dir_out, PathBuf::from("./saida"));
+        
+        let env = vec![
+            ("PDW__SETTINGS__RUN_REPORTS".to_string(), "false".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+        ];
+        let pairs = env_overrides(env);
+        assert_eq!(pairs, vec![("settings.run_reports".to_string(), "false".to_string())]);
+        assert!(!PdwConfig::default().with_overrides(&pairs).unwrap().settings.run_reports);
+    }
+    
+    #[test]
+    fn test_profile_merged_over_base() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("pdw_config.toml");
+        let mut content = toml::to_string_pretty(&PdwConfig::default()).unwrap();
+        content.push_str("\n[profile.test.directories]\ndir_out = \"./saida_teste\"\n\n[profile.test.settings]\nrun_reports = false\n\n\
+                          [profile.prod.settings]\ninsert_batch_size = 5000\n");
+        fs::write(&config_path, content).unwrap();
+        
+        let base = PdwConfig::load_with_profile(&config_path, None, &[]).unwrap();
+        assert!(base.settings.run_reports);
+        // The profile tables are not unused keys
+        assert!(!base.warnings.iter().any(|warning| matches!(warning, ConfigWarning::Unused { .. })));
+        
+        let test = PdwConfig::load_with_profile(&config_path, Some("test"), &["settings.insert_batch_size=10".to_string()]).unwrap();
+        assert_eq!(test.directories.dir_out, PathBuf::from("./saida_teste"));
+        assert!(!test.settings.run_reports);
+        assert_eq!(test.settings.insert_batch_size, 10);
+        // Keys a profile leaves out come from the base tables
+        assert_eq!(test.directories.dir_in, base.directories.dir_in);
+        assert_eq!(PdwConfig::load_with_profile(&config_path, Some("prod"), &[]).unwrap().settings.insert_batch_size, 5000);
+        
+        let missing = PdwConfig::load_with_profile(&config_path, Some("dev"), &[]).unwrap_err();
+        assert!(missing.to_string().contains("Profile 'dev' not found in the configuration (profiles: prod, test)"));
+    }
+    
+    #[test]
+    fn test_invalid_overrides() {
+        assert!(parse_override("settings.create_pivot").is_err());
+        assert!(parse_override("=true").is_err());
+        
+        let config = PdwConfig::default();
+        let wrong_type = vec![("settings.create_pivot".to_string(), "maybe".to_string())];
+        assert!(config.clone().with_overrides(&wrong_type).is_err());
+        let not_a_section = vec![("settings.create_pivot.x".to_string(), "1".to_string())];
+        assert!(config.clone().with_overrides(&not_a_section).is_err());
+        
+        let typo = vec![("settings.create_pivto".to_string(), "false".to_string())];
+        let error = config.clone().with_overrides(&typo).unwrap_err();
+        assert!(error.to_string().contains("settings.create_pivto"));
+        let unknown_section = vec![("setings.run_reports".to_string(), "false".to_string())];
+        assert!(config.with_overrides(&unknown_section).is_err());
+    }
+    
+    #[test]
+    fn test_upgrade_renamed_keys() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("old.toml");
+        let mut content = toml::to_string_pretty(&PdwConfig::default()).unwrap()
+            .replace("run_dynamic_report =", "run_dinamic_report =")
+            .replace("daily_progress = \"contagem_diaria\"", "# dias\ndayly_progress = \"progresso\"")
+            .replace("monthly_summaries =", "monthly_summaties =");
+        content.push_str("\n[relatorios]\nmonthly_summaties = 1\n");
+        fs::write(&config_path, &content).unwrap();
+        
+        // The old spellings still load, flagged as deprecated
+        let config = PdwConfig::load(&config_path).unwrap();
+        assert_eq!(config.settings.daily_progress, "progresso");
+        assert!(config.warnings.contains(&ConfigWarning::Deprecated {
+            key: "settings.dayly_progress".to_string(),
+            replacement: "settings.daily_progress (pdw config-upgrade renames it)".to_string(),
+        }));
+        
+        assert_eq!(PdwConfig::upgrade_file(&config_path, false).unwrap().len(), 3);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), content);
+        
+        let changes = PdwConfig::upgrade_file(&config_path, true).unwrap();
+        assert_eq!(changes[1], "settings.dayly_progress renamed to settings.daily_progress");
+        let upgraded = fs::read_to_string(&config_path).unwrap();
+        assert!(upgraded.contains("# dias\ndaily_progress = \"progresso\""));
+        // Keys of other tables are left alone
+        assert!(upgraded.contains("[relatorios]\nmonthly_summaties = 1"));
+        assert_eq!(fs::read_to_string(temp_dir.path().join("old.toml.bak")).unwrap(), content);
+        
+        let config = PdwConfig::load(&config_path).unwrap();
+        assert_eq!(config.settings.daily_progress, "progresso");
+        assert!(!config.warnings.iter().any(|w| w.to_string().contains("config-upgrade")));
+        assert!(PdwConfig::upgrade_file(&config_path, true).unwrap().is_empty());
+        
+        // A TOML file with a syntax error is reported, not converted as INI
+        let broken = upgraded.replace("daily_progress = \"progresso\"", "daily_progress = \"progresso");
+        fs::write(&config_path, &broken).unwrap();
+        let error = PdwConfig::upgrade_file(&config_path, true).unwrap_err();
+        assert!(error.to_string().contains("old.toml"), "{}", error);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), broken);
     }
 }
\ No newline at end of file
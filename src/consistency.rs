@@ -0,0 +1,199 @@
+/*!
+# Consistency Module
+
+Cross-checks the workbook against its own metadata: GUIDING rows pointing at
+sheets that do not exist, sheets that GUIDING does not mention, and
+TiposLancamentos codes never used by any entry. Findings are logged and stored
+in a table that the reporting phase exports as the "Consistencia" sheet.
+*/
+
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, PdwError};
+use crate::excel::SheetConfig;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Kind of inconsistency found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// Listed in GUIDING but absent from the workbook
+    MissingSheet,
+    /// Present in the workbook but not listed in GUIDING
+    UnlistedSheet,
+    /// Defined in TiposLancamentos but never used by an entry
+    UnusedType,
+}
+
+impl IssueKind {
+    /// Label used in the report table
+    pub fn label(&self) -> &'static str {
+        match self {
+            IssueKind::MissingSheet => "Aba do GUIDING inexistente",
+            IssueKind::UnlistedSheet => "Aba fora do GUIDING",
+            IssueKind::UnusedType => "Tipo sem lancamentos",
+        }
+    }
+}
+
+/// Result of the consistency check
+#[derive(Debug, Clone, Default)]
+pub struct ConsistencyReport {
+    pub missing_sheets: Vec<String>,
+    pub unlisted_sheets: Vec<String>,
+    pub unused_types: Vec<String>,
+}
+
+impl ConsistencyReport {
+    /// Compare GUIDING entries with the sheets actually present in the workbook
+    pub fn check_sheets(sheet_names: &[String], guiding: &[SheetConfig], guiding_table: &str) -> Self {
+        let present: HashSet<&str> = sheet_names.iter().map(|s| s.trim()).collect();
+        let listed: HashSet<&str> = guiding.iter().map(|c| c.table_name.trim()).collect();
+        
+        let missing_sheets = guiding.iter()
+            .map(|c| c.table_name.trim())
+            .filter(|name| !present.contains(name))
+            .map(String::from)
+            .collect();
+        
+        let unlisted_sheets = sheet_names.iter()
+            .map(|s| s.trim())
+            .filter(|name| *name != guiding_table && !listed.contains(name))
+            .map(String::from)
+            .collect();
+        
+        Self { missing_sheets, unlisted_sheets, unused_types: Vec::new() }
+    }
+    
+    /// Find type codes with no entry whose TIPO matches the code or its description
+    pub fn check_unused_types(&mut self, database: &DatabaseManager, types_table: &str, 
+                              entries_table: &str) -> Result<(), PdwError> {
+        let used: HashSet<String> = database
+            .execute_query(&format!("SELECT DISTINCT TIPO FROM {}", entries_table))?
+            .into_iter()
+            .filter_map(|row| match row.first() {
+                Some(Value::String(tipo)) => Some(tipo.trim().to_uppercase()),
+                _ => None,
+            })
+            .collect();
+        
+        let types = database.execute_query(&format!("SELECT * FROM {}", types_table))?;
+        
+        self.unused_types = types.iter()
+            .filter_map(|row| {
+                let code = cell_text(row.first())?;
+                let description = cell_text(row.get(1));
+                let is_used = used.contains(&code.to_uppercase())
+                    || description.is_some_and(|d| used.contains(&d.to_uppercase()));
+                (!is_used).then_some(code)
+            })
+            .collect();
+        
+        Ok(())
+    }
+    
+    /// True when no inconsistency was found
+    pub fn is_clean(&self) -> bool {
+        self.missing_sheets.is_empty() && self.unlisted_sheets.is_empty() && self.unused_types.is_empty()
+    }
+    
+    /// All findings as (kind, item) pairs
+    pub fn issues(&self) -> Vec<(IssueKind, &str)> {
+        let missing = self.missing_sheets.iter().map(|s| (IssueKind::MissingSheet, s.as_str()));
+        let unlisted = self.unlisted_sheets.iter().map(|s| (IssueKind::UnlistedSheet, s.as_str()));
+        let unused = self.unused_types.iter().map(|s| (IssueKind::UnusedType, s.as_str()));
+        missing.chain(unlisted).chain(unused).collect()
+    }
+    
+    /// Print findings to the console log
+    pub fn log(&self) {
+        if self.is_clean() {
+            tracing::info!("Consistency check: no issues found");
+            return;
+        }
+        
+        for (kind, item) in self.issues() {
+            tracing::warn!("Consistency: {} -> {}", kind.label(), item);
+        }
+    }
+    
+    /// Store findings in the report table
+    pub fn write_table(&self, database: &DatabaseManager, table: &str) -> Result<usize, PdwError> {
+        database.drop_table(table)?;
+        
+        let create_query = format!("CREATE TABLE {} (Verificacao TEXT, Item TEXT)", table);
+        database.connection().execute(&create_query, [])
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: create_query.clone(),
+                reason: e.to_string(),
+            })?;
+        
+        let insert_query = format!("INSERT INTO {} (Verificacao, Item) VALUES (?1, ?2)", table);
+        let issues = self.issues();
+        
+        for (kind, item) in &issues {
+            database.connection()
+                .execute(&insert_query, rusqlite::params![kind.label(), item])
+                .map_err(|e| DatabaseError::DataInsertion {
+                    table: table.to_string(),
+                    reason: e.to_string(),
+                })?;
+        }
+        
+        Ok(issues.len())
+    }
+}
+
+/// Non-empty trimmed text of a query cell
+fn cell_text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn sheet(name: &str) -> SheetConfig {
+        SheetConfig { table_name: name.to_string(), is_accounting: true, is_loadable: true }
+    }
+    
+    #[test]
+    fn test_sheet_check() {
+        let sheet_names = vec!["GUIDING".to_string(), "Conta".to_string(), "Rascunho".to_string()];
+        let guiding = vec![sheet("Conta"), sheet("Cartao ")];
+        
+        let report = ConsistencyReport::check_sheets(&sheet_names, &guiding, "GUIDING");
+        assert_eq!(report.missing_sheets, vec!["Cartao"]);
+        assert_eq!(report.unlisted_sheets, vec!["Rascunho"]);
+        assert!(!report.is_clean());
+    }
+    
+    #[test]
+    fn test_unused_types_and_table() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.insert_reference_data("TiposLancamentos", &[
+            vec!["Código".to_string(), "Descrição".to_string()],
+            vec!["ALM".to_string(), "Alimentação".to_string()],
+            vec!["LAZ".to_string(), "Lazer".to_string()],
+            vec!["SAU".to_string(), "Saúde".to_string()],
+        ]).unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Origem) VALUES ('2024-01-15', 'alm', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Origem) VALUES ('2024-01-16', 'Lazer', 'Conta');"
+        ).unwrap();
+        
+        let mut report = ConsistencyReport::default();
+        report.check_unused_types(&db, "TiposLancamentos", "LANCAMENTOS_GERAIS").unwrap();
+        assert_eq!(report.unused_types, vec!["SAU"]);
+        
+        assert_eq!(report.write_table(&db, "Consistencia").unwrap(), 1);
+        let rows = db.execute_query("SELECT Verificacao, Item FROM Consistencia").unwrap();
+        assert_eq!(rows[0][1], Value::String("SAU".to_string()));
+    }
+}
\ No newline at end of file
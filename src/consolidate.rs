@@ -0,0 +1,264 @@
+/*!
+# Consolidation Module
+
+Merges several warehouse databases (one per family member, say) into a
+combined one for `pdw consolidate`. The general entries of every source are
+appended to the combined entries table with a source column naming the
+database they came from; the types table, GUIDING and the reference tables it
+lists are unioned, each row kept once. Generated tables (pivots, summaries, run
+history) are not copied: they are rebuilt from the merged entries afterwards.
+*/
+
+use crate::config::SettingsConfig;
+use crate::database::{quote_identifier, DatabaseManager};
+use crate::error::{DatabaseError, PdwError};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Column of the combined entries table holding the source of each entry
+pub const SOURCE_COLUMN: &str = "Fonte";
+
+/// Schema name each source is attached under while it is merged
+const SOURCE_SCHEMA: &str = "pdw_source";
+
+/// Rows merged from one source database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceSummary {
+    pub path: PathBuf,
+    /// Value of the source column for its entries (the file name without extension)
+    pub label: String,
+    pub entries: usize,
+    pub reference_rows: usize,
+}
+
+/// Merge the `sources` into `target`, in order
+pub fn consolidate(target: &DatabaseManager, sources: &[PathBuf], settings: &SettingsConfig) -> Result<Vec<SourceSummary>, PdwError> {
+    target.create_tables()?;
+    target.add_derived_columns(&settings.general_entries_table, &[(SOURCE_COLUMN.to_string(), "TEXT")])?;
+    
+    let mut labels = BTreeSet::new();
+    let mut summaries = Vec::new();
+    for path in sources {
+        if !path.is_file() {
+            return Err(DatabaseError::ConnectionFailed {
+                path: path.to_string_lossy().to_string(),
+                reason: "Database to consolidate not found".to_string(),
+            }.into());
+        }
+        
+        let label = unique_label(path, &mut labels);
+        execute(target, &format!("ATTACH DATABASE ?1 AS {}", SOURCE_SCHEMA), [path.to_string_lossy()])?;
+        let merged = merge_source(target, settings, &label);
+        execute(target, &format!("DETACH DATABASE {}", SOURCE_SCHEMA), [])?;
+        
+        let (entries, reference_rows) = merged?;
+        summaries.push(SourceSummary { path: path.clone(), label, entries, reference_rows });
+    }
+    
+    Ok(summaries)
+}
+
+/// File name without extension, suffixed when another source already uses it
+fn unique_label(path: &Path, labels: &mut BTreeSet<String>) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let label = (1..)
+        .map(|n| if n == 1 { stem.clone() } else { format!("{}-{}", stem, n) })
+        .find(|label| !labels.contains(label))
+        .unwrap_or(stem);
+    labels.insert(label.clone());
+    label
+}
+
+/// Copy the entries and reference rows of the attached source; returns both counts
+fn merge_source(target: &DatabaseManager, settings: &SettingsConfig, label: &str) -> Result<(usize, usize), PdwError> {
+    let tables = source_tables(target)?;
+    let entries_table = &settings.general_entries_table;
+    
+    let entries = if tables.contains(entries_table) {
+        merge_entries(target, entries_table, label)?
+    } else {
+        tracing::warn!("{} has no {} table - no entries merged", label, entries_table);
+        0
+    };
+    
+    let mut reference_tables = vec![settings.types_of_entries.clone(), settings.guiding_table.clone()];
+    if tables.contains(&settings.guiding_table) {
+        let query = format!(
+            "SELECT TABLE_NAME FROM {}.{} WHERE UPPER(TRIM(LOADABLE)) = 'X' AND UPPER(TRIM(COALESCE(ACCOUNTING, ''))) <> 'X'",
+            SOURCE_SCHEMA, quote_identifier(&settings.guiding_table)
+        );
+        reference_tables.extend(target.execute_query(&query)?.into_iter()
+            .filter_map(|row| match row.into_iter().next() {
+                Some(Value::String(name)) => Some(name),
+                _ => None,
+            }));
+    }
+    
+    let mut reference_rows = 0;
+    let mut merged = BTreeSet::new();
+    for table in reference_tables {
+        if table != *entries_table && tables.contains(&table) && merged.insert(table.clone()) {
+            // TiposLancamentos rows are the pivot columns: one per Descrição
+            let key = (table == settings.types_of_entries).then_some("Descrição");
+            reference_rows += merge_reference(target, &table, key)?;
+        }
+    }
+    
+    Ok((entries, reference_rows))
+}
+
+/// Append the source entries, labelled with the source unless they already carry one
+fn merge_entries(target: &DatabaseManager, table: &str, label: &str) -> Result<usize, PdwError> {
+    // Columns only some members have (derived, categorization, currency) are added
+    let schema = table_schema(target, SOURCE_SCHEMA, table)?;
+    let added: Vec<(String, &str)> = schema.iter().map(|(name, sql_type)| (name.clone(), sql_type.as_str())).collect();
+    target.add_derived_columns(table, &added)?;
+    
+    let (labelled, columns): (Vec<&String>, Vec<&String>) = schema.iter()
+        .map(|(name, _)| name)
+        .partition(|name| name.eq_ignore_ascii_case(SOURCE_COLUMN));
+    let column_list = columns.iter().map(|name| quote_identifier(name)).collect::<Vec<_>>().join(", ");
+    let source_value = if labelled.is_empty() {
+        "?1".to_string()
+    } else {
+        format!("COALESCE({}, ?1)", quote_identifier(SOURCE_COLUMN))
+    };
+    
+    let query = format!(
+        "INSERT INTO main.{table} ({columns}, {source}) SELECT {columns}, {value} FROM {schema}.{table}",
+        table = quote_identifier(table),
+        columns = column_list,
+        source = quote_identifier(SOURCE_COLUMN),
+        value = source_value,
+        schema = SOURCE_SCHEMA,
+    );
+    execute(target, &query, [label])
+}
+
+/// Add the source rows missing from the combined table, matched on `key` or on every shared column
+fn merge_reference(target: &DatabaseManager, table: &str, key: Option<&str>) -> Result<usize, PdwError> {
+    let quoted = quote_identifier(table);
+    // Qualified: unqualified names would also find the attached source's table
+    if table_schema(target, "main", table)?.is_empty() {
+        // Reference tables are created by the loader from the sheet: reuse its definition
+        let query = format!("SELECT sql FROM {}.sqlite_master WHERE type = 'table' AND name = ?1", SOURCE_SCHEMA);
+        let create = target.execute_query_with_params(&query, [table])?;
+        let Some(Value::String(create)) = create.into_iter().next().and_then(|row| row.into_iter().next()) else {
+            return Ok(0);
+        };
+        execute(target, &create, [])?;
+    }
+    
+    let existing: Vec<String> = table_schema(target, "main", table)?.into_iter().map(|(name, _)| name).collect();
+    let columns: Vec<String> = table_schema(target, SOURCE_SCHEMA, table)?.into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| existing.contains(name))
+        .map(|name| quote_identifier(&name))
+        .collect();
+    if columns.is_empty() {
+        return Ok(0);
+    }
+    let column_list = columns.join(", ");
+    
+    let query = match key.filter(|key| existing.iter().any(|name| name == key)) {
+        Some(key) => format!(
+            "INSERT INTO main.{table} ({columns}) SELECT {columns} FROM {schema}.{table} AS s \
+             WHERE NOT EXISTS (SELECT 1 FROM main.{table} AS t WHERE t.{key} IS s.{key})",
+            table = quoted, columns = column_list, schema = SOURCE_SCHEMA, key = quote_identifier(key),
+        ),
+        None => format!(
+            "INSERT INTO main.{table} ({columns}) SELECT {columns} FROM {schema}.{table} \
+             EXCEPT SELECT {columns} FROM main.{table}",
+            table = quoted, columns = column_list, schema = SOURCE_SCHEMA,
+        ),
+    };
+    execute(target, &query, [])
+}
+
+/// Tables of the attached source
+fn source_tables(target: &DatabaseManager) -> Result<BTreeSet<String>, PdwError> {
+    let query = format!("SELECT name FROM {}.sqlite_master WHERE type = 'table'", SOURCE_SCHEMA);
+    Ok(target.execute_query(&query)?.into_iter()
+        .filter_map(|row| match row.into_iter().next() {
+            Some(Value::String(name)) => Some(name),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Column names and declared types of a table of the main or the attached database
+fn table_schema(target: &DatabaseManager, schema: &str, table: &str) -> Result<Vec<(String, String)>, PdwError> {
+    let query = format!("PRAGMA {}.table_info({})", schema, quote_identifier(table));
+    Ok(target.execute_query(&query)?.into_iter()
+        .filter_map(|row| match (row.get(1), row.get(2)) {
+            (Some(Value::String(name)), Some(Value::String(sql_type))) => Some((name.clone(), sql_type.clone())),
+            _ => None,
+        })
+        .collect())
+}
+
+fn execute<P: rusqlite::Params>(target: &DatabaseManager, query: &str, params: P) -> Result<usize, PdwError> {
+    target.connection().execute(query, params)
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: query.to_string(),
+            reason: e.to_string(),
+        }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PdwConfig;
+    use serde_json::json;
+    use tempfile::TempDir;
+    
+    fn member_database(path: &Path, entries: &[(&str, &str, f64)], types: &[(&str, &str)]) {
+        let db = DatabaseManager::new(path).unwrap();
+        db.create_tables().unwrap();
+        for (date, tipo, debit) in entries {
+            db.connection().execute(
+                "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Debito, AnoMes, Ano) VALUES (?1, ?2, ?3, substr(?1, 1, 4) || '/' || substr(?1, 6, 2), substr(?1, 1, 4))",
+                rusqlite::params![date, tipo, debit],
+            ).unwrap();
+        }
+        for (code, description) in types {
+            db.connection().execute("INSERT INTO TiposLancamentos VALUES (?1, ?2)", [code, description]).unwrap();
+        }
+        db.connection().execute_batch(
+            "INSERT INTO GUIDING VALUES ('TiposLancamentos', '', 'X'), ('Contas', '', 'X'), ('Conta', 'X', 'X');
+             CREATE TABLE Contas (col1 TEXT, col2 TEXT);
+             INSERT INTO Contas VALUES ('Banco', 'Corrente');
+             CREATE TABLE PIVOT_MENSAL (AnoMes TEXT);"
+        ).unwrap();
+    }
+    
+    #[test]
+    fn test_consolidate_members() {
+        let temp_dir = TempDir::new().unwrap();
+        let alice = temp_dir.path().join("alice.db");
+        let bob = temp_dir.path().join("bob").join("alice.db");
+        std::fs::create_dir(temp_dir.path().join("bob")).unwrap();
+        member_database(&alice, &[("2024-01-05", "Mercado", 10.0), ("2024-02-01", "Luz", 5.0)], &[("1", "Mercado"), ("2", "Luz")]);
+        member_database(&bob, &[("2024-01-07", "Mercado", 20.0)], &[("9", "Mercado"), ("3", "Lazer")]);
+        let settings = PdwConfig::default().settings;
+        
+        let target = DatabaseManager::new(&temp_dir.path().join("combined.db")).unwrap();
+        let summaries = consolidate(&target, &[alice.clone(), bob.clone()], &settings).unwrap();
+        assert_eq!(summaries.iter().map(|s| (s.label.as_str(), s.entries)).collect::<Vec<_>>(), vec![("alice", 2), ("alice-2", 1)]);
+        
+        let sources = target.execute_query("SELECT Fonte, COUNT(*), SUM(Debito) FROM LANCAMENTOS_GERAIS GROUP BY Fonte ORDER BY Fonte").unwrap();
+        assert_eq!(sources, vec![vec![json!("alice"), json!(2), json!(15.0)], vec![json!("alice-2"), json!(1), json!(20.0)]]);
+        
+        // Types unioned on Descrição, reference rows kept once, generated tables left out
+        let types = target.execute_query("SELECT Descrição FROM TiposLancamentos ORDER BY Descrição").unwrap();
+        assert_eq!(types, vec![vec![json!("Lazer")], vec![json!("Luz")], vec![json!("Mercado")]]);
+        assert_eq!(target.execute_query("SELECT COUNT(*) FROM Contas").unwrap(), vec![vec![json!(1)]]);
+        assert_eq!(target.execute_query("SELECT COUNT(*) FROM GUIDING").unwrap(), vec![vec![json!(3)]]);
+        assert!(target.table_columns("PIVOT_MENSAL").unwrap().is_empty());
+        
+        // A missing source is reported, not skipped
+        let error = consolidate(&target, &[temp_dir.path().join("carol.db")], &settings).unwrap_err();
+        assert!(error.to_string().contains("carol.db"), "{}", error);
+    }
+}
\ No newline at end of file
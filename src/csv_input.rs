@@ -0,0 +1,473 @@
+/*!
+# CSV Input Module
+
+Reads bank statement CSV exports as an alternative to the Excel workbook. With
+`file_types.type_in = "csv"` the input is a directory (`dir_in/<input_file>/`)
+holding one CSV file per sheet: `GUIDING.csv`, `TiposLancamentos.csv` and one
+file per origin. When no GUIDING file exists, every CSV file is loaded as an
+accounting sheet except the types table, which is loaded as reference data.
+*/
+
+use crate::error::{ConfigError, ExcelError, PdwError};
+use crate::excel::{self, ExcelReader, SheetConfig, Transaction};
+use crate::input_files;
+use crate::money::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// CSV parsing options (`[csv]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvInputConfig {
+    /// Field delimiter (Brazilian bank exports usually use ';')
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    /// Amounts use ',' as decimal separator and '.' for thousands ("1.234,56")
+    #[serde(default = "default_decimal_comma")]
+    pub decimal_comma: bool,
+    /// Header names mapped to accounting columns; positional order is used when absent
+    #[serde(default)]
+    pub columns: CsvColumnMap,
+}
+
+/// Header names of the accounting columns in bank exports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvColumnMap {
+    #[serde(default = "default_date_column")]
+    pub date: String,
+    #[serde(default = "default_type_column")]
+    pub tipo: String,
+    #[serde(default = "default_description_column")]
+    pub description: String,
+    #[serde(default = "default_credit_column")]
+    pub credit: String,
+    #[serde(default = "default_debit_column")]
+    pub debit: String,
+    /// Single signed amount column (negative = debit), used instead of credit/debit
+    #[serde(default)]
+    pub amount: Option<String>,
+    /// TIPO used when the export has no type column
+    #[serde(default)]
+    pub default_tipo: Option<String>,
+    /// Currency of the amounts, read when the file has the column
+    #[serde(default = "default_currency_column")]
+    pub currency: String,
+    /// Semicolon-separated tags, read when the file has the column
+    #[serde(default = "default_tags_column")]
+    pub tags: String,
+}
+
+fn default_delimiter() -> char {
+    ';'
+}
+
+fn default_decimal_comma() -> bool {
+    true
+}
+
+fn default_date_column() -> String {
+    "Data".to_string()
+}
+
+fn default_type_column() -> String {
+    "TIPO".to_string()
+}
+
+fn default_description_column() -> String {
+    "DESCRICAO".to_string()
+}
+
+fn default_credit_column() -> String {
+    "Credito".to_string()
+}
+
+fn default_debit_column() -> String {
+    "Debito".to_string()
+}
+
+fn default_currency_column() -> String {
+    "Moeda".to_string()
+}
+
+fn default_tags_column() -> String {
+    "Tags".to_string()
+}
+
+impl Default for CsvInputConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: default_delimiter(),
+            decimal_comma: default_decimal_comma(),
+            columns: CsvColumnMap::default(),
+        }
+    }
+}
+
+impl CsvInputConfig {
+    /// Check the options; the CSV reader splits on a single byte
+    pub fn check(&self) -> Result<(), PdwError> {
+        if !self.delimiter.is_ascii() {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("csv.delimiter ('{}') must be a single ASCII character", self.delimiter),
+            }.into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for CsvColumnMap {
+    fn default() -> Self {
+        Self {
+            date: default_date_column(),
+            tipo: default_type_column(),
+            description: default_description_column(),
+            credit: default_credit_column(),
+            debit: default_debit_column(),
+            amount: None,
+            default_tipo: None,
+            currency: default_currency_column(),
+            tags: default_tags_column(),
+        }
+    }
+}
+
+/// CSV processor reading one file per sheet from an input directory
+pub struct CsvProcessor {
+    directory: PathBuf,
+    options: CsvInputConfig,
+    types_sheet: String,
+}
+
+/// Resolved positions of the accounting columns
+struct ColumnPositions {
+    date: Option<usize>,
+    tipo: Option<usize>,
+    description: Option<usize>,
+    credit: Option<usize>,
+    debit: Option<usize>,
+    amount: Option<usize>,
+    currency: Option<usize>,
+    tags: Option<usize>,
+}
+
+impl CsvProcessor {
+    /// Open a CSV input directory
+    pub fn new(directory: &Path, options: CsvInputConfig, types_sheet: &str) -> Result<Self, PdwError> {
+        if !directory.is_dir() {
+            return Err(ExcelError::FileOpen {
+                path: directory.to_string_lossy().to_string(),
+                reason: "CSV input must be a directory with one file per sheet".to_string(),
+            }.into());
+        }
+        
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            options,
+            types_sheet: types_sheet.to_string(),
+        })
+    }
+    
+    /// Sheet names, i.e. CSV file stems, sorted
+    pub fn get_sheet_names(&self) -> Vec<String> {
+        input_files::list(&self.directory, &["csv"])
+            .map(|files| files.iter()
+                .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+                .collect())
+            .unwrap_or_default()
+    }
+    
+    /// Read guiding configuration, synthesizing it when no GUIDING file exists
+    pub fn read_guiding_sheet(&mut self, sheet_name: &str) -> Result<Vec<SheetConfig>, PdwError> {
+        if !self.sheet_path(sheet_name).exists() {
+            tracing::info!("No {}.csv found - loading every CSV file in {}", sheet_name, self.directory.display());
+            return Ok(self.get_sheet_names().into_iter()
+                .map(|name| SheetConfig {
+                    is_accounting: name != self.types_sheet,
+                    is_loadable: true,
+                    table_name: name,
+                })
+                .collect());
+        }
+        
+        Ok(self.read_rows(sheet_name)?.into_iter()
+            .skip(1)
+            .filter(|row| row.len() >= 3 && !row[0].trim().is_empty())
+            .map(|row| SheetConfig {
+                table_name: row[0].trim().to_string(),
+                is_accounting: row[1].trim().eq_ignore_ascii_case("X"),
+                is_loadable: row[2].trim().eq_ignore_ascii_case("X"),
+            })
+            .collect())
+    }
+    
+    /// Read accounting rows, mapping bank export headers onto PDW columns
+    pub fn read_accounting_sheet(&mut self, sheet_name: &str) -> Result<Vec<Transaction>, PdwError> {
+        let rows = self.read_rows(sheet_name)?;
+        let header = match rows.first() {
+            Some(header) => header,
+            None => return Ok(Vec::new()),
+        };
+        let positions = self.resolve_columns(header);
+        let cell = |row: &[String], idx: Option<usize>| -> Option<String> {
+            idx.and_then(|i| row.get(i))
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        
+        let mut transactions = Vec::new();
+        for (idx, row) in rows.iter().enumerate().skip(1) {
+            let date = cell(row, positions.date).and_then(|s| excel::parse_date(&s));
+            let transaction_type = cell(row, positions.tipo).or_else(|| self.options.columns.default_tipo.clone());
+            let description = cell(row, positions.description);
+            let currency = cell(row, positions.currency);
+            let invalid_amount = [positions.amount, positions.credit, positions.debit].into_iter()
+                .filter_map(|position| cell(row, position))
+                .find(|text| self.parse_amount(text).is_none());
+            
+            let (credit, debit) = match positions.amount {
+                Some(_) => match cell(row, positions.amount).and_then(|s| self.parse_amount(&s)) {
+                    Some(amount) if amount < Decimal::ZERO => (None, Some(-amount)),
+                    Some(amount) => (Some(amount), None),
+                    None => (None, None),
+                },
+                None => (
+                    cell(row, positions.credit).and_then(|s| self.parse_amount(&s)),
+                    cell(row, positions.debit).and_then(|s| self.parse_amount(&s)),
+                ),
+            };
+            
+            // Only add transaction if it has essential data
+            if date.is_some() || transaction_type.is_some() {
+                transactions.push(Transaction {
+                    date,
+                    transaction_type,
+                    description,
+                    credit,
+                    debit,
+                    origin: sheet_name.to_string(),
+                    currency,
+                    tags: cell(row, positions.tags),
+                    row: Some(idx + 1),
+                    invalid_amount,
+                });
+            }
+        }
+        
+        Ok(transactions)
+    }
+    
+    /// Read reference rows verbatim (header included, as with Excel sheets)
+    pub fn read_reference_sheet(&mut self, sheet_name: &str) -> Result<Vec<Vec<String>>, PdwError> {
+        self.read_rows(sheet_name)
+    }
+    
+    /// Path of the CSV file backing a sheet
+    fn sheet_path(&self, sheet_name: &str) -> PathBuf {
+        self.directory.join(format!("{}.csv", sheet_name.trim()))
+    }
+    
+    /// Read all records of a sheet file
+    fn read_rows(&self, sheet_name: &str) -> Result<Vec<Vec<String>>, PdwError> {
+        let path = self.sheet_path(sheet_name);
+        if !path.exists() {
+            return Err(ExcelError::SheetNotFound { sheet_name: sheet_name.to_string() }.into());
+        }
+        input_files::hydrate(&path)?;
+        
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.options.delimiter as u8)
+            .has_headers(false)
+            .flexible(true)
+            .from_path(&path)
+            .map_err(|e| ExcelError::FileOpen {
+                path: path.to_string_lossy().to_string(),
+                reason: e.to_string(),
+            })?;
+        
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| ExcelError::InvalidStructure {
+                sheet_name: sheet_name.to_string(),
+                reason: e.to_string(),
+            })?;
+            // Strip a UTF-8 BOM left by spreadsheet exports
+            rows.push(record.iter().map(|field| field.trim_start_matches('\u{feff}').to_string()).collect());
+        }
+        
+        Ok(rows)
+    }
+    
+    /// Locate accounting columns by header name, falling back to PDW's positional layout
+    fn resolve_columns(&self, header: &[String]) -> ColumnPositions {
+        let index: HashMap<String, usize> = header.iter()
+            .enumerate()
+            .map(|(i, name)| (name.trim().to_lowercase(), i))
+            .collect();
+        let find = |name: &str| index.get(&name.trim().to_lowercase()).copied();
+        let columns = &self.options.columns;
+        
+        if let Some(amount) = &columns.amount {
+            return ColumnPositions {
+                date: find(&columns.date),
+                tipo: find(&columns.tipo),
+                description: find(&columns.description),
+                credit: None,
+                debit: None,
+                amount: find(amount),
+                currency: find(&columns.currency),
+                tags: find(&columns.tags),
+            };
+        }
+        
+        let named = ColumnPositions {
+            date: find(&columns.date),
+            tipo: find(&columns.tipo),
+            description: find(&columns.description),
+            credit: find(&columns.credit),
+            debit: find(&columns.debit),
+            amount: None,
+            currency: find(&columns.currency),
+            tags: find(&columns.tags),
+        };
+        
+        if named.date.is_some() {
+            named
+        } else {
+            // Expected columns: Data, TIPO, DESCRICAO, Credito, Debito
+            ColumnPositions {
+                date: Some(0),
+                tipo: Some(1),
+                description: Some(2),
+                credit: Some(3),
+                debit: Some(4),
+                amount: None,
+                currency: None,
+                tags: None,
+            }
+        }
+    }
+    
+    /// Parse an amount such as "1.234,56", "R$ -10,00" or "(15,90)"
+    fn parse_amount(&self, text: &str) -> Option<Decimal> {
+        let mut cleaned: String = text.chars()
+            .filter(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | '(' | ')'))
+            .collect();
+        
+        let negative = cleaned.starts_with('(') && cleaned.ends_with(')');
+        cleaned.retain(|c| c != '(' && c != ')');
+        
+        if self.options.decimal_comma {
+            cleaned = cleaned.replace('.', "").replace(',', ".");
+        } else {
+            cleaned = cleaned.replace(',', "");
+        }
+        
+        let value: Decimal = cleaned.parse().ok()?;
+        Some(if negative { -value } else { value })
+    }
+}
+
+impl ExcelReader for CsvProcessor {
+    fn open_workbook(path: &Path) -> Result<Self, PdwError> {
+        Self::new(path, CsvInputConfig::default(), "TiposLancamentos")
+    }
+    
+    fn sheet_names(&self) -> Vec<String> {
+        self.get_sheet_names()
+    }
+    
+    fn read_guiding_sheet(&mut self, sheet_name: &str) -> Result<Vec<SheetConfig>, PdwError> {
+        self.read_guiding_sheet(sheet_name)
+    }
+    
+    fn read_accounting_sheet(&mut self, sheet_name: &str) -> Result<Vec<Transaction>, PdwError> {
+        self.read_accounting_sheet(sheet_name)
+    }
+    
+    fn read_reference_sheet(&mut self, sheet_name: &str) -> Result<Vec<Vec<String>>, PdwError> {
+        self.read_reference_sheet(sheet_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::fs;
+    use tempfile::TempDir;
+    
+    fn processor(dir: &TempDir, options: CsvInputConfig) -> CsvProcessor {
+        CsvProcessor::new(dir.path(), options, "TiposLancamentos").unwrap()
+    }
+    
+    #[test]
+    fn test_parse_amount() {
+        let dir = TempDir::new().unwrap();
+        let csv = processor(&dir, CsvInputConfig::default());
+        assert_eq!(csv.parse_amount("1.234,56"), Some(Decimal::new(123456, 2)));
+        assert_eq!(csv.parse_amount("R$ -10,00"), Some(Decimal::new(-10, 0)));
+        assert_eq!(csv.parse_amount("(15,90)"), Some(Decimal::new(-159, 1)));
+        assert_eq!(csv.parse_amount(""), None);
+    }
+    
+    #[test]
+    fn test_delimiter_must_be_ascii() {
+        assert!(CsvInputConfig::default().check().is_ok());
+        let options = CsvInputConfig { delimiter: '¦', ..CsvInputConfig::default() };
+        assert!(options.check().is_err());
+    }
+    
+    #[test]
+    fn test_guiding_and_accounting_rows() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nTiposLancamentos;;X\n").unwrap();
+        fs::write(dir.path().join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;1.042,50\n16/01/2024;SAL;Salario;5.000,00;\n").unwrap();
+        fs::write(dir.path().join("TiposLancamentos.csv"), "Código;Descrição\nALM;Alimentação\n").unwrap();
+        
+        let mut csv = processor(&dir, CsvInputConfig::default());
+        let guiding = csv.read_guiding_sheet("GUIDING").unwrap();
+        assert_eq!(guiding.len(), 2);
+        assert!(guiding[0].is_accounting && guiding[0].is_loadable);
+        assert!(!guiding[1].is_accounting);
+        
+        let transactions = csv.read_accounting_sheet("Conta").unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].date, NaiveDate::from_ymd_opt(2024, 1, 15));
+        assert_eq!(transactions[0].debit, Some(Decimal::new(104250, 2)));
+        assert_eq!(transactions[1].credit, Some(Decimal::new(5000, 0)));
+        assert_eq!(transactions[1].origin, "Conta");
+        
+        assert_eq!(csv.read_reference_sheet("TiposLancamentos").unwrap().len(), 2);
+    }
+    
+    #[test]
+    fn test_bank_export_with_signed_amount() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("Nubank.csv"), "date,title,amount,Moeda\n2024-02-01,Padaria,-12.50,USD\n2024-02-03,Estorno,30.00,\n").unwrap();
+        
+        let options = CsvInputConfig {
+            delimiter: ',',
+            decimal_comma: false,
+            columns: CsvColumnMap {
+                date: "date".to_string(),
+                description: "title".to_string(),
+                amount: Some("amount".to_string()),
+                default_tipo: Some("CARTAO".to_string()),
+                ..CsvColumnMap::default()
+            },
+        };
+        let mut csv = processor(&dir, options);
+        
+        // No GUIDING.csv: every file is an accounting sheet
+        let guiding = csv.read_guiding_sheet("GUIDING").unwrap();
+        assert_eq!(guiding.len(), 1);
+        assert_eq!(guiding[0].table_name, "Nubank");
+        
+        let transactions = csv.read_accounting_sheet("Nubank").unwrap();
+        assert_eq!(transactions[0].debit, Some(Decimal::new(1250, 2)));
+        assert_eq!(transactions[0].transaction_type.as_deref(), Some("CARTAO"));
+        assert_eq!(transactions[1].credit, Some(Decimal::new(30, 0)));
+        assert_eq!(transactions[0].currency.as_deref(), Some("USD"));
+        assert_eq!(transactions[1].currency, None);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,314 @@
+/*!
+# Multi-Currency Module
+
+Accounts kept in foreign currencies, converted into the base currency
+(`fx.base_currency`) by the transform phase. The currency of an entry comes from
+a Moeda column of its sheet, from the statement currency of an OFX download or,
+for sheets without either, from `[currency.origins]`; anything else is in the
+base currency. Credito and Debito hold the converted amounts, so pivots and
+reports stay in one currency, while the Moeda, CreditoOriginal, DebitoOriginal
+and Cambio columns keep the amounts as entered and the rate applied.
+
+Rates (base currency units per unit) come from a CAMBIO sheet of the input or a
+CSV file with Data, Moeda and Taxa columns, stored in the CAMBIO table at load
+time, and then from the exchange-rate cache filled by `pdw rates`. The rate of
+the entry's day, or of the closest earlier day within `fx.lookback_days`, is
+applied; an entry without a rate fails the load rather than being converted
+with a guess.
+*/
+
+use crate::collation;
+use crate::config::PdwConfig;
+use crate::database::DatabaseManager;
+use crate::error::{ConfigError, DatabaseError, EtlError, PdwError};
+use crate::excel;
+use crate::fx::RateCache;
+use crate::money::{self, Decimal};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Currency the amounts were entered in
+pub const CURRENCY_COLUMN: &str = "Moeda";
+
+/// Credit as entered, before conversion
+pub const ORIGINAL_CREDIT_COLUMN: &str = "CreditoOriginal";
+
+/// Debit as entered, before conversion
+pub const ORIGINAL_DEBIT_COLUMN: &str = "DebitoOriginal";
+
+/// Rate applied to the amounts as entered
+pub const RATE_COLUMN: &str = "Cambio";
+
+/// Multi-currency settings (`[currency]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Input sheet with the rates, read when present
+    #[serde(default = "default_rates_name")]
+    pub sheet: String,
+    /// CSV file with the rates, relative to the input directory
+    #[serde(default)]
+    pub rates_file: Option<PathBuf>,
+    /// Table the rates of the sheet and file are stored in
+    #[serde(default = "default_rates_name")]
+    pub table: String,
+    /// Currency of the entries of an origin whose sheet has no Moeda column
+    #[serde(default)]
+    pub origins: BTreeMap<String, String>,
+    /// Fall back to the exchange-rate cache of `pdw rates`
+    #[serde(default = "default_use_rate_cache")]
+    pub use_rate_cache: bool,
+}
+
+fn default_rates_name() -> String {
+    "CAMBIO".to_string()
+}
+
+fn default_use_rate_cache() -> bool {
+    true
+}
+
+impl Default for CurrencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sheet: default_rates_name(),
+            rates_file: None,
+            table: default_rates_name(),
+            origins: BTreeMap::new(),
+            use_rate_cache: default_use_rate_cache(),
+        }
+    }
+}
+
+/// Columns added to the general entries table
+pub fn schema() -> Vec<(String, &'static str)> {
+    vec![
+        (CURRENCY_COLUMN.to_string(), "TEXT"),
+        (ORIGINAL_CREDIT_COLUMN.to_string(), "REAL"),
+        (ORIGINAL_DEBIT_COLUMN.to_string(), "REAL"),
+        (RATE_COLUMN.to_string(), "REAL"),
+    ]
+}
+
+/// Rates of a CAMBIO sheet or file (header first); rows without a date are skipped
+pub fn parse_rates(rows: &[Vec<String>], source: &str) -> Result<RateCache, PdwError> {
+    let mut rates = RateCache::default();
+    let Some((header, rows)) = rows.split_first() else {
+        return Ok(rates);
+    };
+    
+    let column = |names: &[&str], field: &str| header.iter()
+        .position(|h| names.contains(&collation::fold(h.trim()).as_str()))
+        .ok_or_else(|| ConfigError::MissingField {
+            field: format!("{} column of {}", field, source),
+        });
+    let date_column = column(&["data", "date"], "Data")?;
+    let currency_column = column(&["moeda", "currency"], "Moeda")?;
+    let rate_column = column(&["taxa", "rate", "cotacao"], "Taxa")?;
+    
+    for row in rows {
+        let cell = |index: usize| row.get(index).map(|c| c.trim()).unwrap_or_default();
+        let Some(date) = parse_rate_date(cell(date_column)) else {
+            continue;
+        };
+        let currency = cell(currency_column);
+        let rate = cell(rate_column).replace(',', ".").parse::<f64>().ok()
+            .filter(|rate| *rate > 0.0 && !currency.is_empty())
+            .ok_or_else(|| ConfigError::InvalidFormat {
+                message: format!("Invalid {} rate '{}' on {} in {}", currency, cell(rate_column), date, source),
+            })?;
+        rates.rates.entry(currency.to_uppercase()).or_default().insert(date, rate);
+    }
+    
+    Ok(rates)
+}
+
+/// Date of a rate row: text in a spreadsheet format, a date cell read as
+/// "YYYY-MM-DD HH:MM:SS" or an Excel serial number
+fn parse_rate_date(text: &str) -> Option<NaiveDate> {
+    if let Some(date) = excel::parse_date(text).or_else(|| text.get(..10).and_then(excel::parse_date)) {
+        return Some(date);
+    }
+    excel::DateSystem::Excel1900.serial_date(text.parse().ok()?)
+}
+
+/// Rows of a rates CSV file, split on the CSV input delimiter
+pub fn read_rates_file(path: &Path, delimiter: char) -> Result<Vec<Vec<String>>, PdwError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter as u8)
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| ConfigError::InvalidPath {
+            path: path.to_string_lossy().to_string(),
+            reason: e.to_string(),
+        })?;
+    
+    reader.records()
+        .map(|record| record
+            .map(|record| record.iter().map(str::to_string).collect())
+            .map_err(|e| ConfigError::InvalidFormat {
+                message: format!("Invalid rates file {}: {}", path.display(), e),
+            }.into()))
+        .collect()
+}
+
+/// Store the rates of the sheet and file, replacing the previous load's table
+pub fn write_rates_table(database: &DatabaseManager, table: &str, rates: &RateCache) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!("CREATE TABLE {} (Data DATE, Moeda TEXT, Taxa REAL, PRIMARY KEY (Data, Moeda))", table);
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3)", table);
+    let mut count = 0;
+    for (currency, days) in &rates.rates {
+        for (date, rate) in days {
+            database.connection().execute(&insert_query, rusqlite::params![date.format("%Y-%m-%d").to_string(), currency, rate])
+                .map_err(|e| DatabaseError::DataInsertion {
+                    table: table.to_string(),
+                    reason: e.to_string(),
+                })?;
+            count += 1;
+        }
+    }
+    
+    Ok(count)
+}
+
+/// Rate lookups for the transform phase, without network access
+#[derive(Debug, Clone)]
+pub struct CurrencyConverter {
+    base: String,
+    origins: BTreeMap<String, String>,
+    lookback_days: i64,
+    /// Rates of the sheet and file, preferred
+    rates: RateCache,
+    /// Exchange-rate cache of `pdw rates`
+    cache: RateCache,
+}
+
+impl CurrencyConverter {
+    /// Converter over the given rates and, when enabled, the exchange-rate cache
+    pub fn new(config: &PdwConfig, rates: RateCache) -> Result<Self, PdwError> {
+        let cache = if config.currency.use_rate_cache {
+            RateCache::load(&config.get_fx_cache_path())?
+        } else {
+            RateCache::default()
+        };
+        
+        Ok(Self {
+            base: config.fx.base_currency.to_uppercase(),
+            origins: config.currency.origins.iter()
+                .map(|(origin, currency)| (origin.trim().to_string(), currency.trim().to_uppercase()))
+                .collect(),
+            lookback_days: config.fx.lookback_days,
+            rates,
+            cache,
+        })
+    }
+    
+    /// Currency of an entry: its own, else its origin's, else the base currency
+    pub fn currency_of(&self, currency: Option<&str>, origin: &str) -> String {
+        currency.map(str::trim)
+            .filter(|currency| !currency.is_empty())
+            .map(str::to_uppercase)
+            .or_else(|| self.origins.get(origin.trim()).cloned())
+            .unwrap_or_else(|| self.base.clone())
+    }
+    
+    /// Rates of the sheet and file
+    pub fn rates(&self) -> &RateCache {
+        &self.rates
+    }
+    
+    pub fn is_base(&self, currency: &str) -> bool {
+        currency.eq_ignore_ascii_case(&self.base)
+    }
+    
+    /// Base currency units per unit of `currency` on `date`
+    pub fn rate(&self, currency: &str, date: NaiveDate) -> Option<f64> {
+        if self.is_base(currency) {
+            return Some(1.0);
+        }
+        self.rates.on_or_before(currency, date, self.lookback_days)
+            .or_else(|| self.cache.on_or_before(currency, date, self.lookback_days))
+            .map(|(_, rate)| rate)
+    }
+    
+    /// Amount in the base currency, unrounded; fails when no rate is known
+    pub fn convert(&self, amount: Decimal, currency: &str, date: NaiveDate, origin: &str) -> Result<(Decimal, f64), PdwError> {
+        let rate = self.rate(currency, date).ok_or_else(|| EtlError::TransformationFailed {
+            stage: "currency conversion".to_string(),
+            reason: format!(
+                "no {} rate on or before {} for an entry of {}; add it to the CAMBIO sheet or run 'pdw rates --currency {}'",
+                currency, date, origin, currency
+            ),
+        })?;
+        Ok((amount * money::from_f64(rate).unwrap_or_default(), rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn rows(cells: &[&[&str]]) -> Vec<Vec<String>> {
+        cells.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect()
+    }
+    
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+    
+    #[test]
+    fn test_parse_rates() {
+        let sheet = rows(&[
+            &["Data", "Moeda", "Taxa"],
+            &["2024-01-15 00:00:00", "usd", "4,89"],
+            &["45307", "EUR", "5.35"],
+            &["", "USD", "9"],
+        ]);
+        let rates = parse_rates(&sheet, "the CAMBIO sheet").unwrap();
+        assert_eq!(rates.on_or_before("USD", date("2024-01-17"), 7), Some((date("2024-01-15"), 4.89)));
+        assert_eq!(rates.on_or_before("EUR", date("2024-01-16"), 0), Some((date("2024-01-16"), 5.35)));
+        
+        assert!(parse_rates(&rows(&[&["Data", "Moeda"]]), "x").is_err());
+        assert!(parse_rates(&rows(&[&["Data", "Moeda", "Taxa"], &["2024-01-15", "USD", "-"]]), "x").is_err());
+    }
+    
+    #[test]
+    fn test_converter_prefers_sheet_rates() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.database_dir = temp_dir.path().to_path_buf();
+        config.currency.origins.insert("Conta EUA".to_string(), "usd".to_string());
+        let mut cache = RateCache::default();
+        cache.insert("USD", date("2024-01-10"), 4.90);
+        cache.insert("EUR", date("2024-01-10"), 5.30);
+        cache.save(&config.get_fx_cache_path()).unwrap();
+        
+        let mut sheet = RateCache::default();
+        sheet.insert("USD", date("2024-01-12"), 5.0);
+        let converter = CurrencyConverter::new(&config, sheet).unwrap();
+        
+        assert_eq!(converter.currency_of(None, "Conta EUA"), "USD");
+        assert_eq!(converter.currency_of(Some(" eur "), "Conta EUA"), "EUR");
+        assert_eq!(converter.currency_of(None, "Conta"), "BRL");
+        
+        let amount = Decimal::new(1050, 2);
+        assert_eq!(converter.convert(amount, "USD", date("2024-01-14"), "x").unwrap(), (Decimal::new(5250, 2), 5.0));
+        assert_eq!(converter.convert(amount, "EUR", date("2024-01-14"), "x").unwrap().1, 5.30);
+        assert_eq!(converter.convert(amount, "BRL", date("2024-01-14"), "x").unwrap(), (amount, 1.0));
+        let error = converter.convert(amount, "USD", date("2024-01-01"), "Conta EUA").unwrap_err().to_string();
+        assert!(error.contains("no USD rate on or before 2024-01-01 for an entry of Conta EUA"), "{}", error);
+    }
+}
\ No newline at end of file
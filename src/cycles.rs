@@ -0,0 +1,241 @@
+/*!
+# Statement Cycles Module
+
+Credit card invoices (faturas) close on a fixed day of the month rather than
+at month end: with closing day 5, a purchase on 06/01 is billed on the February
+invoice. Origins listed under `[statement_cycles.closing_days]` get a `Fatura`
+period column (the closing month, as AnoMes) and a per-invoice summary table
+that matches what the bank actually bills. Origins with a due day under
+`[statement_cycles.due_days]` also get the invoice due date (`Vencimento`),
+keeping both the purchase and the payment date of each entry.
+
+`basis` chooses which date the monthly columns (AnoMes, Mes, Ano) of card
+entries follow, and with them the pivots and summaries: the purchase date
+(accrual view, the default), the invoice closing date, or the due date (cash
+flow view). `group_by_cycle = true` is the older spelling of `basis = "statement"`.
+*/
+
+use crate::collation;
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Date that places card entries in a month
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateBasis {
+    /// Purchase date (accrual view)
+    #[default]
+    Purchase,
+    /// Invoice closing date
+    Statement,
+    /// Invoice due date (cash flow view)
+    Due,
+}
+
+/// Statement cycle settings (`[statement_cycles]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementCycleConfig {
+    /// Invoice closing day (1-31) per origin (sheet name)
+    #[serde(default)]
+    pub closing_days: BTreeMap<String, u32>,
+    /// Invoice due day (1-31) per origin; the origin needs a closing day too
+    #[serde(default)]
+    pub due_days: BTreeMap<String, u32>,
+    /// Date the AnoMes/Mes/Ano of card entries follow
+    #[serde(default)]
+    pub basis: DateBasis,
+    /// Also move AnoMes/Mes/Ano of card entries to the invoice month
+    #[serde(default)]
+    pub group_by_cycle: bool,
+    #[serde(default = "default_cycle_column")]
+    pub column: String,
+    #[serde(default = "default_due_column")]
+    pub due_column: String,
+    #[serde(default = "default_cycle_table")]
+    pub table: String,
+}
+
+fn default_cycle_column() -> String {
+    "Fatura".to_string()
+}
+
+fn default_due_column() -> String {
+    "Vencimento".to_string()
+}
+
+fn default_cycle_table() -> String {
+    "Resumo_Faturas".to_string()
+}
+
+impl Default for StatementCycleConfig {
+    fn default() -> Self {
+        Self {
+            closing_days: BTreeMap::new(),
+            due_days: BTreeMap::new(),
+            basis: DateBasis::default(),
+            group_by_cycle: false,
+            column: default_cycle_column(),
+            due_column: default_due_column(),
+            table: default_cycle_table(),
+        }
+    }
+}
+
+impl StatementCycleConfig {
+    /// Whether any origin is billed by statement cycle
+    pub fn is_enabled(&self) -> bool {
+        !self.closing_days.is_empty()
+    }
+    
+    /// Whether any origin records invoice due dates
+    pub fn has_due_dates(&self) -> bool {
+        !self.due_days.is_empty()
+    }
+    
+    /// Basis in effect, honoring the older `group_by_cycle` switch
+    pub fn effective_basis(&self) -> DateBasis {
+        match self.basis {
+            DateBasis::Purchase if self.group_by_cycle => DateBasis::Statement,
+            basis => basis,
+        }
+    }
+    
+    /// Closing day of an origin, ignoring case, accents and surrounding spaces
+    pub fn closing_day(&self, origin: &str) -> Option<u32> {
+        day_of(&self.closing_days, origin)
+    }
+    
+    /// Due day of an origin, matched like the closing day
+    pub fn due_day(&self, origin: &str) -> Option<u32> {
+        day_of(&self.due_days, origin)
+    }
+    
+    /// Invoice closing date of an entry, or None for origins without a cycle
+    pub fn statement_date(&self, origin: &str, date: NaiveDate) -> Option<NaiveDate> {
+        self.closing_day(origin).map(|day| statement_closing_date(date, day))
+    }
+    
+    /// Invoice due date of an entry, or None for origins without a due day
+    pub fn due_date(&self, origin: &str, date: NaiveDate) -> Option<NaiveDate> {
+        let closing_date = self.statement_date(origin, date)?;
+        self.due_day(origin).map(|day| statement_due_date(closing_date, day))
+    }
+    
+    /// Closing or due days outside 1-31
+    pub fn invalid_origins(&self) -> Vec<&str> {
+        self.closing_days.iter()
+            .chain(&self.due_days)
+            .filter(|(_, day)| !(1..=31).contains(*day))
+            .map(|(origin, _)| origin.as_str())
+            .collect()
+    }
+    
+    /// Origins with a due day but no closing day
+    pub fn due_without_closing(&self) -> Vec<&str> {
+        self.due_days.keys()
+            .filter(|origin| self.closing_day(origin).is_none())
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Closing date of the invoice an entry falls on: the closing day of its own
+/// month when on or before it, otherwise of the next month (clamped to month end)
+pub fn statement_closing_date(date: NaiveDate, closing_day: u32) -> NaiveDate {
+    let this_month = closing_date_in(date.year(), date.month(), closing_day);
+    if date <= this_month {
+        return this_month;
+    }
+    
+    let next = date.with_day(1).and_then(|d| d.checked_add_months(Months::new(1))).unwrap_or(date);
+    closing_date_in(next.year(), next.month(), closing_day)
+}
+
+/// Due date of an invoice: the first due day after its closing date (clamped to month end)
+pub fn statement_due_date(closing_date: NaiveDate, due_day: u32) -> NaiveDate {
+    if due_day > closing_date.day() {
+        let due = closing_date_in(closing_date.year(), closing_date.month(), due_day);
+        if due > closing_date {
+            return due;
+        }
+    }
+    
+    let next = closing_date.with_day(1).and_then(|d| d.checked_add_months(Months::new(1))).unwrap_or(closing_date);
+    closing_date_in(next.year(), next.month(), due_day)
+}
+
+/// Invoice period as AnoMes ("2024/02")
+pub fn statement_period(closing_date: NaiveDate) -> String {
+    format!("{}/{:02}", closing_date.year(), closing_date.month())
+}
+
+/// Day of an origin in a per-origin map, ignoring case, accents and surrounding spaces
+fn day_of(days: &BTreeMap<String, u32>, origin: &str) -> Option<u32> {
+    let origin = collation::fold(origin.trim());
+    days.iter()
+        .find(|(name, _)| collation::fold(name.trim()) == origin)
+        .map(|(_, day)| *day)
+}
+
+/// Closing day in a given month, clamped to its last day
+fn closing_date_in(year: i32, month: u32, closing_day: u32) -> NaiveDate {
+    (1..=closing_day.clamp(1, 31))
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .expect("every month has a first day")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+    
+    #[test]
+    fn test_statement_closing_date() {
+        assert_eq!(statement_closing_date(date(2024, 1, 5), 5), date(2024, 1, 5));
+        assert_eq!(statement_closing_date(date(2024, 1, 6), 5), date(2024, 2, 5));
+        assert_eq!(statement_closing_date(date(2024, 12, 20), 10), date(2025, 1, 10));
+        // Closing day past month end
+        assert_eq!(statement_closing_date(date(2024, 2, 15), 30), date(2024, 2, 29));
+        assert_eq!(statement_closing_date(date(2024, 1, 31), 30), date(2024, 2, 29));
+        assert_eq!(statement_period(date(2025, 1, 10)), "2025/01");
+    }
+    
+    #[test]
+    fn test_closing_day_lookup() {
+        let config: StatementCycleConfig = toml::from_str(
+            "group_by_cycle = true\n[closing_days]\n\"Cartão Visa\" = 5\nNubank = 40"
+        ).unwrap();
+        assert_eq!(config.column, "Fatura");
+        assert!(config.is_enabled());
+        assert_eq!(config.closing_day(" CARTAO VISA "), Some(5));
+        assert_eq!(config.closing_day("Conta"), None);
+        assert_eq!(config.statement_date("cartão visa", date(2024, 3, 9)), Some(date(2024, 4, 5)));
+        assert_eq!(config.invalid_origins(), vec!["Nubank"]);
+        assert_eq!(config.effective_basis(), DateBasis::Statement);
+    }
+    
+    #[test]
+    fn test_due_dates() {
+        // Due after the closing day in the same month, or early the next month
+        assert_eq!(statement_due_date(date(2024, 2, 5), 15), date(2024, 2, 15));
+        assert_eq!(statement_due_date(date(2024, 2, 25), 5), date(2024, 3, 5));
+        assert_eq!(statement_due_date(date(2024, 1, 30), 31), date(2024, 1, 31));
+        // Due day 31 after a closing on the 29th of February
+        assert_eq!(statement_due_date(date(2024, 2, 29), 31), date(2024, 3, 31));
+        assert_eq!(statement_due_date(date(2024, 12, 28), 8), date(2025, 1, 8));
+        
+        let config: StatementCycleConfig = toml::from_str(
+            "basis = \"due\"\n[closing_days]\nVisa = 25\n[due_days]\nVisa = 5\nConta = 10"
+        ).unwrap();
+        assert!(config.has_due_dates());
+        assert_eq!(config.effective_basis(), DateBasis::Due);
+        assert_eq!(config.due_date("visa", date(2024, 1, 26)), Some(date(2024, 3, 5)));
+        assert_eq!(config.due_date("Conta", date(2024, 1, 26)), None);
+        assert_eq!(config.due_without_closing(), vec!["Conta"]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,388 @@
+/*!
+# Daemon Module
+
+`pdw daemon start` keeps the configuration, the open database and the pipeline
+between runs, and takes commands on a local socket: a Unix domain socket next
+to the database (`<out_db_file>.sock`, owner only), or on Windows a named pipe
+only its owner may open and never over the network.
+
+- `run` - the configured phases, or only `load`, `pivot` or `report`; the load
+  is skipped while the input files are unchanged since the daemon's last load,
+  unless forced
+- `query` - a read-only SQL query, answered in the JSON export layout
+- `status` - process, database and last run
+- `stop` - exit once the command is answered
+
+Requests and responses are one JSON object per line (`{"command": "run",
+"phase": "report"}` is answered `{"result": ...}` or `{"error": ...}`). Each
+connection is read on a thread of its own, so a client that stalls holds up no
+other, while the commands themselves are handled one at a time. `pdw watch` and `pdw serve` hand their runs
+to a daemon serving the same database instead of starting a pipeline of their
+own. The configuration is read once: restart the daemon after changing it.
+Listening needs a build with `--features daemon`.
+*/
+
+use crate::checkpoint::{self, Phase};
+use crate::error::PdwError;
+use crate::etl::EtlPipeline;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::Instant;
+
+/// Command sent to the daemon
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Run one phase, or every configured phase when none is given
+    Run {
+        #[serde(default)]
+        phase: Option<Phase>,
+        /// Load even though the inputs are unchanged
+        #[serde(default)]
+        force: bool,
+    },
+    Query { sql: String },
+    Status,
+    Stop,
+}
+
+/// Answer to a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Result(Value),
+    Error(String),
+}
+
+/// Phases a `run` request asks of the runner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunPlan {
+    /// One phase, or every configured phase when None
+    pub phase: Option<Phase>,
+    /// Whether the load runs; false while the inputs are unchanged since the last load
+    pub load: bool,
+}
+
+/// Outcome of the daemon's last run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastRun {
+    pub phase: String,
+    pub finished_at: String,
+    pub seconds: f64,
+    /// Whether the load was skipped for unchanged inputs
+    pub load_skipped: bool,
+    pub error: Option<String>,
+}
+
+/// Pipeline kept open between the commands of a daemon
+pub struct Daemon {
+    pipeline: EtlPipeline,
+    started_at: String,
+    runs: usize,
+    last_run: Option<LastRun>,
+    /// Inputs of the last successful load
+    loaded_inputs: Option<String>,
+    stopping: bool,
+}
+
+impl Daemon {
+    pub fn new(pipeline: EtlPipeline) -> Self {
+        Self {
+            pipeline,
+            started_at: crate::deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            runs: 0,
+            last_run: None,
+            loaded_inputs: None,
+            stopping: false,
+        }
+    }
+    
+    /// Whether a `stop` request was answered
+    pub fn is_stopping(&self) -> bool {
+        self.stopping
+    }
+    
+    /// Answer a request; `runner` runs the phases of a `run` on the pipeline
+    pub fn handle<F>(&mut self, request: Request, runner: &mut F) -> Response
+    where
+        F: FnMut(&mut EtlPipeline, RunPlan) -> Result<(), String>,
+    {
+        match request {
+            Request::Run { phase, force } => self.run(phase, force, runner),
+            Request::Query { sql } => {
+                let document = self.pipeline.database().read_only(|database| {
+                    Ok(crate::export_schema::ExportDocument::new(database.query_columns(&sql)?, database.execute_query(&sql)?))
+                });
+                match document.and_then(|document| document.to_json()) {
+                    Ok(body) => serde_json::from_str(&body).map_or_else(|e| Response::Error(e.to_string()), Response::Result),
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
+            Request::Status => Response::Result(json!({
+                "pid": std::process::id(),
+                "started_at": self.started_at,
+                "database": self.pipeline.database().path(),
+                "runs": self.runs,
+                "inputs_loaded": self.loaded_inputs.is_some(),
+                "last_run": self.last_run,
+            })),
+            Request::Stop => {
+                self.stopping = true;
+                Response::Result(json!({ "status": "stopping" }))
+            }
+        }
+    }
+    
+    fn run<F>(&mut self, phase: Option<Phase>, force: bool, runner: &mut F) -> Response
+    where
+        F: FnMut(&mut EtlPipeline, RunPlan) -> Result<(), String>,
+    {
+        let inputs = checkpoint::input_fingerprint(self.pipeline.config());
+        let loads = match phase {
+            None => self.pipeline.config().settings.run_data_loader,
+            Some(phase) => phase == Phase::Load,
+        };
+        let load = loads && (force || self.loaded_inputs.as_ref() != Some(&inputs));
+        if loads && !load {
+            tracing::info!("Inputs unchanged since the last load - load skipped");
+        }
+        
+        let started = Instant::now();
+        self.pipeline.start_run();
+        let result = runner(&mut self.pipeline, RunPlan { phase, load });
+        if load {
+            // A failed load may have left the warehouse half replaced
+            self.loaded_inputs = result.as_ref().ok().map(|_| inputs);
+        }
+        
+        let last_run = LastRun {
+            phase: phase.map_or_else(|| "all".to_string(), |phase| phase.to_string()),
+            finished_at: crate::deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            seconds: started.elapsed().as_secs_f64(),
+            load_skipped: loads && !load,
+            error: result.err(),
+        };
+        self.runs += 1;
+        self.last_run = Some(last_run.clone());
+        match last_run.error {
+            Some(error) => Response::Error(error),
+            None => Response::Result(json!(last_run)),
+        }
+    }
+}
+
+/// Named pipe access on Windows: network logons denied, the owner allowed
+#[cfg(all(feature = "daemon", windows))]
+const PIPE_SDDL: &str = "D:P(D;;GA;;;NU)(A;;GA;;;OW)";
+
+/// Request read from a connection, with the channel its answer goes back on
+#[cfg(feature = "daemon")]
+type Pending = (Request, std::sync::mpsc::Sender<Response>);
+
+/// Take commands on `socket` until a `stop` request
+#[cfg(feature = "daemon")]
+pub fn listen<F>(daemon: &mut Daemon, socket: &Path, mut runner: F) -> Result<(), PdwError>
+where
+    F: FnMut(&mut EtlPipeline, RunPlan) -> Result<(), String>,
+{
+    use interprocess::local_socket::{prelude::*, ListenerOptions};
+    
+    if is_running(socket) {
+        return Err(PdwError::Daemon(format!("A daemon is already listening on {}", socket.display())));
+    }
+    let daemon_error = |e: std::io::Error| PdwError::Daemon(format!("{}: {}", socket.display(), e));
+    let options = ListenerOptions::new().name(socket_name(socket).map_err(daemon_error)?).try_overwrite(true);
+    #[cfg(unix)]
+    let options = {
+        use interprocess::os::unix::local_socket::ListenerOptionsExt;
+        options.mode(0o600)
+    };
+    #[cfg(windows)]
+    let options = {
+        use interprocess::os::windows::{local_socket::ListenerOptionsExt, security_descriptor::SecurityDescriptor};
+        let sddl = widestring::U16CString::from_str(PIPE_SDDL).expect("SDDL without NUL");
+        options.security_descriptor(SecurityDescriptor::deserialize(&sddl).map_err(daemon_error)?)
+    };
+    let listener = options.create_sync().map_err(daemon_error)?;
+    tracing::info!("Daemon listening on {}", socket.display());
+    
+    let (requests, pending) = std::sync::mpsc::channel::<Pending>();
+    std::thread::spawn(move || {
+        for connection in listener.incoming() {
+            match connection {
+                Ok(connection) => {
+                    let requests = requests.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve_connection(connection, &requests) {
+                            tracing::warn!("Daemon connection dropped: {}", e);
+                        }
+                    });
+                }
+                Err(e) => tracing::warn!("Daemon connection refused: {}", e),
+            }
+        }
+    });
+    
+    for (request, reply) in pending {
+        // A client that went away meanwhile just misses its answer
+        let _ = reply.send(daemon.handle(request, &mut runner));
+        if daemon.is_stopping() {
+            tracing::info!("Daemon stopped");
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "daemon"))]
+pub fn listen<F>(_daemon: &mut Daemon, _socket: &Path, _runner: F) -> Result<(), PdwError>
+where
+    F: FnMut(&mut EtlPipeline, RunPlan) -> Result<(), String>,
+{
+    Err(PdwError::Daemon("pdw daemon requires a build with --features daemon".to_string()))
+}
+
+/// Pass the requests of a connection, one per line, to the daemon and write back its
+/// answers, until the connection closes or the daemon stops
+#[cfg(feature = "daemon")]
+fn serve_connection(connection: interprocess::local_socket::Stream, requests: &std::sync::mpsc::Sender<Pending>) -> std::io::Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    
+    let mut reader = BufReader::new(connection);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                tracing::info!("Daemon command: {}", line.trim());
+                let (reply, answer) = std::sync::mpsc::channel();
+                // Either fails only once the daemon stopped
+                if requests.send((request, reply)).is_err() {
+                    break;
+                }
+                match answer.recv() {
+                    Ok(response) => response,
+                    Err(_) => break,
+                }
+            }
+            Err(e) => Response::Error(format!("Invalid request: {}", e)),
+        };
+        let mut body = serde_json::to_string(&response)?;
+        body.push('\n');
+        reader.get_mut().write_all(body.as_bytes())?;
+        reader.get_mut().flush()?;
+    }
+    Ok(())
+}
+
+/// Send a request to the daemon listening on `socket` and return its result
+#[cfg(feature = "daemon")]
+pub fn send(socket: &Path, request: &Request) -> Result<Value, PdwError> {
+    use interprocess::local_socket::{prelude::*, Stream};
+    use std::io::{BufRead, BufReader, Write};
+    
+    let daemon_error = |e: std::io::Error| PdwError::Daemon(format!("{}: {}", socket.display(), e));
+    let mut stream = Stream::connect(socket_name(socket).map_err(daemon_error)?)
+        .map_err(|e| PdwError::Daemon(format!("No daemon listening on {} ({})", socket.display(), e)))?;
+    let mut body = serde_json::to_string(request).map_err(crate::error::ReportError::JsonSerialization)?;
+    body.push('\n');
+    stream.write_all(body.as_bytes()).map_err(daemon_error)?;
+    
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).map_err(daemon_error)?;
+    let response = serde_json::from_str::<Response>(&line)
+        .map_err(|e| PdwError::Daemon(format!("Unreadable answer from {}: {}", socket.display(), e)))?;
+    match response {
+        Response::Result(value) => Ok(value),
+        Response::Error(message) => Err(PdwError::Daemon(message)),
+    }
+}
+
+#[cfg(not(feature = "daemon"))]
+pub fn send(_socket: &Path, _request: &Request) -> Result<Value, PdwError> {
+    Err(PdwError::Daemon("pdw daemon requires a build with --features daemon".to_string()))
+}
+
+/// Whether a daemon answers on `socket`
+#[cfg(feature = "daemon")]
+pub fn is_running(socket: &Path) -> bool {
+    use interprocess::local_socket::{prelude::*, Stream};
+    
+    socket_name(socket).and_then(Stream::connect).is_ok()
+}
+
+#[cfg(not(feature = "daemon"))]
+pub fn is_running(_socket: &Path) -> bool {
+    false
+}
+
+/// Local socket name of a socket path: the path itself, or a named pipe called after it on Windows
+#[cfg(feature = "daemon")]
+fn socket_name(socket: &Path) -> std::io::Result<interprocess::local_socket::Name<'static>> {
+    use interprocess::local_socket::{prelude::*, GenericFilePath, GenericNamespaced};
+    
+    if cfg!(windows) {
+        let pipe: String = socket.to_string_lossy().chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        pipe.to_ns_name::<GenericNamespaced>()
+    } else {
+        socket.to_path_buf().to_fs_name::<GenericFilePath>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PdwConfig;
+    
+    #[test]
+    fn test_daemon_skips_unchanged_load() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.directories.database_dir = temp_dir.path().to_path_buf();
+        std::fs::write(config.get_input_file_path(), "v1").unwrap();
+        let input = config.get_input_file_path();
+        let mut daemon = Daemon::new(EtlPipeline::new(config).unwrap());
+        
+        let plans = std::cell::RefCell::new(Vec::new());
+        let mut runner = |_: &mut EtlPipeline, plan: RunPlan| {
+            plans.borrow_mut().push(plan);
+            Ok(())
+        };
+        daemon.handle(Request::Run { phase: None, force: false }, &mut runner);
+        daemon.handle(Request::Run { phase: None, force: false }, &mut runner);
+        daemon.handle(Request::Run { phase: Some(Phase::Load), force: true }, &mut runner);
+        std::fs::write(&input, "version 2").unwrap();
+        daemon.handle(Request::Run { phase: Some(Phase::Report), force: false }, &mut runner);
+        daemon.handle(Request::Run { phase: Some(Phase::Load), force: false }, &mut runner);
+        let loads: Vec<_> = plans.borrow().iter().map(|plan| plan.load).collect();
+        assert_eq!(loads, vec![true, false, true, false, true]);
+        
+        let Response::Result(status) = daemon.handle(Request::Status, &mut runner) else { panic!("status failed") };
+        assert_eq!(status["runs"], 5);
+        assert_eq!(status["last_run"]["phase"], "load");
+        let Response::Result(rows) = daemon.handle(Request::Query { sql: "SELECT 1 + 1 AS dois".to_string() }, &mut runner) else {
+            panic!("query failed")
+        };
+        assert_eq!(rows["columns"], json!(["dois"]));
+        assert!(matches!(daemon.handle(Request::Query { sql: "DELETE FROM LANCAMENTOS_GERAIS".to_string() }, &mut runner), Response::Error(_)));
+        
+        let mut failing = |_: &mut EtlPipeline, _: RunPlan| Err("disk full".to_string());
+        assert_eq!(daemon.handle(Request::Run { phase: None, force: false }, &mut failing), Response::Error("disk full".to_string()));
+        
+        let request: Request = serde_json::from_str(r#"{"command": "run", "phase": "report"}"#).unwrap();
+        assert_eq!(request, Request::Run { phase: Some(Phase::Report), force: false });
+        assert_eq!(daemon.handle(Request::Stop, &mut runner), Response::Result(json!({ "status": "stopping" })));
+        assert!(daemon.is_stopping());
+    }
+}
\ No newline at end of file
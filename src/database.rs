@@ -5,16 +5,298 @@ Handles SQLite operations including connection management, schema creation,
 and data operations. Maintains compatibility with Python PDW database structure.
 */
 
+use crate::collation::{self, TextCollation};
+use crate::config::SettingsConfig;
 use crate::error::{DatabaseError, PdwError};
 use crate::excel::Transaction;
-use rusqlite::{Connection, params, Result as SqliteResult, Row};
-use std::path::Path;
+use crate::expression::DerivedValue;
+use crate::money::{self, Decimal, MoneyMode};
+use crate::reference::ReferenceSheet;
+use crate::type_normalization::TypeNormalizer;
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags, params, Result as SqliteResult, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use chrono::NaiveDate;
 use serde_json::Value;
 
+/// Default number of rows committed per insert transaction
+pub const DEFAULT_INSERT_BATCH_SIZE: usize = 5000;
+
+/// Default time a statement waits for a lock held by another connection, in milliseconds
+pub const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5000;
+
+/// Key/value table backing [`MetaStore`]
+pub const META_TABLE: &str = "PDW_META";
+
+/// Pages copied per step when a database moves into or out of memory
+const BACKUP_PAGES_PER_STEP: std::os::raw::c_int = 4096;
+
 /// Database manager for SQLite operations
 pub struct DatabaseManager {
     connection: Connection,
+    path: PathBuf,
+    insert_batch_size: usize,
+    collation: TextCollation,
+    money: MoneyMode,
+    busy_timeout_ms: u64,
+    /// Built in memory; the file at `path` is only written by `flush_to_disk`
+    in_memory: bool,
+}
+
+/// Integrity check performed when opening an existing database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrityCheck {
+    /// Skip the check
+    Off,
+    /// PRAGMA quick_check (O(N), skips index consistency)
+    #[default]
+    Quick,
+    /// PRAGMA integrity_check (thorough, slower on big databases)
+    Full,
+}
+
+/// Storage backend of the warehouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseBackend {
+    /// Local SQLite file under database_dir
+    #[default]
+    Sqlite,
+    /// Shared PostgreSQL server (`postgres` feature)
+    Postgres,
+}
+
+/// `[database]` configuration section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    #[serde(default)]
+    pub backend: DatabaseBackend,
+    /// libpq-style connection string, e.g. "host=nas user=pdw dbname=pdw"
+    #[serde(default)]
+    pub connection_string: Option<String>,
+    #[serde(default)]
+    pub sqlite: SqliteTuning,
+}
+
+/// SQLite rollback journal mode (`PRAGMA journal_mode`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    /// Write-ahead log: readers do not block the load, far fewer fsyncs
+    #[default]
+    Wal,
+    Off,
+}
+
+/// Disk synchronization level (`PRAGMA synchronous`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    Off,
+    /// Safe with WAL: a power loss may only undo the last commits
+    #[default]
+    Normal,
+    Full,
+    Extra,
+}
+
+/// Where temporary tables and indices live (`PRAGMA temp_store`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TempStore {
+    #[default]
+    Default,
+    File,
+    Memory,
+}
+
+/// `[database.sqlite]` connection tuning, applied when the warehouse database is opened
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SqliteTuning {
+    #[serde(default)]
+    pub journal_mode: JournalMode,
+    #[serde(default)]
+    pub synchronous: Synchronous,
+    /// Page cache: pages when positive, KiB when negative (SQLite's default when unset)
+    #[serde(default)]
+    pub cache_size: Option<i64>,
+    #[serde(default)]
+    pub temp_store: TempStore,
+    /// Bytes of the file accessed through memory mapping (0 disables it)
+    #[serde(default)]
+    pub mmap_size: Option<i64>,
+    /// Build the warehouse in memory during a pipeline run and write the file once at the end
+    #[serde(default)]
+    pub in_memory: bool,
+}
+
+impl JournalMode {
+    fn pragma(self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Memory => "MEMORY",
+            JournalMode::Wal => "WAL",
+            JournalMode::Off => "OFF",
+        }
+    }
+}
+
+impl Synchronous {
+    fn pragma(self) -> &'static str {
+        match self {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+            Synchronous::Extra => "EXTRA",
+        }
+    }
+}
+
+impl TempStore {
+    fn pragma(self) -> &'static str {
+        match self {
+            TempStore::Default => "DEFAULT",
+            TempStore::File => "FILE",
+            TempStore::Memory => "MEMORY",
+        }
+    }
+}
+
+/// Periods (AnoMes and Ano) touched by a load, used to scope pivot refreshes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeriodSet {
+    pub months: BTreeSet<String>,
+    pub years: BTreeSet<String>,
+}
+
+impl PeriodSet {
+    /// Collect the periods of a batch of loaded transactions
+    pub fn from_transactions<'a, I: IntoIterator<Item = &'a ProcessedTransaction>>(transactions: I) -> Self {
+        let mut periods = Self::default();
+        periods.add(transactions);
+        periods
+    }
+    
+    /// Add the periods of another batch (a chunk of a streamed load)
+    pub fn add<'a, I: IntoIterator<Item = &'a ProcessedTransaction>>(&mut self, transactions: I) {
+        for transaction in transactions {
+            self.months.insert(transaction.year_month.clone());
+            self.years.insert(transaction.year.clone());
+        }
+    }
+    
+    /// Build from AnoMes values ("2024/01"), deriving the years
+    pub fn from_months<I: IntoIterator<Item = String>>(months: I) -> Self {
+        let mut periods = Self::default();
+        for month in months {
+            periods.years.insert(month.chars().take(4).collect());
+            periods.months.insert(month);
+        }
+        periods
+    }
+    
+    /// True when no period was touched
+    pub fn is_empty(&self) -> bool {
+        self.months.is_empty() && self.years.is_empty()
+    }
+}
+
+/// Stable identity of each transaction (hex FNV-1a over date, type, description,
+/// amounts and origin), numbered per occurrence so identical rows stay distinct
+pub fn row_hashes(transactions: &[ProcessedTransaction]) -> Vec<String> {
+    let mut hasher = RowHasher::default();
+    transactions.iter().map(|t| hasher.hash(t)).collect()
+}
+
+/// Row hashes numbered across several batches, so the chunks of a streamed
+/// load get the same hashes as a single batch would
+#[derive(Debug, Default)]
+pub struct RowHasher {
+    occurrences: HashMap<u64, u32>,
+}
+
+impl RowHasher {
+    /// Hash of the next occurrence of a transaction
+    pub fn hash(&mut self, t: &ProcessedTransaction) -> String {
+        let key = format!(
+            "{}\u{1f}{}\u{1f}{}\u{1f}{:.2}\u{1f}{:.2}\u{1f}{}",
+            t.date.format("%Y-%m-%d"), t.transaction_type, t.description, t.credit, t.debit, t.origin
+        );
+        let hash = fnv1a(key.as_bytes());
+        let occurrence = self.occurrences.entry(hash).or_insert(0);
+        *occurrence += 1;
+        format!("{:016x}-{}", hash, occurrence)
+    }
+}
+
+/// 64-bit FNV-1a, stable across builds and platforms
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Other processes holding the database (or its journal) open, as "name (pid N)",
+/// read from /proc
+#[cfg(target_os = "linux")]
+fn lock_holders(path: &Path) -> Vec<String> {
+    let Ok(database) = path.canonicalize() else {
+        return Vec::new();
+    };
+    let files: Vec<PathBuf> = ["", "-journal", "-wal"].iter()
+        .map(|suffix| PathBuf::from(format!("{}{}", database.display(), suffix)))
+        .collect();
+    let own_pid = std::process::id();
+    
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut holders = Vec::new();
+    for process in processes.filter_map(|entry| entry.ok()) {
+        let Some(pid) = process.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        // Processes of other users are not readable: skipped
+        let Ok(descriptors) = std::fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let holds = descriptors.filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+            .any(|target| files.contains(&target));
+        if holds && pid != own_pid {
+            let name = std::fs::read_to_string(process.path().join("comm")).unwrap_or_default();
+            holders.push(format!("{} (pid {})", name.trim(), pid));
+        }
+    }
+    holders
+}
+
+/// Other processes holding the database open; not known on this platform
+#[cfg(not(target_os = "linux"))]
+fn lock_holders(_path: &Path) -> Vec<String> {
+    Vec::new()
+}
+
+/// Copy every page of the main database of `from` into `to` (SQLite online backup)
+fn copy_database(from: &Connection, to: &mut Connection, operation: &str) -> Result<(), PdwError> {
+    Backup::new(from, to)
+        .and_then(|backup| backup.run_to_completion(BACKUP_PAGES_PER_STEP, std::time::Duration::ZERO, None))
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: operation.to_string(),
+            reason: e.to_string(),
+        }.into())
+}
+
+/// Quote an SQL identifier, doubling embedded double quotes
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
 }
 
 /// Processed transaction with enriched temporal data
@@ -24,13 +306,17 @@ pub struct ProcessedTransaction {
     pub day_of_week: String,
     pub transaction_type: String,
     pub description: String,
-    pub credit: f64,
-    pub debit: f64,
+    pub credit: Decimal,
+    pub debit: Decimal,
     pub month: String,
     pub year: String,
     pub month_name: String,
     pub year_month: String,
     pub origin: String,
+    /// Config-defined derived columns (name, value), persisted as extra columns
+    pub derived: Vec<(String, DerivedValue)>,
+    /// Currency the amounts were entered in, when converted from another than the base currency
+    pub currency: Option<String>,
 }
 
 impl DatabaseManager {
@@ -41,8 +327,296 @@ impl DatabaseManager {
                 path: db_path.to_string_lossy().to_string(),
                 reason: e.to_string(),
             })?;
+        Self::with_connection(connection, db_path)
+    }
+    
+    /// Open an existing database without write access, e.g. a baseline that must stay untouched
+    pub fn open_read_only(db_path: &Path) -> Result<Self, PdwError> {
+        let connection = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+            .map_err(|e| DatabaseError::ConnectionFailed {
+                path: db_path.to_string_lossy().to_string(),
+                reason: e.to_string(),
+            })?;
+        Self::with_connection(connection, db_path)
+    }
+    
+    fn with_connection(connection: Connection, db_path: &Path) -> Result<Self, PdwError> {
+        let mut manager = Self {
+            connection,
+            path: db_path.to_path_buf(),
+            insert_batch_size: DEFAULT_INSERT_BATCH_SIZE,
+            collation: TextCollation::Binary,
+            money: MoneyMode::default(),
+            busy_timeout_ms: DEFAULT_BUSY_TIMEOUT_MS,
+            in_memory: false,
+        };
+        manager.set_busy_timeout(DEFAULT_BUSY_TIMEOUT_MS)?;
+        Ok(manager)
+    }
+    
+    /// Open the warehouse database applying the busy timeout, SQLite tuning, integrity check,
+    /// batch size and collation settings. The journal mode is stored in the file, so other
+    /// databases (diff baselines, consolidation inputs) are opened with [`new`](Self::new)
+    pub fn open_configured(db_path: &Path, settings: &SettingsConfig, tuning: &SqliteTuning) -> Result<Self, PdwError> {
+        let existed = db_path.exists();
+        let mut manager = Self::new(db_path)?;
+        manager.set_busy_timeout(settings.busy_timeout_ms)?;
+        manager.apply_tuning(tuning)?;
+        if existed {
+            manager.check_integrity(settings.integrity_check)
+                .map_err(|e| manager.explain_lock(e))?;
+        }
+        
+        manager.set_insert_batch_size(settings.insert_batch_size);
+        manager.set_collation(settings.collation)?;
+        manager.set_money_mode(settings.money);
+        Ok(manager)
+    }
+    
+    /// Set the journal mode, synchronous level, cache, temp store and mmap pragmas
+    pub fn apply_tuning(&self, tuning: &SqliteTuning) -> Result<(), PdwError> {
+        let pragma_error = |pragma: &str, e: rusqlite::Error| DatabaseError::SqlExecution {
+            query: format!("PRAGMA {}", pragma),
+            reason: e.to_string(),
+        };
+        
+        // journal_mode answers with the mode in effect (always "memory" for in-memory databases)
+        self.connection.pragma_update_and_check(None, "journal_mode", tuning.journal_mode.pragma(), |row| row.get::<_, String>(0))
+            .map_err(|e| pragma_error("journal_mode", e))?;
+        self.connection.pragma_update(None, "synchronous", tuning.synchronous.pragma())
+            .map_err(|e| pragma_error("synchronous", e))?;
+        self.connection.pragma_update(None, "temp_store", tuning.temp_store.pragma())
+            .map_err(|e| pragma_error("temp_store", e))?;
+        if let Some(cache_size) = tuning.cache_size {
+            self.connection.pragma_update(None, "cache_size", cache_size)
+                .map_err(|e| pragma_error("cache_size", e))?;
+        }
+        if let Some(mmap_size) = tuning.mmap_size {
+            self.connection.pragma_update_and_check(None, "mmap_size", mmap_size, |row| row.get::<_, i64>(0))
+                .map_err(|e| pragma_error("mmap_size", e))?;
+        }
+        Ok(())
+    }
+    
+    /// Continue on an in-memory copy of the database; the file is only rewritten by
+    /// [`flush_to_disk`](Self::flush_to_disk)
+    pub fn move_to_memory(&mut self) -> Result<(), PdwError> {
+        let mut memory = Connection::open_in_memory()
+            .map_err(|e| DatabaseError::ConnectionFailed {
+                path: ":memory:".to_string(),
+                reason: e.to_string(),
+            })?;
+        copy_database(&self.connection, &mut memory, "copy into memory")?;
+        
+        self.connection = memory;
+        self.in_memory = true;
+        self.set_busy_timeout(self.busy_timeout_ms)?;
+        if self.collation != TextCollation::Binary {
+            collation::register(&self.connection)?;
+        }
+        Ok(())
+    }
+    
+    /// Write a database built in memory to its file; false when it was not in memory
+    pub fn flush_to_disk(&self) -> Result<bool, PdwError> {
+        if !self.in_memory {
+            return Ok(false);
+        }
+        
+        let mut file = Connection::open(&self.path)
+            .map_err(|e| DatabaseError::ConnectionFailed {
+                path: self.path.to_string_lossy().to_string(),
+                reason: e.to_string(),
+            })?;
+        file.busy_timeout(std::time::Duration::from_millis(self.busy_timeout_ms))
+            .map_err(|e| DatabaseError::ConnectionFailed {
+                path: self.path.to_string_lossy().to_string(),
+                reason: e.to_string(),
+            })?;
+        copy_database(&self.connection, &mut file, "flush to disk")?;
+        Ok(true)
+    }
+    
+    /// Copy the database, as it is now, to another file
+    pub fn backup_to(&self, target: &Path) -> Result<(), PdwError> {
+        let mut file = Connection::open(target)
+            .map_err(|e| DatabaseError::ConnectionFailed {
+                path: target.to_string_lossy().to_string(),
+                reason: e.to_string(),
+            })?;
+        copy_database(&self.connection, &mut file, "backup")
+    }
+    
+    /// Wait up to `timeout_ms` for locks held by other connections before failing
+    pub fn set_busy_timeout(&mut self, timeout_ms: u64) -> Result<(), PdwError> {
+        self.connection.busy_timeout(std::time::Duration::from_millis(timeout_ms))
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: "busy_timeout".to_string(),
+                reason: e.to_string(),
+            })?;
+        self.busy_timeout_ms = timeout_ms;
+        Ok(())
+    }
+    
+    /// Turn an error caused by another connection's lock into [`DatabaseError::Locked`],
+    /// naming the processes that hold the file open where the OS tells; other errors pass through
+    pub fn explain_lock(&self, error: PdwError) -> PdwError {
+        if matches!(error, PdwError::Database(DatabaseError::Locked { .. })) {
+            return error;
+        }
+        // Lock errors usually arrive as the text of a wrapped rusqlite error:
+        // SQLITE_BUSY reads "database is locked", SQLITE_LOCKED "database table is locked"
+        let text = error.to_string();
+        if !text.contains("database is locked") && !text.contains("database table is locked") {
+            return error;
+        }
+        
+        let holders = lock_holders(&self.path);
+        let holder = if holders.is_empty() { "another process".to_string() } else { holders.join(", ") };
+        tracing::debug!("Lock error on {}: {}", self.path.display(), text);
+        
+        DatabaseError::Locked {
+            path: self.path.to_string_lossy().to_string(),
+            holder,
+            timeout_ms: self.busy_timeout_ms,
+        }.into()
+    }
+    
+    /// Select how pivot sums treat monetary amounts
+    pub fn set_money_mode(&mut self, money: MoneyMode) {
+        self.money = money;
+    }
+    
+    /// Arithmetic used for monetary amounts
+    pub fn money_mode(&self) -> MoneyMode {
+        self.money
+    }
+    
+    /// Add the integer cents columns to an entries table created before they existed
+    pub fn add_money_columns(&self, table_name: &str) -> Result<(), PdwError> {
+        self.add_derived_columns(table_name, &money::cents_schema())
+    }
+    
+    /// Select the text collation, registering it on the connection when needed
+    pub fn set_collation(&mut self, collation: TextCollation) -> Result<(), PdwError> {
+        if collation != TextCollation::Binary {
+            collation::register(&self.connection)?;
+        }
+        self.collation = collation;
+        Ok(())
+    }
+    
+    /// Text collation used for generated ORDER BY clauses
+    pub fn collation(&self) -> TextCollation {
+        self.collation
+    }
+    
+    /// ORDER BY expression for a text column under the configured collation
+    pub fn order_by(&self, column: &str) -> String {
+        self.collation.order_by(column)
+    }
+    
+    /// Set the number of rows committed per insert transaction (0 = single transaction)
+    pub fn set_insert_batch_size(&mut self, batch_size: usize) {
+        self.insert_batch_size = batch_size;
+    }
+    
+    /// Run `insert_one` for every item inside explicit transactions committed per batch
+    fn insert_batched<T>(
+        &self,
+        table_name: &str,
+        items: &[T],
+        mut insert_one: impl FnMut(&T) -> SqliteResult<usize>,
+    ) -> Result<usize, PdwError> {
+        // Nested use inside a caller-managed transaction: just insert
+        if !self.connection.is_autocommit() {
+            let mut count = 0;
+            for item in items {
+                count += insert_one(item).map_err(|e| DatabaseError::DataInsertion {
+                    table: table_name.to_string(),
+                    reason: e.to_string(),
+                })?;
+            }
+            return Ok(count);
+        }
+        
+        let batch_size = if self.insert_batch_size == 0 { items.len().max(1) } else { self.insert_batch_size };
+        let mut count = 0;
+        
+        for batch in items.chunks(batch_size) {
+            self.connection.execute_batch("BEGIN")
+                .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+            
+            for item in batch {
+                match insert_one(item) {
+                    Ok(inserted) => count += inserted,
+                    Err(e) => {
+                        let _ = self.connection.execute_batch("ROLLBACK");
+                        return Err(DatabaseError::DataInsertion {
+                            table: table_name.to_string(),
+                            reason: e.to_string(),
+                        }.into());
+                    }
+                }
+            }
+            
+            self.connection.execute_batch("COMMIT")
+                .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+            tracing::debug!("Committed {} rows into {}", batch.len(), table_name);
+        }
+        
+        Ok(count)
+    }
+    
+    /// Open a database, verifying its integrity first if the file already exists
+    pub fn open_checked(db_path: &Path, check: IntegrityCheck) -> Result<Self, PdwError> {
+        let existed = db_path.exists();
+        let manager = Self::new(db_path)?;
+        
+        if existed {
+            manager.check_integrity(check)?;
+        }
+        
+        Ok(manager)
+    }
+    
+    /// Run an integrity check, returning DatabaseError::Corrupted on failure
+    pub fn check_integrity(&self, check: IntegrityCheck) -> Result<(), PdwError> {
+        let pragma = match check {
+            IntegrityCheck::Off => return Ok(()),
+            IntegrityCheck::Quick => "PRAGMA quick_check",
+            IntegrityCheck::Full => "PRAGMA integrity_check",
+        };
+        
+        let corrupted = |details: String| DatabaseError::Corrupted {
+            path: self.path.to_string_lossy().to_string(),
+            details,
+        };
+        
+        // A badly damaged file fails before returning any rows
+        let rows = self.execute_query(pragma)
+            .map_err(|e| corrupted(e.to_string()))?;
+        
+        let messages: Vec<String> = rows.iter()
+            .filter_map(|row| row.first().and_then(Value::as_str).map(str::to_string))
+            .collect();
+        
+        if messages.len() == 1 && messages[0] == "ok" {
+            tracing::debug!("Database integrity check ({:?}) passed: {}", check, self.path.display());
+            return Ok(());
+        }
+        
+        let mut details = messages.iter().take(5).cloned().collect::<Vec<_>>().join("; ");
+        if messages.len() > 5 {
+            details.push_str(&format!("; ... {} more", messages.len() - 5));
+        }
         
-        Ok(Self { connection })
+        Err(corrupted(details).into())
+    }
+    
+    /// Path of the underlying database file
+    pub fn path(&self) -> &Path {
+        &self.path
     }
     
     /// Create all required database tables
@@ -60,7 +634,10 @@ impl DatabaseManager {
                 Ano TEXT,
                 MES_EXTENSO TEXT,
                 AnoMes TEXT,
-                Origem TEXT
+                Origem TEXT,
+                RowHash TEXT,
+                CreditoCentavos INTEGER,
+                DebitoCentavos INTEGER
             )",
             [],
         ).map_err(|e| DatabaseError::SqlExecution {
@@ -121,73 +698,133 @@ impl DatabaseManager {
         Ok(())
     }
     
-    /// Insert processed transactions
-    pub fn insert_transactions(&self, transactions: &[ProcessedTransaction]) -> Result<usize, PdwError> {
-        let mut stmt = self.connection.prepare(
-            "INSERT INTO LANCAMENTOS_GERAIS 
-             (Data, DIA_SEMANA, TIPO, DESCRICAO, Credito, Debito, Mes, Ano, MES_EXTENSO, AnoMes, Origem)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"
-        ).map_err(|e| DatabaseError::SqlExecution {
-            query: "INSERT INTO LANCAMENTOS_GERAIS".to_string(),
+    /// Run `f` with writes refused on the connection (`PRAGMA query_only`), for SQL taken from
+    /// outside PDW such as the YAML reports; the previous mode is restored afterwards
+    pub fn read_only<T>(&self, f: impl FnOnce(&Self) -> Result<T, PdwError>) -> Result<T, PdwError> {
+        let pragma_error = |e: rusqlite::Error| DatabaseError::SqlExecution {
+            query: "PRAGMA query_only".to_string(),
             reason: e.to_string(),
-        })?;
-        
-        let mut count = 0;
-        for transaction in transactions {
-            stmt.execute(params![
-                transaction.date.format("%Y-%m-%d").to_string(),
-                transaction.day_of_week,
-                transaction.transaction_type,
-                transaction.description,
-                transaction.credit,
-                transaction.debit,
-                transaction.month,
-                transaction.year,
-                transaction.month_name,
-                transaction.year_month,
-                transaction.origin,
-            ]).map_err(|e| DatabaseError::DataInsertion {
-                table: "LANCAMENTOS_GERAIS".to_string(),
+        };
+        let was_query_only: bool = self.connection.query_row("PRAGMA query_only", [], |row| row.get(0))
+            .map_err(pragma_error)?;
+        self.connection.pragma_update(None, "query_only", true).map_err(pragma_error)?;
+        let result = f(self);
+        self.connection.pragma_update(None, "query_only", was_query_only).map_err(pragma_error)?;
+        result
+    }
+    
+    /// Delete the entries dated between two days (inclusive); returns how many were deleted
+    pub fn delete_entries_between(&self, table_name: &str, first: NaiveDate, last: NaiveDate) -> Result<usize, PdwError> {
+        let query = format!("DELETE FROM {} WHERE Data BETWEEN ?1 AND ?2", table_name);
+        self.connection.execute(&query, [first.format("%Y-%m-%d").to_string(), last.format("%Y-%m-%d").to_string()])
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: query.clone(),
                 reason: e.to_string(),
-            })?;
-            count += 1;
+            }.into())
+    }
+    
+    /// Add derived columns to a table, skipping columns that already exist
+    pub fn add_derived_columns(&self, table_name: &str, columns: &[(String, &str)]) -> Result<(), PdwError> {
+        let existing = self.table_columns(table_name)?;
+        
+        for (name, sql_type) in columns {
+            if existing.iter().any(|c| c.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            
+            let query = format!("ALTER TABLE {} ADD COLUMN [{}] {}", table_name, name, sql_type);
+            self.connection.execute(&query, [])
+                .map_err(|e| DatabaseError::SqlExecution {
+                    query: query.clone(),
+                    reason: e.to_string(),
+                })?;
         }
         
-        Ok(count)
+        Ok(())
     }
     
-    /// Insert reference data
-    pub fn insert_reference_data(&self, table_name: &str, data: &[Vec<String>]) -> Result<usize, PdwError> {
-        if data.is_empty() {
-            return Ok(0);
-        }
+    /// Get column names of a table
+    pub fn table_columns(&self, table_name: &str) -> Result<Vec<String>, PdwError> {
+        let query = format!("PRAGMA table_info({})", table_name);
+        let rows = self.execute_query(&query)?;
         
-        // Create table dynamically based on data structure
-        let column_count = data[0].len();
-        let columns: Vec<String> = (1..=column_count)
-            .map(|i| format!("col{} TEXT", i))
+        Ok(rows.into_iter()
+            .filter_map(|row| match row.get(1) {
+                Some(Value::String(name)) => Some(name.clone()),
+                _ => None,
+            })
+            .collect())
+    }
+    
+    /// Insert processed transactions
+    pub fn insert_transactions(&self, transactions: &[ProcessedTransaction]) -> Result<usize, PdwError> {
+        let rows: Vec<(&ProcessedTransaction, String)> = transactions.iter()
+            .zip(row_hashes(transactions))
+            .collect();
+        self.insert_hashed_transactions(&rows)
+    }
+    
+    /// Insert one chunk of a streamed load, numbering repeated rows across the chunks
+    pub fn insert_transaction_chunk(&self, transactions: &[ProcessedTransaction], hasher: &mut RowHasher) -> Result<usize, PdwError> {
+        let rows: Vec<(&ProcessedTransaction, String)> = transactions.iter()
+            .map(|transaction| (transaction, hasher.hash(transaction)))
+            .collect();
+        self.insert_hashed_transactions(&rows)
+    }
+    
+    /// Insert only transactions whose row hash is not stored yet, returning them
+    pub fn insert_new_transactions<'a>(&self, transactions: &'a [ProcessedTransaction])
+                                       -> Result<Vec<&'a ProcessedTransaction>, PdwError> {
+        let existing: HashSet<String> = self
+            .execute_query("SELECT RowHash FROM LANCAMENTOS_GERAIS WHERE RowHash IS NOT NULL")?
+            .into_iter()
+            .filter_map(|mut row| match row.pop() {
+                Some(Value::String(hash)) => Some(hash),
+                _ => None,
+            })
             .collect();
         
-        let create_query = format!(
-            "CREATE TABLE IF NOT EXISTS {} ({})",
-            table_name,
-            columns.join(", ")
-        );
+        let rows: Vec<(&ProcessedTransaction, String)> = transactions.iter()
+            .zip(row_hashes(transactions))
+            .filter(|(_, hash)| !existing.contains(hash))
+            .collect();
+        self.insert_hashed_transactions(&rows)?;
         
-        self.connection.execute(&create_query, [])
+        Ok(rows.into_iter().map(|(transaction, _)| transaction).collect())
+    }
+    
+    /// Number of entries loaded without a row hash (before incremental loading existed)
+    pub fn count_unhashed_rows(&self, table_name: &str) -> Result<i64, PdwError> {
+        let query = format!("SELECT COUNT(*) FROM {} WHERE RowHash IS NULL", table_name);
+        self.connection.query_row(&query, [], |row| row.get(0))
             .map_err(|e| DatabaseError::SqlExecution {
-                query: create_query,
+                query: query.clone(),
                 reason: e.to_string(),
-            })?;
+            }.into())
+    }
+    
+    /// Insert transactions together with their row hashes
+    fn insert_hashed_transactions(&self, rows: &[(&ProcessedTransaction, String)]) -> Result<usize, PdwError> {
+        let derived_names: Vec<&str> = rows.first()
+            .map(|(t, _)| t.derived.iter().map(|(name, _)| name.as_str()).collect())
+            .unwrap_or_default();
         
-        // Insert data
-        let placeholders: Vec<String> = (1..=column_count)
+        let mut columns = vec![
+            "Data", "DIA_SEMANA", "TIPO", "DESCRICAO", "Credito", "Debito",
+            "Mes", "Ano", "MES_EXTENSO", "AnoMes", "Origem", "RowHash",
+            money::CREDIT_CENTS_COLUMN, money::DEBIT_CENTS_COLUMN,
+        ].into_iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        columns.extend(derived_names.iter().map(|name| format!("[{}]", name)));
+        
+        let placeholders: Vec<String> = (1..=columns.len())
             .map(|i| format!("?{}", i))
             .collect();
         
         let insert_query = format!(
-            "INSERT INTO {} VALUES ({})",
-            table_name,
+            "INSERT INTO LANCAMENTOS_GERAIS ({}) VALUES ({})",
+            columns.join(", "),
             placeholders.join(", ")
         );
         
@@ -197,25 +834,108 @@ impl DatabaseManager {
                 reason: e.to_string(),
             })?;
         
-        let mut count = 0;
-        for row in data {
-            let params: Vec<&dyn rusqlite::ToSql> = row.iter()
-                .map(|s| s as &dyn rusqlite::ToSql)
-                .collect();
+        self.insert_batched("LANCAMENTOS_GERAIS", rows, |(transaction, row_hash)| {
+            let date = transaction.date.format("%Y-%m-%d").to_string();
+            let (credit, debit) = (money::to_f64(transaction.credit), money::to_f64(transaction.debit));
+            let (credit_cents, debit_cents) = (money::to_cents(transaction.credit), money::to_cents(transaction.debit));
+            let mut values: Vec<&dyn rusqlite::ToSql> = vec![
+                &date,
+                &transaction.day_of_week,
+                &transaction.transaction_type,
+                &transaction.description,
+                &credit,
+                &debit,
+                &transaction.month,
+                &transaction.year,
+                &transaction.month_name,
+                &transaction.year_month,
+                &transaction.origin,
+                row_hash,
+                &credit_cents,
+                &debit_cents,
+            ];
+            for (_, value) in &transaction.derived {
+                values.push(value);
+            }
             
-            stmt.execute(&params[..])
-                .map_err(|e| DatabaseError::DataInsertion {
-                    table: table_name.to_string(),
+            stmt.execute(&values[..])
+        })
+    }
+    
+    /// Insert the rows of a reference sheet into a table named after its header row; returns the rows inserted
+    pub fn insert_reference_data(&self, table_name: &str, data: &[Vec<String>]) -> Result<usize, PdwError> {
+        let Some(sheet) = ReferenceSheet::from_rows(data) else {
+            return Ok(0);
+        };
+        
+        // Create the table from the header, or fill the one already there
+        let existing = self.table_columns(table_name)?;
+        let target_columns = if existing.is_empty() {
+            let columns: Vec<String> = sheet.columns.iter()
+                .map(|(name, column_type)| format!("{} {}", quote_identifier(name), column_type.sql_type()))
+                .collect();
+            let create_query = format!("CREATE TABLE {} ({})", table_name, columns.join(", "));
+            self.connection.execute(&create_query, [])
+                .map_err(|e| DatabaseError::SqlExecution {
+                    query: create_query,
                     reason: e.to_string(),
                 })?;
-            count += 1;
-        }
+            sheet.columns.iter().map(|(name, _)| name.clone()).collect()
+        } else {
+            sheet.target_columns(&existing).unwrap_or_default()
+        };
         
-        Ok(count)
+        let column_list = if target_columns.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", target_columns.iter().map(|name| quote_identifier(name)).collect::<Vec<_>>().join(", "))
+        };
+        let placeholders: Vec<String> = (1..=sheet.columns.len())
+            .map(|i| format!("?{}", i))
+            .collect();
+        
+        let insert_query = format!(
+            "INSERT INTO {}{} VALUES ({})",
+            table_name,
+            column_list,
+            placeholders.join(", ")
+        );
+        
+        let mut stmt = self.connection.prepare(&insert_query)
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: insert_query.clone(),
+                reason: e.to_string(),
+            })?;
+        
+        self.insert_batched(table_name, sheet.rows, |row| {
+            let values = sheet.values(row)
+                .ok_or(rusqlite::Error::InvalidParameterCount(row.len(), sheet.columns.len()))?;
+            if values.iter().all(Option::is_none) {
+                return Ok(0);
+            }
+            
+            stmt.execute(rusqlite::params_from_iter(values))
+        })
     }
     
     /// Execute SQL query and return results
     pub fn execute_query(&self, sql: &str) -> Result<Vec<Vec<Value>>, PdwError> {
+        self.execute_query_with_params(sql, [])
+    }
+    
+    /// Column names a query would return (without executing it)
+    pub fn query_columns(&self, sql: &str) -> Result<Vec<String>, PdwError> {
+        let stmt = self.connection.prepare(sql)
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: sql.to_string(),
+                reason: e.to_string(),
+            })?;
+        
+        Ok(stmt.column_names().into_iter().map(String::from).collect())
+    }
+    
+    /// Execute SQL query with bound parameters and return results
+    pub fn execute_query_with_params<P: rusqlite::Params>(&self, sql: &str, params: P) -> Result<Vec<Vec<Value>>, PdwError> {
         let mut stmt = self.connection.prepare(sql)
             .map_err(|e| DatabaseError::SqlExecution {
                 query: sql.to_string(),
@@ -223,7 +943,7 @@ impl DatabaseManager {
             })?;
         
         let column_count = stmt.column_count();
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params, |row| {
             let mut values = Vec::new();
             for i in 0..column_count {
                 let value: rusqlite::types::Value = row.get(i)?;
@@ -256,99 +976,182 @@ impl DatabaseManager {
     }
     
     /// Create pivot tables for historical analysis
-    pub fn create_pivot_tables(&self, entries_table: &str, types_table: &str, 
-                              full_pivot_table: &str, annual_pivot_table: &str) -> Result<(), PdwError> {
+    pub fn create_pivot_tables(&self, entries_table: &str, types_table: &str,
+                              full_pivot_table: &str, annual_pivot_table: &str,
+                              normalizer: Option<&TypeNormalizer>) -> Result<(), PdwError> {
         
-        // Get transaction types for column ordering
-        let types_query = format!("SELECT Descrição FROM {}", types_table);
-        let types_result = self.execute_query(&types_query)?;
+        let types_result = self.pivot_types(types_table, normalizer)?;
+        if self.money == MoneyMode::Decimal {
+            self.add_money_columns(entries_table)?;
+        }
         
         // Create monthly pivot table
-        self.create_monthly_pivot(entries_table, full_pivot_table, &types_result)?;
+        self.create_pivot(entries_table, full_pivot_table, "AnoMes", &types_result)?;
         
-        // Create annual pivot table  
-        self.create_annual_pivot(entries_table, annual_pivot_table, &types_result)?;
+        // Create annual pivot table
+        self.create_pivot(entries_table, annual_pivot_table, "Ano", &types_result)?;
         
         Ok(())
     }
     
-    /// Create monthly pivot table
-    fn create_monthly_pivot(&self, entries_table: &str, pivot_table: &str, 
-                           types: &[Vec<Value>]) -> Result<(), PdwError> {
+    /// Recompute only the pivot rows of the given periods; returns false when a full rebuild is needed
+    pub fn refresh_pivot_periods(&self, entries_table: &str, types_table: &str,
+                                 full_pivot_table: &str, annual_pivot_table: &str,
+                                 normalizer: Option<&TypeNormalizer>, periods: &PeriodSet) -> Result<bool, PdwError> {
+        let types = self.pivot_types(types_table, normalizer)?;
+        if self.money == MoneyMode::Decimal {
+            self.add_money_columns(entries_table)?;
+        }
         
-        // Drop existing table
-        self.drop_table(pivot_table)?;
+        // New or removed types change the pivot schema
+        for (pivot_table, period_column) in [(full_pivot_table, "AnoMes"), (annual_pivot_table, "Ano")] {
+            let mut expected = vec![period_column.to_string()];
+            expected.extend(Self::pivot_type_names(&types).into_iter().map(String::from));
+            if self.table_columns(pivot_table)? != expected {
+                tracing::info!("Pivot {} missing or schema changed - full rebuild required", pivot_table);
+                return Ok(false);
+            }
+        }
+        
+        self.connection.execute_batch("BEGIN")
+            .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
         
-        // Build dynamic pivot query
-        let mut columns = vec!["AnoMes TEXT".to_string()];
-        let mut select_columns = vec!["AnoMes".to_string()];
-        
-        for type_row in types {
-            if let Some(Value::String(type_name)) = type_row.get(0) {
-                let safe_name = type_name.replace(" ", "_").replace("'", "");
-                columns.push(format!("[{}] REAL", type_name));
-                select_columns.push(format!(
-                    "COALESCE(SUM(CASE WHEN TIPO = '{}' THEN Debito ELSE 0 END), 0) AS [{}]",
-                    type_name, type_name
-                ));
+        let result = self.refresh_pivot(entries_table, full_pivot_table, "AnoMes", &types, &periods.months)
+            .and_then(|_| self.refresh_pivot(entries_table, annual_pivot_table, "Ano", &types, &periods.years));
+        
+        match result {
+            Ok(()) => {
+                self.connection.execute_batch("COMMIT")
+                    .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+                tracing::info!("Pivots refreshed for {} months / {} years", periods.months.len(), periods.years.len());
+                Ok(true)
+            }
+            Err(e) => {
+                let _ = self.connection.execute_batch("ROLLBACK");
+                Err(e)
             }
         }
+    }
+    
+    /// Delete and recompute the pivot rows of the given period values
+    fn refresh_pivot(&self, entries_table: &str, pivot_table: &str, period_column: &str,
+                     types: &[Vec<Value>], periods: &BTreeSet<String>) -> Result<(), PdwError> {
+        if periods.is_empty() {
+            return Ok(());
+        }
         
-        // Create table
-        let create_query = format!(
-            "CREATE TABLE {} ({})",
-            pivot_table,
-            columns.join(", ")
-        );
+        let type_names = Self::pivot_type_names(types);
+        let periods: Vec<&str> = periods.iter().map(String::as_str).collect();
+        let period_list = |first: usize| -> String {
+            (first..first + periods.len()).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ")
+        };
         
-        self.connection.execute(&create_query, [])
+        let delete_query = format!(
+            "DELETE FROM {} WHERE {} IN ({})",
+            quote_identifier(pivot_table), quote_identifier(period_column), period_list(1)
+        );
+        self.connection.execute(&delete_query, rusqlite::params_from_iter(&periods))
             .map_err(|e| DatabaseError::SqlExecution {
-                query: create_query,
+                query: delete_query.clone(),
                 reason: e.to_string(),
             })?;
         
-        // Insert pivot data
+        // Type names bind to ?1..?n, the periods follow them
         let insert_query = format!(
-            "INSERT INTO {} SELECT {} FROM {} GROUP BY AnoMes ORDER BY AnoMes",
-            pivot_table,
-            select_columns.join(", "),
-            entries_table
+            "INSERT INTO {} SELECT {} FROM {} WHERE {} IN ({}) GROUP BY {} ORDER BY {}",
+            quote_identifier(pivot_table),
+            self.pivot_select_columns(period_column, &type_names).join(", "),
+            quote_identifier(entries_table),
+            quote_identifier(period_column),
+            period_list(type_names.len() + 1),
+            quote_identifier(period_column),
+            quote_identifier(period_column)
         );
-        
-        self.connection.execute(&insert_query, [])
+        let params = type_names.iter().chain(periods.iter());
+        self.connection.execute(&insert_query, rusqlite::params_from_iter(params))
             .map_err(|e| DatabaseError::SqlExecution {
-                query: insert_query,
+                query: insert_query.clone(),
                 reason: e.to_string(),
             })?;
         
         Ok(())
     }
     
-    /// Create annual pivot table
-    fn create_annual_pivot(&self, entries_table: &str, pivot_table: &str, 
-                          types: &[Vec<Value>]) -> Result<(), PdwError> {
+    /// SELECT list of a pivot: the period column plus one debit sum per type, names bound to ?1..?n
+    fn pivot_select_columns(&self, period_column: &str, type_names: &[&str]) -> Vec<String> {
+        let mut select_columns = vec![quote_identifier(period_column)];
+        for (index, type_name) in type_names.iter().enumerate() {
+            select_columns.push(format!(
+                "COALESCE({}, 0) AS {}",
+                self.money.sum_sql("Debito", Some(&format!("TIPO = ?{}", index + 1))),
+                quote_identifier(type_name)
+            ));
+        }
+        select_columns
+    }
+    
+    /// Names of the type columns of a pivot, without blanks or case-insensitive duplicates
+    pub(crate) fn pivot_type_names(types: &[Vec<Value>]) -> Vec<&str> {
+        let mut seen = std::collections::HashSet::new();
+        types.iter()
+            .filter_map(|type_row| match type_row.first() {
+                Some(Value::String(type_name)) if !type_name.trim().is_empty() => Some(type_name.as_str()),
+                _ => None,
+            })
+            .filter(|type_name| {
+                // SQLite column names only differ by more than ASCII case
+                let is_new = seen.insert(type_name.to_ascii_lowercase());
+                if !is_new {
+                    tracing::warn!("Type {} duplicates a pivot column and is skipped", type_name);
+                }
+                is_new
+            })
+            .collect()
+    }
+    
+    /// Transaction types that become pivot columns
+    fn pivot_types(&self, types_table: &str, normalizer: Option<&TypeNormalizer>) -> Result<Vec<Vec<Value>>, PdwError> {
+        // Get transaction types for column ordering (sheet order unless a collation is set)
+        let types_query = match self.collation {
+            TextCollation::Binary => format!("SELECT Descrição FROM {}", types_table),
+            _ => format!("SELECT Descrição FROM {} ORDER BY {}", types_table, self.order_by("Descrição")),
+        };
+        let mut types_result = self.execute_query(&types_query)?;
+        
+        // Pivot on normalized type names so they match the normalized TIPO values
+        if let Some(normalizer) = normalizer {
+            let mut seen = std::collections::HashSet::new();
+            types_result = types_result.into_iter()
+                .filter_map(|row| match row.first() {
+                    Some(Value::String(name)) => Some(normalizer.normalize(name)),
+                    _ => None,
+                })
+                .filter(|name| seen.insert(name.clone()))
+                .map(|name| vec![Value::String(name)])
+                .collect();
+        }
+        
+        Ok(types_result)
+    }
+    
+    /// Create a pivot table with one row per period and one debit column per type
+    fn create_pivot(&self, entries_table: &str, pivot_table: &str, period_column: &str,
+                    types: &[Vec<Value>]) -> Result<(), PdwError> {
         
         // Drop existing table
         self.drop_table(pivot_table)?;
         
-        // Build dynamic pivot query
-        let mut columns = vec!["Ano TEXT".to_string()];
-        let mut select_columns = vec!["Ano".to_string()];
-        
-        for type_row in types {
-            if let Some(Value::String(type_name)) = type_row.get(0) {
-                columns.push(format!("[{}] REAL", type_name));
-                select_columns.push(format!(
-                    "COALESCE(SUM(CASE WHEN TIPO = '{}' THEN Debito ELSE 0 END), 0) AS [{}]",
-                    type_name, type_name
-                ));
-            }
+        // Identifiers are quoted; type names only reach the SQL as bound values
+        let type_names = Self::pivot_type_names(types);
+        let mut columns = vec![format!("{} TEXT", quote_identifier(period_column))];
+        for type_name in &type_names {
+            columns.push(format!("{} REAL", quote_identifier(type_name)));
         }
         
         // Create table
         let create_query = format!(
             "CREATE TABLE {} ({})",
-            pivot_table,
+            quote_identifier(pivot_table),
             columns.join(", ")
         );
         
@@ -360,13 +1163,15 @@ impl DatabaseManager {
         
         // Insert pivot data
         let insert_query = format!(
-            "INSERT INTO {} SELECT {} FROM {} GROUP BY Ano ORDER BY Ano",
-            pivot_table,
-            select_columns.join(", "),
-            entries_table
+            "INSERT INTO {} SELECT {} FROM {} GROUP BY {} ORDER BY {}",
+            quote_identifier(pivot_table),
+            self.pivot_select_columns(period_column, &type_names).join(", "),
+            quote_identifier(entries_table),
+            quote_identifier(period_column),
+            quote_identifier(period_column)
         );
         
-        self.connection.execute(&insert_query, [])
+        self.connection.execute(&insert_query, rusqlite::params_from_iter(&type_names))
             .map_err(|e| DatabaseError::SqlExecution {
                 query: insert_query,
                 reason: e.to_string(),
@@ -375,26 +1180,11 @@ impl DatabaseManager {
         Ok(())
     }
     
-    /// Perform data validation and cleanup
-    pub fn validate_and_clean_data(&self, entries_table: &str, types_table: &str, 
-                                  save_discarded: bool, discarded_table: &str) -> Result<(), PdwError> {
-        
-        if save_discarded {
-            // Save discarded data
-            let save_query = format!(
-                "CREATE TABLE IF NOT EXISTS {} AS SELECT * FROM {} WHERE (Data IS NULL OR TIPO IS NULL)",
-                discarded_table, entries_table
-            );
-            self.connection.execute(&save_query, [])
-                .map_err(|e| DatabaseError::SqlExecution {
-                    query: save_query,
-                    reason: e.to_string(),
-                })?;
-        }
-        
-        // Remove invalid records
+    /// Clean up the reference tables; entries that cannot be loaded are quarantined instead
+    /// (see the quarantine module)
+    pub fn validate_and_clean_data(&self, types_table: &str) -> Result<(), PdwError> {
+        // Remove invalid reference rows
         let cleanup_queries = vec![
-            format!("DELETE FROM {} WHERE (Data IS NULL OR TIPO IS NULL)", entries_table),
             format!("DELETE FROM {} WHERE (Código IS NULL OR Descrição IS NULL)", types_table),
             "DELETE FROM PARCELAMENTOS WHERE (DATA IS NULL OR \"Tipo Lançamento\" IS NULL)".to_string(),
         ];
@@ -415,8 +1205,8 @@ impl DatabaseManager {
             })?;
         
         self.connection.execute(
-            "CREATE VIEW Origens AS 
-             SELECT TABLE_NAME as nome FROM GUIDING 
+            "CREATE VIEW Origens AS
+             SELECT TABLE_NAME as nome FROM GUIDING
              WHERE LOADABLE = 'X' AND ACCOUNTING = 'X'",
             []
         ).map_err(|e| DatabaseError::SqlExecution {
@@ -427,10 +1217,153 @@ impl DatabaseManager {
         Ok(())
     }
     
+    /// Fill an empty types table with the distinct TIPOs of the entries; returns the types added
+    pub fn seed_types(&self, entries_table: &str, types_table: &str) -> Result<usize, PdwError> {
+        let existing = self.execute_query(&format!("SELECT COUNT(*) FROM {}", types_table))?;
+        if existing.first().and_then(|row| row.first()).and_then(Value::as_i64).unwrap_or(0) > 0 {
+            return Ok(0);
+        }
+        
+        // The code doubles as description: pivots name their columns after it
+        let seed_query = format!(
+            "INSERT INTO {} SELECT DISTINCT TRIM(TIPO), TRIM(TIPO) FROM {}
+             WHERE TIPO IS NOT NULL AND TRIM(TIPO) <> '' ORDER BY 1",
+            types_table, entries_table
+        );
+        self.connection.execute(&seed_query, [])
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: seed_query,
+                reason: e.to_string(),
+            }.into())
+    }
+    
     /// Get connection reference for advanced operations
     pub fn connection(&self) -> &Connection {
         &self.connection
     }
+    
+    /// Persistent key/value state of the warehouse
+    pub fn meta(&self) -> MetaStore<'_> {
+        MetaStore { database: self }
+    }
+}
+
+/// Typed key/value state kept in the warehouse (PDW_META), grouped by namespace
+///
+/// Values are stored as JSON, so any serde type round-trips. Reads never create
+/// the table, leaving databases of other producers untouched until the first write.
+pub struct MetaStore<'a> {
+    database: &'a DatabaseManager,
+}
+
+impl MetaStore<'_> {
+    /// Producer stamp and schema version (see the compat module)
+    pub const SCHEMA: &'static str = "schema";
+    /// Summary of the latest load
+    pub const LAST_LOAD: &'static str = "last_load";
+    /// Content hashes of input files, keyed by file name
+    pub const FILE_HASHES: &'static str = "file_hashes";
+    /// Months (AnoMes) closed for changes
+    pub const CLOSED_MONTHS: &'static str = "closed_months";
+    /// Phases completed by an unfinished pipeline run
+    pub const CHECKPOINT: &'static str = "checkpoint";
+    /// When each schema migration was applied, keyed by version
+    pub const MIGRATIONS: &'static str = "migrations";
+    
+    /// Value of a key, or None when unset
+    pub fn get<T: DeserializeOwned>(&self, namespace: &str, key: &str) -> Result<Option<T>, PdwError> {
+        if !self.exists()? {
+            return Ok(None);
+        }
+        
+        let rows = self.database.execute_query_with_params(
+            &format!("SELECT Valor FROM {} WHERE Namespace = ?1 AND Chave = ?2", META_TABLE),
+            [namespace, key],
+        )?;
+        match rows.first().and_then(|row| row.first()) {
+            Some(Value::String(json)) => serde_json::from_str(json).map(Some)
+                .map_err(|e| DatabaseError::SchemaValidation {
+                    reason: format!("{} value {}.{} does not match the expected type: {}", META_TABLE, namespace, key, e),
+                }.into()),
+            _ => Ok(None),
+        }
+    }
+    
+    /// Set a key, replacing its previous value
+    pub fn set<T: Serialize + ?Sized>(&self, namespace: &str, key: &str, value: &T) -> Result<(), PdwError> {
+        let json = serde_json::to_string(value).map_err(|e| DatabaseError::DataInsertion {
+            table: META_TABLE.to_string(),
+            reason: e.to_string(),
+        })?;
+        self.ensure_table()?;
+        
+        let updated_at = crate::deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        self.database.connection.execute(
+            &format!(
+                "INSERT INTO {} (Namespace, Chave, Valor, Atualizado) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (Namespace, Chave) DO UPDATE SET Valor = excluded.Valor, Atualizado = excluded.Atualizado",
+                META_TABLE
+            ),
+            params![namespace, key, json, updated_at],
+        ).map_err(|e| DatabaseError::DataInsertion {
+            table: META_TABLE.to_string(),
+            reason: e.to_string(),
+        })?;
+        
+        Ok(())
+    }
+    
+    /// Delete a key; returns whether it was set
+    pub fn remove(&self, namespace: &str, key: &str) -> Result<bool, PdwError> {
+        if !self.exists()? {
+            return Ok(false);
+        }
+        
+        let query = format!("DELETE FROM {} WHERE Namespace = ?1 AND Chave = ?2", META_TABLE);
+        let removed = self.database.connection.execute(&query, params![namespace, key])
+            .map_err(|e| DatabaseError::SqlExecution { query, reason: e.to_string() })?;
+        Ok(removed > 0)
+    }
+    
+    /// Raw JSON values of a namespace, by key
+    pub fn entries(&self, namespace: &str) -> Result<BTreeMap<String, Value>, PdwError> {
+        if !self.exists()? {
+            return Ok(BTreeMap::new());
+        }
+        
+        let rows = self.database.execute_query_with_params(
+            &format!("SELECT Chave, Valor FROM {} WHERE Namespace = ?1", META_TABLE),
+            [namespace],
+        )?;
+        Ok(rows.into_iter()
+            .filter_map(|row| match (row.first(), row.get(1)) {
+                (Some(Value::String(key)), Some(Value::String(json))) => {
+                    Some((key.clone(), serde_json::from_str(json).unwrap_or(Value::Null)))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+    
+    fn exists(&self) -> Result<bool, PdwError> {
+        Ok(!self.database.table_columns(META_TABLE)?.is_empty())
+    }
+    
+    fn ensure_table(&self) -> Result<(), PdwError> {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                Namespace TEXT NOT NULL,
+                Chave TEXT NOT NULL,
+                Valor TEXT NOT NULL,
+                Atualizado TEXT,
+                PRIMARY KEY (Namespace, Chave)
+            )",
+            META_TABLE
+        );
+        self.database.connection.execute(&query, [])
+            .map_err(|e| DatabaseError::SqlExecution { query, reason: e.to_string() })?;
+        Ok(())
+    }
 }
 
 /// Trait for database operations
@@ -440,7 +1373,12 @@ pub trait DatabaseOperations {
         Self: Sized;
     
     fn create_tables(&self) -> Result<(), PdwError>;
+    fn drop_table(&self, table_name: &str) -> Result<(), PdwError>;
     fn insert_transactions(&self, transactions: &[ProcessedTransaction]) -> Result<usize, PdwError>;
+    fn insert_reference_data(&self, table_name: &str, data: &[Vec<String>]) -> Result<usize, PdwError>;
+    fn create_pivot_tables(&self, entries_table: &str, types_table: &str,
+                           full_pivot_table: &str, annual_pivot_table: &str,
+                           normalizer: Option<&TypeNormalizer>) -> Result<(), PdwError>;
     fn execute_query(&self, sql: &str) -> Result<Vec<Vec<Value>>, PdwError>;
 }
 
@@ -453,10 +1391,24 @@ impl DatabaseOperations for DatabaseManager {
         self.create_tables()
     }
     
+    fn drop_table(&self, table_name: &str) -> Result<(), PdwError> {
+        self.drop_table(table_name)
+    }
+    
     fn insert_transactions(&self, transactions: &[ProcessedTransaction]) -> Result<usize, PdwError> {
         self.insert_transactions(transactions)
     }
     
+    fn insert_reference_data(&self, table_name: &str, data: &[Vec<String>]) -> Result<usize, PdwError> {
+        self.insert_reference_data(table_name, data)
+    }
+    
+    fn create_pivot_tables(&self, entries_table: &str, types_table: &str,
+                           full_pivot_table: &str, annual_pivot_table: &str,
+                           normalizer: Option<&TypeNormalizer>) -> Result<(), PdwError> {
+        self.create_pivot_tables(entries_table, types_table, full_pivot_table, annual_pivot_table, normalizer)
+    }
+    
     fn execute_query(&self, sql: &str) -> Result<Vec<Vec<Value>>, PdwError> {
         self.execute_query(sql)
     }
@@ -477,6 +1429,56 @@ mod tests {
         assert!(db_path.exists());
     }
     
+    #[test]
+    fn test_open_read_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("previous.db");
+        DatabaseManager::new(&db_path).unwrap().create_tables().unwrap();
+        
+        let db = DatabaseManager::open_read_only(&db_path).unwrap();
+        assert!(!db.table_columns("LANCAMENTOS_GERAIS").unwrap().is_empty());
+        assert!(db.connection().execute("INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO) VALUES ('2024-01-15', 'ALM')", []).is_err());
+        assert!(DatabaseManager::open_read_only(&temp_dir.path().join("missing.db")).is_err());
+    }
+    
+    #[test]
+    fn test_sqlite_tuning_and_in_memory_build() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let pragma = |db: &DatabaseManager, name: &str| db.connection()
+            .query_row(&format!("PRAGMA {}", name), [], |row| row.get::<_, rusqlite::types::Value>(0)).unwrap();
+        
+        // A plain open leaves the journal mode stored in the file alone
+        let db = DatabaseManager::new(&db_path).unwrap();
+        assert_eq!(pragma(&db, "journal_mode"), rusqlite::types::Value::Text("delete".to_string()));
+        db.create_tables().unwrap();
+        drop(db);
+        assert!(!temp_dir.path().join("test.db-wal").exists());
+        
+        // WAL + NORMAL by default for the warehouse
+        let db = DatabaseManager::open_configured(&db_path, &crate::config::PdwConfig::default().settings, &SqliteTuning::default()).unwrap();
+        assert_eq!(pragma(&db, "journal_mode"), rusqlite::types::Value::Text("wal".to_string()));
+        assert_eq!(pragma(&db, "synchronous"), rusqlite::types::Value::Integer(1));
+        drop(db);
+        
+        let tuning: DatabaseConfig = toml::from_str("[sqlite]\njournal_mode = \"delete\"\nsynchronous = \"full\"\ncache_size = -20000\ntemp_store = \"memory\"\nin_memory = true").unwrap();
+        let mut db = DatabaseManager::open_configured(&db_path, &crate::config::PdwConfig::default().settings, &tuning.sqlite).unwrap();
+        assert_eq!(pragma(&db, "journal_mode"), rusqlite::types::Value::Text("delete".to_string()));
+        assert_eq!(pragma(&db, "synchronous"), rusqlite::types::Value::Integer(2));
+        assert_eq!(pragma(&db, "cache_size"), rusqlite::types::Value::Integer(-20000));
+        assert_eq!(pragma(&db, "temp_store"), rusqlite::types::Value::Integer(2));
+        assert!(!db.flush_to_disk().unwrap());
+        
+        // Changes made in memory reach the file only when flushed
+        db.move_to_memory().unwrap();
+        db.connection().execute("INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO) VALUES ('2024-01-15', 'ALM')", []).unwrap();
+        let count = |path: &Path| Connection::open(path).unwrap()
+            .query_row("SELECT COUNT(*) FROM LANCAMENTOS_GERAIS", [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!(count(&db_path), 0);
+        assert!(db.flush_to_disk().unwrap());
+        assert_eq!(count(&db_path), 1);
+    }
+    
     #[test]
     fn test_table_creation() {
         let temp_dir = TempDir::new().unwrap();
@@ -506,13 +1508,15 @@ mod tests {
                 day_of_week: "Segunda-feira".to_string(),
                 transaction_type: "ALM".to_string(),
                 description: "Test transaction".to_string(),
-                credit: 0.0,
-                debit: 100.0,
+                credit: Decimal::ZERO,
+                debit: Decimal::ONE_HUNDRED,
                 month: "01".to_string(),
                 year: "2024".to_string(),
                 month_name: "01-Janeiro".to_string(),
                 year_month: "2024/01".to_string(),
                 origin: "TestSheet".to_string(),
+                derived: Vec::new(),
+                currency: None,
             }
         ];
         
@@ -520,6 +1524,271 @@ mod tests {
         assert_eq!(count, 1);
     }
     
+    #[test]
+    fn test_incremental_insertion_by_row_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        
+        let db = DatabaseManager::new(&db_path).unwrap();
+        db.create_tables().unwrap();
+        
+        let transaction = |day: u32, debit: f64| ProcessedTransaction {
+            date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            day_of_week: String::new(),
+            transaction_type: "ALM".to_string(),
+            description: "Padaria".to_string(),
+            credit: Decimal::ZERO,
+            debit: money::from_f64(debit).unwrap(),
+            month: "01".to_string(),
+            year: "2024".to_string(),
+            month_name: "01-Janeiro".to_string(),
+            year_month: "2024/01".to_string(),
+            origin: "Conta".to_string(),
+            derived: Vec::new(),
+            currency: None,
+        };
+        
+        // Two identical purchases on the same day are distinct rows
+        let first_load = vec![transaction(15, 10.0), transaction(15, 10.0)];
+        let hashes = row_hashes(&first_load);
+        assert_ne!(hashes[0], hashes[1]);
+        assert_eq!(hashes, row_hashes(&first_load));
+        assert_eq!(db.insert_transactions(&first_load).unwrap(), 2);
+        
+        let second_load = vec![transaction(15, 10.0), transaction(15, 10.0), transaction(15, 10.0), transaction(16, 5.0)];
+        let inserted = db.insert_new_transactions(&second_load).unwrap();
+        assert_eq!(inserted.len(), 2);
+        assert_eq!(PeriodSet::from_transactions(inserted).months.len(), 1);
+        
+        assert!(db.insert_new_transactions(&second_load).unwrap().is_empty());
+        assert_eq!(db.count_unhashed_rows("LANCAMENTOS_GERAIS").unwrap(), 0);
+        
+        // Chunks of a streamed load keep numbering the repeats
+        let mut hasher = RowHasher::default();
+        let chunked: Vec<String> = first_load.iter().map(|t| hasher.hash(t)).collect();
+        assert_eq!(chunked, hashes);
+    }
+    
+    #[test]
+    fn test_batched_insertion() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        
+        let mut db = DatabaseManager::new(&db_path).unwrap();
+        db.set_insert_batch_size(3);
+        
+        let header = vec!["Code".to_string(), "Description".to_string()];
+        let data: Vec<Vec<String>> = std::iter::once(header.clone())
+            .chain((0..10).map(|i| vec![format!("code{}", i), format!("desc{}", i)]))
+            .collect();
+        let count = db.insert_reference_data("REF", &data).unwrap();
+        assert_eq!(count, 10);
+        assert!(db.connection().is_autocommit());
+        
+        // A failing row rolls back only its own batch
+        let bad: Vec<Vec<String>> = vec![
+            header,
+            vec!["a".to_string(), "b".to_string()],
+            vec!["c".to_string(), "d".to_string(), "e".to_string()],
+        ];
+        assert!(db.insert_reference_data("REF", &bad).is_err());
+        assert!(db.connection().is_autocommit());
+        let result = db.execute_query("SELECT COUNT(*) FROM REF").unwrap();
+        assert_eq!(result[0][0], serde_json::json!(10));
+    }
+    
+    #[test]
+    fn test_derived_column_insertion() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        
+        let db = DatabaseManager::new(&db_path).unwrap();
+        db.create_tables().unwrap();
+        db.add_derived_columns("LANCAMENTOS_GERAIS", &[("Liquido".to_string(), "REAL")]).unwrap();
+        // Adding the same column twice is a no-op
+        db.add_derived_columns("LANCAMENTOS_GERAIS", &[("Liquido".to_string(), "REAL")]).unwrap();
+        
+        let transactions = vec![
+            ProcessedTransaction {
+                date: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                day_of_week: "Segunda-feira".to_string(),
+                transaction_type: "ALM".to_string(),
+                description: "Test transaction".to_string(),
+                credit: Decimal::new(150, 0),
+                debit: Decimal::ONE_HUNDRED,
+                month: "01".to_string(),
+                year: "2024".to_string(),
+                month_name: "01-Janeiro".to_string(),
+                year_month: "2024/01".to_string(),
+                origin: "TestSheet".to_string(),
+                derived: vec![("Liquido".to_string(), DerivedValue::Number(50.0))],
+                currency: None,
+            }
+        ];
+        
+        db.insert_transactions(&transactions).unwrap();
+        let result = db.execute_query("SELECT Liquido FROM LANCAMENTOS_GERAIS").unwrap();
+        assert_eq!(result[0][0], serde_json::json!(50.0));
+    }
+    
+    #[test]
+    fn test_collated_type_ordering() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        
+        let mut db = DatabaseManager::new(&db_path).unwrap();
+        assert_eq!(db.order_by("TIPO"), "TIPO");
+        
+        db.set_collation(TextCollation::PtBr).unwrap();
+        let types = vec![
+            vec!["Código".to_string(), "Descrição".to_string()],
+            vec!["SAU".to_string(), "Saúde".to_string()],
+            vec!["EDU".to_string(), "Educação".to_string()],
+            vec!["ALM".to_string(), "Água".to_string()],
+            vec!["BAN".to_string(), "Banco".to_string()],
+        ];
+        db.insert_reference_data("TiposLancamentos", &types).unwrap();
+        
+        let query = format!("SELECT Descrição FROM TiposLancamentos ORDER BY {}", db.order_by("Descrição"));
+        let ordered: Vec<Value> = db.execute_query(&query).unwrap().into_iter()
+            .map(|mut row| row.remove(0))
+            .collect();
+        assert_eq!(ordered, vec!["Água", "Banco", "Educação", "Saúde"]);
+    }
+    
+    #[test]
+    fn test_pivot_with_special_type_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        
+        let db = DatabaseManager::new(&db_path).unwrap();
+        db.create_tables().unwrap();
+        let names = ["Conta de Luz", "Pão d'Água", "Saúde [SUS]", "Apelido \"X\"", "Educação"];
+        for name in names {
+            db.connection().execute("INSERT INTO TiposLancamentos VALUES (?1, ?1)", [name]).unwrap();
+            db.connection().execute(
+                "INSERT INTO LANCAMENTOS_GERAIS (TIPO, Debito, Ano, AnoMes) VALUES (?1, 2, '2024', '2024/01')",
+                [name],
+            ).unwrap();
+        }
+        // Same column as "Conta de Luz" for SQLite, skipped instead of failing the CREATE
+        db.connection().execute("INSERT INTO TiposLancamentos VALUES ('CONTA DE LUZ', 'CONTA DE LUZ')", []).unwrap();
+        
+        db.create_pivot_tables("LANCAMENTOS_GERAIS", "TiposLancamentos", "HistoricoGeral", "HistoricoAnual", None)
+            .unwrap();
+        
+        let mut expected = vec!["AnoMes".to_string()];
+        expected.extend(names.iter().map(|n| n.to_string()));
+        assert_eq!(db.table_columns("HistoricoGeral").unwrap(), expected);
+        
+        let row = db.execute_query("SELECT * FROM HistoricoAnual").unwrap().remove(0);
+        assert_eq!(row[0], serde_json::json!("2024"));
+        assert!(row[1..].iter().all(|value| *value == serde_json::json!(2.0)));
+        
+        let periods = PeriodSet::from_months(["2024/01".to_string()]);
+        assert!(db.refresh_pivot_periods("LANCAMENTOS_GERAIS", "TiposLancamentos", "HistoricoGeral",
+                                         "HistoricoAnual", None, &periods).unwrap());
+        let quoted = format!("SELECT {} FROM HistoricoGeral", quote_identifier("Apelido \"X\""));
+        assert_eq!(db.execute_query(&quoted).unwrap()[0][0], serde_json::json!(2.0));
+    }
+    
+    #[test]
+    fn test_refresh_pivot_periods() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        
+        let db = DatabaseManager::new(&db_path).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO TiposLancamentos VALUES ('ALM', 'ALM'), ('LAZ', 'LAZ');
+             INSERT INTO LANCAMENTOS_GERAIS (TIPO, Debito, Ano, AnoMes) VALUES ('ALM', 10, '2024', '2024/01');
+             INSERT INTO LANCAMENTOS_GERAIS (TIPO, Debito, Ano, AnoMes) VALUES ('LAZ', 5, '2024', '2024/02');"
+        ).unwrap();
+        db.create_pivot_tables("LANCAMENTOS_GERAIS", "TiposLancamentos", "HistoricoGeral", "HistoricoAnual", None)
+            .unwrap();
+        
+        // Change both months, but only refresh February
+        db.connection().execute_batch(
+            "UPDATE LANCAMENTOS_GERAIS SET Debito = Debito * 2;
+             INSERT INTO LANCAMENTOS_GERAIS (TIPO, Debito, Ano, AnoMes) VALUES ('ALM', 1, '2024', '2024/03');"
+        ).unwrap();
+        let periods = PeriodSet::from_months(["2024/02".to_string(), "2024/03".to_string()]);
+        assert_eq!(periods.years.len(), 1);
+        assert!(db.refresh_pivot_periods("LANCAMENTOS_GERAIS", "TiposLancamentos", "HistoricoGeral",
+                                         "HistoricoAnual", None, &periods).unwrap());
+        
+        let monthly = db.execute_query("SELECT AnoMes, ALM, LAZ FROM HistoricoGeral ORDER BY AnoMes").unwrap();
+        assert_eq!(monthly.len(), 3);
+        assert_eq!(monthly[0][1], serde_json::json!(10.0));
+        assert_eq!(monthly[1][2], serde_json::json!(10.0));
+        assert_eq!(monthly[2][1], serde_json::json!(1.0));
+        
+        let annual = db.execute_query("SELECT ALM FROM HistoricoAnual").unwrap();
+        assert_eq!(annual[0][0], serde_json::json!(21.0));
+        
+        // A new type changes the pivot schema
+        db.connection().execute("INSERT INTO TiposLancamentos VALUES ('SAU', 'SAU')", []).unwrap();
+        assert!(!db.refresh_pivot_periods("LANCAMENTOS_GERAIS", "TiposLancamentos", "HistoricoGeral",
+                                          "HistoricoAnual", None, &periods).unwrap());
+    }
+    
+    #[test]
+    fn test_integrity_check() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        
+        let db = DatabaseManager::new(&db_path).unwrap();
+        db.create_tables().unwrap();
+        drop(db);
+        
+        let db = DatabaseManager::open_checked(&db_path, IntegrityCheck::Full).unwrap();
+        assert!(db.check_integrity(IntegrityCheck::Quick).is_ok());
+        assert!(db.check_integrity(IntegrityCheck::Off).is_ok());
+    }
+    
+    #[test]
+    fn test_integrity_check_detects_garbage_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("garbage.db");
+        std::fs::write(&db_path, vec![0x5Au8; 8192]).unwrap();
+        
+        let result = DatabaseManager::open_checked(&db_path, IntegrityCheck::Quick);
+        assert!(matches!(
+            result,
+            Err(PdwError::Database(DatabaseError::Corrupted { .. })) | Err(PdwError::Database(DatabaseError::ConnectionFailed { .. }))
+        ));
+    }
+    
+    #[test]
+    fn test_busy_timeout_and_lock_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("locked.db");
+        let mut db = DatabaseManager::new(&db_path).unwrap();
+        db.create_tables().unwrap();
+        db.set_busy_timeout(50).unwrap();
+        
+        // Another connection in the middle of a write, like DB Browser with unsaved changes
+        let other = Connection::open(&db_path).unwrap();
+        other.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let error = db.explain_lock(db.drop_table("LANCAMENTOS_GERAIS").unwrap_err());
+        assert!(matches!(error, PdwError::Database(DatabaseError::Locked { timeout_ms: 50, .. })), "{}", error);
+        // The lock is held by this very process, which is never listed
+        assert!(error.to_string().contains("in use by another process"), "{}", error);
+        assert!(error.to_string().contains("settings.busy_timeout_ms"));
+        
+        let unrelated = db.explain_lock(PdwError::sql_execution_error("SELECT x", "no such column: x"));
+        assert!(matches!(unrelated, PdwError::Database(DatabaseError::SqlExecution { .. })));
+        
+        // A lock released within the timeout is waited for
+        db.set_busy_timeout(5000).unwrap();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            other.execute_batch("ROLLBACK").unwrap();
+        });
+        db.drop_table("LANCAMENTOS_GERAIS").unwrap();
+        release.join().unwrap();
+    }
+    
     #[test]
     fn test_query_execution() {
         let temp_dir = TempDir::new().unwrap();
@@ -531,4 +1800,34 @@ mod tests {
         let result = db.execute_query("SELECT COUNT(*) FROM LANCAMENTOS_GERAIS").unwrap();
         assert_eq!(result.len(), 1);
     }
+    
+    #[test]
+    fn test_meta_store() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        let meta = db.meta();
+        
+        // Reads leave the database untouched
+        assert_eq!(meta.get::<i64>(MetaStore::SCHEMA, "version").unwrap(), None);
+        assert!(db.table_columns(META_TABLE).unwrap().is_empty());
+        
+        meta.set(MetaStore::SCHEMA, "version", &1).unwrap();
+        meta.set(MetaStore::SCHEMA, "version", &2).unwrap();
+        meta.set(MetaStore::CLOSED_MONTHS, "2024/01", &true).unwrap();
+        meta.set(MetaStore::FILE_HASHES, "PDW.xlsx", "9f2c").unwrap();
+        let months = vec!["2024/01".to_string(), "2024/02".to_string()];
+        meta.set(MetaStore::LAST_LOAD, "months", &months).unwrap();
+        
+        assert_eq!(meta.get::<i64>(MetaStore::SCHEMA, "version").unwrap(), Some(2));
+        assert_eq!(meta.get::<String>(MetaStore::FILE_HASHES, "PDW.xlsx").unwrap().as_deref(), Some("9f2c"));
+        assert_eq!(meta.get::<Vec<String>>(MetaStore::LAST_LOAD, "months").unwrap(), Some(months));
+        // Same key in another namespace
+        assert_eq!(meta.get::<bool>(MetaStore::SCHEMA, "2024/01").unwrap(), None);
+        assert!(meta.get::<bool>(MetaStore::FILE_HASHES, "PDW.xlsx").is_err());
+        
+        assert_eq!(meta.entries(MetaStore::CLOSED_MONTHS).unwrap().len(), 1);
+        assert!(meta.remove(MetaStore::CLOSED_MONTHS, "2024/01").unwrap());
+        assert!(!meta.remove(MetaStore::CLOSED_MONTHS, "2024/01").unwrap());
+        assert!(meta.entries(MetaStore::CLOSED_MONTHS).unwrap().is_empty());
+    }
 }
\ No newline at end of file
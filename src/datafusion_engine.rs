@@ -0,0 +1,202 @@
+/*!
+# DataFusion Reporting Module
+
+Runs the YAML report queries through an embedded DataFusion engine over
+in-memory Arrow batches and Parquet files, turning PDW into a pure
+file-to-report transformer that never writes a database file. Enabled with the
+`datafusion` cargo feature and `settings.report_engine = "datafusion"`.
+
+Queries are executed with DataFusion's SQL dialect: SQLite-only functions such
+as `strftime` are not available, and the pivot/summary tables of the SQLite
+warehouse do not exist, so reports depending on them are skipped with a warning.
+*/
+
+use crate::arrow_export;
+use crate::config::PdwConfig;
+use crate::database::ProcessedTransaction;
+use crate::error::{PdwError, ReportError};
+use crate::reporting;
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use datafusion::error::DataFusionError;
+use datafusion::prelude::{ParquetReadOptions, SessionContext};
+use std::path::Path;
+
+/// Report generator backed by DataFusion instead of SQLite
+pub struct DataFusionReporter {
+    config: PdwConfig,
+    context: SessionContext,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl DataFusionReporter {
+    /// Create a reporter with an empty session
+    pub fn new(config: PdwConfig) -> Result<Self, PdwError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        
+        Ok(Self { config, context: SessionContext::new(), runtime })
+    }
+    
+    /// Register processed transactions as the general entries table
+    pub fn register_transactions(&self, transactions: &[ProcessedTransaction]) -> Result<(), PdwError> {
+        let batch = arrow_export::transactions_to_batch(transactions)?;
+        self.register_batch(&self.config.settings.general_entries_table, batch)
+    }
+    
+    /// Register an in-memory batch as a table
+    pub fn register_batch(&self, table: &str, batch: RecordBatch) -> Result<(), PdwError> {
+        self.context.register_batch(table, batch).map_err(engine_error)?;
+        Ok(())
+    }
+    
+    /// Register every *.parquet file of a directory as a table named after the file
+    pub fn register_parquet_dir(&self, dir: &Path) -> Result<usize, PdwError> {
+        let mut count = 0;
+        
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("parquet")) {
+                continue;
+            }
+            
+            let table = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let location = path.to_string_lossy().to_string();
+            self.runtime
+                .block_on(self.context.register_parquet(&table, &location, ParquetReadOptions::default()))
+                .map_err(engine_error)?;
+            tracing::info!("Registered Parquet table {} from {}", table, path.display());
+            count += 1;
+        }
+        
+        Ok(count)
+    }
+    
+    /// Run a query and collect its result batches
+    pub fn query(&self, sql: &str) -> Result<Vec<RecordBatch>, PdwError> {
+        self.runtime
+            .block_on(async { self.context.sql(sql).await?.collect().await })
+            .map_err(|e| ReportError::QueryProcessing {
+                query_name: sql.to_string(),
+                reason: e.to_string(),
+            }.into())
+    }
+    
+    /// Run the standard YAML queries and write the report workbook; returns the sheet count
+    pub fn generate_excel_reports(&self) -> Result<usize, PdwError> {
+        let query_config = reporting::load_query_file(&self.config.get_yaml_queries_path())?
+            .with_profiles(&self.config.export_profiles)?;
+        let variables = reporting::query_variables(&self.config.settings, String::new());
+        let output_path = self.config.get_report_path();
+        
+        let mut workbook = crate::deterministic::workbook();
+        let mut sheets = 0;
+        
+        // queries_gera_hist read the SQLite pivot tables, which are not built here
+        for query_def in &query_config.queries_padrao {
+            let query_def = query_def.expand(&variables);
+            if query_def.output.is_some() || query_def.writes {
+                tracing::warn!("Report {} skipped under DataFusion: output targets and scripts need the SQLite engine", query_def.sheet_name);
+                continue;
+            }
+            
+            match self.query(&query_def.sql) {
+                Ok(batches) => {
+                    if self.add_batches_to_workbook(&mut workbook, &batches, &query_def.sheet_name)? {
+                        sheets += 1;
+                    }
+                }
+                Err(e) => tracing::warn!("Report {} skipped under DataFusion: {}", query_def.sheet_name, e),
+            }
+        }
+        
+        self.config.branding.apply_to_workbook(&mut workbook);
+        workbook.save(&output_path)
+            .map_err(ReportError::ExcelWriter)?;
+        
+        tracing::info!("Excel reports generated with DataFusion: {}", output_path.display());
+        Ok(sheets)
+    }
+    
+    /// Write result batches to a new worksheet; returns false when there are no rows
+    fn add_batches_to_workbook(
+        &self,
+        workbook: &mut rust_xlsxwriter::Workbook,
+        batches: &[RecordBatch],
+        sheet_name: &str,
+    ) -> Result<bool, PdwError> {
+        if batches.iter().all(|batch| batch.num_rows() == 0) {
+            return Ok(false);
+        }
+        
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(sheet_name)
+            .map_err(ReportError::ExcelWriter)?;
+        
+        let options = FormatOptions::default();
+        let mut row_idx = 0u32;
+        
+        for batch in batches {
+            let formatters = batch.columns().iter()
+                .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| engine_error(e.into()))?;
+            
+            for row in 0..batch.num_rows() {
+                for (col_idx, formatter) in formatters.iter().enumerate() {
+                    worksheet.write_string(row_idx, col_idx as u16, formatter.value(row).to_string())
+                        .map_err(ReportError::ExcelWriter)?;
+                }
+                row_idx += 1;
+            }
+        }
+        
+        Ok(true)
+    }
+}
+
+fn engine_error(e: DataFusionError) -> PdwError {
+    ReportError::OutputGeneration {
+        format: "datafusion".to_string(),
+        reason: e.to_string(),
+    }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    
+    fn transaction(tipo: &str, debit: f64) -> ProcessedTransaction {
+        ProcessedTransaction {
+            date: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            day_of_week: "Segunda-feira".to_string(),
+            transaction_type: tipo.to_string(),
+            description: "Test".to_string(),
+            credit: crate::money::Decimal::ZERO,
+            debit: crate::money::from_f64(debit).unwrap(),
+            month: "01".to_string(),
+            year: "2024".to_string(),
+            month_name: "01-Janeiro".to_string(),
+            year_month: "2024/01".to_string(),
+            origin: "Conta".to_string(),
+            derived: Vec::new(),
+            currency: None,
+        }
+    }
+    
+    #[test]
+    fn test_query_registered_transactions() {
+        let reporter = DataFusionReporter::new(PdwConfig::default()).unwrap();
+        reporter.register_transactions(&[transaction("ALM", 10.0), transaction("ALM", 5.0)]).unwrap();
+        
+        let batches = reporter
+            .query("SELECT \"TIPO\", SUM(\"Debito\") AS total FROM \"LANCAMENTOS_GERAIS\" GROUP BY \"TIPO\"")
+            .unwrap();
+        let total: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(total, 1);
+        
+        assert!(reporter.query("SELECT * FROM missing_table").is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,169 @@
+/*!
+# Deterministic Output Module
+
+Reproducible outputs (`pdw --deterministic`): two runs over identical inputs
+write byte-identical reports, exports and warehouse metadata, so the outputs can
+be kept under Git and their diffs trusted.
+
+While enabled, for the whole process:
+
+- timestamps written to outputs (load metadata, run history, checkpoints, OFX
+  headers, alerts) come from a fixed clock: `SOURCE_DATE_EPOCH` when set, as in
+  other reproducible builds, else 2000-01-01 00:00:00; durations are zero
+- workbooks get that creation date instead of the current time
+- results of queries without an ORDER BY are sorted by their contents, and the
+  general entries export breaks ties on the date by the other columns
+
+Dates used to judge the data itself (future-dated entries, default report
+periods) still follow the calendar.
+*/
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use rust_xlsxwriter::{DocProperties, ExcelDateTime, Workbook};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::time::{Duration, Instant};
+
+/// Environment variable of the fixed clock (seconds since the Unix epoch)
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Fixed clock when SOURCE_DATE_EPOCH is not set: 2000-01-01 00:00:00 UTC
+pub const DEFAULT_EPOCH: i64 = 946_684_800;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn deterministic output on for the rest of the process
+pub fn enable() {
+    ENABLED.store(true, AtomicOrdering::Relaxed);
+}
+
+/// Whether deterministic output is on
+pub fn is_enabled() -> bool {
+    ENABLED.load(AtomicOrdering::Relaxed)
+}
+
+/// Time of the fixed clock
+pub fn fixed_time() -> DateTime<Utc> {
+    epoch_from(std::env::var(SOURCE_DATE_EPOCH).ok().as_deref())
+}
+
+fn epoch_from(value: Option<&str>) -> DateTime<Utc> {
+    let seconds = match value.map(str::trim) {
+        Some(text) => text.parse().unwrap_or_else(|_| {
+            tracing::warn!("Ignoring invalid {} '{}'", SOURCE_DATE_EPOCH, text);
+            DEFAULT_EPOCH
+        }),
+        None => DEFAULT_EPOCH,
+    };
+    Utc.timestamp_opt(seconds, 0).single()
+        .unwrap_or_else(|| Utc.timestamp_opt(DEFAULT_EPOCH, 0).unwrap())
+}
+
+/// Current time, or the fixed clock (same wall time in every time zone) when enabled
+pub fn now() -> DateTime<Local> {
+    if !is_enabled() {
+        return Local::now();
+    }
+    Local.from_local_datetime(&fixed_time().naive_utc()).earliest().unwrap_or_else(Local::now)
+}
+
+/// Time since `started`, zero when enabled
+pub fn elapsed(started: Instant) -> Duration {
+    if is_enabled() { Duration::ZERO } else { started.elapsed() }
+}
+
+/// New workbook, created at the fixed clock when enabled
+pub fn workbook() -> Workbook {
+    let mut workbook = Workbook::new();
+    if is_enabled() {
+        if let Ok(created) = ExcelDateTime::from_timestamp(fixed_time().timestamp()) {
+            workbook.set_properties(&DocProperties::new().set_creation_datetime(&created));
+        }
+    }
+    workbook
+}
+
+/// Sort the rows of a query without an ORDER BY by their contents, when enabled
+pub fn sort_unordered(sql: &str, rows: &mut [Vec<Value>]) {
+    if is_enabled() && !has_order_by(sql) {
+        sort_rows(rows);
+    }
+}
+
+/// ", a, b" ORDER BY tie-breakers over `columns` when enabled, empty otherwise
+pub fn tie_break(columns: &[&str]) -> String {
+    if !is_enabled() {
+        return String::new();
+    }
+    columns.iter().map(|column| format!(", {}", column)).collect()
+}
+
+fn has_order_by(sql: &str) -> bool {
+    sql.split_whitespace()
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .windows(2)
+        .any(|words| words[0] == "order" && words[1] == "by")
+}
+
+/// Rows sorted column by column: nulls, then booleans, numbers and text
+pub fn sort_rows(rows: &mut [Vec<Value>]) {
+    rows.sort_by(|a, b| {
+        a.iter().zip(b.iter())
+            .map(|(left, right)| compare_values(left, right))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    });
+}
+
+fn compare_values(left: &Value, right: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            _ => 4,
+        }
+    }
+    
+    match (left, right) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => {
+            a.as_f64().unwrap_or_default().total_cmp(&b.as_f64().unwrap_or_default())
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => rank(left).cmp(&rank(right)).then_with(|| left.to_string().cmp(&right.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_fixed_clock_and_row_order() {
+        assert_eq!(epoch_from(None).to_rfc3339(), "2000-01-01T00:00:00+00:00");
+        assert_eq!(epoch_from(Some(" 1704067200 ")).to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(epoch_from(Some("yesterday")).timestamp(), DEFAULT_EPOCH);
+        
+        assert!(has_order_by("SELECT * FROM t\n ORDER\tBY Data"));
+        assert!(!has_order_by("SELECT border, bytes FROM t"));
+        
+        let mut rows = vec![
+            vec![json!("2024/02"), json!(10)],
+            vec![json!("2024/01"), json!(5.5)],
+            vec![json!("2024/01"), json!(null)],
+            vec![json!("2024/01"), json!(-3)],
+        ];
+        sort_rows(&mut rows);
+        assert_eq!(rows, vec![
+            vec![json!("2024/01"), json!(null)],
+            vec![json!("2024/01"), json!(-3)],
+            vec![json!("2024/01"), json!(5.5)],
+            vec![json!("2024/02"), json!(10)],
+        ]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,279 @@
+/*!
+# Digest Module
+
+One-page summary of a recent period read from the existing database
+(`pdw digest --period week`), for a schedule separate from the monthly
+pipeline: nothing is loaded or rebuilt. The digest covers the week (or month to
+date) ending on a day, compares its debits with the period before, and lists
+the TIPOs that cost most and the largest debits. It is rendered as text or HTML
+(under the `[branding]` title and footer) and sent through the `[notifications]`
+channels.
+*/
+
+use crate::analytics::exclusion_condition;
+use crate::branding::BrandingConfig;
+use crate::database::DatabaseManager;
+use crate::error::PdwError;
+use crate::pdf::format_amount;
+use crate::reporting::xml_escape;
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// TIPOs and debits listed in a digest
+const TOP_LINES: usize = 5;
+
+/// Span covered by a digest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    /// The seven days ending on the day, against the seven before
+    #[default]
+    Week,
+    /// The month up to the day, against the same days of the previous month
+    Month,
+}
+
+/// Body layout of a digest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFormat {
+    #[default]
+    Text,
+    Html,
+}
+
+/// Totals of a period and its largest items
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digest {
+    pub period: DigestPeriod,
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+    pub entries: usize,
+    pub credit: f64,
+    pub debit: f64,
+    /// Debits of the previous period of the same length
+    pub previous_debit: f64,
+    /// TIPOs with the most debits
+    pub top_types: Vec<(String, f64)>,
+    /// Largest debits: date, description, amount
+    pub largest: Vec<(String, String, f64)>,
+    /// Seasonal expectation of the month's debits (`[baselines]`), for monthly digests
+    pub seasonal: Option<f64>,
+}
+
+impl DigestPeriod {
+    /// First and last day of the period ending on `until`, then of the period before
+    pub fn ranges(self, until: NaiveDate) -> ((NaiveDate, NaiveDate), (NaiveDate, NaiveDate)) {
+        match self {
+            DigestPeriod::Week => {
+                let first = until - Duration::days(6);
+                ((first, until), (first - Duration::days(7), first - Duration::days(1)))
+            }
+            DigestPeriod::Month => {
+                let first = until.with_day(1).unwrap_or(until);
+                let previous_first = first.checked_sub_months(Months::new(1)).unwrap_or(first);
+                let previous_last = until.checked_sub_months(Months::new(1)).unwrap_or(previous_first);
+                ((first, until), (previous_first, previous_last))
+            }
+        }
+    }
+    
+    fn label(self) -> &'static str {
+        match self {
+            DigestPeriod::Week => "Resumo semanal",
+            DigestPeriod::Month => "Resumo do mês",
+        }
+    }
+}
+
+impl Digest {
+    /// Subject line of the message
+    pub fn subject(&self) -> String {
+        format!("PDW: {} {} a {}", self.period.label(), self.first.format("%d/%m/%Y"), self.last.format("%d/%m/%Y"))
+    }
+    
+    /// Add the seasonal expectation of the month a monthly digest covers, from the baselines table
+    pub fn with_seasonal(mut self, database: &DatabaseManager, baselines: &str) -> Result<Self, PdwError> {
+        if self.period == DigestPeriod::Month {
+            self.seasonal = crate::baselines::expected(database, baselines, &self.first.format("%Y/%m").to_string(), None)?;
+        }
+        Ok(self)
+    }
+    
+    /// Message body in the given layout
+    pub fn render(&self, format: DigestFormat, branding: &BrandingConfig) -> String {
+        match format {
+            DigestFormat::Text => self.render_text(),
+            DigestFormat::Html => self.render_html(branding),
+        }
+    }
+    
+    fn change(&self) -> String {
+        if self.previous_debit == 0.0 {
+            return "sem débitos no período anterior".to_string();
+        }
+        let change = (self.debit - self.previous_debit) / self.previous_debit * 100.0;
+        format!("{:+.1}% sobre o período anterior ({})", change, format_amount(self.previous_debit))
+    }
+    
+    fn seasonal_change(&self) -> Option<String> {
+        let expected = self.seasonal.filter(|expected| *expected > 0.0)?;
+        Some(format!("{:.1}% do esperado para o mês ({})", self.debit * 100.0 / expected, format_amount(expected)))
+    }
+    
+    fn render_text(&self) -> String {
+        let mut lines = vec![
+            self.subject(),
+            String::new(),
+            format!("Lançamentos: {}", self.entries),
+            format!("Créditos:    {}", format_amount(self.credit)),
+            format!("Débitos:     {} ({})", format_amount(self.debit), self.change()),
+            format!("Saldo:       {}", format_amount(self.credit - self.debit)),
+        ];
+        if let Some(seasonal) = self.seasonal_change() {
+            lines.insert(5, format!("             {}", seasonal));
+        }
+        if !self.top_types.is_empty() {
+            lines.push(String::new());
+            lines.push("Maiores gastos por TIPO:".to_string());
+            lines.extend(self.top_types.iter().map(|(tipo, amount)| format!("  {:<20} {:>12}", tipo, format_amount(*amount))));
+        }
+        if !self.largest.is_empty() {
+            lines.push(String::new());
+            lines.push("Maiores débitos:".to_string());
+            lines.extend(self.largest.iter()
+                .map(|(date, description, amount)| format!("  {}  {:<30} {:>12}", date, description, format_amount(*amount))));
+        }
+        lines.join("\n")
+    }
+    
+    fn render_html(&self, branding: &BrandingConfig) -> String {
+        let rows = |items: Vec<(String, String)>| -> String {
+            items.into_iter()
+                .map(|(label, amount)| format!("<tr><td>{}</td><td align=\"right\">{}</td></tr>", xml_escape(&label), amount))
+                .collect()
+        };
+        
+        let mut html = "<html><body style=\"font-family: sans-serif\">\n".to_string();
+        if let Some(title) = branding.title() {
+            html.push_str(&format!("<h1>{}</h1>\n", xml_escape(title)));
+        }
+        html.push_str(&format!(
+            "<h2>{}</h2>\n<table>{}</table>\n<p>Débitos: {}</p>\n",
+            xml_escape(&self.subject()),
+            rows(vec![
+                ("Lançamentos".to_string(), self.entries.to_string()),
+                ("Créditos".to_string(), format_amount(self.credit)),
+                ("Débitos".to_string(), format_amount(self.debit)),
+                ("Saldo".to_string(), format_amount(self.credit - self.debit)),
+            ]),
+            xml_escape(&self.change()),
+        ));
+        if let Some(seasonal) = self.seasonal_change() {
+            html.push_str(&format!("<p>Sazonal: {}</p>\n", xml_escape(&seasonal)));
+        }
+        if !self.top_types.is_empty() {
+            html.push_str(&format!("<h3>Maiores gastos por TIPO</h3>\n<table>{}</table>\n",
+                rows(self.top_types.iter().map(|(tipo, amount)| (tipo.clone(), format_amount(*amount))).collect())));
+        }
+        if !self.largest.is_empty() {
+            html.push_str(&format!("<h3>Maiores débitos</h3>\n<table>{}</table>\n",
+                rows(self.largest.iter().map(|(date, description, amount)| (format!("{} {}", date, description), format_amount(*amount))).collect())));
+        }
+        if let Some(footer) = branding.footer_text() {
+            html.push_str(&format!("<p style=\"font-size: small; color: gray\">{}</p>\n", xml_escape(&footer)));
+        }
+        html.push_str("</body></html>");
+        html
+    }
+}
+
+/// Digest of the period ending on `until`, from the general entries except the `excluded` TIPOs
+pub fn build_digest(database: &DatabaseManager, entries_table: &str, period: DigestPeriod, until: NaiveDate,
+                    excluded: &[String]) -> Result<Digest, PdwError> {
+    let ((first, last), (previous_first, previous_last)) = period.ranges(until);
+    let day = |date: NaiveDate| date.format("%Y-%m-%d").to_string();
+    let kept = exclusion_condition("TIPO", excluded);
+    let between = |first: NaiveDate, last: NaiveDate| format!("date(Data) BETWEEN '{}' AND '{}' AND {}", day(first), day(last), kept);
+    let current = between(first, last);
+    
+    let totals = database.execute_query(&format!(
+        "SELECT COUNT(*), COALESCE(SUM(Credito), 0), COALESCE(SUM(Debito), 0) FROM {} WHERE {}",
+        entries_table, current
+    ))?;
+    let total = |index: usize| totals.first().and_then(|row| row.get(index)).and_then(Value::as_f64).unwrap_or(0.0);
+    
+    let previous_debit = database.execute_query(&format!(
+        "SELECT COALESCE(SUM(Debito), 0) FROM {} WHERE {}",
+        entries_table, between(previous_first, previous_last)
+    ))?.first().and_then(|row| row.first()).and_then(Value::as_f64).unwrap_or(0.0);
+    
+    let text = |row: &[Value], index: usize| row.get(index).and_then(Value::as_str).unwrap_or_default().to_string();
+    let amount = |row: &[Value], index: usize| row.get(index).and_then(Value::as_f64).unwrap_or(0.0);
+    let top_types = database.execute_query(&format!(
+        "SELECT TRIM(TIPO), SUM(Debito) AS Total FROM {} WHERE {} AND Debito > 0
+         GROUP BY TRIM(TIPO) ORDER BY Total DESC, 1 LIMIT {}",
+        entries_table, current, TOP_LINES
+    ))?.iter().map(|row| (text(row, 0), amount(row, 1))).collect();
+    let largest = database.execute_query(&format!(
+        "SELECT substr(Data, 1, 10), COALESCE(DESCRICAO, ''), Debito FROM {} WHERE {} AND Debito > 0
+         ORDER BY Debito DESC, Data, DESCRICAO LIMIT {}",
+        entries_table, current, TOP_LINES
+    ))?.iter().map(|row| (text(row, 0), text(row, 1), amount(row, 2))).collect();
+    
+    Ok(Digest {
+        period,
+        first,
+        last,
+        entries: total(0) as usize,
+        credit: total(1),
+        debit: total(2),
+        previous_debit,
+        top_types,
+        largest,
+        seasonal: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_weekly_digest() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito) VALUES ('2024-03-01', 'MERCADO', 'Feira', 0, 100);
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito) VALUES ('2024-03-09', 'MERCADO', 'Supermercado', 0, 250);
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito) VALUES ('2024-03-12', 'LAZER ', 'Cinema <3D>', 0, 60);
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito) VALUES ('2024-03-14', 'SALARIO', 'Empresa', 5000, 0);
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito) VALUES ('2024-03-15', 'MERCADO', 'Padaria', 0, 40);"
+        ).unwrap();
+        
+        let until = NaiveDate::from_ymd_opt(2024, 3, 14).unwrap();
+        let digest = build_digest(&db, "LANCAMENTOS_GERAIS", DigestPeriod::Week, until, &[]).unwrap();
+        assert_eq!((digest.first.to_string(), digest.entries), ("2024-03-08".to_string(), 3));
+        assert_eq!((digest.credit, digest.debit, digest.previous_debit), (5000.0, 310.0, 100.0));
+        assert_eq!(digest.top_types, vec![("MERCADO".to_string(), 250.0), ("LAZER".to_string(), 60.0)]);
+        assert_eq!(digest.largest[1], ("2024-03-12".to_string(), "Cinema <3D>".to_string(), 60.0));
+        
+        let text = digest.render(DigestFormat::Text, &BrandingConfig::default());
+        assert!(text.starts_with("PDW: Resumo semanal 08/03/2024 a 14/03/2024"));
+        assert!(text.contains("Débitos:     310,00 (+210.0% sobre o período anterior (100,00))"));
+        let html = digest.render(DigestFormat::Html, &BrandingConfig::default());
+        assert!(html.contains("2024-03-12 Cinema &lt;3D&gt;"));
+        assert!(!html.contains("<h1>"));
+        let branding = BrandingConfig { enabled: true, title: Some("Casa & Cia".to_string()), ..BrandingConfig::default() };
+        let html = digest.render(DigestFormat::Html, &branding);
+        assert!(html.contains("<h1>Casa &amp; Cia</h1>\n<h2>PDW: Resumo semanal"));
+        assert!(html.contains(&format!("Gerado por PDW {} em ", env!("CARGO_PKG_VERSION"))));
+        let seasonal = Digest { seasonal: Some(200.0), ..digest }.render(DigestFormat::Text, &BrandingConfig::default());
+        assert!(seasonal.contains("310,00 (+210.0% sobre o período anterior (100,00))\n             155.0% do esperado para o mês (200,00)"));
+        
+        let ((first, _), (previous_first, previous_last)) = DigestPeriod::Month.ranges(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap());
+        assert_eq!((first.day(), previous_first.month(), previous_last.day()), (1, 2, 29));
+    }
+}
\ No newline at end of file
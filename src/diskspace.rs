@@ -0,0 +1,217 @@
+/*!
+# Disk Space Module
+
+Preflight check run before the load and report phases: the space each phase may
+need is estimated from the size of its inputs and compared with the free space
+of the volumes it writes to, so a full disk stops the run up front with the
+required and available sizes instead of an SQLITE_FULL error halfway through a
+load.
+
+The estimates are deliberately generous. XLSX workbooks are zip-compressed and
+expand several times once stored as rows; CSV and OFX inputs roughly double with
+the indexes and derived tables. A load may also rewrite the whole existing
+database (replace loads, the rollback journal), and reports add summary tables
+to the database and workbooks and exports to the output directory.
+*/
+
+use crate::config::PdwConfig;
+use crate::error::{EtlError, PdwError};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Expansion of a compressed (XLSX or ODS) workbook once loaded
+const XLSX_EXPANSION: u64 = 10;
+
+/// Expansion of plain-text inputs (CSV sheets, OFX downloads) once loaded
+const TEXT_EXPANSION: u64 = 2;
+
+const MB: u64 = 1024 * 1024;
+
+/// Space a phase needs on the volume holding `path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceRequirement {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+impl SpaceRequirement {
+    pub fn new(path: &Path, bytes: u64) -> Self {
+        Self { path: path.to_path_buf(), bytes }
+    }
+}
+
+/// Space needed to load the inputs into `database`, whose current size counts
+/// as rewritten
+pub fn load_requirements(config: &PdwConfig, database: &Path) -> Vec<SpaceRequirement> {
+    let input = config.get_input_file_path();
+    let compressed = ["xlsx", "ods"].iter().any(|ext| config.file_types.type_in.eq_ignore_ascii_case(ext));
+    let expansion = if compressed { XLSX_EXPANSION } else { TEXT_EXPANSION };
+    let mut bytes = path_size(&input).saturating_mul(expansion);
+    
+    for source in config.sources.values().filter(|source| source.enabled) {
+        bytes = bytes.saturating_add(path_size(&config.directories.dir_in.join(&source.path)).saturating_mul(TEXT_EXPANSION));
+    }
+    
+    vec![SpaceRequirement::new(database, bytes.saturating_add(path_size(database)))]
+}
+
+/// Space needed by the report phase: summary tables in `database`, workbooks
+/// and exports in the output directory, each sized after half the database
+pub fn report_requirements(config: &PdwConfig, database: &Path) -> Vec<SpaceRequirement> {
+    let half = path_size(database) / 2;
+    vec![
+        SpaceRequirement::new(database, half),
+        SpaceRequirement::new(&config.directories.dir_out, half),
+    ]
+}
+
+/// Fail when a volume has less free space than its requirements plus the margin;
+/// volumes whose free space cannot be read are not checked
+pub fn ensure_space(requirements: &[SpaceRequirement], margin_mb: u64, phase: &str) -> Result<(), PdwError> {
+    check_volumes(requirements, margin_mb, phase, available_space)
+}
+
+fn check_volumes(requirements: &[SpaceRequirement], margin_mb: u64, phase: &str,
+                 available: impl Fn(&Path) -> Option<u64>) -> Result<(), PdwError> {
+    // Requirements on the same volume add up
+    let mut volumes: BTreeMap<VolumeId, (PathBuf, u64)> = BTreeMap::new();
+    for requirement in requirements {
+        let volume = volumes.entry(volume_id(&requirement.path))
+            .or_insert_with(|| (requirement.path.clone(), 0));
+        volume.1 = volume.1.saturating_add(requirement.bytes);
+    }
+    
+    for (path, bytes) in volumes.into_values() {
+        let required = bytes.saturating_add(margin_mb.saturating_mul(MB));
+        let Some(free) = available(&path) else {
+            tracing::debug!("Free space of {} unknown, not checked", path.display());
+            continue;
+        };
+        tracing::debug!("{} needs {} MB on {}, {} MB free", phase, to_mb(required), path.display(), to_mb(free));
+        
+        if free < required {
+            return Err(EtlError::ValidationFailed {
+                check: "disk space".to_string(),
+                reason: format!(
+                    "{} needs about {} MB on the volume of {} ({} MB estimated + {} MB margin) but only {} MB are free; \
+                     free some space or lower settings.disk_space_margin_mb",
+                    phase, to_mb(required), path.display(), to_mb(bytes), margin_mb, to_mb(free)
+                ),
+            }.into());
+        }
+    }
+    
+    Ok(())
+}
+
+fn to_mb(bytes: u64) -> u64 {
+    bytes.div_ceil(MB)
+}
+
+/// Size of a file, or of the files directly inside a directory; 0 when missing
+fn path_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    
+    std::fs::read_dir(path)
+        .map(|entries| entries.filter_map(|entry| entry.ok()?.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum())
+        .unwrap_or(0)
+}
+
+/// Closest existing ancestor of a path: output directories may not exist yet
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(unix)]
+type VolumeId = u64;
+
+#[cfg(not(unix))]
+type VolumeId = PathBuf;
+
+#[cfg(unix)]
+fn volume_id(path: &Path) -> VolumeId {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(existing_ancestor(path)).map(|metadata| metadata.dev()).unwrap_or(0)
+}
+
+#[cfg(not(unix))]
+fn volume_id(path: &Path) -> VolumeId {
+    existing_ancestor(path)
+}
+
+/// Bytes available to the current user on the volume holding `path`
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    
+    let path = std::ffi::CString::new(existing_ancestor(path).as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid statvfs to fill in
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+/// Bytes available on the volume holding `path`; not known on this platform
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_requirements_from_input_sizes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.directories.dir_out = temp_dir.path().join("output");
+        std::fs::write(temp_dir.path().join("PDW.xlsx"), vec![0u8; 1000]).unwrap();
+        let database = temp_dir.path().join("PDW.db");
+        std::fs::write(&database, vec![0u8; 400]).unwrap();
+        
+        assert_eq!(load_requirements(&config, &database), vec![SpaceRequirement::new(&database, 10_400)]);
+        assert_eq!(report_requirements(&config, &database)[1], SpaceRequirement::new(&config.directories.dir_out, 200));
+        
+        // CSV input: the files of the input directory, expanded less
+        config.file_types.type_in = "csv".to_string();
+        config.file_types.input_file = "csv".to_string();
+        std::fs::create_dir(temp_dir.path().join("csv")).unwrap();
+        std::fs::write(temp_dir.path().join("csv").join("Conta.csv"), vec![0u8; 300]).unwrap();
+        assert_eq!(load_requirements(&config, &database)[0].bytes, 1_000);
+    }
+    
+    #[test]
+    fn test_check_volumes() {
+        let temp_dir = TempDir::new().unwrap();
+        let database = temp_dir.path().join("database").join("PDW.db");
+        let output = temp_dir.path().join("output");
+        // Both directories are missing and live on the same volume: 30 + 20 MB
+        let requirements = [SpaceRequirement::new(&database, 30 * MB), SpaceRequirement::new(&output, 20 * MB)];
+        
+        assert!(check_volumes(&requirements, 10, "Load", |_| Some(60 * MB)).is_ok());
+        assert!(check_volumes(&requirements, 10, "Load", |_| None).is_ok());
+        let error = check_volumes(&requirements, 10, "Load", |_| Some(59 * MB)).unwrap_err().to_string();
+        assert!(error.contains("Load needs about 60 MB"), "{}", error);
+        assert!(error.contains("50 MB estimated + 10 MB margin) but only 59 MB are free"), "{}", error);
+        
+        #[cfg(unix)]
+        assert!(available_space(&database).is_some_and(|free| free > 0));
+    }
+}
\ No newline at end of file
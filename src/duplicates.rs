@@ -0,0 +1,226 @@
+/*!
+# Duplicates Module
+
+Flags probable duplicate entries: the same amount and description (accents,
+case and punctuation ignored) dated at most `window_days` apart in different
+origins, such as a credit card charge also present in the bank statement. Each
+pair found is written to the DUPLICADOS table and the "Duplicados" report sheet
+for review; nothing is removed from the general entries.
+
+Exact repeats inside a single origin are counted by the `duplicate` data quality
+rule instead; `same_origin = true` lists those pairs here too.
+*/
+
+use crate::collation;
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, PdwError};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Duplicate detection settings (`[duplicates]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicatesConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Most days between the two entries of a pair (0 = same date)
+    #[serde(default = "default_window_days")]
+    pub window_days: u32,
+    /// Also pair entries of the same origin
+    #[serde(default)]
+    pub same_origin: bool,
+    #[serde(default = "default_duplicates_table")]
+    pub table: String,
+}
+
+/// Entry of the general entries table compared for duplicates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub date: NaiveDate,
+    pub origin: String,
+    pub description: String,
+    /// Debit minus credit
+    pub amount: f64,
+}
+
+/// Two entries that are probably the same transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicatePair {
+    pub first: Entry,
+    pub second: Entry,
+    pub days: i64,
+}
+
+fn default_window_days() -> u32 {
+    3
+}
+
+fn default_duplicates_table() -> String {
+    "DUPLICADOS".to_string()
+}
+
+impl Default for DuplicatesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_days: default_window_days(),
+            same_origin: false,
+            table: default_duplicates_table(),
+        }
+    }
+}
+
+/// Dated entries of the general entries table with an amount
+pub fn read_entries(database: &DatabaseManager, entries_table: &str) -> Result<Vec<Entry>, PdwError> {
+    let query = format!(
+        "SELECT Data, COALESCE(Origem, ''), COALESCE(DESCRICAO, ''), COALESCE(Debito, 0) - COALESCE(Credito, 0)
+         FROM {} WHERE Data IS NOT NULL ORDER BY Data, rowid",
+        entries_table
+    );
+    
+    Ok(database.execute_query(&query)?.into_iter()
+        .filter_map(|row| {
+            let text = |index: usize| row.get(index).and_then(Value::as_str).unwrap_or_default().to_string();
+            let date = NaiveDate::parse_from_str(text(0).get(..10)?, "%Y-%m-%d").ok()?;
+            let amount = row.get(3).and_then(Value::as_f64).filter(|amount| *amount != 0.0)?;
+            Some(Entry { date, origin: text(1), description: text(2), amount })
+        })
+        .collect())
+}
+
+/// Pairs of entries with the same amount and description within the window, oldest first
+pub fn find_duplicates(entries: &[Entry], config: &DuplicatesConfig) -> Vec<DuplicatePair> {
+    let mut groups: BTreeMap<(String, i64), Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        let description = normalize(&entry.description);
+        if description.is_empty() {
+            continue;
+        }
+        let cents = (entry.amount * 100.0).round() as i64;
+        groups.entry((description, cents)).or_default().push(entry);
+    }
+    
+    let mut pairs = Vec::new();
+    for group in groups.values_mut() {
+        group.sort_by_key(|entry| entry.date);
+        for (idx, first) in group.iter().enumerate() {
+            for second in &group[idx + 1..] {
+                let days = (second.date - first.date).num_days();
+                if days > config.window_days as i64 {
+                    break;
+                }
+                if config.same_origin || first.origin.trim() != second.origin.trim() {
+                    pairs.push(DuplicatePair { first: (*first).clone(), second: (*second).clone(), days });
+                }
+            }
+        }
+    }
+    
+    pairs.sort_by(|a, b| (a.first.date, &a.first.origin, &a.first.description)
+        .cmp(&(b.first.date, &b.first.origin, &b.first.description)));
+    pairs
+}
+
+/// Store the pairs found, replacing the previous run's table
+pub fn write_duplicates_table(database: &DatabaseManager, table: &str, pairs: &[DuplicatePair]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (Data DATE, Origem TEXT, DataDuplicata DATE, OrigemDuplicata TEXT,
+                          DESCRICAO TEXT, Valor REAL, Dias INTEGER)",
+        table
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)", table);
+    for pair in pairs {
+        database.connection().execute(&insert_query, rusqlite::params![
+            pair.first.date.format("%Y-%m-%d").to_string(),
+            pair.first.origin,
+            pair.second.date.format("%Y-%m-%d").to_string(),
+            pair.second.origin,
+            pair.first.description,
+            pair.first.amount,
+            pair.days,
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(pairs.len())
+}
+
+/// Accent-free lowercase words of a description, single-spaced
+pub(crate) fn normalize(text: &str) -> String {
+    collation::fold(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn entry(date: &str, origin: &str, description: &str, amount: f64) -> Entry {
+        Entry {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            origin: origin.to_string(),
+            description: description.to_string(),
+            amount,
+        }
+    }
+    
+    #[test]
+    fn test_pairs_within_window_across_origins() {
+        let entries = vec![
+            entry("2024-01-05", "Cartão", "Farmácia São João", 52.3),
+            entry("2024-01-07", "Conta", "FARMACIA SAO JOAO.", 52.3),
+            // Too far apart, other amount, same origin
+            entry("2024-01-20", "Conta", "Farmacia Sao Joao", 52.3),
+            entry("2024-01-05", "Conta", "Farmacia Sao Joao", 52.0),
+            entry("2024-01-05", "Cartão", "Farmácia São João", 52.3),
+        ];
+        
+        let config = DuplicatesConfig::default();
+        let pairs = find_duplicates(&entries, &config);
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs.iter().all(|pair| pair.second == entries[1] && pair.days == 2));
+        
+        let same_origin = DuplicatesConfig { same_origin: true, window_days: 0, ..config };
+        let pairs = find_duplicates(&entries, &same_origin);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].first.origin.as_str(), pairs[0].days), ("Cartão", 0));
+        
+        let parsed: DuplicatesConfig = toml::from_str("enabled = true\nwindow_days = 5").unwrap();
+        assert_eq!((parsed.window_days, parsed.same_origin, parsed.table.as_str()), (5, false, "DUPLICADOS"));
+    }
+    
+    #[test]
+    fn test_duplicates_table() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-05', 'SAUDE', 'Farmácia', 0, 52.3, '2024/01', 'Cartão');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-06', 'SAUDE', 'FARMACIA', 0, 52.3, '2024/01', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-06', 'MERCADO', 'Supermercado', 0, 200, '2024/01', 'Conta');"
+        ).unwrap();
+        
+        let entries = read_entries(&db, "LANCAMENTOS_GERAIS").unwrap();
+        assert_eq!(entries.len(), 3);
+        let pairs = find_duplicates(&entries, &DuplicatesConfig::default());
+        assert_eq!(write_duplicates_table(&db, "DUPLICADOS", &pairs).unwrap(), 1);
+        
+        let rows = db.execute_query("SELECT Data, Origem, DataDuplicata, OrigemDuplicata, Valor, Dias FROM DUPLICADOS").unwrap();
+        assert_eq!(rows, vec![vec![json!("2024-01-05"), json!("Cartão"), json!("2024-01-06"), json!("Conta"), json!(52.3), json!(1)]]);
+    }
+}
\ No newline at end of file
@@ -30,6 +30,27 @@ pub enum PdwError {
     
     #[error("Logging initialization error: {0}")]
     Logging(String),
+    
+    #[error("Notification error: {0}")]
+    Notification(String),
+    
+    #[error("Exchange rate error: {0}")]
+    ExchangeRate(String),
+    
+    #[error("Watch error: {0}")]
+    Watch(String),
+    
+    #[error("Server error: {0}")]
+    Server(String),
+    
+    #[error("Daemon error: {0}")]
+    Daemon(String),
+    
+    #[error("Update error: {0}")]
+    Update(String),
+    
+    #[error("Upload error: {0}")]
+    Upload(String),
 }
 
 /// Configuration-related errors
@@ -102,6 +123,14 @@ pub enum DatabaseError {
     #[error("Data insertion error: {table} - {reason}")]
     DataInsertion { table: String, reason: String },
     
+    #[error("Database is locked: {path} - still in use by {holder} after waiting {timeout_ms} ms. \
+             Close it there (DB Browser for SQLite holds the lock until changes are written or reverted) \
+             or raise settings.busy_timeout_ms")]
+    Locked { path: String, holder: String, timeout_ms: u64 },
+    
+    #[error("Database file is corrupted: {path} - {details}")]
+    Corrupted { path: String, details: String },
+    
     #[error("SQLite error: {0}")]
     Sqlite(#[from] rusqlite::Error),
 }
@@ -191,6 +220,7 @@ impl PdwError {
             PdwError::Config(_) => false,  // Configuration errors are not recoverable
             PdwError::Excel(ExcelError::FileOpen { .. }) => false,  // File access errors
             PdwError::Database(DatabaseError::ConnectionFailed { .. }) => false,  // Connection errors
+            PdwError::Database(DatabaseError::Corrupted { .. }) => false,  // Needs manual repair
             PdwError::Io(_) => false,  // IO errors are generally not recoverable
             _ => true,  // Other errors might be recoverable
         }
@@ -211,6 +241,13 @@ impl PdwError {
             PdwError::Database(DatabaseError::ConnectionFailed { path, .. }) => {
                 format!("Cannot connect to database: {}. Please check file permissions and disk space.", path)
             }
+            PdwError::Database(DatabaseError::Corrupted { path, details }) => {
+                format!(
+                    "Database {} failed its integrity check ({}). Run 'pdw repair' to salvage its data into a fresh file, \
+                     or delete it and reload from the workbook with overwrite_db = true.",
+                    path, details
+                )
+            }
             _ => self.to_string(),
         }
     }
@@ -246,5 +283,12 @@ mod tests {
         let message = error.user_message();
         assert!(message.contains("Configuration file not found"));
         assert!(message.contains("test.toml"));
+        
+        let error = PdwError::Database(DatabaseError::Corrupted {
+            path: "PDW.db".to_string(),
+            details: "row 3 missing from index".to_string(),
+        });
+        assert!(!error.is_recoverable());
+        assert!(error.user_message().contains("pdw repair"));
     }
 }
\ No newline at end of file
@@ -5,27 +5,199 @@ Orchestrates the Extract, Transform, Load process for Excel to SQLite conversion
 Handles data transformation, enrichment, and validation.
 */
 
+use crate::alerts::{self, AlertEngine};
+use crate::analytics::{self, Measure};
+use crate::baselines;
+use crate::budgets;
+use crate::cash_flow;
+use crate::categorize::Categorizer;
+use crate::charts::StatementCard;
+use crate::compat;
+use crate::compression;
 use crate::config::PdwConfig;
-use crate::database::{DatabaseManager, ProcessedTransaction};
-use crate::error::{EtlError, PdwError};
-use crate::excel::{ExcelProcessor, Transaction, SheetConfig};
+use crate::consistency::ConsistencyReport;
+use crate::database::{DatabaseManager, DatabaseOperations, MetaStore, PeriodSet, ProcessedTransaction, RowHasher};
+use crate::deterministic;
+use crate::diskspace::{self, SpaceRequirement};
+use crate::duplicates;
+use crate::error::{EtlError, ExcelError, PdwError};
+use crate::csv_input::CsvProcessor;
+use crate::currency::{self, CurrencyConverter};
+use crate::excel::{ExcelProcessor, ExcelReader, Transaction, SheetConfig};
+use crate::cycles::{self, DateBasis};
+use crate::expression::{DerivedColumn, DerivedValue};
+use crate::fees::{self, FeeClassifier};
+use crate::forecast;
+use crate::fx::RateCache;
+use crate::importers::{self, Importer};
 use crate::logging;
-use chrono::{NaiveDate, Datelike, Weekday};
+use crate::migrations;
+use crate::money::{self, MoneyMode};
+use crate::mqtt;
+use crate::non_data::NonDataFilter;
+use crate::notifications::Notifier;
+use crate::opening_balances::{self, OpeningBalances};
+use crate::pdf;
+use crate::publish;
+use crate::purge;
+use crate::quality::QualityReport;
+use crate::quarantine::{Quarantine, RejectReason};
+use crate::reconciliation;
+use crate::report_diff;
+use crate::runs::RunRecord;
+use crate::sanity::{self, SanityTables};
+use crate::splits::SplitRules;
+use crate::tags;
+use crate::telemetry::{self, RunMetrics};
+use crate::type_normalization::{self, TypeNormalizer, VariantTracker};
+use crate::window::DateWindow;
+use chrono::{Datelike, NaiveDate};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How a load treats entries already in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadMode {
+    /// Drop the entries table and reload every row
+    #[default]
+    Replace,
+    /// Insert every row on top of the existing entries
+    Append,
+    /// Insert only rows whose RowHash is not stored yet
+    Incremental,
+}
+
+/// What a transform hook does with an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformAction {
+    /// Load the entry, with the hook's changes
+    Keep,
+    /// Leave the entry out of the load
+    Drop,
+}
+
+/// Code run on every entry the load transforms (`EtlPipeline::add_transform`)
+pub type TransformHook = Box<dyn Fn(&mut ProcessedTransaction) -> TransformAction + Send + Sync>;
 
 /// ETL Pipeline orchestrator
 pub struct EtlPipeline {
     config: PdwConfig,
     database: DatabaseManager,
+    derived_columns: Vec<DerivedColumn>,
+    type_normalizer: Option<TypeNormalizer>,
+    categorizer: Option<Categorizer>,
+    /// Parts of the entries listed in the splits sheet
+    splits: Option<SplitRules>,
+    /// Subtotal and section-header rules for the accounting sheets
+    non_data: Option<NonDataFilter>,
+    /// Periods changed by the last load; None means every period may have changed
+    touched_periods: Option<PeriodSet>,
+    /// Current run, written to PDW_RUNS by `record_run`
+    run: RunRecord,
+    /// Exchange rates of the current load, when multi-currency is enabled
+    converter: Option<CurrencyConverter>,
+    /// Previous report (workbook or database) the report phase lists changes against
+    diff_baseline: Option<PathBuf>,
+    /// Months the next load is restricted to (`--window`)
+    window: Option<DateWindow>,
+    /// Sources registered in code, loaded after the `[sources]` entries
+    importers: Vec<(String, Box<dyn Importer>)>,
+    /// Hooks registered in code, run in order on every transformed entry
+    transforms: Vec<TransformHook>,
+}
+
+/// Transformation state without the database connection, shared with the sheet worker threads
+struct SheetTransformer<'a> {
+    config: &'a PdwConfig,
+    derived_columns: &'a [DerivedColumn],
+    type_normalizer: Option<&'a TypeNormalizer>,
+    categorizer: Option<&'a Categorizer>,
+    splits: Option<&'a SplitRules>,
+    non_data: Option<&'a NonDataFilter>,
+    converter: Option<&'a CurrencyConverter>,
+    window: Option<&'a DateWindow>,
+    transforms: &'a [TransformHook],
+}
+
+/// Accounting sheet read and transformed by a worker thread (or streamed, with no transactions kept)
+struct ExtractedSheet {
+    lines_read: usize,
+    /// Subtotal and section-header rows dropped after reading
+    non_data_rows: usize,
+    /// Rows dated outside the load window
+    outside_window: usize,
+    transactions: Vec<ProcessedTransaction>,
+    variants: VariantTracker,
+    quality: QualityReport,
+    quarantine: Quarantine,
+    elapsed: Duration,
+}
+
+/// Chunks of the accounting sheets already inserted by a streamed load
+#[derive(Default)]
+struct StreamedLoad {
+    hasher: RowHasher,
+    periods: PeriodSet,
+    processed: usize,
+    inserted: usize,
 }
 
 impl EtlPipeline {
     /// Create new ETL pipeline
     pub fn new(config: PdwConfig) -> Result<Self, PdwError> {
         let db_path = config.get_database_path();
-        let database = DatabaseManager::new(&db_path)?;
+        let mut database = DatabaseManager::open_configured(&db_path, &config.settings, &config.database.sqlite)?;
+        if config.database.sqlite.in_memory {
+            database.move_to_memory()?;
+        }
+        // A Python database is adapted, then migrated, by the first load only
+        let python = compat::prepare(&database, &config, false)?.producer == Some(compat::Producer::Python);
+        if config.settings.auto_migrate && !python {
+            migrations::migrate(&database, &config)?;
+        } else if compat::user_version(&database)? < migrations::LATEST_VERSION {
+            tracing::warn!("{} has pending schema migrations - run `pdw migrate up`", database.path().display());
+        }
+        let derived_columns = config.compile_derived_columns()?;
+        let type_normalizer = TypeNormalizer::from_config(&config.type_normalization);
+        let categorizer = Categorizer::from_config(&config.categorization, config.get_category_rules_path().as_deref())?;
+        let non_data = NonDataFilter::from_config(&config.non_data_rows)?;
+        
+        Ok(Self { config, database, derived_columns, type_normalizer, categorizer, splits: None, non_data, touched_periods: None, run: RunRecord::start(), converter: None, diff_baseline: None, window: None, importers: Vec::new(), transforms: Vec::new() })
+    }
+    
+    /// Create a pipeline backed by an in-memory database (no warehouse file is written)
+    pub fn in_memory(config: PdwConfig) -> Result<Self, PdwError> {
+        let mut database = DatabaseManager::new(Path::new(":memory:"))?;
+        database.set_collation(config.settings.collation)?;
+        database.set_money_mode(config.settings.money);
+        let derived_columns = config.compile_derived_columns()?;
+        let type_normalizer = TypeNormalizer::from_config(&config.type_normalization);
+        let categorizer = Categorizer::from_config(&config.categorization, config.get_category_rules_path().as_deref())?;
+        let non_data = NonDataFilter::from_config(&config.non_data_rows)?;
         
-        Ok(Self { config, database })
+        Ok(Self { config, database, derived_columns, type_normalizer, categorizer, splits: None, non_data, touched_periods: None, run: RunRecord::start(), converter: None, diff_baseline: None, window: None, importers: Vec::new(), transforms: Vec::new() })
+    }
+    
+    /// Load the entries of `importer` with the next loads, under the origin-like source `name`
+    pub fn register_importer(&mut self, name: &str, importer: Box<dyn Importer>) {
+        self.importers.push((name.to_string(), importer));
+    }
+    
+    /// Run `hook` on every entry of the next loads, after the TIPO is normalized and before
+    /// the derived columns are evaluated; hooks run in the order they were added, and one
+    /// returning `TransformAction::Drop` leaves the entry out. AnoMes and the other calendar
+    /// fields follow a date (or origin) the hooks changed
+    pub fn add_transform(&mut self, hook: TransformHook) {
+        self.transforms.push(hook);
+    }
+    
+    /// Give up the pipeline, keeping its database (e.g. an in-memory one, for a ReportGenerator)
+    pub fn into_database(self) -> DatabaseManager {
+        self.database
     }
     
     /// Get configuration reference
@@ -33,45 +205,188 @@ impl EtlPipeline {
         &self.config
     }
     
+    /// Warehouse the pipeline loads into
+    pub fn database(&self) -> &DatabaseManager {
+        &self.database
+    }
+    
+    /// What the current run loaded so far
+    pub fn run_record(&self) -> &RunRecord {
+        &self.run
+    }
+    
+    /// Start recording a new run, for a pipeline kept open between runs (`pdw daemon`)
+    pub fn start_run(&mut self) {
+        self.run = RunRecord::start();
+    }
+    
+    /// Record the current run in PDW_RUNS under a command name; `error` marks it failed
+    pub fn record_run(&self, command: &str, error: Option<&str>) -> Result<i64, PdwError> {
+        self.run.write(&self.database, command, error)
+    }
+    
+    /// Push the current run's metrics and span timings (`[telemetry]`); push failures are only logged
+    pub fn push_run_metrics(&self, command: &str, failed: bool) -> usize {
+        if !self.config.telemetry.enabled {
+            return 0;
+        }
+        let metrics = RunMetrics::new(&self.run, command, failed, &telemetry::take_timings());
+        match telemetry::push(&self.config.telemetry, &metrics) {
+            Ok(pushed) => pushed,
+            Err(e) => {
+                tracing::warn!("Run metrics not pushed: {}", e);
+                0
+            }
+        }
+    }
+    
+    /// Fail early when a phase may not fit in the free space of its volumes
+    fn check_disk_space(&self, phase: &str, requirements: fn(&PdwConfig, &Path) -> Vec<SpaceRequirement>) -> Result<(), PdwError> {
+        let database = self.database.path();
+        if !self.config.settings.check_disk_space || database == Path::new(":memory:") {
+            return Ok(());
+        }
+        diskspace::ensure_space(&requirements(&self.config, database), self.config.settings.disk_space_margin_mb, phase)
+    }
+    
     /// Execute data loading phase
     pub fn execute_data_loading(&mut self) -> Result<(), PdwError> {
-        logging::log_phase_start("Running Loader of the Sheets into database Tables");
+        let _phase = logging::log_phase_start("Running Loader of the Sheets into database Tables");
+        self.check_disk_space("Load", diskspace::load_requirements)?;
+        if compat::prepare(&self.database, &self.config, true)?.producer == Some(compat::Producer::Python)
+            && self.config.settings.auto_migrate {
+            migrations::migrate(&self.database, &self.config)?;
+        }
+        
+        let load_mode = self.effective_load_mode()?;
+        
+        // Drop existing general entries table (full reload: every period may change)
+        if load_mode == LoadMode::Replace && self.window.is_none() {
+            self.database.drop_table(&self.config.settings.general_entries_table)?;
+        }
+        self.touched_periods = None;
         
         // Create database tables
         self.database.create_tables()?;
         
-        // Drop existing general entries table
-        self.database.drop_table(&self.config.settings.general_entries_table)?;
+        // A windowed load only replaces the entries of its months
+        if let Some(window) = self.window {
+            tracing::info!("   . .. ... Load restricted to {} - other periods are kept", window);
+            if load_mode == LoadMode::Replace {
+                let deleted = self.database.delete_entries_between(&self.config.settings.general_entries_table, window.first, window.last)?;
+                logging::log_result("Window Entries Deleted", deleted);
+            }
+        }
+        
+        // Add config-defined derived columns (and RowHash on tables created before it)
+        self.add_derived_schema()?;
         
-        // Open Excel file
-        let input_file = self.config.get_input_file_path();
-        let mut excel_processor = ExcelProcessor::new(&input_file)?;
+        // Open Excel workbook or CSV directory
+        let mut excel_processor = self.open_input()?;
         
         // Read guiding sheet configuration
         let sheet_configs = excel_processor.read_guiding_sheet(&self.config.settings.guiding_table)?;
         
+        // Categorization rules kept in the input itself
+        self.categorizer = self.categorizer_with_sheet(excel_processor.as_mut())?;
+        
+        // Entries split into parts of other TIPOs
+        self.splits = self.split_rules(excel_processor.as_mut())?;
+        
+        // Exchange rates for the entries in other currencies
+        self.converter = self.currency_converter(excel_processor.as_mut())?;
+        
+        // Cross-check GUIDING against the sheets actually present
+        let mut consistency = self.config.settings.check_consistency.then(|| ConsistencyReport::check_sheets(
+            &excel_processor.sheet_names(),
+            &sheet_configs,
+            &self.config.settings.guiding_table,
+        ));
+        // A loadable sheet GUIDING lists but the workbook lacks fails the load, as reading it would
+        let missing_loadable = consistency.as_ref().and_then(|report| sheet_configs.iter().find(|config| {
+            config.is_loadable && report.missing_sheets.iter().any(|s| s == config.table_name.trim())
+        }));
+        if let Some(config) = missing_loadable {
+            return Err(ExcelError::SheetNotFound { sheet_name: config.table_name.trim().to_string() }.into());
+        }
+        
+        // Read and transform the accounting sheets on worker threads when enabled, unless streaming
+        let stream_rows = self.stream_chunk_rows(load_mode);
+        let mut streamed = StreamedLoad::default();
+        let mut extracted = match self.extraction_workers(&sheet_configs, consistency.as_ref()).filter(|_| stream_rows.is_none()) {
+            Some(workers) => self.extract_sheets_parallel(&sheet_configs, consistency.as_ref(), workers)?,
+            None => HashMap::new(),
+        };
+        
         // Process each sheet according to configuration
         let mut all_transactions = Vec::new();
+        let mut prepared_transactions = Vec::new();
+        let mut variants = VariantTracker::default();
+        let mut quality = QualityReport::default();
+        let mut quarantine = Quarantine::default();
+        let mut entries_read = 0;
         let mut step_counter = 1;
         
         for config in &sheet_configs {
+            let _sheet = tracing::info_span!("sheet", sheet = %config.table_name.trim()).entered();
             logging::log_step(
                 step_counter,
                 &format!("Table (Sheet) :-> {}", config.table_name.trim()),
                 ""
             );
             
-            if config.is_loadable {
-                if config.is_accounting {
+            let is_missing = consistency.as_ref()
+                .is_some_and(|report| report.missing_sheets.iter().any(|s| s == config.table_name.trim()));
+            
+            if is_missing {
+                logging::log_result("Missing Sheet - Skipped", 0);
+            } else if config.is_loadable {
+                let prepared = match stream_rows {
+                    Some(chunk_rows) if config.is_accounting => {
+                        Some(self.stream_sheet(excel_processor.as_mut(), &config.table_name, chunk_rows, &mut streamed)?)
+                    }
+                    _ => extracted.remove(&config.table_name),
+                };
+                if let Some(sheet) = prepared {
+                    // Accounting sheet already prepared by a worker, or streamed into the database
+                    logging::log_result("Lines Created", sheet.lines_read);
+                    if sheet.non_data_rows > 0 {
+                        logging::log_result("Non-data Rows - Skipped", sheet.non_data_rows);
+                    }
+                    if sheet.outside_window > 0 {
+                        logging::log_result("Outside Window - Skipped", sheet.outside_window);
+                    }
+                    logging::log_timing("Read and Transformed in", sheet.elapsed);
+                    self.run.record_sheet(&config.table_name, sheet.lines_read);
+                    entries_read += sheet.lines_read;
+                    prepared_transactions.extend(sheet.transactions);
+                    variants.merge(sheet.variants);
+                    quality.merge(sheet.quality);
+                    quarantine.merge(sheet.quarantine);
+                } else if config.is_accounting {
                     // Process accounting sheet
-                    let transactions = excel_processor.read_accounting_sheet(&config.table_name)?;
+                    let mut transactions = excel_processor.read_accounting_sheet(&config.table_name)?;
+                    let non_data_rows = self.transformer().skip_non_data(&mut transactions);
+                    let outside_window = self.transformer().skip_outside_window(&mut transactions);
                     logging::log_result("Lines Created", transactions.len());
+                    if non_data_rows > 0 {
+                        logging::log_result("Non-data Rows - Skipped", non_data_rows);
+                    }
+                    if outside_window > 0 {
+                        logging::log_result("Outside Window - Skipped", outside_window);
+                    }
+                    self.run.record_sheet(&config.table_name, transactions.len());
+                    entries_read += transactions.len();
                     all_transactions.extend(transactions);
+                } else if self.window.is_some() {
+                    // A windowed load leaves the reference tables as they are
+                    logging::log_result("Reference Sheet - Kept", 0);
                 } else {
                     // Process reference sheet
                     let data = excel_processor.read_reference_sheet(&config.table_name)?;
                     let count = self.database.insert_reference_data(&config.table_name, &data)?;
                     logging::log_result("Lines Created", count);
+                    self.run.record_sheet(&config.table_name, count);
                 }
             } else {
                 logging::log_result("Skipped", 0);
@@ -80,137 +395,613 @@ impl EtlPipeline {
             step_counter += 1;
         }
         
-        // Transform and enrich transaction data
-        let processed_transactions = self.transform_transactions(all_transactions)?;
+        // Bank downloads configured under [sources], then the importers registered in code
+        let mut downloads = Vec::new();
+        for (name, source) in self.config.sources.iter().filter(|(_, source)| source.enabled) {
+            downloads.push((name.clone(), importers::read_source(name, source, &self.config.directories.dir_in)?));
+        }
+        for (name, importer) in &self.importers {
+            downloads.push((name.clone(), importer.read(&self.config.directories.dir_in)?));
+        }
+        for (name, mut transactions) in downloads {
+            logging::log_step(step_counter, &format!("Source :-> {}", name), "");
+            let outside_window = self.transformer().skip_outside_window(&mut transactions);
+            logging::log_result("Lines Created", transactions.len());
+            if outside_window > 0 {
+                logging::log_result("Outside Window - Skipped", outside_window);
+            }
+            self.run.record_sheet(&name, transactions.len());
+            entries_read += transactions.len();
+            all_transactions.extend(transactions);
+            step_counter += 1;
+        }
+        
+        // Monthly limits per TIPO for the budget vs actual report
+        if self.config.budgets.enabled {
+            logging::log_step(step_counter, &format!("Budgets :-> {}", self.config.budgets.table), "");
+            let count = self.load_budgets(excel_processor.as_mut())?;
+            logging::log_result("Lines Created", count);
+        }
+        
+        // Balances each origin held before its entries
+        if self.config.opening_balances.enabled {
+            logging::log_step(step_counter, &format!("Opening Balances :-> {}", self.config.opening_balances.table), "");
+            let count = self.load_opening_balances(excel_processor.as_mut())?;
+            logging::log_result("Lines Created", count);
+        }
+        
+        // End balances declared by the bank statements
+        if self.config.reconciliation.enabled {
+            logging::log_step(step_counter, &format!("Statement Balances :-> {}", self.config.reconciliation.balances_table), "");
+            let count = self.load_declared_balances(excel_processor.as_mut())?;
+            logging::log_result("Lines Created", count);
+        }
+        
+        // Exchange rates of the rates sheet and file
+        if let Some(converter) = &self.converter {
+            logging::log_step(step_counter, &format!("Exchange Rates :-> {}", self.config.currency.table), "");
+            let count = currency::write_rates_table(&self.database, &self.config.currency.table, converter.rates())?;
+            logging::log_result("Lines Created", count);
+        }
+        
+        // Report TIPO spellings merged by normalization
+        if self.type_normalizer.is_some() {
+            self.transformer().track_variants(&mut variants, &all_transactions);
+            self.write_merged_types(&variants)?;
+        }
+        self.transformer().check_quality(&mut quality, &all_transactions);
+        
+        // Transform and enrich transaction data, then merge the sheets prepared by the workers
+        let mut processed_transactions = self.transform_transactions(all_transactions, &mut quarantine)?;
+        if !prepared_transactions.is_empty() {
+            processed_transactions.extend(prepared_transactions);
+            sort_by_date(&mut processed_transactions);
+        }
         
-        // Insert processed transactions
-        let count = self.database.insert_transactions(&processed_transactions)?;
+        // Insert processed transactions, recording the periods they touch
+        let count = streamed.inserted + match load_mode {
+            LoadMode::Replace => self.database.insert_transactions(&processed_transactions)?,
+            LoadMode::Append => {
+                let mut periods = std::mem::take(&mut streamed.periods);
+                periods.add(&processed_transactions);
+                self.touched_periods = Some(periods);
+                self.database.insert_transactions(&processed_transactions)?
+            }
+            LoadMode::Incremental => {
+                let inserted = self.database.insert_new_transactions(&processed_transactions)?;
+                logging::log_result("Already Loaded - Skipped", processed_transactions.len() - inserted.len());
+                self.touched_periods = Some(PeriodSet::from_transactions(inserted.iter().copied()));
+                inserted.len()
+            }
+        };
+        // Only the window's periods changed, whatever the mode
+        if let Some(window) = self.window {
+            self.touched_periods = Some(window.periods());
+        }
         logging::log_result("Total Transactions Processed", count);
         
-        // Perform data validation and cleanup
-        self.database.validate_and_clean_data(
+        // Rejected entries go to the discarded table, with those of unknown TIPO moved there after the load
+        self.database.validate_and_clean_data(&self.config.settings.types_of_entries)?;
+        quarantine.write_table(&self.database, &self.config.settings.discarted_data_table)?;
+        let discarded = quarantine.reject_unknown_types(
+            &self.database,
             &self.config.settings.general_entries_table,
             &self.config.settings.types_of_entries,
-            self.config.settings.save_discarted_data,
             &self.config.settings.discarted_data_table,
         )?;
+        quarantine.log();
+        // Entries purged earlier stay purged when the workbook still holds them
+        let purged = purge::reapply(&self.database, &self.config.settings.general_entries_table,
+                                    &purge::copy_tables(&self.config.settings))?;
+        if purged > 0 {
+            logging::log_result("Purged Again", purged);
+        }
+        // Without a types sheet the pivots would have no columns
+        if self.config.settings.seed_types {
+            let seeded = self.database.seed_types(
+                &self.config.settings.general_entries_table,
+                &self.config.settings.types_of_entries,
+            )?;
+            if seeded > 0 {
+                logging::log_result("Types Seeded from TIPO", seeded);
+                tracing::warn!("{} is empty - seeded with {} TIPOs found in the entries; add a {} sheet to describe them",
+                           self.config.settings.types_of_entries, seeded, self.config.settings.types_of_entries);
+            }
+        }
+        // Rows skipped by the transformation count as discarded too
+        self.run.rows_loaded += count - (discarded + purged).min(count);
+        self.run.rows_discarded += discarded + entries_read.saturating_sub(processed_transactions.len() + streamed.processed);
+        compat::write_stamp(&self.database, &self.config, None)?;
+        let meta = self.database.meta();
+        meta.set(MetaStore::LAST_LOAD, "finished_at", &deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string())?;
+        meta.set(MetaStore::LAST_LOAD, "mode", &load_mode)?;
+        meta.set(MetaStore::LAST_LOAD, "rows", &count)?;
+        match self.window {
+            Some(window) => meta.set(MetaStore::LAST_LOAD, "window", &window.to_string())?,
+            None => { meta.remove(MetaStore::LAST_LOAD, "window")?; }
+        }
+        
+        // Count rule violations per origin, including the rows the load skipped
+        if self.config.quality.enabled {
+            quality.count_rejected(&self.config.quality, &quarantine);
+            quality.check_database(
+                &self.config.quality,
+                &self.database,
+                &self.config.settings.general_entries_table,
+                &self.config.settings.types_of_entries,
+            )?;
+            quality.log();
+            quality.write_table(&self.database, &self.config.quality.table)?;
+        }
+        
+        // Report GUIDING/TiposLancamentos inconsistencies
+        if let Some(report) = consistency.as_mut() {
+            report.check_unused_types(
+                &self.database,
+                &self.config.settings.types_of_entries,
+                &self.config.settings.general_entries_table,
+            )?;
+            report.log();
+            report.write_table(&self.database, &self.config.settings.consistency_table)?;
+        }
+        
+        // Same-month averages of the previous years, for budgets, alerts and the digest
+        if self.config.baselines.enabled {
+            self.create_baselines()?;
+        }
+        
+        // Evaluate alert rules against the freshly loaded data
+        let triggered_alerts = if self.config.alerts.is_empty() { 0 } else { self.evaluate_alerts()? };
+        
+        // Publish key metrics to Home Assistant
+        if self.config.notifications.enabled && self.config.notifications.mqtt.is_some() {
+            self.publish_mqtt_metrics(triggered_alerts)?;
+        }
         
         Ok(())
     }
     
-    /// Transform raw transactions into processed format
-    fn transform_transactions(&self, transactions: Vec<Transaction>) -> Result<Vec<ProcessedTransaction>, PdwError> {
-        let mut processed = Vec::new();
+    /// Send current metrics to the MQTT broker; broker failures are only logged
+    pub fn publish_mqtt_metrics(&self, triggered_alerts: usize) -> Result<usize, PdwError> {
+        let Some(mqtt_config) = &self.config.notifications.mqtt else {
+            return Ok(0);
+        };
         
-        for transaction in transactions {
-            if let Some(processed_transaction) = self.process_single_transaction(transaction)? {
-                processed.push(processed_transaction);
+        let entries_table = &self.config.settings.general_entries_table;
+        let openings = OpeningBalances::load(&self.database, entries_table, &self.config.opening_balances)?;
+        let metrics = mqtt::collect_metrics(
+            &self.database,
+            entries_table,
+            &mqtt_config.currency,
+            &openings,
+            triggered_alerts,
+            &self.config.analytics.exclusions.types(Measure::Metrics, &self.config.category_groups),
+        )?;
+        
+        match mqtt::publish(mqtt_config, &metrics) {
+            Ok(published) => {
+                logging::log_result("MQTT Messages Published", published);
+                Ok(published)
+            }
+            Err(e) => {
+                tracing::warn!("MQTT publishing failed: {}", e);
+                Ok(0)
+            }
+        }
+    }
+    
+    /// Load reference sheets, entries and pivots into another warehouse backend
+    pub fn load_into<D: DatabaseOperations>(&self, target: &D) -> Result<usize, PdwError> {
+        let _phase = logging::log_phase_start("Running Loader of the Sheets into the external database");
+        let settings = &self.config.settings;
+        
+        let mut input = self.open_input()?;
+        let sheet_names = input.sheet_names();
+        let sheet_configs: Vec<SheetConfig> = input.read_guiding_sheet(&settings.guiding_table)?
+            .into_iter()
+            .filter(|config| config.is_loadable && sheet_names.iter().any(|s| s.trim() == config.table_name.trim()))
+            .collect();
+        
+        // Full reload: reference tables are dropped too so their rows do not pile up
+        target.drop_table(&settings.general_entries_table)?;
+        for config in sheet_configs.iter().filter(|c| !c.is_accounting) {
+            target.drop_table(&config.table_name)?;
+        }
+        target.create_tables()?;
+        
+        let mut transactions = Vec::new();
+        for config in &sheet_configs {
+            let _sheet = tracing::info_span!("sheet", sheet = %config.table_name.trim()).entered();
+            if config.is_accounting {
+                transactions.extend(input.read_accounting_sheet(&config.table_name)?);
+            } else {
+                let data = input.read_reference_sheet(&config.table_name)?;
+                let count = target.insert_reference_data(&config.table_name, &data)?;
+                logging::log_result(&format!("{} - Lines Created", config.table_name.trim()), count);
             }
         }
         
-        // Sort by date (most recent first)
-        processed.sort_by(|a, b| b.date.cmp(&a.date));
+        for (name, source) in self.config.sources.iter().filter(|(_, source)| source.enabled) {
+            transactions.extend(importers::read_source(name, source, &self.config.directories.dir_in)?);
+        }
         
-        Ok(processed)
+        let categorizer = self.categorizer_with_sheet(input.as_mut())?;
+        let splits = self.split_rules(input.as_mut())?;
+        let mut quarantine = Quarantine::default();
+        let mut processed_transactions = SheetTransformer { categorizer: categorizer.as_ref(), splits: splits.as_ref(), ..self.transformer() }
+            .transform(transactions, &mut quarantine)?;
+        quarantine.log();
+        sort_by_date(&mut processed_transactions);
+        let count = target.insert_transactions(&processed_transactions)?;
+        logging::log_result("Total Transactions Processed", count);
+        
+        if settings.create_pivot {
+            target.create_pivot_tables(
+                &settings.general_entries_table,
+                &settings.types_of_entries,
+                &settings.full_pivot_table,
+                &settings.anual_pivot_table,
+                self.type_normalizer.as_ref(),
+            )?;
+        }
+        
+        Ok(count)
     }
     
-    /// Process a single transaction with data enrichment
-    fn process_single_transaction(&self, transaction: Transaction) -> Result<Option<ProcessedTransaction>, PdwError> {
-        // Skip transactions without essential data
-        let date = match transaction.date {
-            Some(d) => d,
-            None => return Ok(None),
+    /// Configured load mode, falling back to Replace when stored entries carry no RowHash
+    fn effective_load_mode(&self) -> Result<LoadMode, PdwError> {
+        let mode = self.config.settings.load_mode;
+        let entries_table = &self.config.settings.general_entries_table;
+        let columns = self.database.table_columns(entries_table)?;
+        
+        if mode != LoadMode::Incremental || columns.is_empty() {
+            return Ok(mode);
+        }
+        
+        let hashed = columns.iter().any(|c| c.eq_ignore_ascii_case("RowHash"))
+            && self.database.count_unhashed_rows(entries_table)? == 0;
+        if !hashed {
+            tracing::warn!("{} has rows without RowHash - running a full reload instead of an incremental one", entries_table);
+            return Ok(LoadMode::Replace);
+        }
+        
+        Ok(mode)
+    }
+    
+    /// Evaluate configured alert rules and route triggered alerts
+    pub fn evaluate_alerts(&self) -> Result<usize, PdwError> {
+        let _phase = logging::log_phase_start("Evaluating alert rules");
+        
+        let engine = AlertEngine::new(&self.database, &self.config.settings.general_entries_table)
+            .with_baselines(self.config.baselines.enabled.then_some(self.config.baselines.table.as_str()));
+        let triggered = engine.evaluate(&self.config.alerts)?;
+        
+        alerts::write_alerts_table(&self.database, &self.config.settings.alerts_table, &triggered)?;
+        alerts::dispatch_alerts(&triggered, &Notifier::from_config(&self.config.notifications));
+        logging::log_result("Alerts Triggered", triggered.len());
+        
+        Ok(triggered.len())
+    }
+    
+    /// Open the input source selected by `file_types.type_in`
+    fn open_input(&self) -> Result<Box<dyn ExcelReader>, PdwError> {
+        open_input(&self.config)
+    }
+    
+    /// Worker threads for the accounting sheets, or None to read them one by one
+    fn extraction_workers(&self, sheet_configs: &[SheetConfig], consistency: Option<&ConsistencyReport>) -> Option<usize> {
+        if !self.config.settings.multithreading {
+            return None;
+        }
+        
+        let sheets = parallel_sheets(sheet_configs, consistency).count();
+        let limit = self.config.settings.parallels
+            .map(|parallels| parallels as usize)
+            .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+        
+        let workers = sheets.min(limit);
+        (workers > 1).then_some(workers)
+    }
+    
+    /// Entries per chunk of a streamed load, None when the sheets are read whole
+    fn stream_chunk_rows(&self, load_mode: LoadMode) -> Option<usize> {
+        let chunk_rows = self.config.settings.stream_chunk_rows;
+        if chunk_rows == 0 {
+            return None;
+        }
+        if load_mode == LoadMode::Incremental {
+            tracing::info!("   . .. ... Streaming off - an incremental load compares all entries with the stored ones");
+            return None;
+        }
+        
+        tracing::info!("   . .. ... Streaming accounting sheets {} entries at a time", chunk_rows);
+        Some(chunk_rows)
+    }
+    
+    /// Read, transform and insert one accounting sheet a chunk at a time, keeping no entries
+    fn stream_sheet(&self, input: &mut dyn ExcelReader, sheet_name: &str, chunk_rows: usize,
+                    streamed: &mut StreamedLoad) -> Result<ExtractedSheet, PdwError> {
+        let started = Instant::now();
+        let transformer = self.transformer();
+        let mut sheet = ExtractedSheet {
+            lines_read: 0,
+            non_data_rows: 0,
+            outside_window: 0,
+            transactions: Vec::new(),
+            variants: VariantTracker::default(),
+            quality: QualityReport::default(),
+            quarantine: Quarantine::default(),
+            elapsed: Duration::ZERO,
         };
         
-        let transaction_type = match transaction.transaction_type {
-            Some(t) => t.trim().to_string(),
-            None => return Ok(None),
+        input.read_accounting_chunks(sheet_name, chunk_rows, &mut |mut raw| {
+            sheet.non_data_rows += transformer.skip_non_data(&mut raw);
+            sheet.outside_window += transformer.skip_outside_window(&mut raw);
+            transformer.track_variants(&mut sheet.variants, &raw);
+            transformer.check_quality(&mut sheet.quality, &raw);
+            sheet.lines_read += raw.len();
+            
+            let mut transactions = transformer.transform(raw, &mut sheet.quarantine)?;
+            sort_by_date(&mut transactions);
+            streamed.periods.add(&transactions);
+            streamed.processed += transactions.len();
+            streamed.inserted += self.database.insert_transaction_chunk(&transactions, &mut streamed.hasher)?;
+            Ok(())
+        })?;
+        
+        sheet.elapsed = started.elapsed();
+        Ok(sheet)
+    }
+    
+    /// Read and transform the accounting sheets on a pool of `workers` threads, keyed by sheet name
+    fn extract_sheets_parallel(&self, sheet_configs: &[SheetConfig], consistency: Option<&ConsistencyReport>,
+                               workers: usize) -> Result<HashMap<String, ExtractedSheet>, PdwError> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(workers)
+            .thread_name(|index| format!("pdw-sheet-{}", index))
+            .build()
+            .map_err(|e| EtlError::InitializationFailed { reason: e.to_string() })?;
+        
+        let sheets: Vec<&SheetConfig> = parallel_sheets(sheet_configs, consistency).collect();
+        tracing::info!("   . .. ... Reading {} accounting sheets on {} threads", sheets.len(), workers);
+        
+        let transformer = self.transformer();
+        let config = &self.config;
+        let started = Instant::now();
+        
+        // Each worker opens its own reader on first use: workbook handles are not shared between threads
+        let extracted = pool.install(|| {
+            sheets.par_iter()
+                .map_init(
+                    || None,
+                    |input: &mut Option<Box<dyn ExcelReader>>, sheet| {
+                        let input = match input {
+                            Some(input) => input,
+                            None => input.insert(open_input(config)?),
+                        };
+                        transformer.extract_sheet(input.as_mut(), &sheet.table_name)
+                            .map(|extracted| (sheet.table_name.clone(), extracted))
+                    },
+                )
+                .collect::<Result<Vec<_>, PdwError>>()
+        })?;
+        
+        logging::log_timing("Parallel Extraction Finished in", started.elapsed());
+        Ok(extracted.into_iter().collect())
+    }
+    
+    /// Transformation state shareable with worker threads
+    fn transformer(&self) -> SheetTransformer<'_> {
+        SheetTransformer {
+            config: &self.config,
+            derived_columns: &self.derived_columns,
+            type_normalizer: self.type_normalizer.as_ref(),
+            categorizer: self.categorizer.as_ref(),
+            splits: self.splits.as_ref(),
+            non_data: self.non_data.as_ref(),
+            converter: self.converter.as_ref(),
+            window: self.window.as_ref(),
+            transforms: &self.transforms,
+        }
+    }
+    
+    /// Configured categorization rules plus those of the rules sheet, when the input has one
+    fn categorizer_with_sheet(&self, input: &mut dyn ExcelReader) -> Result<Option<Categorizer>, PdwError> {
+        let settings = &self.config.categorization;
+        let Some(mut categorizer) = Categorizer::from_config(settings, self.config.get_category_rules_path().as_deref())? else {
+            return Ok(None);
         };
         
-        if transaction_type.is_empty() {
+        if input.sheet_names().iter().any(|name| name.trim() == settings.sheet.trim()) {
+            let rows = input.read_reference_sheet(&settings.sheet)?;
+            categorizer.add_sheet_rules(&rows)?;
+        }
+        logging::log_result("Categorization Rules", categorizer.len());
+        
+        Ok(Some(categorizer))
+    }
+    
+    /// Parts of the splits sheet; None unless splits are enabled and the input has the sheet
+    fn split_rules(&self, input: &mut dyn ExcelReader) -> Result<Option<SplitRules>, PdwError> {
+        let settings = &self.config.splits;
+        if !settings.enabled || !input.sheet_names().iter().any(|name| name.trim() == settings.sheet.trim()) {
             return Ok(None);
         }
         
-        // Clean and process description
-        let description = transaction.description
-            .unwrap_or_else(|| "".to_string())
-            .trim()
-            .replace(";", "|")
-            .replace(",", "|")
-            .replace("∴", " .'. ")
-            .replace("ś", "s");
+        let rules = SplitRules::from_sheet(&input.read_reference_sheet(&settings.sheet)?, &settings.sheet)?;
+        logging::log_result("Split Entries", rules.len());
+        Ok(Some(rules))
+    }
+    
+    /// Rates of the rates file and, over them, of the rates sheet, backed by the
+    /// exchange-rate cache; None unless multi-currency is enabled
+    fn currency_converter(&self, input: &mut dyn ExcelReader) -> Result<Option<CurrencyConverter>, PdwError> {
+        let settings = &self.config.currency;
+        if !settings.enabled {
+            return Ok(None);
+        }
+        
+        let mut rates = RateCache::default();
+        if let Some(file) = &settings.rates_file {
+            let path = self.config.directories.dir_in.join(file);
+            let rows = currency::read_rates_file(&path, self.config.csv.delimiter)?;
+            rates = currency::parse_rates(&rows, &path.to_string_lossy())?;
+        }
+        if input.sheet_names().iter().any(|name| name.trim() == settings.sheet.trim()) {
+            let rows = input.read_reference_sheet(&settings.sheet)?;
+            let sheet_rates = currency::parse_rates(&rows, &format!("the {} sheet", settings.sheet))?;
+            for (code, days) in sheet_rates.rates {
+                rates.rates.entry(code).or_default().extend(days);
+            }
+        }
         
-        // Process financial amounts
-        let credit = transaction.credit.unwrap_or(0.0);
-        let debit = transaction.debit.unwrap_or(0.0);
+        Ok(Some(CurrencyConverter::new(&self.config, rates)?))
+    }
+    
+    /// Store the configured budget limits, overridden by the budgets sheet when present
+    fn load_budgets(&self, input: &mut dyn ExcelReader) -> Result<usize, PdwError> {
+        let settings = &self.config.budgets;
+        let rows = if input.sheet_names().iter().any(|name| name.trim() == settings.sheet.trim()) {
+            input.read_reference_sheet(&settings.sheet)?
+        } else {
+            Vec::new()
+        };
         
-        // Round to 2 decimal places
-        let credit = (credit * 100.0).round() / 100.0;
-        let debit = (debit * 100.0).round() / 100.0;
+        let limits = settings.limits_with_sheet(&rows)?;
+        budgets::write_budget_table(&self.database, &settings.table, &limits)
+    }
+    
+    /// Store the configured opening balances, overridden by the opening balances sheet when present
+    fn load_opening_balances(&self, input: &mut dyn ExcelReader) -> Result<usize, PdwError> {
+        let settings = &self.config.opening_balances;
+        let rows = if input.sheet_names().iter().any(|name| name.trim() == settings.sheet.trim()) {
+            input.read_reference_sheet(&settings.sheet)?
+        } else {
+            Vec::new()
+        };
         
-        // Generate temporal data
-        let day_of_week = self.get_day_of_week_portuguese(date);
-        let month = format!("{:02}", date.month());
-        let year = date.year().to_string();
-        let month_name = self.get_month_name_portuguese(date.month());
-        let year_month = format!("{}/{:02}", date.year(), date.month());
+        let balances = settings.balances_with_sheet(&rows)?;
+        opening_balances::write_opening_balance_table(&self.database, &settings.table, &balances)
+    }
+    
+    /// Store the balances of the statement balances sheet, when the input has one
+    fn load_declared_balances(&self, input: &mut dyn ExcelReader) -> Result<usize, PdwError> {
+        let settings = &self.config.reconciliation;
+        let rows = if input.sheet_names().iter().any(|name| name.trim() == settings.sheet.trim()) {
+            input.read_reference_sheet(&settings.sheet)?
+        } else {
+            tracing::warn!("Reconciliation enabled but the input has no {} sheet", settings.sheet);
+            Vec::new()
+        };
         
-        Ok(Some(ProcessedTransaction {
-            date,
-            day_of_week,
-            transaction_type,
-            description,
-            credit,
-            debit,
-            month,
-            year,
-            month_name,
-            year_month,
-            origin: transaction.origin,
-        }))
-    }
-    
-    /// Get Portuguese day of week name
-    fn get_day_of_week_portuguese(&self, date: NaiveDate) -> String {
-        match date.weekday() {
-            Weekday::Mon => "Segunda-feira",
-            Weekday::Tue => "Terça-feira", 
-            Weekday::Wed => "Quarta-feira",
-            Weekday::Thu => "Quinta-feira",
-            Weekday::Fri => "Sexta-feira",
-            Weekday::Sat => "Sábado",
-            Weekday::Sun => "Domingo",
-        }.to_string()
-    }
-    
-    /// Get Portuguese month name
-    fn get_month_name_portuguese(&self, month: u32) -> String {
-        match month {
-            1 => "01-Janeiro",
-            2 => "02-Fevereiro",
-            3 => "03-Março",
-            4 => "04-Abril",
-            5 => "05-Maio",
-            6 => "06-Junho",
-            7 => "07-Julho",
-            8 => "08-Agosto",
-            9 => "09-Setembro",
-            10 => "10-Outubro",
-            11 => "11-Novembro",
-            12 => "12-Dezembro",
-            _ => "00-Inválido",
-        }.to_string()
+        let balances = settings.balances_from_sheet(&rows)?;
+        reconciliation::write_declared_balances(&self.database, &settings.balances_table, &balances)
+    }
+    
+    /// Persist the TIPO spellings merged by normalization
+    fn write_merged_types(&self, tracker: &VariantTracker) -> Result<usize, PdwError> {
+        let merged = tracker.merged();
+        type_normalization::write_merge_report(
+            &self.database,
+            &self.config.type_normalization.report_table,
+            &merged,
+        )?;
+        logging::log_result("TIPO Groups Merged", merged.len());
+        
+        Ok(merged.len())
+    }
+    
+    /// Transform raw transactions into processed format
+    fn transform_transactions(&self, transactions: Vec<Transaction>,
+                              quarantine: &mut Quarantine) -> Result<Vec<ProcessedTransaction>, PdwError> {
+        let mut processed = self.transformer().transform(transactions, quarantine)?;
+        sort_by_date(&mut processed);
+        Ok(processed)
+    }
+    
+    /// Restrict the next pivot refresh to the given periods
+    pub fn set_touched_periods(&mut self, periods: PeriodSet) {
+        self.touched_periods = Some(periods);
+    }
+    
+    /// Compare the next reports with a previous report workbook or database
+    pub fn set_diff_baseline(&mut self, path: PathBuf) {
+        self.diff_baseline = Some(path);
+    }
+    
+    /// Restrict the next load to the entries of a range of months
+    pub fn set_window(&mut self, window: DateWindow) {
+        self.window = Some(window);
+    }
+    
+    /// Periods changed by the last load, if known
+    pub fn touched_periods(&self) -> Option<&PeriodSet> {
+        self.touched_periods.as_ref()
+    }
+    
+    /// Columns the load adds to the entries table: derived, cycle, category, currency, tags, RowHash and cents
+    fn add_derived_schema(&self) -> Result<(), PdwError> {
+        let mut derived_schema: Vec<(String, &str)> = self.derived_columns.iter()
+            .map(|c| (c.name.clone(), c.expression.kind().sql_type()))
+            .collect();
+        if self.config.statement_cycles.is_enabled() {
+            derived_schema.push((self.config.statement_cycles.column.clone(), "TEXT"));
+        }
+        if self.config.statement_cycles.has_due_dates() {
+            derived_schema.push((self.config.statement_cycles.due_column.clone(), "TEXT"));
+        }
+        if self.categorizer.is_some() {
+            derived_schema.push((self.config.categorization.category_column.clone(), "TEXT"));
+            derived_schema.push((self.config.categorization.tag_column.clone(), "TEXT"));
+        }
+        if self.config.currency.enabled {
+            derived_schema.extend(currency::schema());
+        }
+        if self.config.tags.enabled {
+            derived_schema.push((tags::TAGS_COLUMN.to_string(), "TEXT"));
+        }
+        derived_schema.push(("RowHash".to_string(), "TEXT"));
+        derived_schema.extend(money::cents_schema());
+        self.database.add_derived_columns(&self.config.settings.general_entries_table, &derived_schema)
+    }
+    
+    /// Tables of an empty warehouse, up to the summaries and pivots the YAML queries read
+    /// (the dry run checks the queries against it without loading anything)
+    pub fn create_empty_schema(&self) -> Result<(), PdwError> {
+        self.database.create_tables()?;
+        self.add_derived_schema()?;
+        self.create_summary_tables()?;
+        if self.config.settings.create_pivot {
+            self.create_pivot_tables()?;
+        }
+        Ok(())
     }
     
     /// Create pivot tables for historical analysis
     pub fn create_pivot_tables(&self) -> Result<(), PdwError> {
-        logging::log_phase_start("Creating pivot Tables");
+        let _phase = logging::log_phase_start("Creating pivot Tables");
+        
+        // Period-scoped refresh when the load reported what it touched (nothing when empty)
+        if let Some(periods) = self.touched_periods.as_ref() {
+            let refreshed = self.database.refresh_pivot_periods(
+                &self.config.settings.general_entries_table,
+                &self.config.settings.types_of_entries,
+                &self.config.settings.full_pivot_table,
+                &self.config.settings.anual_pivot_table,
+                self.type_normalizer.as_ref(),
+                periods,
+            )?;
+            if refreshed {
+                return Ok(());
+            }
+        }
         
         self.database.create_pivot_tables(
             &self.config.settings.general_entries_table,
             &self.config.settings.types_of_entries,
             &self.config.settings.full_pivot_table,
             &self.config.settings.anual_pivot_table,
+            self.type_normalizer.as_ref(),
         )?;
         
         Ok(())
@@ -218,16 +1009,64 @@ impl EtlPipeline {
     
     /// Generate reports
     pub fn generate_reports(&self) -> Result<(), PdwError> {
-        logging::log_phase_start("Starting report generation");
+        let _phase = logging::log_phase_start("Starting report generation");
+        self.check_disk_space("Report generation", diskspace::report_requirements)?;
+        let started = SystemTime::now() - Duration::from_secs(1);
         
-        // Create daily progress tracking
-        self.create_daily_progress()?;
+        self.create_summary_tables()?;
         
-        // Create monthly summaries
-        self.create_monthly_summaries()?;
+        // Compare savings with the CDI/SELIC benchmark
+        if self.config.benchmark.enabled {
+            self.create_benchmark_comparison()?;
+        }
         
-        // Create installment summaries
-        self.create_installment_summaries()?;
+        // Per-invoice totals of credit card origins
+        if self.config.statement_cycles.is_enabled() {
+            self.create_statement_summaries()?;
+        }
+        
+        // Summarize bank fees, interest and IOF
+        if self.config.fees.enabled {
+            self.create_fee_summary()?;
+        }
+        
+        // Probable duplicates across origins
+        if self.config.duplicates.enabled {
+            self.create_duplicate_report()?;
+        }
+        
+        // Recurring debits and open installments of the coming months
+        if self.config.forecast.enabled {
+            self.create_forecast()?;
+        }
+        
+        // Trailing months cash-flow statement by category group
+        if self.config.cash_flow.enabled {
+            self.create_cash_flow()?;
+        }
+        
+        // Expense groups against each month's income
+        if self.config.income_share.enabled {
+            self.create_income_share()?;
+        }
+        
+        // Budgeted vs actual debits per month and TIPO
+        if self.config.budgets.enabled {
+            self.create_budget_report()?;
+        }
+        
+        // Cells changed since the previous report
+        self.create_change_report()?;
+        
+        // Running balances against the statement balances
+        if self.config.reconciliation.enabled {
+            self.create_reconciliation()?;
+        }
+        
+        // Accounting invariants over the summaries and pivots
+        if self.config.sanity.enabled {
+            self.create_sanity_report()?;
+        }
         
         // Generate Excel reports
         self.generate_excel_reports()?;
@@ -235,19 +1074,418 @@ impl EtlPipeline {
         // Export general entries
         self.export_general_entries()?;
         
+        // Render the e-ink statement card
+        if self.config.statement_card.enabled {
+            self.generate_statement_card()?;
+        }
+        
+        // Period-close PDF statements per origin
+        if self.config.pdf_statements.enabled {
+            self.generate_pdf_statements()?;
+        }
+        
+        // One dated zip of everything this phase wrote
+        if self.config.export.compression.archive {
+            self.archive_report_outputs(started)?;
+        }
+        
+        // Upload the exports; an interrupted upload resumes on the next run
+        if self.config.publish.enabled {
+            self.publish_exports()?;
+        }
+        
         Ok(())
     }
     
-    /// Create daily progress tracking
+    /// Zip the files written to `dir_out` since `since` into `<out_rpt_file>_YYYY-MM-DD.zip`
+    pub fn archive_report_outputs(&self, since: SystemTime) -> Result<Option<PathBuf>, PdwError> {
+        let dir_out = &self.config.directories.dir_out;
+        let files = compression::written_since(dir_out, since)?;
+        if files.is_empty() {
+            return Ok(None);
+        }
+        
+        let stem = Path::new(&self.config.file_types.out_rpt_file).file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let archive = dir_out.join(format!("{}_{}.zip", stem, deterministic::now().format("%Y-%m-%d")));
+        let count = compression::archive_files(dir_out, &files, &archive, &self.config.export.compression)?;
+        logging::log_result(&format!("{} - Files Archived", archive.display()), count);
+        Ok(Some(archive))
+    }
+    
+    /// Build the savings vs CDI/SELIC table; a missing rate series only skips it
+    pub fn create_benchmark_comparison(&self) -> Result<usize, PdwError> {
+        let benchmark = &self.config.benchmark;
+        let excluded = self.config.analytics.exclusions.types(Measure::Savings, &self.config.category_groups);
+        let savings = analytics::monthly_savings(&self.database, &self.config.settings.general_entries_table, &excluded)?;
+        
+        let (Some((first, _)), Some((last, _))) = (savings.first(), savings.last()) else {
+            return Ok(0);
+        };
+        
+        let rates = match analytics::load_series(benchmark, first, last) {
+            Ok(rates) => rates,
+            Err(e) => {
+                tracing::warn!("{} benchmark skipped: {}", benchmark.series.label(), e);
+                return Ok(0);
+            }
+        };
+        
+        let rows = analytics::savings_vs_benchmark(&savings, &rates);
+        let count = analytics::write_benchmark_table(&self.database, &benchmark.table, benchmark.series, &rows)?;
+        logging::log_result(&format!("{} - Lines Created", benchmark.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Build the table of expense group shares of income
+    pub fn create_income_share(&self) -> Result<usize, PdwError> {
+        let settings = &self.config.income_share;
+        let rows = analytics::income_shares(
+            &self.database,
+            &self.config.settings.general_entries_table,
+            &self.config.category_groups,
+            &self.config.analytics.exclusions.types(Measure::IncomeShare, &self.config.category_groups),
+        )?;
+        let count = analytics::write_income_share_table(&self.database, &settings.table, &rows)?;
+        logging::log_result(&format!("{} - Lines Created", settings.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Build the per-invoice summary of origins billed by statement cycle
+    pub fn create_statement_summaries(&self) -> Result<usize, PdwError> {
+        let cycles = &self.config.statement_cycles;
+        let entries_table = &self.config.settings.general_entries_table;
+        
+        // Tables loaded before the cycles were configured have no period column
+        let columns = self.database.table_columns(entries_table)?;
+        if !columns.iter().any(|c| c.eq_ignore_ascii_case(&cycles.column)) {
+            tracing::warn!("{} skipped: {} has no {} column - reload the data", cycles.table, entries_table, cycles.column);
+            return Ok(0);
+        }
+        
+        // Due date of each invoice, when recorded
+        let due_column = if cycles.has_due_dates() && columns.iter().any(|c| c.eq_ignore_ascii_case(&cycles.due_column)) {
+            format!("MAX([{0}]) as [{0}],", cycles.due_column)
+        } else {
+            String::new()
+        };
+        
+        let money = self.database.money_mode();
+        if money == MoneyMode::Decimal {
+            self.database.add_money_columns(entries_table)?;
+        }
+        let (credit, debit) = (money.sum_sql("Credito", None), money.sum_sql("Debito", None));
+        
+        self.database.drop_table(&cycles.table)?;
+        let query = format!(
+            "CREATE TABLE {table} AS
+             SELECT Origem, [{column}], MIN(Data) as Inicio, MAX(Data) as Fim, {due_column}
+                    COUNT(*) as Lancamentos,
+                    ROUND({credit}, 2) as CREDITO,
+                    ROUND({debit}, 2) as DEBITO,
+                    ROUND({debit} - {credit}, 2) as Total
+             FROM {entries}
+             WHERE [{column}] IS NOT NULL
+             GROUP BY Origem, [{column}]
+             ORDER BY {origin_order}, [{column}]",
+            table = cycles.table,
+            column = cycles.column,
+            entries = entries_table,
+            origin_order = self.database.order_by("Origem"),
+        );
+        
+        self.database.connection().execute(&query, [])
+            .map_err(|e| EtlError::TransformationFailed {
+                stage: "statement_summaries".to_string(),
+                reason: e.to_string(),
+            })?;
+        
+        let count = self.database.execute_query(&format!("SELECT COUNT(*) FROM {}", cycles.table))?
+            .first()
+            .and_then(|r| r.first())
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0) as usize;
+        logging::log_result(&format!("{} - Lines Created", cycles.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Build the fees/interest/IOF summary table
+    pub fn create_fee_summary(&self) -> Result<usize, PdwError> {
+        let classifier = FeeClassifier::from_config(&self.config.fees);
+        let rows = fees::summarize_fees(&self.database, &self.config.settings.general_entries_table, &classifier)?;
+        let count = fees::write_fee_table(&self.database, &self.config.fees.table, &rows)?;
+        logging::log_result(&format!("{} - Lines Created", self.config.fees.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Build the table of probable duplicate entries
+    pub fn create_duplicate_report(&self) -> Result<usize, PdwError> {
+        let settings = &self.config.duplicates;
+        let entries = duplicates::read_entries(&self.database, &self.config.settings.general_entries_table)?;
+        let pairs = duplicates::find_duplicates(&entries, settings);
+        let count = duplicates::write_duplicates_table(&self.database, &settings.table, &pairs)?;
+        logging::log_result(&format!("{} - Lines Created", settings.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Reconcile the running balance of each origin with its statements
+    pub fn create_reconciliation(&self) -> Result<usize, PdwError> {
+        let settings = &self.config.reconciliation;
+        let entries_table = &self.config.settings.general_entries_table;
+        let openings = OpeningBalances::load(&self.database, entries_table, &self.config.opening_balances)?;
+        let reconciliations = reconciliation::reconcile(&self.database, entries_table, settings, &openings)?;
+        for divergent in reconciliations.iter().filter(|r| !r.is_reconciled(settings.tolerance)) {
+            tracing::warn!(
+                "{} on {}: statement balance {:.2}, entries give {:.2} ({:+.2}, {:+.2} since the previous statement)",
+                divergent.origin, divergent.date.format("%d/%m/%Y"), divergent.declared, divergent.computed,
+                divergent.difference, divergent.period_difference
+            );
+        }
+        let count = reconciliation::write_reconciliation_table(&self.database, &settings.table, &reconciliations, settings.tolerance)?;
+        logging::log_result(&format!("{} - Lines Created", settings.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Check the accounting invariants and store the violations
+    pub fn create_sanity_report(&self) -> Result<usize, PdwError> {
+        let settings = &self.config.sanity;
+        let tables = SanityTables {
+            entries: &self.config.settings.general_entries_table,
+            monthly_summaries: &self.config.settings.monthly_summaries,
+            full_pivot: &self.config.settings.full_pivot_table,
+        };
+        let violations = sanity::check(&self.database, tables, settings, &self.config.category_groups)?;
+        sanity::log_violations(&violations);
+        let count = sanity::write_sanity_table(&self.database, &settings.table, &violations)?;
+        logging::log_result(&format!("{} - Lines Created", settings.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Build the forecast table of recurring debits and installments after the last entry
+    pub fn create_forecast(&self) -> Result<usize, PdwError> {
+        let settings = &self.config.forecast;
+        let entries_table = &self.config.settings.general_entries_table;
+        let last_entry = self.database.execute_query(&format!("SELECT MAX(date(Data)) FROM {}", entries_table))?;
+        let Some(as_of) = last_entry.first()
+            .and_then(|row| row.first())
+            .and_then(|value| value.as_str())
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()) else {
+            return Ok(0);
+        };
+        
+        let debits = forecast::read_debits(&self.database, entries_table)?;
+        let recurrences = forecast::detect_recurrences(&debits, settings);
+        let installments = forecast::read_installments(&self.database, &self.config.settings.splt_paymnt_tab)?;
+        let lines = forecast::project(&recurrences, &installments, as_of, settings.months);
+        let count = forecast::write_forecast_table(&self.database, &settings.table, &lines)?;
+        logging::log_result(&format!("{} - Lines Created", settings.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Build the cash-flow statement table of the trailing months
+    pub fn create_cash_flow(&self) -> Result<usize, PdwError> {
+        let settings = &self.config.cash_flow;
+        let (months, rows) = cash_flow::build_statement(
+            &self.database,
+            &self.config.settings.general_entries_table,
+            &self.config.category_groups,
+            settings,
+        )?;
+        let count = cash_flow::write_cash_flow_table(&self.database, &settings.table, &months, &rows)?;
+        logging::log_result(&format!("{} - Lines Created", settings.table), count);
+        
+        Ok(count)
+    }
+    
+    /// Refresh the seasonal baselines from the general entries
+    pub fn create_baselines(&self) -> Result<usize, PdwError> {
+        let _phase = logging::log_phase_start("Computing seasonal baselines");
+        let count = baselines::write_baselines(&self.database, &self.config.settings.general_entries_table, &self.config.baselines)?;
+        logging::log_result(&format!("{} - Lines Created", self.config.baselines.table), count);
+        Ok(count)
+    }
+    
+    /// Build the budget vs actual table; a warehouse loaded without budgets only skips it
+    pub fn create_budget_report(&self) -> Result<usize, PdwError> {
+        let settings = &self.config.budgets;
+        if self.database.table_columns(&settings.table)?.is_empty() {
+            tracing::warn!("{} skipped: {} is missing - reload the data", settings.report_table, settings.table);
+            return Ok(0);
+        }
+        
+        let baselines = self.config.baselines.enabled.then_some(self.config.baselines.table.as_str());
+        let excluded = self.config.analytics.exclusions.types(Measure::Budgets, &self.config.category_groups);
+        let count = budgets::write_budget_report(&self.database, &self.config.settings.general_entries_table, settings, baselines, &excluded)?;
+        logging::log_result(&format!("{} - Lines Created", settings.report_table), count);
+        
+        Ok(count)
+    }
+    
+    /// List the report cells changed since the diff baseline; without one, a
+    /// change table left by an earlier run is dropped
+    pub fn create_change_report(&self) -> Result<usize, PdwError> {
+        let Some(baseline) = &self.diff_baseline else {
+            self.database.drop_table(report_diff::CHANGES_TABLE)?;
+            return Ok(0);
+        };
+        
+        let previous = report_diff::baseline_snapshot(baseline, &self.database, &self.config)?;
+        let current = report_diff::query_snapshot(&self.database, &self.config)?;
+        let compared = current.keys().filter(|sheet| previous.contains_key(*sheet)).count();
+        if compared == 0 {
+            tracing::warn!("No report sheet in common with {} - nothing compared", baseline.display());
+        }
+        
+        let changes = report_diff::diff(&previous, &current);
+        let count = report_diff::write_changes_table(&self.database, &changes)?;
+        logging::log_result(&format!("{} - Cells Changed in {} Sheets", report_diff::CHANGES_TABLE, compared), count);
+        
+        Ok(count)
+    }
+    
+    /// Render the statement card PNG into the output directory
+    pub fn generate_statement_card(&self) -> Result<Option<PathBuf>, PdwError> {
+        let card_config = &self.config.statement_card;
+        let entries_table = &self.config.settings.general_entries_table;
+        
+        let openings = OpeningBalances::load(&self.database, entries_table, &self.config.opening_balances)?;
+        let card = match StatementCard::from_database(&self.database, entries_table, card_config, &openings)? {
+            Some(card) => card,
+            None => {
+                tracing::warn!("Statement card skipped: {} is empty", entries_table);
+                return Ok(None);
+            }
+        };
+        
+        let path = self.config.directories.dir_out.join(&card_config.file);
+        card.render(card_config.width, card_config.height).write_png(&path)?;
+        tracing::info!("Statement card written to {}", path.display());
+        
+        Ok(Some(path))
+    }
+    
+    /// Write the PDF statements of the configured month, or of the latest closed months
+    pub fn generate_pdf_statements(&self) -> Result<Vec<PathBuf>, PdwError> {
+        let pdf_config = &self.config.pdf_statements;
+        let entries_table = &self.config.settings.general_entries_table;
+        
+        let months = match &pdf_config.month {
+            Some(month) => vec![month.clone()],
+            None => {
+                let closed = pdf::closed_months(&self.database, entries_table)?;
+                let skip = closed.len().saturating_sub(pdf_config.months);
+                closed.into_iter().skip(skip).collect()
+            }
+        };
+        
+        if self.database.money_mode() == MoneyMode::Decimal {
+            self.database.add_money_columns(entries_table)?;
+        }
+        
+        let openings = OpeningBalances::load(&self.database, entries_table, &self.config.opening_balances)?;
+        let directory = self.config.directories.dir_out.join(&pdf_config.directory);
+        let mut files = Vec::new();
+        for month in &months {
+            let statements = pdf::build_statements(&self.database, entries_table, month, &openings)?;
+            files.extend(pdf::write_statements(&statements, &directory, &self.config.branding)?);
+        }
+        logging::log_result("PDF Statements Written", files.len());
+        
+        Ok(files)
+    }
+    
+    /// Upload the exports of dir_out to the [publish] target; upload failures are only logged
+    pub fn publish_exports(&self) -> Result<usize, PdwError> {
+        let files = publish::export_files(&self.config.directories.dir_out, &self.config.publish)?;
+        match publish::publish(&self.config.publish, &files, &self.config.get_publish_state_path()) {
+            Ok(summary) => {
+                logging::log_result("Files Published", summary.uploaded);
+                Ok(summary.uploaded)
+            }
+            Err(e) => {
+                tracing::warn!("Publishing failed, to be resumed by the next run or `pdw publish`: {}", e);
+                Ok(0)
+            }
+        }
+    }
+    
+    /// Rebuild the pivots, summaries and tables derived from the entries after entries were
+    /// purged or restored; derived tables of disabled features are dropped instead
+    pub fn rebuild_derived_tables(&self) -> Result<(), PdwError> {
+        let settings = &self.config.settings;
+        // Summary tables are only created when missing
+        for table in [
+            settings.daily_progress.clone(),
+            settings.monthly_summaries.clone(),
+            format!("{}_ANUAL", settings.monthly_summaries),
+            format!("{}_FULL", settings.monthly_summaries),
+        ] {
+            self.database.drop_table(&table)?;
+        }
+        if settings.create_pivot {
+            self.create_pivot_tables()?;
+        }
+        self.create_summary_tables()?;
+        
+        if !self.config.tags.enabled {
+            for table in [tags::TAGS_TYPE_TABLE, tags::TAGS_MONTHLY_TABLE, tags::TRANSACTION_TAGS_TABLE, tags::TAGS_TABLE] {
+                self.database.drop_table(table)?;
+            }
+        }
+        if self.config.fees.enabled {
+            self.create_fee_summary()?;
+        } else {
+            self.database.drop_table(&self.config.fees.table)?;
+        }
+        if self.config.duplicates.enabled {
+            self.create_duplicate_report()?;
+        } else {
+            self.database.drop_table(&self.config.duplicates.table)?;
+        }
+        if self.config.budgets.enabled {
+            self.create_budget_report()?;
+        } else {
+            self.database.drop_table(&self.config.budgets.report_table)?;
+        }
+        Ok(())
+    }
+    
+    /// Create the summary tables the Python PDW also produces
+    pub fn create_summary_tables(&self) -> Result<(), PdwError> {
+        // Create daily progress tracking
+        self.create_daily_progress()?;
+        
+        // Create monthly summaries
+        self.create_monthly_summaries()?;
+        
+        // Create installment summaries
+        self.create_installment_summaries()?;
+        
+        // Normalize the entries' tags and total them per month and TIPO
+        if self.config.tags.enabled {
+            let count = tags::write_tables(&self.database, &self.config.settings.general_entries_table)?;
+            logging::log_result(&format!("{} - Tags Found", tags::TAGS_TABLE), count);
+        }
+        Ok(())
+    }
+    
+    /// Create daily progress tracking
     fn create_daily_progress(&self) -> Result<(), PdwError> {
         let query = format!(
             "CREATE TABLE IF NOT EXISTS {} AS
              SELECT Data, COUNT(*) as Contagem,
                     SUM(COUNT(*)) OVER (ORDER BY Data) as 'Contagem Acumulada'
-             FROM {} 
-             GROUP BY Data 
+             FROM {}
+             GROUP BY Data
              ORDER BY Data DESC",
-            self.config.settings.dayly_progress,
+            self.config.settings.daily_progress,
             self.config.settings.general_entries_table
         );
         
@@ -262,20 +1500,28 @@ impl EtlPipeline {
     
     /// Create monthly summaries
     fn create_monthly_summaries(&self) -> Result<(), PdwError> {
-        let base_table = &self.config.settings.monthly_summaties;
+        let base_table = &self.config.settings.monthly_summaries;
+        let money = self.database.money_mode();
+        if money == MoneyMode::Decimal {
+            self.database.add_money_columns(&self.config.settings.general_entries_table)?;
+        }
+        let (credit, debit) = (money.sum_sql("Credito", None), money.sum_sql("Debito", None));
         
         // Monthly summaries
         let monthly_query = format!(
             "CREATE TABLE IF NOT EXISTS {} AS
-             SELECT AnoMes, Origem, 
-                    SUM(Credito) as CREDITO,
-                    SUM(Debito) as DEBITO,
-                    (SUM(Credito) - SUM(Debito)) as Posição
-             FROM {} 
-             GROUP BY AnoMes, Origem 
-             ORDER BY Origem, AnoMes",
+             SELECT AnoMes, Origem,
+                    {credit} as CREDITO,
+                    {debit} as DEBITO,
+                    ({credit} - {debit}) as Posição
+             FROM {}
+             GROUP BY AnoMes, Origem
+             ORDER BY {}, AnoMes",
             base_table,
-            self.config.settings.general_entries_table
+            self.config.settings.general_entries_table,
+            self.database.order_by("Origem"),
+            credit = credit,
+            debit = debit,
         );
         
         self.database.connection().execute(&monthly_query, [])
@@ -288,14 +1534,17 @@ impl EtlPipeline {
         let annual_query = format!(
             "CREATE TABLE IF NOT EXISTS {}_ANUAL AS
              SELECT Ano, Origem,
-                    SUM(Credito) as CREDITO,
-                    SUM(Debito) as DEBITO,
-                    (SUM(Credito) - SUM(Debito)) as Posição
-             FROM {} 
-             GROUP BY Ano, Origem 
-             ORDER BY Origem, Ano",
+                    {credit} as CREDITO,
+                    {debit} as DEBITO,
+                    ({credit} - {debit}) as Posição
+             FROM {}
+             GROUP BY Ano, Origem
+             ORDER BY {}, Ano",
             base_table,
-            self.config.settings.general_entries_table
+            self.config.settings.general_entries_table,
+            self.database.order_by("Origem"),
+            credit = credit,
+            debit = debit,
         );
         
         self.database.connection().execute(&annual_query, [])
@@ -308,14 +1557,17 @@ impl EtlPipeline {
         let full_query = format!(
             "CREATE TABLE IF NOT EXISTS {}_FULL AS
              SELECT Origem,
-                    SUM(Credito) as CREDITO,
-                    SUM(Debito) as DEBITO,
-                    (SUM(Credito) - SUM(Debito)) as Posição
-             FROM {} 
-             GROUP BY Origem 
-             ORDER BY Origem",
+                    {credit} as CREDITO,
+                    {debit} as DEBITO,
+                    ({credit} - {debit}) as Posição
+             FROM {}
+             GROUP BY Origem
+             ORDER BY {}",
             base_table,
-            self.config.settings.general_entries_table
+            self.config.settings.general_entries_table,
+            self.database.order_by("Origem"),
+            credit = credit,
+            debit = debit,
         );
         
         self.database.connection().execute(&full_query, [])
@@ -355,18 +1607,312 @@ impl EtlPipeline {
     /// Generate Excel reports (placeholder - will be implemented in reporting module)
     fn generate_excel_reports(&self) -> Result<(), PdwError> {
         // This will be implemented in the reporting module
-        log::info!("Excel report generation will be implemented in reporting module");
+        tracing::info!("Excel report generation will be implemented in reporting module");
         Ok(())
     }
     
     /// Export general entries (placeholder - will be implemented in reporting module)
     fn export_general_entries(&self) -> Result<(), PdwError> {
         // This will be implemented in the reporting module
-        log::info!("General entries export will be implemented in reporting module");
+        tracing::info!("General entries export will be implemented in reporting module");
         Ok(())
     }
 }
 
+impl SheetTransformer<'_> {
+    /// Read one accounting sheet and transform its entries
+    fn extract_sheet(&self, input: &mut dyn ExcelReader, sheet_name: &str) -> Result<ExtractedSheet, PdwError> {
+        // Runs on a worker thread, outside the span of the sheet loop
+        let _sheet = tracing::info_span!("sheet", sheet = sheet_name.trim()).entered();
+        let started = Instant::now();
+        let mut raw = input.read_accounting_sheet(sheet_name)?;
+        let non_data_rows = self.skip_non_data(&mut raw);
+        let outside_window = self.skip_outside_window(&mut raw);
+        
+        let mut variants = VariantTracker::default();
+        self.track_variants(&mut variants, &raw);
+        let mut quality = QualityReport::default();
+        self.check_quality(&mut quality, &raw);
+        
+        let lines_read = raw.len();
+        let mut quarantine = Quarantine::default();
+        let transactions = self.transform(raw, &mut quarantine)?;
+        Ok(ExtractedSheet {
+            lines_read, non_data_rows, outside_window, transactions, variants, quality, quarantine,
+            elapsed: started.elapsed(),
+        })
+    }
+    
+    /// Drop subtotal and section-header rows before they count as entries; returns how many
+    fn skip_non_data(&self, transactions: &mut Vec<Transaction>) -> usize {
+        self.non_data.map_or(0, |filter| filter.retain_data(transactions))
+    }
+    
+    /// Drop the rows dated outside the load window, if any; returns how many
+    fn skip_outside_window(&self, transactions: &mut Vec<Transaction>) -> usize {
+        self.window.map_or(0, |window| window.retain(transactions))
+    }
+    
+    /// Check entries as read against the row-level data quality rules
+    fn check_quality(&self, report: &mut QualityReport, transactions: &[Transaction]) {
+        let quality = &self.config.quality;
+        if !quality.enabled {
+            return;
+        }
+        
+        let today = chrono::Local::now().date_naive();
+        for transaction in transactions {
+            report.check_entry(quality, transaction, self.is_typed(transaction), today);
+        }
+    }
+    
+    /// Whether an entry ends up with a TIPO; categorization rules may still give it one
+    fn is_typed(&self, transaction: &Transaction) -> bool {
+        transaction.transaction_type.as_deref().is_some_and(|t| !t.trim().is_empty())
+            || self.categorizer.is_some_and(|categorizer| categorizer
+                .categorize(transaction.description.as_deref().unwrap_or_default())
+                .is_some_and(|rule| rule.tipo.as_deref().is_some_and(|t| !t.trim().is_empty())))
+    }
+    
+    /// Why an entry as read cannot be loaded, if it cannot
+    fn rejection(&self, transaction: &Transaction) -> Option<RejectReason> {
+        if transaction.date.is_none() {
+            Some(RejectReason::MissingDate)
+        } else if !self.is_typed(transaction) {
+            Some(RejectReason::MissingTipo)
+        } else if transaction.invalid_amount.is_some() {
+            Some(RejectReason::UnparsableAmount)
+        } else {
+            None
+        }
+    }
+    
+    /// Record the original and normalized TIPO of each dated entry
+    fn track_variants(&self, tracker: &mut VariantTracker, transactions: &[Transaction]) {
+        let Some(normalizer) = self.type_normalizer else {
+            return;
+        };
+        
+        for transaction in transactions.iter().filter(|t| t.date.is_some()) {
+            if let Some(raw) = transaction.transaction_type.as_deref().map(str::trim) {
+                if !raw.is_empty() {
+                    tracker.record(raw, &normalizer.normalize(raw));
+                }
+            }
+        }
+    }
+    
+    /// Transform raw transactions, keeping their order; the ones that cannot be loaded are quarantined
+    fn transform(&self, transactions: Vec<Transaction>, quarantine: &mut Quarantine) -> Result<Vec<ProcessedTransaction>, PdwError> {
+        let mut processed = Vec::new();
+        
+        for transaction in transactions {
+            if let Some(reason) = self.rejection(&transaction) {
+                quarantine.reject(transaction, reason);
+            } else if let Some(parts) = self.splits.and_then(|splits| splits.split(&transaction)) {
+                for part in parts {
+                    processed.extend(self.process_typed(part, true)?);
+                }
+            } else if let Some(processed_transaction) = self.process(transaction)? {
+                processed.push(processed_transaction);
+            }
+        }
+        
+        Ok(processed)
+    }
+    
+    /// Set DIA_SEMANA, Mes, Ano, MES_EXTENSO and AnoMes from the entry's date and
+    /// origin; returns its card statement closing and due dates
+    fn fill_calendar(&self, processed: &mut ProcessedTransaction) -> (Option<NaiveDate>, Option<NaiveDate>) {
+        // Credit card entries belong to the invoice closing on or after their date
+        let cycles = &self.config.statement_cycles;
+        let date = processed.date;
+        let statement_date = cycles.statement_date(&processed.origin, date);
+        let due_date = cycles.due_date(&processed.origin, date);
+        let period_date = match cycles.effective_basis() {
+            DateBasis::Purchase => None,
+            DateBasis::Statement => statement_date,
+            DateBasis::Due => due_date,
+        }.unwrap_or(date);
+        
+        processed.day_of_week = self.config.locale.day_name(date);
+        processed.month = format!("{:02}", period_date.month());
+        processed.year = period_date.year().to_string();
+        processed.month_name = self.config.locale.month_name(period_date.month());
+        processed.year_month = format!("{}/{:02}", period_date.year(), period_date.month());
+        
+        (statement_date, due_date)
+    }
+    
+    /// Process a single transaction with data enrichment
+    fn process(&self, transaction: Transaction) -> Result<Option<ProcessedTransaction>, PdwError> {
+        self.process_typed(transaction, false)
+    }
+    
+    /// Process a transaction; with `keep_type` (a split part) rules only fill a missing TIPO
+    fn process_typed(&self, transaction: Transaction, keep_type: bool) -> Result<Option<ProcessedTransaction>, PdwError> {
+        // Skip transactions without essential data
+        let date = match transaction.date {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        if transaction.invalid_amount.is_some() {
+            return Ok(None);
+        }
+        
+        // Categorization rules fill (or override) the TIPO from the description
+        let rule = self.categorizer
+            .and_then(|categorizer| categorizer.categorize(transaction.description.as_deref().unwrap_or_default()));
+        let transaction_type = transaction.transaction_type
+            .map(|t| t.trim().to_string())
+            .unwrap_or_default();
+        let transaction_type = match self.categorizer {
+            Some(_) if keep_type && !transaction_type.is_empty() => transaction_type,
+            Some(categorizer) => categorizer.apply_type(transaction_type, rule),
+            None => transaction_type,
+        };
+        
+        if transaction_type.is_empty() {
+            return Ok(None);
+        }
+        
+        let transaction_type = match &self.type_normalizer {
+            Some(normalizer) => normalizer.normalize(&transaction_type),
+            None => transaction_type,
+        };
+        
+        // Clean and process description
+        let description = transaction.description
+            .unwrap_or_else(|| "".to_string())
+            .trim()
+            .replace(";", "|")
+            .replace(",", "|")
+            .replace("∴", " .'. ")
+            .replace("ś", "s");
+        
+        // Round amounts to cents
+        let money = self.config.settings.money;
+        let mut credit = money.round(transaction.credit.unwrap_or_default());
+        let mut debit = money.round(transaction.debit.unwrap_or_default());
+        
+        // Amounts in other currencies are converted into the base currency, keeping the originals
+        let mut currency = None;
+        let mut original = None;
+        if let Some(converter) = self.converter {
+            let code = converter.currency_of(transaction.currency.as_deref(), &transaction.origin);
+            let (converted_credit, rate) = converter.convert(credit, &code, date, &transaction.origin)?;
+            let (converted_debit, _) = converter.convert(debit, &code, date, &transaction.origin)?;
+            if !converter.is_base(&code) {
+                currency = Some(code.clone());
+            }
+            original = Some((code, credit, debit, rate));
+            credit = money.round(converted_credit);
+            debit = money.round(converted_debit);
+        }
+        
+        let mut processed = ProcessedTransaction {
+            date,
+            day_of_week: String::new(),
+            transaction_type,
+            description,
+            credit,
+            debit,
+            month: String::new(),
+            year: String::new(),
+            month_name: String::new(),
+            year_month: String::new(),
+            origin: transaction.origin,
+            derived: Vec::new(),
+            currency,
+        };
+        let cycles = &self.config.statement_cycles;
+        let (mut statement_date, mut due_date) = self.fill_calendar(&mut processed);
+        
+        // Hooks registered in code may clean, retype or drop the entry
+        if !self.transforms.is_empty() {
+            let origin = processed.origin.clone();
+            for hook in self.transforms {
+                if hook(&mut processed) == TransformAction::Drop {
+                    return Ok(None);
+                }
+            }
+            // An entry moved to another date or origin gets the calendar fields of its new one
+            if processed.date != date || processed.origin != origin {
+                (statement_date, due_date) = self.fill_calendar(&mut processed);
+            }
+        }
+        
+        // Evaluate config-defined derived columns
+        for column in self.derived_columns {
+            let value = column.evaluate(&processed)?;
+            processed.derived.push((column.name.clone(), value));
+        }
+        
+        if cycles.is_enabled() {
+            let period = statement_date
+                .map(|closing_date| DerivedValue::Text(cycles::statement_period(closing_date)))
+                .unwrap_or(DerivedValue::Null);
+            processed.derived.push((cycles.column.clone(), period));
+        }
+        
+        if cycles.has_due_dates() {
+            let due = due_date
+                .map(|due_date| DerivedValue::Text(due_date.format("%Y-%m-%d").to_string()))
+                .unwrap_or(DerivedValue::Null);
+            processed.derived.push((cycles.due_column.clone(), due));
+        }
+        
+        if self.categorizer.is_some() {
+            let text = |value: Option<&String>| value.map_or(DerivedValue::Null, |v| DerivedValue::Text(v.clone()));
+            let settings = &self.config.categorization;
+            processed.derived.push((settings.category_column.clone(), text(rule.and_then(|r| r.category.as_ref()))));
+            processed.derived.push((settings.tag_column.clone(), text(rule.and_then(|r| r.tag.as_ref()))));
+        }
+        
+        if let Some((code, credit, debit, rate)) = original {
+            processed.derived.push((currency::CURRENCY_COLUMN.to_string(), DerivedValue::Text(code)));
+            processed.derived.push((currency::ORIGINAL_CREDIT_COLUMN.to_string(), DerivedValue::Number(money::to_f64(credit))));
+            processed.derived.push((currency::ORIGINAL_DEBIT_COLUMN.to_string(), DerivedValue::Number(money::to_f64(debit))));
+            processed.derived.push((currency::RATE_COLUMN.to_string(), DerivedValue::Number(rate)));
+        }
+        
+        if self.config.tags.enabled {
+            let entry_tags = transaction.tags.as_deref().and_then(tags::normalize);
+            processed.derived.push((tags::TAGS_COLUMN.to_string(), entry_tags.map_or(DerivedValue::Null, DerivedValue::Text)));
+        }
+        
+        Ok(Some(processed))
+    }
+}
+
+/// Open the input source selected by `file_types.type_in`
+pub(crate) fn open_input(config: &PdwConfig) -> Result<Box<dyn ExcelReader>, PdwError> {
+    let input_path = config.get_input_file_path();
+    
+    if config.file_types.type_in.eq_ignore_ascii_case("csv") {
+        Ok(Box::new(CsvProcessor::new(
+            &input_path,
+            config.csv.clone(),
+            &config.settings.types_of_entries,
+        )?))
+    } else {
+        Ok(Box::new(ExcelProcessor::new(&input_path)?.with_locale(config.locale.clone())))
+    }
+}
+
+/// Loadable accounting sheets present in the input
+fn parallel_sheets<'a>(sheet_configs: &'a [SheetConfig], consistency: Option<&'a ConsistencyReport>) -> impl Iterator<Item = &'a SheetConfig> {
+    sheet_configs.iter().filter(move |config| {
+        config.is_loadable && config.is_accounting && !consistency
+            .is_some_and(|report| report.missing_sheets.iter().any(|s| s == config.table_name.trim()))
+    })
+}
+
+/// Sort by date (most recent first), keeping sheet order within a day
+fn sort_by_date(transactions: &mut [ProcessedTransaction]) {
+    transactions.sort_by(|a, b| b.date.cmp(&a.date));
+}
+
 /// Trait for ETL operations
 pub trait EtlOperations {
     fn extract_data(&mut self) -> Result<Vec<Transaction>, PdwError>;
@@ -377,8 +1923,7 @@ pub trait EtlOperations {
 
 impl EtlOperations for EtlPipeline {
     fn extract_data(&mut self) -> Result<Vec<Transaction>, PdwError> {
-        let input_file = self.config.get_input_file_path();
-        let mut excel_processor = ExcelProcessor::new(&input_file)?;
+        let mut excel_processor = self.open_input()?;
         
         let sheet_configs = excel_processor.read_guiding_sheet(&self.config.settings.guiding_table)?;
         let mut all_transactions = Vec::new();
@@ -394,7 +1939,10 @@ impl EtlOperations for EtlPipeline {
     }
     
     fn transform_data(&self, data: Vec<Transaction>) -> Result<Vec<ProcessedTransaction>, PdwError> {
-        self.transform_transactions(data)
+        let mut quarantine = Quarantine::default();
+        let processed = self.transform_transactions(data, &mut quarantine)?;
+        quarantine.log();
+        Ok(processed)
     }
     
     fn load_data(&self, transactions: Vec<ProcessedTransaction>) -> Result<(), PdwError> {
@@ -410,64 +1958,799 @@ impl EtlOperations for EtlPipeline {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::locale::Locale;
     use tempfile::TempDir;
     use chrono::NaiveDate;
     
-    #[test]
-    fn test_day_of_week_portuguese() {
-        let config = PdwConfig::default();
-        let temp_dir = TempDir::new().unwrap();
+    fn test_pipeline(temp_dir: &TempDir, config: PdwConfig) -> EtlPipeline {
         let db_path = temp_dir.path().join("test.db");
         let database = DatabaseManager::new(&db_path).unwrap();
+        let derived_columns = config.compile_derived_columns().unwrap();
+        let type_normalizer = TypeNormalizer::from_config(&config.type_normalization);
+        let categorizer = Categorizer::from_config(&config.categorization, None).unwrap();
+        let non_data = NonDataFilter::from_config(&config.non_data_rows).unwrap();
         
-        let pipeline = EtlPipeline { config, database };
-        
+        EtlPipeline { config, database, derived_columns, type_normalizer, categorizer, splits: None, non_data, touched_periods: None, run: RunRecord::start(), converter: None, diff_baseline: None, window: None, importers: Vec::new(), transforms: Vec::new() }
+    }
+    
+    #[test]
+    fn test_day_of_week_portuguese() {
+        let locale = Locale::default();
         let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(); // Monday
-        assert_eq!(pipeline.get_day_of_week_portuguese(date), "Segunda-feira");
+        assert_eq!(locale.day_name(date), "Segunda-feira");
         
         let date = NaiveDate::from_ymd_opt(2024, 1, 20).unwrap(); // Saturday
-        assert_eq!(pipeline.get_day_of_week_portuguese(date), "Sábado");
+        assert_eq!(locale.day_name(date), "Sábado");
     }
     
     #[test]
     fn test_month_name_portuguese() {
-        let config = PdwConfig::default();
-        let temp_dir = TempDir::new().unwrap();
-        let db_path = temp_dir.path().join("test.db");
-        let database = DatabaseManager::new(&db_path).unwrap();
-        
-        let pipeline = EtlPipeline { config, database };
-        
-        assert_eq!(pipeline.get_month_name_portuguese(1), "01-Janeiro");
-        assert_eq!(pipeline.get_month_name_portuguese(12), "12-Dezembro");
-        assert_eq!(pipeline.get_month_name_portuguese(13), "00-Inválido");
+        let locale = Locale::default();
+        assert_eq!(locale.month_name(1), "01-Janeiro");
+        assert_eq!(locale.month_name(12), "12-Dezembro");
+        assert_eq!(locale.month_name(13), "00-Inválido");
     }
     
     #[test]
     fn test_transaction_processing() {
-        let config = PdwConfig::default();
         let temp_dir = TempDir::new().unwrap();
-        let db_path = temp_dir.path().join("test.db");
-        let database = DatabaseManager::new(&db_path).unwrap();
-        
-        let pipeline = EtlPipeline { config, database };
+        let pipeline = test_pipeline(&temp_dir, PdwConfig::default());
         
         let transaction = Transaction {
             date: Some(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()),
             transaction_type: Some("ALM".to_string()),
             description: Some("Test; transaction, with∴special chars".to_string()),
-            credit: Some(100.555),
-            debit: Some(50.999),
+            credit: money::from_f64(100.555),
+            debit: money::from_f64(50.999),
             origin: "TestSheet".to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
         };
         
-        let processed = pipeline.process_single_transaction(transaction).unwrap().unwrap();
+        let processed = pipeline.transformer().process(transaction).unwrap().unwrap();
         
         assert_eq!(processed.transaction_type, "ALM");
-        assert_eq!(processed.credit, 100.56); // Rounded
-        assert_eq!(processed.debit, 51.0); // Rounded
+        assert_eq!(processed.credit, money::Decimal::new(10056, 2)); // Rounded
+        assert_eq!(processed.debit, money::Decimal::new(51, 0)); // Rounded
         assert_eq!(processed.description, "Test| transaction| with .'. special chars");
         assert_eq!(processed.day_of_week, "Segunda-feira");
         assert_eq!(processed.month_name, "01-Janeiro");
     }
+    
+    #[test]
+    fn test_transaction_processing_english_locale() {
+        let mut config = PdwConfig::default();
+        config.locale = toml::from_str("language = \"en\"").unwrap();
+        
+        let temp_dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&temp_dir, config);
+        
+        let transaction = Transaction {
+            date: Some(NaiveDate::from_ymd_opt(2024, 3, 16).unwrap()),
+            transaction_type: Some("ALM".to_string()),
+            description: Some("Groceries".to_string()),
+            credit: None,
+            debit: money::from_f64(20.0),
+            origin: "TestSheet".to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
+        };
+        
+        let processed = pipeline.transformer().process(transaction).unwrap().unwrap();
+        assert_eq!(processed.day_of_week, "Saturday");
+        assert_eq!(processed.month_name, "03-March");
+    }
+    
+    #[test]
+    fn test_derived_columns_evaluation() {
+        let mut config = PdwConfig::default();
+        config.derived_columns.push(crate::config::DerivedColumnConfig {
+            name: "Liquido".to_string(),
+            expression: "Credito - Debito".to_string(),
+        });
+        config.derived_columns.push(crate::config::DerivedColumnConfig {
+            name: "Categoria".to_string(),
+            expression: "upper(TIPO)".to_string(),
+        });
+        
+        let temp_dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&temp_dir, config);
+        
+        let transaction = Transaction {
+            date: Some(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()),
+            transaction_type: Some("alm".to_string()),
+            description: None,
+            credit: Some(money::Decimal::ONE_HUNDRED),
+            debit: Some(money::Decimal::new(40, 0)),
+            origin: "TestSheet".to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
+        };
+        
+        let processed = pipeline.transformer().process(transaction).unwrap().unwrap();
+        assert_eq!(processed.derived[0], ("Liquido".to_string(), crate::expression::DerivedValue::Number(60.0)));
+        assert_eq!(processed.derived[1], ("Categoria".to_string(), crate::expression::DerivedValue::Text("ALM".to_string())));
+    }
+    
+    #[test]
+    fn test_statement_cycle_columns() {
+        let mut config = PdwConfig::default();
+        config.statement_cycles.closing_days.insert("Cartão".to_string(), 5);
+        config.statement_cycles.group_by_cycle = true;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&temp_dir, config);
+        
+        let transaction = |origin: &str| Transaction {
+            date: Some(NaiveDate::from_ymd_opt(2024, 1, 20).unwrap()),
+            transaction_type: Some("LAZER".to_string()),
+            description: None,
+            credit: None,
+            debit: Some(money::Decimal::new(30, 0)),
+            origin: origin.to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
+        };
+        
+        let card = pipeline.transformer().process(transaction("Cartão")).unwrap().unwrap();
+        assert_eq!(card.year_month, "2024/02");
+        assert_eq!(card.month_name, "02-Fevereiro");
+        assert_eq!(card.day_of_week, "Sábado");
+        assert_eq!(card.derived, vec![("Fatura".to_string(), DerivedValue::Text("2024/02".to_string()))]);
+        
+        let account = pipeline.transformer().process(transaction("Conta")).unwrap().unwrap();
+        assert_eq!(account.year_month, "2024/01");
+        assert_eq!(account.derived, vec![("Fatura".to_string(), DerivedValue::Null)]);
+        
+        pipeline.database.create_tables().unwrap();
+        pipeline.database.add_derived_columns("LANCAMENTOS_GERAIS", &[("Fatura".to_string(), "TEXT")]).unwrap();
+        pipeline.database.insert_transactions(&[card, account]).unwrap();
+        assert_eq!(pipeline.create_statement_summaries().unwrap(), 1);
+        
+        let summary = pipeline.database.execute_query("SELECT Origem, Fatura, Lancamentos, Total FROM Resumo_Faturas").unwrap();
+        assert_eq!(summary, vec![vec![
+            serde_json::json!("Cartão"), serde_json::json!("2024/02"), serde_json::json!(1), serde_json::json!(30.0),
+        ]]);
+    }
+    
+    #[test]
+    fn test_due_date_basis() {
+        let mut config = PdwConfig::default();
+        config.statement_cycles.closing_days.insert("Cartão".to_string(), 25);
+        config.statement_cycles.due_days.insert("Cartão".to_string(), 5);
+        config.statement_cycles.basis = DateBasis::Due;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&temp_dir, config);
+        
+        let card = pipeline.transformer().process(Transaction {
+            date: Some(NaiveDate::from_ymd_opt(2024, 1, 20).unwrap()),
+            transaction_type: Some("LAZER".to_string()),
+            description: None,
+            credit: None,
+            debit: Some(money::Decimal::new(30, 0)),
+            origin: "Cartão".to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
+        }).unwrap().unwrap();
+        
+        // Bought in January, billed on the 25/01 invoice and paid in February
+        assert_eq!(card.date, NaiveDate::from_ymd_opt(2024, 1, 20).unwrap());
+        assert_eq!(card.year_month, "2024/02");
+        assert_eq!(card.derived, vec![
+            ("Fatura".to_string(), DerivedValue::Text("2024/01".to_string())),
+            ("Vencimento".to_string(), DerivedValue::Text("2024-02-05".to_string())),
+        ]);
+        
+        pipeline.database.create_tables().unwrap();
+        pipeline.database.add_derived_columns("LANCAMENTOS_GERAIS", &[
+            ("Fatura".to_string(), "TEXT"), ("Vencimento".to_string(), "TEXT"),
+        ]).unwrap();
+        pipeline.database.insert_transactions(&[card]).unwrap();
+        assert_eq!(pipeline.create_statement_summaries().unwrap(), 1);
+        
+        let summary = pipeline.database.execute_query("SELECT Fatura, Vencimento FROM Resumo_Faturas").unwrap();
+        assert_eq!(summary, vec![vec![serde_json::json!("2024/01"), serde_json::json!("2024-02-05")]]);
+    }
+    
+    #[test]
+    fn test_categorization_fills_type() {
+        let mut config = PdwConfig::default();
+        config.categorization.enabled = true;
+        config.categorization.rules.push(crate::categorize::CategoryRule {
+            pattern: "ifood".to_string(),
+            regex: false,
+            tipo: Some("DELIVERY".to_string()),
+            category: Some("Alimentação".to_string()),
+            tag: None,
+        });
+        
+        let temp_dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&temp_dir, config);
+        
+        let transaction = |tipo: Option<&str>, description: &str| Transaction {
+            date: Some(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()),
+            transaction_type: tipo.map(str::to_string),
+            description: Some(description.to_string()),
+            credit: None,
+            debit: Some(money::Decimal::new(52, 0)),
+            origin: "Cartão".to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
+        };
+        
+        let filled = pipeline.transformer().process(transaction(None, "IFOOD *RESTAURANTE")).unwrap().unwrap();
+        assert_eq!(filled.transaction_type, "DELIVERY");
+        assert_eq!(filled.derived, vec![
+            ("Categoria".to_string(), DerivedValue::Text("Alimentação".to_string())),
+            ("Tag".to_string(), DerivedValue::Null),
+        ]);
+        
+        // A typed TIPO is kept unless override_types is set
+        let typed = pipeline.transformer().process(transaction(Some("LAZER"), "iFood")).unwrap().unwrap();
+        assert_eq!(typed.transaction_type, "LAZER");
+        assert!(pipeline.transformer().process(transaction(None, "Padaria")).unwrap().is_none());
+    }
+    
+    #[test]
+    fn test_type_normalization_before_grouping() {
+        let mut config = PdwConfig::default();
+        config.type_normalization.enabled = true;
+        config.type_normalization.canonical.insert("supermercado".to_string(), "MERCADO".to_string());
+        
+        let temp_dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&temp_dir, config);
+        
+        let transactions: Vec<Transaction> = ["Mercado", "mercâdo", "SuperMercado", "Lazer"].iter()
+            .map(|tipo| Transaction {
+                date: Some(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()),
+                transaction_type: Some(tipo.to_string()),
+                description: None,
+                credit: None,
+                debit: Some(money::Decimal::TEN),
+                origin: "TestSheet".to_string(),
+                currency: None,
+                tags: None,
+                row: None,
+                invalid_amount: None,
+            })
+            .collect();
+        
+        let mut tracker = VariantTracker::default();
+        pipeline.transformer().track_variants(&mut tracker, &transactions);
+        // "Lazer" -> LAZER only changes case and is not reported
+        assert_eq!(pipeline.write_merged_types(&tracker).unwrap(), 1);
+        
+        let processed = pipeline.transform_transactions(transactions, &mut Quarantine::default()).unwrap();
+        let types: Vec<&str> = processed.iter().map(|t| t.transaction_type.as_str()).collect();
+        assert_eq!(types, vec!["MERCADO", "MERCADO", "MERCADO", "LAZER"]);
+    }
+    
+    #[test]
+    fn test_load_into_other_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nTiposLancamentos;;X\nAusente;X;X\n").unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        let pipeline = EtlPipeline::in_memory(config).unwrap();
+        
+        let target = DatabaseManager::new(&temp_dir.path().join("target.db")).unwrap();
+        assert_eq!(pipeline.load_into(&target).unwrap(), 1);
+        // A second load replaces instead of accumulating
+        assert_eq!(pipeline.load_into(&target).unwrap(), 1);
+        
+        let pivot = target.execute_query("SELECT AnoMes, ALM FROM HistoricoGeral").unwrap();
+        assert_eq!(pivot, vec![vec![serde_json::json!("2024/01"), serde_json::json!(10.0)]]);
+    }
+    
+    #[test]
+    fn test_missing_loadable_sheet_fails_the_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nTiposLancamentos;;X\nAntiga;X;\nAusente;X;X\n").unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        let error = pipeline.execute_data_loading().unwrap_err();
+        assert!(error.to_string().contains("Sheet not found: Ausente"), "{}", error);
+        
+        // Sheets GUIDING does not load may be missing
+        std::fs::write(input_dir.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nTiposLancamentos;;X\nAntiga;X;\n").unwrap();
+        pipeline.execute_data_loading().unwrap();
+    }
+    
+    #[test]
+    fn test_ofx_source_joins_the_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\nLAZ;LAZ\n").unwrap();
+        std::fs::write(temp_dir.path().join("cartao.ofx"), "<OFX><CCACCTFROM><ACCTID>9876</CCACCTFROM>\
+            <STMTTRN><DTPOSTED>20240118<TRNAMT>-45.50<FITID>1<MEMO>Cinema</STMTTRN></OFX>").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        config.sources = toml::from_str(
+            "[cartao]\npath = \"cartao.ofx\"\ndefault_tipo = \"LAZ\"\naccounts = { \"9876\" = \"Cartao\" }"
+        ).unwrap();
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        pipeline.execute_data_loading().unwrap();
+        
+        let rows = pipeline.database.execute_query(
+            "SELECT Data, TIPO, DESCRICAO, Debito, Origem FROM LANCAMENTOS_GERAIS ORDER BY Data"
+        ).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], vec![
+            serde_json::json!("2024-01-18"), serde_json::json!("LAZ"), serde_json::json!("Cinema"),
+            serde_json::json!(45.5), serde_json::json!("Cartao"),
+        ]);
+    }
+    
+    #[test]
+    fn test_registered_importer_joins_the_load() {
+        struct Fixed;
+        impl Importer for Fixed {
+            fn read(&self, _dir_in: &Path) -> Result<Vec<Transaction>, PdwError> {
+                Ok(vec![Transaction {
+                    date: NaiveDate::from_ymd_opt(2024, 1, 20),
+                    transaction_type: Some("LAZ".to_string()),
+                    description: Some("Livraria".to_string()),
+                    credit: None,
+                    debit: Some(rust_decimal::Decimal::new(8990, 2)),
+                    origin: "Carteira".to_string(),
+                    currency: None,
+                    tags: None,
+                    row: None,
+                    invalid_amount: None,
+                }])
+            }
+        }
+        
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\nLAZ;LAZ\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        pipeline.register_importer("carteira", Box::new(Fixed));
+        pipeline.execute_data_loading().unwrap();
+        
+        let database = pipeline.into_database();
+        let rows = database.execute_query("SELECT Origem, Debito FROM LANCAMENTOS_GERAIS ORDER BY Data").unwrap();
+        assert_eq!(rows[1], vec![serde_json::json!("Carteira"), serde_json::json!(89.9)]);
+    }
+
+    #[test]
+    fn test_transform_hooks() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n\
+            15/01/2024;ALM;Mercado;;10,00\n16/01/2024;ALM;Uber Eats;;30,00\n17/01/2024;ALM;Teste PIX;;0,01\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\nDELIVERY;Delivery\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        config.derived_columns.push(crate::config::DerivedColumnConfig {
+            name: "Grupo".to_string(),
+            expression: "lower(TIPO)".to_string(),
+        });
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        pipeline.add_transform(Box::new(|entry| {
+            if entry.description.starts_with("Teste") {
+                return TransformAction::Drop;
+            }
+            if entry.description.contains("Uber") {
+                entry.transaction_type = "DELIVERY".to_string();
+            }
+            TransformAction::Keep
+        }));
+        pipeline.add_transform(Box::new(|entry| {
+            entry.description = entry.description.to_uppercase();
+            if entry.description == "MERCADO" {
+                entry.date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+            }
+            TransformAction::Keep
+        }));
+        pipeline.execute_data_loading().unwrap();
+        
+        // Derived columns and calendar fields follow the entries as the hooks left them
+        let rows = pipeline.database.execute_query(
+            "SELECT TIPO, DESCRICAO, Grupo, AnoMes, DIA_SEMANA FROM LANCAMENTOS_GERAIS ORDER BY Data"
+        ).unwrap();
+        assert_eq!(rows, vec![
+            vec![
+                serde_json::json!("DELIVERY"), serde_json::json!("UBER EATS"), serde_json::json!("delivery"),
+                serde_json::json!("2024/01"), serde_json::json!("Terça-feira"),
+            ],
+            vec![
+                serde_json::json!("ALM"), serde_json::json!("MERCADO"), serde_json::json!("alm"),
+                serde_json::json!("2024/02"), serde_json::json!("Quinta-feira"),
+            ],
+        ]);
+    }
+    
+    #[test]
+    fn test_types_seeded_without_types_sheet() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("Conta.csv"),
+                       "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM ;Mercado;;10,00\n20/01/2024;LAZ;Cinema;;30,00\n22/01/2024;ALM;Feira;;5,00\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        pipeline.execute_data_loading().unwrap();
+        pipeline.create_pivot_tables().unwrap();
+        
+        let database = pipeline.into_database();
+        let types = database.execute_query("SELECT Código, Descrição FROM TiposLancamentos").unwrap();
+        assert_eq!(types, vec![vec![serde_json::json!("ALM"), serde_json::json!("ALM")],
+                               vec![serde_json::json!("LAZ"), serde_json::json!("LAZ")]]);
+        assert_eq!(database.table_columns("HistoricoGeral").unwrap(), vec!["AnoMes", "ALM", "LAZ"]);
+        // A filled table is left alone
+        assert_eq!(database.seed_types("LANCAMENTOS_GERAIS", "TiposLancamentos").unwrap(), 0);
+    }
+    
+    #[test]
+    fn test_budgets_sheet_and_report() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;610,00\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\nLAZ;LAZ\n").unwrap();
+        std::fs::write(input_dir.join("BUDGETS.csv"), "TIPO;LIMITE\nALM;600,00\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        config.budgets = toml::from_str("enabled = true\n[limits]\nALM = 500\nLAZ = 200").unwrap();
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        pipeline.execute_data_loading().unwrap();
+        assert_eq!(pipeline.create_budget_report().unwrap(), 2);
+        
+        let rows = pipeline.database.execute_query(
+            "SELECT TIPO, Orcado, Realizado, Diferenca FROM ORCAMENTO_VS_REAL ORDER BY TIPO"
+        ).unwrap();
+        assert_eq!(rows, vec![
+            vec![serde_json::json!("ALM"), serde_json::json!(600.0), serde_json::json!(610.0), serde_json::json!(-10.0)],
+            vec![serde_json::json!("LAZ"), serde_json::json!(200.0), serde_json::json!(0.0), serde_json::json!(200.0)],
+        ]);
+    }
+    
+    #[test]
+    fn test_change_report_against_previous_database() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\n").unwrap();
+        std::fs::write(temp_dir.path().join("PDW_QUERIES.yaml"), "queries_gera_hist:\n  - sql: SELECT * FROM {full_hist}\n    sheet_name: Historico\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.directories.database_dir = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        config.settings.overwrite_db = true;
+        
+        // Previous report database
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n").unwrap();
+        let mut previous = EtlPipeline::new(config.clone()).unwrap();
+        previous.execute_data_loading().unwrap();
+        previous.create_pivot_tables().unwrap();
+        let baseline = previous.database().path().to_path_buf();
+        drop(previous);
+        
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;12,50\n").unwrap();
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        pipeline.execute_data_loading().unwrap();
+        pipeline.create_pivot_tables().unwrap();
+        assert_eq!(pipeline.create_change_report().unwrap(), 0);
+        
+        pipeline.set_diff_baseline(baseline);
+        assert_eq!(pipeline.create_change_report().unwrap(), 1);
+        let rows = pipeline.database.execute_query("SELECT Planilha, Chave, Coluna, Anterior, Atual, Diferenca FROM MUDANCAS").unwrap();
+        assert_eq!(rows, vec![vec![
+            serde_json::json!("Historico"), serde_json::json!("2024/01"), serde_json::json!("ALM"),
+            serde_json::json!(10.0), serde_json::json!(12.5), serde_json::json!(2.5),
+        ]]);
+    }
+    
+    #[test]
+    fn test_run_record_of_a_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n16/01/2024;;Sem tipo;;5,00\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        pipeline.execute_data_loading().unwrap();
+        
+        let run = pipeline.run_record();
+        assert_eq!(run.sheets.get("Conta"), Some(&2));
+        assert_eq!((run.rows_loaded, run.rows_discarded), (1, 1));
+        assert_eq!(pipeline.record_run("load", None).unwrap(), 1);
+        
+        // The untyped row is kept with where it came from
+        let discarded = pipeline.database.execute_query("SELECT Origem, Linha, Motivo FROM discarted_data").unwrap();
+        assert_eq!(discarded, vec![vec![serde_json::json!("Conta"), serde_json::json!(3), serde_json::json!("missing_tipo")]]);
+    }
+    
+    #[test]
+    fn test_foreign_currency_entries_converted() {
+        use serde_json::json;
+        
+        let temp_dir= TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito;Moeda\n\
+            15/01/2024;ALM;Mercado;;10,00;\n16/01/2024;VIAGEM;Hotel;;100,00;eur\n").unwrap();
+        std::fs::write(input_dir.join("ContaEUA.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;SAL;Salario;1000,00;\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\nVIAGEM;VIAGEM\nSAL;SAL\n").unwrap();
+        std::fs::write(temp_dir.path().join("cambio.csv"), "Data;Moeda;Taxa\n2024-01-12;USD;4,90\n16/01/2024;EUR;5,35\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.directories.database_dir = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        config.currency = toml::from_str("enabled = true\nrates_file = \"cambio.csv\"\norigins = { ContaEUA = \"USD\" }").unwrap();
+        let mut pipeline = EtlPipeline::in_memory(config.clone()).unwrap();
+        pipeline.execute_data_loading().unwrap();
+        
+        let rows = pipeline.database().execute_query(
+            "SELECT Origem, Credito, Debito, Moeda, CreditoOriginal, DebitoOriginal, Cambio FROM LANCAMENTOS_GERAIS ORDER BY Origem, Data"
+        ).unwrap();
+        assert_eq!(rows, vec![
+            vec![json!("Conta"), json!(0.0), json!(10.0), json!("BRL"), json!(0.0), json!(10.0), json!(1.0)],
+            vec![json!("Conta"), json!(0.0), json!(535.0), json!("EUR"), json!(0.0), json!(100.0), json!(5.35)],
+            vec![json!("ContaEUA"), json!(4900.0), json!(0.0), json!("USD"), json!(1000.0), json!(0.0), json!(4.9)],
+        ]);
+        let rates = pipeline.database().execute_query("SELECT Moeda, Taxa FROM CAMBIO ORDER BY Moeda").unwrap();
+        assert_eq!(rates, vec![vec![json!("EUR"), json!(5.35)], vec![json!("USD"), json!(4.9)]]);
+        
+        // No EUR rate on or before the hotel entry: the load fails instead of guessing
+        std::fs::write(temp_dir.path().join("cambio.csv"), "Data;Moeda;Taxa\n2024-01-12;USD;4,90\n").unwrap();
+        let error = EtlPipeline::in_memory(config).unwrap().execute_data_loading().unwrap_err().to_string();
+        assert!(error.contains("no EUR rate on or before 2024-01-16 for an entry of Conta"), "{}", error);
+    }
+    
+    #[test]
+    fn test_disk_space_preflight() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        // No volume has an exabyte to spare
+        config.settings.disk_space_margin_mb = 1 << 40;
+        let mut pipeline = test_pipeline(&temp_dir, config);
+        
+        let error = pipeline.execute_data_loading().unwrap_err().to_string();
+        assert!(error.contains("disk space - Load needs about"), "{}", error);
+        assert!(pipeline.generate_reports().unwrap_err().to_string().contains("Report generation needs"));
+        
+        // Disabled, the load goes on and fails on the missing workbook instead
+        pipeline.config.settings.check_disk_space = false;
+        assert!(!pipeline.execute_data_loading().unwrap_err().to_string().contains("disk space"));
+    }
+    
+    #[test]
+    fn test_quality_counts_skipped_rows() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nCartao;X;X\nTiposLancamentos;;X\n").unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n16/01/2024;;Sem tipo;;5,00\n").unwrap();
+        std::fs::write(input_dir.join("Cartao.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n17/01/2024;XYZ;Cinema;;45,50\n;LAZ;Sem data;;1,00\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\nLAZ;LAZ\n").unwrap();
+        
+        // Sheets read by worker threads report their rows too
+        for multithreading in [false, true] {
+            let mut config = PdwConfig::default();
+            config.directories.dir_in = temp_dir.path().to_path_buf();
+            config.file_types.type_in = "csv".to_string();
+            config.settings.multithreading = multithreading;
+            config.settings.parallels = Some(2);
+            let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+            pipeline.execute_data_loading().unwrap();
+            
+            let rows = pipeline.database.execute_query("SELECT Regra, Origem, Violacoes FROM DATA_QUALITY ORDER BY Regra").unwrap();
+            assert_eq!(rows, vec![
+                vec![serde_json::json!("missing_tipo"), serde_json::json!("Conta"), serde_json::json!(1)],
+                vec![serde_json::json!("null_date"), serde_json::json!("Cartao"), serde_json::json!(1)],
+                vec![serde_json::json!("unknown_tipo"), serde_json::json!("Cartao"), serde_json::json!(1)],
+            ]);
+        }
+    }
+    
+    #[test]
+    fn test_non_data_rows_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nCartao;X;X\nTiposLancamentos;;X\n").unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n;Moradia;Janeiro 2024;;\n15/01/2024;ALM;Mercado;;10,00\n;TOTAL;;;10,00\n").unwrap();
+        std::fs::write(input_dir.join("Cartao.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n17/01/2024;ALM;Feira;;4,50\n31/01/2024;;Subtotal Janeiro;;4,50\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\n").unwrap();
+        
+        for multithreading in [false, true] {
+            let mut config = PdwConfig::default();
+            config.directories.dir_in = temp_dir.path().to_path_buf();
+            config.file_types.type_in = "csv".to_string();
+            config.settings.multithreading = multithreading;
+            config.settings.parallels = Some(2);
+            let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+            pipeline.execute_data_loading().unwrap();
+            
+            // Neither discarded nor data-quality failures
+            let run = pipeline.run_record();
+            assert_eq!((run.rows_loaded, run.rows_discarded), (2, 0));
+            assert_eq!((run.sheets.get("Conta"), run.sheets.get("Cartao")), (Some(&1), Some(&1)));
+            let violations = pipeline.database.execute_query("SELECT COUNT(*) FROM DATA_QUALITY").unwrap();
+            assert_eq!(violations, vec![vec![serde_json::json!(0)]]);
+        }
+    }
+    
+    #[test]
+    fn test_window_load_keeps_other_periods() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nTiposLancamentos;;X\n").unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n10/02/2024;ALM;Feira;;20,00\n05/03/2024;ALM;Padaria;;30,00\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\n").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+        pipeline.execute_data_loading().unwrap();
+        
+        // Every month edited, but only February reloaded
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;11,00\n10/02/2024;ALM;Feira;;21,00\n11/02/2024;ALM;Acougue;;5,00\n;ALM;Sem data;;1,00\n05/03/2024;ALM;Padaria;;31,00\n").unwrap();
+        pipeline.set_window(DateWindow::parse("2024-02").unwrap());
+        pipeline.execute_data_loading().unwrap();
+        
+        let rows = pipeline.database.execute_query("SELECT Data, Debito FROM LANCAMENTOS_GERAIS ORDER BY Data, Debito").unwrap();
+        assert_eq!(rows, vec![
+            vec![serde_json::json!("2024-01-15"), serde_json::json!(10.0)],
+            vec![serde_json::json!("2024-02-10"), serde_json::json!(21.0)],
+            vec![serde_json::json!("2024-02-11"), serde_json::json!(5.0)],
+            vec![serde_json::json!("2024-03-05"), serde_json::json!(30.0)],
+        ]);
+        assert_eq!(pipeline.touched_periods().unwrap().months.iter().collect::<Vec<_>>(), vec!["2024/02"]);
+        assert_eq!(pipeline.database.meta().get::<String>(MetaStore::LAST_LOAD, "window").unwrap(), Some("2024-02..2024-02".to_string()));
+    }
+    
+    #[test]
+    fn test_parallel_extraction_matches_serial_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nCartao;X;X\nPoupanca;X;X\nTiposLancamentos;;X\n").unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Mercado;;10,00\n16/01/2024;SAL;Salario;1000,00;\n").unwrap();
+        std::fs::write(input_dir.join("Cartao.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;LAZ;Cinema;;45,50\n02/02/2024;ALM;Feira;;23,10\n").unwrap();
+        std::fs::write(input_dir.join("Poupanca.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n16/01/2024;INV;Aporte;200,00;\n").unwrap();
+        std::fs::write(input_dir.join("TiposLancamentos.csv"), "Código;Descrição\nALM;ALM\nSAL;SAL\nLAZ;LAZ\nINV;INV\n").unwrap();
+        
+        let load = |multithreading: bool| {
+            let mut config = PdwConfig::default();
+            config.directories.dir_in = temp_dir.path().to_path_buf();
+            config.file_types.type_in = "csv".to_string();
+            config.settings.multithreading = multithreading;
+            config.settings.parallels = Some(2);
+            let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+            pipeline.execute_data_loading().unwrap();
+            pipeline.database.execute_query("SELECT Data, TIPO, Debito, Origem FROM LANCAMENTOS_GERAIS ORDER BY rowid").unwrap()
+        };
+        
+        let serial = load(false);
+        assert_eq!(serial.len(), 5);
+        assert_eq!(load(true), serial);
+    }
+    
+    #[test]
+    fn test_streamed_load_matches_whole_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_dir = temp_dir.path().join("PDW");
+        std::fs::create_dir(&input_dir).unwrap();
+        std::fs::write(input_dir.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nCartao;X;X\n").unwrap();
+        std::fs::write(input_dir.join("Conta.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;ALM;Padaria;;10,00\n15/01/2024;ALM;Padaria;;10,00\n16/01/2024;SAL;Salario;1000,00;\n;;;;\n").unwrap();
+        std::fs::write(input_dir.join("Cartao.csv"), "Data;TIPO;DESCRICAO;Credito;Debito\n15/01/2024;LAZ;Cinema;;45,50\n02/02/2024;ALM;Feira;;23,10\n").unwrap();
+        
+        let load = |stream_chunk_rows: usize, load_mode: LoadMode| {
+            let mut config = PdwConfig::default();
+            config.directories.dir_in = temp_dir.path().to_path_buf();
+            config.file_types.type_in = "csv".to_string();
+            config.settings.stream_chunk_rows = stream_chunk_rows;
+            config.settings.load_mode = load_mode;
+            let mut pipeline = EtlPipeline::in_memory(config).unwrap();
+            pipeline.execute_data_loading().unwrap();
+            let rows = pipeline.database.execute_query("SELECT Data, TIPO, Debito, Origem, RowHash FROM LANCAMENTOS_GERAIS ORDER BY RowHash").unwrap();
+            (rows, pipeline.run.rows_loaded, pipeline.touched_periods().cloned())
+        };
+        
+        let whole = load(0, LoadMode::Replace);
+        assert_eq!(whole.0.len(), 5);
+        assert_eq!(load(1, LoadMode::Replace), whole);
+        assert_eq!(load(2, LoadMode::Append), load(0, LoadMode::Append));
+        assert_eq!(load(2, LoadMode::Append).2.unwrap().months.len(), 2);
+    }
+    
+    #[test]
+    fn test_extraction_workers() {
+        let sheet = |name: &str, is_accounting: bool| SheetConfig {
+            table_name: name.to_string(),
+            is_accounting,
+            is_loadable: true,
+        };
+        let sheets = vec![sheet("Conta", true), sheet("Cartao", true), sheet("Poupanca", true), sheet("Tipos", false)];
+        
+        let mut config = PdwConfig::default();
+        config.settings.parallels = Some(2);
+        let temp_dir = TempDir::new().unwrap();
+        let mut pipeline = test_pipeline(&temp_dir, config);
+        assert_eq!(pipeline.extraction_workers(&sheets, None), None);
+        
+        pipeline.config.settings.multithreading = true;
+        assert_eq!(pipeline.extraction_workers(&sheets, None), Some(2));
+        pipeline.config.settings.parallels = Some(89);
+        assert_eq!(pipeline.extraction_workers(&sheets, None), Some(3));
+        // A single accounting sheet is read in place
+        assert_eq!(pipeline.extraction_workers(&sheets[2..], None), None);
+    }
+    
+    #[test]
+    fn test_incremental_mode_falls_back_on_unhashed_rows() {
+        let mut config = PdwConfig::default();
+        config.settings.load_mode = LoadMode::Incremental;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&temp_dir, config);
+        
+        // Fresh database: nothing to match against yet
+        assert_eq!(pipeline.effective_load_mode().unwrap(), LoadMode::Incremental);
+        
+        pipeline.database.create_tables().unwrap();
+        pipeline.database.connection()
+            .execute("INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Debito) VALUES ('2024-01-15', 'ALM', 10)", [])
+            .unwrap();
+        assert_eq!(pipeline.effective_load_mode().unwrap(), LoadMode::Replace);
+        
+        pipeline.database.connection().execute("UPDATE LANCAMENTOS_GERAIS SET RowHash = 'x'", []).unwrap();
+        assert_eq!(pipeline.effective_load_mode().unwrap(), LoadMode::Incremental);
+    }
 }
\ No newline at end of file
@@ -3,17 +3,93 @@
 
 Handles Excel file reading and parsing using the calamine crate.
 Provides functionality for reading guiding sheets, accounting data, and reference data.
+The workbook format follows the file extension (`file_types.type_in`): Excel
+(`xlsx`, `xlsm`, `xls`, `xlsb`) or LibreOffice/OpenDocument (`ods`).
+
+Dates kept as numbers are serials of the workbook's date system. In the 1900
+system (Windows Excel) serial 1 is 01/01/1900 and serial 60 is 29/02/1900, a day
+that never was: Excel kept the Lotus 1-2-3 leap-year bug, so serials up to 59 are
+one day off from the ones after it, and 60 is no date at all. Workbooks saved by
+older Mac Excel count from 01/01/1904 instead (`date1904` in `xl/workbook.xml`),
+which is read when an `xlsx`/`xlsm` workbook opens; other formats use 1900.
+Cells formatted as dates are converted by calamine, numbers by `DateSystem`.
+
+Credit and debit cells typed as text ("1.234,56") are read with the separators
+of `[locale]`; the ones that are not numbers keep their entry out of the load,
+with the conversion error as the discarded row's detail.
 */
 
 use crate::error::{ExcelError, PdwError};
-use calamine::{Reader, Xlsx, open_workbook, DataType, Range};
+use crate::input_files;
+use crate::locale::Locale;
+use crate::money::{self, Decimal};
+use crate::tags;
+use calamine::{Reader, Sheets, open_workbook_auto, DataType, Range};
 use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
+use std::io::Read;
 use std::path::Path;
 
 /// Excel processor for reading workbooks
 pub struct ExcelProcessor {
-    workbook: Xlsx<std::io::BufReader<std::fs::File>>,
+    workbook: Sheets<std::io::BufReader<std::fs::File>>,
+    date_system: DateSystem,
+    /// Separators of the amounts typed as text
+    locale: Locale,
+}
+
+/// Day serial numbers count from: 1900 (Windows Excel, LibreOffice) or 1904 (older Mac Excel)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DateSystem {
+    #[default]
+    Excel1900,
+    Excel1904,
+}
+
+impl DateSystem {
+    /// Date system an `xlsx`/`xlsm` workbook declares; 1900 for other formats or when it cannot be read
+    pub fn of_workbook(path: &Path) -> Self {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if !matches!(extension.as_str(), "xlsx" | "xlsm") {
+            return DateSystem::Excel1900;
+        }
+        
+        let workbook_xml = std::fs::File::open(path).ok()
+            .and_then(|file| zip::ZipArchive::new(file).ok())
+            .and_then(|mut archive| {
+                let mut xml = String::new();
+                archive.by_name("xl/workbook.xml").ok()?.read_to_string(&mut xml).ok()?;
+                Some(xml)
+            });
+        match workbook_xml {
+            Some(xml) if declares_date1904(&xml) => DateSystem::Excel1904,
+            _ => DateSystem::Excel1900,
+        }
+    }
+    
+    /// Day of a serial number, time of day dropped; None before the epoch and for 29/02/1900
+    pub fn serial_date(self, serial: f64) -> Option<NaiveDate> {
+        if !serial.is_finite() {
+            return None;
+        }
+        let days = serial.floor() as i64;
+        let (epoch, days) = match self {
+            DateSystem::Excel1900 if days < 1 || days == 60 => return None,
+            DateSystem::Excel1900 if days < 60 => ((1899, 12, 31), days),
+            DateSystem::Excel1900 => ((1899, 12, 30), days),
+            DateSystem::Excel1904 if days < 0 => return None,
+            DateSystem::Excel1904 => ((1904, 1, 1), days),
+        };
+        NaiveDate::from_ymd_opt(epoch.0, epoch.1, epoch.2)?.checked_add_signed(chrono::Duration::days(days))
+    }
+}
+
+/// Whether a workbook.xml sets `date1904` on its workbookPr element
+fn declares_date1904(workbook_xml: &str) -> bool {
+    workbook_xml.split("date1904=").skip(1).any(|value| {
+        let value = value.trim_start_matches(['"', '\'']);
+        value.starts_with('1') || value.starts_with("true")
+    })
 }
 
 /// Configuration for sheet processing
@@ -30,9 +106,27 @@ pub struct Transaction {
     pub date: Option<NaiveDate>,
     pub transaction_type: Option<String>,
     pub description: Option<String>,
-    pub credit: Option<f64>,
-    pub debit: Option<f64>,
+    pub credit: Option<Decimal>,
+    pub debit: Option<Decimal>,
     pub origin: String,
+    /// Currency of the amounts (Moeda column); None when the sheet has no such column
+    pub currency: Option<String>,
+    /// Semicolon-separated tags (Tags column); None when the sheet has no such column
+    #[serde(default)]
+    pub tags: Option<String>,
+    /// Row of the sheet the entry was read from, header included; None for other sources
+    #[serde(default)]
+    pub row: Option<usize>,
+    /// Why a credit or debit cell is not a number (its text, or the conversion error), which keeps the entry out of the load
+    #[serde(default)]
+    pub invalid_amount: Option<String>,
+}
+
+/// Positions of the optional columns of an accounting sheet
+#[derive(Debug, Clone, Copy)]
+struct OptionalColumns {
+    currency: Option<usize>,
+    tags: Option<usize>,
 }
 
 /// Raw sheet data
@@ -45,15 +139,30 @@ pub struct SheetData {
 }
 
 impl ExcelProcessor {
-    /// Open Excel workbook
+    /// Open Excel or OpenDocument workbook
     pub fn new(path: &Path) -> Result<Self, PdwError> {
-        let workbook = open_workbook(path)
+        input_files::hydrate(path)?;
+        if let Some(lock) = input_files::lock_file(path) {
+            tracing::warn!("{} is open in Excel or LibreOffice ({} exists) - unsaved changes are not loaded", path.display(), lock.display());
+        }
+        
+        let workbook = open_workbook_auto(path)
             .map_err(|e| ExcelError::FileOpen {
                 path: path.to_string_lossy().to_string(),
                 reason: e.to_string(),
             })?;
+        let date_system = DateSystem::of_workbook(path);
+        if date_system == DateSystem::Excel1904 {
+            tracing::debug!("{} uses the 1904 date system", path.display());
+        }
         
-        Ok(Self { workbook })
+        Ok(Self { workbook, date_system, locale: Locale::default() })
+    }
+    
+    /// Read the amounts typed as text with the separators of `locale` instead of the Brazilian ones
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
     }
     
     /// Get list of sheet names
@@ -91,34 +200,100 @@ impl ExcelProcessor {
     /// Read accounting sheet data
     pub fn read_accounting_sheet(&mut self, sheet_name: &str) -> Result<Vec<Transaction>, PdwError> {
         let range = self.get_sheet_range(sheet_name)?;
-        let mut transactions = Vec::new();
+        let optional_columns = self.optional_columns(&range);
+        let first_row = first_row(&range);
         
-        // Expected columns: Data, TIPO, DESCRICAO, Credito, Debito
-        for row_idx in 1..range.height() {
-            if let Some(row) = range.rows().nth(row_idx) {
-                if row.len() >= 5 {
-                    let date = self.cell_to_date(&row[0]);
-                    let transaction_type = self.cell_to_string_option(&row[1]);
-                    let description = self.cell_to_string_option(&row[2]);
-                    let credit = self.cell_to_float(&row[3]);
-                    let debit = self.cell_to_float(&row[4]);
-                    
-                    // Only add transaction if it has essential data
-                    if date.is_some() || transaction_type.is_some() {
-                        transactions.push(Transaction {
-                            date,
-                            transaction_type,
-                            description,
-                            credit,
-                            debit,
-                            origin: sheet_name.to_string(),
-                        });
-                    }
-                }
+        Ok(range.rows()
+            .enumerate()
+            .skip(1)
+            .filter_map(|(idx, row)| self.row_to_transaction(row, first_row + idx, sheet_name, optional_columns))
+            .collect())
+    }
+    
+    /// Read accounting sheet data in chunks of at most `chunk_rows` entries, handing each
+    /// one over before building the next; returns the entries read.
+    ///
+    /// calamine 0.22 still loads the sheet's cells at once, so memory stays bounded by
+    /// the largest sheet plus one chunk instead of every entry of the workbook.
+    pub fn read_accounting_chunks(&mut self, sheet_name: &str, chunk_rows: usize,
+                                  on_chunk: &mut dyn FnMut(Vec<Transaction>) -> Result<(), PdwError>) -> Result<usize, PdwError> {
+        let range = self.get_sheet_range(sheet_name)?;
+        let optional_columns = self.optional_columns(&range);
+        let chunk_rows = chunk_rows.max(1);
+        let first_row = first_row(&range);
+        
+        let mut chunk = Vec::with_capacity(chunk_rows.min(range.height()));
+        let mut read = 0;
+        let transactions = range.rows()
+            .enumerate()
+            .skip(1)
+            .filter_map(|(idx, row)| self.row_to_transaction(row, first_row + idx, sheet_name, optional_columns));
+        for transaction in transactions {
+            chunk.push(transaction);
+            if chunk.len() == chunk_rows {
+                read += chunk.len();
+                on_chunk(std::mem::replace(&mut chunk, Vec::with_capacity(chunk_rows)))?;
             }
         }
+        if !chunk.is_empty() {
+            read += chunk.len();
+            on_chunk(chunk)?;
+        }
         
-        Ok(transactions)
+        Ok(read)
+    }
+    
+    /// Optional Moeda and Tags columns after the expected ones
+    fn optional_columns(&self, range: &Range<DataType>) -> OptionalColumns {
+        let find = |is_header: fn(&str) -> bool| range.rows().next()
+            .and_then(|header| header.iter().skip(5)
+                .position(|cell| is_header(&self.cell_to_string(cell))))
+            .map(|position| position + 5);
+        OptionalColumns {
+            currency: find(is_currency_header),
+            tags: find(tags::is_tags_header),
+        }
+    }
+    
+    /// Entry of an accounting row (`row_number` counted from 1); expected columns: Data, TIPO, DESCRICAO, Credito, Debito
+    fn row_to_transaction(&self, row: &[DataType], row_number: usize, sheet_name: &str,
+                          optional_columns: OptionalColumns) -> Option<Transaction> {
+        if row.len() < 5 {
+            return None;
+        }
+        
+        let date = self.cell_to_date(&row[0]);
+        let transaction_type = self.cell_to_string_option(&row[1]);
+        
+        // Only add transaction if it has essential data
+        if date.is_none() && transaction_type.is_none() {
+            return None;
+        }
+        
+        Some(Transaction {
+            date,
+            transaction_type,
+            description: self.cell_to_string_option(&row[2]),
+            credit: self.cell_to_decimal(&row[3]),
+            debit: self.cell_to_decimal(&row[4]),
+            origin: sheet_name.to_string(),
+            currency: optional_columns.currency
+                .and_then(|column| row.get(column))
+                .and_then(|cell| self.cell_to_string_option(cell)),
+            tags: optional_columns.tags
+                .and_then(|column| row.get(column))
+                .and_then(|cell| self.cell_to_string_option(cell)),
+            row: Some(row_number),
+            invalid_amount: row[3..5].iter()
+                .zip(4..)
+                .find_map(|(cell, col)| self.invalid_amount(cell)
+                    .map(|reason| ExcelError::DataConversion {
+                        sheet_name: sheet_name.to_string(),
+                        row: row_number,
+                        col,
+                        reason,
+                    }.to_string())),
+        })
     }
     
     /// Read reference sheet data (non-accounting)
@@ -156,7 +331,8 @@ impl ExcelProcessor {
             DataType::Float(f) => f.to_string(),
             DataType::Int(i) => i.to_string(),
             DataType::Bool(b) => b.to_string(),
-            DataType::DateTime(dt) => dt.to_string(),
+            // Dates as YYYY-MM-DD, so reference tables type the column as DATE
+            DataType::DateTime(_) => self.cell_to_date(cell).map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default(),
             DataType::Error(_) => String::new(),
             DataType::Empty => String::new(),
         }
@@ -176,11 +352,8 @@ impl ExcelProcessor {
     fn cell_to_date(&self, cell: &DataType) -> Option<NaiveDate> {
         match cell {
             DataType::DateTime(dt) => Some(dt.date()),
-            DataType::Float(f) => {
-                // Excel date serial number
-                let base_date = NaiveDate::from_ymd_opt(1900, 1, 1)?;
-                base_date.checked_add_signed(chrono::Duration::days(*f as i64 - 2))
-            }
+            DataType::Float(f) => self.date_system.serial_date(*f),
+            DataType::Int(i) => self.date_system.serial_date(*i as f64),
             DataType::String(s) => {
                 // Try to parse various date formats
                 self.parse_date_string(s)
@@ -189,35 +362,59 @@ impl ExcelProcessor {
         }
     }
     
-    /// Convert cell to float
-    fn cell_to_float(&self, cell: &DataType) -> Option<f64> {
+    /// Convert cell to a decimal amount; text is read with the locale's separators
+    fn cell_to_decimal(&self, cell: &DataType) -> Option<Decimal> {
         match cell {
-            DataType::Float(f) => Some(*f),
-            DataType::Int(i) => Some(*i as f64),
-            DataType::String(s) => s.parse().ok(),
+            DataType::Float(f) => money::from_f64(*f),
+            DataType::Int(i) => Some(Decimal::from(*i)),
+            DataType::String(s) => self.locale.parse_amount(s).ok(),
+            _ => None,
+        }
+    }
+    
+    /// Why an amount cell holds something other than a number
+    fn invalid_amount(&self, cell: &DataType) -> Option<String> {
+        match cell {
+            DataType::String(s) if !s.trim().is_empty() => self.locale.parse_amount(s).err(),
+            DataType::Error(e) => Some(format!("cell error {}", e)),
             _ => None,
         }
     }
     
     /// Parse date from string
     fn parse_date_string(&self, s: &str) -> Option<NaiveDate> {
-        // Try common date formats
-        let formats = [
-            "%Y-%m-%d",
-            "%d/%m/%Y",
-            "%m/%d/%Y",
-            "%d-%m-%Y",
-            "%Y/%m/%d",
-        ];
-        
-        for format in &formats {
-            if let Ok(date) = NaiveDate::parse_from_str(s, format) {
-                return Some(date);
-            }
+        parse_date(s)
+    }
+}
+
+/// Sheet row (counted from 1) of a range's first row; leading empty rows are not in the range
+fn first_row(range: &Range<DataType>) -> usize {
+    range.start().map_or(1, |(row, _)| row as usize + 1)
+}
+
+/// Parse a date in any of the common spreadsheet formats
+pub(crate) fn parse_date(s: &str) -> Option<NaiveDate> {
+    // Try common date formats
+    let formats = [
+        "%Y-%m-%d",
+        "%d/%m/%Y",
+        "%m/%d/%Y",
+        "%d-%m-%Y",
+        "%Y/%m/%d",
+    ];
+    
+    for format in &formats {
+        if let Ok(date) = NaiveDate::parse_from_str(s, format) {
+            return Some(date);
         }
-        
-        None
     }
+    
+    None
+}
+
+/// Header of the optional currency column of accounting sheets
+pub(crate) fn is_currency_header(name: &str) -> bool {
+    matches!(name.trim().to_lowercase().as_str(), "moeda" | "currency")
 }
 
 /// Trait for Excel reading operations
@@ -226,9 +423,23 @@ pub trait ExcelReader {
     where
         Self: Sized;
     
+    fn sheet_names(&self) -> Vec<String>;
     fn read_guiding_sheet(&mut self, sheet_name: &str) -> Result<Vec<SheetConfig>, PdwError>;
     fn read_accounting_sheet(&mut self, sheet_name: &str) -> Result<Vec<Transaction>, PdwError>;
     fn read_reference_sheet(&mut self, sheet_name: &str) -> Result<Vec<Vec<String>>, PdwError>;
+    
+    /// Accounting entries handed over in chunks of at most `chunk_rows`; returns the entries read.
+    /// Readers without a lower-memory path read the whole sheet first.
+    fn read_accounting_chunks(&mut self, sheet_name: &str, chunk_rows: usize,
+                              on_chunk: &mut dyn FnMut(Vec<Transaction>) -> Result<(), PdwError>) -> Result<usize, PdwError> {
+        let transactions = self.read_accounting_sheet(sheet_name)?;
+        let read = transactions.len();
+        let mut remaining = transactions.into_iter().peekable();
+        while remaining.peek().is_some() {
+            on_chunk(remaining.by_ref().take(chunk_rows.max(1)).collect())?;
+        }
+        Ok(read)
+    }
 }
 
 impl ExcelReader for ExcelProcessor {
@@ -236,6 +447,10 @@ impl ExcelReader for ExcelProcessor {
         Self::new(path)
     }
     
+    fn sheet_names(&self) -> Vec<String> {
+        self.get_sheet_names()
+    }
+    
     fn read_guiding_sheet(&mut self, sheet_name: &str) -> Result<Vec<SheetConfig>, PdwError> {
         self.read_guiding_sheet(sheet_name)
     }
@@ -247,11 +462,17 @@ impl ExcelReader for ExcelProcessor {
     fn read_reference_sheet(&mut self, sheet_name: &str) -> Result<Vec<Vec<String>>, PdwError> {
         self.read_reference_sheet(sheet_name)
     }
+    
+    fn read_accounting_chunks(&mut self, sheet_name: &str, chunk_rows: usize,
+                              on_chunk: &mut dyn FnMut(Vec<Transaction>) -> Result<(), PdwError>) -> Result<usize, PdwError> {
+        self.read_accounting_chunks(sheet_name, chunk_rows, on_chunk)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use calamine::open_workbook;
     use tempfile::TempDir;
     use std::fs;
     
@@ -262,6 +483,8 @@ mod tests {
                 // Create a mock workbook for testing
                 panic!("Test requires a valid Excel file");
             }),
+            date_system: DateSystem::default(),
+            locale: Locale::default(),
         };
         
         // Test string conversion
@@ -270,11 +493,19 @@ mod tests {
         
         // Test float conversion
         let cell = DataType::Float(123.45);
-        assert_eq!(processor.cell_to_float(&cell), Some(123.45));
+        assert_eq!(processor.cell_to_decimal(&cell), Some(Decimal::new(12345, 2)));
         
         // Test empty cell
         let cell = DataType::Empty;
         assert_eq!(processor.cell_to_string(&cell), "");
+        
+        // Text amounts follow the locale's separators
+        let cell = DataType::String("1.234,56".to_string());
+        assert_eq!(processor.cell_to_decimal(&cell), Some(Decimal::new(123456, 2)));
+        assert_eq!(processor.invalid_amount(&cell), None);
+        let cell = DataType::String("1,234.56".to_string());
+        assert_eq!(processor.cell_to_decimal(&cell), None);
+        assert!(processor.invalid_amount(&cell).is_some());
     }
     
     #[test]
@@ -283,6 +514,8 @@ mod tests {
             workbook: open_workbook("test.xlsx").unwrap_or_else(|_| {
                 panic!("Test requires a valid Excel file");
             }),
+            date_system: DateSystem::default(),
+            locale: Locale::default(),
         };
         
         // Test date string parsing
@@ -309,18 +542,172 @@ mod tests {
         assert!(config.is_loadable);
     }
     
+    #[test]
+    fn test_serial_dates_across_epochs() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+        let excel1900 = DateSystem::Excel1900;
+        assert_eq!(excel1900.serial_date(0.0), None);
+        assert_eq!(excel1900.serial_date(1.0), date(1900, 1, 1));
+        assert_eq!(excel1900.serial_date(59.0), date(1900, 2, 28));
+        // 29/02/1900 never was, and the days after it are one serial later
+        assert_eq!(excel1900.serial_date(60.0), None);
+        assert_eq!(excel1900.serial_date(61.0), date(1900, 3, 1));
+        assert_eq!(excel1900.serial_date(45306.75), date(2024, 1, 15));
+        assert_eq!(excel1900.serial_date(-1.0), None);
+        assert_eq!(excel1900.serial_date(f64::NAN), None);
+        
+        let excel1904 = DateSystem::Excel1904;
+        assert_eq!(excel1904.serial_date(0.0), date(1904, 1, 1));
+        assert_eq!(excel1904.serial_date(59.0), date(1904, 2, 29));
+        assert_eq!(excel1904.serial_date(45306.0 - 1462.0), date(2024, 1, 15));
+        assert_eq!(excel1904.serial_date(-0.5), None);
+        
+        assert!(declares_date1904("<workbook><workbookPr date1904=\"1\" defaultThemeVersion=\"124226\"/></workbook>"));
+        assert!(declares_date1904("<workbookPr date1904='true'/>"));
+        assert!(!declares_date1904("<workbookPr date1904=\"0\"/>"));
+        assert!(!declares_date1904("<workbookPr defaultThemeVersion=\"124226\"/>"));
+        
+        // Workbooks written without date1904 stay on the 1900 system
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("serials.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        workbook.add_worksheet().write_number(0, 0, 45306.0).unwrap();
+        workbook.save(&path).unwrap();
+        assert_eq!(DateSystem::of_workbook(&path), DateSystem::Excel1900);
+        assert_eq!(DateSystem::of_workbook(Path::new("PDW.ods")), DateSystem::Excel1900);
+    }
+    
     #[test]
     fn test_transaction_creation() {
         let transaction = Transaction {
             date: Some(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()),
             transaction_type: Some("ALM".to_string()),
             description: Some("Test transaction".to_string()),
-            credit: Some(100.0),
+            credit: Some(Decimal::ONE_HUNDRED),
             debit: None,
             origin: "TestSheet".to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
         };
         
         assert!(transaction.date.is_some());
         assert_eq!(transaction.origin, "TestSheet");
     }
+    
+    #[test]
+    fn test_accounting_sheet_in_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("chunks.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Conta").unwrap();
+        let rows = [
+            ["Data", "TIPO", "DESCRICAO", "Credito", "Debito", "Moeda", "Tags"],
+            ["2024-01-15", "ALM", "Mercado", "", "10", "BRL", ""],
+            ["", "", "subtotal", "", "10", "", ""],
+            ["2024-01-16", "SAL", "Salario", "1000", "", "BRL", ""],
+            ["2024-01-17", "LAZ", "Cinema", "", "45.5", "USD", "viagem-2024; ferias"],
+        ];
+        for (row, values) in rows.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                sheet.write_string(row as u32, col as u16, *value).unwrap();
+            }
+        }
+        workbook.save(&path).unwrap();
+        
+        let mut processor = ExcelProcessor::new(&path).unwrap();
+        let mut chunks = Vec::new();
+        let read = processor.read_accounting_chunks("Conta", 2, &mut |chunk| {
+            chunks.push(chunk);
+            Ok(())
+        }).unwrap();
+        
+        assert_eq!(read, 3);
+        assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(chunks[1][0].currency.as_deref(), Some("USD"));
+        assert_eq!(chunks[1][0].tags.as_deref(), Some("viagem-2024; ferias"));
+        let whole = processor.read_accounting_sheet("Conta").unwrap();
+        let descriptions = |transactions: &[Transaction]| transactions.iter().map(|t| t.description.clone()).collect::<Vec<_>>();
+        assert_eq!(descriptions(&whole), descriptions(&chunks.concat()));
+    }
+    
+    #[test]
+    fn test_text_amounts_with_locale() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("texto.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Conta").unwrap();
+        let rows = [
+            ["Data", "TIPO", "DESCRICAO", "Credito", "Debito"],
+            ["2024-01-15", "SAL", "Salario", "1.234,56", ""],
+            ["2024-01-16", "ALM", "Mercado", "", "1,234.56"],
+        ];
+        for (row, values) in rows.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                sheet.write_string(row as u32, col as u16, *value).unwrap();
+            }
+        }
+        workbook.save(&path).unwrap();
+        
+        let entries = ExcelProcessor::new(&path).unwrap().read_accounting_sheet("Conta").unwrap();
+        assert_eq!(entries[0].credit, Some(Decimal::new(123456, 2)));
+        assert_eq!(entries[0].invalid_amount, None);
+        assert_eq!(entries[1].debit, None);
+        assert_eq!(entries[1].invalid_amount.as_deref(), Some(
+            "Data type conversion error in Conta at row 3, column 5: '1,234.56' is not a number with ',' as decimal and '.' as thousands separator"
+        ));
+        
+        let locale: Locale = toml::from_str("decimal_separator = \".\"").unwrap();
+        let entries = ExcelProcessor::new(&path).unwrap().with_locale(locale).read_accounting_sheet("Conta").unwrap();
+        assert_eq!(entries[1].debit, Some(Decimal::new(123456, 2)));
+        assert!(entries[0].invalid_amount.is_some());
+    }
+    
+    #[test]
+    fn test_ods_workbook() {
+        use std::io::Write;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("PDW.ods");
+        let table = |name: &str, rows: &[&[&str]]| -> String {
+            let rows: String = rows.iter().map(|cells| {
+                let cells: String = cells.iter().map(|cell| match cell.parse::<f64>() {
+                    Ok(number) => format!("<table:table-cell office:value-type=\"float\" office:value=\"{}\"><text:p>{}</text:p></table:table-cell>", number, cell),
+                    Err(_) if cell.is_empty() => "<table:table-cell/>".to_string(),
+                    Err(_) => format!("<table:table-cell office:value-type=\"string\"><text:p>{}</text:p></table:table-cell>", cell),
+                }).collect();
+                format!("<table:table-row>{}</table:table-row>", cells)
+            }).collect();
+            format!("<table:table table:name=\"{}\">{}</table:table>", name, rows)
+        };
+        let content = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <office:document-content xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\" \
+             xmlns:table=\"urn:oasis:names:tc:opendocument:xmlns:table:1.0\" \
+             xmlns:text=\"urn:oasis:names:tc:opendocument:xmlns:text:1.0\">\
+             <office:body><office:spreadsheet>{}{}</office:spreadsheet></office:body></office:document-content>",
+            table("GUIDING", &[&["TABLE_NAME", "ACCOUNTING", "LOADABLE"], &["Conta", "X", "X"], &["TiposLancamentos", "", "X"]]),
+            table("Conta", &[&["Data", "TIPO", "DESCRICAO", "Credito", "Debito"],
+                             &["2024-01-15", "ALM", "Mercado", "", "10.5"],
+                             &["2024-01-16", "SAL", "Salario", "1000", ""]]),
+        );
+        
+        let mut archive = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        archive.start_file("mimetype", zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored)).unwrap();
+        archive.write_all(b"application/vnd.oasis.opendocument.spreadsheet").unwrap();
+        archive.start_file("content.xml", zip::write::FileOptions::default()).unwrap();
+        archive.write_all(content.as_bytes()).unwrap();
+        archive.finish().unwrap();
+        
+        let mut processor = ExcelProcessor::new(&path).unwrap();
+        assert_eq!(processor.get_sheet_names(), vec!["GUIDING", "Conta"]);
+        let guiding = processor.read_guiding_sheet("GUIDING").unwrap();
+        assert_eq!((guiding[0].is_accounting, guiding[1].is_accounting), (true, false));
+        
+        let transactions = processor.read_accounting_sheet("Conta").unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].debit, Some(Decimal::new(105, 1)));
+        assert_eq!(transactions[1].date, NaiveDate::from_ymd_opt(2024, 1, 16));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,154 @@
+/*!
+# Export Profiles Module
+
+Named column selections (`[export_profiles.<name>]`), so the columns each
+reader of the exports gets are chosen in the configuration instead of by
+editing SQL:
+
+```toml
+[export_profiles.accountant]
+columns = ["Data", "TIPO", "DESCRICAO", "Credito", "Debito"]
+headers = { Data = "Quando", DESCRICAO = "Descricao" }
+
+[export_profiles.analysis]
+columns = []    # every column of the entries table, enrichments included
+```
+
+`settings.export_profile` picks the profile of the general entries export
+(CSV, JSON, XML and Parquet alike), which otherwise keeps its formatted
+Portuguese layout; profile columns are written as stored. A YAML query naming a
+`profile:` keeps the profile's columns of its result, or of the general entries
+table, newest first, when its `sql` is left out.
+*/
+
+use crate::database::quote_identifier;
+use crate::error::{ConfigError, PdwError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Profiles by name
+pub type ExportProfiles = BTreeMap<String, ExportProfile>;
+
+/// Columns an export keeps, in order, and the headers they are written under
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExportProfile {
+    /// Columns in export order; empty for every column
+    #[serde(default)]
+    pub columns: Vec<String>,
+    /// Header written instead of a column's name
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl ExportProfile {
+    /// SELECT list of the profile's columns
+    pub fn select_list(&self) -> String {
+        if self.columns.is_empty() {
+            return "*".to_string();
+        }
+        self.columns.iter()
+            .map(|column| match self.headers.get(column) {
+                Some(header) => format!("{} AS {}", quote_identifier(column), quote_identifier(header)),
+                None => quote_identifier(column),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+    
+    /// Profile columns missing from `available` (case ignored)
+    pub fn unknown_columns(&self, available: &[String]) -> Vec<String> {
+        self.columns.iter()
+            .filter(|column| !available.iter().any(|name| name.eq_ignore_ascii_case(column)))
+            .cloned()
+            .collect()
+    }
+    
+    /// The profile's columns of a query's result, or of the general entries table for an empty query
+    pub fn apply(&self, sql: &str) -> String {
+        let sql = sql.trim().trim_end_matches(';');
+        if sql.is_empty() {
+            format!("SELECT {} FROM {{entries_table}} ORDER BY Data DESC", self.select_list())
+        } else {
+            format!("SELECT {} FROM ({})", self.select_list(), sql)
+        }
+    }
+}
+
+/// The profile called `name`
+pub fn find<'a>(profiles: &'a ExportProfiles, name: &str) -> Result<&'a ExportProfile, PdwError> {
+    profiles.get(name).ok_or_else(|| ConfigError::InvalidFormat {
+        message: format!(
+            "Unknown export profile '{}' (defined: {})",
+            name,
+            profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    }.into())
+}
+
+/// Profiles listing a blank column, which no table has
+pub fn check(profiles: &ExportProfiles) -> Result<(), PdwError> {
+    match profiles.iter().find(|(_, profile)| profile.columns.iter().any(|column| column.trim().is_empty())) {
+        Some((name, _)) => Err(ConfigError::InvalidFormat {
+            message: format!("export_profiles.{} lists a blank column", name),
+        }.into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PdwConfig;
+    use crate::database::DatabaseManager;
+    use crate::reporting::{QueryConfig, ReportGenerator};
+    use std::collections::HashMap;
+    
+    #[test]
+    fn test_profiles_select_columns() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_out = temp_dir.path().to_path_buf();
+        config.export_profiles.insert("accountant".to_string(), ExportProfile {
+            columns: vec!["Data".to_string(), "TIPO".to_string(), "Debito".to_string()],
+            headers: BTreeMap::from([("Data".to_string(), "Quando".to_string())]),
+        });
+        config.settings.export_profile = Some("accountant".to_string());
+        let database = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        database.create_tables().unwrap();
+        database.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem)
+             VALUES ('2024-01-15', 'ALM', 'Mercado', 0, 42.5, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem)
+             VALUES ('2024-01-16', 'SAL', 'Salario', 1000, 0, 'Conta');"
+        ).unwrap();
+        
+        // A query without SQL reads the entries table, one with SQL is narrowed down
+        let queries: QueryConfig = serde_yaml::from_str(
+            "queries_padrao:
+  - sheet_name: Contador
+    profile: accountant
+  - sql: SELECT TIPO, Debito, Data FROM {entries_table} WHERE Debito > 0
+    sheet_name: Gastos
+    profile: accountant"
+        ).unwrap();
+        let queries = queries.with_profiles(&config.export_profiles).unwrap();
+        let variables = HashMap::from([("entries_table".to_string(), "LANCAMENTOS_GERAIS".to_string())]);
+        let rows: Vec<_> = queries.queries_padrao.iter()
+            .map(|query| database.execute_query(&query.expand(&variables).sql).unwrap())
+            .collect();
+        assert_eq!(rows[0].len(), 2);
+        assert_eq!(rows[0][0][1], "SAL");
+        assert_eq!(rows[1], vec![vec![serde_json::json!("2024-01-15"), serde_json::json!("ALM"), serde_json::json!(42.5)]]);
+        assert_eq!(database.query_columns(&queries.queries_padrao[1].expand(&variables).sql).unwrap(), vec!["Quando", "TIPO", "Debito"]);
+        
+        let generator = ReportGenerator::new(database, config.clone());
+        generator.export_general_entries().unwrap();
+        let csv = std::fs::read_to_string(temp_dir.path().join("LANCAMENTOS_GERAIS.csv")).unwrap();
+        assert_eq!(csv.lines().next(), Some("2024-01-16;SAL;0,0"));
+        
+        config.export_profiles.get_mut("accountant").unwrap().columns.push("Conta".to_string());
+        assert!(ReportGenerator::new(DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap(), config)
+            .export_general_entries().is_err());
+        assert!(find(&ExportProfiles::new(), "analysis").is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,331 @@
+/*!
+# Export Schema Module
+
+Versioned layout of the JSON, XML and Parquet exports, so programs reading them
+keep working across PDW releases. Version 3 files carry their version and column
+names: JSON is `{"schema_version": 3, "columns": [...], "rows": [[...]]}`, XML a
+`<data schema_version="3">` element with a `<columns>` list before the
+`<item>` rows, and Parquet files hold the version in the
+`pdw.export_schema_version` key of their metadata. Version 2 is the earlier
+layout without either: a JSON array of rows and `<colN>` XML items.
+
+`settings.export_schema_version` pins the version exports are written in, and
+`pdw convert-export` rewrites an existing JSON or XML export (gzipped or not) in
+another version. Each step between consecutive versions has an upgrade and a
+downgrade; a release renaming or adding columns adds the next step, so files of
+every earlier version stay convertible.
+*/
+
+use crate::error::{PdwError, ReportError};
+use crate::reporting::xml_escape;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Version written by this release
+pub const EXPORT_SCHEMA_VERSION: u32 = 3;
+
+/// Oldest version files can be converted from or to
+pub const OLDEST_EXPORT_SCHEMA_VERSION: u32 = 2;
+
+/// Parquet metadata key holding the version
+pub const PARQUET_VERSION_KEY: &str = "pdw.export_schema_version";
+
+/// Columns of the general entries export, which version 2 files do not name
+pub const GENERAL_ENTRIES_COLUMNS: [&str; 11] = [
+    "Quando", "Dia da Semana", "Tipo", "Descricao/Lancamento", "Credito", "Debito",
+    "Mes", "Ano", "Mes(Por Extenso)", "Ano/Mes", "Origem",
+];
+
+/// Rows of an export in a given schema version, whatever the file format
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportDocument {
+    pub version: u32,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Change between a version and the next one
+struct Step {
+    /// Version the step leads to
+    to: u32,
+    upgrade: fn(&mut ExportDocument),
+    downgrade: fn(&mut ExportDocument),
+}
+
+/// Every step, oldest first
+const STEPS: &[Step] = &[
+    Step { to: 3, upgrade: name_columns, downgrade: number_columns },
+];
+
+/// Output format, from the file extension (`.gz` ignored)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Xml,
+}
+
+impl ExportFormat {
+    /// Format and whether the file is gzipped
+    pub fn of(path: &Path) -> Result<(Self, bool), PdwError> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+        let gzipped = name.ends_with(".gz");
+        let name = name.trim_end_matches(".gz");
+        if name.ends_with(".json") {
+            Ok((ExportFormat::Json, gzipped))
+        } else if name.ends_with(".xml") {
+            Ok((ExportFormat::Xml, gzipped))
+        } else {
+            Err(ReportError::UnsupportedFormat {
+                format: format!("{} (convert-export reads .json and .xml exports, optionally .gz)", path.display()),
+            }.into())
+        }
+    }
+}
+
+impl ExportDocument {
+    /// Query results in the current version
+    pub fn new(columns: Vec<String>, rows: Vec<Vec<Value>>) -> Self {
+        Self { version: EXPORT_SCHEMA_VERSION, columns, rows }
+    }
+    
+    /// The same rows in another version
+    pub fn converted(mut self, target: u32) -> Result<Self, PdwError> {
+        check_version(target)?;
+        while self.version < target {
+            let step = STEPS.iter().find(|step| step.to == self.version + 1).ok_or_else(|| unsupported(self.version + 1))?;
+            (step.upgrade)(&mut self);
+            self.version = step.to;
+        }
+        while self.version > target {
+            let step = STEPS.iter().find(|step| step.to == self.version).ok_or_else(|| unsupported(self.version))?;
+            (step.downgrade)(&mut self);
+            self.version = step.to - 1;
+        }
+        Ok(self)
+    }
+    
+    /// JSON text in the document's version layout
+    pub fn to_json(&self) -> Result<String, PdwError> {
+        let document = if self.version >= 3 {
+            json!({ "schema_version": self.version, "columns": self.columns, "rows": self.rows })
+        } else {
+            json!(self.rows)
+        };
+        serde_json::to_string_pretty(&document).map_err(|e| ReportError::JsonSerialization(e).into())
+    }
+    
+    /// XML text in the document's version layout
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        if self.version >= 3 {
+            xml.push_str(&format!("<data schema_version=\"{}\">\n   <columns>\n", self.version));
+            for (idx, column) in self.columns.iter().enumerate() {
+                xml.push_str(&format!("      <column index=\"{}\">{}</column>\n", idx + 1, xml_escape(column)));
+            }
+            xml.push_str("   </columns>\n");
+        } else {
+            xml.push_str("<data>\n");
+        }
+        
+        for row in &self.rows {
+            xml.push_str("   <item>\n");
+            for (idx, cell_value) in row.iter().enumerate() {
+                let value = match cell_value {
+                    Value::String(s) => xml_escape(s),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    Value::Null => String::new(),
+                    _ => xml_escape(&cell_value.to_string()),
+                };
+                xml.push_str(&format!("      <col{}>{}</col{}>\n", idx + 1, value, idx + 1));
+            }
+            xml.push_str("   </item>\n");
+        }
+        
+        xml.push_str("</data>\n");
+        xml
+    }
+    
+    /// Read a JSON export of any supported version
+    pub fn from_json(text: &str) -> Result<Self, PdwError> {
+        let parsed: Value = serde_json::from_str(text).map_err(ReportError::JsonSerialization)?;
+        let rows = |value: &Value| -> Vec<Vec<Value>> {
+            value.as_array().map(|rows| rows.iter().map(|row| row.as_array().cloned().unwrap_or_default()).collect())
+                .unwrap_or_default()
+        };
+        
+        match &parsed {
+            Value::Array(_) => {
+                let rows = rows(&parsed);
+                Ok(Self { version: 2, columns: numbered_columns(width(&rows)), rows })
+            }
+            Value::Object(document) => {
+                let version = document.get("schema_version").and_then(Value::as_u64).ok_or_else(|| invalid("JSON", "no schema_version"))? as u32;
+                check_version(version)?;
+                let columns = document.get("columns").and_then(Value::as_array)
+                    .map(|columns| columns.iter().map(|c| c.as_str().unwrap_or_default().to_string()).collect())
+                    .unwrap_or_default();
+                Ok(Self { version, columns, rows: document.get("rows").map(rows).unwrap_or_default() })
+            }
+            _ => Err(invalid("JSON", "neither a row array nor an export object")),
+        }
+    }
+    
+    /// Read an XML export of any supported version; values come back as text
+    pub fn from_xml(text: &str) -> Result<Self, PdwError> {
+        let data = Regex::new(r#"<data(?:\s+schema_version="(\d+)")?\s*>"#).expect("valid regex");
+        let column = Regex::new(r"<column[^>]*>(.*?)</column>").expect("valid regex");
+        let item = Regex::new(r"(?s)<item>(.*?)</item>").expect("valid regex");
+        let cell = Regex::new(r"<col\d+>(.*?)</col\d+>|<col\d+\s*/>").expect("valid regex");
+        
+        let captures = data.captures(text).ok_or_else(|| invalid("XML", "no <data> element"))?;
+        let version = match captures.get(1) {
+            Some(version) => version.as_str().parse().map_err(|_| invalid("XML", "bad schema_version"))?,
+            None => 2,
+        };
+        check_version(version)?;
+        
+        let rows: Vec<Vec<Value>> = item.captures_iter(text)
+            .map(|item| cell.captures_iter(&item[1])
+                .map(|cell| match cell.get(1).map(|value| xml_unescape(value.as_str())) {
+                    Some(value) if !value.is_empty() => Value::String(value),
+                    _ => Value::Null,
+                })
+                .collect())
+            .collect();
+        let columns = if version >= 3 {
+            column.captures_iter(text).map(|column| xml_unescape(&column[1])).collect()
+        } else {
+            numbered_columns(width(&rows))
+        };
+        Ok(Self { version, columns, rows })
+    }
+}
+
+/// Rewrite a JSON or XML export in another version; returns the version it was in
+pub fn convert_file(input: &Path, output: &Path, target: u32) -> Result<u32, PdwError> {
+    let (input_format, input_gzipped) = ExportFormat::of(input)?;
+    let (output_format, output_gzipped) = ExportFormat::of(output)?;
+    
+    let mut text = String::new();
+    let file = std::fs::File::open(input)?;
+    if input_gzipped {
+        flate2::read::GzDecoder::new(file).read_to_string(&mut text)?;
+    } else {
+        std::io::BufReader::new(file).read_to_string(&mut text)?;
+    }
+    
+    let document = match input_format {
+        ExportFormat::Json => ExportDocument::from_json(&text)?,
+        ExportFormat::Xml => ExportDocument::from_xml(&text)?,
+    };
+    let source_version = document.version;
+    let document = document.converted(target)?;
+    let text = match output_format {
+        ExportFormat::Json => document.to_json()?,
+        ExportFormat::Xml => document.to_xml(),
+    };
+    
+    let file = std::fs::File::create(output)?;
+    if output_gzipped {
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        encoder.write_all(text.as_bytes())?;
+        encoder.finish()?;
+    } else {
+        std::io::BufWriter::new(file).write_all(text.as_bytes())?;
+    }
+    Ok(source_version)
+}
+
+/// Error unless files can be converted to or from the version
+pub fn check_version(version: u32) -> Result<(), PdwError> {
+    if (OLDEST_EXPORT_SCHEMA_VERSION..=EXPORT_SCHEMA_VERSION).contains(&version) {
+        Ok(())
+    } else {
+        Err(unsupported(version))
+    }
+}
+
+/// 2 -> 3: name the columns of general entries exports (others keep col1..colN)
+fn name_columns(document: &mut ExportDocument) {
+    let numbered = document.columns == numbered_columns(document.columns.len());
+    if numbered && document.columns.len() == GENERAL_ENTRIES_COLUMNS.len() {
+        document.columns = GENERAL_ENTRIES_COLUMNS.iter().map(|column| column.to_string()).collect();
+    }
+}
+
+/// 3 -> 2: version 2 files had no column names
+fn number_columns(document: &mut ExportDocument) {
+    document.columns = numbered_columns(document.columns.len());
+}
+
+fn numbered_columns(count: usize) -> Vec<String> {
+    (1..=count).map(|idx| format!("col{}", idx)).collect()
+}
+
+fn width(rows: &[Vec<Value>]) -> usize {
+    rows.iter().map(Vec::len).max().unwrap_or(0)
+}
+
+fn xml_unescape(input: &str) -> String {
+    input.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn unsupported(version: u32) -> PdwError {
+    ReportError::UnsupportedFormat {
+        format: format!(
+            "export schema version {} (this PDW converts versions {} to {})",
+            version, OLDEST_EXPORT_SCHEMA_VERSION, EXPORT_SCHEMA_VERSION
+        ),
+    }.into()
+}
+
+fn invalid(format: &str, reason: &str) -> PdwError {
+    ReportError::OutputGeneration { format: format.to_string(), reason: format!("not a PDW export: {}", reason) }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_export_versions_round_trip() {
+        let mut row = vec![json!("15/01/2024"), json!("Segunda-feira"), json!("ALM"), json!("Feira & <Cia>"), Value::Null, json!("10,5")];
+        row.extend(["'01", "'2024", "'01-Janeiro", "'2024/01", "Conta"].map(|value| json!(value)));
+        let current = ExportDocument::new(GENERAL_ENTRIES_COLUMNS.iter().map(|c| c.to_string()).collect(), vec![row.clone()]);
+        
+        let json_text = current.to_json().unwrap();
+        assert!(json_text.contains("\"schema_version\": 3"));
+        assert_eq!(ExportDocument::from_json(&json_text).unwrap(), current);
+        
+        // Downstream v2 readers get the bare row arrays back, and the names return on upgrade
+        let legacy = current.clone().converted(2).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&legacy.to_json().unwrap()).unwrap(), json!([row]));
+        assert_eq!(ExportDocument::from_json(&legacy.to_json().unwrap()).unwrap().converted(3).unwrap(), current);
+        
+        let xml = current.to_xml();
+        assert!(xml.contains("<data schema_version=\"3\">") && xml.contains("<column index=\"4\">Descricao/Lancamento</column>"));
+        let read = ExportDocument::from_xml(&xml).unwrap();
+        assert_eq!((read.version, &read.columns), (3, &current.columns));
+        assert_eq!((read.rows[0][3].clone(), read.rows[0][4].clone()), (json!("Feira & <Cia>"), Value::Null));
+        assert_eq!(ExportDocument::from_xml(&legacy.to_xml()).unwrap().columns[0], "col1");
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input = temp_dir.path().join("LANCAMENTOS_GERAIS.v2.xml");
+        let output = temp_dir.path().join("converted.json.gz");
+        std::fs::write(&input, legacy.to_xml()).unwrap();
+        assert_eq!(convert_file(&input, &output, 3).unwrap(), 2);
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&output).unwrap()).read_to_string(&mut text).unwrap();
+        assert_eq!(ExportDocument::from_json(&text).unwrap().columns, current.columns);
+        
+        assert!(current.converted(4).is_err());
+        assert!(ExportFormat::of(Path::new("entries.csv")).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,585 @@
+/*!
+# Expression Module
+
+Small expression language used by config-defined derived columns.
+Supports arithmetic over numeric columns, string literals, parentheses and a
+handful of functions (`upper`, `lower`, `trim`, `abs`, `round`, `concat`,
+`coalesce`), evaluated against a processed transaction.
+*/
+
+use crate::database::ProcessedTransaction;
+use crate::error::{ConfigError, EtlError, PdwError};
+use crate::money;
+use serde::{Deserialize, Serialize};
+
+/// Value produced by a derived column expression
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DerivedValue {
+    Number(f64),
+    Text(String),
+    Null,
+}
+
+/// Static type of an expression result, used for the SQLite column type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Number,
+    Text,
+}
+
+/// Parsed expression tree
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(f64),
+    Text(String),
+    Column(Column),
+    Negate(Box<Expression>),
+    Binary {
+        op: BinaryOp,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Call {
+        function: Function,
+        args: Vec<Expression>,
+    },
+}
+
+/// Arithmetic operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// Built-in functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Upper,
+    Lower,
+    Trim,
+    Abs,
+    Round,
+    Concat,
+    Coalesce,
+}
+
+/// Transaction columns addressable from expressions (named after the database columns)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Data,
+    DiaSemana,
+    Tipo,
+    Descricao,
+    Credito,
+    Debito,
+    Mes,
+    Ano,
+    MesExtenso,
+    AnoMes,
+    Origem,
+}
+
+/// Compiled derived column definition
+#[derive(Debug, Clone)]
+pub struct DerivedColumn {
+    pub name: String,
+    pub expression: Expression,
+}
+
+impl DerivedValue {
+    /// Numeric view of the value, if any
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            DerivedValue::Number(n) => Some(*n),
+            DerivedValue::Text(s) => s.trim().parse().ok(),
+            DerivedValue::Null => None,
+        }
+    }
+    
+    /// Text view of the value
+    pub fn as_text(&self) -> String {
+        match self {
+            DerivedValue::Number(n) => n.to_string(),
+            DerivedValue::Text(s) => s.clone(),
+            DerivedValue::Null => String::new(),
+        }
+    }
+}
+
+impl rusqlite::ToSql for DerivedValue {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        use rusqlite::types::{ToSqlOutput, Value, ValueRef};
+        Ok(match self {
+            DerivedValue::Number(n) => ToSqlOutput::Owned(Value::Real(*n)),
+            DerivedValue::Text(s) => ToSqlOutput::Borrowed(ValueRef::Text(s.as_bytes())),
+            DerivedValue::Null => ToSqlOutput::Owned(Value::Null),
+        })
+    }
+}
+
+impl ValueKind {
+    /// SQLite column type for this kind
+    pub fn sql_type(&self) -> &'static str {
+        match self {
+            ValueKind::Number => "REAL",
+            ValueKind::Text => "TEXT",
+        }
+    }
+}
+
+impl Column {
+    /// Resolve a column by name (case-insensitive)
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "DATA" => Some(Column::Data),
+            "DIA_SEMANA" => Some(Column::DiaSemana),
+            "TIPO" => Some(Column::Tipo),
+            "DESCRICAO" => Some(Column::Descricao),
+            "CREDITO" => Some(Column::Credito),
+            "DEBITO" => Some(Column::Debito),
+            "MES" => Some(Column::Mes),
+            "ANO" => Some(Column::Ano),
+            "MES_EXTENSO" => Some(Column::MesExtenso),
+            "ANOMES" => Some(Column::AnoMes),
+            "ORIGEM" => Some(Column::Origem),
+            _ => None,
+        }
+    }
+    
+    fn kind(&self) -> ValueKind {
+        match self {
+            Column::Credito | Column::Debito => ValueKind::Number,
+            _ => ValueKind::Text,
+        }
+    }
+    
+    fn value(&self, transaction: &ProcessedTransaction) -> DerivedValue {
+        match self {
+            Column::Data => DerivedValue::Text(transaction.date.format("%Y-%m-%d").to_string()),
+            Column::DiaSemana => DerivedValue::Text(transaction.day_of_week.clone()),
+            Column::Tipo => DerivedValue::Text(transaction.transaction_type.clone()),
+            Column::Descricao => DerivedValue::Text(transaction.description.clone()),
+            Column::Credito => DerivedValue::Number(money::to_f64(transaction.credit)),
+            Column::Debito => DerivedValue::Number(money::to_f64(transaction.debit)),
+            Column::Mes => DerivedValue::Text(transaction.month.clone()),
+            Column::Ano => DerivedValue::Text(transaction.year.clone()),
+            Column::MesExtenso => DerivedValue::Text(transaction.month_name.clone()),
+            Column::AnoMes => DerivedValue::Text(transaction.year_month.clone()),
+            Column::Origem => DerivedValue::Text(transaction.origin.clone()),
+        }
+    }
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "upper" => Some(Function::Upper),
+            "lower" => Some(Function::Lower),
+            "trim" => Some(Function::Trim),
+            "abs" => Some(Function::Abs),
+            "round" => Some(Function::Round),
+            "concat" => Some(Function::Concat),
+            "coalesce" => Some(Function::Coalesce),
+            _ => None,
+        }
+    }
+    
+    /// Allowed argument count range
+    fn arity(&self) -> (usize, usize) {
+        match self {
+            Function::Upper | Function::Lower | Function::Trim | Function::Abs => (1, 1),
+            Function::Round => (1, 2),
+            Function::Concat | Function::Coalesce => (1, usize::MAX),
+        }
+    }
+}
+
+impl Expression {
+    /// Parse an expression from source text
+    pub fn parse(source: &str) -> Result<Self, PdwError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, position: 0, source };
+        let expression = parser.parse_expression()?;
+        
+        if parser.position < parser.tokens.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        
+        Ok(expression)
+    }
+    
+    /// Static result type of the expression
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Expression::Number(_) | Expression::Negate(_) | Expression::Binary { .. } => ValueKind::Number,
+            Expression::Text(_) => ValueKind::Text,
+            Expression::Column(column) => column.kind(),
+            Expression::Call { function, args } => match function {
+                Function::Abs | Function::Round => ValueKind::Number,
+                Function::Coalesce => args.first().map(|a| a.kind()).unwrap_or(ValueKind::Text),
+                _ => ValueKind::Text,
+            },
+        }
+    }
+    
+    /// Evaluate the expression against a transaction
+    pub fn evaluate(&self, transaction: &ProcessedTransaction) -> Result<DerivedValue, String> {
+        match self {
+            Expression::Number(n) => Ok(DerivedValue::Number(*n)),
+            Expression::Text(s) => Ok(DerivedValue::Text(s.clone())),
+            Expression::Column(column) => Ok(column.value(transaction)),
+            Expression::Negate(inner) => {
+                let value = inner.evaluate(transaction)?;
+                Ok(value.as_number().map(|n| DerivedValue::Number(-n)).unwrap_or(DerivedValue::Null))
+            }
+            Expression::Binary { op, left, right } => {
+                let left = left.evaluate(transaction)?;
+                let right = right.evaluate(transaction)?;
+                let (l, r) = match (left.as_number(), right.as_number()) {
+                    (Some(l), Some(r)) => (l, r),
+                    _ => return Ok(DerivedValue::Null),
+                };
+                let result = match op {
+                    BinaryOp::Add => l + r,
+                    BinaryOp::Subtract => l - r,
+                    BinaryOp::Multiply => l * r,
+                    BinaryOp::Divide => {
+                        if r == 0.0 {
+                            return Ok(DerivedValue::Null);
+                        }
+                        l / r
+                    }
+                };
+                Ok(DerivedValue::Number(result))
+            }
+            Expression::Call { function, args } => {
+                let values = args.iter()
+                    .map(|a| a.evaluate(transaction))
+                    .collect::<Result<Vec<_>, _>>()?;
+                apply_function(*function, &values)
+            }
+        }
+    }
+}
+
+impl DerivedColumn {
+    /// Compile a derived column from its name and expression source
+    pub fn compile(name: &str, source: &str) -> Result<Self, PdwError> {
+        let valid_name = !name.is_empty()
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            && Column::from_name(name).is_none();
+        
+        if !valid_name {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("Invalid derived column name '{}'", name),
+            }.into());
+        }
+        
+        Ok(Self {
+            name: name.to_string(),
+            expression: Expression::parse(source)?,
+        })
+    }
+    
+    /// Evaluate this column for a transaction
+    pub fn evaluate(&self, transaction: &ProcessedTransaction) -> Result<DerivedValue, PdwError> {
+        self.expression.evaluate(transaction).map_err(|reason| {
+            EtlError::TransformationFailed {
+                stage: format!("derived_columns:{}", self.name),
+                reason,
+            }.into()
+        })
+    }
+}
+
+/// Apply a built-in function to evaluated arguments
+fn apply_function(function: Function, values: &[DerivedValue]) -> Result<DerivedValue, String> {
+    let first = values.first().cloned().unwrap_or(DerivedValue::Null);
+    
+    Ok(match function {
+        Function::Upper => DerivedValue::Text(first.as_text().to_uppercase()),
+        Function::Lower => DerivedValue::Text(first.as_text().to_lowercase()),
+        Function::Trim => DerivedValue::Text(first.as_text().trim().to_string()),
+        Function::Abs => first.as_number().map(|n| DerivedValue::Number(n.abs())).unwrap_or(DerivedValue::Null),
+        Function::Round => {
+            let digits = values.get(1).and_then(|v| v.as_number()).unwrap_or(0.0);
+            if !(0.0..=10.0).contains(&digits) {
+                return Err(format!("round() precision out of range: {}", digits));
+            }
+            let factor = 10f64.powi(digits as i32);
+            first.as_number()
+                .map(|n| DerivedValue::Number((n * factor).round() / factor))
+                .unwrap_or(DerivedValue::Null)
+        }
+        Function::Concat => DerivedValue::Text(values.iter().map(|v| v.as_text()).collect()),
+        Function::Coalesce => values.iter()
+            .find(|v| match v {
+                DerivedValue::Null => false,
+                DerivedValue::Text(s) => !s.is_empty(),
+                DerivedValue::Number(_) => true,
+            })
+            .cloned()
+            .unwrap_or(DerivedValue::Null),
+    })
+}
+
+/// Lexical token
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LParen,
+    RParen,
+    Comma,
+}
+
+/// Split expression source into tokens
+fn tokenize(source: &str) -> Result<Vec<Token>, PdwError> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = source.chars().collect();
+    let mut i = 0;
+    
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' | '\t' | '\n' | '\r' => i += 1,
+            '+' => { tokens.push(Token::Plus); i += 1; }
+            '-' => { tokens.push(Token::Minus); i += 1; }
+            '*' => { tokens.push(Token::Star); i += 1; }
+            '/' => { tokens.push(Token::Slash); i += 1; }
+            '(' => { tokens.push(Token::LParen); i += 1; }
+            ')' => { tokens.push(Token::RParen); i += 1; }
+            ',' => { tokens.push(Token::Comma); i += 1; }
+            '\'' | '"' => {
+                let quote = c;
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != quote {
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err(expression_error(source, "unterminated string literal"));
+                }
+                tokens.push(Token::Text(chars[start..i].iter().collect()));
+                i += 1;
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let literal: String = chars[start..i].iter().collect();
+                let number = literal.parse()
+                    .map_err(|_| expression_error(source, &format!("invalid number '{}'", literal)))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            other => {
+                return Err(expression_error(source, &format!("unexpected character '{}'", other)));
+            }
+        }
+    }
+    
+    Ok(tokens)
+}
+
+/// Recursive-descent parser over tokens
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    source: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+    
+    fn error(&self, reason: &str) -> PdwError {
+        expression_error(self.source, reason)
+    }
+    
+    fn parse_expression(&mut self) -> Result<Expression, PdwError> {
+        let mut left = self.parse_term()?;
+        
+        while let Some(op) = match self.peek() {
+            Some(Token::Plus) => Some(BinaryOp::Add),
+            Some(Token::Minus) => Some(BinaryOp::Subtract),
+            _ => None,
+        } {
+            self.position += 1;
+            let right = self.parse_term()?;
+            left = Expression::Binary { op, left: Box::new(left), right: Box::new(right) };
+        }
+        
+        Ok(left)
+    }
+    
+    fn parse_term(&mut self) -> Result<Expression, PdwError> {
+        let mut left = self.parse_unary()?;
+        
+        while let Some(op) = match self.peek() {
+            Some(Token::Star) => Some(BinaryOp::Multiply),
+            Some(Token::Slash) => Some(BinaryOp::Divide),
+            _ => None,
+        } {
+            self.position += 1;
+            let right = self.parse_unary()?;
+            left = Expression::Binary { op, left: Box::new(left), right: Box::new(right) };
+        }
+        
+        Ok(left)
+    }
+    
+    fn parse_unary(&mut self) -> Result<Expression, PdwError> {
+        if self.peek() == Some(&Token::Minus) {
+            self.position += 1;
+            return Ok(Expression::Negate(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+    
+    fn parse_primary(&mut self) -> Result<Expression, PdwError> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expression::Number(n)),
+            Some(Token::Text(s)) => Ok(Expression::Text(s)),
+            Some(Token::LParen) => {
+                let inner = self.parse_expression()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err(self.error("expected ')'")),
+                }
+            }
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.position += 1;
+                    let function = Function::from_name(&name)
+                        .ok_or_else(|| self.error(&format!("unknown function '{}'", name)))?;
+                    let args = self.parse_arguments()?;
+                    let (min, max) = function.arity();
+                    if args.len() < min || args.len() > max {
+                        return Err(self.error(&format!("wrong number of arguments for '{}'", name)));
+                    }
+                    Ok(Expression::Call { function, args })
+                } else {
+                    Column::from_name(&name)
+                        .map(Expression::Column)
+                        .ok_or_else(|| self.error(&format!("unknown column '{}'", name)))
+                }
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+    
+    fn parse_arguments(&mut self) -> Result<Vec<Expression>, PdwError> {
+        let mut args = Vec::new();
+        
+        if self.peek() == Some(&Token::RParen) {
+            self.position += 1;
+            return Ok(args);
+        }
+        
+        loop {
+            args.push(self.parse_expression()?);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => break,
+                _ => return Err(self.error("expected ',' or ')'")),
+            }
+        }
+        
+        Ok(args)
+    }
+}
+
+/// Build a configuration error for an invalid expression
+fn expression_error(source: &str, reason: &str) -> PdwError {
+    ConfigError::InvalidFormat {
+        message: format!("Invalid expression '{}': {}", source, reason),
+    }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    
+    fn sample_transaction() -> ProcessedTransaction {
+        ProcessedTransaction {
+            date: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            day_of_week: "Segunda-feira".to_string(),
+            transaction_type: "Mercado".to_string(),
+            description: "  Compra semanal ".to_string(),
+            credit: money::Decimal::new(150, 0),
+            debit: money::Decimal::new(10025, 2),
+            month: "01".to_string(),
+            year: "2024".to_string(),
+            month_name: "01-Janeiro".to_string(),
+            year_month: "2024/01".to_string(),
+            origin: "Conta".to_string(),
+            derived: Vec::new(),
+            currency: None,
+        }
+    }
+    
+    #[test]
+    fn test_arithmetic_expression() {
+        let expression = Expression::parse("Credito - Debito * 2").unwrap();
+        assert_eq!(expression.kind(), ValueKind::Number);
+        assert_eq!(expression.evaluate(&sample_transaction()).unwrap(), DerivedValue::Number(-50.5));
+        
+        let expression = Expression::parse("(Credito - Debito) / 0").unwrap();
+        assert_eq!(expression.evaluate(&sample_transaction()).unwrap(), DerivedValue::Null);
+    }
+    
+    #[test]
+    fn test_function_expression() {
+        let transaction = sample_transaction();
+        
+        let expression = Expression::parse("upper(TIPO)").unwrap();
+        assert_eq!(expression.kind(), ValueKind::Text);
+        assert_eq!(expression.evaluate(&transaction).unwrap(), DerivedValue::Text("MERCADO".to_string()));
+        
+        let expression = Expression::parse("concat(Origem, '-', trim(DESCRICAO))").unwrap();
+        assert_eq!(expression.evaluate(&transaction).unwrap(), DerivedValue::Text("Conta-Compra semanal".to_string()));
+        
+        let expression = Expression::parse("round(Debito / 3, 2)").unwrap();
+        assert_eq!(expression.evaluate(&transaction).unwrap(), DerivedValue::Number(33.42));
+    }
+    
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expression::parse("Credito +").is_err());
+        assert!(Expression::parse("unknown_column * 2").is_err());
+        assert!(Expression::parse("nope(TIPO)").is_err());
+        assert!(Expression::parse("upper(TIPO, Origem)").is_err());
+        assert!(Expression::parse("'open").is_err());
+    }
+    
+    #[test]
+    fn test_derived_column_names() {
+        assert!(DerivedColumn::compile("Liquido", "Credito - Debito").is_ok());
+        assert!(DerivedColumn::compile("TIPO", "upper(TIPO)").is_err());
+        assert!(DerivedColumn::compile("bad name", "1").is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,294 @@
+/*!
+# Fees Module
+
+Detects bank fees, interest charges and IOF among the general entries. Each
+entry's TIPO and DESCRICAO are matched against description patterns from the
+enabled presets (plus custom `[[fees.rules]]`), whole words only and ignoring
+accents and case. The matches are summarized per month, category and origin
+into the TARIFAS table, since these costs are usually scattered across types:
+the debits make up the fee total (Valor) and credits matching the same patterns
+are listed apart as refunds (Estornos), so income under a fee TIPO never hides
+what was charged.
+*/
+
+use crate::collation;
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, PdwError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Kind of banking cost
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeCategory {
+    /// Service fees (tarifas, anuidade, cesta de serviços)
+    Tarifa,
+    /// Interest and late charges (juros, mora, multa, encargos)
+    Juros,
+    /// Imposto sobre Operações Financeiras
+    Iof,
+}
+
+/// Built-in pattern sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeePreset {
+    /// Terms common to checking account statements
+    Generic,
+    /// Credit card invoices
+    CreditCard,
+    /// Overdraft (cheque especial)
+    Overdraft,
+}
+
+/// Custom description pattern (`[[fees.rules]]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeRule {
+    pub category: FeeCategory,
+    pub pattern: String,
+}
+
+/// Fee detection settings (`[fees]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_presets")]
+    pub presets: Vec<FeePreset>,
+    /// Checked before the presets
+    #[serde(default)]
+    pub rules: Vec<FeeRule>,
+    #[serde(default = "default_fees_table")]
+    pub table: String,
+}
+
+/// Classifies entry descriptions into fee categories
+#[derive(Debug, Clone)]
+pub struct FeeClassifier {
+    rules: Vec<(FeeCategory, Vec<String>)>,
+}
+
+/// One month, category and origin of the fee summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeSummaryRow {
+    pub year_month: String,
+    pub category: FeeCategory,
+    pub origin: String,
+    pub entries: usize,
+    /// Fees charged (debits)
+    pub amount: f64,
+    /// Credits among the matching entries (refunds)
+    pub refunds: f64,
+}
+
+fn default_presets() -> Vec<FeePreset> {
+    vec![FeePreset::Generic, FeePreset::CreditCard, FeePreset::Overdraft]
+}
+
+fn default_fees_table() -> String {
+    "TARIFAS".to_string()
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            presets: default_presets(),
+            rules: Vec::new(),
+            table: default_fees_table(),
+        }
+    }
+}
+
+impl FeeCategory {
+    /// Category name stored in the summary table
+    pub fn label(&self) -> &'static str {
+        match self {
+            FeeCategory::Tarifa => "TARIFA",
+            FeeCategory::Juros => "JUROS",
+            FeeCategory::Iof => "IOF",
+        }
+    }
+}
+
+impl FeePreset {
+    /// Description patterns of a category in this preset
+    pub fn patterns(&self, category: FeeCategory) -> &'static [&'static str] {
+        match (self, category) {
+            (FeePreset::Generic, FeeCategory::Iof) => &["IOF"],
+            (FeePreset::Generic, FeeCategory::Juros) => &["JUROS", "MORA", "MULTA", "ENCARGOS"],
+            (FeePreset::Generic, FeeCategory::Tarifa) => &[
+                "TARIFA", "TAR", "CESTA", "PACOTE SERVICOS", "MANUTENCAO CONTA",
+            ],
+            (FeePreset::CreditCard, FeeCategory::Iof) => &[],
+            (FeePreset::CreditCard, FeeCategory::Juros) => &["ROTATIVO", "ENCARGOS FINANCIAMENTO"],
+            (FeePreset::CreditCard, FeeCategory::Tarifa) => &[
+                "ANUIDADE", "AVALIACAO EMERGENCIAL", "SEGURO FATURA",
+            ],
+            (FeePreset::Overdraft, FeeCategory::Iof) => &[],
+            (FeePreset::Overdraft, FeeCategory::Juros) => &["CHEQUE ESPECIAL", "JUROS LIMITE"],
+            (FeePreset::Overdraft, FeeCategory::Tarifa) => &["DEPOSITANTE"],
+        }
+    }
+}
+
+impl FeeClassifier {
+    /// Custom rules first, then preset patterns by category (IOF, interest, fees)
+    pub fn from_config(config: &FeeConfig) -> Self {
+        let mut rules: Vec<(FeeCategory, Vec<String>)> = config.rules.iter()
+            .map(|rule| (rule.category, words(&rule.pattern)))
+            .collect();
+        
+        // "IOF S/ JUROS" is IOF and "JUROS S/ TARIFA" is interest
+        for category in [FeeCategory::Iof, FeeCategory::Juros, FeeCategory::Tarifa] {
+            for preset in &config.presets {
+                rules.extend(preset.patterns(category).iter().map(|pattern| (category, words(pattern))));
+            }
+        }
+        
+        rules.retain(|(_, pattern)| !pattern.is_empty());
+        Self { rules }
+    }
+    
+    /// Category of the first rule whose words appear in the text
+    pub fn classify(&self, text: &str) -> Option<FeeCategory> {
+        let text = words(text);
+        self.rules.iter()
+            .find(|(_, pattern)| text.windows(pattern.len()).any(|window| window == pattern.as_slice()))
+            .map(|(category, _)| *category)
+    }
+}
+
+/// Fees charged and refunded per AnoMes, category and origin
+pub fn summarize_fees(database: &DatabaseManager, entries_table: &str,
+                      classifier: &FeeClassifier) -> Result<Vec<FeeSummaryRow>, PdwError> {
+    let query = format!(
+        "SELECT AnoMes, COALESCE(Origem, ''), COALESCE(TIPO, '') || ' ' || COALESCE(DESCRICAO, ''),
+                COALESCE(Debito, 0), COALESCE(Credito, 0)
+         FROM {} WHERE AnoMes IS NOT NULL",
+        entries_table
+    );
+    
+    let mut totals: BTreeMap<(String, FeeCategory, String), (usize, f64, f64)> = BTreeMap::new();
+    for row in database.execute_query(&query)? {
+        let text = |index: usize| row.get(index).and_then(Value::as_str).unwrap_or_default().to_string();
+        let Some(category) = classifier.classify(&text(2)) else {
+            continue;
+        };
+        
+        let total = totals.entry((text(0), category, text(1))).or_default();
+        total.0 += 1;
+        total.1 += row.get(3).and_then(Value::as_f64).unwrap_or(0.0);
+        total.2 += row.get(4).and_then(Value::as_f64).unwrap_or(0.0);
+    }
+    
+    Ok(totals.into_iter()
+        .map(|((year_month, category, origin), (entries, amount, refunds))| FeeSummaryRow {
+            year_month,
+            category,
+            origin,
+            entries,
+            amount: (amount * 100.0).round() / 100.0,
+            refunds: (refunds * 100.0).round() / 100.0,
+        })
+        .collect())
+}
+
+/// Store the fee summary, replacing the previous run's table
+pub fn write_fee_table(database: &DatabaseManager, table: &str, rows: &[FeeSummaryRow]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (AnoMes TEXT, Categoria TEXT, Origem TEXT, Lancamentos INTEGER, Valor REAL, Estornos REAL)",
+        table
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6)", table);
+    for row in rows {
+        database.connection().execute(&insert_query, rusqlite::params![
+            row.year_month,
+            row.category.label(),
+            row.origin,
+            row.entries as i64,
+            row.amount,
+            row.refunds,
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(rows.len())
+}
+
+/// Accent-free lowercase words of a text
+fn words(text: &str) -> Vec<String> {
+    collation::fold(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_classification() {
+        let classifier = FeeClassifier::from_config(&FeeConfig::default());
+        assert_eq!(classifier.classify("TAR PACOTE SERVIÇOS"), Some(FeeCategory::Tarifa));
+        assert_eq!(classifier.classify("Anuidade cartão 3/12"), Some(FeeCategory::Tarifa));
+        assert_eq!(classifier.classify("IOF s/ juros cheque especial"), Some(FeeCategory::Iof));
+        assert_eq!(classifier.classify("Juros do rotativo"), Some(FeeCategory::Juros));
+        // Whole words only
+        assert_eq!(classifier.classify("TARDE NO CINEMA"), None);
+        assert_eq!(classifier.classify("IOFERTA MERCADO"), None);
+    }
+    
+    #[test]
+    fn test_custom_rules_and_presets() {
+        let config: FeeConfig = toml::from_str(
+            "enabled = true\npresets = [\"credit_card\"]\n\
+             [[rules]]\ncategory = \"tarifa\"\npattern = \"Juros Zero Plus\""
+        ).unwrap();
+        assert_eq!(config.table, "TARIFAS");
+        
+        let classifier = FeeClassifier::from_config(&config);
+        assert_eq!(classifier.classify("MENSALIDADE JUROS ZERO PLUS"), Some(FeeCategory::Tarifa));
+        assert_eq!(classifier.classify("Anuidade"), Some(FeeCategory::Tarifa));
+        // Generic preset disabled
+        assert_eq!(classifier.classify("TARIFA DOC"), None);
+    }
+    
+    #[test]
+    fn test_fee_summary_table() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-05', 'BANCO', 'Tarifa pacote', 0, 35.9, '2024/01', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-06', 'BANCO', 'Estorno tarifa', 10, 0, '2024/01', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-10', 'COMPRAS', 'IOF compra internacional', 0, 4.38, '2024/01', 'Cartão');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-11', 'MERCADO', 'Supermercado', 0, 200, '2024/01', 'Conta');"
+        ).unwrap();
+        
+        let classifier = FeeClassifier::from_config(&FeeConfig::default());
+        let rows = summarize_fees(&db, "LANCAMENTOS_GERAIS", &classifier).unwrap();
+        assert_eq!(write_fee_table(&db, "TARIFAS", &rows).unwrap(), 2);
+        
+        // The refund is listed apart instead of shrinking the fees charged
+        let result = db.execute_query("SELECT Categoria, Origem, Lancamentos, Valor, Estornos FROM TARIFAS ORDER BY Categoria").unwrap();
+        assert_eq!(result, vec![
+            vec![json!("IOF"), json!("Cartão"), json!(1), json!(4.38), json!(0.0)],
+            vec![json!("TARIFA"), json!("Conta"), json!(2), json!(35.9), json!(10.0)],
+        ]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,316 @@
+/*!
+# Forecast Module
+
+Committed future spend. Recurring debits are found in the general entries: the
+same TIPO and description (accents, case and punctuation ignored) on about the
+same day of the month, a fixed number of months apart, at least
+`min_occurrences` times in a row up to the latest occurrence. Each is projected
+over the next `months` months at the average of its last three amounts, next to
+the installments of PARCELAMENTOS dated after the last entry. The lines go into
+the PREVISAO table and the "Previsao" report sheet.
+
+Recurrences whose next date passed without an entry are taken as ended;
+a projection falling in a month that has an installment with the same
+description is left to the installment.
+*/
+
+use crate::database::DatabaseManager;
+use crate::duplicates;
+use crate::error::{DatabaseError, PdwError};
+use crate::excel;
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Source of a recurring projection
+pub const RECURRING: &str = "Recorrente";
+
+/// Source of an open installment
+pub const INSTALLMENT: &str = "Parcelamento";
+
+/// Forecast settings (`[forecast]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForecastConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Months projected after the month of the last entry
+    #[serde(default = "default_months")]
+    pub months: u32,
+    /// Consecutive regular occurrences making a debit recurring
+    #[serde(default = "default_min_occurrences")]
+    pub min_occurrences: usize,
+    /// Most days the day of the month may drift between occurrences
+    #[serde(default = "default_day_tolerance")]
+    pub day_tolerance: u32,
+    #[serde(default = "default_forecast_table")]
+    pub table: String,
+}
+
+/// Debit of the general entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Debit {
+    pub date: NaiveDate,
+    pub tipo: String,
+    pub description: String,
+    pub amount: f64,
+}
+
+/// A debit repeated at a regular interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    pub tipo: String,
+    pub description: String,
+    /// Months between occurrences (1 monthly, 12 yearly)
+    pub interval_months: u32,
+    pub last: NaiveDate,
+    pub amount: f64,
+}
+
+/// Expected debit of a future date
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastLine {
+    pub date: NaiveDate,
+    pub tipo: String,
+    pub description: String,
+    pub amount: f64,
+    /// `RECURRING` or `INSTALLMENT`
+    pub source: &'static str,
+}
+
+fn default_months() -> u32 {
+    3
+}
+
+fn default_min_occurrences() -> usize {
+    3
+}
+
+fn default_day_tolerance() -> u32 {
+    5
+}
+
+fn default_forecast_table() -> String {
+    "PREVISAO".to_string()
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            months: default_months(),
+            min_occurrences: default_min_occurrences(),
+            day_tolerance: default_day_tolerance(),
+            table: default_forecast_table(),
+        }
+    }
+}
+
+/// Dated debits of the general entries, oldest first
+pub fn read_debits(database: &DatabaseManager, entries_table: &str) -> Result<Vec<Debit>, PdwError> {
+    let query = format!(
+        "SELECT Data, COALESCE(TRIM(TIPO), ''), COALESCE(DESCRICAO, ''), Debito
+         FROM {} WHERE Data IS NOT NULL AND Debito > 0 ORDER BY Data, rowid",
+        entries_table
+    );
+    
+    Ok(database.execute_query(&query)?.into_iter()
+        .filter_map(|row| {
+            let text = |index: usize| row.get(index).and_then(Value::as_str).unwrap_or_default().to_string();
+            let date = NaiveDate::parse_from_str(text(0).get(..10)?, "%Y-%m-%d").ok()?;
+            let amount = row.get(3).and_then(Value::as_f64)?;
+            Some(Debit { date, tipo: text(1), description: text(2), amount })
+        })
+        .collect())
+}
+
+/// Debits repeating at a regular interval up to their latest occurrence
+pub fn detect_recurrences(debits: &[Debit], config: &ForecastConfig) -> Vec<Recurrence> {
+    let mut groups: BTreeMap<(String, String), Vec<&Debit>> = BTreeMap::new();
+    for debit in debits {
+        let description = duplicates::normalize(&debit.description);
+        if !description.is_empty() {
+            groups.entry((debit.tipo.clone(), description)).or_default().push(debit);
+        }
+    }
+    
+    let mut recurrences = Vec::new();
+    for group in groups.values_mut() {
+        group.sort_by_key(|debit| debit.date);
+        let [.., before, last] = group.as_slice() else {
+            continue;
+        };
+        let interval = month_index(last.date) - month_index(before.date);
+        if !(1..=12).contains(&interval) {
+            continue;
+        }
+        
+        // Occurrences at the same interval, counting back from the latest
+        let mut run = vec![*last];
+        for pair in group.windows(2).rev() {
+            let on_day = pair[0].date.day().abs_diff(last.date.day()) <= config.day_tolerance;
+            if month_index(pair[1].date) - month_index(pair[0].date) != interval || !on_day {
+                break;
+            }
+            run.push(pair[0]);
+        }
+        if run.len() < config.min_occurrences.max(2) {
+            continue;
+        }
+        
+        let recent: Vec<f64> = run.iter().take(3).map(|debit| debit.amount).collect();
+        recurrences.push(Recurrence {
+            tipo: last.tipo.clone(),
+            description: last.description.clone(),
+            interval_months: interval as u32,
+            last: last.date,
+            amount: (recent.iter().sum::<f64>() / recent.len() as f64 * 100.0).round() / 100.0,
+        });
+    }
+    recurrences
+}
+
+/// Installments of the installments table, as forecast lines
+pub fn read_installments(database: &DatabaseManager, installments_table: &str) -> Result<Vec<ForecastLine>, PdwError> {
+    if database.table_columns(installments_table)?.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    // Data, Tipo Lançamento, Descricao, Debito; sheet header rows do not parse
+    let rows = database.execute_query(&format!("SELECT * FROM {}", installments_table))?;
+    Ok(rows.into_iter()
+        .filter_map(|row| {
+            let text = |index: usize| match row.get(index) {
+                Some(Value::String(text)) => text.trim().to_string(),
+                Some(Value::Number(number)) => number.to_string(),
+                _ => String::new(),
+            };
+            let date = text(0);
+            let date = excel::parse_date(date.get(..10).unwrap_or(&date))
+                .or_else(|| date.parse::<f64>().ok().filter(|serial| *serial > 1.0).and_then(|serial| excel::DateSystem::Excel1900.serial_date(serial)))?;
+            let amount = text(3).replace(',', ".").parse::<f64>().ok().filter(|amount| *amount != 0.0)?;
+            Some(ForecastLine { date, tipo: text(1), description: text(2), amount: amount.abs(), source: INSTALLMENT })
+        })
+        .collect())
+}
+
+/// Recurring projections and installments after `as_of`, through `months` months past its month
+pub fn project(recurrences: &[Recurrence], installments: &[ForecastLine], as_of: NaiveDate, months: u32) -> Vec<ForecastLine> {
+    let horizon = month_index(as_of) + months as i32;
+    let in_range = |date: NaiveDate| date > as_of && month_index(date) <= horizon;
+    
+    let mut lines: Vec<ForecastLine> = installments.iter().filter(|line| in_range(line.date)).cloned().collect();
+    let installed: BTreeSet<(i32, String)> = lines.iter()
+        .map(|line| (month_index(line.date), duplicates::normalize(&line.description)))
+        .collect();
+    
+    for recurrence in recurrences {
+        let description = duplicates::normalize(&recurrence.description);
+        let next = |step: u32| recurrence.last.checked_add_months(Months::new(recurrence.interval_months * step));
+        // Ended: the next occurrence is already overdue
+        if next(1).map_or(true, |date| date < as_of) {
+            continue;
+        }
+        for date in (1..).map_while(next).take_while(|date| month_index(*date) <= horizon) {
+            if in_range(date) && !installed.contains(&(month_index(date), description.clone())) {
+                lines.push(ForecastLine {
+                    date,
+                    tipo: recurrence.tipo.clone(),
+                    description: recurrence.description.clone(),
+                    amount: recurrence.amount,
+                    source: RECURRING,
+                });
+            }
+        }
+    }
+    
+    lines.sort_by(|a, b| (a.date, a.source, &a.tipo, &a.description).cmp(&(b.date, b.source, &b.tipo, &b.description)));
+    lines
+}
+
+/// Store the forecast, replacing the previous run's table
+pub fn write_forecast_table(database: &DatabaseManager, table: &str, lines: &[ForecastLine]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (AnoMes TEXT, Data DATE, TIPO TEXT, DESCRICAO TEXT, Debito REAL, Fonte TEXT)",
+        table
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6)", table);
+    for line in lines {
+        database.connection().execute(&insert_query, rusqlite::params![
+            line.date.format("%Y/%m").to_string(),
+            line.date.format("%Y-%m-%d").to_string(),
+            line.tipo,
+            line.description,
+            line.amount,
+            line.source,
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(lines.len())
+}
+
+fn month_index(date: NaiveDate) -> i32 {
+    date.year() * 12 + date.month0() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_recurring_debits_and_installments_projected() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Debito) VALUES
+                ('2024-01-10', 'LAZER', 'Streaming', 39.9), ('2024-02-09', 'LAZER', 'STREAMING.', 39.9),
+                ('2024-03-11', 'LAZER', 'Streaming', 45.9),
+                ('2024-01-05', 'CASA', 'Aluguel', 1500), ('2024-03-05', 'CASA', 'Aluguel', 1500),
+                ('2024-01-20', 'SAUDE', 'Academia', 99), ('2024-02-20', 'SAUDE', 'Academia', 99),
+                ('2023-11-15', 'CASA', 'Seguro', 80), ('2023-12-15', 'CASA', 'Seguro', 80),
+                ('2024-01-15', 'CASA', 'Seguro', 80);
+             INSERT INTO PARCELAMENTOS VALUES ('Data', 'Tipo Lançamento', 'Descricao', 'Debito'),
+                ('2024-04-12', 'CASA', 'Geladeira 3/10', '250.5'), ('45427', 'CASA', 'Geladeira 4/10', '250,5'),
+                ('2024-02-12', 'CASA', 'Geladeira 1/10', '250.5');"
+        ).unwrap();
+        
+        let config = ForecastConfig::default();
+        let debits = read_debits(&db, "LANCAMENTOS_GERAIS").unwrap();
+        let recurrences = detect_recurrences(&debits, &config);
+        // Aluguel skips a month and Academia is seen twice; Seguro is monthly
+        assert_eq!(recurrences.iter().map(|r| r.description.as_str()).collect::<Vec<_>>(), vec!["Seguro", "Streaming"]);
+        assert_eq!(recurrences[1].amount, 41.9);
+        
+        let installments = read_installments(&db, "PARCELAMENTOS").unwrap();
+        assert_eq!(installments.len(), 3);
+        let as_of = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let lines = project(&recurrences, &installments, as_of, 2);
+        let summary: Vec<(String, &str, f64)> = lines.iter()
+            .map(|line| (line.date.to_string(), line.source, line.amount))
+            .collect();
+        // Seguro was due in February and is taken as ended
+        assert_eq!(summary, vec![
+            ("2024-04-11".to_string(), RECURRING, 41.9),
+            ("2024-04-12".to_string(), INSTALLMENT, 250.5),
+            ("2024-05-11".to_string(), RECURRING, 41.9),
+            ("2024-05-15".to_string(), INSTALLMENT, 250.5),
+        ]);
+        
+        assert_eq!(write_forecast_table(&db, "PREVISAO", &lines).unwrap(), 4);
+        let rows = db.execute_query("SELECT AnoMes, TIPO, DESCRICAO, Fonte FROM PREVISAO LIMIT 1").unwrap();
+        assert_eq!(rows, vec![vec![json!("2024/04"), json!("LAZER"), json!("Streaming"), json!("Recorrente")]]);
+    }
+}
\ No newline at end of file
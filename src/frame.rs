@@ -0,0 +1,95 @@
+/*!
+# Frame Module
+
+Query results as a small column-oriented table, for programs embedding the
+pipeline: `Frame::query(pipeline.database(), "SELECT ...")` keeps the column
+names with the rows, so values are read by column name (`frame.column("TIPO")`,
+`frame.sum("Debito")`) or as one record per row. Printing a frame renders the
+same aligned table as `pdw query`.
+*/
+
+use crate::database::DatabaseManager;
+use crate::error::PdwError;
+use crate::shell::{self, OutputFormat};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Rows of a query with their column names
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frame {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Frame {
+    /// Run a query against the warehouse
+    pub fn query(database: &DatabaseManager, sql: &str) -> Result<Self, PdwError> {
+        Ok(Self {
+            columns: database.query_columns(sql)?,
+            rows: database.execute_query(sql)?,
+        })
+    }
+    
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+    
+    /// Values of a column, None when the frame has no such column
+    pub fn column(&self, name: &str) -> Option<Vec<&Value>> {
+        let index = self.columns.iter().position(|column| column == name)?;
+        Some(self.rows.iter().map(|row| row.get(index).unwrap_or(&Value::Null)).collect())
+    }
+    
+    /// Numeric values of a column (NULLs and text count as 0)
+    pub fn numbers(&self, name: &str) -> Option<Vec<f64>> {
+        self.column(name).map(|values| values.into_iter().map(|value| value.as_f64().unwrap_or(0.0)).collect())
+    }
+    
+    /// Sum of a numeric column
+    pub fn sum(&self, name: &str) -> Option<f64> {
+        self.numbers(name).map(|numbers| numbers.iter().sum())
+    }
+    
+    /// A row keyed by column name
+    pub fn record(&self, index: usize) -> Option<BTreeMap<&str, &Value>> {
+        let row = self.rows.get(index)?;
+        Some(self.columns.iter().map(String::as_str).zip(row).collect())
+    }
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let table = shell::render(&self.columns, &self.rows, OutputFormat::Table).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_frame_columns_and_records() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito) VALUES ('2024-01-05', 'SALARIO', 5000, NULL);
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito) VALUES ('2024-01-10', 'ALUGUEL', 0, 1500);"
+        ).unwrap();
+        
+        let frame = Frame::query(&db, "SELECT Data, TIPO, Credito, Debito FROM LANCAMENTOS_GERAIS ORDER BY Data").unwrap();
+        assert_eq!(frame.len(), 2);
+        assert_eq!(frame.column("TIPO").unwrap(), vec![&json!("SALARIO"), &json!("ALUGUEL")]);
+        assert_eq!(frame.numbers("Debito").unwrap(), vec![0.0, 1500.0]);
+        assert_eq!(frame.sum("Credito"), Some(5000.0));
+        assert_eq!(frame.column("Saldo"), None);
+        assert_eq!(frame.record(1).unwrap()["TIPO"], &json!("ALUGUEL"));
+        assert!(frame.to_string().lines().next().unwrap().starts_with("| Data"));
+    }
+}
\ No newline at end of file
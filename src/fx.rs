@@ -0,0 +1,368 @@
+/*!
+# Exchange Rates Module
+
+Daily exchange rates for converting foreign-currency amounts into the base
+currency. Rates come from the Banco Central PTAX API or exchangerate.host and
+are kept in an on-disk JSON cache: a cached rate is never refetched or
+overwritten, so reruns convert with the same figures. With `offline = true`
+only the cache is consulted and a missing rate is an error, never a guess.
+Network access needs a build with `--features fx`.
+*/
+
+use crate::error::PdwError;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Source of daily exchange rates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateProvider {
+    /// Banco Central do Brasil PTAX closing rates (base currency BRL)
+    #[default]
+    BcbPtax,
+    /// exchangerate.host timeframe endpoint (needs an access key)
+    ExchangerateHost,
+}
+
+/// Exchange-rate settings (`[fx]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FxConfig {
+    #[serde(default)]
+    pub provider: RateProvider,
+    #[serde(default = "default_base_currency")]
+    pub base_currency: String,
+    /// Cache file name, stored in database_dir
+    #[serde(default = "default_cache_file")]
+    pub cache_file: String,
+    /// Never touch the network; missing rates are errors
+    #[serde(default)]
+    pub offline: bool,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Days searched backwards for weekends and holidays
+    #[serde(default = "default_lookback_days")]
+    pub lookback_days: i64,
+}
+
+/// Cached rates: currency -> date -> base currency units per unit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateCache {
+    #[serde(default)]
+    pub rates: BTreeMap<String, BTreeMap<NaiveDate, f64>>,
+}
+
+/// Rate lookups backed by the cache and, when allowed, the provider
+pub struct FxRates {
+    config: FxConfig,
+    cache_path: PathBuf,
+    cache: RateCache,
+    dirty: bool,
+}
+
+fn default_base_currency() -> String {
+    "BRL".to_string()
+}
+
+fn default_cache_file() -> String {
+    "fx_rates.json".to_string()
+}
+
+fn default_lookback_days() -> i64 {
+    7
+}
+
+impl Default for FxConfig {
+    fn default() -> Self {
+        Self {
+            provider: RateProvider::default(),
+            base_currency: default_base_currency(),
+            cache_file: default_cache_file(),
+            offline: false,
+            api_key: None,
+            lookback_days: default_lookback_days(),
+        }
+    }
+}
+
+impl RateCache {
+    /// Read the cache file, empty when it does not exist yet
+    pub fn load(path: &Path) -> Result<Self, PdwError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| PdwError::ExchangeRate(format!("Invalid rate cache {}: {}", path.display(), e)))
+    }
+    
+    /// Write the cache through a temporary file so a crash never truncates it
+    pub fn save(&self, path: &Path) -> Result<(), PdwError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| PdwError::ExchangeRate(e.to_string()))?;
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, content)?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+    
+    /// Store a rate unless one is already cached for that day; returns true when added
+    pub fn insert(&mut self, currency: &str, date: NaiveDate, rate: f64) -> bool {
+        let days = self.rates.entry(currency.to_uppercase()).or_default();
+        if days.contains_key(&date) {
+            return false;
+        }
+        days.insert(date, rate);
+        true
+    }
+    
+    /// Rate of the date or the closest earlier day within the lookback window
+    pub fn on_or_before(&self, currency: &str, date: NaiveDate, lookback_days: i64) -> Option<(NaiveDate, f64)> {
+        let earliest = date - Duration::days(lookback_days);
+        self.rates.get(&currency.to_uppercase())?
+            .range(earliest..=date)
+            .next_back()
+            .map(|(day, rate)| (*day, *rate))
+    }
+}
+
+impl FxRates {
+    /// Open the cache at `cache_path`
+    pub fn open(config: &FxConfig, cache_path: PathBuf) -> Result<Self, PdwError> {
+        let cache = RateCache::load(&cache_path)?;
+        Ok(Self { config: config.clone(), cache_path, cache, dirty: false })
+    }
+    
+    /// Base currency units per unit of `currency` on `date`
+    pub fn rate(&mut self, currency: &str, date: NaiveDate) -> Result<f64, PdwError> {
+        if currency.eq_ignore_ascii_case(&self.config.base_currency) {
+            return Ok(1.0);
+        }
+        
+        if let Some((_, rate)) = self.cache.on_or_before(currency, date, self.config.lookback_days) {
+            return Ok(rate);
+        }
+        
+        if self.config.offline {
+            return Err(PdwError::ExchangeRate(format!(
+                "No cached {} rate on or before {} (offline mode)", currency, date
+            )));
+        }
+        
+        self.prefetch(currency, date - Duration::days(self.config.lookback_days), date)?;
+        self.cache.on_or_before(currency, date, self.config.lookback_days)
+            .map(|(_, rate)| rate)
+            .ok_or_else(|| PdwError::ExchangeRate(format!(
+                "{:?} returned no {} rate on or before {}", self.config.provider, currency, date
+            )))
+    }
+    
+    /// Download a date range into the cache; returns the number of new rates
+    pub fn prefetch(&mut self, currency: &str, from: NaiveDate, to: NaiveDate) -> Result<usize, PdwError> {
+        if self.config.offline {
+            return Err(PdwError::ExchangeRate("Rate download disabled (offline mode)".to_string()));
+        }
+        
+        let url = provider_url(&self.config, currency, from, to)?;
+        let response = fetch_json(&url)?;
+        let rates = match self.config.provider {
+            RateProvider::BcbPtax => parse_ptax(&response)?,
+            RateProvider::ExchangerateHost => parse_exchangerate_host(&response, currency, &self.config.base_currency)?,
+        };
+        
+        let added = rates.into_iter()
+            .filter(|(date, rate)| self.cache.insert(currency, *date, *rate))
+            .count();
+        self.dirty |= added > 0;
+        tracing::info!("{} new {} rates cached ({} to {})", added, currency.to_uppercase(), from, to);
+        
+        Ok(added)
+    }
+    
+    /// Persist newly fetched rates
+    pub fn save(&mut self) -> Result<(), PdwError> {
+        if self.dirty {
+            self.cache.save(&self.cache_path)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+    
+    /// Cached rates
+    pub fn cache(&self) -> &RateCache {
+        &self.cache
+    }
+}
+
+/// Request URL for a currency and date range
+fn provider_url(config: &FxConfig, currency: &str, from: NaiveDate, to: NaiveDate) -> Result<String, PdwError> {
+    let currency = currency.to_uppercase();
+    
+    match config.provider {
+        RateProvider::BcbPtax => {
+            if !config.base_currency.eq_ignore_ascii_case("BRL") {
+                return Err(PdwError::ExchangeRate("PTAX rates are quoted in BRL only".to_string()));
+            }
+            Ok(format!(
+                "https://olinda.bcb.gov.br/olinda/servico/PTAX/versao/v1/odata/\
+                 CotacaoMoedaPeriodo(moeda=@moeda,dataInicial=@dataInicial,dataFinalCotacao=@dataFinalCotacao)\
+                 ?@moeda='{}'&@dataInicial='{}'&@dataFinalCotacao='{}'&$format=json",
+                currency, from.format("%m-%d-%Y"), to.format("%m-%d-%Y")
+            ))
+        }
+        RateProvider::ExchangerateHost => {
+            let key = config.api_key.as_deref()
+                .ok_or_else(|| PdwError::ExchangeRate("fx.api_key is required for exchangerate.host".to_string()))?;
+            Ok(format!(
+                "https://api.exchangerate.host/timeframe?access_key={}&source={}&currencies={}&start_date={}&end_date={}",
+                key, currency, config.base_currency.to_uppercase(), from, to
+            ))
+        }
+    }
+}
+
+/// Closing (Fechamento) selling rates from a PTAX OData response
+pub fn parse_ptax(response: &Value) -> Result<Vec<(NaiveDate, f64)>, PdwError> {
+    let entries = response.get("value").and_then(Value::as_array)
+        .ok_or_else(|| PdwError::ExchangeRate("Unexpected PTAX response".to_string()))?;
+    
+    Ok(entries.iter()
+        .filter(|entry| entry.get("tipoBoletim").and_then(Value::as_str).is_some_and(|t| t.starts_with("Fechamento")))
+        .filter_map(|entry| {
+            let timestamp = entry.get("dataHoraCotacao")?.as_str()?;
+            let date = NaiveDate::parse_from_str(timestamp.get(..10)?, "%Y-%m-%d").ok()?;
+            Some((date, entry.get("cotacaoVenda")?.as_f64()?))
+        })
+        .collect())
+}
+
+/// Daily quotes from an exchangerate.host timeframe response
+pub fn parse_exchangerate_host(response: &Value, currency: &str, base_currency: &str) -> Result<Vec<(NaiveDate, f64)>, PdwError> {
+    if response.get("success").and_then(Value::as_bool) == Some(false) {
+        return Err(PdwError::ExchangeRate(format!("exchangerate.host error: {}", response["error"])));
+    }
+    
+    let quotes = response.get("quotes").and_then(Value::as_object)
+        .ok_or_else(|| PdwError::ExchangeRate("Unexpected exchangerate.host response".to_string()))?;
+    let pair = format!("{}{}", currency.to_uppercase(), base_currency.to_uppercase());
+    
+    Ok(quotes.iter()
+        .filter_map(|(day, rates)| {
+            let date = NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()?;
+            Some((date, rates.get(&pair)?.as_f64()?))
+        })
+        .collect())
+}
+
+/// URL with the values of key parameters (access_key, api_key, ...) hidden, for messages
+pub(crate) fn redact_url(url: &str) -> String {
+    let Some((base, query)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let query: Vec<String> = query.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if name.to_ascii_lowercase().contains("key") => format!("{}=***", name),
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("{}?{}", base, query.join("&"))
+}
+
+#[cfg(feature = "fx")]
+pub(crate) fn fetch_json(url: &str) -> Result<Value, PdwError> {
+    ureq::get(url)
+        .timeout(std::time::Duration::from_secs(30))
+        .call()
+        .map_err(|e| {
+            // ureq errors quote the URL, API key included
+            let reason = match &e {
+                ureq::Error::Status(code, _) => format!("HTTP {}", code),
+                ureq::Error::Transport(transport) => match transport.message() {
+                    Some(message) => format!("{}: {}", transport.kind(), message),
+                    None => transport.kind().to_string(),
+                },
+            };
+            PdwError::ExchangeRate(format!("Rate request to {} failed: {}", redact_url(url), reason))
+        })?
+        .into_json()
+        .map_err(|e| PdwError::ExchangeRate(format!("Invalid rate response: {}", e)))
+}
+
+#[cfg(not(feature = "fx"))]
+pub(crate) fn fetch_json(_url: &str) -> Result<Value, PdwError> {
+    Err(PdwError::ExchangeRate("Online rate download requires a build with --features fx".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+    
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+    
+    #[test]
+    fn test_parse_provider_responses() {
+        let ptax = json!({"value": [
+            {"cotacaoCompra": 4.90, "cotacaoVenda": 4.91, "dataHoraCotacao": "2024-01-15 10:04:27.510", "tipoBoletim": "Abertura"},
+            {"cotacaoCompra": 4.88, "cotacaoVenda": 4.89, "dataHoraCotacao": "2024-01-15 13:03:28.254", "tipoBoletim": "Fechamento"},
+        ]});
+        assert_eq!(parse_ptax(&ptax).unwrap(), vec![(date("2024-01-15"), 4.89)]);
+        
+        let host = json!({"success": true, "quotes": {"2024-01-15": {"USDBRL": 4.9}, "2024-01-16": {"USDEUR": 0.9}}});
+        assert_eq!(parse_exchangerate_host(&host, "usd", "BRL").unwrap(), vec![(date("2024-01-15"), 4.9)]);
+        assert!(parse_exchangerate_host(&json!({"success": false, "error": {}}), "USD", "BRL").is_err());
+    }
+    
+    #[test]
+    fn test_cache_lookup_keeps_first_rate() {
+        let mut cache = RateCache::default();
+        assert!(cache.insert("usd", date("2024-01-12"), 4.87));
+        assert!(!cache.insert("USD", date("2024-01-12"), 5.00));
+        
+        // Weekend falls back to Friday, but not beyond the lookback window
+        assert_eq!(cache.on_or_before("USD", date("2024-01-14"), 7), Some((date("2024-01-12"), 4.87)));
+        assert_eq!(cache.on_or_before("USD", date("2024-01-30"), 7), None);
+        assert_eq!(cache.on_or_before("EUR", date("2024-01-12"), 7), None);
+    }
+    
+    #[test]
+    fn test_offline_rates_from_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("fx_rates.json");
+        let mut cache = RateCache::default();
+        cache.insert("USD", date("2024-01-12"), 4.87);
+        cache.save(&path).unwrap();
+        
+        let config = FxConfig { offline: true, ..Default::default() };
+        let mut rates = FxRates::open(&config, path).unwrap();
+        assert_eq!(rates.rate("BRL", date("2024-01-13")).unwrap(), 1.0);
+        assert_eq!(rates.rate("USD", date("2024-01-13")).unwrap(), 4.87);
+        assert!(rates.rate("EUR", date("2024-01-13")).is_err());
+        assert!(rates.prefetch("USD", date("2024-01-01"), date("2024-01-31")).is_err());
+    }
+    
+    #[test]
+    fn test_redact_url() {
+        let config = FxConfig {
+            provider: RateProvider::ExchangerateHost,
+            api_key: Some("s3cret".to_string()),
+            base_currency: "BRL".to_string(),
+            ..Default::default()
+        };
+        let url = provider_url(&config, "usd", date("2024-01-01"), date("2024-01-31")).unwrap();
+        let redacted = redact_url(&url);
+        assert!(!redacted.contains("s3cret"), "{}", redacted);
+        assert!(redacted.contains("access_key=***&source=USD&currencies=BRL"), "{}", redacted);
+        assert_eq!(redact_url("https://api.bcb.gov.br/dados"), "https://api.bcb.gov.br/dados");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,157 @@
+/*!
+# Importers Module
+
+Bank downloads loaded next to the workbook sheets, without retyping them.
+Each `[sources.<name>]` table points at a file or directory of downloads in a
+given format; the transactions read from it join the entries of the workbook
+and go through the same transformation (type normalization, categorization,
+statement cycles). Downloads carry no PDW TIPO, so sources usually rely on the
+categorization rules or a `default_tipo`.
+*/
+
+pub mod ofx;
+
+use crate::error::{ExcelError, PdwError};
+use crate::excel::Transaction;
+use crate::input_files;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Source of entries registered in code (`EtlPipeline::register_importer`),
+/// loaded like a `[sources]` entry
+pub trait Importer {
+    /// Entries of the source; `dir_in` is the configured input directory
+    fn read(&self, dir_in: &Path) -> Result<Vec<Transaction>, PdwError>;
+}
+
+/// File format of a source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceFormat {
+    /// OFX 1.x (SGML) or 2.x (XML) statements, including Quicken's QFX
+    #[default]
+    #[serde(alias = "qfx")]
+    Ofx,
+}
+
+/// One source of bank downloads (`[sources.<name>]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub format: SourceFormat,
+    /// File or directory of downloads, relative to the input directory
+    pub path: PathBuf,
+    /// Origin of every entry; by default the account id, or its `accounts` mapping
+    #[serde(default)]
+    pub origin: Option<String>,
+    /// Account id mapped to the origin of its entries
+    #[serde(default)]
+    pub accounts: BTreeMap<String, String>,
+    /// TIPO of the imported entries (categorization rules may fill it instead)
+    #[serde(default)]
+    pub default_tipo: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl SourceConfig {
+    /// Origin of the entries of an account
+    pub fn origin_of(&self, account_id: &str) -> String {
+        self.accounts.get(account_id)
+            .or(self.origin.as_ref())
+            .cloned()
+            .unwrap_or_else(|| account_id.to_string())
+    }
+    
+    /// Download files of the source, sorted by name
+    pub fn files(&self, dir_in: &Path) -> Result<Vec<PathBuf>, PdwError> {
+        let path = dir_in.join(&self.path);
+        if path.is_file() {
+            return Ok(vec![path]);
+        }
+        
+        let extensions: &[&str] = match self.format {
+            SourceFormat::Ofx => &["ofx", "qfx"],
+        };
+        input_files::list(&path, extensions).map_err(|e| ExcelError::FileOpen {
+            path: path.to_string_lossy().to_string(),
+            reason: e.to_string(),
+        }.into())
+    }
+}
+
+/// Read every download of a source; transactions repeated across overlapping
+/// downloads (same account and FITID) are kept once
+pub fn read_source(name: &str, source: &SourceConfig, dir_in: &Path) -> Result<Vec<Transaction>, PdwError> {
+    let mut seen = HashSet::new();
+    let mut transactions = Vec::new();
+    
+    for file in source.files(dir_in)? {
+        input_files::hydrate(&file)?;
+        let entries = match source.format {
+            SourceFormat::Ofx => ofx::read_file(&file)?,
+        };
+        tracing::debug!("Source {}: {} entries in {}", name, entries.len(), file.display());
+        
+        for entry in entries {
+            if !entry.fit_id.is_empty() && !seen.insert((entry.account_id.clone(), entry.fit_id.clone())) {
+                continue;
+            }
+            let origin = source.origin_of(&entry.account_id);
+            transactions.push(entry.into_transaction(origin, source.default_tipo.clone()));
+        }
+    }
+    
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    const STATEMENT: &str = "OFXHEADER:100\nDATA:OFXSGML\n\n<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS>\
+        <BANKACCTFROM><BANKID>260<ACCTID>12345-6</BANKACCTFROM><BANKTRANLIST>\
+        <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240115<TRNAMT>-42.50<FITID>A1<MEMO>Mercado</STMTTRN>\
+        <STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20240120<TRNAMT>100.00<FITID>A2<MEMO>Pix recebido</STMTTRN>\
+        </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+    
+    #[test]
+    fn test_read_source_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let downloads = temp_dir.path().join("extratos");
+        std::fs::create_dir(&downloads).unwrap();
+        std::fs::write(downloads.join("janeiro.ofx"), STATEMENT).unwrap();
+        // Overlapping download of the same period
+        std::fs::write(downloads.join("janeiro-2.QFX"), STATEMENT).unwrap();
+        std::fs::write(downloads.join("notas.txt"), "ignored").unwrap();
+        
+        let source: SourceConfig = toml::from_str(
+            "path = \"extratos\"\ndefault_tipo = \"A CLASSIFICAR\"\n[accounts]\n\"12345-6\" = \"Nubank\""
+        ).unwrap();
+        assert!(source.enabled);
+        assert_eq!(source.files(temp_dir.path()).unwrap().len(), 2);
+        
+        let transactions = read_source("nubank", &source, temp_dir.path()).unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert!(transactions.iter().all(|t| t.origin == "Nubank"));
+        assert_eq!(transactions[0].transaction_type.as_deref(), Some("A CLASSIFICAR"));
+    }
+    
+    #[test]
+    fn test_origin_of_account() {
+        let mut source: SourceConfig = toml::from_str("path = \"x.ofx\"\nformat = \"qfx\"").unwrap();
+        assert_eq!(source.format, SourceFormat::Ofx);
+        assert_eq!(source.origin_of("999"), "999");
+        
+        source.origin = Some("Itau".to_string());
+        source.accounts.insert("111".to_string(), "Poupanca".to_string());
+        assert_eq!(source.origin_of("999"), "Itau");
+        assert_eq!(source.origin_of("111"), "Poupanca");
+    }
+}
\ No newline at end of file
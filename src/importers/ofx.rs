@@ -0,0 +1,234 @@
+/*!
+# OFX Importer
+
+Parses OFX/QFX statement downloads. OFX 1.x files are SGML whose value tags are
+usually left unclosed (`<TRNAMT>-42.50`), OFX 2.x files are XML; both are read
+by the same tag scanner. Each `<STMTTRN>` becomes an entry of the account of the
+enclosing statement (`<BANKACCTFROM>` or `<CCACCTFROM>`), in the statement
+currency (`<CURDEF>`): the memo (or the name when there is no memo) is the
+description and the sign of the amount chooses between credit and debit.
+*/
+
+use crate::error::{ExcelError, PdwError};
+use crate::excel::Transaction;
+use crate::money::Decimal;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Transaction read from a statement download
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementEntry {
+    pub account_id: String,
+    pub date: NaiveDate,
+    /// Positive for credits, negative for debits
+    pub amount: Decimal,
+    pub fit_id: String,
+    pub name: String,
+    pub memo: String,
+    /// Statement currency, empty when the statement does not declare one
+    pub currency: String,
+}
+
+impl StatementEntry {
+    /// PDW entry with the given origin and TIPO
+    pub fn into_transaction(self, origin: String, transaction_type: Option<String>) -> Transaction {
+        let description = if self.memo.is_empty() { self.name } else { self.memo };
+        let (credit, debit) = if self.amount < Decimal::ZERO {
+            (None, Some(-self.amount))
+        } else {
+            (Some(self.amount), None)
+        };
+        
+        Transaction {
+            date: Some(self.date),
+            transaction_type,
+            description: Some(description).filter(|d| !d.is_empty()),
+            credit,
+            debit,
+            origin,
+            currency: Some(self.currency).filter(|c| !c.is_empty()),
+            tags: None,
+            row: None,
+            invalid_amount: None,
+        }
+    }
+}
+
+/// Read and parse a download; files that are not UTF-8 are read as Latin-1,
+/// the usual charset of Brazilian bank exports
+pub fn read_file(path: &Path) -> Result<Vec<StatementEntry>, PdwError> {
+    let bytes = std::fs::read(path).map_err(|e| ExcelError::FileOpen {
+        path: path.to_string_lossy().to_string(),
+        reason: e.to_string(),
+    })?;
+    let content = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+    };
+    
+    parse(&content).map_err(|reason| ExcelError::InvalidStructure {
+        sheet_name: path.to_string_lossy().to_string(),
+        reason,
+    }.into())
+}
+
+/// Entries of every statement in an OFX document
+pub fn parse(content: &str) -> Result<Vec<StatementEntry>, String> {
+    let Some(start) = content.find("<OFX>").or_else(|| content.find("<ofx>")) else {
+        return Err("no <OFX> element found".to_string());
+    };
+    
+    let mut entries = Vec::new();
+    let mut account_id = String::new();
+    let mut currency = String::new();
+    let mut fields: Option<HashMap<String, String>> = None;
+    
+    for (tag, text) in tags(&content[start..]) {
+        match tag.as_str() {
+            // Transfers name the other account (BANKACCTTO/CCACCTTO) inside the entry
+            "ACCTID" if fields.is_none() => account_id = text,
+            "CURDEF" => currency = text.to_uppercase(),
+            "STMTTRN" => fields = Some(HashMap::new()),
+            "/STMTTRN" => {
+                if let Some(fields) = fields.take() {
+                    entries.push(entry(&account_id, &currency, &fields)?);
+                }
+            }
+            _ => {
+                if let Some(fields) = fields.as_mut() {
+                    if !tag.starts_with('/') && !text.is_empty() {
+                        fields.insert(tag, text);
+                    }
+                }
+            }
+        }
+    }
+    
+    Ok(entries)
+}
+
+/// Tag names (uppercased, `/` kept on closing tags) with the text following them
+fn tags(content: &str) -> Vec<(String, String)> {
+    content.split('<')
+        .skip(1)
+        .filter_map(|piece| {
+            let (tag, text) = piece.split_once('>')?;
+            let tag = tag.trim().trim_end_matches('/').to_uppercase();
+            // Skip XML declarations and processing instructions
+            (!tag.starts_with('?') && !tag.starts_with('!')).then(|| (tag, unescape(text.trim())))
+        })
+        .collect()
+}
+
+fn entry(account_id: &str, currency: &str, fields: &HashMap<String, String>) -> Result<StatementEntry, String> {
+    let field = |name: &str| fields.get(name).cloned().unwrap_or_default();
+    
+    let posted = field("DTPOSTED");
+    let date = parse_date(&posted).ok_or_else(|| format!("invalid DTPOSTED '{}'", posted))?;
+    let amount_text = field("TRNAMT");
+    let amount = parse_amount(&amount_text).ok_or_else(|| format!("invalid TRNAMT '{}'", amount_text))?;
+    
+    Ok(StatementEntry {
+        account_id: account_id.to_string(),
+        date,
+        amount,
+        fit_id: field("FITID"),
+        name: field("NAME"),
+        memo: field("MEMO"),
+        currency: currency.to_string(),
+    })
+}
+
+/// OFX date (`YYYYMMDD`, optionally followed by time and zone)
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    let digits = text.get(..8)?;
+    NaiveDate::parse_from_str(digits, "%Y%m%d").ok()
+}
+
+/// OFX amount; some banks write a decimal comma
+fn parse_amount(text: &str) -> Option<Decimal> {
+    text.trim().replace(',', ".").parse().ok()
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ofx::{OfxStatement, OfxTransaction};
+    
+    #[test]
+    fn test_sgml_statement() {
+        let content = "OFXHEADER:100\r\nDATA:OFXSGML\r\nCHARSET:1252\r\n\r\n<OFX>\r\n<CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS><CURDEF>usd\r\n\
+            <CCACCTFROM><ACCTID>5555 **** 1234</CCACCTFROM>\r\n<BANKTRANLIST>\r\n\
+            <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240115120000[-3:BRT]<TRNAMT>-42,50<FITID>F1\
+            <NAME>PADARIA<MEMO>Padaria P&amp;A</STMTTRN>\r\n\
+            <STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20240120<TRNAMT>100<FITID>F2<NAME>ESTORNO</STMTTRN>\r\n\
+            </BANKTRANLIST></CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1></OFX>";
+        
+        let entries = parse(content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].account_id, "5555 **** 1234");
+        assert_eq!(entries[0].date, NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
+        assert_eq!(entries[0].amount, Decimal::new(-4250, 2));
+        assert_eq!(entries[0].memo, "Padaria P&A");
+        
+        let debit = entries[0].clone().into_transaction("Cartao".to_string(), None);
+        assert_eq!(debit.debit, Some(Decimal::new(4250, 2)));
+        assert_eq!(debit.credit, None);
+        assert_eq!(debit.description.as_deref(), Some("Padaria P&A"));
+        assert_eq!(debit.currency.as_deref(), Some("USD"));
+        // No memo: the name is the description
+        let credit = entries[1].clone().into_transaction("Cartao".to_string(), None);
+        assert_eq!(credit.credit, Some(Decimal::from(100)));
+        assert_eq!(credit.description.as_deref(), Some("ESTORNO"));
+    }
+    
+    #[test]
+    fn test_xml_statement_round_trip() {
+        // Statements written by `pdw export --ofx` (closed tags) read back unchanged
+        let mut statement = OfxStatement::new("Conta", "BRL");
+        statement.transactions.push(OfxTransaction {
+            date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            amount: -10.0,
+            fit_id: "X1".to_string(),
+            name: "ALM".to_string(),
+            memo: "Feira <sábado>".to_string(),
+        });
+        let content = statement.to_ofx(NaiveDate::from_ymd_opt(2024, 3, 2).unwrap());
+        
+        let entries = parse(&content).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].account_id, "Conta");
+        assert_eq!(entries[0].amount, Decimal::from(-10));
+        assert_eq!(entries[0].memo, "Feira <sábado>");
+        assert_eq!(entries[0].currency, "BRL");
+    }
+    
+    #[test]
+    fn test_transfer_keeps_statement_account() {
+        let content = "<OFX><STMTRS><CURDEF>BRL<BANKACCTFROM><BANKID>001<ACCTID>1234-5</BANKACCTFROM><BANKTRANLIST>\
+            <STMTTRN><TRNTYPE>XFER<DTPOSTED>20240201<TRNAMT>-500<FITID>T1<NAME>TRANSFERENCIA\
+            <BANKACCTTO><BANKID>237<ACCTID>9999-0</BANKACCTTO></STMTTRN>\
+            <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240202<TRNAMT>-20<FITID>T2<NAME>TARIFA</STMTTRN>\
+            </BANKTRANLIST></STMTRS></OFX>";
+        
+        let entries = parse(content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.account_id == "1234-5"));
+    }
+    
+    #[test]
+    fn test_invalid_documents() {
+        assert!(parse("not an ofx file").is_err());
+        let error = parse("<OFX><STMTTRN><DTPOSTED>2024<TRNAMT>1</STMTTRN></OFX>").unwrap_err();
+        assert!(error.contains("DTPOSTED"), "{}", error);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,163 @@
+/*!
+# Input Files Module
+
+Files the input paths pick up. Directory inputs (CSV sheet files, bank
+downloads) skip what Office, LibreOffice, editors and sync clients leave next to
+the real files: `~$PDW.xlsx` and `.~lock.PDW.xlsx#` lock files, hidden files
+(including iCloud `.name.icloud` stubs), `name~` backups and `.tmp` partial
+downloads.
+
+OneDrive "online-only" files are placeholders until something reads them. On
+Windows, input files marked as such are read through once so OneDrive downloads
+them, then checked again until the placeholder flag clears or
+`HYDRATION_TIMEOUT` passes.
+*/
+
+use crate::error::{ExcelError, PdwError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use attributes::{is_cloud_placeholder, is_hidden};
+
+/// Longest wait for a cloud placeholder to be downloaded
+pub const HYDRATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Whether a file is a lock file, hidden file, backup or partial download rather than input
+pub fn is_temporary(path: &Path) -> bool {
+    let Some(file_name) = path.file_name().map(|name| name.to_string_lossy()) else {
+        return false;
+    };
+    
+    file_name.starts_with("~$")
+        || file_name.starts_with('.')
+        || file_name.ends_with('~')
+        || path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tmp"))
+        || is_hidden(path)
+}
+
+/// Input files of a directory with one of the extensions (case ignored), sorted by name
+pub fn list(directory: &Path, extensions: &[&str]) -> std::io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e))))
+        .filter(|path| {
+            let skipped = is_temporary(path);
+            if skipped {
+                tracing::debug!("Ignoring temporary file {}", path.display());
+            }
+            !skipped
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Lock file of a workbook, present while Excel (`~$name`) or LibreOffice (`.~lock.name#`) has it open
+pub fn lock_file(workbook: &Path) -> Option<PathBuf> {
+    let file_name = workbook.file_name()?.to_string_lossy();
+    [format!("~${}", file_name), format!(".~lock.{}#", file_name)].into_iter()
+        .map(|lock| workbook.with_file_name(lock))
+        .find(|lock| lock.exists())
+}
+
+/// Make sure an input file has its contents on disk, downloading a cloud placeholder
+pub fn hydrate(path: &Path) -> Result<(), PdwError> {
+    hydrate_within(path, HYDRATION_TIMEOUT)
+}
+
+fn hydrate_within(path: &Path, timeout: Duration) -> Result<(), PdwError> {
+    if !is_cloud_placeholder(path) {
+        return Ok(());
+    }
+    
+    tracing::info!("   . .. ... {} is an online-only file - downloading it", path.display());
+    let started = Instant::now();
+    loop {
+        // Reading the contents makes the sync client fetch them
+        let read = std::fs::File::open(path)
+            .and_then(|mut file| std::io::copy(&mut file, &mut std::io::sink()));
+        if read.is_ok() && !is_cloud_placeholder(path) {
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            return Err(ExcelError::FileOpen {
+                path: path.to_string_lossy().to_string(),
+                reason: match read {
+                    Err(e) => format!("online-only file could not be downloaded: {}", e),
+                    Ok(_) => format!("online-only file still not downloaded after {}s - \
+                                      mark it \"Always keep on this device\"", timeout.as_secs()),
+                },
+            }.into());
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+#[cfg(windows)]
+mod attributes {
+    use std::os::windows::fs::MetadataExt;
+    use std::path::Path;
+    
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+    
+    fn attributes(path: &Path) -> u32 {
+        std::fs::metadata(path).map_or(0, |metadata| metadata.file_attributes())
+    }
+    
+    pub fn is_hidden(path: &Path) -> bool {
+        attributes(path) & FILE_ATTRIBUTE_HIDDEN != 0
+    }
+    
+    pub fn is_cloud_placeholder(path: &Path) -> bool {
+        attributes(path) & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0
+    }
+}
+
+#[cfg(not(windows))]
+mod attributes {
+    use std::path::Path;
+    
+    /// Hidden files are dot files, already skipped by name
+    pub fn is_hidden(_path: &Path) -> bool {
+        false
+    }
+    
+    /// Sync clients here keep downloaded files (or named stubs, skipped as hidden)
+    pub fn is_cloud_placeholder(_path: &Path) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_lock_and_temporary_files_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["Conta.csv", "~$Conta.csv", ".~lock.Conta.csv#", "Cartao.CSV", ".Poupanca.csv.icloud",
+                     "Conta.csv~", "Download.tmp", "notes.txt"] {
+            std::fs::write(temp_dir.path().join(name), "Data;TIPO\n").unwrap();
+        }
+        
+        let names: Vec<String> = list(temp_dir.path(), &["csv"]).unwrap().iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["Cartao.CSV", "Conta.csv"]);
+        
+        let workbook = temp_dir.path().join("PDW.xlsx");
+        assert_eq!(lock_file(&workbook), None);
+        std::fs::write(temp_dir.path().join("~$PDW.xlsx"), "").unwrap();
+        assert_eq!(lock_file(&workbook), Some(temp_dir.path().join("~$PDW.xlsx")));
+        std::fs::write(temp_dir.path().join(".~lock.PDW.ods#"), "").unwrap();
+        assert_eq!(lock_file(&temp_dir.path().join("PDW.ods")), Some(temp_dir.path().join(".~lock.PDW.ods#")));
+        assert!(is_temporary(&temp_dir.path().join("~$PDW.xlsx")));
+        
+        // Regular files are already local
+        assert!(hydrate_within(&workbook, Duration::ZERO).is_ok());
+    }
+}
\ No newline at end of file
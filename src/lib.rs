@@ -0,0 +1,126 @@
+/*!
+# Personal Data Warehouse (PDW) - Library
+
+Embeddable ETL pipeline turning Excel financial workbooks into a SQLite data
+warehouse with pivot tables and reports. The `pdw` binary is a thin wrapper
+around this crate.
+
+The types re-exported at the crate root form the stable public API:
+
+```no_run
+use pdw_rust::{DatabaseManager, EtlPipeline, PdwConfig, ReportGenerator};
+use std::path::Path;
+
+let config = PdwConfig::load(Path::new("pdw_config.toml"))?;
+let mut pipeline = EtlPipeline::new(config.clone())?;
+pipeline.execute_data_loading()?;
+pipeline.create_pivot_tables()?;
+pipeline.database().flush_to_disk()?; // no-op unless [database.sqlite] in_memory
+
+let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+ReportGenerator::new(database, config).generate_excel_reports()?;
+# Ok::<(), pdw_rust::PdwError>(())
+```
+
+Modules remain public for advanced use, but their contents may change between
+minor versions. The `examples/` directory shows configuration built in code, a
+custom importer, an in-memory run read back as a `Frame`, and embedded reports
+(`cargo run --example in_memory_frame`).
+*/
+
+pub mod alerts;
+pub mod analytics;
+pub mod baselines;
+pub mod branding;
+pub mod budgets;
+pub mod cash_flow;
+#[cfg(feature = "arrow")]
+pub mod arrow_export;
+pub mod categorize;
+pub mod category_groups;
+pub mod charts;
+pub mod checkpoint;
+pub mod collation;
+pub mod compat;
+pub mod compression;
+pub mod config;
+pub mod consistency;
+pub mod consolidate;
+pub mod csv_input;
+pub mod currency;
+pub mod cycles;
+pub mod daemon;
+pub mod database;
+pub mod deterministic;
+pub mod digest;
+pub mod diskspace;
+pub mod duplicates;
+#[cfg(feature = "datafusion")]
+pub mod datafusion_engine;
+pub mod error;
+pub mod etl;
+pub mod excel;
+pub mod export_profiles;
+pub mod export_schema;
+pub mod expression;
+pub mod fees;
+pub mod forecast;
+pub mod frame;
+pub mod fx;
+pub mod importers;
+pub mod input_files;
+pub mod locale;
+pub mod logging;
+pub mod migrations;
+pub mod money;
+pub mod mqtt;
+pub mod non_data;
+pub mod notifications;
+pub mod parity;
+pub mod pdf;
+pub mod preflight;
+pub mod ofx;
+pub mod opening_balances;
+#[cfg(feature = "postgres")]
+pub mod postgres_backend;
+pub mod publish;
+pub mod purge;
+pub mod quality;
+pub mod quarantine;
+pub mod reconciliation;
+pub mod recovery;
+pub mod reference;
+pub mod report_charts;
+pub mod report_diff;
+pub mod report_dictionary;
+pub mod report_dispatch;
+pub mod report_shape;
+pub mod reporting;
+pub mod runs;
+pub mod sample;
+pub mod sanity;
+pub mod self_update;
+pub mod server;
+pub mod shell;
+pub mod splits;
+pub mod tags;
+pub mod telemetry;
+pub mod trash;
+pub mod type_normalization;
+pub mod watch;
+pub mod window;
+pub mod workbook;
+
+pub use crate::config::PdwConfig;
+pub use crate::database::{DatabaseManager, DatabaseOperations, ProcessedTransaction};
+pub use crate::error::{PdwError, PdwResult};
+pub use crate::etl::{EtlOperations, EtlPipeline, TransformAction, TransformHook};
+pub use crate::csv_input::CsvProcessor;
+pub use crate::excel::{ExcelProcessor, ExcelReader, SheetConfig, Transaction};
+pub use crate::frame::Frame;
+pub use crate::importers::Importer;
+pub use crate::reporting::{ReportGenerator, ReportOperations};
+pub use crate::workbook::WorkbookWriter;
+
+/// Library version (matches the Python PDW release it mirrors)
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
\ No newline at end of file
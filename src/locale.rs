@@ -0,0 +1,267 @@
+/*!
+# Locale Module
+
+Language and number/date conventions of the warehouse output (`[locale]`):
+the day-of-week and month names stored with every entry (DIA_SEMANA and
+MES_EXTENSO), the decimal separator and delimiter of the CSV exports and the
+date format of their "Quando" column. The defaults reproduce the Brazilian
+Portuguese output of the Python PDW.
+
+The decimal and thousands separators also read amount cells typed as text in
+the workbook ("1.234,56"); a cell they cannot read keeps its row out of the
+load, with the conversion error in the discarded table.
+*/
+
+use crate::error::{ConfigError, PdwError};
+use crate::money::Decimal;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Currency symbols dropped from text amounts
+const CURRENCY_SYMBOLS: [&str; 6] = ["R$", "US$", "$", "€", "£", "¥"];
+
+/// Language of the day and month names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    #[serde(alias = "pt-br", alias = "pt_br")]
+    Pt,
+    #[serde(alias = "en-us", alias = "en_us")]
+    En,
+    Es,
+}
+
+/// Output locale settings (`[locale]`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Locale {
+    #[serde(default)]
+    pub language: Language,
+    /// Decimal separator of the numbers in CSV exports and of text amount cells
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: char,
+    /// Thousands separator of text amount cells; '.' with a ',' decimal separator, ',' otherwise
+    #[serde(default)]
+    pub thousands_separator: Option<char>,
+    /// Field delimiter of the CSV exports
+    #[serde(default = "default_csv_delimiter")]
+    pub csv_delimiter: char,
+    /// Date format of exported dates, with %d, %m and %Y placeholders
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+}
+
+fn default_decimal_separator() -> char {
+    ','
+}
+
+fn default_csv_delimiter() -> char {
+    ';'
+}
+
+fn default_date_format() -> String {
+    "%d-%m-%Y".to_string()
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            language: Language::default(),
+            decimal_separator: default_decimal_separator(),
+            thousands_separator: None,
+            csv_delimiter: default_csv_delimiter(),
+            date_format: default_date_format(),
+        }
+    }
+}
+
+/// Day names from Monday, as chrono numbers them
+const PT_DAYS: [&str; 7] = ["Segunda-feira", "Terça-feira", "Quarta-feira", "Quinta-feira", "Sexta-feira", "Sábado", "Domingo"];
+const EN_DAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const ES_DAYS: [&str; 7] = ["Lunes", "Martes", "Miércoles", "Jueves", "Viernes", "Sábado", "Domingo"];
+
+const PT_MONTHS: [&str; 12] = [
+    "Janeiro", "Fevereiro", "Março", "Abril", "Maio", "Junho",
+    "Julho", "Agosto", "Setembro", "Outubro", "Novembro", "Dezembro",
+];
+const EN_MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+const ES_MONTHS: [&str; 12] = [
+    "Enero", "Febrero", "Marzo", "Abril", "Mayo", "Junio",
+    "Julio", "Agosto", "Septiembre", "Octubre", "Noviembre", "Diciembre",
+];
+
+impl Locale {
+    /// Day-of-week name of a date (DIA_SEMANA)
+    pub fn day_name(&self, date: NaiveDate) -> String {
+        let days = match self.language {
+            Language::Pt => &PT_DAYS,
+            Language::En => &EN_DAYS,
+            Language::Es => &ES_DAYS,
+        };
+        days[date.weekday().num_days_from_monday() as usize].to_string()
+    }
+    
+    /// Month number and name, sortable as text (MES_EXTENSO: "01-Janeiro")
+    pub fn month_name(&self, month: u32) -> String {
+        let (months, invalid) = match self.language {
+            Language::Pt => (&PT_MONTHS, "Inválido"),
+            Language::En => (&EN_MONTHS, "Invalid"),
+            Language::Es => (&ES_MONTHS, "Inválido"),
+        };
+        match month.checked_sub(1).and_then(|index| months.get(index as usize)) {
+            Some(name) => format!("{:02}-{}", month, name),
+            None => format!("00-{}", invalid),
+        }
+    }
+    
+    /// Number as exported to CSV, with the locale's decimal separator
+    pub fn format_number(&self, number: &serde_json::Number) -> String {
+        number.to_string().replace('.', &self.decimal_separator.to_string())
+    }
+    
+    /// SQL expression formatting a stored date column the way exports show dates
+    pub fn sql_date(&self, column: &str) -> String {
+        format!("strftime('{}', {})", self.date_format.replace('\'', "''"), column)
+    }
+    
+    /// Thousands separator of text amounts
+    pub fn thousands(&self) -> char {
+        self.thousands_separator
+            .unwrap_or(if self.decimal_separator == ',' { '.' } else { ',' })
+    }
+    
+    /// Amount written as text with the locale's separators ("1.234,56", "R$ -10,00", "(15,90)");
+    /// plain "1234.56" is still read when the separators do not fit it. Only whitespace and
+    /// currency symbols are skipped; the error is the reason the text is not a number.
+    pub fn parse_amount(&self, text: &str) -> Result<Decimal, String> {
+        let (decimal, thousands) = (self.decimal_separator, self.thousands());
+        let mut cleaned = text.to_string();
+        for symbol in CURRENCY_SYMBOLS {
+            cleaned = cleaned.replace(symbol, "");
+        }
+        let cleaned: String = cleaned.trim().chars()
+            .filter(|c| !c.is_whitespace() || *c == thousands)
+            .collect();
+        if let Some(other) = cleaned.chars()
+            .find(|c| !c.is_ascii_digit() && !matches!(c, '-' | '(' | ')') && *c != decimal && *c != thousands)
+        {
+            return Err(format!("'{}' is not a number: unexpected '{}'", text.trim(), other));
+        }
+        let cleaned = cleaned.trim();
+        let negative = cleaned.starts_with('(') && cleaned.ends_with(')');
+        let cleaned = cleaned.trim_matches(|c| c == '(' || c == ')').trim();
+        
+        let (integer, fraction) = match cleaned.rsplit_once(decimal) {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (cleaned, None),
+        };
+        let (sign, integer) = match integer.strip_prefix('-') {
+            Some(digits) => ("-", digits.trim()),
+            None => ("", integer),
+        };
+        let groups: Vec<&str> = integer.split(thousands).collect();
+        let grouped = groups.len() == 1
+            || (matches!(groups[0].len(), 1..=3) && groups[1..].iter().all(|group| group.len() == 3));
+        let digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+        
+        if grouped && groups.iter().copied().all(digits) && fraction.map_or(true, digits) {
+            let number = format!("{}{}.{}", sign, groups.concat(), fraction.unwrap_or("0"));
+            if let Ok(value) = number.parse::<Decimal>() {
+                return Ok(if negative { -value } else { value });
+            }
+        }
+        
+        text.trim().parse().map_err(|_| format!(
+            "'{}' is not a number with '{}' as decimal and '{}' as thousands separator",
+            text.trim(), decimal, thousands
+        ))
+    }
+    
+    /// Separators that cannot tell the parts of a number apart
+    pub fn check(&self) -> Result<(), PdwError> {
+        let thousands = self.thousands();
+        if thousands == self.decimal_separator || thousands.is_ascii_digit() || self.decimal_separator.is_ascii_digit() {
+            return Err(ConfigError::InvalidFormat {
+                message: format!(
+                    "locale.decimal_separator ('{}') and locale.thousands_separator ('{}') must be different non-digits",
+                    self.decimal_separator, thousands
+                ),
+            }.into());
+        }
+        if !self.csv_delimiter.is_ascii() {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("locale.csv_delimiter ('{}') must be a single ASCII character", self.csv_delimiter),
+            }.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_names_per_language() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 17).unwrap(); // Wednesday
+        let mut locale = Locale::default();
+        assert_eq!((locale.day_name(date), locale.month_name(3)), ("Quarta-feira".to_string(), "03-Março".to_string()));
+        
+        locale = toml::from_str("language = \"en\"").unwrap();
+        assert_eq!((locale.day_name(date), locale.month_name(12)), ("Wednesday".to_string(), "12-December".to_string()));
+        assert_eq!(locale.month_name(0), "00-Invalid");
+        
+        locale = toml::from_str("language = \"es\"").unwrap();
+        assert_eq!((locale.day_name(date), locale.month_name(9)), ("Miércoles".to_string(), "09-Septiembre".to_string()));
+    }
+    
+    #[test]
+    fn test_number_and_date_formats() {
+        let locale = Locale::default();
+        assert_eq!(locale.format_number(json!(1234.5).as_number().unwrap()), "1234,5");
+        assert_eq!(locale.sql_date("LG.Data"), "strftime('%d-%m-%Y', LG.Data)");
+        
+        let locale: Locale = toml::from_str("language = \"en-us\"\ndecimal_separator = \".\"\ncsv_delimiter = \",\"\ndate_format = \"%m/%d/%Y\"").unwrap();
+        assert_eq!(locale.language, Language::En);
+        assert_eq!(locale.format_number(json!(1234.5).as_number().unwrap()), "1234.5");
+        
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        let shown: String = connection.query_row(&format!("SELECT {}", locale.sql_date("'2024-01-17 00:00:00'")), [], |row| row.get(0)).unwrap();
+        assert_eq!(shown, "01/17/2024");
+    }
+    
+    #[test]
+    fn test_parse_amount() {
+        let amount = |units: i64, scale: u32| Ok(Decimal::new(units, scale));
+        let locale = Locale::default();
+        assert_eq!(locale.parse_amount("1.234,56"), amount(123456, 2));
+        assert_eq!(locale.parse_amount("R$ -1.234.567,8"), amount(-12345678, 1));
+        assert_eq!(locale.parse_amount("(15,90)"), amount(-1590, 2));
+        assert_eq!(locale.parse_amount("1.234"), amount(1234, 0));
+        // Plain numbers the separators do not fit are read as before
+        assert_eq!(locale.parse_amount("10.5"), amount(105, 1));
+        assert!(locale.parse_amount("1,234.56").unwrap_err().contains("'1,234.56' is not a number"));
+        assert!(locale.parse_amount("dez reais").is_err());
+        assert_eq!(locale.parse_amount("12/01"), Err("'12/01' is not a number: unexpected '/'".to_string()));
+        assert!(locale.parse_amount("1O0").is_err());
+        assert!(locale.check().is_ok());
+        
+        let locale: Locale = toml::from_str("decimal_separator = \".\"").unwrap();
+        assert_eq!(locale.thousands(), ',');
+        assert_eq!(locale.parse_amount("$1,234.56"), amount(123456, 2));
+        assert_eq!(locale.parse_amount("-0.5"), amount(-5, 1));
+        
+        let locale: Locale = toml::from_str("thousands_separator = \" \"").unwrap();
+        assert_eq!(locale.parse_amount("1 234,56 €"), amount(123456, 2));
+        
+        let locale: Locale = toml::from_str("decimal_separator = \".\"\nthousands_separator = \".\"").unwrap();
+        assert!(locale.check().is_err());
+        
+        let locale: Locale = toml::from_str("csv_delimiter = \"§\"").unwrap();
+        assert!(locale.check().is_err());
+    }
+}
\ No newline at end of file
@@ -3,58 +3,265 @@
 
 Structured logging system compatible with the Python PDW log format
 while providing enhanced debugging capabilities.
+
+Records go through the `tracing` crate and are written by `PdwLayer` in the
+usual PDW text lines (or JSON objects). Phases, sheets and report queries are
+spans carrying their names as fields, so a slow run can be profiled by adding
+a flamegraph or other `tracing-subscriber` layer; JSON records include the
+fields of the spans they happen in. Dependencies logging through `log` are
+forwarded, and `RUST_LOG` filters everything.
 */
 
 use crate::error::PdwError;
-use env_logger::{Builder, Target};
-use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::cell::Cell;
+use std::fs::File;
 use std::io::Write;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, EnteredSpan, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Layout of the log records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Colored text lines (plain text in the log file)
+    #[default]
+    Text,
+    /// One JSON object per record, for log collectors
+    Json,
+}
+
+/// A record ready to be written
+#[derive(Debug, Clone, PartialEq)]
+struct LogLine {
+    level: Level,
+    target: String,
+    message: String,
+    /// Fields of the enclosing spans (outermost first), the step, then the record's own
+    fields: Map<String, Value>,
+}
+
+/// Message and fields recorded on an event or span
+#[derive(Debug, Clone, Default)]
+struct FieldValues {
+    message: Option<String>,
+    values: Map<String, Value>,
+}
+
+impl FieldValues {
+    fn insert(&mut self, field: &Field, value: Value) {
+        match (field.name(), value) {
+            ("message", Value::String(message)) => self.message = Some(message),
+            // Source location of records forwarded from `log`
+            (name, _) if name.starts_with("log.") => {}
+            (name, value) => {
+                self.values.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for FieldValues {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.insert(field, json!(format!("{:?}", value)));
+    }
+    
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, json!(value));
+    }
+    
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, json!(value));
+    }
+    
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, json!(value));
+    }
+    
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, json!(value));
+    }
+    
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, json!(value));
+    }
+}
+
+thread_local! {
+    /// Step of the latest `log_step`; it lasts until the next one or the next phase
+    static STEP: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Log file the records are copied to
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Writes every record to stdout and, once attached, to the log file
+struct PdwLayer {
+    format: LogFormat,
+}
+
+impl<S> Layer<S> for PdwLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = FieldValues::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+    
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<FieldValues>() {
+                values.record(fields);
+            }
+        }
+    }
+    
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<FieldValues>() {
+                    fields.extend(span_fields.values.clone());
+                }
+            }
+        }
+        if let Some(step) = STEP.with(Cell::get) {
+            fields.insert("step".to_string(), json!(step));
+        }
+        let mut recorded = FieldValues::default();
+        event.record(&mut recorded);
+        fields.extend(recorded.values);
+        
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let line = LogLine {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: recorded.message.unwrap_or_default(),
+            fields,
+        };
+        
+        let (console, file) = match self.format {
+            LogFormat::Text => (format_text(&line, true), format_text(&line, false)),
+            LogFormat::Json => {
+                let json_line = format_json(&line);
+                (json_line.clone(), json_line)
+            }
+        };
+        
+        let _ = writeln!(std::io::stdout().lock(), "{}", console);
+        if let Ok(mut log_file) = LOG_FILE.lock() {
+            if let Some(log_file) = log_file.as_mut() {
+                let _ = writeln!(log_file, "{}", file);
+            }
+        }
+    }
+}
 
 /// Initialize the logging system
 pub fn init_logger(verbose: bool) -> Result<(), PdwError> {
+    init_logger_with(verbose, LogFormat::Text)
+}
+
+/// Initialize the logging system with a record format
+pub fn init_logger_with(verbose: bool, format: LogFormat) -> Result<(), PdwError> {
     let log_level = if verbose {
-        LevelFilter::Debug
+        LevelFilter::DEBUG
     } else {
-        LevelFilter::Info
+        LevelFilter::INFO
     };
     
-    let mut builder = Builder::from_default_env();
-    
-    builder
-        .target(Target::Stdout)
-        .filter_level(log_level)
-        .format(|buf, record| {
-            let timestamp = chrono::Local::now().format("%Y/%m/%d %H:%M:%S");
-            
-            // Color coding for different log levels
-            let level_color = match record.level() {
-                log::Level::Error => "\x1b[31m", // Red
-                log::Level::Warn => "\x1b[33m",  // Yellow
-                log::Level::Info => "\x1b[32m",  // Green
-                log::Level::Debug => "\x1b[36m", // Cyan
-                log::Level::Trace => "\x1b[37m", // White
-            };
-            let reset_color = "\x1b[0m";
-            
-            writeln!(
-                buf,
-                "{} [{}{}{}] {}: {}",
-                timestamp,
-                level_color,
-                record.level(),
-                reset_color,
-                record.target(),
-                record.args()
-            )
-        })
-        .init();
+    let filter = EnvFilter::builder()
+        .with_default_directive(log_level.into())
+        .from_env_lossy();
     
-    Ok(())
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(PdwLayer { format })
+        .with(crate::telemetry::SpanTimer)
+        .try_init()
+        .map_err(|e| PdwError::Logging(format!("Failed to install logger: {}", e)))
+}
+
+/// Text line of a record, color coded for the terminal
+fn format_text(line: &LogLine, colored: bool) -> String {
+    let timestamp = chrono::Local::now().format("%Y/%m/%d %H:%M:%S");
+    if !colored {
+        return format!("{} [{}] {}: {}", timestamp, line.level, line.target, strip_ansi(&line.message));
+    }
+    
+    // Color coding for different log levels
+    let level_color = match line.level {
+        Level::ERROR => "\x1b[31m", // Red
+        Level::WARN => "\x1b[33m",  // Yellow
+        Level::INFO => "\x1b[32m",  // Green
+        Level::DEBUG => "\x1b[36m", // Cyan
+        Level::TRACE => "\x1b[37m", // White
+    };
+    let reset_color = "\x1b[0m";
+    
+    format!(
+        "{} [{}{}{}] {}: {}",
+        timestamp,
+        level_color,
+        line.level,
+        reset_color,
+        line.target,
+        line.message
+    )
+}
+
+/// JSON object of a record, with the span fields (phase, sheet, query), step and counts when known
+fn format_json(line: &LogLine) -> String {
+    let mut object = json!({
+        "timestamp": chrono::Local::now().to_rfc3339(),
+        "level": line.level.to_string(),
+        "target": line.target,
+        "message": strip_ansi(&line.message).trim(),
+    });
+    
+    for (key, value) in &line.fields {
+        object[key] = value.clone();
+    }
+    
+    object.to_string()
+}
+
+/// Remove ANSI color sequences from a message
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip up to and including the final letter of the sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
 }
 
 /// Log processing step with consistent formatting
 pub fn log_step(step_number: usize, description: &str, detail: &str) {
-    log::info!(
+    STEP.with(|step| step.set(Some(step_number)));
+    tracing::info!(
         "   . .. ... Step: {:04} :-> {} :-> {}",
         step_number,
         description,
@@ -64,22 +271,37 @@ pub fn log_step(step_number: usize, description: &str, detail: &str) {
 
 /// Log processing result with count
 pub fn log_result(description: &str, count: usize) {
-    log::info!(
+    tracing::info!(
+        count,
         "   . .. ... {} :-> \x1b[32m{:>6}\x1b[0m",
         description,
         count
     );
 }
 
+/// Log processing time of a step
+pub fn log_timing(description: &str, duration: std::time::Duration) {
+    let seconds = duration.as_secs_f64();
+    tracing::info!(
+        seconds,
+        "   . .. ... {} :-> \x1b[36m{:>9.3}s\x1b[0m",
+        description,
+        seconds
+    );
+}
+
 /// Log section separator (equivalent to Python's out_line)
 pub fn log_separator() {
-    log::info!("{}", "=".repeat(120));
+    tracing::info!("{}", "=".repeat(120));
 }
 
-/// Log processing phase start
-pub fn log_phase_start(phase_name: &str) {
+/// Log processing phase start; the phase span lasts while the returned guard is held
+pub fn log_phase_start(phase_name: &str) -> EnteredSpan {
+    STEP.with(|step| step.set(None));
     log_separator();
-    log::info!("{}", phase_name);
+    let phase = tracing::info_span!("phase", phase = phase_name).entered();
+    tracing::info!("{}", phase_name);
+    phase
 }
 
 /// Log system information (equivalent to Python startup info)
@@ -93,15 +315,15 @@ pub fn log_system_info(
     guiding_sheet: &str,
 ) {
     log_separator();
-    log::info!("Current Version         :-> \x1b[32m{}\x1b[0m", version);
-    log::info!("Config/TOML File        :-> \x1b[32m{}\x1b[0m", config_file);
-    log::info!("YAML Queries File       :-> \x1b[32m{}\x1b[0m", yaml_file);
-    log::info!("LOG File                :-> \x1b[32m{}\x1b[0m", log_file);
-    log::info!("Excel Sheet Input file  :-> \x1b[32m{}\x1b[0m", input_file);
-    log::info!("Output SQLite3 Database :-> \x1b[32m{}\x1b[0m", database_file);
-    log::info!("Guiding Excel Sheet     :-> \x1b[32m{}\x1b[0m", guiding_sheet);
+    tracing::info!("Current Version         :-> \x1b[32m{}\x1b[0m", version);
+    tracing::info!("Config/TOML File        :-> \x1b[32m{}\x1b[0m", config_file);
+    tracing::info!("YAML Queries File       :-> \x1b[32m{}\x1b[0m", yaml_file);
+    tracing::info!("LOG File                :-> \x1b[32m{}\x1b[0m", log_file);
+    tracing::info!("Excel Sheet Input file  :-> \x1b[32m{}\x1b[0m", input_file);
+    tracing::info!("Output SQLite3 Database :-> \x1b[32m{}\x1b[0m", database_file);
+    tracing::info!("Guiding Excel Sheet     :-> \x1b[32m{}\x1b[0m", guiding_sheet);
     log_separator();
-    log::info!("Personal Data Warehouse Processes are Starting | ET&L -> Extract, Transform & Loader !");
+    tracing::info!("Personal Data Warehouse Processes are Starting | ET&L -> Extract, Transform & Loader !");
 }
 
 /// Log completion with timing information
@@ -110,8 +332,8 @@ pub fn log_completion(start_time: std::time::Instant, version: &str, hostname: &
     let total_seconds = duration.as_secs_f64();
     
     log_separator();
-    log::info!("All Personal Data Warehouse processes have ended!");
-    log::info!(
+    tracing::info!("All Personal Data Warehouse processes have ended!");
+    tracing::info!(
         "Processing completed in {:.2} seconds | Version {} | Hostname {} | OS {}",
         total_seconds,
         version,
@@ -121,7 +343,7 @@ pub fn log_completion(start_time: std::time::Instant, version: &str, hostname: &
     log_separator();
 }
 
-/// Create a file logger for persistent logging (equivalent to Python log file)
+/// Copy the log records to a file from now on (equivalent to Python log file)
 pub fn create_file_logger(log_file_path: &std::path::Path) -> Result<(), PdwError> {
     use std::fs::OpenOptions;
     
@@ -133,7 +355,7 @@ pub fn create_file_logger(log_file_path: &std::path::Path) -> Result<(), PdwErro
     }
     
     // Create or append to log file
-    let _log_file = OpenOptions::new()
+    let log_file = OpenOptions::new()
         .create(true)
         .append(true)
         .open(log_file_path)
@@ -141,6 +363,10 @@ pub fn create_file_logger(log_file_path: &std::path::Path) -> Result<(), PdwErro
             PdwError::Logging(format!("Failed to open log file: {}", e))
         })?;
     
+    if let Ok(mut current) = LOG_FILE.lock() {
+        *current = Some(log_file);
+    }
+    
     Ok(())
 }
 
@@ -223,4 +449,43 @@ mod tests {
         assert!(content.contains("Started"));
         assert!(content.contains("Ended"));
     }
+    
+    #[test]
+    fn test_json_record_format() {
+        let mut fields = Map::new();
+        fields.insert("phase".to_string(), json!("Creating pivot Tables"));
+        fields.insert("step".to_string(), json!(3));
+        fields.insert("count".to_string(), json!(42));
+        let line = format_json(&LogLine {
+            level: Level::INFO,
+            target: "pdw_rust::etl".to_string(),
+            message: format!("   . .. ... Lines Created :-> \x1b[32m{:>6}\x1b[0m", 42),
+            fields,
+        });
+        
+        let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(record["level"], "INFO");
+        assert_eq!(record["message"], ". .. ... Lines Created :->     42");
+        assert_eq!(record["phase"], "Creating pivot Tables");
+        assert_eq!(record["step"], 3);
+        assert_eq!(record["count"], 42);
+        assert!(record.get("seconds").is_none());
+        assert!(chrono::DateTime::parse_from_rfc3339(record["timestamp"].as_str().unwrap()).is_ok());
+    }
+    
+    #[test]
+    fn test_plain_text_for_log_file() {
+        assert_eq!(strip_ansi("Lines :-> \x1b[32m    12\x1b[0m"), "Lines :->     12");
+        
+        let line = format_text(
+            &LogLine {
+                level: Level::WARN,
+                target: "pdw".to_string(),
+                message: "Version :-> \x1b[32m9.11.0\x1b[0m".to_string(),
+                fields: Map::new(),
+            },
+            false,
+        );
+        assert!(line.ends_with(" [WARN] pdw: Version :-> 9.11.0"));
+    }
 }
\ No newline at end of file
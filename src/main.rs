@@ -18,60 +18,452 @@ with comprehensive reporting capabilities.
 */
 
 use anyhow::Result;
-use clap::Parser;
-use log::{info, error};
-use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use tracing::{info, error, warn};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
-mod config;
-mod database;
-mod error;
-mod etl;
-mod excel;
-mod logging;
-mod reporting;
-
-use crate::config::PdwConfig;
-use crate::etl::EtlPipeline;
-use crate::error::PdwError;
+use pdw_rust::database::PeriodSet;
+use pdw_rust::database::DatabaseBackend;
+use pdw_rust::reporting::ReportEngine;
+use pdw_rust::fx::FxRates;
+use pdw_rust::watch::{self, WatchSet};
+use pdw_rust::window::DateWindow;
+use pdw_rust::logging::{self, LogFormat};
+use pdw_rust::shell::{self, OutputFormat, ShellStep, SqlShell};
+use pdw_rust::checkpoint::{Checkpoint, Phase};
+use pdw_rust::{compat, consolidate, daemon, deterministic, export_schema, migrations, parity, publish, purge, recovery, runs, sample, self_update, server, telemetry, trash};
+use pdw_rust::preflight::PreflightReport;
+use pdw_rust::analytics::Measure;
+use pdw_rust::digest::{self, DigestFormat, DigestPeriod};
+use pdw_rust::notifications::Notifier;
+use pdw_rust::{DatabaseManager, EtlPipeline, PdwConfig, PdwError, ReportGenerator, WorkbookWriter};
 
 /// Personal Data Warehouse - ETL system for Excel to SQLite processing
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Configuration file path (TOML format)
-    #[arg(short, long, value_name = "FILE")]
+    #[arg(short, long, value_name = "FILE", global = true)]
     config: Option<PathBuf>,
     
     /// Enable verbose logging
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     verbose: bool,
     
-    /// Dry run - validate configuration without processing
-    #[arg(short, long)]
+    /// Log record format (json emits one object per record)
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+    
+    /// Override a configuration value (e.g. settings.create_pivot=false); repeatable,
+    /// applied after PDW__SECTION__KEY environment variables
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    overrides: Vec<String>,
+    
+    /// Configuration profile merged over the base tables ([profile.NAME] in the file);
+    /// defaults to the PDW_PROFILE environment variable
+    #[arg(long, value_name = "NAME", global = true)]
+    profile: Option<String>,
+    
+    /// Dry run - validate the configuration, the input's GUIDING and accounting
+    /// sheets and the YAML queries without processing
+    #[arg(short, long, global = true)]
     dry_run: bool,
     
-    /// Skip data loading phase
+    /// Continue a failed run from the first phase it did not complete
+    /// (only while the input files are unchanged)
     #[arg(long)]
-    skip_loader: bool,
+    resume: bool,
     
-    /// Skip report generation phase
-    #[arg(long)]
-    skip_reports: bool,
+    /// Only load the entries of these months (e.g. 2024-01..2024-03); the other
+    /// periods and the reference tables in the database are left untouched
+    #[arg(long, value_name = "FROM..TO", value_parser = DateWindow::parse, global = true)]
+    window: Option<DateWindow>,
+    
+    /// Byte-identical outputs for identical inputs: fixed timestamps (SOURCE_DATE_EPOCH
+    /// or 2000-01-01) and stable row ordering in every export
+    #[arg(long, global = true)]
+    deterministic: bool,
+    
+    /// Phase to run (runs the full pipeline per configuration when omitted)
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Pipeline phases and standalone commands
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Load the workbook sheets into the database
+    Load,
+    
+    /// Build the monthly and annual pivot tables
+    Pivot {
+        /// Only recompute these AnoMes periods (e.g. 2024/01); repeatable
+        #[arg(long = "period", value_name = "ANOMES")]
+        periods: Vec<String>,
+    },
+    
+    /// Generate summary tables and report files
+    Report {
+        /// Previous report workbook (.xlsx) or database: changed summary and pivot
+        /// cells are listed in a "Mudanças" sheet
+        #[arg(long, value_name = "FILE")]
+        diff_against: Option<PathBuf>,
+    },
+    
+    /// Run an SQL query against the database, or open an interactive SQL shell
+    Query {
+        /// SQL statement to execute (omit for the interactive shell)
+        sql: Option<String>,
+        
+        /// Result layout
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    
+    /// List the tables and views of the database, or the columns of one
+    Schema {
+        /// Table or view whose columns are listed
+        table: Option<String>,
+        
+        /// Result layout
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    
+    /// Create a sample configuration and the directory structure
+    Init {
+        /// Overwrite an existing configuration file
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Write a synthetic input workbook and matching report queries, to try the
+    /// pipeline or share a bug report without real data
+    GenSample {
+        /// Directory written to (defaults to dir_in)
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+        
+        /// Months of entries, up to the current one
+        #[arg(long, default_value_t = 12)]
+        months: u32,
+        
+        /// Seed of the generated entries; the same seed gives the same sample
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+        
+        /// Overwrite an existing workbook or queries file
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Rewrite the configuration file with the current key names (INI files become TOML);
+    /// with --dry-run the changes are only listed
+    ConfigUpgrade,
+    
+    /// Rewrite a JSON or XML export (optionally .gz) in another export schema version
+    ConvertExport {
+        /// Export file to convert
+        input: PathBuf,
+        
+        /// Target export schema version (defaults to the current one)
+        #[arg(long, value_name = "VERSION", default_value_t = export_schema::EXPORT_SCHEMA_VERSION)]
+        to: u32,
+        
+        /// Converted file (defaults to <input name>.schema<VERSION>.<ext> next to the input)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    
+    /// Export warehouse contents back into input formats
+    Export {
+        /// Regenerate a cleaned master workbook (one sheet per origin)
+        #[arg(long)]
+        workbook: bool,
+        
+        /// Export the consolidated ledger as OFX statements (one file per origin)
+        #[arg(long)]
+        ofx: bool,
+        
+        /// Output path: workbook file, or directory for OFX (defaults under dir_out)
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    
+    /// Upload the exports to the [publish] S3/WebDAV target, resuming interrupted uploads
+    Publish {
+        /// Files to upload instead of the exports of dir_out
+        #[arg(value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+    
+    /// Salvage a damaged database into a fresh file
+    Repair {
+        /// Database to repair (defaults to the configured database)
+        #[arg(long, value_name = "FILE")]
+        database: Option<PathBuf>,
+        
+        /// Recovered database path (defaults to <name>.recovered.<ext>)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        
+        /// Replace the damaged file with the recovered one (original kept as .corrupt)
+        #[arg(long)]
+        replace: bool,
+    },
+    
+    /// Download exchange rates into the offline cache
+    Rates {
+        /// Currency codes to fetch (e.g. USD); repeatable
+        #[arg(long = "currency", value_name = "CODE", required = true)]
+        currencies: Vec<String>,
+        
+        /// First day of the range (YYYY-MM-DD)
+        #[arg(long)]
+        from: chrono::NaiveDate,
+        
+        /// Last day of the range (defaults to today)
+        #[arg(long)]
+        to: Option<chrono::NaiveDate>,
+    },
+    
+    /// Load the workbook in memory and diff every shared table against a Python PDW database
+    Parity {
+        /// Database produced by the Python PDW from the same workbook
+        #[arg(long, value_name = "FILE")]
+        python_db: PathBuf,
+        
+        /// Decimal places numbers are compared at
+        #[arg(long, default_value_t = 2)]
+        decimals: usize,
+        
+        /// Differing rows listed per table and kind
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        max_differences: usize,
+    },
+    
+    /// List the latest pipeline runs recorded in PDW_RUNS
+    History {
+        /// Number of runs to show
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+        
+        /// Result layout
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    
+    /// Summarize the last week (or month to date) from the existing database and send it
+    /// through the notification channels, without loading anything
+    Digest {
+        /// Span summarized, ending on --until
+        #[arg(long, value_enum, default_value_t = DigestPeriod::Week)]
+        period: DigestPeriod,
+        
+        /// Last day of the period (defaults to today)
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        until: Option<chrono::NaiveDate>,
+        
+        /// Message body layout
+        #[arg(long, value_enum, default_value_t = DigestFormat::Text)]
+        format: DigestFormat,
+    },
+    
+    /// Merge several databases (one per family member) into a combined one and rebuild its pivots and summaries
+    Consolidate {
+        /// Databases to merge, in order
+        #[arg(required = true, value_name = "DB")]
+        databases: Vec<PathBuf>,
+        
+        /// Combined database to create
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+        
+        /// Overwrite an existing combined database
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Delete old or sensitive entries, rebuild the pivots and summaries and record the purge
+    /// (preview only with --dry-run)
+    Purge {
+        /// Entries dated before this day
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        before: Option<chrono::NaiveDate>,
+        
+        /// Entries of these TIPOs (comma-separated)
+        #[arg(long, value_name = "TIPO", value_delimiter = ',')]
+        types: Vec<String>,
+        
+        /// Move the entries to the trash instead, so `pdw trash restore` can bring them back
+        #[arg(long)]
+        to_trash: bool,
+    },
+    
+    /// List the entries purges moved to the trash, restore them or delete them for good
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    
+    /// List or apply the schema migrations of the database
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    
+    /// Re-run the pipeline whenever the input workbook or YAML queries change
+    Watch {
+        /// Quiet period after the last change before running (milliseconds)
+        #[arg(long, value_name = "MS", default_value_t = 1000)]
+        debounce: u64,
+    },
+    
+    /// Serve read-only JSON endpoints over the warehouse, plus POST /run to re-run the pipeline
+    Serve {
+        /// Address and port to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8765")]
+        bind: String,
+        
+        /// Bearer token POST /run must carry (default: PDW_SERVE_TOKEN, else a random one printed at startup)
+        #[arg(long)]
+        token: Option<String>,
+    },
+    
+    /// Keep the configuration and database open and take commands on a local socket
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
+    
+    /// Install the latest GitHub release over this binary, after verifying its checksum
+    /// and signature (checksum only with --insecure, for builds without a release key)
+    SelfUpdate {
+        /// Only report whether a newer release exists
+        #[arg(long)]
+        check_only: bool,
+        
+        /// GitHub repository the releases come from (owner/name); others than the default
+        /// need a build with a release key
+        #[arg(long, value_name = "OWNER/NAME", default_value = self_update::DEFAULT_REPOSITORY)]
+        repository: String,
+        
+        /// Install from a build without a release key, trusting the release's checksum only
+        #[arg(long)]
+        insecure: bool,
+    },
+}
+
+/// `pdw migrate` actions
+#[derive(Subcommand, Debug, PartialEq)]
+enum MigrateAction {
+    /// List the migrations, applied or pending
+    Status,
+    /// Apply the pending migrations
+    Up,
+}
+
+/// `pdw trash` actions
+#[derive(Subcommand, Debug, PartialEq)]
+enum TrashAction {
+    /// Show the trashed entries, of every purge or the given ones
+    List {
+        /// Purge Ids (PDW_PURGES)
+        purges: Vec<i64>,
+        
+        /// Result layout
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Move the entries of the given purges back and stop applying those purges
+    Restore {
+        /// Purge Ids (PDW_PURGES)
+        #[arg(required_unless_present = "all")]
+        purges: Vec<i64>,
+        
+        /// Restore every trashed entry
+        #[arg(long, conflicts_with = "purges")]
+        all: bool,
+    },
+    /// Delete the trashed entries of the given purges for good
+    Empty {
+        /// Purge Ids (PDW_PURGES)
+        #[arg(required_unless_present = "all")]
+        purges: Vec<i64>,
+        
+        /// Delete every trashed entry
+        #[arg(long, conflicts_with = "purges")]
+        all: bool,
+    },
+}
+
+/// `pdw daemon` actions; all but start talk to the running daemon
+#[derive(Subcommand, Debug, PartialEq)]
+enum DaemonAction {
+    /// Start the daemon in the foreground
+    Start,
+    /// Show the daemon's process, database and last run
+    Status,
+    /// Run the configured phases, or one of them
+    Run {
+        /// Phase to run (every configured phase when omitted)
+        #[arg(value_enum)]
+        phase: Option<Phase>,
+        
+        /// Load even though the input files are unchanged since the daemon's last load
+        #[arg(long)]
+        force: bool,
+    },
+    /// Run a read-only SQL query
+    Query {
+        sql: String,
+        
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Stop the daemon
+    Stop,
 }
 
+/// Default YAML report queries written by `pdw init`
+const SAMPLE_QUERIES: &str = include_str!("../PDW_QUERIES.yaml");
+
 fn main() -> Result<()> {
     let args = Args::parse();
+    if args.deterministic {
+        deterministic::enable();
+    }
     
     // Initialize logging
-    logging::init_logger(args.verbose)?;
+    logging::init_logger_with(args.verbose, args.log_format)?;
     
     let start_time = Instant::now();
     info!("Personal Data Warehouse (Rust) v{} starting", env!("CARGO_PKG_VERSION"));
     
-    // Load configuration
     let config_path = args.config.unwrap_or_else(|| PathBuf::from("pdw_config.toml"));
-    let config = match PdwConfig::load(&config_path) {
+    
+    // Scaffolding and updates run before a configuration exists
+    if let Some(Command::Init { force }) = args.command {
+        return init_project(&config_path, force);
+    }
+    if let Some(Command::GenSample { output, months, seed, force }) = &args.command {
+        let options = sample::SampleOptions { months: *months, seed: *seed, end: deterministic::now().date_naive() };
+        return generate_sample(&config_path, args.profile.as_deref(), &args.overrides, output.as_deref(), options, *force);
+    }
+    if let Some(Command::ConfigUpgrade) = args.command {
+        return upgrade_config(&config_path, args.dry_run);
+    }
+    if let Some(Command::ConvertExport { input, to, output }) = &args.command {
+        return convert_export(input, *to, output.as_deref());
+    }
+    if let Some(Command::SelfUpdate { check_only, repository, insecure }) = &args.command {
+        return update_binary(repository, *check_only, *insecure);
+    }
+    
+    // Load configuration
+    let config = match PdwConfig::load_with_profile(&config_path, args.profile.as_deref(), &args.overrides) {
         Ok(cfg) => cfg,
         Err(e) => {
             error!("Failed to load configuration: {}", e);
@@ -79,53 +471,718 @@ fn main() -> Result<()> {
         }
     };
     
-    info!("Configuration loaded from: {}", config_path.display());
+    // Copy the records to the configured log file from here on
+    logging::create_file_logger(&config.get_log_file_path())?;
+    match pdw_rust::config::selected_profile(args.profile.as_deref()) {
+        Some(profile) => info!("Configuration loaded from: {} (profile {})", config_path.display(), profile),
+        None => info!("Configuration loaded from: {}", config_path.display()),
+    }
+    if !config.warnings.is_empty() {
+        warn!("Configuration warnings ({}):", config.warnings.len());
+        for warning in &config.warnings {
+            warn!("   . .. ... {}", warning);
+        }
+    }
     
     // Validate configuration
     if let Err(e) = config.validate() {
         error!("Configuration validation failed: {}", e);
         return Err(e.into());
     }
+    telemetry::record_spans(config.telemetry.enabled);
     
+    if let (true, Some(Command::Purge { before, types, .. })) = (args.dry_run, &args.command) {
+        return preview_purge(&config, *before, types);
+    }
     if args.dry_run {
-        info!("Dry run completed successfully - configuration is valid");
+        let report = PreflightReport::check(&config);
+        report.log();
+        if !report.is_clean() {
+            return Err(anyhow::anyhow!("Dry run found {} problem(s)", report.problems.len()));
+        }
+        info!("Dry run completed successfully - configuration, input and queries are valid");
         return Ok(());
     }
     
-    // Create ETL pipeline
-    let mut pipeline = EtlPipeline::new(config)?;
+    match args.command {
+        None => run_pipeline(config, "run", args.resume, args.window)?,
+        Some(command) => run_command(command, config, args.window)?,
+    }
     
-    // Execute ETL phases based on configuration and arguments
-    let run_loader = pipeline.config().settings.run_data_loader && !args.skip_loader;
-    let run_reports = pipeline.config().settings.run_reports && !args.skip_reports;
+    let duration = start_time.elapsed();
+    info!(
+        "PDW processing completed successfully in {:.2} seconds", 
+        duration.as_secs_f64()
+    );
     
-    if run_loader {
+    Ok(())
+}
+
+/// Run every phase enabled in the configuration, recording the run under `command`;
+/// with `resume`, phases completed by the last (failed) run are skipped
+fn run_pipeline(config: PdwConfig, command: &str, resume: bool, window: Option<DateWindow>) -> Result<()> {
+    if config.settings.report_engine == ReportEngine::DataFusion {
+        return run_datafusion_reports(config);
+    }
+    if config.database.backend == DatabaseBackend::Postgres {
+        return run_postgres_load(config);
+    }
+    
+    let mut pipeline = EtlPipeline::new(config)?;
+    if let Some(window) = window {
+        pipeline.set_window(window);
+    }
+    let result = run_phases(&mut pipeline, resume, true);
+    record_run(&pipeline, command, result)
+}
+
+/// Run the pipeline in the daemon serving this configuration's database, or here when none is running
+fn run_pipeline_or_delegate(config: &PdwConfig, command: &str) -> Result<()> {
+    let socket = config.get_daemon_socket_path();
+    if daemon::is_running(&socket) {
+        info!("Pipeline run handed to the daemon on {}", socket.display());
+        daemon::send(&socket, &daemon::Request::Run { phase: None, force: false })?;
+        return Ok(());
+    }
+    run_pipeline(config.clone(), command, false, None)
+}
+
+/// Loader, pivot and report phases, as enabled, checkpointed after each one;
+/// without `load` the loaded data is kept
+fn run_phases(pipeline: &mut EtlPipeline, resume: bool, load: bool) -> Result<()> {
+    let mut checkpoint = if resume {
+        Checkpoint::resume(pipeline.database(), pipeline.config())?
+    } else {
+        Checkpoint::start(pipeline.config())
+    };
+    let skip = |checkpoint: &Checkpoint, phase: Phase| {
+        let done = checkpoint.is_done(phase);
+        if done {
+            info!("Skipping the {} phase - completed by the run of {}", phase, checkpoint.started_at);
+        }
+        done
+    };
+    
+    if load && pipeline.config().settings.run_data_loader && !skip(&checkpoint, Phase::Load) {
         info!("Starting data loading phase...");
         pipeline.execute_data_loading()?;
+        checkpoint.complete(pipeline.database(), Phase::Load)?;
         info!("Data loading completed successfully");
     }
     
-    if pipeline.config().settings.create_pivot {
+    if pipeline.config().settings.create_pivot && !skip(&checkpoint, Phase::Pivot) {
         info!("Creating pivot tables...");
         pipeline.create_pivot_tables()?;
+        checkpoint.complete(pipeline.database(), Phase::Pivot)?;
         info!("Pivot tables created successfully");
     }
     
-    if run_reports {
+    if pipeline.config().settings.run_reports && !skip(&checkpoint, Phase::Report) {
         info!("Starting report generation...");
         pipeline.generate_reports()?;
         info!("Report generation completed successfully");
     }
     
-    let duration = start_time.elapsed();
+    Checkpoint::clear(pipeline.database())?;
+    Ok(())
+}
+
+/// Record a run in PDW_RUNS whatever its outcome; failing to record it only warns.
+/// A failure caused by another program's lock on the database is reported as such
+fn record_run(pipeline: &EtlPipeline, command: &str, result: Result<()>) -> Result<()> {
+    let result = result.map_err(|e| match e.downcast::<PdwError>() {
+        Ok(e) => pipeline.database().explain_lock(e).into(),
+        Err(e) => e,
+    });
+    let error = result.as_ref().err().map(|e| format!("{:#}", e));
+    if let Err(e) = pipeline.record_run(command, error.as_deref()) {
+        warn!("Run not recorded in {}: {}", runs::RUNS_TABLE, e);
+    }
+    pipeline.push_run_metrics(command, error.is_some());
+    
+    // Rows read per sheet, for runs that loaded any
+    if result.is_ok() && !pipeline.run_record().sheets.is_empty() {
+        let (columns, rows) = pipeline.run_record().summary();
+        println!("{}", shell::render(&columns, &rows, OutputFormat::Table)?);
+    }
+    
+    // A database built in memory ([database.sqlite] in_memory) is written out once, at the end
+    match pipeline.database().flush_to_disk() {
+        Ok(true) => info!("Database written to {}", pipeline.database().path().display()),
+        Ok(false) => {}
+        Err(e) if result.is_ok() => return Err(e.into()),
+        Err(e) => error!("Database not written to {}: {}", pipeline.database().path().display(), e),
+    }
+    result
+}
+
+/// Transform the workbook and report through DataFusion without a database file
+#[cfg(feature = "datafusion")]
+fn run_datafusion_reports(config: PdwConfig) -> Result<()> {
+    use pdw_rust::datafusion_engine::DataFusionReporter;
+    use pdw_rust::EtlOperations;
+    
+    info!("Extracting workbook for DataFusion reporting...");
+    let mut pipeline = EtlPipeline::in_memory(config.clone())?;
+    let transactions = pipeline.extract_data()?;
+    let processed = pipeline.transform_data(transactions)?;
+    
+    let reporter = DataFusionReporter::new(config.clone())?;
+    reporter.register_transactions(&processed)?;
+    if let Some(parquet_dir) = &config.settings.parquet_dir {
+        let tables = reporter.register_parquet_dir(parquet_dir)?;
+        info!("{} Parquet tables registered from {}", tables, parquet_dir.display());
+    }
+    
+    let sheets = reporter.generate_excel_reports()?;
+    info!("DataFusion report generation completed ({} sheets)", sheets);
+    Ok(())
+}
+
+#[cfg(not(feature = "datafusion"))]
+fn run_datafusion_reports(_config: PdwConfig) -> Result<()> {
+    anyhow::bail!("report_engine = \"datafusion\" requires a build with --features datafusion")
+}
+
+/// Load the workbook into the PostgreSQL warehouse configured in [database]
+#[cfg(feature = "postgres")]
+fn run_postgres_load(config: PdwConfig) -> Result<()> {
+    use pdw_rust::postgres_backend::PostgresManager;
+    
+    let connection_string = config.database.connection_string.clone()
+        .ok_or_else(|| anyhow::anyhow!("database.connection_string is required for backend = \"postgres\""))?;
+    let target = PostgresManager::connect(&connection_string)?;
+    
+    if config.settings.run_data_loader {
+        info!("Starting data loading phase (PostgreSQL)...");
+        let count = EtlPipeline::in_memory(config.clone())?.load_into(&target)?;
+        info!("Data loading completed successfully ({} transactions)", count);
+    }
+    if config.settings.run_reports {
+        info!("Reports are generated from SQLite only - skipped for the PostgreSQL backend");
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "postgres"))]
+fn run_postgres_load(_config: PdwConfig) -> Result<()> {
+    anyhow::bail!("backend = \"postgres\" requires a build with --features postgres")
+}
+
+/// Write a sample configuration and create the directory structure
+fn init_project(config_path: &Path, force: bool) -> Result<()> {
+    if config_path.exists() && !force {
+        anyhow::bail!("{} already exists - use --force to overwrite", config_path.display());
+    }
+    
+    PdwConfig::create_sample_config(config_path)?;
+    info!("Sample configuration written to {}", config_path.display());
+    
+    let config = PdwConfig::load(config_path)?;
+    config.create_directories()?;
+    
+    let queries_path = config.get_yaml_queries_path();
+    if !queries_path.exists() {
+        std::fs::write(&queries_path, SAMPLE_QUERIES)?;
+        info!("Sample report queries written to {}", queries_path.display());
+    }
+    
     info!(
-        "PDW processing completed successfully in {:.2} seconds", 
-        duration.as_secs_f64()
+        "Project initialized - place {} in {}",
+        config.get_input_file_path().file_name().unwrap_or_default().to_string_lossy(),
+        config.directories.dir_in.display()
     );
     
     Ok(())
 }
 
+/// Write the sample workbook and queries where the configuration (or its defaults) reads them
+fn generate_sample(config_path: &Path, profile: Option<&str>, overrides: &[String], output: Option<&Path>,
+                   options: sample::SampleOptions, force: bool) -> Result<()> {
+    let config = if config_path.exists() {
+        PdwConfig::load_with_profile(config_path, profile, overrides)?
+    } else {
+        PdwConfig::default()
+    };
+    let dir = output.map(Path::to_path_buf).unwrap_or_else(|| config.directories.dir_in.clone());
+    let workbook_path = dir.join(format!("{}.xlsx", config.file_types.input_file));
+    let queries_path = dir.join(&config.settings.yaml_sql_file);
+    
+    // The queries written by pdw init are replaced without --force
+    let untouched = |path: &Path| std::fs::read_to_string(path).is_ok_and(|content| content == SAMPLE_QUERIES);
+    for path in [&workbook_path, &queries_path] {
+        if path.exists() && !force && !(path == &queries_path && untouched(path)) {
+            anyhow::bail!("{} already exists - use --force to overwrite", path.display());
+        }
+    }
+    
+    let entries = sample::write_sample_workbook(&workbook_path, &config.settings, &options)?;
+    std::fs::write(&queries_path, sample::SAMPLE_QUERIES)?;
+    info!("Sample workbook written to {} ({} entries, {} months, seed {})", workbook_path.display(), entries, options.months, options.seed);
+    info!("Sample report queries written to {}", queries_path.display());
+    if !config.file_types.type_in.eq_ignore_ascii_case("xlsx") {
+        warn!("type_in is \"{}\" - set it to \"xlsx\" to load the sample", config.file_types.type_in);
+    }
+    
+    Ok(())
+}
+
+/// Rename outdated keys of the configuration file, keeping a backup
+fn upgrade_config(config_path: &Path, dry_run: bool) -> Result<()> {
+    let changes = PdwConfig::upgrade_file(config_path, !dry_run)?;
+    if changes.is_empty() {
+        info!("{} is up to date", config_path.display());
+        return Ok(());
+    }
+    
+    for change in &changes {
+        info!("   . .. ... {}", change);
+    }
+    if dry_run {
+        info!("{} change(s) to {} - run without --dry-run to apply", changes.len(), config_path.display());
+    } else {
+        info!("{} upgraded, original kept as {}.bak", config_path.display(), config_path.display());
+    }
+    Ok(())
+}
+
+/// Convert an export file between export schema versions
+fn convert_export(input: &Path, version: u32, output: Option<&Path>) -> Result<()> {
+    let output = output.map(Path::to_path_buf).unwrap_or_else(|| {
+        let name = input.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let (stem, extension) = ["json.gz", "xml.gz", "json", "xml"].iter()
+            .find_map(|ext| name.strip_suffix(&format!(".{}", ext)).map(|stem| (stem.to_string(), *ext)))
+            .unwrap_or((name.clone(), "json"));
+        input.with_file_name(format!("{}.schema{}.{}", stem, version, extension))
+    });
+    
+    let from = export_schema::convert_file(input, &output, version)?;
+    info!("Converted {} (schema {}) to {} (schema {})", input.display(), from, output.display(), version);
+    Ok(())
+}
+
+/// Install the latest release over the running binary, or only report it
+fn update_binary(repository: &str, check_only: bool, insecure: bool) -> Result<()> {
+    if !check_only {
+        self_update::check_source(repository, self_update::RELEASE_PUBLIC_KEY, insecure)?;
+    }
+    let release = self_update::latest_release(repository)?;
+    if !release.is_newer() {
+        info!("pdw {} is up to date (latest release: {})", env!("CARGO_PKG_VERSION"), release.tag);
+        return Ok(());
+    }
+    
+    info!("pdw {} is available (running {})", release.tag, env!("CARGO_PKG_VERSION"));
+    if check_only {
+        info!("Run pdw self-update to install it");
+        return Ok(());
+    }
+    let executable = self_update::install(&release, insecure)?;
+    info!("{} updated to {}", executable.display(), release.tag);
+    Ok(())
+}
+
+/// Execute a single phase or standalone subcommand
+fn run_command(command: Command, config: PdwConfig, window: Option<DateWindow>) -> Result<()> {
+    match command {
+        Command::Load => {
+            let mut pipeline = EtlPipeline::new(config)?;
+            if let Some(window) = window {
+                pipeline.set_window(window);
+            }
+            let result = pipeline.execute_data_loading();
+            record_run(&pipeline, "load", result.map_err(Into::into))?;
+            info!("Data loading completed successfully");
+        }
+        Command::Pivot { periods } => {
+            let mut pipeline = EtlPipeline::new(config)?;
+            if !periods.is_empty() {
+                pipeline.set_touched_periods(PeriodSet::from_months(periods));
+            }
+            let result = pipeline.create_pivot_tables();
+            record_run(&pipeline, "pivot", result.map_err(Into::into))?;
+            info!("Pivot tables created successfully");
+        }
+        Command::Report { diff_against } => {
+            let mut pipeline = EtlPipeline::new(config)?;
+            if let Some(baseline) = diff_against {
+                pipeline.set_diff_baseline(baseline);
+            }
+            let result = pipeline.generate_reports();
+            record_run(&pipeline, "report", result.map_err(Into::into))?;
+            info!("Report generation completed successfully");
+        }
+        Command::Query { sql, format } => {
+            let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+            match sql {
+                Some(sql) => {
+                    let output = shell::run_statement(&database, &sql, format)?;
+                    if !output.is_empty() {
+                        println!("{}", output);
+                    }
+                }
+                None => run_shell(&database, format)?,
+            }
+        }
+        Command::Schema { table, format } => {
+            let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+            let (columns, rows) = shell::schema(&database, table.as_deref())?;
+            println!("{}", shell::render(&columns, &rows, format)?);
+        }
+        Command::Init { .. } | Command::GenSample { .. } | Command::ConfigUpgrade | Command::ConvertExport { .. } | Command::SelfUpdate { .. } => {
+            unreachable!("handled before the configuration is loaded")
+        }
+        Command::Export { workbook, ofx, output } => {
+            if !workbook && !ofx {
+                anyhow::bail!("Nothing to export - use --workbook and/or --ofx");
+            }
+            
+            if workbook {
+                let output_path = output.clone().unwrap_or_else(|| config.get_clean_workbook_path());
+                let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+                let writer = WorkbookWriter::new(database, config.clone());
+                let sheets = writer.write_master_workbook(&output_path)?;
+                info!("Exported {} sheets to {}", sheets, output_path.display());
+            }
+            
+            if ofx {
+                let output_dir = output.filter(|_| !workbook)
+                    .unwrap_or_else(|| config.directories.dir_out.join("ofx"));
+                let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+                let generator = ReportGenerator::new(database, config);
+                let files = generator.export_ofx(&output_dir)?;
+                info!("Exported {} OFX statements to {}", files.len(), output_dir.display());
+            }
+        }
+        Command::Publish { files } => {
+            let files = if files.is_empty() {
+                publish::export_files(&config.directories.dir_out, &config.publish)?
+            } else {
+                files
+            };
+            let summary = publish::publish(&config.publish, &files, &config.get_publish_state_path())?;
+            info!(
+                "Published {} files ({} unchanged, {:.1} MiB sent)",
+                summary.uploaded, summary.skipped, summary.bytes_sent as f64 / (1024.0 * 1024.0)
+            );
+        }
+        Command::Repair { database, output, replace } => {
+            let source = database.unwrap_or_else(|| config.get_database_path());
+            let target = output.unwrap_or_else(|| config.get_recovered_database_path(&source));
+            
+            let report = recovery::recover_database(&source, &target)?;
+            for table in &report.tables {
+                info!("   {:<30} recovered {:>8} | lost {:>6}", table.table, table.recovered_rows, table.lost_rows);
+            }
+            for object in &report.skipped_objects {
+                error!("   Could not recreate schema object: {}", object);
+            }
+            info!(
+                "Recovered {} rows ({} lost) into {}",
+                report.recovered_rows(), report.lost_rows(), target.display()
+            );
+            
+            if replace {
+                let backup = source.with_extension(format!("{}.corrupt", config.file_types.db_file_type));
+                std::fs::rename(&source, &backup)?;
+                std::fs::rename(&target, &source)?;
+                info!("Replaced {} (damaged file kept as {})", source.display(), backup.display());
+            }
+        }
+        Command::Rates { currencies, from, to } => {
+            let to = to.unwrap_or_else(|| chrono::Local::now().date_naive());
+            let mut rates = FxRates::open(&config.fx, config.get_fx_cache_path())?;
+            let mut failed = Vec::new();
+            for currency in &currencies {
+                match rates.prefetch(currency, from, to) {
+                    Ok(added) => info!("   {:<6} {:>6} new rates", currency.to_uppercase(), added),
+                    Err(e) => {
+                        error!("   {:<6} {}", currency.to_uppercase(), e);
+                        failed.push(currency.to_uppercase());
+                    }
+                }
+            }
+            // Keep the rates that did arrive before reporting the failures
+            rates.save()?;
+            info!("Rate cache updated: {}", config.get_fx_cache_path().display());
+            if !failed.is_empty() {
+                anyhow::bail!("Rate download failed for {}", failed.join(", "));
+            }
+        }
+        Command::Parity { python_db, decimals, max_differences } => {
+            // Load into memory so the configured warehouse is left untouched
+            let mut pipeline = EtlPipeline::in_memory(config)?;
+            pipeline.execute_data_loading()?;
+            if pipeline.config().settings.create_pivot {
+                pipeline.create_pivot_tables()?;
+            }
+            pipeline.create_summary_tables()?;
+            
+            let _phase = logging::log_phase_start(&format!("Comparing with {}", python_db.display()));
+            let report = parity::compare_databases(pipeline.database(), &python_db, decimals)?;
+            report.log(max_differences);
+            
+            if !report.is_identical() {
+                anyhow::bail!("Parity check failed: {} of {} shared tables differ", report.mismatched_tables(), report.tables.len());
+            }
+            info!("Parity check passed: {} shared tables identical", report.tables.len());
+        }
+        Command::History { limit, format } => {
+            let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+            let (columns, rows) = runs::recent_runs(&database, limit)?;
+            if columns.is_empty() {
+                info!("No runs recorded yet in {}", database.path().display());
+            } else {
+                println!("{}", shell::render(&columns, &rows, format)?);
+            }
+        }
+        Command::Digest { period, until, format } => {
+            let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+            let until = until.unwrap_or_else(|| chrono::Local::now().date_naive());
+            let excluded = config.analytics.exclusions.types(Measure::Digest, &config.category_groups);
+            let mut digest = digest::build_digest(&database, &config.settings.general_entries_table, period, until, &excluded)?;
+            if config.baselines.enabled {
+                digest = digest.with_seasonal(&database, &config.baselines.table)?;
+            }
+            let body = digest.render(format, &config.branding);
+            
+            let notifier = Notifier::from_config(&config.notifications);
+            if notifier.is_empty() {
+                warn!("No notification channel configured - printing the digest");
+                println!("{}", body);
+            } else {
+                let delivered = notifier.notify(&digest.subject(), &body);
+                info!("Digest sent to {} channel(s)", delivered);
+            }
+        }
+        Command::Consolidate { databases, out, force } => {
+            if databases.iter().any(|database| database == &out) {
+                anyhow::bail!("{} is one of the databases to merge - choose another --out", out.display());
+            }
+            if out.exists() {
+                if !force {
+                    anyhow::bail!("{} already exists - use --force to overwrite it", out.display());
+                }
+                std::fs::remove_file(&out)?;
+            }
+            
+            let pipeline = EtlPipeline::new(config.for_database(&out))?;
+            let result = consolidate_databases(&pipeline, &databases);
+            record_run(&pipeline, "consolidate", result)?;
+            info!("Consolidated {} databases into {}", databases.len(), out.display());
+        }
+        Command::Purge { before, types, to_trash } => {
+            let filter = purge::PurgeFilter::new(before, &types)?;
+            let pipeline = EtlPipeline::new(config)?;
+            let result = purge_entries(&pipeline, &filter, to_trash);
+            record_run(&pipeline, "purge", result)?;
+        }
+        Command::Trash { action: TrashAction::List { purges, format } } => {
+            let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+            let (columns, rows) = trash::list(&database, &purges)?;
+            if rows.is_empty() {
+                info!("No trashed entries in {}", database.path().display());
+            } else {
+                println!("{}", shell::render(&columns, &rows, format)?);
+            }
+        }
+        Command::Trash { action: TrashAction::Restore { purges, all: _ } } => {
+            let pipeline = EtlPipeline::new(config)?;
+            let result = restore_entries(&pipeline, &purges);
+            record_run(&pipeline, "trash-restore", result)?;
+        }
+        Command::Trash { action: TrashAction::Empty { purges, all: _ } } => {
+            let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+            let deleted = trash::empty(&database, &purges)?;
+            purge::scrub(&database)?;
+            logging::log_result("Trashed Entries Deleted", deleted);
+        }
+        Command::Migrate { action } => {
+            let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+            compat::prepare(&database, &config, action == MigrateAction::Up)?;
+            if action == MigrateAction::Up {
+                let applied = migrations::migrate(&database, &config)?;
+                info!("{} migration(s) applied to {}", applied.len(), database.path().display());
+            }
+            for migration in migrations::status(&database)? {
+                let state = match migration.applied_at.as_deref() {
+                    None => "pending".to_string(),
+                    Some("") => "applied".to_string(),
+                    Some(at) => format!("applied {}", at),
+                };
+                info!("   {:>3}  {:<27} {}", migration.version, state, migration.description);
+            }
+        }
+        Command::Watch { debounce } => {
+            let watch_set = WatchSet::from_config(&config);
+            
+            // Bring the warehouse up to date before waiting for edits
+            if let Err(e) = run_pipeline_or_delegate(&config, "watch") {
+                error!("Pipeline run failed: {:#}", e);
+            }
+            
+            watch::watch(&watch_set, std::time::Duration::from_millis(debounce), |changed| {
+                for path in changed {
+                    info!("Changed: {}", path.display());
+                }
+                let run_start = Instant::now();
+                match run_pipeline_or_delegate(&config, "watch") {
+                    Ok(()) => info!("Pipeline re-run completed in {:.2} seconds", run_start.elapsed().as_secs_f64()),
+                    Err(e) => error!("Pipeline run failed: {:#}", e),
+                }
+            })?;
+        }
+        Command::Serve { bind, token } => {
+            let token = match token.or_else(|| std::env::var("PDW_SERVE_TOKEN").ok()) {
+                Some(token) => token,
+                None => {
+                    // Straight to the terminal: log records are also written to the log file
+                    let token = server::generate_token()?;
+                    eprintln!("POST /run token: {}", token);
+                    token
+                }
+            };
+            let api = server::WarehouseApi::new(config.clone());
+            server::serve(api, &bind, token, move || {
+                run_pipeline_or_delegate(&config, "serve").map_err(|e| format!("{:#}", e))
+            })?;
+        }
+        Command::Daemon { action } => control_daemon(action, config)?,
+    }
+    
+    Ok(())
+}
+
+/// Start the daemon, or send it a command and print its answer
+fn control_daemon(action: DaemonAction, config: PdwConfig) -> Result<()> {
+    let socket = config.get_daemon_socket_path();
+    let request = match action {
+        DaemonAction::Start => return start_daemon(config),
+        DaemonAction::Status => daemon::Request::Status,
+        DaemonAction::Run { phase, force } => daemon::Request::Run { phase, force },
+        DaemonAction::Query { sql, format } => {
+            let result = daemon::send(&socket, &daemon::Request::Query { sql })?;
+            let document = export_schema::ExportDocument::from_json(&result.to_string())?;
+            println!("{}", shell::render(&document.columns, &document.rows, format)?);
+            return Ok(());
+        }
+        DaemonAction::Stop => daemon::Request::Stop,
+    };
+    
+    let result = daemon::send(&socket, &request)?;
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
+/// Keep a pipeline open and run the phases the daemon's clients ask for
+fn start_daemon(config: PdwConfig) -> Result<()> {
+    if config.settings.report_engine == ReportEngine::DataFusion || config.database.backend == DatabaseBackend::Postgres {
+        anyhow::bail!("pdw daemon keeps a SQLite warehouse open - not available with the DataFusion engine or the PostgreSQL backend");
+    }
+    
+    let socket = config.get_daemon_socket_path();
+    let mut daemon = daemon::Daemon::new(EtlPipeline::new(config)?);
+    daemon::listen(&mut daemon, &socket, |pipeline, plan| {
+        let result = match plan.phase {
+            None => run_phases(pipeline, false, plan.load),
+            Some(Phase::Load) if !plan.load => Ok(()),
+            Some(Phase::Load) => pipeline.execute_data_loading().map_err(Into::into),
+            Some(Phase::Pivot) => pipeline.create_pivot_tables().map_err(Into::into),
+            Some(Phase::Report) => pipeline.generate_reports().map_err(Into::into),
+        };
+        let command = plan.phase.map_or_else(|| "daemon".to_string(), |phase| format!("daemon {}", phase));
+        record_run(pipeline, &command, result).map_err(|e| format!("{:#}", e))
+    })?;
+    Ok(())
+}
+
+/// Merge the member databases into the pipeline's database, then rebuild pivots and summaries
+fn consolidate_databases(pipeline: &EtlPipeline, databases: &[PathBuf]) -> Result<()> {
+    let _phase = logging::log_phase_start("Consolidating databases");
+    let summaries = consolidate::consolidate(pipeline.database(), databases, &pipeline.config().settings)?;
+    for summary in &summaries {
+        info!(
+            "   {:<20} {:>8} entries | {:>6} reference rows ({})",
+            summary.label, summary.entries, summary.reference_rows, summary.path.display()
+        );
+    }
+    
+    if pipeline.config().settings.create_pivot {
+        pipeline.create_pivot_tables()?;
+    }
+    pipeline.create_summary_tables()?;
+    Ok(())
+}
+
+/// Entries a purge would delete, without changing the database
+fn preview_purge(config: &PdwConfig, before: Option<chrono::NaiveDate>, types: &[String]) -> Result<()> {
+    let filter = purge::PurgeFilter::new(before, types)?;
+    let database = DatabaseManager::open_configured(&config.get_database_path(), &config.settings, &config.database.sqlite)?;
+    let summary = purge::preview(&database, &config.settings.general_entries_table, &filter)?;
+    info!("Dry run - purge of entries {} would delete {} entries", filter, summary.rows);
+    if let (Some(first), Some(last)) = (&summary.first_date, &summary.last_date) {
+        info!("   dated {} to {} | credits {:.2} | debits {:.2}", first, last, summary.credit, summary.debit);
+    }
+    Ok(())
+}
+
+/// Delete (or trash) the matching entries, then rebuild pivots and summaries from what is left
+fn purge_entries(pipeline: &EtlPipeline, filter: &purge::PurgeFilter, to_trash: bool) -> Result<()> {
+    let _phase = logging::log_phase_start("Purging entries");
+    let settings = &pipeline.config().settings;
+    let summary = purge::purge(pipeline.database(), &settings.general_entries_table, filter, to_trash)?;
+    logging::log_result("Entries Purged", summary.rows);
+    // Trashed entries can come back, so the copies are kept until the trash is emptied
+    if !to_trash {
+        let copies = purge::purge_copies(pipeline.database(), &purge::copy_tables(settings), filter)?;
+        logging::log_result("Entry Copies Purged", copies);
+    }
+    
+    pipeline.rebuild_derived_tables()?;
+    if !to_trash {
+        purge::scrub(pipeline.database())?;
+    }
+    Ok(())
+}
+
+/// Move trashed entries back (every purge's when `purges` is empty), then rebuild pivots and summaries
+fn restore_entries(pipeline: &EtlPipeline, purges: &[i64]) -> Result<()> {
+    let _phase = logging::log_phase_start("Restoring trashed entries");
+    let restored = trash::restore(pipeline.database(), &pipeline.config().settings.general_entries_table, purges)?;
+    logging::log_result("Entries Restored", restored);
+    
+    pipeline.rebuild_derived_tables()?;
+    Ok(())
+}
+
+/// Interactive SQL shell; errors are printed and the session goes on
+fn run_shell(database: &DatabaseManager, format: OutputFormat) -> Result<()> {
+    use rustyline::error::ReadlineError;
+    
+    let mut editor = rustyline::DefaultEditor::new()?;
+    let mut shell = SqlShell::new(database, format);
+    println!("Connected to {} - .help for commands, .quit to leave", database.path().display());
+    
+    loop {
+        match editor.readline(shell.prompt()) {
+            Ok(line) => {
+                let _ = editor.add_history_entry(line.as_str());
+                match shell.feed(&line) {
+                    Ok(ShellStep::Output(text)) if !text.is_empty() => println!("{}", text),
+                    Ok(ShellStep::Quit) => break,
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+            Err(ReadlineError::Interrupted) => shell.cancel(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,4 +1204,95 @@ mod tests {
         assert_eq!(env!("CARGO_PKG_VERSION"), "9.11.0");
         assert_eq!(env!("CARGO_PKG_NAME"), "pdw-rust");
     }
+    
+    #[test]
+    fn test_subcommand_parsing() {
+        let args = Args::try_parse_from(["pdw", "query", "SELECT 1", "--config", "alt.toml"]).unwrap();
+        assert_eq!(args.config, Some(PathBuf::from("alt.toml")));
+        assert!(matches!(args.command, Some(Command::Query { sql: Some(sql), format: OutputFormat::Table }) if sql == "SELECT 1"));
+        
+        let args = Args::try_parse_from(["pdw", "query", "--format", "json"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Query { sql: None, format: OutputFormat::Json })));
+        
+        let args = Args::try_parse_from(["pdw", "-v", "load", "--set", "settings.create_pivot=false", "--profile", "test"]).unwrap();
+        assert_eq!(args.overrides, vec!["settings.create_pivot=false"]);
+        assert_eq!(args.profile.as_deref(), Some("test"));
+        assert!(args.verbose);
+        assert_eq!(args.log_format, LogFormat::Text);
+        assert!(matches!(args.command, Some(Command::Load)));
+        
+        let args = Args::try_parse_from(["pdw", "report", "--log-format", "json"]).unwrap();
+        assert_eq!(args.log_format, LogFormat::Json);
+        assert!(matches!(args.command, Some(Command::Report { diff_against: None })));
+        
+        let args = Args::try_parse_from(["pdw", "report", "--diff-against", "previous.xlsx"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Report { diff_against: Some(path) }) if path == Path::new("previous.xlsx")));
+        
+        let args = Args::try_parse_from(["pdw", "parity", "--python-db", "py.db"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Parity { decimals: 2, .. })));
+        
+        let args = Args::try_parse_from(["pdw", "history", "--limit", "5"]).unwrap();
+        assert!(matches!(args.command, Some(Command::History { limit: 5, format: OutputFormat::Table })));
+        let args = Args::try_parse_from(["pdw", "schema", "LANCAMENTOS_GERAIS", "--format", "csv"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Schema { table: Some(table), format: OutputFormat::Csv }) if table == "LANCAMENTOS_GERAIS"));
+        
+        let args = Args::try_parse_from(["pdw", "gen-sample", "--months", "3", "--seed", "42"]).unwrap();
+        assert!(matches!(args.command, Some(Command::GenSample { output: None, months: 3, seed: 42, force: false })));
+        
+        let args = Args::try_parse_from(["pdw", "config-upgrade", "--dry-run"]).unwrap();
+        assert!(args.dry_run);
+        assert!(matches!(args.command, Some(Command::ConfigUpgrade)));
+        
+        let args = Args::try_parse_from(["pdw", "watch", "--debounce", "250"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Watch { debounce: 250 })));
+        let args = Args::try_parse_from(["pdw", "daemon", "run", "report", "--force"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Daemon { action: DaemonAction::Run { phase: Some(Phase::Report), force: true } })));
+        let args = Args::try_parse_from(["pdw", "serve"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Serve { bind, token: None }) if bind == "127.0.0.1:8765"));
+        let args = Args::try_parse_from(["pdw", "publish", "out/a.parquet"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Publish { files }) if files == vec![PathBuf::from("out/a.parquet")]));
+        let args = Args::try_parse_from(["pdw", "self-update", "--check-only"]).unwrap();
+        assert!(matches!(args.command, Some(Command::SelfUpdate { check_only: true, repository, insecure: false }) if repository == "lcarlin/PDW_RST"));
+        
+        let args = Args::try_parse_from(["pdw"]).unwrap();
+        assert!(args.command.is_none());
+        assert!(!args.resume);
+        
+        let args = Args::try_parse_from(["pdw", "--resume", "-c", "alt.toml"]).unwrap();
+        assert!(args.resume && args.command.is_none());
+        
+        let args = Args::try_parse_from(["pdw", "load", "--window", "2024-01..2024-03"]).unwrap();
+        assert_eq!(args.window.map(|window| window.to_string()), Some("2024-01..2024-03".to_string()));
+        assert!(Args::try_parse_from(["pdw", "--window", "2024-03..2024-01"]).is_err());
+        assert!(Args::try_parse_from(["pdw", "report", "--deterministic"]).unwrap().deterministic);
+        
+        let args = Args::try_parse_from(["pdw", "digest", "--period", "month", "--format", "html"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Digest { period: DigestPeriod::Month, until: None, format: DigestFormat::Html })));
+        
+        assert!(Args::try_parse_from(["pdw", "trash", "restore"]).is_err());
+        let args = Args::try_parse_from(["pdw", "trash", "restore", "3", "5"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Trash { action: TrashAction::Restore { purges, all: false } }) if purges == [3, 5]));
+        let args = Args::try_parse_from(["pdw", "trash", "empty", "--all"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Trash { action: TrashAction::Empty { all: true, .. } })));
+        let args = Args::try_parse_from(["pdw", "purge", "--before", "2005-01-01", "--types", "Saude,Lazer", "--dry-run"]).unwrap();
+        assert!(args.dry_run);
+        assert!(matches!(args.command, Some(Command::Purge { before: Some(_), types, to_trash: false }) if types == ["Saude", "Lazer"]));
+        
+        let args = Args::try_parse_from(["pdw", "convert-export", "entries.json.gz", "--to", "2"]).unwrap();
+        assert!(matches!(args.command, Some(Command::ConvertExport { to: 2, output: None, .. })));
+        
+        let args = Args::try_parse_from(["pdw", "migrate", "up"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Migrate { action: MigrateAction::Up })));
+        assert!(Args::try_parse_from(["pdw", "migrate"]).is_err());
+    }
+    
+    #[test]
+    fn test_init_keeps_existing_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("pdw_config.toml");
+        fs::write(&config_path, "# my settings").unwrap();
+        
+        assert!(init_project(&config_path, false).is_err());
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "# my settings");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,141 @@
+/*!
+# Migrations Module
+
+Versioned changes to the warehouse schema, so a database created by an older
+release is upgraded in place instead of deleted. `PRAGMA user_version` holds the
+last migration applied; the pending ones run in order, each in its own
+transaction, when the pipeline opens the database (`settings.auto_migrate`) or
+with `pdw migrate up`. When each migration was applied is kept in the
+`migrations` namespace of the metadata store (PDW_META).
+
+A schema change is a new entry at the end of `MIGRATIONS` with the next version;
+the compatibility stamp's `SCHEMA_VERSION` follows the last one. Migrations must
+be idempotent: Python-produced databases start at version 0 with some of the
+tables already in place.
+*/
+
+use crate::compat;
+use crate::config::PdwConfig;
+use crate::database::{DatabaseManager, MetaStore};
+use crate::error::{DatabaseError, PdwError};
+use crate::money;
+
+/// One schema change
+pub struct Migration {
+    /// `PRAGMA user_version` once applied
+    pub version: i64,
+    pub description: &'static str,
+    apply: fn(&DatabaseManager, &PdwConfig) -> Result<(), PdwError>,
+}
+
+/// Every migration, oldest first
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Core tables, RowHash and cent columns on the general entries",
+        apply: core_tables,
+    },
+];
+
+/// Schema version of a fully migrated database
+pub const LATEST_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// A migration and whether the database has it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: &'static str,
+    /// When it was applied; Some("") for databases migrated before the history was kept
+    pub applied_at: Option<String>,
+}
+
+/// Every migration with its state in the database
+pub fn status(database: &DatabaseManager) -> Result<Vec<MigrationStatus>, PdwError> {
+    let current = compat::user_version(database)?;
+    let history = database.meta();
+    MIGRATIONS.iter()
+        .map(|migration| {
+            let applied_at = if migration.version <= current {
+                Some(history.get::<String>(MetaStore::MIGRATIONS, &migration.version.to_string())?.unwrap_or_default())
+            } else {
+                None
+            };
+            Ok(MigrationStatus { version: migration.version, description: migration.description, applied_at })
+        })
+        .collect()
+}
+
+/// Apply the pending migrations in order; returns the versions applied
+pub fn migrate(database: &DatabaseManager, config: &PdwConfig) -> Result<Vec<i64>, PdwError> {
+    let current = compat::user_version(database)?;
+    let mut applied = Vec::new();
+    
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current) {
+        database.connection().execute_batch("BEGIN")
+            .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+        let result = (migration.apply)(database, config).and_then(|_| record(database, migration));
+        match result {
+            Ok(()) => database.connection().execute_batch("COMMIT")
+                .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?,
+            Err(e) => {
+                let _ = database.connection().execute_batch("ROLLBACK");
+                return Err(DatabaseError::SchemaValidation {
+                    reason: format!("migration {} ({}) failed: {}", migration.version, migration.description, e),
+                }.into());
+            }
+        }
+        
+        tracing::info!("   . .. ... Schema migration {} applied: {}", migration.version, migration.description);
+        applied.push(migration.version);
+    }
+    
+    Ok(applied)
+}
+
+/// Bump `user_version` and note when the migration ran
+fn record(database: &DatabaseManager, migration: &Migration) -> Result<(), PdwError> {
+    database.connection().pragma_update(None, "user_version", migration.version)
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: "PRAGMA user_version".to_string(),
+            reason: e.to_string(),
+        })?;
+    let applied_at = crate::deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    database.meta().set(MetaStore::MIGRATIONS, &migration.version.to_string(), &applied_at)
+}
+
+/// 1: the tables every load expects, with the columns added after the first releases
+fn core_tables(database: &DatabaseManager, config: &PdwConfig) -> Result<(), PdwError> {
+    database.create_tables()?;
+    let mut columns = vec![("RowHash".to_string(), "TEXT")];
+    columns.extend(money::cents_schema());
+    database.add_derived_columns(&config.settings.general_entries_table, &columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_pending_migrations_applied_once() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        // Entries table of an early release, before RowHash and the cent columns
+        db.connection().execute_batch(
+            "CREATE TABLE LANCAMENTOS_GERAIS (Data DATE, TIPO TEXT, DESCRICAO TEXT, Credito REAL, Debito REAL, Origem TEXT);
+             INSERT INTO LANCAMENTOS_GERAIS VALUES ('2024-01-15', 'ALM', 'Mercado', NULL, 10.0, 'Conta');"
+        ).unwrap();
+        assert_eq!(status(&db).unwrap()[0].applied_at, None);
+        
+        let config = PdwConfig::default();
+        assert_eq!(migrate(&db, &config).unwrap(), vec![1]);
+        assert_eq!(compat::user_version(&db).unwrap(), LATEST_VERSION);
+        let columns = db.table_columns("LANCAMENTOS_GERAIS").unwrap();
+        assert!(columns.contains(&"RowHash".to_string()) && columns.contains(&"DebitoCentavos".to_string()));
+        assert_eq!(db.execute_query("SELECT COUNT(*) FROM LANCAMENTOS_GERAIS").unwrap(), vec![vec![serde_json::json!(1)]]);
+        assert!(!db.table_columns("TiposLancamentos").unwrap().is_empty());
+        assert!(status(&db).unwrap().iter().all(|migration| migration.applied_at.as_deref().is_some_and(|at| !at.is_empty())));
+        
+        assert!(migrate(&db, &config).unwrap().is_empty());
+        assert_eq!(compat::SCHEMA_VERSION, LATEST_VERSION);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,136 @@
+/*!
+# Money Module
+
+Exact handling of monetary amounts. Credito and Debito are read and rounded as
+`Decimal`, then stored twice in the general entries table: as REAL in
+Credito/Debito, so existing queries keep working, and as integer cents in
+CreditoCentavos/DebitoCentavos. Pivots and summaries sum the cents, so totals
+never show float drift (0.009999...). `settings.money = "float"` restores the
+legacy f64 rounding and REAL sums of the Python version.
+*/
+
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::RoundingStrategy;
+use serde::{Deserialize, Serialize};
+
+pub use rust_decimal::Decimal;
+
+/// Integer cents column of the credit amounts
+pub const CREDIT_CENTS_COLUMN: &str = "CreditoCentavos";
+
+/// Integer cents column of the debit amounts
+pub const DEBIT_CENTS_COLUMN: &str = "DebitoCentavos";
+
+/// Arithmetic used for monetary amounts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoneyMode {
+    /// Decimal rounding (half away from zero) and sums over integer cents
+    #[default]
+    Decimal,
+    /// Legacy f64 rounding and sums over the REAL columns
+    Float,
+}
+
+impl MoneyMode {
+    /// Round an amount to cents
+    pub fn round(&self, amount: Decimal) -> Decimal {
+        match self {
+            MoneyMode::Decimal => amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero),
+            MoneyMode::Float => {
+                let value = to_f64(amount);
+                from_f64((value * 100.0).round() / 100.0).unwrap_or_default()
+            }
+        }
+    }
+    
+    /// SQL sum of Credito or Debito, counting only rows matching `condition` when given
+    pub fn sum_sql(&self, column: &str, condition: Option<&str>) -> String {
+        let value = match self {
+            // Rows loaded before the cents columns existed fall back to the REAL value
+            MoneyMode::Decimal => format!("COALESCE({}, CAST(ROUND({} * 100) AS INTEGER))", cents_column(column), column),
+            MoneyMode::Float => column.to_string(),
+        };
+        let summed = match condition {
+            Some(condition) => format!("SUM(CASE WHEN {} THEN {} ELSE 0 END)", condition, value),
+            None => format!("SUM({})", value),
+        };
+        
+        match self {
+            MoneyMode::Decimal => format!("({} / 100.0)", summed),
+            MoneyMode::Float => summed,
+        }
+    }
+}
+
+/// Cents column paired with a money column ("Debito" -> "DebitoCentavos")
+pub fn cents_column(column: &str) -> String {
+    format!("{}Centavos", column)
+}
+
+/// Schema of the cents columns, for tables created before they existed
+pub fn cents_schema() -> Vec<(String, &'static str)> {
+    vec![
+        (CREDIT_CENTS_COLUMN.to_string(), "INTEGER"),
+        (DEBIT_CENTS_COLUMN.to_string(), "INTEGER"),
+    ]
+}
+
+/// Whole cents of an amount already rounded to two places
+pub fn to_cents(amount: Decimal) -> i64 {
+    (amount * Decimal::ONE_HUNDRED).trunc().to_i64().unwrap_or(0)
+}
+
+/// Nearest f64 of an amount
+pub fn to_f64(amount: Decimal) -> f64 {
+    amount.to_f64().unwrap_or(0.0)
+}
+
+/// Shortest decimal representing a float (0.1 -> 0.1, not 0.1000000000000000055...)
+pub fn from_f64(value: f64) -> Option<Decimal> {
+    Decimal::from_f64(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+    use serde_json::json;
+    
+    #[test]
+    fn test_rounding_modes() {
+        let amount: Decimal = "100.555".parse().unwrap();
+        assert_eq!(MoneyMode::Decimal.round(amount), Decimal::new(10056, 2));
+        assert_eq!(MoneyMode::Decimal.round(-amount), Decimal::new(-10056, 2));
+        assert_eq!(MoneyMode::Decimal.round(from_f64(0.1 + 0.2).unwrap()), Decimal::new(30, 2));
+        // 1.005 is 1.00499999999999989... as f64
+        assert_eq!(MoneyMode::Float.round("1.005".parse().unwrap()), Decimal::new(100, 2));
+        assert_eq!(MoneyMode::Decimal.round("1.005".parse().unwrap()), Decimal::new(101, 2));
+        assert_eq!(to_cents(Decimal::new(-1050, 2)), -1050);
+    }
+    
+    #[test]
+    fn test_cents_sum_has_no_drift() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        for _ in 0..10 {
+            db.connection().execute(
+                "INSERT INTO LANCAMENTOS_GERAIS (TIPO, Debito, DebitoCentavos) VALUES ('ALM', 0.1, 10)", [],
+            ).unwrap();
+        }
+        // Loaded before the cents columns existed
+        db.connection().execute("INSERT INTO LANCAMENTOS_GERAIS (TIPO, Debito) VALUES ('LAZ', 0.7)", []).unwrap();
+        
+        let sums = format!(
+            "SELECT {}, {}, {} FROM LANCAMENTOS_GERAIS",
+            MoneyMode::Decimal.sum_sql("Debito", Some("TIPO = 'ALM'")),
+            MoneyMode::Decimal.sum_sql("Debito", None),
+            MoneyMode::Float.sum_sql("Debito", Some("TIPO = 'ALM'")),
+        );
+        let result = db.execute_query(&sums).unwrap();
+        assert_eq!(result[0][0], json!(1.0));
+        assert_eq!(result[0][1], json!(1.7));
+        assert_eq!(result[0][2], json!(0.9999999999999999));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,307 @@
+/*!
+# MQTT Module
+
+Publishes key warehouse metrics (current month spend, balance per origin,
+alert status) to an MQTT broker after each load, together with Home Assistant
+discovery messages so the sensors show up on a home dashboard without manual
+YAML. Configured under `[notifications.mqtt]`; the broker connection needs a
+build with `--features mqtt`. Publishing failures are logged and never abort a run.
+*/
+
+use crate::analytics;
+use crate::collation;
+use crate::database::DatabaseManager;
+use crate::error::PdwError;
+use crate::opening_balances::OpeningBalances;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// MQTT broker and Home Assistant settings (`[notifications.mqtt]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Home Assistant discovery prefix
+    #[serde(default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+    /// Base topic for sensor states
+    #[serde(default = "default_state_topic")]
+    pub state_topic: String,
+    /// ISO 4217 code used as the unit of monetary sensors
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Seconds to wait for the broker to acknowledge the messages
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+/// A sensor value published to the broker
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    pub object_id: String,
+    pub name: String,
+    pub value: String,
+    pub unit: Option<String>,
+    pub device_class: Option<&'static str>,
+}
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_client_id() -> String {
+    "pdw".to_string()
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+fn default_state_topic() -> String {
+    "pdw".to_string()
+}
+
+fn default_currency() -> String {
+    "BRL".to_string()
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+impl MqttConfig {
+    /// Topic carrying the state of a metric
+    pub fn state_topic_for(&self, metric: &Metric) -> String {
+        format!("{}/{}/state", self.state_topic, metric.object_id)
+    }
+    
+    /// Home Assistant discovery topic of a metric
+    pub fn discovery_topic_for(&self, metric: &Metric) -> String {
+        format!("{}/sensor/{}/{}/config", self.discovery_prefix, self.client_id, metric.object_id)
+    }
+    
+    /// Discovery payload describing a metric as a Home Assistant sensor
+    pub fn discovery_payload(&self, metric: &Metric) -> Value {
+        let mut payload = json!({
+            "name": metric.name,
+            "unique_id": format!("{}_{}", self.client_id, metric.object_id),
+            "object_id": format!("{}_{}", self.client_id, metric.object_id),
+            "state_topic": self.state_topic_for(metric),
+            "device": {
+                "identifiers": [self.client_id],
+                "name": "PDW",
+                "model": "Personal Data Warehouse",
+                "sw_version": env!("CARGO_PKG_VERSION"),
+            },
+        });
+        
+        if let Some(unit) = &metric.unit {
+            payload["unit_of_measurement"] = json!(unit);
+        }
+        if let Some(device_class) = metric.device_class {
+            payload["device_class"] = json!(device_class);
+        }
+        
+        payload
+    }
+    
+    /// Discovery and state messages for all metrics, as (topic, payload) pairs
+    pub fn messages(&self, metrics: &[Metric]) -> Vec<(String, String)> {
+        let discovery = metrics.iter()
+            .map(|m| (self.discovery_topic_for(m), self.discovery_payload(m).to_string()));
+        let states = metrics.iter()
+            .map(|m| (self.state_topic_for(m), m.value.clone()));
+        discovery.chain(states).collect()
+    }
+}
+
+/// Collect the published metrics from the general entries table; the month spend leaves the `excluded` TIPOs out
+pub fn collect_metrics(database: &DatabaseManager, entries_table: &str, currency: &str,
+                       openings: &OpeningBalances, triggered_alerts: usize, excluded: &[String]) -> Result<Vec<Metric>, PdwError> {
+    let money = |object_id: String, name: String, value: f64| Metric {
+        object_id,
+        name,
+        value: format!("{:.2}", value),
+        unit: Some(currency.to_string()),
+        device_class: Some("monetary"),
+    };
+    let mut metrics = Vec::new();
+    
+    let query = format!("SELECT MAX(AnoMes) FROM {}", entries_table);
+    if let Some(Value::String(month)) = database.execute_query(&query)?.first().and_then(|r| r.first()) {
+        let query = format!(
+            "SELECT COALESCE(SUM(Debito), 0) FROM {} WHERE AnoMes = ?1 AND {}",
+            entries_table, analytics::exclusion_condition("TIPO", excluded)
+        );
+        let spend = database.execute_query_with_params(&query, [month])?
+            .first()
+            .and_then(|r| r.first())
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0);
+        
+        metrics.push(Metric {
+            object_id: "mes_referencia".to_string(),
+            name: "Mês de referência".to_string(),
+            value: month.clone(),
+            unit: None,
+            device_class: None,
+        });
+        metrics.push(money("gasto_mes".to_string(), "Gasto do mês".to_string(), spend));
+    }
+    
+    let query = format!(
+        "SELECT Origem, SUM(COALESCE(Credito, 0) - COALESCE(Debito, 0)) FROM {}
+         WHERE Origem IS NOT NULL GROUP BY Origem ORDER BY Origem",
+        entries_table
+    );
+    // Origins with an opening balance have one even without entries
+    let mut balances: BTreeMap<String, f64> = openings.origins().map(|origin| (origin.to_string(), 0.0)).collect();
+    for row in database.execute_query(&query)? {
+        if let Some(Value::String(origin)) = row.first() {
+            *balances.entry(origin.trim().to_string()).or_default() += row.get(1).and_then(Value::as_f64).unwrap_or(0.0);
+        }
+    }
+    for (origin, balance) in balances {
+        let balance = balance + openings.offset(&origin);
+        metrics.push(money(format!("saldo_{}", object_id(&origin)), format!("Saldo {}", origin), balance));
+    }
+    
+    metrics.push(Metric {
+        object_id: "alertas".to_string(),
+        name: "Alertas".to_string(),
+        value: triggered_alerts.to_string(),
+        unit: None,
+        device_class: None,
+    });
+    
+    Ok(metrics)
+}
+
+/// Lowercase ASCII identifier safe for MQTT topics and entity ids
+pub fn object_id(name: &str) -> String {
+    let slug: String = collation::strip_accents(name.trim())
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    
+    slug.split('_').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("_")
+}
+
+/// Publish retained discovery and state messages, waiting for the broker acknowledgements
+#[cfg(feature = "mqtt")]
+pub fn publish(config: &MqttConfig, metrics: &[Metric]) -> Result<usize, PdwError> {
+    use rumqttc::{Client, Event, MqttOptions, Outgoing, Packet, QoS};
+    use std::time::{Duration, Instant};
+    
+    let mqtt_error = |e: &dyn std::fmt::Display| PdwError::Notification(format!("MQTT {}:{}: {}", config.host, config.port, e));
+    
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+    }
+    
+    let messages = config.messages(metrics);
+    // Room for every message so queuing never blocks before the event loop runs
+    let (client, mut connection) = Client::new(options, messages.len() + 1);
+    for (topic, payload) in &messages {
+        client.publish(topic.as_str(), QoS::AtLeastOnce, true, payload.as_bytes().to_vec())
+            .map_err(|e| mqtt_error(&e))?;
+    }
+    
+    let deadline = Instant::now() + Duration::from_secs(config.timeout_secs);
+    let mut acknowledged = 0;
+    while acknowledged < messages.len() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match connection.recv_timeout(remaining) {
+            Ok(Ok(Event::Incoming(Packet::PubAck(_)))) => acknowledged += 1,
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => return Err(mqtt_error(&e)),
+            Err(_) => return Err(mqtt_error(&format!("{} of {} messages acknowledged before timeout", acknowledged, messages.len()))),
+        }
+    }
+    
+    client.disconnect().map_err(|e| mqtt_error(&e))?;
+    for event in connection.iter() {
+        match event {
+            Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+    
+    Ok(acknowledged)
+}
+
+#[cfg(not(feature = "mqtt"))]
+pub fn publish(_config: &MqttConfig, _metrics: &[Metric]) -> Result<usize, PdwError> {
+    Err(PdwError::Notification("[notifications.mqtt] requires a build with --features mqtt".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn mqtt_config() -> MqttConfig {
+        toml::from_str("host = \"broker.local\"").unwrap()
+    }
+    
+    #[test]
+    fn test_object_id() {
+        assert_eq!(object_id("Cartão Visa"), "cartao_visa");
+        assert_eq!(object_id(" Conta-Corrente (BB) "), "conta_corrente_bb");
+    }
+    
+    #[test]
+    fn test_collect_metrics() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-05', 'SALARIO', 1000, 0, '2024/01', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes, Origem) VALUES ('2024-02-03', 'MERCADO', 0, 200, '2024/02', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes, Origem) VALUES ('2024-02-10', 'LAZER', 0, 50.5, '2024/02', 'Cartão');"
+        ).unwrap();
+        
+        let metrics = collect_metrics(&db, "LANCAMENTOS_GERAIS", "BRL", &OpeningBalances::default(), 2, &[]).unwrap();
+        let values: Vec<(&str, &str)> = metrics.iter().map(|m| (m.object_id.as_str(), m.value.as_str())).collect();
+        assert_eq!(values, vec![
+            ("mes_referencia", "2024/02"),
+            ("gasto_mes", "250.50"),
+            ("saldo_cartao", "-50.50"),
+            ("saldo_conta", "800.00"),
+            ("alertas", "2"),
+        ]);
+    }
+    
+    #[test]
+    fn test_discovery_messages() {
+        let config = mqtt_config();
+        assert_eq!(config.port, 1883);
+        
+        let metric = Metric {
+            object_id: "gasto_mes".to_string(),
+            name: "Gasto do mês".to_string(),
+            value: "250.50".to_string(),
+            unit: Some("BRL".to_string()),
+            device_class: Some("monetary"),
+        };
+        let messages = config.messages(std::slice::from_ref(&metric));
+        assert_eq!(messages[0].0, "homeassistant/sensor/pdw/gasto_mes/config");
+        assert_eq!(messages[1], ("pdw/gasto_mes/state".to_string(), "250.50".to_string()));
+        
+        let payload: Value = serde_json::from_str(&messages[0].1).unwrap();
+        assert_eq!(payload["state_topic"], "pdw/gasto_mes/state");
+        assert_eq!(payload["device_class"], "monetary");
+        assert_eq!(payload["unit_of_measurement"], "BRL");
+    }
+}
\ No newline at end of file
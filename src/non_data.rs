@@ -0,0 +1,149 @@
+/*!
+# Non-Data Rows Module
+
+Recognizes the rows of an accounting sheet that are not entries: subtotal
+lines ("Total Janeiro", "Subtotal") and section headers ("JANEIRO 2024",
+"Cartão"), which otherwise reach the load as undated or untyped entries,
+end up discarded and show up as data-quality violations.
+
+A row is non-data when its TIPO or DESCRICAO matches one of the configured
+patterns, or (heuristic) when it has neither a date nor an amount but does
+carry text. Such rows are dropped right after reading, counted per sheet in
+the log, and neither discarded nor checked for quality.
+*/
+
+use crate::error::{ConfigError, PdwError};
+use crate::excel::Transaction;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Non-data row settings (`[non_data_rows]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NonDataRowsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Case-insensitive regexes matched against TIPO and DESCRICAO
+    #[serde(default = "default_patterns")]
+    pub patterns: Vec<String>,
+    /// Also skip rows with text but no date and no amount
+    #[serde(default = "default_true")]
+    pub text_only_rows: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_patterns() -> Vec<String> {
+    vec![r"^\s*(sub-?\s*)?tota(l|is)\b".to_string()]
+}
+
+impl Default for NonDataRowsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            patterns: default_patterns(),
+            text_only_rows: true,
+        }
+    }
+}
+
+/// Compiled non-data row rules
+#[derive(Debug, Clone)]
+pub struct NonDataFilter {
+    patterns: Vec<Regex>,
+    text_only_rows: bool,
+}
+
+impl NonDataFilter {
+    /// Compile the configured patterns; None when the filter is disabled
+    pub fn from_config(config: &NonDataRowsConfig) -> Result<Option<Self>, PdwError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        
+        let patterns = config.patterns.iter()
+            .map(|pattern| RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| ConfigError::InvalidFormat {
+                    message: format!("Invalid non_data_rows pattern '{}': {}", pattern, e),
+                }))
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        Ok(Some(Self { patterns, text_only_rows: config.text_only_rows }))
+    }
+    
+    /// Whether a row read from an accounting sheet is a subtotal or section header
+    pub fn is_non_data(&self, transaction: &Transaction) -> bool {
+        let texts = [transaction.transaction_type.as_deref(), transaction.description.as_deref()];
+        let matches_pattern = texts.iter().flatten()
+            .any(|text| self.patterns.iter().any(|pattern| pattern.is_match(text)));
+        if matches_pattern {
+            return true;
+        }
+        
+        self.text_only_rows
+            && transaction.date.is_none()
+            && transaction.credit.is_none()
+            && transaction.debit.is_none()
+            && texts.iter().flatten().any(|text| !text.trim().is_empty())
+    }
+    
+    /// Drop the non-data rows; returns how many were dropped
+    pub fn retain_data(&self, transactions: &mut Vec<Transaction>) -> usize {
+        let before = transactions.len();
+        transactions.retain(|transaction| !self.is_non_data(transaction));
+        before - transactions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Decimal;
+    use chrono::NaiveDate;
+    
+    fn row(date: Option<&str>, tipo: Option<&str>, description: Option<&str>, debit: Option<&str>) -> Transaction {
+        Transaction {
+            date: date.map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap()),
+            transaction_type: tipo.map(String::from),
+            description: description.map(String::from),
+            credit: None,
+            debit: debit.map(|d| d.parse::<Decimal>().unwrap()),
+            origin: "Conta".to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
+        }
+    }
+    
+    #[test]
+    fn test_non_data_rows() {
+        let filter = NonDataFilter::from_config(&NonDataRowsConfig::default()).unwrap().unwrap();
+        
+        // Subtotals, dated or not, and a section header with no amount
+        assert!(filter.is_non_data(&row(None, Some("TOTAL"), None, Some("150.00"))));
+        assert!(filter.is_non_data(&row(Some("2024-01-31"), None, Some("Subtotal Janeiro"), Some("150.00"))));
+        assert!(filter.is_non_data(&row(None, Some("Cartão"), Some("Fevereiro 2024"), None)));
+        
+        // Entries, including an undated one with an amount (a data-quality failure)
+        assert!(!filter.is_non_data(&row(Some("2024-01-05"), Some("ALM"), Some("Mercado total"), Some("10.00"))));
+        assert!(!filter.is_non_data(&row(Some("2024-01-05"), Some("ALM"), None, None)));
+        assert!(!filter.is_non_data(&row(None, Some("ALM"), Some("Mercado"), Some("10.00"))));
+        
+        let mut rows = vec![row(None, Some("Totais"), None, Some("1.00")), row(Some("2024-01-05"), Some("ALM"), None, Some("1.00"))];
+        assert_eq!(filter.retain_data(&mut rows), 1);
+        assert_eq!(rows.len(), 1);
+        
+        let config: NonDataRowsConfig = toml::from_str("patterns = ['^saldo']\ntext_only_rows = false").unwrap();
+        let filter = NonDataFilter::from_config(&config).unwrap().unwrap();
+        assert!(filter.is_non_data(&row(None, None, Some("Saldo anterior"), Some("5.00"))));
+        assert!(!filter.is_non_data(&row(None, Some("TOTAL"), None, Some("150.00"))));
+        assert!(!filter.is_non_data(&row(None, Some("Cartão"), None, None)));
+        
+        assert!(NonDataFilter::from_config(&NonDataRowsConfig { enabled: false, ..NonDataRowsConfig::default() }).unwrap().is_none());
+        assert!(NonDataFilter::from_config(&NonDataRowsConfig { patterns: vec!["(".to_string()], ..NonDataRowsConfig::default() }).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,189 @@
+/*!
+# Notifications Module
+
+Delivery channels for run messages (alerts, summaries). Channels are configured
+under `[notifications]`; delivery failures are logged and never abort a run.
+Metric publishing to Home Assistant lives in the mqtt module.
+*/
+
+use crate::error::PdwError;
+use crate::mqtt::MqttConfig;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Notification channel configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    /// Master switch for all channels
+    #[serde(default)]
+    pub enabled: bool,
+    /// Shell command receiving the message body on stdin (subject in PDW_SUBJECT)
+    #[serde(default)]
+    pub command: Option<String>,
+    /// File that messages are appended to
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    /// MQTT broker receiving the run metrics (`[notifications.mqtt]`)
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+}
+
+/// A destination for notification messages
+pub trait NotificationChannel {
+    fn name(&self) -> &str;
+    fn send(&self, subject: &str, body: &str) -> Result<(), PdwError>;
+}
+
+/// Runs a shell command with the message on stdin
+pub struct CommandChannel {
+    command: String,
+}
+
+/// Appends messages to a text file
+pub struct FileChannel {
+    path: PathBuf,
+}
+
+/// Fan-out over all configured channels
+#[derive(Default)]
+pub struct Notifier {
+    channels: Vec<Box<dyn NotificationChannel>>,
+}
+
+impl NotificationChannel for CommandChannel {
+    fn name(&self) -> &str {
+        "command"
+    }
+    
+    fn send(&self, subject: &str, body: &str) -> Result<(), PdwError> {
+        let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        
+        let mut child = Command::new(shell)
+            .arg(flag)
+            .arg(&self.command)
+            .env("PDW_SUBJECT", subject)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| PdwError::Notification(format!("Failed to start '{}': {}", self.command, e)))?;
+        
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(body.as_bytes())
+                .map_err(|e| PdwError::Notification(format!("Failed to write message: {}", e)))?;
+        }
+        
+        let status = child.wait()
+            .map_err(|e| PdwError::Notification(format!("Command failed: {}", e)))?;
+        
+        if !status.success() {
+            return Err(PdwError::Notification(format!("'{}' exited with {}", self.command, status)));
+        }
+        
+        Ok(())
+    }
+}
+
+impl NotificationChannel for FileChannel {
+    fn name(&self) -> &str {
+        "file"
+    }
+    
+    fn send(&self, subject: &str, body: &str) -> Result<(), PdwError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| PdwError::Notification(format!("Failed to open {}: {}", self.path.display(), e)))?;
+        
+        let timestamp = chrono::Local::now().format("%Y/%m/%d %H:%M:%S");
+        writeln!(file, "[{}] {}\n{}\n", timestamp, subject, body)
+            .map_err(|e| PdwError::Notification(format!("Failed to write {}: {}", self.path.display(), e)))?;
+        
+        Ok(())
+    }
+}
+
+impl Notifier {
+    /// Build notifier from configuration (empty when disabled)
+    pub fn from_config(config: &NotificationConfig) -> Self {
+        let mut notifier = Notifier::default();
+        
+        if !config.enabled {
+            return notifier;
+        }
+        
+        if let Some(command) = &config.command {
+            notifier.add_channel(Box::new(CommandChannel { command: command.clone() }));
+        }
+        if let Some(path) = &config.file {
+            notifier.add_channel(Box::new(FileChannel { path: path.clone() }));
+        }
+        
+        notifier
+    }
+    
+    /// Register an additional channel
+    pub fn add_channel(&mut self, channel: Box<dyn NotificationChannel>) {
+        self.channels.push(channel);
+    }
+    
+    /// Check whether any channel is configured
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+    
+    /// Send a message to every channel, returning the number of successful deliveries
+    pub fn notify(&self, subject: &str, body: &str) -> usize {
+        let mut delivered = 0;
+        
+        for channel in &self.channels {
+            match channel.send(subject, body) {
+                Ok(()) => delivered += 1,
+                Err(e) => tracing::warn!("Notification channel '{}' failed: {}", channel.name(), e),
+            }
+        }
+        
+        delivered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_disabled_notifier_is_empty() {
+        let config = NotificationConfig {
+            enabled: false,
+            command: Some("true".to_string()),
+            file: None,
+            mqtt: None,
+        };
+        assert!(Notifier::from_config(&config).is_empty());
+    }
+    
+    #[test]
+    fn test_file_channel_delivery() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("notifications.log");
+        let config = NotificationConfig {
+            enabled: true,
+            command: None,
+            file: Some(path.clone()),
+            mqtt: None,
+        };
+        
+        let notifier = Notifier::from_config(&config);
+        assert_eq!(notifier.notify("PDW alert", "Debit above threshold"), 1);
+        
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("PDW alert"));
+        assert!(content.contains("Debit above threshold"));
+    }
+}
\ No newline at end of file
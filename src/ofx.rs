@@ -0,0 +1,179 @@
+/*!
+# OFX Format Module
+
+Shared OFX (Open Financial Exchange) statement model and writer. Statements are
+written as OFX 2.1.1 XML in UTF-8, so accented descriptions survive the trip
+into GnuCash, Money and most bank apps.
+*/
+
+use crate::database;
+use chrono::NaiveDate;
+
+/// Single OFX statement transaction (positive amount = credit)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfxTransaction {
+    pub date: NaiveDate,
+    pub amount: f64,
+    pub fit_id: String,
+    pub name: String,
+    pub memo: String,
+}
+
+/// Bank statement for one account
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfxStatement {
+    pub bank_id: String,
+    pub account_id: String,
+    pub account_type: String,
+    pub currency: String,
+    pub transactions: Vec<OfxTransaction>,
+}
+
+/// Maximum length of the OFX NAME element
+const NAME_MAX_LEN: usize = 32;
+
+impl OfxStatement {
+    /// Create an empty checking-account statement
+    pub fn new(account_id: &str, currency: &str) -> Self {
+        Self {
+            bank_id: "PDW".to_string(),
+            account_id: account_id.to_string(),
+            account_type: "CHECKING".to_string(),
+            currency: currency.to_string(),
+            transactions: Vec::new(),
+        }
+    }
+    
+    /// Statement balance (sum of all transaction amounts)
+    pub fn balance(&self) -> f64 {
+        let total: f64 = self.transactions.iter().map(|t| t.amount).sum();
+        (total * 100.0).round() / 100.0
+    }
+    
+    /// Earliest and latest transaction dates
+    pub fn date_range(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let start = self.transactions.iter().map(|t| t.date).min()?;
+        let end = self.transactions.iter().map(|t| t.date).max()?;
+        Some((start, end))
+    }
+    
+    /// Render the statement as an OFX document
+    pub fn to_ofx(&self, generated: NaiveDate) -> String {
+        let mut out = String::new();
+        
+        // OFX 2.1.1 header; the body is UTF-8 like the rest of the exports
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\r\n");
+        out.push_str("<?OFX OFXHEADER=\"200\" VERSION=\"211\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\r\n");
+        
+        let generated = format_date(generated);
+        let (start, end) = self.date_range()
+            .map(|(s, e)| (format_date(s), format_date(e)))
+            .unwrap_or_else(|| (generated.clone(), generated.clone()));
+        
+        out.push_str("<OFX>\r\n");
+        out.push_str("<SIGNONMSGSRSV1><SONRS>\r\n");
+        out.push_str("<STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\r\n");
+        out.push_str(&format!("<DTSERVER>{}</DTSERVER><LANGUAGE>POR</LANGUAGE>\r\n", generated));
+        out.push_str("</SONRS></SIGNONMSGSRSV1>\r\n");
+        out.push_str("<BANKMSGSRSV1><STMTTRNRS>\r\n");
+        out.push_str("<TRNUID>1</TRNUID><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\r\n");
+        out.push_str(&format!("<STMTRS><CURDEF>{}</CURDEF>\r\n", escape(&self.currency)));
+        out.push_str(&format!(
+            "<BANKACCTFROM><BANKID>{}</BANKID><ACCTID>{}</ACCTID><ACCTTYPE>{}</ACCTTYPE></BANKACCTFROM>\r\n",
+            escape(&self.bank_id), escape(&self.account_id), escape(&self.account_type)
+        ));
+        out.push_str(&format!("<BANKTRANLIST><DTSTART>{}</DTSTART><DTEND>{}</DTEND>\r\n", start, end));
+        
+        for transaction in &self.transactions {
+            let trn_type = if transaction.amount >= 0.0 { "CREDIT" } else { "DEBIT" };
+            let name: String = transaction.name.chars().take(NAME_MAX_LEN).collect();
+            
+            out.push_str("<STMTTRN>\r\n");
+            out.push_str(&format!("<TRNTYPE>{}</TRNTYPE>\r\n", trn_type));
+            out.push_str(&format!("<DTPOSTED>{}</DTPOSTED>\r\n", format_date(transaction.date)));
+            out.push_str(&format!("<TRNAMT>{:.2}</TRNAMT>\r\n", transaction.amount));
+            out.push_str(&format!("<FITID>{}</FITID>\r\n", escape(&transaction.fit_id)));
+            out.push_str(&format!("<NAME>{}</NAME>\r\n", escape(&name)));
+            if !transaction.memo.is_empty() {
+                out.push_str(&format!("<MEMO>{}</MEMO>\r\n", escape(&transaction.memo)));
+            }
+            out.push_str("</STMTTRN>\r\n");
+        }
+        
+        out.push_str("</BANKTRANLIST>\r\n");
+        out.push_str(&format!(
+            "<LEDGERBAL><BALAMT>{:.2}</BALAMT><DTASOF>{}</DTASOF></LEDGERBAL>\r\n",
+            self.balance(), end
+        ));
+        out.push_str("</STMTRS></STMTTRNRS></BANKMSGSRSV1>\r\n");
+        out.push_str("</OFX>\r\n");
+        
+        out
+    }
+}
+
+/// Deterministic financial institution transaction id of one side (credit or
+/// debit) of an entry, from the entry's RowHash; ids do not shift when other
+/// entries are added or removed, so re-exports are not imported twice
+pub fn fit_id(account_id: &str, date: NaiveDate, row_hash: &str, credit: bool) -> String {
+    let key = format!("{}|{}|{}", account_id, row_hash, if credit { "C" } else { "D" });
+    format!("{}{:016X}", date.format("%Y%m%d"), database::fnv1a(key.as_bytes()))
+}
+
+/// Format a date as OFX DTxxx (YYYYMMDD)
+pub fn format_date(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// Escape SGML/XML special characters
+pub fn escape(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_fit_id_is_stable() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        let a = fit_id("Conta", date, "00ab-1", false);
+        let b = fit_id("Conta", date, "00ab-1", false);
+        assert_eq!(a, b);
+        assert_ne!(a, fit_id("Conta", date, "00ab-2", false));
+        assert_ne!(a, fit_id("Conta", date, "00ab-1", true));
+        assert!(a.starts_with("20240115"));
+    }
+    
+    #[test]
+    fn test_statement_rendering() {
+        let mut statement = OfxStatement::new("Conta & Cia", "BRL");
+        statement.transactions.push(OfxTransaction {
+            date: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            amount: -42.5,
+            fit_id: "1".to_string(),
+            name: "ALM".to_string(),
+            memo: "Mercado <centro>".to_string(),
+        });
+        statement.transactions.push(OfxTransaction {
+            date: NaiveDate::from_ymd_opt(2024, 1, 20).unwrap(),
+            amount: 100.0,
+            fit_id: "2".to_string(),
+            name: "SAL".to_string(),
+            memo: String::new(),
+        });
+        
+        let ofx = statement.to_ofx(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+        assert!(ofx.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\""));
+        assert!(ofx.contains("OFXHEADER=\"200\""));
+        assert!(ofx.contains("<ACCTID>Conta &amp; Cia</ACCTID>"));
+        assert!(ofx.contains("<TRNTYPE>DEBIT</TRNTYPE>"));
+        assert!(ofx.contains("<TRNAMT>-42.50</TRNAMT>"));
+        assert!(ofx.contains("<MEMO>Mercado &lt;centro&gt;</MEMO>"));
+        assert!(ofx.contains("<DTSTART>20240115</DTSTART><DTEND>20240120</DTEND>"));
+        assert!(ofx.contains("<BALAMT>57.50</BALAMT>"));
+    }
+}
\ No newline at end of file
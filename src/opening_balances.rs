@@ -0,0 +1,237 @@
+/*!
+# Opening Balances Module
+
+Balances computed from the general entries start at zero, so an account whose
+history in the workbook starts in 2024 shows only what moved since then. Each
+origin can have the balance it held at the end of an as-of day, from
+`[opening_balances.accounts]` and from a SALDOS_INICIAIS sheet of the input
+workbook (ORIGEM, SALDO and DATA columns, the sheet wins over the
+configuration); they are stored in the SALDOS_INICIAIS table at load time.
+
+Entries dated up to the as-of day are already part of the opening balance, so an
+origin's balance is its entries-only balance shifted by a fixed offset: the
+opening balance minus the entries up to the as-of day. The offsets move the
+opening and running balances of the PDF statements, the per-origin balances
+published over MQTT and the balance trend of the statement card.
+*/
+
+use crate::budgets;
+use crate::collation;
+use crate::database::DatabaseManager;
+use crate::error::{ConfigError, DatabaseError, PdwError};
+use crate::excel;
+use crate::money::MoneyMode;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Opening balance settings (`[opening_balances]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpeningBalanceConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Input sheet with the balances, read when present
+    #[serde(default = "default_opening_balances_name")]
+    pub sheet: String,
+    /// Table the balances are stored in
+    #[serde(default = "default_opening_balances_name")]
+    pub table: String,
+    /// Opening balance by Origem
+    #[serde(default)]
+    pub accounts: BTreeMap<String, OpeningBalance>,
+}
+
+/// Balance of an origin at the end of a day
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OpeningBalance {
+    pub balance: f64,
+    /// Last day the balance includes
+    pub as_of: NaiveDate,
+}
+
+fn default_opening_balances_name() -> String {
+    "SALDOS_INICIAIS".to_string()
+}
+
+impl Default for OpeningBalanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sheet: default_opening_balances_name(),
+            table: default_opening_balances_name(),
+            accounts: BTreeMap::new(),
+        }
+    }
+}
+
+impl OpeningBalanceConfig {
+    /// Balances of the configuration with the rows of a SALDOS_INICIAIS sheet (header first) over them
+    pub fn balances_with_sheet(&self, rows: &[Vec<String>]) -> Result<BTreeMap<String, OpeningBalance>, PdwError> {
+        let mut balances: BTreeMap<String, OpeningBalance> = self.accounts.iter()
+            .map(|(origin, balance)| (origin.trim().to_string(), *balance))
+            .collect();
+        let Some((header, rows)) = rows.split_first() else {
+            return Ok(balances);
+        };
+        
+        let column = |names: &[&str], field: &str| header.iter()
+            .position(|h| names.contains(&collation::fold(h.trim()).as_str()))
+            .ok_or_else(|| ConfigError::MissingField {
+                field: format!("{} column of the {} sheet", field, self.sheet),
+            });
+        let origin_column = column(&["origem", "origin", "conta", "account"], "ORIGEM")?;
+        let balance_column = column(&["saldo", "balance"], "SALDO")?;
+        let date_column = column(&["data", "date", "as_of"], "DATA")?;
+        
+        for row in rows {
+            let origin = row.get(origin_column).map(|o| o.trim()).unwrap_or_default();
+            if origin.is_empty() {
+                continue;
+            }
+            let text = row.get(balance_column).map(|b| b.trim()).unwrap_or_default();
+            let balance = budgets::parse_limit(text).ok_or_else(|| ConfigError::InvalidFormat {
+                message: format!("Invalid opening balance '{}' for {} in the {} sheet", text, origin, self.sheet),
+            })?;
+            let text = row.get(date_column).map(|d| d.trim()).unwrap_or_default();
+            let as_of = parse_as_of(text).ok_or_else(|| ConfigError::InvalidFormat {
+                message: format!("Invalid date '{}' for {} in the {} sheet", text, origin, self.sheet),
+            })?;
+            balances.insert(origin.to_string(), OpeningBalance { balance, as_of });
+        }
+        
+        Ok(balances)
+    }
+}
+
+/// Day typed as a date or kept by the sheet as a serial number
+pub(crate) fn parse_as_of(text: &str) -> Option<NaiveDate> {
+    excel::parse_date(text).or_else(|| {
+        excel::DateSystem::Excel1900.serial_date(text.parse().ok()?)
+    })
+}
+
+/// Store the balances, replacing the previous load's table
+pub fn write_opening_balance_table(database: &DatabaseManager, table: &str,
+                                   balances: &BTreeMap<String, OpeningBalance>) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!("CREATE TABLE {} (Origem TEXT PRIMARY KEY, Saldo REAL, Data DATE)", table);
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3)", table);
+    for (origin, opening) in balances {
+        database.connection().execute(&insert_query, rusqlite::params![
+            origin,
+            opening.balance,
+            opening.as_of.format("%Y-%m-%d").to_string(),
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(balances.len())
+}
+
+/// Offsets of the origins with an opening balance, added to balances computed from the entries
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpeningBalances {
+    offsets: BTreeMap<String, f64>,
+}
+
+impl OpeningBalances {
+    /// Offsets of the stored balances; none while disabled or before a load stored them
+    pub fn load(database: &DatabaseManager, entries_table: &str,
+                config: &OpeningBalanceConfig) -> Result<Self, PdwError> {
+        if !config.enabled || database.table_columns(&config.table)?.is_empty() {
+            return Ok(Self::default());
+        }
+        
+        let money = database.money_mode();
+        if money == MoneyMode::Decimal {
+            database.add_money_columns(entries_table)?;
+        }
+        let query = format!(
+            "SELECT b.Origem, ROUND(b.Saldo - COALESCE((
+                 SELECT {credit} - {debit} FROM {entries} e
+                 WHERE TRIM(COALESCE(e.Origem, '')) = b.Origem AND date(e.Data) <= b.Data
+             ), 0), 2)
+             FROM {table} b",
+            credit = money.sum_sql("e.Credito", None),
+            debit = money.sum_sql("e.Debito", None),
+            entries = entries_table,
+            table = config.table,
+        );
+        let offsets = database.execute_query(&query)?
+            .iter()
+            .filter_map(|row| Some((
+                row.first().and_then(Value::as_str)?.to_string(),
+                row.get(1).and_then(Value::as_f64).unwrap_or(0.0),
+            )))
+            .collect();
+        
+        Ok(Self { offsets })
+    }
+    
+    /// Amount added to an origin's balance computed from the entries
+    pub fn offset(&self, origin: &str) -> f64 {
+        self.offsets.get(origin.trim()).copied().unwrap_or(0.0)
+    }
+    
+    /// Amount added to the balance of all origins together
+    pub fn total(&self) -> f64 {
+        self.offsets.values().sum()
+    }
+    
+    /// Origins with an opening balance
+    pub fn origins(&self) -> impl Iterator<Item = &str> {
+        self.offsets.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn rows(cells: &[&[&str]]) -> Vec<Vec<String>> {
+        cells.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect()
+    }
+    
+    #[test]
+    fn test_opening_balances_offset_entries() {
+        let config: OpeningBalanceConfig = toml::from_str(
+            "enabled = true\n[accounts]\nConta = { balance = 1500.0, as_of = \"2023-12-31\" }\nPoupanca = { balance = 200, as_of = \"2024-01-31\" }"
+        ).unwrap();
+        let sheet = rows(&[&["Origem", "Saldo", "Data"], &["Cartão", "-1.250,40", "45291"], &["Poupanca", "300", "31/01/2024"], &["", "10", ""]]);
+        let balances = config.balances_with_sheet(&sheet).unwrap();
+        assert_eq!(balances.len(), 3);
+        assert_eq!(balances["Cartão"], OpeningBalance { balance: -1250.4, as_of: NaiveDate::from_ymd_opt(2023, 12, 31).unwrap() });
+        assert_eq!(balances["Poupanca"].balance, 300.0);
+        assert!(config.balances_with_sheet(&rows(&[&["ORIGEM", "SALDO"], &["Conta", "1"]])).is_err());
+        assert!(config.balances_with_sheet(&rows(&[&["ORIGEM", "SALDO", "DATA"], &["Conta", "1", "ontem"]])).is_err());
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        assert_eq!(OpeningBalances::load(&db, "LANCAMENTOS_GERAIS", &config).unwrap(), OpeningBalances::default());
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2023-12-20', 'SAL', 1000, 0, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-01-05', 'ALM', 0, 150, 'Conta ');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-01-10', 'LAZ', 0, 40, 'Poupanca');"
+        ).unwrap();
+        assert_eq!(write_opening_balance_table(&db, &config.table, &balances).unwrap(), 3);
+        
+        // Entries up to the as-of day are already in the opening balance
+        let openings = OpeningBalances::load(&db, "LANCAMENTOS_GERAIS", &config).unwrap();
+        assert_eq!(openings.offset("Conta"), 500.0);
+        assert_eq!(openings.offset("Poupanca"), 340.0);
+        assert_eq!(openings.offset("Cartão"), -1250.4);
+        assert_eq!(openings.offset("Outra"), 0.0);
+        assert_eq!(openings.origins().collect::<Vec<_>>(), vec!["Cartão", "Conta", "Poupanca"]);
+    }
+}
\ No newline at end of file
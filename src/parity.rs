@@ -0,0 +1,383 @@
+/*!
+# Parity Module
+
+Conformance check against the Python PDW: the workbook is loaded by the Rust
+pipeline and every table both databases share is compared with the one in a
+Python-produced database. Rows are compared as multisets after normalizing the
+representation differences that carry no meaning (pandas timestamps in `Data`,
+integer vs real numbers, float noise below the compared decimals), so the
+report only lists real differences: rows present on one side only and rows
+whose values differ in some columns.
+*/
+
+use crate::database::{quote_identifier, DatabaseManager, META_TABLE};
+use crate::runs::RUNS_TABLE;
+use crate::error::{DatabaseError, PdwError};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Schema name the Python database is attached as
+const PYTHON_SCHEMA: &str = "pdw_python";
+
+/// Unmatched rows of each side paired up to find value differences
+const MAX_PAIRED_ROWS: usize = 1000;
+
+/// Outcome of comparing two warehouses
+#[derive(Debug, Clone, Default)]
+pub struct ParityReport {
+    pub tables: Vec<TableParity>,
+    /// Tables only the Rust pipeline created
+    pub rust_only_tables: Vec<String>,
+    /// Tables only the Python database has
+    pub python_only_tables: Vec<String>,
+}
+
+/// Comparison of one shared table
+#[derive(Debug, Clone, Default)]
+pub struct TableParity {
+    pub table: String,
+    pub rust_rows: usize,
+    pub python_rows: usize,
+    /// Columns compared (present on both sides)
+    pub columns: Vec<String>,
+    pub rust_only_columns: Vec<String>,
+    pub python_only_columns: Vec<String>,
+    /// Normalized rows with no counterpart on the other side
+    pub rust_only_rows: Vec<Vec<String>>,
+    pub python_only_rows: Vec<Vec<String>>,
+    pub differences: Vec<RowDifference>,
+}
+
+/// Row present on both sides with some differing values
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowDifference {
+    /// Normalized Rust row
+    pub row: Vec<String>,
+    pub cells: Vec<CellDifference>,
+}
+
+/// Differing value of a column
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellDifference {
+    pub column: String,
+    pub rust: String,
+    pub python: String,
+}
+
+impl ParityReport {
+    /// Whether every shared table holds the same rows
+    pub fn is_identical(&self) -> bool {
+        self.tables.iter().all(TableParity::matches)
+    }
+    
+    /// Shared tables with differences
+    pub fn mismatched_tables(&self) -> usize {
+        self.tables.iter().filter(|t| !t.matches()).count()
+    }
+    
+    /// Log a summary per table and up to `max_differences` rows of each kind
+    pub fn log(&self, max_differences: usize) {
+        for table in &self.tables {
+            let status = if table.matches() { "\x1b[32mOK\x1b[0m" } else { "\x1b[31mDIFF\x1b[0m" };
+            tracing::info!(
+                "   {:<30} rust {:>7} | python {:>7} | rust only {:>5} | python only {:>5} | changed {:>5}  {}",
+                table.table, table.rust_rows, table.python_rows,
+                table.rust_only_rows.len(), table.python_only_rows.len(), table.differences.len(), status
+            );
+            if !table.rust_only_columns.is_empty() || !table.python_only_columns.is_empty() {
+                tracing::info!(
+                    "      columns not compared - rust: [{}] python: [{}]",
+                    table.rust_only_columns.join(", "), table.python_only_columns.join(", ")
+                );
+            }
+            
+            for difference in table.differences.iter().take(max_differences) {
+                let cells: Vec<String> = difference.cells.iter()
+                    .map(|c| format!("{}: rust '{}' python '{}'", c.column, c.rust, c.python))
+                    .collect();
+                tracing::info!("      changed [{}] {}", difference.row.join(" | "), cells.join("; "));
+            }
+            for row in table.rust_only_rows.iter().take(max_differences) {
+                tracing::info!("      rust only   [{}]", row.join(" | "));
+            }
+            for row in table.python_only_rows.iter().take(max_differences) {
+                tracing::info!("      python only [{}]", row.join(" | "));
+            }
+        }
+        
+        if !self.rust_only_tables.is_empty() {
+            tracing::info!("Tables only in the Rust database: {}", self.rust_only_tables.join(", "));
+        }
+        if !self.python_only_tables.is_empty() {
+            tracing::warn!("Tables only in the Python database: {}", self.python_only_tables.join(", "));
+        }
+    }
+}
+
+impl TableParity {
+    /// Whether both sides hold the same rows
+    pub fn matches(&self) -> bool {
+        self.rust_only_rows.is_empty() && self.python_only_rows.is_empty() && self.differences.is_empty()
+    }
+}
+
+/// Compare every table shared by the Rust database and a Python-produced one,
+/// numbers rounded to `decimals` places
+pub fn compare_databases(rust: &DatabaseManager, python_db: &Path, decimals: usize) -> Result<ParityReport, PdwError> {
+    if !python_db.exists() {
+        return Err(DatabaseError::ConnectionFailed {
+            path: python_db.to_string_lossy().to_string(),
+            reason: "Python database not found".to_string(),
+        }.into());
+    }
+    
+    let attach = format!("ATTACH DATABASE ?1 AS {}", PYTHON_SCHEMA);
+    rust.connection().execute(&attach, [python_db.to_string_lossy()])
+        .map_err(|e| DatabaseError::ConnectionFailed {
+            path: python_db.to_string_lossy().to_string(),
+            reason: e.to_string(),
+        })?;
+    
+    let report = compare_attached(rust, decimals);
+    let _ = rust.connection().execute_batch(&format!("DETACH DATABASE {}", PYTHON_SCHEMA));
+    report
+}
+
+fn compare_attached(database: &DatabaseManager, decimals: usize) -> Result<ParityReport, PdwError> {
+    let rust_tables = table_names(database, "main")?;
+    let python_tables = table_names(database, PYTHON_SCHEMA)?;
+    let find = |tables: &[String], name: &str| tables.iter().find(|t| t.eq_ignore_ascii_case(name)).cloned();
+    
+    let mut report = ParityReport::default();
+    for table in &rust_tables {
+        match find(&python_tables, table) {
+            Some(python_table) => report.tables.push(compare_table(database, table, &python_table, decimals)?),
+            None => report.rust_only_tables.push(table.clone()),
+        }
+    }
+    report.python_only_tables = python_tables.iter()
+        .filter(|table| find(&rust_tables, table).is_none())
+        .cloned()
+        .collect();
+    
+    Ok(report)
+}
+
+/// User tables of a schema, without the metadata and run history tables
+fn table_names(database: &DatabaseManager, schema: &str) -> Result<Vec<String>, PdwError> {
+    let query = format!(
+        "SELECT name FROM {}.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        schema
+    );
+    Ok(database.execute_query(&query)?
+        .into_iter()
+        .filter_map(|row| row.first().and_then(Value::as_str).map(str::to_string))
+        .filter(|name| !name.eq_ignore_ascii_case(META_TABLE) && !name.eq_ignore_ascii_case(RUNS_TABLE))
+        .collect())
+}
+
+fn compare_table(database: &DatabaseManager, table: &str, python_table: &str, decimals: usize) -> Result<TableParity, PdwError> {
+    let rust_columns = columns(database, "main", table)?;
+    let python_columns = columns(database, PYTHON_SCHEMA, python_table)?;
+    let find = |columns: &[String], name: &str| columns.iter().find(|c| c.eq_ignore_ascii_case(name)).cloned();
+    
+    // Shared columns in Rust order, with the Python spelling of each name
+    let shared: Vec<(String, String)> = rust_columns.iter()
+        .filter_map(|column| find(&python_columns, column).map(|python| (column.clone(), python)))
+        .collect();
+    let mut parity = TableParity {
+        table: table.to_string(),
+        columns: shared.iter().map(|(column, _)| column.clone()).collect(),
+        rust_only_columns: rust_columns.iter().filter(|c| find(&python_columns, c).is_none()).cloned().collect(),
+        python_only_columns: python_columns.iter().filter(|c| find(&rust_columns, c).is_none()).cloned().collect(),
+        ..Default::default()
+    };
+    if shared.is_empty() {
+        return Ok(parity);
+    }
+    
+    let select = |schema: &str, table: &str, names: Vec<&String>| format!(
+        "SELECT {} FROM {}.{}",
+        names.iter().map(|c| quote_identifier(c)).collect::<Vec<_>>().join(", "),
+        schema,
+        quote_identifier(table)
+    );
+    let rust_rows = normalized_rows(database, &select("main", table, shared.iter().map(|(c, _)| c).collect()), decimals)?;
+    let python_rows = normalized_rows(database, &select(PYTHON_SCHEMA, python_table, shared.iter().map(|(_, c)| c).collect()), decimals)?;
+    parity.rust_rows = rust_rows.len();
+    parity.python_rows = python_rows.len();
+    
+    // Multiset match of identical rows
+    let mut unmatched_python: HashMap<&[String], usize> = HashMap::new();
+    for row in &python_rows {
+        *unmatched_python.entry(row.as_slice()).or_default() += 1;
+    }
+    let mut rust_only: Vec<&Vec<String>> = Vec::new();
+    for row in &rust_rows {
+        match unmatched_python.get_mut(row.as_slice()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => rust_only.push(row),
+        }
+    }
+    let mut python_only: Vec<Option<&Vec<String>>> = Vec::new();
+    for row in &python_rows {
+        if let Some(count) = unmatched_python.get_mut(row.as_slice()).filter(|count| **count > 0) {
+            *count -= 1;
+            python_only.push(Some(row));
+        }
+    }
+    
+    // Pair leftovers sharing most of their values to report what changed
+    for rust_row in rust_only {
+        let best = python_only.iter()
+            .enumerate()
+            .take(MAX_PAIRED_ROWS)
+            .filter_map(|(index, row)| row.map(|row| (index, equal_cells(rust_row, row))))
+            .filter(|(_, equal)| *equal * 2 >= parity.columns.len())
+            .max_by_key(|(_, equal)| *equal);
+        
+        match best {
+            Some((index, _)) => {
+                let python_row = python_only[index].take().expect("paired row is still unmatched");
+                parity.differences.push(RowDifference {
+                    row: rust_row.clone(),
+                    cells: parity.columns.iter()
+                        .zip(rust_row.iter().zip(python_row))
+                        .filter(|(_, (rust, python))| rust != python)
+                        .map(|(column, (rust, python))| CellDifference {
+                            column: column.clone(),
+                            rust: rust.clone(),
+                            python: python.clone(),
+                        })
+                        .collect(),
+                });
+            }
+            None => parity.rust_only_rows.push(rust_row.clone()),
+        }
+    }
+    parity.python_only_rows = python_only.into_iter().flatten().cloned().collect();
+    
+    Ok(parity)
+}
+
+fn columns(database: &DatabaseManager, schema: &str, table: &str) -> Result<Vec<String>, PdwError> {
+    let query = format!("PRAGMA {}.table_info({})", schema, quote_identifier(table));
+    Ok(database.execute_query(&query)?
+        .into_iter()
+        .filter_map(|row| row.get(1).and_then(Value::as_str).map(str::to_string))
+        .collect())
+}
+
+fn normalized_rows(database: &DatabaseManager, query: &str, decimals: usize) -> Result<Vec<Vec<String>>, PdwError> {
+    Ok(database.execute_query(query)?
+        .iter()
+        .map(|row| row.iter().map(|value| normalize(value, decimals)).collect())
+        .collect())
+}
+
+fn equal_cells(left: &[String], right: &[String]) -> usize {
+    left.iter().zip(right).filter(|(l, r)| l == r).count()
+}
+
+/// Representation-independent text of a value: numbers rounded to `decimals`
+/// places and midnight timestamps reduced to their date
+pub fn normalize(value: &Value, decimals: usize) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Number(number) => {
+            let text = format!("{:.*}", decimals, number.as_f64().unwrap_or(0.0));
+            // -0.00 and 0.00 are the same amount
+            if text.trim_start_matches('-').chars().all(|c| c == '0' || c == '.') {
+                text.trim_start_matches('-').to_string()
+            } else {
+                text
+            }
+        }
+        Value::String(text) => {
+            let text = text.trim();
+            match text.strip_suffix(" 00:00:00") {
+                Some(date) if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => date.to_string(),
+                _ => text.to_string(),
+            }
+        }
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+    
+    fn databases(temp_dir: &TempDir, rust_sql: &str, python_sql: &str) -> (DatabaseManager, std::path::PathBuf) {
+        let rust = DatabaseManager::new(&temp_dir.path().join("rust.db")).unwrap();
+        rust.connection().execute_batch(rust_sql).unwrap();
+        let python_path = temp_dir.path().join("python.db");
+        DatabaseManager::new(&python_path).unwrap().connection().execute_batch(python_sql).unwrap();
+        (rust, python_path)
+    }
+    
+    #[test]
+    fn test_normalization() {
+        assert_eq!(normalize(&json!("2024-01-15 00:00:00"), 2), "2024-01-15");
+        assert_eq!(normalize(&json!("2024-01-15 10:30:00"), 2), "2024-01-15 10:30:00");
+        assert_eq!(normalize(&json!(1), 2), "1.00");
+        assert_eq!(normalize(&json!(0.1 + 0.2), 2), "0.30");
+        assert_eq!(normalize(&json!(-0.001), 2), "0.00");
+        assert_eq!(normalize(&Value::Null, 2), "");
+    }
+    
+    #[test]
+    fn test_equivalent_tables_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let (rust, python) = databases(
+            &temp_dir,
+            "CREATE TABLE LANCAMENTOS_GERAIS (Data DATE, TIPO TEXT, Debito REAL, RowHash TEXT);
+             INSERT INTO LANCAMENTOS_GERAIS VALUES ('2024-01-15', 'ALM', 42.5, 'a'), ('2024-01-16', 'LAZ', 10, 'b');
+             CREATE TABLE PDW_META (Namespace TEXT, Chave TEXT, Valor TEXT);
+             CREATE TABLE Consistencia (Tipo TEXT);",
+            "CREATE TABLE LANCAMENTOS_GERAIS (Data TIMESTAMP, TIPO TEXT, Debito REAL);
+             INSERT INTO LANCAMENTOS_GERAIS VALUES ('2024-01-16 00:00:00', 'LAZ', 10.0), ('2024-01-15 00:00:00', 'ALM', 42.5);
+             CREATE TABLE HistoricoGeral_QTD (AnoMes TEXT);",
+        );
+        
+        let report = compare_databases(&rust, &python, 2).unwrap();
+        assert!(report.is_identical());
+        assert_eq!(report.tables[0].rust_only_columns, vec!["RowHash"]);
+        assert_eq!(report.rust_only_tables, vec!["Consistencia"]);
+        assert_eq!(report.python_only_tables, vec!["HistoricoGeral_QTD"]);
+        
+        // The Python database is detached again
+        assert!(rust.execute_query("SELECT * FROM pdw_python.LANCAMENTOS_GERAIS").is_err());
+    }
+    
+    #[test]
+    fn test_row_and_value_differences() {
+        let temp_dir = TempDir::new().unwrap();
+        let (rust, python) = databases(
+            &temp_dir,
+            "CREATE TABLE t (Data TEXT, TIPO TEXT, DESCRICAO TEXT, Debito REAL);
+             INSERT INTO t VALUES ('2024-01-15', 'ALM', 'Mercado', 42.5), ('2024-01-16', 'LAZ', 'Cinema', 30),
+                                  ('2024-01-17', 'SAU', 'Farmacia', 12);",
+            "CREATE TABLE t (Data TEXT, TIPO TEXT, DESCRICAO TEXT, Debito REAL);
+             INSERT INTO t VALUES ('2024-01-15', 'ALM', 'Mercado', 42.51), ('2024-01-16', 'LAZ', 'Cinema', 30),
+                                  ('2023-12-01', 'X', 'Y', 1);",
+        );
+        
+        let report = compare_databases(&rust, &python, 2).unwrap();
+        let table = &report.tables[0];
+        assert!(!report.is_identical());
+        assert_eq!(report.mismatched_tables(), 1);
+        assert_eq!(table.differences, vec![RowDifference {
+            row: vec!["2024-01-15".into(), "ALM".into(), "Mercado".into(), "42.50".into()],
+            cells: vec![CellDifference { column: "Debito".into(), rust: "42.50".into(), python: "42.51".into() }],
+        }]);
+        assert_eq!(table.rust_only_rows.len(), 1);
+        assert_eq!(table.python_only_rows, vec![vec!["2023-12-01", "X", "Y", "1.00"]]);
+        
+        // Compared at one decimal the amounts agree
+        assert_eq!(compare_databases(&rust, &python, 1).unwrap().tables[0].differences.len(), 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,456 @@
+/*!
+# PDF Module
+
+PDF rendering of period-close statements. Once a month is closed (the
+warehouse holds entries of a later month), each origin gets an A4 statement
+for it: account header, opening balance, every entry of the month with its
+running balance, totals and closing balance. Files are written as
+`<Origem>_<AAAA-MM>.pdf` under a subdirectory of dir_out, for record-keeping.
+With `[branding]` on, the title leads every page title and the footer precedes
+the page number.
+Text uses the standard Helvetica and Courier fonts, so no font is embedded.
+*/
+
+use crate::branding::BrandingConfig;
+use crate::database::DatabaseManager;
+use crate::error::{PdwError, ReportError};
+use crate::opening_balances::OpeningBalances;
+use chrono::{Months, NaiveDate};
+use pdf_writer::{Content, Name, Pdf, Rect, Ref, Str, TextStr};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// A4 page size in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+
+/// Page margin in points
+const MARGIN: f32 = 40.0;
+
+/// Entry rows per page
+const ROWS_PER_PAGE: usize = 58;
+
+/// Courier size of the entry rows (each character is 0.6 em wide)
+const ROW_FONT_SIZE: f32 = 8.0;
+const ROW_HEIGHT: f32 = 11.0;
+
+/// Month-close statement settings (`[pdf_statements]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfStatementConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Render this AnoMes ("2024/01") instead of the latest closed months
+    #[serde(default)]
+    pub month: Option<String>,
+    /// Latest closed months rendered on each run
+    #[serde(default = "default_statement_months")]
+    pub months: usize,
+    /// Subdirectory of dir_out receiving the PDF files
+    #[serde(default = "default_statement_directory")]
+    pub directory: String,
+}
+
+/// One entry line of a statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementEntry {
+    pub date: String,
+    pub transaction_type: String,
+    pub description: String,
+    pub credit: f64,
+    pub debit: f64,
+    pub balance: f64,
+}
+
+/// Month statement of one origin
+#[derive(Debug, Clone, PartialEq)]
+pub struct OriginStatement {
+    pub origin: String,
+    pub year_month: String,
+    pub opening_balance: f64,
+    pub entries: Vec<StatementEntry>,
+    pub credits: f64,
+    pub debits: f64,
+}
+
+fn default_statement_months() -> usize {
+    1
+}
+
+fn default_statement_directory() -> String {
+    "statements".to_string()
+}
+
+impl Default for PdfStatementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            month: None,
+            months: default_statement_months(),
+            directory: default_statement_directory(),
+        }
+    }
+}
+
+impl OriginStatement {
+    /// Balance at the end of the month
+    pub fn closing_balance(&self) -> f64 {
+        round_cents(self.opening_balance + self.credits - self.debits)
+    }
+    
+    /// File name of the statement (`Conta_2024-01.pdf`)
+    pub fn file_name(&self) -> String {
+        let origin: String = self.origin.trim()
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        format!("{}_{}.pdf", origin, self.year_month.replace('/', "-"))
+    }
+    
+    /// Render the statement as a PDF document
+    pub fn render(&self, branding: &BrandingConfig) -> Vec<u8> {
+        let mut lines = vec![
+            StatementLine::Text(format!("Saldo anterior: {}", format_amount(self.opening_balance))),
+            StatementLine::Header,
+        ];
+        lines.extend(self.entries.iter().map(|entry| StatementLine::Entry(entry.clone())));
+        lines.push(StatementLine::Rule);
+        lines.push(StatementLine::Text(format!(
+            "{} lançamentos   Créditos: {}   Débitos: {}",
+            self.entries.len(),
+            format_amount(self.credits),
+            format_amount(self.debits),
+        )));
+        lines.push(StatementLine::Text(format!("Saldo final: {}", format_amount(self.closing_balance()))));
+        
+        let pages: Vec<&[StatementLine]> = lines.chunks(ROWS_PER_PAGE).collect();
+        let mut title = format!("Extrato {} - {}", self.origin.trim(), self.year_month);
+        if let Some(brand) = branding.title() {
+            title = format!("{} - {}", brand, title);
+        }
+        let branded_footer = branding.footer_text();
+        
+        let catalog_id = Ref::new(1);
+        let page_tree_id = Ref::new(2);
+        let regular_id = Ref::new(3);
+        let bold_id = Ref::new(4);
+        let mono_id = Ref::new(5);
+        let info_id = Ref::new(6);
+        let page_ids: Vec<Ref> = (0..pages.len()).map(|i| Ref::new(7 + 2 * i as i32)).collect();
+        
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(page_tree_id);
+        pdf.pages(page_tree_id).kids(page_ids.iter().copied()).count(pages.len() as i32);
+        pdf.document_info(info_id).title(TextStr(&title)).producer(TextStr("PDW"));
+        for (id, font) in [(regular_id, "Helvetica"), (bold_id, "Helvetica-Bold"), (mono_id, "Courier")] {
+            pdf.type1_font(id).base_font(Name(font.as_bytes())).encoding_predefined(Name(b"WinAnsiEncoding"));
+        }
+        
+        for (index, (page_lines, page_id)) in pages.iter().zip(&page_ids).enumerate() {
+            let content_id = Ref::new(page_id.get() + 1);
+            let mut page = pdf.page(*page_id);
+            page.parent(page_tree_id)
+                .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .contents(content_id);
+            page.resources().fonts()
+                .pair(Name(b"F1"), regular_id)
+                .pair(Name(b"F2"), bold_id)
+                .pair(Name(b"F3"), mono_id);
+            drop(page);
+            
+            let mut footer = format!("Página {} de {}", index + 1, pages.len());
+            if let Some(text) = &branded_footer {
+                footer = format!("{}   {}", text, footer);
+            }
+            let content = render_page(&title, page_lines, &footer);
+            pdf.stream(content_id, &content);
+        }
+        
+        pdf.finish()
+    }
+}
+
+/// Row of the statement body
+#[derive(Debug, Clone)]
+enum StatementLine {
+    Text(String),
+    Header,
+    Entry(StatementEntry),
+    Rule,
+}
+
+/// AnoMes values before the month of the most recent entry, oldest first
+pub fn closed_months(database: &DatabaseManager, entries_table: &str) -> Result<Vec<String>, PdwError> {
+    let query = format!(
+        "SELECT DISTINCT strftime('%Y/%m', Data) AS Mes FROM {0}
+         WHERE Data IS NOT NULL AND strftime('%Y/%m', Data) < (SELECT strftime('%Y/%m', MAX(Data)) FROM {0})
+         ORDER BY Mes",
+        entries_table
+    );
+    
+    Ok(database.execute_query(&query)?
+        .iter()
+        .filter_map(|row| row.first().and_then(Value::as_str).map(str::to_string))
+        .collect())
+}
+
+/// Statements of every origin with entries dated in a month ("2024/01"), balances shifted by the opening balances
+pub fn build_statements(database: &DatabaseManager, entries_table: &str, year_month: &str,
+                        openings: &OpeningBalances) -> Result<Vec<OriginStatement>, PdwError> {
+    let start = NaiveDate::parse_from_str(&format!("{}/01", year_month), "%Y/%m/%d")
+        .map_err(|e| ReportError::QueryProcessing {
+            query_name: "pdf_statements".to_string(),
+            reason: format!("Invalid month {}: {}", year_month, e),
+        })?;
+    let end = start.checked_add_months(Months::new(1)).unwrap_or(start);
+    let (start, end) = (start.format("%Y-%m-%d").to_string(), end.format("%Y-%m-%d").to_string());
+    
+    let money = database.money_mode();
+    let opening_query = format!(
+        "SELECT COALESCE(Origem, ''), ROUND({} - {}, 2) FROM {} WHERE Data < '{}' GROUP BY COALESCE(Origem, '')",
+        money.sum_sql("Credito", None),
+        money.sum_sql("Debito", None),
+        entries_table,
+        start,
+    );
+    let balances: Vec<(String, f64)> = database.execute_query(&opening_query)?
+        .iter()
+        .map(|row| (text(row, 0), number(row, 1)))
+        .collect();
+    
+    let entries_query = format!(
+        "SELECT COALESCE(Origem, ''), Data, COALESCE(TIPO, ''), COALESCE(DESCRICAO, ''),
+                COALESCE(Credito, 0), COALESCE(Debito, 0)
+         FROM {} WHERE Data >= '{}' AND Data < '{}'
+         ORDER BY {}, Data, rowid",
+        entries_table,
+        start,
+        end,
+        database.order_by("COALESCE(Origem, '')"),
+    );
+    
+    let mut statements: Vec<OriginStatement> = Vec::new();
+    for row in database.execute_query(&entries_query)? {
+        let origin = text(&row, 0);
+        if statements.last().map_or(true, |s| s.origin != origin) {
+            let opening_balance = round_cents(balances.iter()
+                .find(|(name, _)| *name == origin)
+                .map_or(0.0, |(_, balance)| *balance) + openings.offset(&origin));
+            statements.push(OriginStatement {
+                origin: origin.clone(),
+                year_month: year_month.to_string(),
+                opening_balance,
+                entries: Vec::new(),
+                credits: 0.0,
+                debits: 0.0,
+            });
+        }
+        
+        let statement = statements.last_mut().expect("statement pushed above");
+        let (credit, debit) = (number(&row, 4), number(&row, 5));
+        let previous = statement.entries.last().map_or(statement.opening_balance, |e| e.balance);
+        statement.credits = round_cents(statement.credits + credit);
+        statement.debits = round_cents(statement.debits + debit);
+        statement.entries.push(StatementEntry {
+            date: text(&row, 1),
+            transaction_type: text(&row, 2),
+            description: text(&row, 3),
+            credit,
+            debit,
+            balance: round_cents(previous + credit - debit),
+        });
+    }
+    
+    Ok(statements)
+}
+
+/// Write the statements into a directory, returning the files created
+pub fn write_statements(statements: &[OriginStatement], directory: &Path,
+                        branding: &BrandingConfig) -> Result<Vec<PathBuf>, PdwError> {
+    std::fs::create_dir_all(directory)?;
+    
+    statements.iter()
+        .map(|statement| {
+            let path = directory.join(statement.file_name());
+            std::fs::write(&path, statement.render(branding))?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Content stream of one page
+fn render_page(title: &str, lines: &[StatementLine], footer: &str) -> Vec<u8> {
+    let mut content = Content::new();
+    let mut y = PAGE_HEIGHT - MARGIN;
+    
+    text_at(&mut content, b"F2", 14.0, MARGIN, y, title);
+    y -= 24.0;
+    
+    for line in lines {
+        match line {
+            StatementLine::Text(text) => text_at(&mut content, b"F1", 10.0, MARGIN, y, text),
+            StatementLine::Header => text_at(&mut content, b"F3", ROW_FONT_SIZE, MARGIN, y, &format!(
+                "{:<10} {:<12} {:<40} {:>13} {:>13} {:>13}",
+                "Data", "Tipo", "Descrição", "Crédito", "Débito", "Saldo",
+            )),
+            StatementLine::Entry(entry) => text_at(&mut content, b"F3", ROW_FONT_SIZE, MARGIN, y, &format!(
+                "{:<10} {:<12} {:<40} {:>13} {:>13} {:>13}",
+                entry.date,
+                truncate(&entry.transaction_type, 12),
+                truncate(&entry.description, 40),
+                if entry.credit != 0.0 { format_amount(entry.credit) } else { String::new() },
+                if entry.debit != 0.0 { format_amount(entry.debit) } else { String::new() },
+                format_amount(entry.balance),
+            )),
+            StatementLine::Rule => {
+                content.set_line_width(0.5)
+                    .move_to(MARGIN, y + ROW_HEIGHT / 2.0)
+                    .line_to(PAGE_WIDTH - MARGIN, y + ROW_HEIGHT / 2.0)
+                    .stroke();
+            }
+        }
+        y -= ROW_HEIGHT;
+    }
+    
+    text_at(&mut content, b"F1", 8.0, MARGIN, MARGIN / 2.0, footer);
+    content.finish()
+}
+
+/// Show a line of text at a position
+fn text_at(content: &mut Content, font: &[u8], size: f32, x: f32, y: f32, text: &str) {
+    content.begin_text()
+        .set_font(Name(font), size)
+        .next_line(x, y)
+        .show(Str(&win_ansi(text)))
+        .end_text();
+}
+
+/// WinAnsiEncoding bytes of a text; characters outside Latin-1 become '?'
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(u32::from(c)).ok().filter(|b| *b >= 0x20 && !(0x7f..0xa0).contains(b)).unwrap_or(b'?'))
+        .collect()
+}
+
+/// First `width` characters of a text
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Brazilian amount format (1.234,56)
+pub(crate) fn format_amount(value: f64) -> String {
+    let cents = (value.abs() * 100.0).round() as u64;
+    let digits = (cents / 100).to_string();
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push('.');
+        }
+        grouped.push(digit);
+    }
+    
+    let sign = if value < 0.0 && cents > 0 { "-" } else { "" };
+    format!("{}{},{:02}", sign, grouped, cents % 100)
+}
+
+fn text(row: &[Value], index: usize) -> String {
+    row.get(index).and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+fn number(row: &[Value], index: usize) -> f64 {
+    row.get(index).and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn round_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn test_database(temp_dir: &tempfile::TempDir) -> DatabaseManager {
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2023-12-20', 'SAL', 'Salário', 1000, 0, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2024-01-05', 'ALM', 'Mercado', 0, 150.4, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2024-01-10', 'SAL', 'Salário', 2000, 0, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2024-01-12', 'LAZ', 'Cinema', 0, 40, 'Cartão');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2024-02-01', 'ALM', 'Feira', 0, 30, 'Conta');"
+        ).unwrap();
+        db
+    }
+    
+    #[test]
+    fn test_closed_month_statements() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = test_database(&temp_dir);
+        assert_eq!(closed_months(&db, "LANCAMENTOS_GERAIS").unwrap(), vec!["2023/12", "2024/01"]);
+        
+        let statements = build_statements(&db, "LANCAMENTOS_GERAIS", "2024/01", &OpeningBalances::default()).unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].origin, "Cartão");
+        assert_eq!(statements[0].closing_balance(), -40.0);
+        
+        let account = &statements[1];
+        assert_eq!(account.opening_balance, 1000.0);
+        assert_eq!(account.entries.len(), 2);
+        assert_eq!(account.entries[0].balance, 849.6);
+        assert_eq!((account.credits, account.debits), (2000.0, 150.4));
+        assert_eq!(account.closing_balance(), 2849.6);
+        assert_eq!(account.file_name(), "Conta_2024-01.pdf");
+    }
+    
+    #[test]
+    fn test_statement_rendering() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let entries = (1..=100).map(|day| StatementEntry {
+            date: format!("2024-01-{:02}", day % 28 + 1),
+            transaction_type: "ALM".to_string(),
+            description: "Padaria São João".to_string(),
+            credit: 0.0,
+            debit: 1.5,
+            balance: -1.5 * day as f64,
+        }).collect();
+        let statement = OriginStatement {
+            origin: "Conta Corrente".to_string(),
+            year_month: "2024/01".to_string(),
+            opening_balance: 0.0,
+            entries,
+            credits: 0.0,
+            debits: 150.0,
+        };
+        
+        let files = write_statements(std::slice::from_ref(&statement), &temp_dir.path().join("statements"), &BrandingConfig::default()).unwrap();
+        assert!(files[0].ends_with("Conta_Corrente_2024-01.pdf"));
+        
+        let bytes = std::fs::read(&files[0]).unwrap();
+        assert!(bytes.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("/WinAnsiEncoding"));
+        assert!(text.contains("(Extrato Conta Corrente - 2024/01)"));
+        
+        // Branding leads the title and the footer of every page
+        let branding = BrandingConfig {
+            enabled: true,
+            title: Some("Familia Silva".to_string()),
+            footer: "Preparado para o contador".to_string(),
+            ..BrandingConfig::default()
+        };
+        let text = String::from_utf8_lossy(&statement.render(&branding)).to_string();
+        assert!(text.contains("(Familia Silva - Extrato Conta Corrente - 2024/01)"));
+        // Lines with accents are written in hex
+        let footer: String = b"Preparado para o contador   P".iter().map(|b| format!("{:02X}", b)).collect();
+        assert_eq!(text.matches(&format!("<{}", footer)).count(), 2);
+    }
+    
+    #[test]
+    fn test_text_helpers() {
+        assert_eq!(format_amount(1234567.891), "1.234.567,89");
+        assert_eq!(format_amount(-0.5), "-0,50");
+        assert_eq!(format_amount(-0.001), "0,00");
+        assert_eq!(win_ansi("Pão €"), vec![b'P', 0xe3, b'o', b' ', b'?']);
+    }
+}
\ No newline at end of file
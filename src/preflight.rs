@@ -0,0 +1,204 @@
+/*!
+# Preflight Module
+
+Checks behind `--dry-run`, run before anything is loaded and reported all at
+once instead of failing on the first:
+
+- the input opens and its GUIDING sheet reads
+- every sheet GUIDING lists exists in the input
+- loadable accounting sheets start with the expected columns
+  (Data, TIPO, DESCRICAO, Credito, Debito; case and accents ignored)
+- the YAML query file parses (export profiles included)
+- each query's SQL passes EXPLAIN against an empty in-memory warehouse holding
+  the tables, derived columns, summaries and pivots of a run; `writes: true`
+  queries are run there, in order, so later queries see what they create
+
+Tables only written by optional report sections (budgets, forecast, ...) are not
+part of the empty warehouse, so a query reading one is reported as well.
+*/
+
+use crate::collation;
+use crate::config::PdwConfig;
+use crate::consistency::ConsistencyReport;
+use crate::database::DatabaseManager;
+use crate::etl::{self, EtlPipeline};
+use crate::excel::{ExcelReader, SheetConfig};
+use crate::reporting::{self, QueryDefinition};
+
+/// Columns an accounting sheet starts with, in order
+pub const ACCOUNTING_COLUMNS: [&str; 5] = ["Data", "TIPO", "DESCRICAO", "Credito", "Debito"];
+
+/// One problem found, with what was being checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub check: &'static str,
+    pub detail: String,
+}
+
+/// Problems found by the dry run
+#[derive(Debug, Clone, Default)]
+pub struct PreflightReport {
+    pub sheets_checked: usize,
+    pub queries_checked: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl PreflightReport {
+    /// Run every check against the configuration's input and query file
+    pub fn check(config: &PdwConfig) -> Self {
+        let mut report = Self::default();
+        report.check_input(config);
+        report.check_queries(config);
+        report
+    }
+    
+    /// True when nothing was found
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+    
+    /// Print the findings to the console log
+    pub fn log(&self) {
+        for problem in &self.problems {
+            tracing::error!("Dry run: {} -> {}", problem.check, problem.detail);
+        }
+        tracing::info!(
+            "Dry run checked {} sheet(s) and {} query(ies): {} problem(s)",
+            self.sheets_checked, self.queries_checked, self.problems.len()
+        );
+    }
+    
+    fn problem(&mut self, check: &'static str, detail: impl Into<String>) {
+        self.problems.push(Problem { check, detail: detail.into() });
+    }
+    
+    /// Input, GUIDING and the header row of the accounting sheets
+    fn check_input(&mut self, config: &PdwConfig) {
+        let mut input = match etl::open_input(config) {
+            Ok(input) => input,
+            Err(e) => return self.problem("Input", e.to_string()),
+        };
+        let guiding = match input.read_guiding_sheet(&config.settings.guiding_table) {
+            Ok(guiding) => guiding,
+            Err(e) => return self.problem("GUIDING", e.to_string()),
+        };
+        
+        let consistency = ConsistencyReport::check_sheets(&input.sheet_names(), &guiding, &config.settings.guiding_table);
+        for sheet in &consistency.missing_sheets {
+            self.problem("GUIDING", format!("sheet '{}' is listed but missing from the input", sheet));
+        }
+        
+        let accounting = guiding.iter()
+            .filter(|sheet| sheet.is_accounting && sheet.is_loadable)
+            .filter(|sheet| !consistency.missing_sheets.iter().any(|missing| missing == sheet.table_name.trim()));
+        for sheet in accounting {
+            self.sheets_checked += 1;
+            if let Err(detail) = check_columns(input.as_mut(), sheet) {
+                self.problem("Columns", detail);
+            }
+        }
+    }
+    
+    /// Query file, then EXPLAIN of each query against an empty warehouse
+    fn check_queries(&mut self, config: &PdwConfig) {
+        let queries = match reporting::load_query_file(&config.get_yaml_queries_path())
+            .and_then(|queries| queries.with_profiles(&config.export_profiles)) {
+            Ok(queries) => queries,
+            Err(e) => return self.problem("Queries", e.to_string()),
+        };
+        
+        let database = match empty_warehouse(config) {
+            Ok(database) => database,
+            Err(e) => return self.problem("Schema", e.to_string()),
+        };
+        let variables = reporting::query_variables(&config.settings, database.collation().suffix());
+        
+        let gera_hist = queries.queries_gera_hist.iter().filter(|_| config.settings.create_pivot);
+        for query in gera_hist.chain(&queries.queries_padrao) {
+            self.queries_checked += 1;
+            if let Err(reason) = explain(&database, &query.expand(&variables)) {
+                self.problem("SQL", format!("{}: {}", query.sheet_name, reason));
+            }
+        }
+    }
+}
+
+/// Header row of an accounting sheet against the expected columns
+fn check_columns(input: &mut dyn ExcelReader, sheet: &SheetConfig) -> Result<(), String> {
+    let name = sheet.table_name.trim();
+    let rows = input.read_reference_sheet(name).map_err(|e| format!("{}: {}", name, e))?;
+    let header = rows.first().map(Vec::as_slice).unwrap_or_default();
+    
+    let wrong: Vec<String> = ACCOUNTING_COLUMNS.iter().enumerate()
+        .filter(|(index, expected)| header.get(*index)
+            .map_or(true, |found| collation::fold(found.trim()) != collation::fold(expected)))
+        .map(|(index, expected)| format!(
+            "column {} is '{}', expected {}",
+            index + 1, header.get(index).map(|found| found.trim()).unwrap_or_default(), expected
+        ))
+        .collect();
+    if wrong.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{}: {}", name, wrong.join("; ")))
+    }
+}
+
+/// In-memory warehouse with the schema of a run and no entries
+fn empty_warehouse(config: &PdwConfig) -> Result<DatabaseManager, crate::error::PdwError> {
+    let pipeline = EtlPipeline::in_memory(config.clone())?;
+    pipeline.create_empty_schema()?;
+    Ok(pipeline.into_database())
+}
+
+/// EXPLAIN of a read query; a `writes: true` script is run instead
+fn explain(database: &DatabaseManager, query: &QueryDefinition) -> Result<(), String> {
+    if query.writes {
+        return database.connection().execute_batch(&query.sql).map_err(|e| e.to_string());
+    }
+    let sql = query.sql.trim().trim_end_matches(';');
+    let mut statement = database.connection().prepare(&format!("EXPLAIN {}", sql)).map_err(|e| e.to_string())?;
+    statement.query([]).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    
+    #[test]
+    fn test_dry_run_reports_every_problem() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let input = temp_dir.path().join("entrada");
+        fs::create_dir(&input).unwrap();
+        fs::write(input.join("GUIDING.csv"), "TABLE_NAME;ACCOUNTING;LOADABLE\nConta;X;X\nCartão;X;X\nPoupanca;X;X\nTiposLancamentos;;X\n").unwrap();
+        fs::write(input.join("Conta.csv"), "data;Tipo;Descrição;Crédito;Débito\n15/01/2024;ALM;Mercado;;10,00\n").unwrap();
+        fs::write(input.join("Poupanca.csv"), "Data;TIPO;Historico;Valor\n").unwrap();
+        fs::write(input.join("TiposLancamentos.csv"), "Código;Descrição\nALM;Alimentação\n").unwrap();
+        fs::write(temp_dir.path().join("queries.yaml"), "queries_padrao:
+  - sql: CREATE TABLE PREPARADA AS SELECT TIPO, SUM(Debito) AS Total FROM {entries_table} GROUP BY TIPO
+    sheet_name: Preparo
+    writes: true
+  - sql: SELECT TIPO, Total FROM PREPARADA;
+    sheet_name: Preparada
+  - sql: SELECT * FROM {mont_summ}_ANUAL
+    sheet_name: Anual
+  - sql: SELECT Valor FROM {entries_table}
+    sheet_name: Valores
+").unwrap();
+        
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.file_types.type_in = "csv".to_string();
+        config.file_types.input_file = "entrada".to_string();
+        config.settings.yaml_sql_file = "queries.yaml".to_string();
+        
+        let report = PreflightReport::check(&config);
+        assert_eq!((report.sheets_checked, report.queries_checked), (2, 4));
+        let problems: Vec<_> = report.problems.iter().map(|p| (p.check, p.detail.as_str())).collect();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert_eq!(problems[0], ("GUIDING", "sheet 'Cartão' is listed but missing from the input"));
+        assert_eq!(problems[1], ("Columns", "Poupanca: column 3 is 'Historico', expected DESCRICAO; column 4 is 'Valor', expected Credito; column 5 is '', expected Debito"));
+        assert!(problems[2].0 == "SQL" && problems[2].1.starts_with("Valores: no such column: Valor"));
+    }
+}
\ No newline at end of file
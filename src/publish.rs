@@ -0,0 +1,818 @@
+/*!
+# Publish Module
+
+Uploads of the exports in dir_out (`[publish]`, `pdw publish`) to an S3 bucket
+or a WebDAV share, for multi-GB Parquet and CSV files sent over a home uplink:
+
+- files go up in `chunk_size_mb` pieces: an S3 multipart upload, or WebDAV PUTs
+  with a `Content-Range` (servers accepting partial PUTs, such as Apache mod_dav)
+- `max_kbps` caps the upload rate, so the rest of the house keeps its bandwidth
+- a failed piece is retried `retries` times, waiting longer each time; progress
+  is kept in `state_file` (in database_dir), so the next run or `pdw publish`
+  resumes an interrupted upload instead of starting it over
+- the finished upload is checked against the local file: S3 by the SHA-256
+  checksum it computes over the parts, WebDAV by the size and by the
+  `OC-Checksum` the server reports, when it reports one
+
+Files already published and unchanged since (same size and modification time)
+are skipped. `username` and `password` are the access key and secret for S3,
+the user and password for WebDAV; `PDW__PUBLISH__PASSWORD` keeps the secret out
+of the configuration file. Uploads need a build with `--features publish`.
+*/
+
+use crate::error::{ConfigError, PdwError};
+use crate::input_files;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Smallest S3 part, the last one excepted, in MiB
+const MIN_S3_PART_MB: u64 = 5;
+
+/// Longest wait between attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Where uploads go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishTarget {
+    /// S3 or a compatible store (MinIO, Backblaze B2, Cloudflare R2), path-style URL
+    #[default]
+    S3,
+    /// WebDAV collection
+    Webdav,
+}
+
+/// Upload settings (`[publish]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishConfig {
+    /// Upload the exports at the end of every run
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub target: PublishTarget,
+    /// Bucket (with an optional prefix) or collection the files go under,
+    /// e.g. `https://s3.us-east-1.amazonaws.com/my-bucket/pdw`
+    #[serde(default)]
+    pub url: Option<String>,
+    /// S3 region
+    #[serde(default = "default_region")]
+    pub region: String,
+    /// S3 access key or WebDAV user
+    #[serde(default)]
+    pub username: Option<String>,
+    /// S3 secret key or WebDAV password
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Extensions of the dir_out files uploaded
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    /// Size of the pieces, in MiB (5 or more for S3)
+    #[serde(default = "default_chunk_size_mb")]
+    pub chunk_size_mb: u64,
+    /// Upload rate cap in KiB/s, 0 for none
+    #[serde(default)]
+    pub max_kbps: u64,
+    /// Attempts after a piece's first failure
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Upload progress file name, stored in database_dir
+    #[serde(default = "default_state_file")]
+    pub state_file: String,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_extensions() -> Vec<String> {
+    vec!["parquet".to_string(), "csv".to_string()]
+}
+
+fn default_chunk_size_mb() -> u64 {
+    16
+}
+
+fn default_retries() -> u32 {
+    5
+}
+
+fn default_state_file() -> String {
+    "publish_state.json".to_string()
+}
+
+impl Default for PublishConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: PublishTarget::default(),
+            url: None,
+            region: default_region(),
+            username: None,
+            password: None,
+            extensions: default_extensions(),
+            chunk_size_mb: default_chunk_size_mb(),
+            max_kbps: 0,
+            retries: default_retries(),
+            state_file: default_state_file(),
+        }
+    }
+}
+
+impl PublishConfig {
+    /// Check the target settings before any upload
+    pub fn check(&self) -> Result<(), PdwError> {
+        let url = self.url.as_deref().ok_or_else(|| ConfigError::MissingField {
+            field: "publish.url".to_string(),
+        })?;
+        if split_url(url).is_none() {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("publish.url '{}' is not an http(s) URL", url),
+            }.into());
+        }
+        if self.target == PublishTarget::S3 && self.chunk_size_mb < MIN_S3_PART_MB {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("publish.chunk_size_mb must be at least {} for S3", MIN_S3_PART_MB),
+            }.into());
+        }
+        if !cfg!(feature = "publish") {
+            return Err(ConfigError::InvalidFormat {
+                message: "[publish] requires a build with --features publish".to_string(),
+            }.into());
+        }
+        Ok(())
+    }
+    
+    /// Size of the pieces, in bytes
+    pub fn chunk_size(&self) -> u64 {
+        self.chunk_size_mb.max(1) * 1024 * 1024
+    }
+    
+    /// URL a file is uploaded to
+    pub fn remote_url(&self, path: &Path) -> String {
+        let base = self.url.as_deref().unwrap_or_default().trim_end_matches('/');
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        format!("{}/{}", base, uri_encode(&name))
+    }
+}
+
+/// Files of dir_out with one of the configured extensions, sorted by name
+pub fn export_files(dir_out: &Path, config: &PublishConfig) -> std::io::Result<Vec<PathBuf>> {
+    let extensions: Vec<&str> = config.extensions.iter().map(|e| e.trim_start_matches('.')).collect();
+    input_files::list(dir_out, &extensions)
+}
+
+/// Size and modification time of the local file an upload belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub modified: u64,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> Result<Self, PdwError> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        Ok(Self { size: metadata.len(), modified })
+    }
+}
+
+/// Progress of the uploads by remote URL, kept between runs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PublishState {
+    #[serde(default)]
+    pub files: BTreeMap<String, UploadState>,
+}
+
+/// Progress of one file's upload
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UploadState {
+    pub fingerprint: Fingerprint,
+    /// Piece size the upload started with, kept when resuming
+    pub chunk_size: u64,
+    /// S3 multipart upload
+    #[serde(default)]
+    pub upload_id: Option<String>,
+    /// S3 parts stored so far
+    #[serde(default)]
+    pub parts: Vec<UploadedPart>,
+    /// Uploaded and checked against the remote copy
+    #[serde(default)]
+    pub complete: bool,
+}
+
+/// Part of an S3 multipart upload
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UploadedPart {
+    pub number: u32,
+    pub etag: String,
+    /// SHA-256 of the part, base64
+    pub checksum: String,
+}
+
+impl PublishState {
+    /// Load the state file; a missing one means nothing was uploaded yet
+    pub fn load(path: &Path) -> Result<Self, PdwError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| PdwError::Upload(format!("Invalid upload state {}: {}", path.display(), e)))
+    }
+    
+    /// Write the state file, replacing it only once fully written
+    pub fn save(&self, path: &Path) -> Result<(), PdwError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| PdwError::Upload(e.to_string()))?;
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, content)?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+    
+    /// Whether the file, as it is now, was already published
+    pub fn is_published(&self, remote: &str, fingerprint: Fingerprint) -> bool {
+        self.files.get(remote).is_some_and(|upload| upload.complete && upload.fingerprint == fingerprint)
+    }
+    
+    /// Upload to continue for the file as it is now; a changed file starts over
+    pub fn resume(&self, remote: &str, fingerprint: Fingerprint, chunk_size: u64) -> UploadState {
+        self.files.get(remote)
+            .filter(|upload| upload.fingerprint == fingerprint && !upload.complete)
+            .cloned()
+            .unwrap_or(UploadState { fingerprint, chunk_size, upload_id: None, parts: Vec::new(), complete: false })
+    }
+}
+
+/// What a publish did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PublishSummary {
+    pub uploaded: usize,
+    /// Files already published and unchanged
+    pub skipped: usize,
+    /// Bytes sent, resumed pieces excluded
+    pub bytes_sent: u64,
+}
+
+/// Pieces of a file as (offset, length); an empty file is one empty piece
+pub fn pieces(size: u64, chunk_size: u64) -> Vec<(u64, u64)> {
+    let chunk_size = chunk_size.max(1);
+    (0..size.div_ceil(chunk_size).max(1))
+        .map(|index| {
+            let offset = index * chunk_size;
+            (offset, chunk_size.min(size - offset))
+        })
+        .collect()
+}
+
+/// Wait that keeps `sent` bytes, sent over `elapsed`, at the rate cap
+pub fn throttle_pause(sent: u64, elapsed: Duration, bytes_per_second: u64) -> Duration {
+    if bytes_per_second == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(sent as f64 / bytes_per_second as f64).saturating_sub(elapsed)
+}
+
+/// Wait before retry `attempt` (from 1): 2, 4, 8... seconds, at most a minute
+pub fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt.clamp(1, 6)).min(MAX_BACKOFF)
+}
+
+/// Host (with the port) and path of an http(s) URL
+fn split_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"))?;
+    let (host, path) = rest.find('/').map_or((rest, "/"), |slash| (&rest[..slash], &rest[slash..]));
+    (!host.is_empty()).then_some((host, path))
+}
+
+/// Percent-encode all but the unreserved characters (RFC 3986), as S3 signing expects
+fn uri_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Upload the files to the configured target, resuming interrupted uploads
+#[cfg(feature = "publish")]
+pub fn publish(config: &PublishConfig, files: &[PathBuf], state_path: &Path) -> Result<PublishSummary, PdwError> {
+    config.check()?;
+    let mut uploader = upload::Uploader::new(config, state_path)?;
+    let mut summary = PublishSummary::default();
+    
+    for path in files {
+        let remote = config.remote_url(path);
+        let fingerprint = Fingerprint::of(path)?;
+        if uploader.state.is_published(&remote, fingerprint) {
+            tracing::info!("   . .. ... {} unchanged since published - skipped", path.display());
+            summary.skipped += 1;
+            continue;
+        }
+        
+        summary.bytes_sent += match config.target {
+            PublishTarget::S3 => uploader.upload_s3(path, &remote, fingerprint)?,
+            PublishTarget::Webdav => uploader.upload_webdav(path, &remote, fingerprint)?,
+        };
+        summary.uploaded += 1;
+        tracing::info!("   . .. ... {} published to {}", path.display(), remote);
+    }
+    
+    Ok(summary)
+}
+
+#[cfg(not(feature = "publish"))]
+pub fn publish(_config: &PublishConfig, _files: &[PathBuf], _state_path: &Path) -> Result<PublishSummary, PdwError> {
+    Err(PdwError::Upload("Uploads require a build with --features publish".to_string()))
+}
+
+/// AWS Signature Version 4 of a request: the signed header names and the signature.
+/// `headers` are lowercase and sorted by name, host and x-amz-date included
+#[cfg(feature = "publish")]
+pub fn sigv4_signature(secret_key: &str, region: &str, method: &str, canonical_uri: &str, canonical_query: &str,
+                       headers: &[(String, String)], payload_sha256: &str) -> (String, String) {
+    use sha2::{Digest, Sha256};
+    
+    let amz_date = headers.iter().find(|(name, _)| name == "x-amz-date").map_or("", |(_, value)| value.as_str());
+    let day = amz_date.get(..8).unwrap_or_default();
+    let signed_headers = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+    let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method, canonical_uri, canonical_query, canonical_headers, signed_headers, payload_sha256
+    );
+    
+    let scope = format!("{}/{}/s3/aws4_request", day, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date, scope, upload::hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = [day, region, "s3", "aws4_request"].iter()
+        .fold(format!("AWS4{}", secret_key).into_bytes(), |key, part| upload::hmac(&key, part.as_bytes()));
+    (signed_headers, upload::hex(&upload::hmac(&key, string_to_sign.as_bytes())))
+}
+
+#[cfg(feature = "publish")]
+mod upload {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use sha2::{Digest, Sha256};
+    use std::fs::File;
+    use std::io::{Read, Seek, SeekFrom};
+    use std::time::Instant;
+    
+    /// Bytes handed to the connection between rate checks
+    const THROTTLE_SLICE: usize = 16 * 1024;
+    
+    /// Why a request failed: worth another attempt (network, 429, 5xx) or not
+    enum Failure {
+        Transient(String),
+        Fatal(PdwError),
+    }
+    
+    fn failure(what: &str, error: ureq::Error) -> Failure {
+        match error {
+            ureq::Error::Status(code, _) if code == 429 || code >= 500 => Failure::Transient(format!("HTTP {}", code)),
+            ureq::Error::Status(code, response) => Failure::Fatal(PdwError::Upload(format!(
+                "{} rejected with HTTP {}: {}", what, code, response.into_string().unwrap_or_default().trim()
+            ))),
+            ureq::Error::Transport(transport) => Failure::Transient(transport.to_string()),
+        }
+    }
+    
+    /// Run a request until it succeeds, fails for good or runs out of retries
+    fn with_retries<T>(retries: u32, what: &str, mut request: impl FnMut() -> Result<T, Failure>) -> Result<T, PdwError> {
+        let mut attempt = 0;
+        loop {
+            match request() {
+                Ok(value) => return Ok(value),
+                Err(Failure::Fatal(e)) => return Err(e),
+                Err(Failure::Transient(reason)) if attempt < retries => {
+                    attempt += 1;
+                    let pause = backoff(attempt);
+                    tracing::warn!(
+                        "   . .. ... {} failed ({}) - retry {} of {} in {}s",
+                        what, reason, attempt, retries, pause.as_secs()
+                    );
+                    std::thread::sleep(pause);
+                }
+                Err(Failure::Transient(reason)) => {
+                    return Err(PdwError::Upload(format!("{} failed after {} attempts: {}", what, attempt + 1, reason)));
+                }
+            }
+        }
+    }
+    
+    /// Request body of a piece, handed over no faster than the rate cap
+    struct Throttled<'a> {
+        data: &'a [u8],
+        position: usize,
+        bytes_per_second: u64,
+        started: Instant,
+    }
+    
+    impl Read for Throttled<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let end = self.data.len().min(self.position + buf.len().min(THROTTLE_SLICE));
+            let count = end - self.position;
+            buf[..count].copy_from_slice(&self.data[self.position..end]);
+            self.position = end;
+            std::thread::sleep(throttle_pause(self.position as u64, self.started.elapsed(), self.bytes_per_second));
+            Ok(count)
+        }
+    }
+    
+    pub(super) fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+    
+    /// HMAC-SHA256 (RFC 2104)
+    pub(super) fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+        use hmac::Mac;
+        
+        let mut mac = hmac::Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+        mac.update(message);
+        mac.finalize().into_bytes().to_vec()
+    }
+    
+    /// Checksum S3 reports for a multipart upload: the SHA-256 of the parts' SHA-256s, then the part count
+    pub(super) fn composite_checksum(parts: &[UploadedPart]) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(BASE64.decode(&part.checksum).unwrap_or_default());
+        }
+        format!("{}-{}", BASE64.encode(hasher.finalize()), parts.len())
+    }
+    
+    fn read_piece(file: &mut File, offset: u64, length: u64) -> Result<Vec<u8>, PdwError> {
+        let mut data = vec![0; length as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+    
+    fn file_sha256(path: &Path) -> Result<String, PdwError> {
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(hex(&hasher.finalize()))
+    }
+    
+    fn mib(bytes: u64) -> f64 {
+        bytes as f64 / (1024.0 * 1024.0)
+    }
+    
+    /// Uploads of one publish, sharing the connection pool and the state file
+    pub(super) struct Uploader<'a> {
+        config: &'a PublishConfig,
+        state_path: PathBuf,
+        pub(super) state: PublishState,
+        agent: ureq::Agent,
+    }
+    
+    impl<'a> Uploader<'a> {
+        pub(super) fn new(config: &'a PublishConfig, state_path: &Path) -> Result<Self, PdwError> {
+            let agent = ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(30))
+                .timeout_read(Duration::from_secs(120))
+                .timeout_write(Duration::from_secs(120))
+                .user_agent(concat!("pdw/", env!("CARGO_PKG_VERSION")))
+                .build();
+            Ok(Self { config, state_path: state_path.to_path_buf(), state: PublishState::load(state_path)?, agent })
+        }
+        
+        fn record(&mut self, remote: &str, upload: &UploadState) -> Result<(), PdwError> {
+            self.state.files.insert(remote.to_string(), upload.clone());
+            self.state.save(&self.state_path)
+        }
+        
+        fn throttled<'d>(&self, data: &'d [u8]) -> Throttled<'d> {
+            Throttled { data, position: 0, bytes_per_second: self.config.max_kbps * 1024, started: Instant::now() }
+        }
+        
+        /// S3 request to `url` with the query, signed
+        fn s3_request(&self, method: &str, url: &str, query: &[(&str, String)], payload: &[u8],
+                      extra_headers: &[(&str, String)]) -> ureq::Request {
+            let (host, path) = split_url(url).unwrap_or_default();
+            let mut query: Vec<String> = query.iter()
+                .map(|(name, value)| format!("{}={}", uri_encode(name), uri_encode(value)))
+                .collect();
+            query.sort();
+            let query = query.join("&");
+            
+            let payload_sha256 = hex(&Sha256::digest(payload));
+            let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+            let mut headers: Vec<(String, String)> = vec![
+                ("host".to_string(), host.to_string()),
+                ("x-amz-content-sha256".to_string(), payload_sha256.clone()),
+                ("x-amz-date".to_string(), amz_date),
+            ];
+            headers.extend(extra_headers.iter().map(|(name, value)| (name.to_lowercase(), value.clone())));
+            headers.sort();
+            
+            let access_key = self.config.username.as_deref().unwrap_or_default();
+            let secret_key = self.config.password.as_deref().unwrap_or_default();
+            let (signed_headers, signature) = sigv4_signature(
+                secret_key, &self.config.region, method, path, &query, &headers, &payload_sha256
+            );
+            let authorization = format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}/{}/s3/aws4_request, SignedHeaders={}, Signature={}",
+                access_key, headers.iter().find(|(name, _)| name == "x-amz-date").map_or("", |(_, date)| &date[..8]),
+                self.config.region, signed_headers, signature
+            );
+            
+            let full_url = if query.is_empty() { url.to_string() } else { format!("{}?{}", url, query) };
+            headers.iter()
+                .filter(|(name, _)| name != "host")
+                .fold(self.agent.request(method, &full_url), |request, (name, value)| request.set(name, value))
+                .set("Authorization", &authorization)
+        }
+        
+        /// Upload a file as an S3 multipart upload; returns the bytes sent
+        pub(super) fn upload_s3(&mut self, path: &Path, remote: &str, fingerprint: Fingerprint) -> Result<u64, PdwError> {
+            let retries = self.config.retries;
+            let mut upload = self.state.resume(remote, fingerprint, self.config.chunk_size());
+            
+            // An upload of a file changed since is abandoned, so its parts stop being billed
+            if let Some(stale) = self.state.files.get(remote).filter(|previous| previous.fingerprint != fingerprint) {
+                if let Some(upload_id) = stale.upload_id.clone().filter(|_| !stale.complete) {
+                    if let Err(e) = self.s3_request("DELETE", remote, &[("uploadId", upload_id)], b"", &[]).call() {
+                        tracing::warn!("   . .. ... Abandoned upload of {} not aborted: {}", remote, e);
+                    }
+                }
+            }
+            
+            // The store may have expired an upload started long ago
+            if let Some(upload_id) = upload.upload_id.clone() {
+                let exists = with_retries(retries, "Upload lookup", || {
+                    match self.s3_request("GET", remote, &[("max-parts", "1".to_string()), ("uploadId", upload_id.clone())], b"", &[]).call() {
+                        Ok(_) => Ok(true),
+                        Err(ureq::Error::Status(404, _)) => Ok(false),
+                        Err(e) => Err(failure("Upload lookup", e)),
+                    }
+                })?;
+                if exists {
+                    tracing::info!("   . .. ... Resuming {} ({} parts already sent)", path.display(), upload.parts.len());
+                } else {
+                    upload = UploadState { upload_id: None, parts: Vec::new(), ..upload };
+                }
+            }
+            
+            if upload.upload_id.is_none() {
+                let response = with_retries(retries, "Upload start", || {
+                    self.s3_request("POST", remote, &[("uploads", String::new())], b"", &[("x-amz-checksum-algorithm", "SHA256".to_string())])
+                        .call()
+                        .map_err(|e| failure("Upload start", e))?
+                        .into_string()
+                        .map_err(|e| Failure::Transient(e.to_string()))
+                })?;
+                let upload_id = xml_value(&response, "UploadId")
+                    .ok_or_else(|| PdwError::Upload(format!("No UploadId in the response to starting {}", remote)))?;
+                upload.upload_id = Some(upload_id);
+                self.record(remote, &upload)?;
+            }
+            let upload_id = upload.upload_id.clone().unwrap_or_default();
+            
+            let mut file = File::open(path)?;
+            let pieces = pieces(fingerprint.size, upload.chunk_size);
+            let mut sent = 0;
+            for (index, (offset, length)) in pieces.iter().enumerate() {
+                let number = index as u32 + 1;
+                if upload.parts.iter().any(|part| part.number == number) {
+                    continue;
+                }
+                
+                let data = read_piece(&mut file, *offset, *length)?;
+                let checksum = BASE64.encode(Sha256::digest(&data));
+                let what = format!("Part {} of {}", number, path.display());
+                let etag = with_retries(retries, &what, || {
+                    let response = self.s3_request(
+                        "PUT", remote,
+                        &[("partNumber", number.to_string()), ("uploadId", upload_id.clone())],
+                        &data, &[("x-amz-checksum-sha256", checksum.clone())],
+                    )
+                        .set("Content-Length", &data.len().to_string())
+                        .send(self.throttled(&data))
+                        .map_err(|e| failure(&what, e))?;
+                    Ok(response.header("ETag").unwrap_or_default().to_string())
+                })?;
+                
+                upload.parts.push(UploadedPart { number, etag, checksum });
+                self.record(remote, &upload)?;
+                sent += length;
+                tracing::info!(
+                    "   . .. ... {} part {}/{} sent ({:.1} of {:.1} MiB)",
+                    path.display(), number, pieces.len(), mib(offset + length), mib(fingerprint.size)
+                );
+            }
+            
+            upload.parts.sort_by_key(|part| part.number);
+            let body: String = upload.parts.iter()
+                .map(|part| format!(
+                    "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag><ChecksumSHA256>{}</ChecksumSHA256></Part>",
+                    part.number, part.etag, part.checksum
+                ))
+                .collect();
+            let body = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", body);
+            with_retries(retries, "Upload completion", || {
+                let response = self.s3_request("POST", remote, &[("uploadId", upload_id.clone())], body.as_bytes(), &[])
+                    .send_string(&body)
+                    .map_err(|e| failure("Upload completion", e))?
+                    .into_string()
+                    .map_err(|e| Failure::Transient(e.to_string()))?;
+                // S3 reports some completion failures in a 200 response
+                match xml_value(&response, "Code") {
+                    Some(code) if response.contains("<Error>") => Err(Failure::Transient(code)),
+                    _ => Ok(()),
+                }
+            })?;
+            
+            // The stored object must match the file: size and checksum of the parts
+            let expected = composite_checksum(&upload.parts);
+            let (size, checksum) = with_retries(retries, "Upload check", || {
+                let response = self.s3_request("HEAD", remote, &[], b"", &[("x-amz-checksum-mode", "ENABLED".to_string())])
+                    .call()
+                    .map_err(|e| failure("Upload check", e))?;
+                Ok((
+                    response.header("Content-Length").and_then(|length| length.parse::<u64>().ok()),
+                    response.header("x-amz-checksum-sha256").map(str::to_string),
+                ))
+            })?;
+            self.verify(remote, upload, size, checksum.as_deref(), &expected)?;
+            Ok(sent)
+        }
+        
+        /// Upload a file to a WebDAV share with ranged PUTs; returns the bytes sent
+        pub(super) fn upload_webdav(&mut self, path: &Path, remote: &str, fingerprint: Fingerprint) -> Result<u64, PdwError> {
+            let retries = self.config.retries;
+            let upload = self.state.resume(remote, fingerprint, self.config.chunk_size());
+            let resuming = self.state.files.get(remote).is_some_and(|previous| previous == &upload);
+            let sha256 = file_sha256(path)?;
+            
+            // The remote copy's size says how much of an interrupted upload arrived
+            let remote_size = with_retries(retries, "Upload lookup", || self.webdav_size(remote))?;
+            let start = match remote_size {
+                Some(size) if resuming && size <= fingerprint.size => size,
+                _ => 0,
+            };
+            if start > 0 {
+                tracing::info!("   . .. ... Resuming {} at {:.1} MiB", path.display(), mib(start));
+            }
+            self.record(remote, &upload)?;
+            
+            let mut file = File::open(path)?;
+            let mut sent = 0;
+            // An empty file is one empty PUT; nothing is left of a fully sent one
+            for (offset, length) in pieces(fingerprint.size - start, upload.chunk_size).into_iter().filter(|(_, length)| *length > 0 || start == 0) {
+                let offset = start + offset;
+                let data = read_piece(&mut file, offset, length)?;
+                let last = offset + length == fingerprint.size;
+                let what = format!("Piece at {:.1} MiB of {}", mib(offset), path.display());
+                with_retries(retries, &what, || {
+                    let mut request = self.webdav_request("PUT", remote).set("Content-Length", &data.len().to_string());
+                    // The first piece creates (or truncates) the file, the others extend it
+                    if offset > 0 {
+                        request = request.set("Content-Range", &format!("bytes {}-{}/{}", offset, offset + length - 1, fingerprint.size));
+                    }
+                    if last {
+                        request = request.set("OC-Checksum", &format!("SHA256:{}", sha256));
+                    }
+                    request.send(self.throttled(&data)).map_err(|e| failure(&what, e))?;
+                    Ok(())
+                })?;
+                sent += length;
+                tracing::info!("   . .. ... {} sent {:.1} of {:.1} MiB", path.display(), mib(offset + length), mib(fingerprint.size));
+            }
+            
+            let (size, checksum) = with_retries(retries, "Upload check", || {
+                let response = self.webdav_request("HEAD", remote).call().map_err(|e| failure("Upload check", e))?;
+                Ok((
+                    response.header("Content-Length").and_then(|length| length.parse::<u64>().ok()),
+                    response.header("OC-Checksum")
+                        .and_then(|checksums| checksums.split_whitespace()
+                            .find_map(|checksum| checksum.strip_prefix("SHA256:").map(str::to_lowercase))),
+                ))
+            })?;
+            self.verify(remote, upload, size, checksum.as_deref(), &sha256)?;
+            Ok(sent)
+        }
+        
+        fn webdav_request(&self, method: &str, url: &str) -> ureq::Request {
+            let request = self.agent.request(method, url);
+            match &self.config.username {
+                Some(user) => {
+                    let credentials = format!("{}:{}", user, self.config.password.as_deref().unwrap_or_default());
+                    request.set("Authorization", &format!("Basic {}", BASE64.encode(credentials)))
+                }
+                None => request,
+            }
+        }
+        
+        /// Size of the remote copy, None when there is none
+        fn webdav_size(&self, url: &str) -> Result<Option<u64>, Failure> {
+            match self.webdav_request("HEAD", url).call() {
+                Ok(response) => Ok(response.header("Content-Length").and_then(|length| length.parse().ok())),
+                Err(ureq::Error::Status(404, _)) => Ok(None),
+                Err(e) => Err(failure("Upload lookup", e)),
+            }
+        }
+        
+        /// Mark an upload complete once the remote copy matches; a mismatch starts it over next time
+        fn verify(&mut self, remote: &str, mut upload: UploadState, size: Option<u64>,
+                  checksum: Option<&str>, expected: &str) -> Result<(), PdwError> {
+            let mismatch = if size != Some(upload.fingerprint.size) {
+                Some(format!("size {:?} instead of {}", size, upload.fingerprint.size))
+            } else {
+                checksum.filter(|checksum| *checksum != expected)
+                    .map(|checksum| format!("checksum {} instead of {}", checksum, expected))
+            };
+            if let Some(mismatch) = mismatch {
+                self.state.files.remove(remote);
+                self.state.save(&self.state_path)?;
+                return Err(PdwError::Upload(format!("Remote copy {} does not match the file: {}", remote, mismatch)));
+            }
+            if checksum.is_none() {
+                tracing::warn!("   . .. ... {} reports no checksum - only its size was checked", remote);
+            }
+            
+            upload.complete = true;
+            self.record(remote, &upload)
+        }
+    }
+    
+    /// Text of the first `<tag>` element of an XML response
+    fn xml_value(xml: &str, tag: &str) -> Option<String> {
+        let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+        let end = start + xml[start..].find(&format!("</{}>", tag))?;
+        Some(xml[start..end].to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_upload_planning_and_resume_state() {
+        assert_eq!(pieces(10, 4), vec![(0, 4), (4, 4), (8, 2)]);
+        assert_eq!(pieces(8, 4), vec![(0, 4), (4, 4)]);
+        assert_eq!(pieces(0, 4), vec![(0, 0)]);
+        
+        // 1 MiB at 256 KiB/s takes 4s: a second in, three are left to wait
+        assert_eq!(throttle_pause(1 << 20, Duration::from_secs(1), 256 * 1024), Duration::from_secs(3));
+        assert_eq!(throttle_pause(1 << 20, Duration::from_secs(5), 256 * 1024), Duration::ZERO);
+        assert_eq!(throttle_pause(1 << 20, Duration::ZERO, 0), Duration::ZERO);
+        assert_eq!((1..=8).map(|attempt| backoff(attempt).as_secs()).collect::<Vec<_>>(), vec![2, 4, 8, 16, 32, 60, 60, 60]);
+        
+        let mut config = PublishConfig { url: Some("https://s3.example.com/bucket/pdw/".to_string()), ..Default::default() };
+        assert_eq!(config.remote_url(Path::new("out/LANCAMENTOS GERAIS.v2.parquet")), "https://s3.example.com/bucket/pdw/LANCAMENTOS%20GERAIS.v2.parquet");
+        config.chunk_size_mb = 4;
+        assert!(config.check().is_err());
+        config.target = PublishTarget::Webdav;
+        assert_eq!(config.check().is_ok(), cfg!(feature = "publish"));
+        assert!(PublishConfig { url: Some("s3://bucket".to_string()), ..config.clone() }.check().is_err());
+        
+        // Progress survives a restart and is dropped once the file changes
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_path = temp_dir.path().join("publish_state.json");
+        let fingerprint = Fingerprint { size: 10, modified: 1_700_000_000 };
+        let mut state = PublishState::load(&state_path).unwrap();
+        let mut upload = state.resume("https://x/a.csv", fingerprint, 4);
+        upload.upload_id = Some("id-1".to_string());
+        upload.parts.push(UploadedPart { number: 1, etag: "\"e1\"".to_string(), checksum: "c1".to_string() });
+        state.files.insert("https://x/a.csv".to_string(), upload.clone());
+        state.save(&state_path).unwrap();
+        
+        let state = PublishState::load(&state_path).unwrap();
+        assert_eq!(state.resume("https://x/a.csv", fingerprint, 8), upload);
+        assert!(!state.is_published("https://x/a.csv", fingerprint));
+        let changed = Fingerprint { modified: 1_700_000_060, ..fingerprint };
+        assert!(state.resume("https://x/a.csv", changed, 8).parts.is_empty());
+        
+        #[cfg(feature = "publish")]
+        {
+            // AWS Signature Version 4 example: GET Object with a Range header
+            let headers: Vec<(String, String)> = [
+                ("host", "examplebucket.s3.amazonaws.com"),
+                ("range", "bytes=0-9"),
+                ("x-amz-content-sha256", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+                ("x-amz-date", "20130524T000000Z"),
+            ].iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+            let (signed_headers, signature) = sigv4_signature(
+                "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY", "us-east-1", "GET", "/test.txt", "",
+                &headers, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            );
+            assert_eq!(signed_headers, "host;range;x-amz-content-sha256;x-amz-date");
+            assert_eq!(signature, "f0e8bdb87c964420e857bd35b5d6ed310bd44f0170aba48dd91039c6036bdb41");
+        }
+    }
+}
\ No newline at end of file
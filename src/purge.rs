@@ -0,0 +1,311 @@
+/*!
+# Purge Module
+
+Controlled deletion of entries for data retention (`pdw purge --before
+2005-01-01 --types Saude`): the general entries dated before a day and/or of
+some TIPOs are deleted, then the pivot and summary tables are rebuilt from what
+is left. With `--dry-run` only the matching rows are counted. A purge cannot be
+undone unless it is run with `--to-trash`, which moves the entries to the trash
+instead (see the trash module) until they are restored or the trash is emptied.
+
+A purge that deletes also removes the matching rows from the tables keeping
+copies of entries (the discarded and transient data tables), rebuilds the tables
+derived from them (fees, duplicates, budgets, tags) and rewrites the database
+file with `secure_delete` on and a VACUUM, so the deleted rows cannot be read
+back from free pages or the WAL.
+
+Every purge is recorded in the PDW_PURGES table (when, filter, rows and amounts
+removed, and whether they went to the trash in `Lixeira`), and the recorded
+purges not restored from the trash are applied again after each load, so
+reloading a workbook that still holds the rows does not bring them back.
+*/
+
+use crate::config::SettingsConfig;
+use crate::database::DatabaseManager;
+use crate::deterministic;
+use crate::error::{ConfigError, DatabaseError, PdwError};
+use crate::trash;
+use chrono::NaiveDate;
+use rusqlite::types::Value as SqlValue;
+use serde_json::Value;
+
+/// Table the purges are recorded in
+pub const PURGES_TABLE: &str = "PDW_PURGES";
+
+/// Entries to delete: all conditions given must match
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PurgeFilter {
+    /// Entries dated before this day
+    pub before: Option<NaiveDate>,
+    /// Entries of these TIPOs (case and surrounding spaces ignored)
+    pub types: Vec<String>,
+}
+
+/// Entries matched by a filter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PurgeSummary {
+    pub rows: usize,
+    pub first_date: Option<String>,
+    pub last_date: Option<String>,
+    pub credit: f64,
+    pub debit: f64,
+}
+
+impl PurgeFilter {
+    /// Filter of the given conditions; at least one is required
+    pub fn new(before: Option<NaiveDate>, types: &[String]) -> Result<Self, PdwError> {
+        let types: Vec<String> = types.iter()
+            .map(|tipo| tipo.trim().to_uppercase())
+            .filter(|tipo| !tipo.is_empty())
+            .collect();
+        if before.is_none() && types.is_empty() {
+            return Err(ConfigError::InvalidFormat {
+                message: "Purge needs --before and/or --types".to_string(),
+            }.into());
+        }
+        Ok(Self { before, types })
+    }
+    
+    /// WHERE condition and its parameters
+    fn condition(&self) -> (String, Vec<SqlValue>) {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        if let Some(before) = self.before {
+            conditions.push("Data < ?".to_string());
+            params.push(SqlValue::Text(before.format("%Y-%m-%d").to_string()));
+        }
+        if !self.types.is_empty() {
+            conditions.push(format!("UPPER(TRIM(TIPO)) IN ({})", vec!["?"; self.types.len()].join(", ")));
+            params.extend(self.types.iter().cloned().map(SqlValue::Text));
+        }
+        (conditions.join(" AND "), params)
+    }
+}
+
+impl std::fmt::Display for PurgeFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(before) = self.before {
+            parts.push(format!("before {}", before));
+        }
+        if !self.types.is_empty() {
+            parts.push(format!("TIPO in {}", self.types.join(", ")));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Count the entries a purge would delete
+pub fn preview(database: &DatabaseManager, entries_table: &str, filter: &PurgeFilter) -> Result<PurgeSummary, PdwError> {
+    let (condition, params) = filter.condition();
+    let query = format!(
+        "SELECT COUNT(*), MIN(Data), MAX(Data), COALESCE(SUM(Credito), 0), COALESCE(SUM(Debito), 0) FROM {} WHERE {}",
+        entries_table, condition
+    );
+    database.connection().query_row(&query, rusqlite::params_from_iter(params), |row| {
+        Ok(PurgeSummary {
+            rows: row.get::<_, i64>(0)? as usize,
+            first_date: row.get(1)?,
+            last_date: row.get(2)?,
+            credit: row.get(3)?,
+            debit: row.get(4)?,
+        })
+    }).map_err(|e| DatabaseError::SqlExecution { query, reason: e.to_string() }.into())
+}
+
+/// Delete the matching entries (or, with `to_trash`, move them to the trash) and record
+/// the purge, in one transaction
+pub fn purge(database: &DatabaseManager, entries_table: &str, filter: &PurgeFilter, to_trash: bool) -> Result<PurgeSummary, PdwError> {
+    let summary = preview(database, entries_table, filter)?;
+    if !to_trash {
+        secure_delete(database)?;
+    }
+    
+    database.connection().execute_batch("BEGIN")
+        .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+    let result = record(database, filter, &summary, to_trash)
+        .and_then(|purge_id| remove_matching(database, entries_table, filter, purge_id, to_trash));
+    match result {
+        Ok(_) => database.connection().execute_batch("COMMIT")
+            .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?,
+        Err(e) => {
+            let _ = database.connection().execute_batch("ROLLBACK");
+            return Err(e);
+        }
+    }
+    
+    if to_trash {
+        tracing::info!("Moved {} entries to {} ({})", summary.rows, trash::TRASH_TABLE, filter);
+    } else {
+        tracing::info!("Purged {} entries ({})", summary.rows, filter);
+    }
+    Ok(summary)
+}
+
+/// Tables keeping copies of entries, cleaned along with the general entries
+pub fn copy_tables(settings: &SettingsConfig) -> Vec<&str> {
+    std::iter::once(settings.discarted_data_table.as_str())
+        .chain(settings.transient_data_table.as_deref())
+        .collect()
+}
+
+/// Delete the rows matching `filter` from `tables`; missing tables and those without the
+/// filter's Data or TIPO column are skipped. Returns the rows deleted
+pub fn purge_copies(database: &DatabaseManager, tables: &[&str], filter: &PurgeFilter) -> Result<usize, PdwError> {
+    let (condition, params) = filter.condition();
+    let mut deleted = 0;
+    for table in tables {
+        let columns = database.table_columns(table)?;
+        let has = |name: &str| columns.iter().any(|column| column.eq_ignore_ascii_case(name));
+        if columns.is_empty() || (filter.before.is_some() && !has("Data")) || (!filter.types.is_empty() && !has("TIPO")) {
+            continue;
+        }
+        let query = format!("DELETE FROM {} WHERE {}", table, condition);
+        deleted += database.connection().execute(&query, rusqlite::params_from_iter(params.iter().cloned()))
+            .map_err(|e| DatabaseError::SqlExecution { query, reason: e.to_string() })?;
+    }
+    Ok(deleted)
+}
+
+/// Have SQLite overwrite the content of the rows deleted from now on
+pub(crate) fn secure_delete(database: &DatabaseManager) -> Result<(), PdwError> {
+    database.connection().pragma_update(None, "secure_delete", true)
+        .map_err(|e| DatabaseError::SqlExecution { query: "PRAGMA secure_delete".to_string(), reason: e.to_string() }.into())
+}
+
+/// Rewrite the database file without its free pages and empty the WAL, so deleted rows
+/// cannot be read back from the file
+pub fn scrub(database: &DatabaseManager) -> Result<(), PdwError> {
+    for statement in ["VACUUM", "PRAGMA wal_checkpoint(TRUNCATE)"] {
+        database.connection().execute_batch(statement)
+            .map_err(|e| DatabaseError::SqlExecution { query: statement.to_string(), reason: e.to_string() })?;
+    }
+    Ok(())
+}
+
+/// Apply the recorded purges not restored again (after a load), the deleting ones to the
+/// `copies` tables too; returns the entries removed
+pub fn reapply(database: &DatabaseManager, entries_table: &str, copies: &[&str]) -> Result<usize, PdwError> {
+    if database.table_columns(PURGES_TABLE)?.is_empty() {
+        return Ok(0);
+    }
+    ensure_table(database)?;
+    
+    let mut removed = 0;
+    let query = format!("SELECT Id, Antes, Tipos, Lixeira FROM {} WHERE Restaurado IS NULL ORDER BY Id", PURGES_TABLE);
+    for row in database.execute_query(&query)? {
+        let purge_id = row.first().and_then(Value::as_i64).unwrap_or_default();
+        let before = row.get(1).and_then(Value::as_str)
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+        let types: Vec<String> = row.get(2).and_then(Value::as_str)
+            .map(|types| types.split(',').map(str::to_string).collect())
+            .unwrap_or_default();
+        let to_trash = row.get(3).and_then(Value::as_i64) == Some(1);
+        if let Ok(filter) = PurgeFilter::new(before, &types) {
+            removed += remove_matching(database, entries_table, &filter, purge_id, to_trash)?;
+            if !to_trash {
+                purge_copies(database, copies, &filter)?;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+fn remove_matching(database: &DatabaseManager, entries_table: &str, filter: &PurgeFilter,
+                   purge_id: i64, to_trash: bool) -> Result<usize, PdwError> {
+    let (condition, params) = filter.condition();
+    if to_trash {
+        return trash::move_entries(database, entries_table, &condition, params, purge_id);
+    }
+    let query = format!("DELETE FROM {} WHERE {}", entries_table, condition);
+    database.connection().execute(&query, rusqlite::params_from_iter(params))
+        .map_err(|e| DatabaseError::SqlExecution { query, reason: e.to_string() }.into())
+}
+
+/// Purges table, with the Restaurado and Lixeira columns on tables created before the trash
+fn ensure_table(database: &DatabaseManager) -> Result<(), PdwError> {
+    let create_query = format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            Id INTEGER PRIMARY KEY AUTOINCREMENT,
+            Quando TEXT NOT NULL,
+            Antes TEXT,
+            Tipos TEXT,
+            Linhas INTEGER,
+            Credito REAL,
+            Debito REAL,
+            Restaurado TEXT,
+            Lixeira INTEGER
+        )",
+        PURGES_TABLE
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution { query: create_query, reason: e.to_string() })?;
+    database.add_derived_columns(PURGES_TABLE, &[("Restaurado".to_string(), "TEXT"), ("Lixeira".to_string(), "INTEGER")])
+}
+
+/// Audit row of a purge; returns its Id
+fn record(database: &DatabaseManager, filter: &PurgeFilter, summary: &PurgeSummary, to_trash: bool) -> Result<i64, PdwError> {
+    ensure_table(database)?;
+    
+    let insert_query = format!(
+        "INSERT INTO {} (Quando, Antes, Tipos, Linhas, Credito, Debito, Lixeira) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        PURGES_TABLE
+    );
+    database.connection().execute(&insert_query, rusqlite::params![
+        deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        filter.before.map(|before| before.format("%Y-%m-%d").to_string()),
+        (!filter.types.is_empty()).then(|| filter.types.join(",")),
+        summary.rows as i64,
+        summary.credit,
+        summary.debit,
+        to_trash as i64,
+    ]).map_err(|e| DatabaseError::DataInsertion {
+        table: PURGES_TABLE.to_string(),
+        reason: e.to_string(),
+    })?;
+    Ok(database.connection().last_insert_rowid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_purge_preview_audit_and_reapply() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        let entries = "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito) VALUES ('2004-03-01', 'Saude ', 0, 80);
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito) VALUES ('2004-05-01', 'LAZER', 0, 20);
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito) VALUES ('2006-01-01', 'SAUDE', 0, 50);";
+        db.connection().execute_batch(entries).unwrap();
+        
+        assert!(PurgeFilter::new(None, &[" ".to_string()]).is_err());
+        let before = NaiveDate::from_ymd_opt(2005, 1, 1);
+        let filter = PurgeFilter::new(before, &["saude".to_string()]).unwrap();
+        assert_eq!(filter.to_string(), "before 2005-01-01, TIPO in SAUDE");
+        
+        let summary = preview(&db, "LANCAMENTOS_GERAIS", &filter).unwrap();
+        assert_eq!((summary.rows, summary.debit, summary.first_date.as_deref()), (1, 80.0, Some("2004-03-01")));
+        assert!(db.table_columns(PURGES_TABLE).unwrap().is_empty());
+        
+        assert_eq!(purge(&db, "LANCAMENTOS_GERAIS", &filter, false).unwrap(), summary);
+        let left = db.execute_query("SELECT Data FROM LANCAMENTOS_GERAIS ORDER BY Data").unwrap();
+        assert_eq!(left, vec![vec![json!("2004-05-01")], vec![json!("2006-01-01")]]);
+        let audit = db.execute_query("SELECT Antes, Tipos, Linhas, Debito FROM PDW_PURGES").unwrap();
+        assert_eq!(audit, vec![vec![json!("2005-01-01"), json!("SAUDE"), json!(1), json!(80.0)]]);
+        // Nothing is kept to restore
+        assert_eq!(trash::list(&db, &[]).unwrap(), (Vec::new(), Vec::new()));
+        
+        // A reload brings the row back; the recorded purge removes it again, copies included
+        db.connection().execute_batch(entries).unwrap();
+        db.connection().execute_batch("CREATE TABLE discarted_data AS SELECT Data, TIPO FROM LANCAMENTOS_GERAIS;
+             CREATE TABLE PARCELAS (AnoMes TEXT, TIPO TEXT)").unwrap();
+        assert_eq!(reapply(&db, "LANCAMENTOS_GERAIS", &["discarted_data", "PARCELAS", "MISSING"]).unwrap(), 1);
+        assert_eq!(db.execute_query("SELECT COUNT(*) FROM LANCAMENTOS_GERAIS").unwrap(), vec![vec![json!(4)]]);
+        assert_eq!(db.execute_query("SELECT COUNT(*) FROM discarted_data").unwrap(), vec![vec![json!(4)]]);
+        scrub(&db).unwrap();
+        assert_eq!(db.execute_query("PRAGMA secure_delete").unwrap(), vec![vec![json!(1)]]);
+    }
+}
\ No newline at end of file
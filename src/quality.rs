@@ -0,0 +1,346 @@
+/*!
+# Data Quality Module
+
+Counts the entries that break configurable rules instead of dropping them
+silently: rows without a date or TIPO (which the load skips), TIPO codes
+missing from TiposLancamentos, negative credits, dates in the future and
+repeated rows. Row-level rules run on the entries as read from each sheet,
+table-level rules (unknown TIPO, duplicates) on the loaded general entries.
+Violations are counted per rule and origin sheet, logged, and stored in the
+DATA_QUALITY table that the reporting phase exports as the "Qualidade" sheet.
+*/
+
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, PdwError};
+use crate::excel::Transaction;
+use crate::money::Decimal;
+use crate::quarantine::{Quarantine, RejectReason};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+/// Data quality check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityRule {
+    /// Entry without a date (skipped by the load)
+    NullDate,
+    /// Dated entry without a TIPO, not filled by categorization (skipped by the load)
+    MissingTipo,
+    /// TIPO that is neither a code nor a description of TiposLancamentos
+    UnknownTipo,
+    /// Credit typed as a negative amount
+    NegativeCredit,
+    /// Date after today, plus the configured tolerance
+    FutureDate,
+    /// Same date, TIPO, description, amounts and origin as an earlier entry
+    Duplicate,
+}
+
+impl QualityRule {
+    /// Every rule, in report order
+    pub const ALL: [QualityRule; 6] = [
+        QualityRule::NullDate,
+        QualityRule::MissingTipo,
+        QualityRule::UnknownTipo,
+        QualityRule::NegativeCredit,
+        QualityRule::FutureDate,
+        QualityRule::Duplicate,
+    ];
+    
+    /// Rule name stored in the report table
+    pub fn name(&self) -> &'static str {
+        match self {
+            QualityRule::NullDate => "null_date",
+            QualityRule::MissingTipo => "missing_tipo",
+            QualityRule::UnknownTipo => "unknown_tipo",
+            QualityRule::NegativeCredit => "negative_credit",
+            QualityRule::FutureDate => "future_date",
+            QualityRule::Duplicate => "duplicate",
+        }
+    }
+    
+    /// Description shown next to the counts
+    pub fn label(&self) -> &'static str {
+        match self {
+            QualityRule::NullDate => "Lancamento sem data (descartado)",
+            QualityRule::MissingTipo => "Lancamento sem TIPO (descartado)",
+            QualityRule::UnknownTipo => "TIPO fora de TiposLancamentos",
+            QualityRule::NegativeCredit => "Credito negativo",
+            QualityRule::FutureDate => "Data no futuro",
+            QualityRule::Duplicate => "Lancamento repetido",
+        }
+    }
+}
+
+/// Data quality settings (`[quality]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Rules to check
+    #[serde(default = "default_rules")]
+    pub rules: Vec<QualityRule>,
+    /// Days after today still accepted by `future_date` (scheduled entries)
+    #[serde(default)]
+    pub future_days: i64,
+    #[serde(default = "default_quality_table")]
+    pub table: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_rules() -> Vec<QualityRule> {
+    QualityRule::ALL.to_vec()
+}
+
+fn default_quality_table() -> String {
+    "DATA_QUALITY".to_string()
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rules: default_rules(),
+            future_days: 0,
+            table: default_quality_table(),
+        }
+    }
+}
+
+impl QualityConfig {
+    /// Whether a rule is checked
+    pub fn checks(&self, rule: QualityRule) -> bool {
+        self.enabled && self.rules.contains(&rule)
+    }
+}
+
+/// Violation counts by rule and origin
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QualityReport {
+    violations: BTreeMap<(QualityRule, String), usize>,
+}
+
+impl QualityReport {
+    /// Check one entry as read from its sheet; `typed` tells whether it ends up with a TIPO
+    pub fn check_entry(&mut self, config: &QualityConfig, entry: &Transaction, typed: bool, today: NaiveDate) {
+        let Some(date) = entry.date else {
+            self.add(config, QualityRule::NullDate, &entry.origin, 1);
+            return;
+        };
+        
+        if !typed {
+            self.add(config, QualityRule::MissingTipo, &entry.origin, 1);
+        }
+        if entry.credit.is_some_and(|credit| credit < Decimal::ZERO) {
+            self.add(config, QualityRule::NegativeCredit, &entry.origin, 1);
+        }
+        if (date - today).num_days() > config.future_days {
+            self.add(config, QualityRule::FutureDate, &entry.origin, 1);
+        }
+    }
+    
+    /// Check the loaded entries: TIPO codes against the types table and repeated rows
+    pub fn check_database(&mut self, config: &QualityConfig, database: &DatabaseManager,
+                          entries_table: &str, types_table: &str) -> Result<(), PdwError> {
+        if config.checks(QualityRule::UnknownTipo) && !database.table_columns(types_table)?.is_empty() {
+            let known: HashSet<String> = database.execute_query(&format!("SELECT * FROM {}", types_table))?
+                .iter()
+                .flat_map(|row| row.iter().take(2).filter_map(Value::as_str).map(|s| s.trim().to_uppercase()))
+                .collect();
+            
+            let used = database.execute_query(&format!(
+                "SELECT COALESCE(Origem, ''), TIPO, COUNT(*) FROM {} WHERE TIPO IS NOT NULL GROUP BY Origem, TIPO",
+                entries_table
+            ))?;
+            for row in used {
+                let tipo = row.get(1).and_then(Value::as_str).unwrap_or_default().trim().to_uppercase();
+                if !known.contains(&tipo) {
+                    self.add(config, QualityRule::UnknownTipo, &text(row.first()), count(row.get(2)));
+                }
+            }
+        }
+        
+        if config.checks(QualityRule::Duplicate) {
+            let repeated = database.execute_query(&format!(
+                "SELECT Origem, SUM(Vezes - 1) FROM (
+                     SELECT COALESCE(Origem, '') as Origem, COUNT(*) as Vezes FROM {}
+                     GROUP BY Data, TIPO, DESCRICAO, Credito, Debito, Origem HAVING COUNT(*) > 1
+                 ) GROUP BY Origem",
+                entries_table
+            ))?;
+            for row in repeated {
+                self.add(config, QualityRule::Duplicate, &text(row.first()), count(row.get(1)));
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Count the entries of unknown TIPO the load moved to the discarded table
+    pub fn count_rejected(&mut self, config: &QualityConfig, quarantine: &Quarantine) {
+        for (reason, origin, rejected) in quarantine.counts() {
+            if reason == RejectReason::UnknownTipo {
+                self.add(config, QualityRule::UnknownTipo, origin, rejected);
+            }
+        }
+    }
+    
+    /// Add the counts of another report (a sheet read by a worker thread)
+    pub fn merge(&mut self, other: QualityReport) {
+        for (key, violations) in other.violations {
+            *self.violations.entry(key).or_default() += violations;
+        }
+    }
+    
+    /// Violations of a rule in an origin
+    pub fn count(&self, rule: QualityRule, origin: &str) -> usize {
+        self.violations.get(&(rule, origin.to_string())).copied().unwrap_or(0)
+    }
+    
+    /// Violations of every rule and origin
+    pub fn total(&self) -> usize {
+        self.violations.values().sum()
+    }
+    
+    /// Print the counts to the console log
+    pub fn log(&self) {
+        if self.violations.is_empty() {
+            tracing::info!("Data quality: no violations");
+            return;
+        }
+        
+        tracing::warn!("Data quality: {} violations", self.total());
+        for ((rule, origin), violations) in &self.violations {
+            tracing::warn!("   . .. ... {} - {}: {}", rule.label(), origin, violations);
+        }
+    }
+    
+    /// Store the counts, replacing the previous run's table
+    pub fn write_table(&self, database: &DatabaseManager, table: &str) -> Result<usize, PdwError> {
+        database.drop_table(table)?;
+        
+        let create_query = format!(
+            "CREATE TABLE {} (Regra TEXT, Descricao TEXT, Origem TEXT, Violacoes INTEGER)",
+            table
+        );
+        database.connection().execute(&create_query, [])
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: create_query.clone(),
+                reason: e.to_string(),
+            })?;
+        
+        let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4)", table);
+        for ((rule, origin), violations) in &self.violations {
+            database.connection().execute(&insert_query, rusqlite::params![
+                rule.name(),
+                rule.label(),
+                origin,
+                *violations as i64,
+            ]).map_err(|e| DatabaseError::DataInsertion {
+                table: table.to_string(),
+                reason: e.to_string(),
+            })?;
+        }
+        
+        Ok(self.violations.len())
+    }
+    
+    fn add(&mut self, config: &QualityConfig, rule: QualityRule, origin: &str, violations: usize) {
+        if config.checks(rule) && violations > 0 {
+            *self.violations.entry((rule, origin.trim().to_string())).or_default() += violations;
+        }
+    }
+}
+
+fn text(value: Option<&Value>) -> String {
+    value.and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+fn count(value: Option<&Value>) -> usize {
+    value.and_then(Value::as_u64).unwrap_or(0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn entry(date: Option<(i32, u32, u32)>, tipo: Option<&str>, credit: Option<i64>) -> Transaction {
+        Transaction {
+            date: date.and_then(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d)),
+            transaction_type: tipo.map(str::to_string),
+            description: None,
+            credit: credit.map(Decimal::from),
+            debit: None,
+            origin: "Conta".to_string(),
+            currency: None,
+            tags: None,
+            row: None,
+            invalid_amount: None,
+        }
+    }
+    
+    #[test]
+    fn test_entry_rules() {
+        let config = QualityConfig::default();
+        let today = NaiveDate::from_ymd_opt(2024, 6, 10).unwrap();
+        let mut report = QualityReport::default();
+        
+        for (entry, typed) in [
+            (entry(None, Some("ALM"), None), true),
+            (entry(Some((2024, 6, 1)), None, None), false),
+            (entry(Some((2024, 6, 1)), Some("EST"), Some(-5)), true),
+            (entry(Some((2024, 7, 1)), Some("ALM"), Some(0)), true),
+            (entry(Some((2024, 6, 10)), Some("ALM"), Some(10)), true),
+        ] {
+            report.check_entry(&config, &entry, typed, today);
+        }
+        
+        for rule in [QualityRule::NullDate, QualityRule::MissingTipo, QualityRule::NegativeCredit, QualityRule::FutureDate] {
+            assert_eq!(report.count(rule, "Conta"), 1, "{:?}", rule);
+        }
+        assert_eq!(report.total(), 4);
+        
+        // Disabled rules and tolerated future dates are not counted
+        let config: QualityConfig = toml::from_str("rules = [\"null_date\"]\nfuture_days = 30").unwrap();
+        let mut report = QualityReport::default();
+        report.check_entry(&config, &entry(Some((2024, 7, 1)), None, Some(-1)), false, today);
+        assert_eq!(report.total(), 0);
+    }
+    
+    #[test]
+    fn test_database_rules_and_table() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.insert_reference_data("TiposLancamentos", &[
+            vec!["Código".to_string(), "Descrição".to_string()],
+            vec!["ALM".to_string(), "ALIMENTACAO".to_string()],
+        ]).unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2024-01-05', 'ALM', 'Feira', 0, 20, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2024-01-05', 'ALM', 'Feira', 0, 20, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2024-01-06', 'alimentacao', 'Padaria', 0, 8, 'Cartao');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem) VALUES ('2024-01-07', 'XYZ', 'Outro', 0, 1, 'Cartao');"
+        ).unwrap();
+        
+        let config = QualityConfig::default();
+        let mut report = QualityReport::default();
+        report.check_database(&config, &db, "LANCAMENTOS_GERAIS", "TiposLancamentos").unwrap();
+        assert_eq!(report.count(QualityRule::UnknownTipo, "Cartao"), 1);
+        assert_eq!(report.count(QualityRule::Duplicate, "Conta"), 1);
+        assert_eq!(report.total(), 2);
+        
+        assert_eq!(report.write_table(&db, &config.table).unwrap(), 2);
+        let rows = db.execute_query("SELECT Regra, Origem, Violacoes FROM DATA_QUALITY ORDER BY Regra").unwrap();
+        assert_eq!(rows, vec![
+            vec![json!("duplicate"), json!("Conta"), json!(1)],
+            vec![json!("unknown_tipo"), json!("Cartao"), json!(1)],
+        ]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,269 @@
+/*!
+# Quarantine Module
+
+Entries the load rejects are kept instead of dropped: each goes to the
+discarded table (`settings.discarted_data_table`) with the reason and where it
+came from, so it can be fixed in the workbook. Rows are rejected while read for
+a missing date, a missing TIPO (not filled by categorization) or an amount cell
+that is not a number; once loaded, entries whose TIPO is neither a code nor a
+description of TiposLancamentos are moved from the general entries into the
+same table. The counts per reason and origin are logged, kept in PDW_RUNS as
+discarded rows, and the table is exported as the "Descartados" report sheet.
+
+| Column | Content |
+|--------|---------|
+| Data, TIPO, DESCRICAO, Credito, Debito | The entry as read |
+| Origem | Sheet (or source) of the entry |
+| Linha | Row of the sheet, header included; empty for unknown TIPOs |
+| Motivo | `missing_date`, `missing_tipo`, `unparsable_amount` or `unknown_tipo` |
+| Detalhe | Description of the reason; for amounts, the cell and why it is not a number |
+*/
+
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, PdwError};
+use crate::excel::Transaction;
+use crate::money::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+/// Why an entry was kept out of the general entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RejectReason {
+    MissingDate,
+    /// No TIPO in the sheet nor from the categorization rules
+    MissingTipo,
+    /// Credit or debit cell holding text that is not a number
+    UnparsableAmount,
+    /// TIPO that is neither a code nor a description of TiposLancamentos
+    UnknownTipo,
+}
+
+impl RejectReason {
+    /// Reason stored in the Motivo column
+    pub fn name(&self) -> &'static str {
+        match self {
+            RejectReason::MissingDate => "missing_date",
+            RejectReason::MissingTipo => "missing_tipo",
+            RejectReason::UnparsableAmount => "unparsable_amount",
+            RejectReason::UnknownTipo => "unknown_tipo",
+        }
+    }
+    
+    /// Description shown next to the reason
+    pub fn label(&self) -> &'static str {
+        match self {
+            RejectReason::MissingDate => "Lancamento sem data",
+            RejectReason::MissingTipo => "Lancamento sem TIPO",
+            RejectReason::UnparsableAmount => "Valor nao numerico",
+            RejectReason::UnknownTipo => "TIPO fora de TiposLancamentos",
+        }
+    }
+}
+
+/// Entries rejected by a load, with the counts per reason and origin
+#[derive(Debug, Clone, Default)]
+pub struct Quarantine {
+    /// Rejected while read, waiting for `write_table`
+    rejected: Vec<(Transaction, RejectReason)>,
+    counts: BTreeMap<(RejectReason, String), usize>,
+}
+
+impl Quarantine {
+    /// Keep an entry out of the load
+    pub fn reject(&mut self, entry: Transaction, reason: RejectReason) {
+        *self.counts.entry((reason, entry.origin.trim().to_string())).or_default() += 1;
+        self.rejected.push((entry, reason));
+    }
+    
+    /// Add the entries rejected elsewhere (a sheet read by a worker thread)
+    pub fn merge(&mut self, other: Quarantine) {
+        for (key, rejected) in other.counts {
+            *self.counts.entry(key).or_default() += rejected;
+        }
+        self.rejected.extend(other.rejected);
+    }
+    
+    /// Entries of an origin rejected for a reason
+    pub fn count(&self, reason: RejectReason, origin: &str) -> usize {
+        self.counts.get(&(reason, origin.to_string())).copied().unwrap_or(0)
+    }
+    
+    /// Entries rejected for every reason and origin
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+    
+    /// Counts per reason and origin
+    pub fn counts(&self) -> impl Iterator<Item = (RejectReason, &str, usize)> {
+        self.counts.iter().map(|((reason, origin), rejected)| (*reason, origin.as_str(), *rejected))
+    }
+    
+    /// Print the counts to the console log
+    pub fn log(&self) {
+        if self.counts.is_empty() {
+            return;
+        }
+        
+        tracing::warn!("Discarded entries: {}", self.total());
+        for ((reason, origin), rejected) in &self.counts {
+            tracing::warn!("   . .. ... {} - {}: {}", reason.label(), origin, rejected);
+        }
+    }
+    
+    /// Recreate the discarded table with the entries rejected while read; returns how many
+    pub fn write_table(&mut self, database: &DatabaseManager, table: &str) -> Result<usize, PdwError> {
+        database.drop_table(table)?;
+        let create_query = format!(
+            "CREATE TABLE {} (Data DATE, TIPO TEXT, DESCRICAO TEXT, Credito REAL, Debito REAL, Origem TEXT,
+                              Linha INTEGER, Motivo TEXT, Detalhe TEXT)",
+            table
+        );
+        database.connection().execute(&create_query, [])
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: create_query.clone(),
+                reason: e.to_string(),
+            })?;
+        
+        let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)", table);
+        let amount = |value: Option<Decimal>| value.and_then(|v| v.to_f64());
+        for (entry, reason) in &self.rejected {
+            let detail = match (&entry.invalid_amount, reason) {
+                (Some(text), RejectReason::UnparsableAmount) => format!("{}: {}", reason.label(), text),
+                _ => reason.label().to_string(),
+            };
+            database.connection().execute(&insert_query, rusqlite::params![
+                entry.date.map(|date| date.format("%Y-%m-%d").to_string()),
+                entry.transaction_type,
+                entry.description,
+                amount(entry.credit),
+                amount(entry.debit),
+                entry.origin.trim(),
+                entry.row.map(|row| row as i64),
+                reason.name(),
+                detail,
+            ]).map_err(|e| DatabaseError::DataInsertion {
+                table: table.to_string(),
+                reason: e.to_string(),
+            })?;
+        }
+        
+        Ok(std::mem::take(&mut self.rejected).len())
+    }
+    
+    /// Move the loaded entries whose TIPO the types table does not know into the discarded
+    /// table (created by `write_table`); returns how many. Nothing is unknown while the types
+    /// table is empty
+    pub fn reject_unknown_types(&mut self, database: &DatabaseManager, entries_table: &str,
+                                types_table: &str, table: &str) -> Result<usize, PdwError> {
+        if database.table_columns(types_table)?.is_empty() {
+            return Ok(0);
+        }
+        // Codes and descriptions; the header row of the sheet, if kept, matches nothing
+        let known: HashSet<String> = database.execute_query(&format!("SELECT * FROM {}", types_table))?
+            .iter()
+            .flat_map(|row| row.iter().take(2).filter_map(Value::as_str).map(|s| s.trim().to_uppercase()))
+            .collect();
+        if known.is_empty() {
+            return Ok(0);
+        }
+        
+        let used = database.execute_query(&format!(
+            "SELECT TIPO, COALESCE(Origem, ''), COUNT(*) FROM {} WHERE TIPO IS NOT NULL GROUP BY TIPO, Origem",
+            entries_table
+        ))?;
+        let reason = RejectReason::UnknownTipo;
+        let move_query = format!(
+            "INSERT INTO {} (Data, TIPO, DESCRICAO, Credito, Debito, Origem, Linha, Motivo, Detalhe)
+             SELECT Data, TIPO, DESCRICAO, Credito, Debito, Origem, NULL, ?2, ?3 FROM {} WHERE TIPO = ?1",
+            table, entries_table
+        );
+        let delete_query = format!("DELETE FROM {} WHERE TIPO = ?1", entries_table);
+        
+        let mut moved = 0;
+        let mut unknown = HashSet::new();
+        for row in &used {
+            let tipo = row.first().and_then(Value::as_str).unwrap_or_default();
+            if known.contains(&tipo.trim().to_uppercase()) {
+                continue;
+            }
+            let rejected = row.get(2).and_then(Value::as_u64).unwrap_or(0) as usize;
+            let origin = row.get(1).and_then(Value::as_str).unwrap_or_default().trim().to_string();
+            *self.counts.entry((reason, origin)).or_default() += rejected;
+            moved += rejected;
+            
+            if unknown.insert(tipo) {
+                database.connection().execute(&move_query, rusqlite::params![tipo, reason.name(), reason.label()])
+                    .and_then(|_| database.connection().execute(&delete_query, [tipo]))
+                    .map_err(|e| DatabaseError::SqlExecution {
+                        query: move_query.clone(),
+                        reason: e.to_string(),
+                    })?;
+            }
+        }
+        
+        Ok(moved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    
+    fn entry(date: Option<(i32, u32, u32)>, tipo: Option<&str>, row: usize) -> Transaction {
+        Transaction {
+            date: date.and_then(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d)),
+            transaction_type: tipo.map(str::to_string),
+            description: Some("Mercado".to_string()),
+            credit: None,
+            debit: Some(Decimal::from(10)),
+            origin: "Conta".to_string(),
+            currency: None,
+            tags: None,
+            row: Some(row),
+            invalid_amount: None,
+        }
+    }
+    
+    #[test]
+    fn test_rejected_entries_kept_with_reasons() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        database.connection().execute_batch(
+            "CREATE TABLE TiposLancamentos (Código TEXT, Descrição TEXT);
+             INSERT INTO TiposLancamentos VALUES ('Código', 'Descrição'), ('ALM', 'Alimentacao');
+             CREATE TABLE LANCAMENTOS_GERAIS (Data DATE, TIPO TEXT, DESCRICAO TEXT, Credito REAL, Debito REAL, Origem TEXT);
+             INSERT INTO LANCAMENTOS_GERAIS VALUES
+                ('2024-01-15', 'ALM', 'Mercado', NULL, 10.0, 'Conta'),
+                ('2024-01-16', 'alimentacao', 'Feira', NULL, 5.0, 'Conta'),
+                ('2024-01-17', 'XYZ', 'Loja', NULL, 7.0, 'Cartao'),
+                ('2024-01-18', 'XYZ', 'Loja', NULL, 8.0, 'Cartao');"
+        ).unwrap();
+        
+        let mut quarantine = Quarantine::default();
+        quarantine.reject(entry(None, Some("ALM"), 3), RejectReason::MissingDate);
+        quarantine.reject(entry(Some((2024, 1, 20)), None, 4), RejectReason::MissingTipo);
+        let unparsable = Transaction { invalid_amount: Some("R$ 10".to_string()), ..entry(Some((2024, 1, 21)), Some("ALM"), 9) };
+        quarantine.reject(unparsable, RejectReason::UnparsableAmount);
+        assert_eq!(quarantine.write_table(&database, "discarted_data").unwrap(), 3);
+        
+        assert_eq!(quarantine.reject_unknown_types(&database, "LANCAMENTOS_GERAIS", "TiposLancamentos", "discarted_data").unwrap(), 2);
+        assert_eq!(quarantine.count(RejectReason::UnknownTipo, "Cartao"), 2);
+        assert_eq!(quarantine.total(), 5);
+        
+        let kept = database.execute_query("SELECT DESCRICAO FROM LANCAMENTOS_GERAIS ORDER BY Data").unwrap();
+        assert_eq!(kept, vec![vec![serde_json::json!("Mercado")], vec![serde_json::json!("Feira")]]);
+        let discarded = database.execute_query(
+            "SELECT Origem, Linha, Motivo, Detalhe FROM discarted_data ORDER BY rowid"
+        ).unwrap();
+        let row = |values: [Value; 4]| values.to_vec();
+        assert_eq!(discarded, vec![
+            row([Value::from("Conta"), Value::from(3), Value::from("missing_date"), Value::from("Lancamento sem data")]),
+            row([Value::from("Conta"), Value::from(4), Value::from("missing_tipo"), Value::from("Lancamento sem TIPO")]),
+            row([Value::from("Conta"), Value::from(9), Value::from("unparsable_amount"), Value::from("Valor nao numerico: R$ 10")]),
+            row([Value::from("Cartao"), Value::Null, Value::from("unknown_tipo"), Value::from("TIPO fora de TiposLancamentos")]),
+            row([Value::from("Cartao"), Value::Null, Value::from("unknown_tipo"), Value::from("TIPO fora de TiposLancamentos")]),
+        ]);
+    }
+}
\ No newline at end of file
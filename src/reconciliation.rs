@@ -0,0 +1,319 @@
+/*!
+# Reconciliation Module
+
+Balance reconciliation against bank statements (`[reconciliation]`). A SALDOS
+sheet of the input workbook lists the end balances the statements declare, one
+row per origin and statement day:
+
+| ORIGEM | DATA | SALDO |
+|--------|------|-------|
+| ContaCorrente | 31/01/2024 | 4.250,18 |
+
+The rows are stored at load time in the SALDOS_DECLARADOS table. After each
+load the running balance of every origin is computed from the general entries
+up to each statement day, shifted by its opening balance (`[opening_balances]`),
+and compared with the declared one. The RECONCILIACAO table and the
+"Reconciliacao" report sheet list every statement with both balances, the
+difference and how much of it appeared since the origin's previous statement,
+which points at the period with a missed or duplicated entry.
+*/
+
+use crate::budgets;
+use crate::collation;
+use crate::database::DatabaseManager;
+use crate::error::{ConfigError, DatabaseError, PdwError};
+use crate::money::MoneyMode;
+use crate::opening_balances::{self, OpeningBalances};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Reconciliation settings (`[reconciliation]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconciliationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Input sheet with the statement balances
+    #[serde(default = "default_reconciliation_sheet")]
+    pub sheet: String,
+    /// Table the declared balances are stored in
+    #[serde(default = "default_balances_table")]
+    pub balances_table: String,
+    /// Table of the reconciliation
+    #[serde(default = "default_reconciliation_table")]
+    pub table: String,
+    /// Largest difference still taken as reconciled
+    #[serde(default = "default_reconciliation_tolerance")]
+    pub tolerance: f64,
+}
+
+fn default_reconciliation_sheet() -> String {
+    "SALDOS".to_string()
+}
+
+fn default_balances_table() -> String {
+    "SALDOS_DECLARADOS".to_string()
+}
+
+fn default_reconciliation_table() -> String {
+    "RECONCILIACAO".to_string()
+}
+
+fn default_reconciliation_tolerance() -> f64 {
+    0.01
+}
+
+impl Default for ReconciliationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sheet: default_reconciliation_sheet(),
+            balances_table: default_balances_table(),
+            table: default_reconciliation_table(),
+            tolerance: default_reconciliation_tolerance(),
+        }
+    }
+}
+
+/// End balance a statement declares for an origin
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclaredBalance {
+    pub origin: String,
+    pub date: NaiveDate,
+    pub balance: f64,
+}
+
+impl ReconciliationConfig {
+    /// Declared balances of a SALDOS sheet (header first)
+    pub fn balances_from_sheet(&self, rows: &[Vec<String>]) -> Result<Vec<DeclaredBalance>, PdwError> {
+        let Some((header, rows)) = rows.split_first() else {
+            return Ok(Vec::new());
+        };
+        
+        let column = |names: &[&str], field: &str| header.iter()
+            .position(|h| names.contains(&collation::fold(h.trim()).as_str()))
+            .ok_or_else(|| ConfigError::MissingField {
+                field: format!("{} column of the {} sheet", field, self.sheet),
+            });
+        let origin_column = column(&["origem", "origin", "conta", "account"], "ORIGEM")?;
+        let date_column = column(&["data", "date"], "DATA")?;
+        let balance_column = column(&["saldo", "balance"], "SALDO")?;
+        
+        let mut balances = Vec::new();
+        for row in rows {
+            let cell = |column: usize| row.get(column).map(|c| c.trim()).unwrap_or_default();
+            let origin = cell(origin_column);
+            if origin.is_empty() {
+                continue;
+            }
+            let date = opening_balances::parse_as_of(cell(date_column)).ok_or_else(|| ConfigError::InvalidFormat {
+                message: format!("Invalid date '{}' for {} in the {} sheet", cell(date_column), origin, self.sheet),
+            })?;
+            let balance = budgets::parse_limit(cell(balance_column)).ok_or_else(|| ConfigError::InvalidFormat {
+                message: format!("Invalid balance '{}' for {} in the {} sheet", cell(balance_column), origin, self.sheet),
+            })?;
+            balances.push(DeclaredBalance { origin: origin.to_string(), date, balance });
+        }
+        
+        Ok(balances)
+    }
+}
+
+/// Store the declared balances, replacing the previous load's table
+pub fn write_declared_balances(database: &DatabaseManager, table: &str, balances: &[DeclaredBalance]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!("CREATE TABLE {} (Origem TEXT, Data DATE, Saldo REAL, PRIMARY KEY (Origem, Data))", table);
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    // A day listed twice keeps its last row
+    let insert_query = format!("INSERT OR REPLACE INTO {} VALUES (?1, ?2, ?3)", table);
+    for declared in balances {
+        database.connection().execute(&insert_query, rusqlite::params![
+            declared.origin,
+            declared.date.format("%Y-%m-%d").to_string(),
+            declared.balance,
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(balances.len())
+}
+
+/// Declared against computed balance of an origin on a statement day
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reconciliation {
+    pub origin: String,
+    pub date: NaiveDate,
+    pub declared: f64,
+    pub computed: f64,
+    /// Computed minus declared
+    pub difference: f64,
+    /// Part of the difference that appeared since the origin's previous statement
+    pub period_difference: f64,
+}
+
+impl Reconciliation {
+    /// Whether the difference is within the tolerance
+    pub fn is_reconciled(&self, tolerance: f64) -> bool {
+        self.difference.abs() <= tolerance
+    }
+}
+
+/// Running balance of every declared origin and day, oldest first per origin
+pub fn reconcile(database: &DatabaseManager, entries_table: &str, config: &ReconciliationConfig,
+                 openings: &OpeningBalances) -> Result<Vec<Reconciliation>, PdwError> {
+    if database.table_columns(&config.balances_table)?.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    let money = database.money_mode();
+    if money == MoneyMode::Decimal {
+        database.add_money_columns(entries_table)?;
+    }
+    let query = format!(
+        "SELECT b.Origem, b.Data, b.Saldo, ROUND(COALESCE((
+             SELECT {credit} - {debit} FROM {entries} e
+             WHERE TRIM(COALESCE(e.Origem, '')) = b.Origem AND date(e.Data) <= b.Data
+         ), 0), 2)
+         FROM {table} b ORDER BY b.Origem, b.Data",
+        credit = money.sum_sql("e.Credito", None),
+        debit = money.sum_sql("e.Debito", None),
+        entries = entries_table,
+        table = config.balances_table,
+    );
+    
+    let mut reconciliations: Vec<Reconciliation> = Vec::new();
+    for row in database.execute_query(&query)? {
+        let (Some(origin), Some(date)) = (
+            row.first().and_then(Value::as_str),
+            row.get(1).and_then(Value::as_str).and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+        ) else {
+            continue;
+        };
+        let declared = row.get(2).and_then(Value::as_f64).unwrap_or(0.0);
+        let computed = cents(row.get(3).and_then(Value::as_f64).unwrap_or(0.0) + openings.offset(origin));
+        let difference = cents(computed - declared);
+        let previous = reconciliations.last()
+            .filter(|previous| previous.origin == origin)
+            .map_or(0.0, |previous| previous.difference);
+        
+        reconciliations.push(Reconciliation {
+            origin: origin.to_string(),
+            date,
+            declared,
+            computed,
+            difference,
+            period_difference: cents(difference - previous),
+        });
+    }
+    
+    Ok(reconciliations)
+}
+
+/// Amount rounded to cents
+fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Store the reconciliation, replacing the previous run's table
+pub fn write_reconciliation_table(database: &DatabaseManager, table: &str, reconciliations: &[Reconciliation],
+                                  tolerance: f64) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (Origem TEXT, Data DATE, SaldoDeclarado REAL, SaldoCalculado REAL,
+                          Diferenca REAL, DiferencaPeriodo REAL, Situacao TEXT)",
+        table
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)", table);
+    for reconciliation in reconciliations {
+        database.connection().execute(&insert_query, rusqlite::params![
+            reconciliation.origin,
+            reconciliation.date.format("%Y-%m-%d").to_string(),
+            reconciliation.declared,
+            reconciliation.computed,
+            reconciliation.difference,
+            reconciliation.period_difference,
+            if reconciliation.is_reconciled(tolerance) { "OK" } else { "DIVERGENTE" },
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(reconciliations.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opening_balances::OpeningBalanceConfig;
+    
+    fn rows(cells: &[&[&str]]) -> Vec<Vec<String>> {
+        cells.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect()
+    }
+    
+    #[test]
+    fn test_statement_balances_reconciled() {
+        let config = ReconciliationConfig { enabled: true, ..Default::default() };
+        let sheet = rows(&[
+            &["Conta", "Data", "Saldo"],
+            &["Corrente", "31/01/2024", "1.350,00"],
+            &["Corrente", "2024-02-29", "1260"],
+            &["Poupanca", "45322", "500"],
+            &["", "", ""],
+        ]);
+        let declared = config.balances_from_sheet(&sheet).unwrap();
+        assert_eq!(declared.len(), 3);
+        assert_eq!(declared[2].date, NaiveDate::from_ymd_opt(2024, 1, 31).unwrap());
+        assert!(config.balances_from_sheet(&rows(&[&["Origem", "Data", "Saldo"], &["Corrente", "31/01/2024", "muito"]])).is_err());
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-01-05', 'SAL', 1500, 0, 'Corrente');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-01-20', 'ALM', 0, 150, 'Corrente ');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-02-10', 'ALM', 0, 90, 'Corrente');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-02-10', 'ALM', 0, 90, 'Corrente');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, Origem) VALUES ('2024-01-31', 'REN', 20, 0, 'Poupanca');"
+        ).unwrap();
+        write_declared_balances(&db, &config.balances_table, &declared).unwrap();
+        
+        // Poupanca opened with 480 at the end of 2023
+        let opening_config: OpeningBalanceConfig = toml::from_str(
+            "enabled = true\n[accounts]\nPoupanca = { balance = 480.0, as_of = \"2023-12-31\" }"
+        ).unwrap();
+        let balances = opening_config.balances_with_sheet(&[]).unwrap();
+        opening_balances::write_opening_balance_table(&db, &opening_config.table, &balances).unwrap();
+        let openings = OpeningBalances::load(&db, "LANCAMENTOS_GERAIS", &opening_config).unwrap();
+        
+        let reconciliations = reconcile(&db, "LANCAMENTOS_GERAIS", &config, &openings).unwrap();
+        let found: Vec<_> = reconciliations.iter()
+            .map(|r| (r.origin.as_str(), r.computed, r.difference, r.period_difference, r.is_reconciled(config.tolerance)))
+            .collect();
+        assert_eq!(found, vec![
+            ("Corrente", 1350.0, 0.0, 0.0, true),
+            // The February purchase was entered twice
+            ("Corrente", 1170.0, -90.0, -90.0, false),
+            ("Poupanca", 500.0, 0.0, 0.0, true),
+        ]);
+        
+        assert_eq!(write_reconciliation_table(&db, &config.table, &reconciliations, config.tolerance).unwrap(), 3);
+        let divergent = db.execute_query("SELECT Origem, Data FROM RECONCILIACAO WHERE Situacao = 'DIVERGENTE'").unwrap();
+        assert_eq!(divergent, vec![vec![Value::from("Corrente"), Value::from("2024-02-29")]]);
+    }
+}
\ No newline at end of file
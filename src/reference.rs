@@ -0,0 +1,206 @@
+/*!
+# Reference Tables Module
+
+Reference sheets (TiposLancamentos, GUIDING, the dynamic reports list and any
+other non-accounting sheet) become tables named after their header row:
+
+- the header cells, trimmed, with runs of characters other than letters, digits
+  and `_` turned into `_` ("Valor (R$)" -> `Valor_R`); blank headers become
+  `colN` and repeated ones get a `_2`, `_3`... suffix
+- a column whose filled cells are all numbers is REAL, all dates DATE (stored
+  as `YYYY-MM-DD`), otherwise TEXT; codes with leading zeros stay TEXT
+- the header and blank rows are not inserted, and empty cells are NULL
+
+Tables PDW creates itself (GUIDING, TiposLancamentos) keep their columns: rows
+go into the columns named like the header, or by position when the header
+names other columns.
+*/
+
+use crate::excel;
+use chrono::NaiveDate;
+
+/// Type of a reference table column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceType {
+    Text,
+    Real,
+    Date,
+}
+
+impl ReferenceType {
+    /// SQLite column type
+    pub fn sql_type(&self) -> &'static str {
+        match self {
+            ReferenceType::Text => "TEXT",
+            ReferenceType::Real => "REAL",
+            ReferenceType::Date => "DATE",
+        }
+    }
+}
+
+/// Reference sheet split into its typed columns and data rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceSheet<'a> {
+    /// Sanitized header names and inferred types
+    pub columns: Vec<(String, ReferenceType)>,
+    /// Rows after the header
+    pub rows: &'a [Vec<String>],
+}
+
+impl<'a> ReferenceSheet<'a> {
+    /// Columns of the sheet's header row; None for an empty sheet
+    pub fn from_rows(data: &'a [Vec<String>]) -> Option<Self> {
+        let (header, rows) = data.split_first()?;
+        let names = column_names(header);
+        let columns = names.into_iter().enumerate()
+            .map(|(index, name)| {
+                let cells: Vec<&str> = rows.iter()
+                    .filter_map(|row| row.get(index).map(|cell| cell.trim()))
+                    .filter(|cell| !cell.is_empty())
+                    .collect();
+                (name, infer_type(&cells))
+            })
+            .collect();
+        Some(Self { columns, rows })
+    }
+    
+    /// Values of a row, one per column (None for empty cells), numbers and dates normalized;
+    /// None when the row has filled cells past the header
+    pub fn values(&self, row: &[String]) -> Option<Vec<Option<String>>> {
+        if row.iter().skip(self.columns.len()).any(|cell| !cell.trim().is_empty()) {
+            return None;
+        }
+        Some(self.columns.iter().enumerate()
+            .map(|(index, (_, column_type))| {
+                let cell = row.get(index).map(|cell| cell.trim()).filter(|cell| !cell.is_empty())?;
+                Some(match column_type {
+                    ReferenceType::Text => cell.to_string(),
+                    ReferenceType::Real => parse_number(cell).map_or_else(|| cell.to_string(), |n| n.to_string()),
+                    ReferenceType::Date => parse_reference_date(cell).map_or_else(|| cell.to_string(), |d| d.format("%Y-%m-%d").to_string()),
+                })
+            })
+            .collect())
+    }
+    
+    /// Columns of an existing table the rows go into: the header's names when the table has them
+    /// all (case ignored), None to insert by position
+    pub fn target_columns(&self, existing: &[String]) -> Option<Vec<String>> {
+        self.columns.iter()
+            .map(|(name, _)| existing.iter().find(|column| column.eq_ignore_ascii_case(name)).cloned())
+            .collect()
+    }
+}
+
+/// Sanitized, unique column names of a header row
+fn column_names(header: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (index, cell) in header.iter().enumerate() {
+        let mut name = String::new();
+        for c in cell.trim().chars() {
+            if c.is_alphanumeric() || c == '_' {
+                name.push(c);
+            } else if !name.ends_with('_') {
+                name.push('_');
+            }
+        }
+        let mut name = name.trim_matches('_').to_string();
+        if name.is_empty() {
+            name = format!("col{}", index + 1);
+        } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+            name = format!("col_{}", name);
+        }
+        
+        let base = name.clone();
+        let mut suffix = 1;
+        while names.iter().any(|other| other.eq_ignore_ascii_case(&name)) {
+            suffix += 1;
+            name = format!("{}_{}", base, suffix);
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// Type fitting every filled cell of a column
+fn infer_type(cells: &[&str]) -> ReferenceType {
+    if cells.is_empty() {
+        ReferenceType::Text
+    } else if cells.iter().all(|cell| parse_number(cell).is_some()) {
+        ReferenceType::Real
+    } else if cells.iter().all(|cell| parse_reference_date(cell).is_some()) {
+        ReferenceType::Date
+    } else {
+        ReferenceType::Text
+    }
+}
+
+/// Number written as the sheet readers render them, or with a decimal comma;
+/// codes with leading zeros ("007") are not numbers
+fn parse_number(text: &str) -> Option<f64> {
+    let digits = text.trim_start_matches('-');
+    if digits.is_empty() || !digits.starts_with(|c: char| c.is_ascii_digit())
+        || (digits.len() > 1 && digits.starts_with('0') && !digits[1..].starts_with(['.', ',']))
+        || !digits.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',') {
+        return None;
+    }
+    let normalized = if text.contains(',') && !text.contains('.') {
+        text.replace(',', ".")
+    } else {
+        text.to_string()
+    };
+    normalized.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// Day of a date cell, as the readers render dates or as typed
+fn parse_reference_date(text: &str) -> Option<NaiveDate> {
+    excel::parse_date(text).or_else(|| text.get(..10).filter(|_| text.len() > 10).and_then(excel::parse_date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseManager;
+    use serde_json::json;
+    
+    fn rows(cells: &[&[&str]]) -> Vec<Vec<String>> {
+        cells.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect()
+    }
+    
+    #[test]
+    fn test_reference_table_from_header() {
+        let sheet = rows(&[
+            &["Conta", " Valor (R$) ", "Abertura", "", "Conta", "Agência"],
+            &["Banco A", "1500.5", "15/01/2024", "x", "Corrente", "0012"],
+            &["Banco B", "-20,75", "2024-02-01 00:00:00", "", "Poupança", "3401"],
+            &["", "", "", "", "", ""],
+        ]);
+        let reference = ReferenceSheet::from_rows(&sheet).unwrap();
+        assert_eq!(reference.columns, vec![
+            ("Conta".to_string(), ReferenceType::Text),
+            ("Valor_R".to_string(), ReferenceType::Real),
+            ("Abertura".to_string(), ReferenceType::Date),
+            ("col4".to_string(), ReferenceType::Text),
+            ("Conta_2".to_string(), ReferenceType::Text),
+            ("Agência".to_string(), ReferenceType::Text),
+        ]);
+        assert!(ReferenceSheet::from_rows(&[]).is_none());
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        // The header and the blank row are left out
+        assert_eq!(db.insert_reference_data("Contas", &sheet).unwrap(), 2);
+        let stored = db.execute_query("SELECT Valor_R, Abertura, Agência FROM Contas ORDER BY Valor_R").unwrap();
+        assert_eq!(stored, vec![
+            vec![json!(-20.75), json!("2024-02-01"), json!("3401")],
+            vec![json!(1500.5), json!("2024-01-15"), json!("0012")],
+        ]);
+        
+        // Tables of PDW's own keep their columns: by name, or by position
+        db.insert_reference_data("TiposLancamentos", &rows(&[&["descrição", "código"], &["Alimentação", "ALM"]])).unwrap();
+        db.insert_reference_data("TiposLancamentos", &rows(&[&["Tipo", "Nome"], &["LAZ", "Lazer"]])).unwrap();
+        let types = db.execute_query("SELECT Código, Descrição FROM TiposLancamentos").unwrap();
+        assert_eq!(types, vec![vec![json!("ALM"), json!("Alimentação")], vec![json!("LAZ"), json!("Lazer")]]);
+        assert!(db.insert_reference_data("TiposLancamentos", &rows(&[&["Código", "Descrição"], &["SAU", "Saúde", "?"]])).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,123 @@
+/*!
+# Report Charts Module
+
+Native Excel charts for the report workbook (`settings.generate_charts`): a
+chart drawn next to the data of any YAML query with `chart: line|bar|pie|stacked`,
+and built-in chart sheets - monthly credit and debit from the monthly
+summaries table, debits per TIPO stacked by month from the monthly pivot
+table and, with `[income_share]`, each expense group's share of income.
+
+Charts reference the cells written to their sheet: the first column holds the
+categories (AnoMes, Origem, ...) and every numeric column becomes a series.
+*/
+
+use crate::error::{PdwError, ReportError};
+use rust_xlsxwriter::{Chart, ChartType, Worksheet};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Sheet of the monthly credit/debit line chart
+pub const MONTHLY_CHART_SHEET: &str = "Grafico Mensal";
+
+/// Sheet of the stacked column chart of the monthly pivot
+pub const PIVOT_CHART_SHEET: &str = "Grafico Tipos";
+
+/// Sheet of the stacked column chart of the expense groups' share of income
+pub const INCOME_CHART_SHEET: &str = "Grafico Renda";
+
+/// Chart drawn for a report sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartKind {
+    Line,
+    /// Vertical bars (Excel's column chart)
+    Bar,
+    /// First numeric column only
+    Pie,
+    /// Vertical bars stacked per category
+    Stacked,
+}
+
+impl ChartKind {
+    fn chart_type(self) -> ChartType {
+        match self {
+            ChartKind::Line => ChartType::Line,
+            ChartKind::Bar => ChartType::Column,
+            ChartKind::Pie => ChartType::Pie,
+            ChartKind::Stacked => ChartType::ColumnStacked,
+        }
+    }
+}
+
+/// Columns (after the category column) drawn as series: those holding numbers
+pub fn series_columns(columns: &[String], rows: &[Vec<Value>]) -> Vec<usize> {
+    (1..columns.len())
+        .filter(|&col_idx| rows.iter().any(|row| matches!(row.get(col_idx), Some(Value::Number(_)))))
+        .collect()
+}
+
+/// Chart of the rows written to `sheet_name` (header in row 0); None when nothing is numeric
+pub fn query_chart(kind: ChartKind, sheet_name: &str, columns: &[String], rows: &[Vec<Value>]) -> Option<Chart> {
+    let mut series = series_columns(columns, rows);
+    if series.is_empty() || rows.is_empty() {
+        return None;
+    }
+    if kind == ChartKind::Pie {
+        series.truncate(1);
+    }
+    
+    let last_row = rows.len() as u32;
+    let mut chart = Chart::new(kind.chart_type());
+    chart.title().set_name(sheet_name);
+    for col_idx in series {
+        let col = col_idx as u16;
+        chart.add_series()
+            .set_name((sheet_name, 0, col))
+            .set_categories((sheet_name, 1, 0, last_row, 0))
+            .set_values((sheet_name, 1, col, last_row, col));
+    }
+    Some(chart)
+}
+
+/// Draw the chart of a query sheet to the right of its data
+pub fn insert_query_chart(
+    worksheet: &mut Worksheet,
+    kind: ChartKind,
+    sheet_name: &str,
+    columns: &[String],
+    rows: &[Vec<Value>],
+) -> Result<bool, PdwError> {
+    let Some(chart) = query_chart(kind, sheet_name, columns, rows) else {
+        tracing::warn!("No numeric column to chart in sheet {}", sheet_name);
+        return Ok(false);
+    };
+    
+    worksheet.insert_chart(1, columns.len() as u16 + 1, &chart)
+        .map_err(ReportError::ExcelWriter)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_query_chart_series() {
+        let columns = vec!["AnoMes".to_string(), "Origem".to_string(), "CREDITO".to_string(), "DEBITO".to_string()];
+        let rows = vec![
+            vec![json!("2024/01"), json!("Conta"), json!(100.0), json!(null)],
+            vec![json!("2024/02"), json!("Conta"), json!(null), json!(40)],
+        ];
+        assert_eq!(series_columns(&columns, &rows), vec![2, 3]);
+        assert!(query_chart(ChartKind::Line, "Resumo", &columns, &rows).is_some());
+        assert!(query_chart(ChartKind::Pie, "Resumo", &columns, &rows).is_some());
+        
+        // Text only, or no rows: nothing to draw
+        assert!(query_chart(ChartKind::Bar, "Resumo", &columns[..2], &rows).is_none());
+        assert!(query_chart(ChartKind::Bar, "Resumo", &columns, &[]).is_none());
+        
+        let kind: ChartKind = serde_yaml::from_str("stacked").unwrap();
+        assert_eq!(kind, ChartKind::Stacked);
+    }
+}
\ No newline at end of file
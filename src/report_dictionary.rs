@@ -0,0 +1,165 @@
+/*!
+# Report Dictionary Module
+
+The "Dicionário" sheet closing the report workbook (`settings.data_dictionary`),
+for whoever receives the workbook without the warehouse: one row per sheet,
+in workbook order, with the SQL that filled it (placeholders already
+substituted), the tables and views it reads, its row count and when it was
+written.
+
+Source tables are the tables and views of the database whose names appear in
+the SQL. Below the sheets, one "Exclusões" row per metric that leaves TIPOs out
+(`[analytics.exclusions]`) gives the condition applied.
+*/
+
+use crate::database::DatabaseManager;
+use crate::deterministic;
+use crate::error::{PdwError, ReportError};
+use crate::reporting::{write_styled_sheet, SheetStyle};
+use serde_json::{json, Value};
+
+/// Name of the dictionary sheet
+pub const DICTIONARY_SHEET: &str = "Dicionário";
+
+/// Longest text an Excel cell holds
+const MAX_CELL_TEXT: usize = 32_767;
+
+/// Width of the SQL column, wider than autofit leaves long statements
+const SQL_COLUMN_WIDTH: f64 = 80.0;
+
+/// A sheet written to the report workbook
+#[derive(Debug, Clone, PartialEq)]
+pub struct DictionaryEntry {
+    pub sheet: String,
+    /// SQL after placeholder substitution
+    pub sql: String,
+    /// Rows written, header excluded
+    pub rows: usize,
+    pub generated_at: String,
+}
+
+impl DictionaryEntry {
+    /// Entry of a sheet written now
+    pub fn new(sheet: &str, sql: &str, rows: usize) -> Self {
+        Self {
+            sheet: sheet.to_string(),
+            sql: sql.trim().to_string(),
+            rows,
+            generated_at: deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+/// Tables among `tables` that a statement names (case ignored), in the order given
+pub fn source_tables(sql: &str, tables: &[String]) -> Vec<String> {
+    let sql = sql.to_uppercase();
+    let words: Vec<&str> = sql.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .collect();
+    
+    tables.iter()
+        .filter(|table| {
+            let table = table.to_uppercase();
+            // Names with spaces or symbols only appear quoted
+            words.contains(&table.as_str())
+                || [('"', '"'), ('[', ']'), ('`', '`')].iter()
+                    .any(|(open, close)| sql.contains(&format!("{}{}{}", open, table, close)))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Add the dictionary sheet describing the entries' sheets, then the metrics' exclusions (label, condition)
+pub fn add_dictionary_sheet(
+    workbook: &mut rust_xlsxwriter::Workbook,
+    database: &DatabaseManager,
+    entries: &[DictionaryEntry],
+    exclusions: &[(String, String)],
+    currency_format: &str,
+) -> Result<(), PdwError> {
+    let tables: Vec<String> = database.execute_query(
+        "SELECT name FROM sqlite_master WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' ORDER BY name"
+    )?
+        .iter()
+        .filter_map(|row| row.first().and_then(Value::as_str).map(str::to_string))
+        .collect();
+    
+    let columns: Vec<String> = ["Planilha", "SQL", "Tabelas", "Linhas", "Gerado em"].iter().map(|c| c.to_string()).collect();
+    let rows: Vec<Vec<Value>> = entries.iter()
+        .map(|entry| vec![
+            json!(entry.sheet),
+            json!(entry.sql.chars().take(MAX_CELL_TEXT).collect::<String>()),
+            json!(source_tables(&entry.sql, &tables).join(", ")),
+            json!(entry.rows),
+            json!(entry.generated_at),
+        ])
+        .chain(exclusions.iter().map(|(metric, condition)| vec![
+            json!(format!("Exclusões: {}", metric)),
+            json!(condition),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+        ]))
+        .collect();
+    
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(DICTIONARY_SHEET)
+        .map_err(ReportError::ExcelWriter)?;
+    write_styled_sheet(worksheet, &columns, &rows, &SheetStyle::default(), currency_format)?;
+    worksheet.set_column_width(1, SQL_COLUMN_WIDTH)
+        .map_err(ReportError::ExcelWriter)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PdwConfig;
+    use crate::reporting::{QueryConfig, ReportGenerator};
+    use calamine::{DataType, Reader};
+    
+    #[test]
+    fn test_dictionary_lists_report_sheets() {
+        let tables = vec!["LANCAMENTOS_GERAIS".to_string(), "Resumo Mensal".to_string(), "TiposLancamentos".to_string()];
+        assert_eq!(
+            source_tables("SELECT * FROM lancamentos_gerais g JOIN \"Resumo Mensal\" r ON g.TIPO = r.TIPO", &tables),
+            vec!["LANCAMENTOS_GERAIS", "Resumo Mensal"]
+        );
+        assert!(source_tables("SELECT TiposLancamentosX FROM Outra", &tables).is_empty());
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_out = temp_dir.path().to_path_buf();
+        config.settings.run_dynamic_report = false;
+        config.analytics.exclusions.digest.types = vec!["trf".to_string()];
+        let queries: QueryConfig = serde_yaml::from_str(r#"
+queries_padrao:
+  - sql: "SELECT Origem, SUM(Debito) AS Debito FROM LANCAMENTOS_GERAIS WHERE Data >= '{current_year}-01-01' GROUP BY Origem"
+    sheet_name: "Por Origem"
+  - sql: "SELECT * FROM LANCAMENTOS_GERAIS WHERE 0"
+    sheet_name: "Vazia"
+"#).unwrap();
+        let database = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        database.connection().execute_batch(&format!(
+            "CREATE TABLE LANCAMENTOS_GERAIS (Data DATE, Debito REAL, Origem TEXT);
+             INSERT INTO LANCAMENTOS_GERAIS VALUES ('{0}-01-15', 10.0, 'Conta'), ('{0}-02-03', 4.5, 'Cartao');",
+            chrono::Local::now().format("%Y")
+        )).unwrap();
+        
+        let report_path = config.get_report_path();
+        ReportGenerator::new(database, config).with_queries(queries).generate_excel_reports().unwrap();
+        
+        let mut report: calamine::Xlsx<_> = calamine::open_workbook(&report_path).unwrap();
+        assert_eq!(report.sheet_names().to_vec(), vec!["Por Origem", DICTIONARY_SHEET]);
+        let range = report.worksheet_range(DICTIONARY_SHEET).unwrap().unwrap();
+        assert_eq!(range.height(), 3);
+        assert_eq!(range.get_value((1, 0)), Some(&DataType::String("Por Origem".to_string())));
+        // The SQL as run, placeholders substituted
+        let sql = range.get_value((1, 1)).and_then(DataType::get_string).unwrap();
+        assert!(sql.contains(&format!("'{}-01-01'", chrono::Local::now().format("%Y"))));
+        assert_eq!(range.get_value((1, 2)), Some(&DataType::String("LANCAMENTOS_GERAIS".to_string())));
+        assert_eq!(range.get_value((1, 3)).and_then(DataType::get_float), Some(2.0));
+        assert_eq!(range.get_value((2, 0)), Some(&DataType::String("Exclusões: Resumo (pdw digest)".to_string())));
+        assert_eq!(range.get_value((2, 1)), Some(&DataType::String("UPPER(TRIM(COALESCE(TIPO, ''))) NOT IN ('TRF')".to_string())));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,337 @@
+/*!
+# Report Diff Module
+
+Compares the report sheets (YAML queries: pivots, monthly summaries, daily
+progress...) with those of a previous report for `pdw report --diff-against`.
+The previous report is either a workbook written by an earlier run or the
+database it was generated from, in which case the same queries are run
+against it.
+
+Rows are matched on their first column (AnoMes, Ano, TIPO...) and cells on
+their column header; every cell whose value changed, appeared or disappeared
+is listed in the MUDANCAS table, shown as the "Mudanças" sheet of the report.
+*/
+
+use crate::config::PdwConfig;
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, ExcelError, PdwError};
+use crate::reporting::{load_query_file, query_variables, QueryDefinition};
+use calamine::{DataType, Reader, Xlsx};
+use rusqlite::params;
+use rusqlite::types::Value as SqlValue;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Table the changed cells are written to
+pub const CHANGES_TABLE: &str = "MUDANCAS";
+
+/// Report sheet listing the changed cells
+pub const CHANGES_SHEET: &str = "Mudanças";
+
+/// Numbers closer than this are equal (reports show two decimals)
+const TOLERANCE: f64 = 0.005;
+
+/// Header and rows of one report sheet
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReportSheet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Report sheets by name
+pub type Snapshot = BTreeMap<String, ReportSheet>;
+
+/// Cell whose value differs from the previous report
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub sheet: String,
+    /// First column of the row (suffixed with #n when repeated in the sheet)
+    pub key: String,
+    pub column: String,
+    pub previous: Value,
+    pub current: Value,
+}
+
+impl Change {
+    /// Current minus previous value, when both are numbers (a missing number counts as 0)
+    pub fn difference(&self) -> Option<f64> {
+        match (&self.previous, &self.current) {
+            (Value::String(_), _) | (_, Value::String(_)) => None,
+            (previous, current) => Some(current.as_f64().unwrap_or(0.0) - previous.as_f64().unwrap_or(0.0)),
+        }
+    }
+}
+
+/// Sheets the report queries produce from `database`; queries the database
+/// cannot run (e.g. a table an older version did not create) are left out
+pub fn query_snapshot(database: &DatabaseManager, config: &PdwConfig) -> Result<Snapshot, PdwError> {
+    let queries = load_query_file(&config.get_yaml_queries_path())?.with_profiles(&config.export_profiles)?;
+    let variables = query_variables(&config.settings, database.collation().suffix());
+    
+    let pivot_queries = queries.queries_gera_hist.iter().filter(|_| config.settings.create_pivot);
+    let mut snapshot = Snapshot::new();
+    for query in pivot_queries.chain(&queries.queries_padrao).filter(|query| !query.writes) {
+        let QueryDefinition { sql, sheet_name: sheet, .. } = query.expand(&variables);
+        let result = database.read_only(|database| Ok((database.query_columns(&sql)?, database.execute_query(&sql)?)));
+        match result {
+            Ok((columns, rows)) => {
+                snapshot.insert(sheet, ReportSheet { columns, rows });
+            }
+            Err(e) => tracing::debug!("Sheet {} not compared: {}", sheet, e),
+        }
+    }
+    
+    Ok(snapshot)
+}
+
+/// Sheets of a report workbook; the first row of each is its header
+pub fn workbook_snapshot(path: &Path) -> Result<Snapshot, PdwError> {
+    let open_error = |reason: String| ExcelError::FileOpen { path: path.to_string_lossy().to_string(), reason };
+    let mut workbook: Xlsx<_> = calamine::open_workbook(path).map_err(|e: calamine::XlsxError| open_error(e.to_string()))?;
+    
+    let mut snapshot = Snapshot::new();
+    for name in workbook.sheet_names().to_vec() {
+        let Some(range) = workbook.worksheet_range(&name) else {
+            continue;
+        };
+        let range = range.map_err(|e| open_error(e.to_string()))?;
+        
+        let mut rows = range.rows().map(|row| row.iter().map(cell_value).collect::<Vec<_>>());
+        let columns = rows.next()
+            .map(|header| header.iter().map(|cell| key_text(cell)).collect())
+            .unwrap_or_default();
+        snapshot.insert(name, ReportSheet { columns, rows: rows.collect() });
+    }
+    
+    Ok(snapshot)
+}
+
+/// Sheets of the previous report: an .xlsx workbook, or the database it was generated from
+pub fn baseline_snapshot(path: &Path, database: &DatabaseManager, config: &PdwConfig) -> Result<Snapshot, PdwError> {
+    if !path.is_file() {
+        return Err(DatabaseError::ConnectionFailed {
+            path: path.to_string_lossy().to_string(),
+            reason: "Previous report not found".to_string(),
+        }.into());
+    }
+    
+    let is_workbook = path.extension()
+        .is_some_and(|extension| ["xlsx", "xlsm"].iter().any(|e| extension.eq_ignore_ascii_case(e)));
+    if is_workbook {
+        return workbook_snapshot(path);
+    }
+    
+    // The previous database is only read: no tuning pragmas, no writes
+    let mut previous = DatabaseManager::open_read_only(path)?;
+    previous.set_collation(database.collation())?;
+    query_snapshot(&previous, config)
+}
+
+/// Cells that differ between the sheets present in both snapshots
+pub fn diff(previous: &Snapshot, current: &Snapshot) -> Vec<Change> {
+    let mut changes = Vec::new();
+    
+    for (name, sheet) in current {
+        let Some(previous_sheet) = previous.get(name) else {
+            continue;
+        };
+        let previous_cells = keyed_cells(previous_sheet);
+        let current_cells = keyed_cells(sheet);
+        
+        // Row order of the current sheet, then rows only the previous one had
+        let mut keys: Vec<&String> = current_cells.keys.iter().collect();
+        keys.extend(previous_cells.keys.iter().filter(|key| !current_cells.cells.contains_key(*key)));
+        let mut columns: Vec<&String> = sheet.columns.iter().skip(1).collect();
+        columns.extend(previous_sheet.columns.iter().skip(1).filter(|column| !sheet.columns.contains(column)));
+        
+        for key in keys {
+            for column in &columns {
+                let previous_value = previous_cells.get(key, column);
+                let current_value = current_cells.get(key, column);
+                if !same_value(&previous_value, &current_value) {
+                    changes.push(Change {
+                        sheet: name.clone(),
+                        key: key.clone(),
+                        column: (*column).clone(),
+                        previous: previous_value,
+                        current: current_value,
+                    });
+                }
+            }
+        }
+    }
+    
+    changes
+}
+
+/// Replace the MUDANCAS table with the changed cells; returns their count
+pub fn write_changes_table(database: &DatabaseManager, changes: &[Change]) -> Result<usize, PdwError> {
+    database.drop_table(CHANGES_TABLE)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (Planilha TEXT, Chave TEXT, Coluna TEXT, Anterior, Atual, Diferenca REAL)",
+        CHANGES_TABLE
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution { query: create_query, reason: e.to_string() })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6)", CHANGES_TABLE);
+    for change in changes {
+        database.connection().execute(
+            &insert_query,
+            params![change.sheet, change.key, change.column, sql_value(&change.previous), sql_value(&change.current), change.difference()],
+        ).map_err(|e| DatabaseError::DataInsertion {
+            table: CHANGES_TABLE.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(changes.len())
+}
+
+/// Cells of a sheet by row key and column
+struct KeyedCells<'a> {
+    keys: Vec<String>,
+    cells: BTreeMap<String, BTreeMap<&'a str, &'a Value>>,
+}
+
+impl KeyedCells<'_> {
+    fn get(&self, key: &str, column: &str) -> Value {
+        self.cells.get(key).and_then(|row| row.get(column)).map_or(Value::Null, |value| (*value).clone())
+    }
+}
+
+fn keyed_cells(sheet: &ReportSheet) -> KeyedCells<'_> {
+    let mut keys = Vec::new();
+    let mut cells = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+    
+    for row in &sheet.rows {
+        let base = row.first().map(key_text).unwrap_or_default();
+        let key = (1..)
+            .map(|n| if n == 1 { base.clone() } else { format!("{} #{}", base, n) })
+            .find(|key| !seen.contains(key))
+            .unwrap_or(base);
+        seen.insert(key.clone());
+        
+        let values = sheet.columns.iter().zip(row).skip(1)
+            .map(|(column, value)| (column.as_str(), value))
+            .collect();
+        cells.insert(key.clone(), values);
+        keys.push(key);
+    }
+    
+    KeyedCells { keys, cells }
+}
+
+/// Row key text; whole numbers lose the ".0" a workbook gives them
+fn key_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        Value::Number(number) => match number.as_f64() {
+            Some(float) if float.fract() == 0.0 && float.abs() < 1e15 => format!("{}", float as i64),
+            _ => number.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+fn same_value(previous: &Value, current: &Value) -> bool {
+    match (previous.as_f64(), current.as_f64()) {
+        (Some(previous), Some(current)) => (previous - current).abs() < TOLERANCE,
+        _ => previous == current,
+    }
+}
+
+fn cell_value(cell: &DataType) -> Value {
+    if cell.is_empty() {
+        return Value::Null;
+    }
+    if let Some(text) = cell.get_string() {
+        return Value::String(text.to_string());
+    }
+    match cell.get_float().or_else(|| cell.get_int().map(|i| i as f64)) {
+        Some(number) => Value::from(number),
+        None => Value::String(cell.to_string()),
+    }
+}
+
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Number(number) => number.as_i64().map(SqlValue::Integer)
+            .unwrap_or_else(|| SqlValue::Real(number.as_f64().unwrap_or_default())),
+        Value::String(text) => SqlValue::Text(text.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporting::{write_styled_sheet, SheetStyle};
+    use serde_json::json;
+    use tempfile::TempDir;
+    
+    fn sheet(columns: &[&str], rows: Vec<Vec<Value>>) -> ReportSheet {
+        ReportSheet { columns: columns.iter().map(|c| c.to_string()).collect(), rows }
+    }
+    
+    #[test]
+    fn test_diff_cells() {
+        let previous = Snapshot::from([
+            ("HIST_MES".to_string(), sheet(&["AnoMes", "Mercado", "Luz"], vec![
+                vec![json!("2024/01"), json!(100.0), json!(50.0)],
+                vec![json!("2024/02"), json!(80.0), json!(null)],
+            ])),
+            ("Removida".to_string(), sheet(&["A"], vec![vec![json!(1)]])),
+        ]);
+        let current = Snapshot::from([
+            ("HIST_MES".to_string(), sheet(&["AnoMes", "Mercado", "Luz", "Lazer"], vec![
+                vec![json!("2024/01"), json!(100.001), json!(50), json!(null)],
+                vec![json!("2024/02"), json!(95.5), json!(null), json!(20.0)],
+                vec![json!("2024/03"), json!(10.0), json!(null), json!(null)],
+            ])),
+            ("Nova".to_string(), sheet(&["A"], vec![vec![json!(1)]])),
+        ]);
+        
+        let changes = diff(&previous, &current);
+        let cells: Vec<(&str, &str, Option<f64>)> = changes.iter()
+            .map(|c| (c.key.as_str(), c.column.as_str(), c.difference()))
+            .collect();
+        assert_eq!(cells, vec![
+            ("2024/02", "Mercado", Some(15.5)),
+            ("2024/02", "Lazer", Some(20.0)),
+            ("2024/03", "Mercado", Some(10.0)),
+        ]);
+        assert_eq!((&changes[0].previous, &changes[0].current), (&json!(80.0), &json!(95.5)));
+        
+        let db = DatabaseManager::new(Path::new(":memory:")).unwrap();
+        assert_eq!(write_changes_table(&db, &changes).unwrap(), 3);
+        let rows = db.execute_query(&format!("SELECT Chave, Anterior, Atual FROM {} WHERE Coluna = 'Lazer'", CHANGES_TABLE)).unwrap();
+        assert_eq!(rows, vec![vec![json!("2024/02"), json!(null), json!(20.0)]]);
+    }
+    
+    #[test]
+    fn test_workbook_snapshot() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("previous.xlsx");
+        let columns = vec!["Ano".to_string(), "Total".to_string()];
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name("HIST_ANO").unwrap();
+        write_styled_sheet(worksheet, &columns, &[vec![json!(2024), json!(10.5)]], &SheetStyle::default(), "#,##0.00").unwrap();
+        workbook.save(&path).unwrap();
+        
+        let previous = workbook_snapshot(&path).unwrap();
+        assert_eq!(previous["HIST_ANO"], sheet(&["Ano", "Total"], vec![vec![json!(2024.0), json!(10.5)]]));
+        
+        // Database values match the workbook ones: whole numbers and integers alike
+        let current = Snapshot::from([("HIST_ANO".to_string(), sheet(&["Ano", "Total"], vec![vec![json!(2024), json!(12)]]))]);
+        let changes = diff(&previous, &current);
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].key.as_str(), changes[0].difference()), ("2024", Some(1.5)));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,289 @@
+/*!
+# Report Dispatch Module
+
+Output targets of the YAML queries, so one PDW_QUERIES.yaml drives every export
+and not only the report workbook. A query without `output` is a sheet of the
+report workbook, as before; with one, the `ReportDispatcher` sends it where it
+asks:
+
+```yaml
+  - sql: "SELECT * FROM Resumido_In_Out"
+    sheet_name: "Resumo"
+    output:
+      format: csv               # xlsx | csv | json | parquet
+      file: "resumo_{current_year}.csv"
+      table: RESUMO_EXPORT      # also replaces this table of the database
+```
+
+- `file` is written under `dir_out`; without it the file is named after the
+  sheet with the format's extension. The format defaults to the file's
+  extension.
+- `xlsx` without a `file` is the report workbook; with one, a workbook of its
+  own holding the single sheet.
+- `table` recreates the table from the query; with neither `format` nor `file`
+  the table is the only output.
+
+File names and tables take the same {placeholders} as the SQL.
+
+The SQL runs on a connection refusing writes (`PRAGMA query_only`), so a stray
+`DROP TABLE` in the YAML fails instead of emptying the warehouse; a `table`
+output is created by PDW around the query. A query that must change the
+database, such as a preparation step, opts in with `writes: true`: its SQL runs
+as a script, in order with the other queries, and produces no sheet.
+*/
+
+use crate::database::quote_identifier;
+use crate::deterministic;
+use crate::error::{DatabaseError, PdwError, ReportError};
+use crate::reporting::{QueryDefinition, ReportGenerator};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File layout of a query output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Xlsx,
+    Csv,
+    Json,
+    Parquet,
+}
+
+/// Where a query's results go instead of the report workbook
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryOutput {
+    /// File layout (defaults to the extension of `file`)
+    #[serde(default)]
+    pub format: Option<ReportFormat>,
+    /// File name under dir_out (defaults to the sheet name)
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Database table replaced with the results
+    #[serde(default)]
+    pub table: Option<String>,
+}
+
+impl ReportFormat {
+    /// Format of a file name's extension
+    pub fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "xlsx" => Some(ReportFormat::Xlsx),
+            "csv" => Some(ReportFormat::Csv),
+            "json" => Some(ReportFormat::Json),
+            "parquet" => Some(ReportFormat::Parquet),
+            _ => None,
+        }
+    }
+    
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Xlsx => "xlsx",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+            ReportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl QueryOutput {
+    /// Format of the file output, None when there is no file output of its own
+    fn file_format(&self) -> Result<Option<ReportFormat>, PdwError> {
+        match (self.format, &self.file) {
+            (Some(format), _) => Ok(Some(format)),
+            (None, Some(file)) => ReportFormat::of(Path::new(file))
+                .map(Some)
+                .ok_or_else(|| ReportError::UnsupportedFormat { format: file.clone() }.into()),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Sends each query to the report workbook, its own file or a table
+pub struct ReportDispatcher<'a> {
+    generator: &'a ReportGenerator,
+}
+
+impl<'a> ReportDispatcher<'a> {
+    pub fn new(generator: &'a ReportGenerator) -> Self {
+        Self { generator }
+    }
+    
+    /// Write an expanded query to its targets; `workbook` is the report workbook
+    pub fn dispatch(&self, workbook: &mut rust_xlsxwriter::Workbook, query_def: &QueryDefinition) -> Result<(), PdwError> {
+        let _query = tracing::info_span!("query", query = %query_def.sheet_name).entered();
+        if query_def.writes {
+            return self.run_script(query_def);
+        }
+        let database = self.generator.database();
+        let Some(output) = &query_def.output else {
+            return database.read_only(|_| self.generator.add_query_sheet(workbook, query_def));
+        };
+        
+        if let Some(table) = &output.table {
+            self.write_table(&query_def.sql, table)?;
+        }
+        
+        let format = output.file_format()?;
+        if format.is_none() && output.table.is_some() {
+            return Ok(());
+        }
+        database.read_only(|_| match (format.unwrap_or(ReportFormat::Xlsx), &output.file) {
+            (ReportFormat::Xlsx, None) => self.generator.add_query_sheet(workbook, query_def),
+            (format, file) => {
+                let file = file.clone()
+                    .unwrap_or_else(|| format!("{}.{}", query_def.sheet_name, format.extension()));
+                let path = self.generator.config().directories.dir_out.join(file);
+                self.write_file(format, query_def, &path)
+            }
+        })
+    }
+    
+    /// Run a query that opted in to changing the database
+    fn run_script(&self, query_def: &QueryDefinition) -> Result<(), PdwError> {
+        self.generator.database().connection().execute_batch(&query_def.sql)
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: query_def.sql.clone(),
+                reason: e.to_string(),
+            })?;
+        
+        tracing::info!("Script {} executed", query_def.sheet_name);
+        Ok(())
+    }
+    
+    /// Write the results to a file of their own
+    fn write_file(&self, format: ReportFormat, query_def: &QueryDefinition, path: &Path) -> Result<(), PdwError> {
+        match format {
+            ReportFormat::Xlsx => {
+                let mut workbook = deterministic::workbook();
+                self.generator.write_query_sheet(&mut workbook, query_def)?;
+                self.generator.config().branding.apply_to_workbook(&mut workbook);
+                workbook.save(path).map_err(ReportError::ExcelWriter)?;
+            }
+            ReportFormat::Csv => self.generator.write_csv(&query_def.sql, path)?,
+            ReportFormat::Json => self.generator.write_json(&query_def.sql, path)?,
+            ReportFormat::Parquet => self.generator.export_parquet(&query_def.sql, path)?,
+        }
+        
+        tracing::info!("Query {} written to {}", query_def.sheet_name, path.display());
+        Ok(())
+    }
+    
+    /// Recreate a table from the query
+    fn write_table(&self, sql: &str, table: &str) -> Result<(), PdwError> {
+        let settings = &self.generator.config().settings;
+        if [&settings.general_entries_table, &settings.types_of_entries].iter().any(|name| name.eq_ignore_ascii_case(table)) {
+            return Err(ReportError::OutputGeneration {
+                format: "table".to_string(),
+                reason: format!("{} is loaded from the input and cannot be a query output", table),
+            }.into());
+        }
+        
+        // A single statement whose only write is the CREATE: the query is a subquery
+        let database = self.generator.database();
+        database.drop_table(&quote_identifier(table))?;
+        let create_query = format!(
+            "CREATE TABLE {} AS SELECT * FROM ({})",
+            quote_identifier(table),
+            sql.trim().trim_end_matches(';'),
+        );
+        database.connection().execute(&create_query, [])
+            .map_err(|e| DatabaseError::SqlExecution {
+                query: create_query.clone(),
+                reason: e.to_string(),
+            })?;
+        
+        tracing::info!("Query output table {} created", table);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PdwConfig;
+    use crate::database::DatabaseManager;
+    use crate::reporting::QueryConfig;
+    use calamine::Reader;
+    
+    #[test]
+    fn test_queries_dispatched_to_their_outputs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_out = temp_dir.path().to_path_buf();
+        config.settings.run_dynamic_report = false;
+        let queries: QueryConfig = serde_yaml::from_str(r#"
+queries_padrao:
+  - sql: "SELECT Origem, DEBITO FROM Resumido_In_Out"
+    sheet_name: "Resumo"
+  - sql: "SELECT Origem, SUM(DEBITO) FROM Resumido_In_Out GROUP BY Origem"
+    sheet_name: "Por Origem"
+    output:
+      file: "origens_{entries_table}.csv"
+      table: ORIGENS_EXPORT
+  - sql: "SELECT AnoMes FROM Resumido_In_Out"
+    sheet_name: "Meses"
+    output:
+      table: MESES_EXPORT
+  - sql: "SELECT * FROM Resumido_In_Out"
+    sheet_name: "Completo"
+    output:
+      format: xlsx
+      file: "completo.xlsx"
+"#).unwrap();
+        
+        let database = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        database.connection().execute_batch(
+            "CREATE TABLE Resumido_In_Out (AnoMes TEXT, Origem TEXT, DEBITO REAL);
+             INSERT INTO Resumido_In_Out VALUES ('2024/01', 'Conta', 42.5), ('2024/02', 'Cartao', 10);"
+        ).unwrap();
+        
+        let report_path = config.get_report_path();
+        let generator = ReportGenerator::new(database, config).with_queries(queries);
+        generator.generate_excel_reports().unwrap();
+        
+        let report: calamine::Xlsx<_> = calamine::open_workbook(&report_path).unwrap();
+        assert_eq!(report.sheet_names().to_vec(), vec!["Resumo", crate::report_dictionary::DICTIONARY_SHEET]);
+        let own: calamine::Xlsx<_> = calamine::open_workbook(temp_dir.path().join("completo.xlsx")).unwrap();
+        assert_eq!(own.sheet_names().to_vec(), vec!["Completo"]);
+        let csv = std::fs::read_to_string(temp_dir.path().join("origens_LANCAMENTOS_GERAIS.csv")).unwrap();
+        assert_eq!(csv.lines().count(), 2);
+        let count = |table: &str| generator.database().execute_query(&format!("SELECT COUNT(*) FROM {}", table)).unwrap();
+        assert_eq!(count("ORIGENS_EXPORT"), vec![vec![serde_json::json!(2)]]);
+        assert_eq!(count("MESES_EXPORT"), vec![vec![serde_json::json!(2)]]);
+        
+        // Tables loaded from the input are never replaced
+        assert!(ReportDispatcher::new(&generator).write_table("SELECT 1", "lancamentos_gerais").is_err());
+    }
+    
+    #[test]
+    fn test_yaml_sql_runs_read_only() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_out = temp_dir.path().to_path_buf();
+        let database = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        database.create_tables().unwrap();
+        let generator = ReportGenerator::new(database, config);
+        let dispatcher = ReportDispatcher::new(&generator);
+        let mut workbook = deterministic::workbook();
+        let query = |sql: &str, output: Option<QueryOutput>, writes: bool| QueryDefinition {
+            sql: sql.to_string(),
+            sheet_name: "Teste".to_string(),
+            output,
+            writes,
+            ..QueryDefinition::default()
+        };
+        let tables = || generator.database().table_columns("LANCAMENTOS_GERAIS").unwrap().len();
+        
+        // A stray DROP is refused, and so is one smuggled into a table output
+        assert!(dispatcher.dispatch(&mut workbook, &query("DROP TABLE LANCAMENTOS_GERAIS", None, false)).is_err());
+        let smuggled = QueryOutput { table: Some("COPIA".to_string()), ..QueryOutput::default() };
+        let _ = dispatcher.dispatch(&mut workbook, &query("SELECT 1); DROP TABLE LANCAMENTOS_GERAIS; --", Some(smuggled), false));
+        assert!(tables() > 0);
+        
+        // Writes go through once the query opts in, and the connection is writable again
+        dispatcher.dispatch(&mut workbook, &query("CREATE TABLE AUXILIAR (x); INSERT INTO AUXILIAR VALUES (1)", None, true)).unwrap();
+        assert_eq!(generator.database().execute_query("SELECT x FROM AUXILIAR").unwrap(), vec![vec![serde_json::json!(1)]]);
+        generator.database().drop_table("AUXILIAR").unwrap();
+    }
+}
\ No newline at end of file
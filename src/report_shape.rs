@@ -0,0 +1,190 @@
+/*!
+# Report Shape Module
+
+Long (tidy) and wide layouts of a report query (`shape: long|wide` in the
+YAML), so the same SQL serves consumers of either format:
+
+- `long` unpivots: the key columns are kept and every other column becomes a
+  row holding its name ("Coluna") and value ("Valor"); empty cells are left out.
+  The monthly pivot (AnoMes, ALM, LAZ, ...) becomes AnoMes, Coluna, Valor.
+- `wide` pivots back: after the key columns, the distinct values of the next
+  column become columns holding the values of the last one; numbers falling in
+  the same cell are added.
+
+The key columns are `shape_keys`, by default the first column for `long` and
+every column but the last two for `wide`.
+*/
+
+use crate::error::{PdwError, ReportError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Name column of the long layout
+pub const NAME_COLUMN: &str = "Coluna";
+
+/// Value column of the long layout
+pub const VALUE_COLUMN: &str = "Valor";
+
+/// Layout a query result is converted to before it is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    Wide,
+    Long,
+}
+
+/// Query result as columns and rows
+pub type Table = (Vec<String>, Vec<Vec<Value>>);
+
+/// Convert a query result to `shape`, keeping the `keys` columns (defaults when empty)
+pub fn reshape(shape: Shape, keys: &[String], columns: Vec<String>, rows: Vec<Vec<Value>>) -> Result<Table, PdwError> {
+    let key_indexes = match keys {
+        [] => {
+            let count = match shape {
+                Shape::Long => 1.min(columns.len()),
+                Shape::Wide => columns.len().saturating_sub(2),
+            };
+            (0..count).collect()
+        }
+        keys => keys.iter()
+            .map(|key| columns.iter().position(|column| column.eq_ignore_ascii_case(key))
+                .ok_or_else(|| shape_error(&format!("key column '{}' not in the query result", key))))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    let others: Vec<usize> = (0..columns.len()).filter(|idx| !key_indexes.contains(idx)).collect();
+    
+    match shape {
+        Shape::Long => Ok(unpivot(&key_indexes, &others, &columns, rows)),
+        Shape::Wide => match others[..] {
+            [name, value] => Ok(pivot(&key_indexes, name, value, &columns, rows)),
+            _ => Err(shape_error(&format!(
+                "wide needs exactly two columns besides the keys (a name and a value), got {}",
+                others.len()
+            ))),
+        },
+    }
+}
+
+/// Key columns, then one row per other column with a value
+fn unpivot(keys: &[usize], others: &[usize], columns: &[String], rows: Vec<Vec<Value>>) -> Table {
+    let mut long_columns: Vec<String> = keys.iter().map(|&idx| columns[idx].clone()).collect();
+    long_columns.extend([NAME_COLUMN.to_string(), VALUE_COLUMN.to_string()]);
+    
+    let mut long_rows = Vec::new();
+    for row in rows {
+        for &idx in others {
+            let value = row.get(idx).cloned().unwrap_or(Value::Null);
+            if value.is_null() {
+                continue;
+            }
+            let mut long_row: Vec<Value> = keys.iter().map(|&key| row.get(key).cloned().unwrap_or(Value::Null)).collect();
+            long_row.push(Value::String(columns[idx].clone()));
+            long_row.push(value);
+            long_rows.push(long_row);
+        }
+    }
+    (long_columns, long_rows)
+}
+
+/// Key columns, then one column per distinct name, in order of appearance
+fn pivot(keys: &[usize], name: usize, value: usize, columns: &[String], rows: Vec<Vec<Value>>) -> Table {
+    let mut names: Vec<String> = Vec::new();
+    let mut row_keys: Vec<Vec<Value>> = Vec::new();
+    let mut cells: HashMap<(usize, usize), Value> = HashMap::new();
+    
+    for row in rows {
+        let key: Vec<Value> = keys.iter().map(|&idx| row.get(idx).cloned().unwrap_or(Value::Null)).collect();
+        let row_idx = row_keys.iter().position(|existing| *existing == key).unwrap_or_else(|| {
+            row_keys.push(key);
+            row_keys.len() - 1
+        });
+        let name = match row.get(name) {
+            Some(Value::String(text)) => text.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(other) => other.to_string(),
+        };
+        let col_idx = names.iter().position(|existing| *existing == name).unwrap_or_else(|| {
+            names.push(name);
+            names.len() - 1
+        });
+        
+        let cell = row.get(value).cloned().unwrap_or(Value::Null);
+        let entry = cells.entry((row_idx, col_idx)).or_insert(Value::Null);
+        *entry = match (&*entry, cell) {
+            (Value::Number(a), Value::Number(b)) => serde_json::Number::from_f64(a.as_f64().unwrap_or_default() + b.as_f64().unwrap_or_default())
+                .map_or(Value::Null, Value::Number),
+            (_, cell) => cell,
+        };
+    }
+    
+    let mut wide_columns: Vec<String> = keys.iter().map(|&idx| columns[idx].clone()).collect();
+    wide_columns.extend(names.iter().cloned());
+    let wide_rows = row_keys.into_iter().enumerate()
+        .map(|(row_idx, mut row)| {
+            row.extend((0..names.len()).map(|col_idx| cells.remove(&(row_idx, col_idx)).unwrap_or(Value::Null)));
+            row
+        })
+        .collect();
+    (wide_columns, wide_rows)
+}
+
+fn shape_error(reason: &str) -> PdwError {
+    ReportError::QueryProcessing {
+        query_name: "shape".to_string(),
+        reason: reason.to_string(),
+    }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn names(columns: &[&str]) -> Vec<String> {
+        columns.iter().map(|c| c.to_string()).collect()
+    }
+    
+    #[test]
+    fn test_long_and_back_to_wide() {
+        let columns = names(&["AnoMes", "ALM", "LAZ"]);
+        let rows = vec![
+            vec![json!("2024/01"), json!(52.5), json!(null)],
+            vec![json!("2024/02"), json!(30.0), json!(50.0)],
+        ];
+        
+        let (long_columns, long_rows) = reshape(Shape::Long, &[], columns.clone(), rows.clone()).unwrap();
+        assert_eq!(long_columns, names(&["AnoMes", NAME_COLUMN, VALUE_COLUMN]));
+        assert_eq!(long_rows, vec![
+            vec![json!("2024/01"), json!("ALM"), json!(52.5)],
+            vec![json!("2024/02"), json!("ALM"), json!(30.0)],
+            vec![json!("2024/02"), json!("LAZ"), json!(50.0)],
+        ]);
+        
+        let (wide_columns, wide_rows) = reshape(Shape::Wide, &[], long_columns, long_rows).unwrap();
+        assert_eq!((wide_columns, wide_rows), (columns, rows));
+    }
+    
+    #[test]
+    fn test_wide_adds_repeated_cells_and_checks_keys() {
+        let columns = names(&["Ano", "Origem", "TIPO", "Debito"]);
+        let rows = vec![
+            vec![json!("2024"), json!("Conta"), json!("ALM"), json!(10.0)],
+            vec![json!("2024"), json!("Conta"), json!("ALM"), json!(5.5)],
+            vec![json!("2024"), json!("Cartao"), json!("LAZ"), json!(7)],
+        ];
+        let (wide_columns, wide_rows) = reshape(Shape::Wide, &[], columns.clone(), rows.clone()).unwrap();
+        assert_eq!(wide_columns, names(&["Ano", "Origem", "ALM", "LAZ"]));
+        assert_eq!(wide_rows, vec![
+            vec![json!("2024"), json!("Conta"), json!(15.5), json!(null)],
+            vec![json!("2024"), json!("Cartao"), json!(null), json!(7)],
+        ]);
+        
+        assert!(reshape(Shape::Wide, &names(&["Ano"]), columns.clone(), rows.clone()).is_err());
+        assert!(reshape(Shape::Long, &names(&["Mes"]), columns.clone(), rows.clone()).is_err());
+        
+        let (long_columns, long_rows) = reshape(Shape::Long, &names(&["ano", "Origem", "TIPO"]), columns, rows).unwrap();
+        assert_eq!(long_columns, names(&["Ano", "Origem", "TIPO", NAME_COLUMN, VALUE_COLUMN]));
+        assert_eq!(long_rows[2], vec![json!("2024"), json!("Cartao"), json!("LAZ"), json!("Debito"), json!(7)]);
+    }
+}
\ No newline at end of file
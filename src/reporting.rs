@@ -1,28 +1,44 @@
 /*!
 # Reporting Module
 
-Handles report generation in multiple formats (Excel, CSV, JSON, XML)
-using YAML-defined queries and templates.
+Handles report generation in multiple formats (Excel, CSV, JSON, XML, and
+Parquet with the `parquet` feature) using YAML-defined queries and templates.
 */
 
-use crate::config::PdwConfig;
+use crate::cash_flow;
+use crate::compression;
+use crate::config::{PdwConfig, SettingsConfig};
 use crate::database::DatabaseManager;
+use crate::deterministic;
 use crate::error::{ReportError, PdwError};
+use crate::export_profiles::{self, ExportProfiles};
+use crate::export_schema::ExportDocument;
+use crate::ofx::{self, OfxStatement, OfxTransaction};
+use crate::report_charts::{self, ChartKind};
+use crate::report_diff;
+use crate::report_dictionary::{self, DictionaryEntry};
+use crate::report_dispatch::{QueryOutput, ReportDispatcher};
+use crate::report_shape::{self, Shape};
+use crate::tags;
+use chrono::{Datelike, Local, Months, NaiveDate};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 
 /// Report generator
 pub struct ReportGenerator {
     database: DatabaseManager,
     config: PdwConfig,
+    /// Queries given in code, used instead of the YAML file
+    queries: Option<QueryConfig>,
+    /// Sheets written to the report workbook so far, for its dictionary
+    dictionary: RefCell<Vec<DictionaryEntry>>,
 }
 
 /// YAML query configuration
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct QueryConfig {
     #[serde(default)]
     pub queries_gera_hist: Vec<QueryDefinition>,
@@ -30,85 +46,494 @@ pub struct QueryConfig {
     pub queries_padrao: Vec<QueryDefinition>,
 }
 
+impl QueryConfig {
+    /// The queries with the columns of the export profiles they name
+    pub fn with_profiles(mut self, profiles: &ExportProfiles) -> Result<Self, PdwError> {
+        for query in self.queries_gera_hist.iter_mut().chain(self.queries_padrao.iter_mut()) {
+            if let Some(name) = query.profile.take() {
+                query.sql = export_profiles::find(profiles, &name)?.apply(&query.sql);
+            }
+        }
+        Ok(self)
+    }
+}
+
 /// Individual query definition
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct QueryDefinition {
+    /// May be left out with a `profile`, to read the general entries table
+    #[serde(default)]
     pub sql: String,
     pub sheet_name: String,
+    #[serde(default)]
+    pub style: SheetStyle,
+    /// Chart drawn next to the data when settings.generate_charts is on
+    #[serde(default)]
+    pub chart: Option<ChartKind>,
+    /// Long (unpivoted) or wide (pivoted) layout of the result, as queried when unset
+    #[serde(default)]
+    pub shape: Option<Shape>,
+    /// Columns kept as keys by `shape` (first column for long, all but the last two for wide)
+    #[serde(default)]
+    pub shape_keys: Vec<String>,
+    /// Query-specific {name} placeholders; values may use the built-in variables
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+    /// File or table the results go to instead of the report workbook
+    #[serde(default)]
+    pub output: Option<QueryOutput>,
+    /// Run the SQL as a script that may change the database, producing no sheet;
+    /// every other query runs read-only
+    #[serde(default)]
+    pub writes: bool,
+    /// Export profile picking the columns of the result (see `[export_profiles]`)
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+impl QueryDefinition {
+    /// The query with its sql and sheet name placeholders replaced by its params and `variables`
+    pub fn expand(&self, variables: &HashMap<String, String>) -> QueryDefinition {
+        let mut all = variables.clone();
+        for (name, value) in &self.params {
+            all.insert(name.clone(), substitute_variables(value, variables));
+        }
+        
+        QueryDefinition {
+            sql: substitute_variables(&self.sql, &all),
+            sheet_name: substitute_variables(&self.sheet_name, &all),
+            output: self.output.as_ref().map(|output| QueryOutput {
+                file: output.file.as_ref().map(|file| substitute_variables(file, &all)),
+                table: output.table.as_ref().map(|table| substitute_variables(table, &all)),
+                ..output.clone()
+            }),
+            ..self.clone()
+        }
+    }
+}
+
+/// Optional presentation hints for an Excel report sheet
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SheetStyle {
+    /// Number format for real-valued columns (defaults to settings.currency_format)
+    #[serde(default)]
+    pub number_format: Option<String>,
+    /// Columns always written with the number format, even when they hold integers
+    #[serde(default)]
+    pub currency_columns: Vec<String>,
+    /// Columns always written as text, even when numeric (codes, account numbers)
+    #[serde(default)]
+    pub text_columns: Vec<String>,
+    /// Columns holding fractions, written as percentages (0.3 -> 30.0%)
+    #[serde(default)]
+    pub percent_columns: Vec<String>,
+    /// Rows whose first cell is one of these are subtotals: bold with a top border
+    #[serde(default)]
+    pub total_rows: Vec<String>,
+    #[serde(default = "default_true")]
+    pub autofilter: bool,
+    #[serde(default = "default_true")]
+    pub freeze_header: bool,
+    #[serde(default = "default_true")]
+    pub autofit: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SheetStyle {
+    fn default() -> Self {
+        Self {
+            number_format: None,
+            currency_columns: Vec::new(),
+            text_columns: Vec::new(),
+            percent_columns: Vec::new(),
+            total_rows: Vec::new(),
+            autofilter: true,
+            freeze_header: true,
+            autofit: true,
+        }
+    }
+}
+
+/// Engine running the YAML report queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportEngine {
+    /// Query the SQLite warehouse (all phases)
+    #[default]
+    Sqlite,
+    /// Query in-memory Arrow batches and Parquet files, no database file (`datafusion` feature)
+    DataFusion,
+}
+
+/// Load report queries from a YAML file
+pub fn load_query_file(yaml_path: &Path) -> Result<QueryConfig, PdwError> {
+    if !yaml_path.exists() {
+        return Err(ReportError::YamlQueryFile {
+            path: yaml_path.to_string_lossy().to_string(),
+            reason: "File not found".to_string(),
+        }.into());
+    }
+    
+    let content = std::fs::read_to_string(yaml_path)
+        .map_err(|e| ReportError::YamlQueryFile {
+            path: yaml_path.to_string_lossy().to_string(),
+            reason: e.to_string(),
+        })?;
+    
+    let config: QueryConfig = serde_yaml::from_str(&content)
+        .map_err(|e| ReportError::YamlParse(e))?;
+    
+    Ok(config)
+}
+
+/// Write a bold header row and the query rows, numbers as numbers, applying the sheet style
+pub fn write_styled_sheet(
+    worksheet: &mut rust_xlsxwriter::Worksheet,
+    columns: &[String],
+    rows: &[Vec<Value>],
+    style: &SheetStyle,
+    currency_format: &str,
+) -> Result<(), PdwError> {
+    let header_format = rust_xlsxwriter::Format::new().set_bold();
+    let number_format = rust_xlsxwriter::Format::new()
+        .set_num_format(style.number_format.as_deref().unwrap_or(currency_format));
+    let percent_format = rust_xlsxwriter::Format::new().set_num_format("0.0%");
+    let total_format = rust_xlsxwriter::Format::new().set_bold().set_border_top(rust_xlsxwriter::FormatBorder::Thin);
+    let total_number_format = number_format.clone().set_bold().set_border_top(rust_xlsxwriter::FormatBorder::Thin);
+    let listed = |names: &[String], column: &str| names.iter().any(|n| n.eq_ignore_ascii_case(column));
+    
+    // A column is monetary when listed or when any of its values is a real number
+    let monetary: Vec<bool> = (0..columns.len())
+        .map(|col_idx| {
+            listed(&style.currency_columns, &columns[col_idx])
+                || rows.iter().any(|row| matches!(row.get(col_idx), Some(Value::Number(n)) if n.is_f64()))
+        })
+        .collect();
+    let as_text: Vec<bool> = columns.iter().map(|c| listed(&style.text_columns, c)).collect();
+    let as_percent: Vec<bool> = columns.iter().map(|c| listed(&style.percent_columns, c)).collect();
+    
+    for (col_idx, column) in columns.iter().enumerate() {
+        worksheet.write_string_with_format(0, col_idx as u16, column, &header_format)
+            .map_err(ReportError::ExcelWriter)?;
+    }
+    
+    for (row_idx, row_data) in rows.iter().enumerate() {
+        let row = row_idx as u32 + 1;
+        let total = matches!(row_data.first(), Some(Value::String(label)) if listed(&style.total_rows, label));
+        for (col_idx, cell_value) in row_data.iter().enumerate() {
+            let col = col_idx as u16;
+            let written = match cell_value {
+                Value::Number(n) if as_percent.get(col_idx).copied().unwrap_or(false) => {
+                    worksheet.write_number_with_format(row, col, n.as_f64().unwrap_or_default(), &percent_format).map(|_| ())
+                }
+                Value::Number(n) if !as_text.get(col_idx).copied().unwrap_or(false) => {
+                    let number = n.as_f64().unwrap_or_default();
+                    match (monetary.get(col_idx).copied().unwrap_or(false), total) {
+                        (true, true) => worksheet.write_number_with_format(row, col, number, &total_number_format).map(|_| ()),
+                        (true, false) => worksheet.write_number_with_format(row, col, number, &number_format).map(|_| ()),
+                        (false, true) => worksheet.write_number_with_format(row, col, number, &total_format).map(|_| ()),
+                        (false, false) => worksheet.write_number(row, col, number).map(|_| ()),
+                    }
+                }
+                Value::Null if total => worksheet.write_blank(row, col, &total_format).map(|_| ()),
+                Value::Null => Ok(()),
+                Value::String(s) if total => worksheet.write_string_with_format(row, col, s, &total_format).map(|_| ()),
+                Value::String(s) => worksheet.write_string(row, col, s).map(|_| ()),
+                other => worksheet.write_string(row, col, other.to_string()).map(|_| ()),
+            };
+            written.map_err(ReportError::ExcelWriter)?;
+        }
+    }
+    
+    let last_col = columns.len().saturating_sub(1) as u16;
+    if style.autofilter && !columns.is_empty() {
+        worksheet.autofilter(0, 0, rows.len() as u32, last_col)
+            .map_err(ReportError::ExcelWriter)?;
+    }
+    if style.freeze_header {
+        worksheet.set_freeze_panes(1, 0)
+            .map_err(ReportError::ExcelWriter)?;
+    }
+    if style.autofit {
+        worksheet.autofit();
+    }
+    
+    Ok(())
+}
+
+/// Variables available to YAML queries as {name}
+pub(crate) fn query_variables(settings: &SettingsConfig, collate: String) -> HashMap<String, String> {
+    let mut variables = HashMap::new();
+    
+    variables.insert("entries_table".to_string(), settings.general_entries_table.clone());
+    variables.insert("full_hist".to_string(), settings.full_pivot_table.clone());
+    variables.insert("anual_hist".to_string(), settings.anual_pivot_table.clone());
+    variables.insert("day_prog".to_string(), settings.daily_progress.clone());
+    variables.insert("splt_pmnt_res".to_string(), settings.out_res_pmnt_tab.clone());
+    variables.insert("mont_summ".to_string(), settings.monthly_summaries.clone());
+    variables.insert("dyn_rep_tab".to_string(), settings.din_report_guiding.clone());
+    variables.insert("collate".to_string(), collate);
+    variables.insert("tags".to_string(), tags::tagged_entries());
+    variables.extend(date_variables(Local::now().date_naive()));
+    
+    variables
+}
+
+/// Date placeholders relative to `today`: days as YYYY-MM-DD, months as AnoMes (YYYY/MM)
+pub(crate) fn date_variables(today: NaiveDate) -> HashMap<String, String> {
+    let month_start = today.with_day(1).unwrap_or(today);
+    let last_month_start = month_start.checked_sub_months(Months::new(1)).unwrap_or(month_start);
+    let last_month_end = month_start.pred_opt().unwrap_or(month_start);
+    let day = |date: NaiveDate| date.format("%Y-%m-%d").to_string();
+    let year_month = |date: NaiveDate| date.format("%Y/%m").to_string();
+    
+    HashMap::from([
+        ("today".to_string(), day(today)),
+        ("current_year".to_string(), today.year().to_string()),
+        ("last_year".to_string(), (today.year() - 1).to_string()),
+        ("current_month".to_string(), year_month(today)),
+        ("last_month".to_string(), year_month(last_month_start)),
+        ("current_month_start".to_string(), day(month_start)),
+        ("last_month_start".to_string(), day(last_month_start)),
+        ("last_month_end".to_string(), day(last_month_end)),
+    ])
+}
+
+/// Replace {name} placeholders in a query template
+pub(crate) fn substitute_variables(template: &str, variables: &HashMap<String, String>) -> String {
+    let mut result = template.to_string();
+    
+    for (key, value) in variables {
+        let placeholder = format!("{{{}}}", key);
+        result = result.replace(&placeholder, value);
+    }
+    
+    result
 }
 
 impl ReportGenerator {
     /// Create new report generator
     pub fn new(database: DatabaseManager, config: PdwConfig) -> Self {
-        Self { database, config }
+        Self { database, config, queries: None, dictionary: RefCell::new(Vec::new()) }
+    }
+    
+    /// Use these queries instead of reading the YAML queries file
+    pub fn with_queries(mut self, queries: QueryConfig) -> Self {
+        self.queries = Some(queries);
+        self
+    }
+    
+    pub(crate) fn database(&self) -> &DatabaseManager {
+        &self.database
+    }
+    
+    pub(crate) fn config(&self) -> &PdwConfig {
+        &self.config
     }
     
     /// Load queries from YAML file
     pub fn load_queries(&self) -> Result<QueryConfig, PdwError> {
-        let yaml_path = self.config.get_yaml_queries_path();
-        
-        if !yaml_path.exists() {
-            return Err(ReportError::YamlQueryFile {
-                path: yaml_path.to_string_lossy().to_string(),
-                reason: "File not found".to_string(),
-            }.into());
-        }
-        
-        let content = std::fs::read_to_string(&yaml_path)
-            .map_err(|e| ReportError::YamlQueryFile {
-                path: yaml_path.to_string_lossy().to_string(),
-                reason: e.to_string(),
-            })?;
-        
-        let config: QueryConfig = serde_yaml::from_str(&content)
-            .map_err(|e| ReportError::YamlParse(e))?;
-        
-        Ok(config)
+        let queries = match &self.queries {
+            Some(queries) => queries.clone(),
+            None => load_query_file(&self.config.get_yaml_queries_path())?,
+        };
+        queries.with_profiles(&self.config.export_profiles)
     }
     
     /// Generate Excel reports
     pub fn generate_excel_reports(&self) -> Result<(), PdwError> {
         let query_config = self.load_queries()?;
-        let output_path = self.config.directories.dir_out.join(format!(
-            "{}.{}",
-            self.config.file_types.out_rpt_file,
-            self.config.file_types.type_out
-        ));
+        let output_path = self.config.get_report_path();
         
         // Create Excel workbook
-        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let mut workbook = deterministic::workbook();
+        self.dictionary.borrow_mut().clear();
+        self.config.branding.add_summary_sheet(
+            &mut workbook,
+            &self.database,
+            &self.config.settings.general_entries_table,
+            &self.config.directories.dir_in,
+        )?;
         
         // Variable substitution map
         let variables = self.create_variable_map();
+        let dispatcher = ReportDispatcher::new(self);
         
         // Process conditional queries (gera_hist)
         if self.config.settings.create_pivot {
             for query_def in &query_config.queries_gera_hist {
-                let sql = self.substitute_variables(&query_def.sql, &variables);
-                let sheet_name = self.substitute_variables(&query_def.sheet_name, &variables);
-                
-                self.add_query_to_workbook(&mut workbook, &sql, &sheet_name)?;
+                dispatcher.dispatch(&mut workbook, &query_def.expand(&variables))?;
             }
         }
         
-        // Process standard queries
+        // Process standard queries, each to the workbook or its own output
         for query_def in &query_config.queries_padrao {
-            let sql = self.substitute_variables(&query_def.sql, &variables);
-            let sheet_name = &query_def.sheet_name;
-            
-            self.add_query_to_workbook(&mut workbook, &sql, sheet_name)?;
+            dispatcher.dispatch(&mut workbook, &query_def.expand(&variables))?;
+        }
+        
+        // Monthly credit/debit, debits per TIPO and share of income charts
+        if self.config.settings.generate_charts {
+            self.add_chart_sheets(&mut workbook)?;
+        }
+        
+        // Process dynamic reports if enabled; their tables are named by the workbook
+        if self.config.settings.run_dynamic_report {
+            self.database.read_only(|_| self.add_dynamic_reports_to_workbook(&mut workbook))?;
+        }
+        
+        // Alerts triggered during the last load
+        if !self.config.alerts.is_empty()
+            && !self.database.table_columns(&self.config.settings.alerts_table)?.is_empty() {
+            let alerts_query = format!("SELECT * FROM {}", self.config.settings.alerts_table);
+            self.add_query_to_workbook(&mut workbook, &alerts_query, "Alertas", &SheetStyle::default())?;
+        }
+        
+        // Savings vs CDI/SELIC benchmark
+        if self.config.benchmark.enabled
+            && !self.database.table_columns(&self.config.benchmark.table)?.is_empty() {
+            let benchmark_query = format!("SELECT * FROM {}", self.config.benchmark.table);
+            let style = SheetStyle { currency_columns: vec!["Poupanca".to_string()], ..SheetStyle::default() };
+            self.add_query_to_workbook(&mut workbook, &benchmark_query, "Benchmark", &style)?;
+        }
+        
+        // Credit card invoices by statement cycle
+        if self.config.statement_cycles.is_enabled()
+            && !self.database.table_columns(&self.config.statement_cycles.table)?.is_empty() {
+            let cycles_query = format!("SELECT * FROM {}", self.config.statement_cycles.table);
+            self.add_query_to_workbook(&mut workbook, &cycles_query, "Faturas", &SheetStyle::default())?;
+        }
+        
+        // Bank fees, interest and IOF
+        if self.config.fees.enabled
+            && !self.database.table_columns(&self.config.fees.table)?.is_empty() {
+            let fees_query = format!(
+                "SELECT * FROM {} ORDER BY AnoMes DESC, Categoria, Origem",
+                self.config.fees.table
+            );
+            self.add_query_to_workbook(&mut workbook, &fees_query, "Tarifas", &SheetStyle::default())?;
+        }
+        
+        // Probable duplicate entries across origins
+        if self.config.duplicates.enabled
+            && !self.database.table_columns(&self.config.duplicates.table)?.is_empty() {
+            let duplicates_query = format!("SELECT * FROM {} ORDER BY Data DESC, Origem", self.config.duplicates.table);
+            let style = SheetStyle { currency_columns: vec!["Valor".to_string()], ..SheetStyle::default() };
+            self.add_query_to_workbook(&mut workbook, &duplicates_query, "Duplicados", &style)?;
+        }
+        
+        // Committed spend of the coming months
+        if self.config.forecast.enabled
+            && !self.database.table_columns(&self.config.forecast.table)?.is_empty() {
+            let forecast_query = format!("SELECT * FROM {} ORDER BY Data, Fonte, TIPO", self.config.forecast.table);
+            let style = SheetStyle { currency_columns: vec!["Debito".to_string()], ..SheetStyle::default() };
+            self.add_query_to_workbook(&mut workbook, &forecast_query, "Previsao", &style)?;
+        }
+        
+        // Cash-flow statement with bold subtotals, in the stored line order
+        if self.config.cash_flow.enabled
+            && !self.database.table_columns(&self.config.cash_flow.table)?.is_empty() {
+            let cash_flow_query = format!("SELECT * FROM {} ORDER BY rowid", self.config.cash_flow.table);
+            let style = SheetStyle {
+                total_rows: cash_flow::total_rows(),
+                autofilter: false,
+                ..SheetStyle::default()
+            };
+            self.add_query_to_workbook(&mut workbook, &cash_flow_query, "Fluxo de Caixa", &style)?;
+        }
+        
+        // Expense groups as a share of income and of expenses
+        if self.config.income_share.enabled
+            && !self.database.table_columns(&self.config.income_share.table)?.is_empty() {
+            let share_query = format!("SELECT * FROM {} ORDER BY AnoMes DESC, rowid", self.config.income_share.table);
+            let style = SheetStyle {
+                currency_columns: vec!["Despesa".to_string(), "Receita".to_string()],
+                percent_columns: vec!["PercentualRenda".to_string(), "PercentualDespesas".to_string()],
+                ..SheetStyle::default()
+            };
+            self.add_query_to_workbook(&mut workbook, &share_query, "Percentual Renda", &style)?;
+        }
+        
+        // Budgeted vs actual debits
+        if self.config.budgets.enabled
+            && !self.database.table_columns(&self.config.budgets.report_table)?.is_empty() {
+            let budget_query = format!("SELECT * FROM {}", self.config.budgets.report_table);
+            let style = SheetStyle {
+                currency_columns: vec!["Orcado".to_string(), "Realizado".to_string(), "Diferenca".to_string()],
+                ..SheetStyle::default()
+            };
+            self.add_query_to_workbook(&mut workbook, &budget_query, "Orcamento", &style)?;
+        }
+        
+        // Data quality violations per rule and origin
+        if self.config.quality.enabled
+            && !self.database.table_columns(&self.config.quality.table)?.is_empty() {
+            let quality_query = format!("SELECT * FROM {} ORDER BY Regra, Origem", self.config.quality.table);
+            self.add_query_to_workbook(&mut workbook, &quality_query, "Qualidade", &SheetStyle::default())?;
+        }
+        
+        // Running balances against the statement balances
+        if self.config.reconciliation.enabled
+            && !self.database.table_columns(&self.config.reconciliation.table)?.is_empty() {
+            let reconciliation_query = format!("SELECT * FROM {} ORDER BY Origem, Data", self.config.reconciliation.table);
+            let style = SheetStyle {
+                currency_columns: vec![
+                    "SaldoDeclarado".to_string(), "SaldoCalculado".to_string(),
+                    "Diferenca".to_string(), "DiferencaPeriodo".to_string(),
+                ],
+                ..SheetStyle::default()
+            };
+            self.add_query_to_workbook(&mut workbook, &reconciliation_query, "Reconciliacao", &style)?;
+        }
+        
+        // Broken accounting invariants, each with its drill-down query
+        if self.config.sanity.enabled
+            && !self.database.table_columns(&self.config.sanity.table)?.is_empty() {
+            let sanity_query = format!("SELECT * FROM {} ORDER BY rowid", self.config.sanity.table);
+            let style = SheetStyle {
+                currency_columns: vec!["Esperado".to_string(), "Encontrado".to_string(), "Diferenca".to_string()],
+                ..SheetStyle::default()
+            };
+            self.add_query_to_workbook(&mut workbook, &sanity_query, "Sanidade", &style)?;
         }
         
-        // Process dynamic reports if enabled
-        if self.config.settings.run_dinamic_report {
-            self.add_dynamic_reports_to_workbook(&mut workbook)?;
+        // Entries the load rejected, with where to fix them
+        let discarded_table = &self.config.settings.discarted_data_table;
+        if !self.database.table_columns(discarded_table)?.is_empty() {
+            let discarded_query = format!("SELECT * FROM {} ORDER BY Origem, Linha", discarded_table);
+            let style = SheetStyle { currency_columns: vec!["Credito".to_string(), "Debito".to_string()], ..SheetStyle::default() };
+            self.add_query_to_workbook(&mut workbook, &discarded_query, "Descartados", &style)?;
         }
         
-        // Save workbook
+        // Cells changed since the report given to --diff-against
+        if !self.database.table_columns(report_diff::CHANGES_TABLE)?.is_empty() {
+            let changes_query = format!("SELECT * FROM {}", report_diff::CHANGES_TABLE);
+            let style = SheetStyle { currency_columns: vec!["Diferenca".to_string()], ..SheetStyle::default() };
+            self.add_query_to_workbook(&mut workbook, &changes_query, report_diff::CHANGES_SHEET, &style)?;
+        }
+        
+        // GUIDING/TiposLancamentos consistency findings
+        if self.config.settings.check_consistency
+            && !self.database.table_columns(&self.config.settings.consistency_table)?.is_empty() {
+            let consistency_query = format!("SELECT * FROM {}", self.config.settings.consistency_table);
+            self.add_query_to_workbook(&mut workbook, &consistency_query, "Consistencia", &SheetStyle::default())?;
+        }
+        
+        // Where the numbers of every sheet come from
+        let entries = self.dictionary.take();
+        if self.config.settings.data_dictionary && !entries.is_empty() {
+            let exclusions = self.config.analytics.exclusions.describe(&self.config.category_groups);
+            report_dictionary::add_dictionary_sheet(&mut workbook, &self.database, &entries, &exclusions, &self.config.settings.currency_format)?;
+        }
+        
+        // Save workbook, title and footer on every sheet
+        self.config.branding.apply_to_workbook(&mut workbook);
         workbook.save(&output_path)
             .map_err(|e| ReportError::ExcelWriter(e))?;
         
-        log::info!("Excel reports generated: {}", output_path.display());
+        tracing::info!("Excel reports generated: {}", output_path.display());
         Ok(())
     }
     
@@ -118,31 +543,104 @@ impl ReportGenerator {
         workbook: &mut rust_xlsxwriter::Workbook,
         sql: &str,
         sheet_name: &str,
+        style: &SheetStyle,
+    ) -> Result<(), PdwError> {
+        let query_def = QueryDefinition {
+            sql: sql.to_string(),
+            sheet_name: sheet_name.to_string(),
+            style: style.clone(),
+            ..QueryDefinition::default()
+        };
+        let _query = tracing::info_span!("query", query = sheet_name).entered();
+        self.add_query_sheet(workbook, &query_def)
+    }
+    
+    /// Add the results of a query definition to the report workbook, noted in its dictionary
+    pub(crate) fn add_query_sheet(
+        &self,
+        workbook: &mut rust_xlsxwriter::Workbook,
+        query_def: &QueryDefinition,
     ) -> Result<(), PdwError> {
-        let results = self.database.execute_query(sql)?;
+        if let Some(rows) = self.write_query_sheet(workbook, query_def)? {
+            self.dictionary.borrow_mut().push(DictionaryEntry::new(&query_def.sheet_name, &query_def.sql, rows));
+        }
+        Ok(())
+    }
+    
+    /// Write the results of a query definition as a sheet, reshaped and charted as it asks;
+    /// returns the rows written, None when there were none and no sheet was added
+    pub(crate) fn write_query_sheet(
+        &self,
+        workbook: &mut rust_xlsxwriter::Workbook,
+        query_def: &QueryDefinition,
+    ) -> Result<Option<usize>, PdwError> {
+        let mut results = self.database.execute_query(&query_def.sql)?;
+        deterministic::sort_unordered(&query_def.sql, &mut results);
         
         if results.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
         
-        let mut worksheet = workbook.add_worksheet();
-        worksheet.set_name(sheet_name)
+        let mut columns = self.database.query_columns(&query_def.sql)?;
+        if let Some(shape) = query_def.shape {
+            (columns, results) = report_shape::reshape(shape, &query_def.shape_keys, columns, results)
+                .map_err(|e| ReportError::QueryProcessing {
+                    query_name: query_def.sheet_name.clone(),
+                    reason: e.to_string(),
+                })?;
+        }
+        
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(&query_def.sheet_name)
             .map_err(|e| ReportError::ExcelWriter(e))?;
         
-        // Write data to worksheet
-        for (row_idx, row_data) in results.iter().enumerate() {
-            for (col_idx, cell_value) in row_data.iter().enumerate() {
-                let value = match cell_value {
-                    Value::String(s) => s.clone(),
-                    Value::Number(n) => n.to_string(),
-                    Value::Bool(b) => b.to_string(),
-                    Value::Null => String::new(),
-                    _ => cell_value.to_string(),
-                };
-                
-                worksheet.write_string(row_idx as u32, col_idx as u16, &value)
-                    .map_err(|e| ReportError::ExcelWriter(e))?;
-            }
+        write_styled_sheet(worksheet, &columns, &results, &query_def.style, &self.config.settings.currency_format)?;
+        if let Some(kind) = query_def.chart.filter(|_| self.config.settings.generate_charts) {
+            report_charts::insert_query_chart(worksheet, kind, &query_def.sheet_name, &columns, &results)?;
+        }
+        Ok(Some(results.len()))
+    }
+    
+    /// Built-in chart sheets: monthly credit/debit (line), debits per TIPO and month (stacked)
+    /// and expense groups' share of income per month (stacked)
+    fn add_chart_sheets(&self, workbook: &mut rust_xlsxwriter::Workbook) -> Result<(), PdwError> {
+        let settings = &self.config.settings;
+        
+        if !self.database.table_columns(&settings.monthly_summaries)?.is_empty() {
+            let monthly_query = format!(
+                "SELECT AnoMes, ROUND(SUM(CREDITO), 2) AS Credito, ROUND(SUM(DEBITO), 2) AS Debito
+                 FROM {} GROUP BY AnoMes ORDER BY AnoMes",
+                settings.monthly_summaries
+            );
+            self.add_query_sheet(workbook, &QueryDefinition {
+                sql: monthly_query,
+                sheet_name: report_charts::MONTHLY_CHART_SHEET.to_string(),
+                chart: Some(ChartKind::Line),
+                ..QueryDefinition::default()
+            })?;
+        }
+        
+        if settings.create_pivot && !self.database.table_columns(&settings.full_pivot_table)?.is_empty() {
+            let pivot_query = format!("SELECT * FROM {} ORDER BY AnoMes", settings.full_pivot_table);
+            self.add_query_sheet(workbook, &QueryDefinition {
+                sql: pivot_query,
+                sheet_name: report_charts::PIVOT_CHART_SHEET.to_string(),
+                chart: Some(ChartKind::Stacked),
+                ..QueryDefinition::default()
+            })?;
+        }
+        
+        // One column per expense group, months as rows
+        let share_table = &self.config.income_share.table;
+        if self.config.income_share.enabled && !self.database.table_columns(share_table)?.is_empty() {
+            self.add_query_sheet(workbook, &QueryDefinition {
+                sql: format!("SELECT AnoMes, Grupo, PercentualRenda FROM {} ORDER BY AnoMes, rowid", share_table),
+                sheet_name: report_charts::INCOME_CHART_SHEET.to_string(),
+                style: SheetStyle { number_format: Some("0.0%".to_string()), ..SheetStyle::default() },
+                chart: Some(ChartKind::Stacked),
+                shape: Some(Shape::Wide),
+                ..QueryDefinition::default()
+            })?;
         }
         
         Ok(())
@@ -162,11 +660,17 @@ impl ReportGenerator {
         
         for report_row in dynamic_reports {
             if report_row.len() >= 2 {
-                if let (Some(Value::String(dest_table)), Some(Value::String(report_name))) = 
+                if let (Some(Value::String(dest_table)), Some(Value::String(report_name))) =
                     (report_row.get(0), report_row.get(1)) {
                     
                     let query = format!("SELECT * FROM {}", dest_table);
-                    self.add_query_to_workbook(workbook, &query, report_name)?;
+                    self.add_query_to_workbook(workbook, &query, report_name, &SheetStyle::default())?;
+                    
+                    if self.config.settings.export_parquet {
+                        let parquet_path = self.config.directories.dir_out
+                            .join(format!("{}.parquet", file_stem(report_name)));
+                        self.export_parquet(&query, &parquet_path)?;
+                    }
                 }
             }
         }
@@ -174,12 +678,19 @@ impl ReportGenerator {
         Ok(())
     }
     
-    /// Export data to CSV format
+    /// Export data to CSV format, compressed as `[export.compression]` says
     pub fn export_csv(&self, query: &str, output_path: &Path) -> Result<(), PdwError> {
-        let results = self.database.execute_query(query)?;
+        self.write_csv(query, output_path)?;
+        self.compress_export(output_path)
+    }
+    
+    /// Write query results as CSV under the given name
+    pub(crate) fn write_csv(&self, query: &str, output_path: &Path) -> Result<(), PdwError> {
+        let mut results = self.database.execute_query(query)?;
+        deterministic::sort_unordered(query, &mut results);
         
         let mut writer = csv::WriterBuilder::new()
-            .delimiter(b';')
+            .delimiter(self.config.locale.csv_delimiter as u8)
             .from_path(output_path)
             .map_err(|e| ReportError::CsvWriter(e))?;
         
@@ -187,7 +698,7 @@ impl ReportGenerator {
             let string_row: Vec<String> = row_data.iter()
                 .map(|v| match v {
                     Value::String(s) => s.clone(),
-                    Value::Number(n) => n.to_string().replace(".", ","), // Portuguese decimal format
+                    Value::Number(n) => self.config.locale.format_number(n),
                     Value::Bool(b) => b.to_string(),
                     Value::Null => String::new(),
                     _ => v.to_string(),
@@ -204,57 +715,139 @@ impl ReportGenerator {
         Ok(())
     }
     
-    /// Export data to JSON format
+    /// Export data to JSON format, in the configured export schema version
     pub fn export_json(&self, query: &str, output_path: &Path) -> Result<(), PdwError> {
-        let results = self.database.execute_query(query)?;
-        
-        let json_data = serde_json::to_string_pretty(&results)
-            .map_err(|e| ReportError::JsonSerialization(e))?;
-        
+        self.write_json(query, output_path)?;
+        self.compress_export(output_path)
+    }
+    
+    /// Write query results as JSON under the given name
+    pub(crate) fn write_json(&self, query: &str, output_path: &Path) -> Result<(), PdwError> {
+        let json_data = self.export_document(query)?.to_json()?;
         std::fs::write(output_path, json_data)?;
-        
-        // Compress if configured
-        if self.config.settings.export_other_types {
-            self.compress_file(output_path)?;
-        }
-        
         Ok(())
     }
     
-    /// Export data to XML format
+    /// Export data to XML format, in the configured export schema version
     pub fn export_xml(&self, query: &str, output_path: &Path) -> Result<(), PdwError> {
-        let results = self.database.execute_query(query)?;
+        let xml_content = self.export_document(query)?.to_xml();
+        std::fs::write(output_path, xml_content)?;
+        self.compress_export(output_path)
+    }
+    
+    /// Compress an export per `[export.compression]`
+    fn compress_export(&self, output_path: &Path) -> Result<(), PdwError> {
+        compression::compress_file(output_path, &self.config.export.compression)?;
+        Ok(())
+    }
+    
+    /// Query results in `settings.export_schema_version`
+    fn export_document(&self, query: &str) -> Result<ExportDocument, PdwError> {
+        let mut results = self.database.execute_query(query)?;
+        deterministic::sort_unordered(query, &mut results);
         
-        let mut xml_content = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<data>\n");
+        ExportDocument::new(self.database.query_columns(query)?, results)
+            .converted(self.config.settings.export_schema_version)
+    }
+    
+    /// Export query results as a Parquet file, keeping the SQLite column types
+    #[cfg(feature = "parquet")]
+    pub fn export_parquet(&self, query: &str, output_path: &Path) -> Result<(), PdwError> {
+        let batch = crate::arrow_export::query_to_batch(&self.database, query)?;
+        crate::arrow_export::write_parquet_file(&batch, output_path)?;
         
-        for row_data in results {
-            xml_content.push_str("   <item>\n");
+        tracing::info!("Parquet file exported: {} ({} rows)", output_path.display(), batch.num_rows());
+        Ok(())
+    }
+    
+    /// Export query results as a Parquet file (needs the `parquet` feature)
+    #[cfg(not(feature = "parquet"))]
+    pub fn export_parquet(&self, _query: &str, output_path: &Path) -> Result<(), PdwError> {
+        Err(ReportError::OutputGeneration {
+            format: "parquet".to_string(),
+            reason: format!("{}: requires a build with --features parquet", output_path.display()),
+        }.into())
+    }
+    
+    /// Export general entries as OFX statements, one file per origin
+    pub fn export_ofx(&self, output_dir: &Path) -> Result<Vec<PathBuf>, PdwError> {
+        let entries_table = &self.config.settings.general_entries_table;
+        let hashed = self.database.table_columns(entries_table)?.iter().any(|c| c.eq_ignore_ascii_case("RowHash"));
+        let query = format!(
+            "SELECT Origem, Data, TIPO, DESCRICAO, Credito, Debito, {} FROM {} ORDER BY Data",
+            if hashed { "RowHash" } else { "NULL" },
+            entries_table
+        );
+        let results = self.database.execute_query(&query)?;
+        let currency = self.config.fx.base_currency.to_uppercase();
+        
+        // Grouped by the exact origin: a collated ORDER BY may interleave origins differing
+        // only in case or accents, which would open the same statement (and file) twice
+        let mut statements: BTreeMap<String, OfxStatement> = BTreeMap::new();
+        let mut occurrences: HashMap<String, u32> = HashMap::new();
+        for row in &results {
+            let origin = match row.first() {
+                Some(Value::String(origin)) => origin.clone(),
+                _ => continue,
+            };
+            let date = match row.get(1) {
+                Some(Value::String(date)) => match chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                    Ok(date) => date,
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            let tipo = row.get(2).and_then(Value::as_str).unwrap_or_default().to_string();
+            let description = row.get(3).and_then(Value::as_str).unwrap_or_default().to_string();
+            let credit = row.get(4).and_then(Value::as_f64).unwrap_or(0.0);
+            let debit = row.get(5).and_then(Value::as_f64).unwrap_or(0.0);
+            // Rows loaded before RowHash existed are numbered per identical row, as RowHash does
+            let row_hash = match row.get(6) {
+                Some(Value::String(hash)) => hash.clone(),
+                _ => {
+                    let key = format!("{}|{}|{}|{}|{:.2}|{:.2}", origin, date, tipo, description, credit, debit);
+                    let occurrence = occurrences.entry(key.clone()).or_insert(0);
+                    *occurrence += 1;
+                    format!("{}-{}", key, occurrence)
+                }
+            };
             
-            for (idx, cell_value) in row_data.iter().enumerate() {
-                let value = match cell_value {
-                    Value::String(s) => xml_escape(s),
-                    Value::Number(n) => n.to_string(),
-                    Value::Bool(b) => b.to_string(),
-                    Value::Null => String::new(),
-                    _ => xml_escape(&cell_value.to_string()),
-                };
-                
-                xml_content.push_str(&format!("      <col{}>{}</col{}>\n", idx + 1, value, idx + 1));
-            }
+            let statement = statements.entry(origin.clone())
+                .or_insert_with(|| OfxStatement::new(&origin, &currency));
             
-            xml_content.push_str("   </item>\n");
+            // Rows carrying both a credit and a debit become two OFX transactions
+            for (amount, is_credit) in [(credit, true), (-debit, false)] {
+                if amount == 0.0 {
+                    continue;
+                }
+                statement.transactions.push(OfxTransaction {
+                    date,
+                    amount,
+                    fit_id: ofx::fit_id(&origin, date, &row_hash, is_credit),
+                    name: tipo.clone(),
+                    memo: description.clone(),
+                });
+            }
         }
         
-        xml_content.push_str("</data>\n");
+        std::fs::create_dir_all(output_dir)?;
+        let generated = deterministic::now().date_naive();
+        let mut written = Vec::new();
         
-        std::fs::write(output_path, xml_content)?;
-        
-        // Compress if configured
-        if self.config.settings.export_other_types {
-            self.compress_file(output_path)?;
+        for statement in statements.values() {
+            let path = output_dir.join(format!("{}.ofx", file_stem(&statement.account_id)));
+            
+            std::fs::write(&path, statement.to_ofx(generated))
+                .map_err(|e| ReportError::OutputGeneration {
+                    format: "ofx".to_string(),
+                    reason: format!("{}: {}", path.display(), e),
+                })?;
+            
+            tracing::info!("OFX statement exported: {} ({} transactions)", path.display(), statement.transactions.len());
+            written.push(path);
         }
         
-        Ok(())
+        Ok(written)
     }
     
     /// Export general entries to multiple formats
@@ -262,23 +855,17 @@ impl ReportGenerator {
         let base_filename = format!("{}.v2", self.config.settings.general_entries_table);
         let base_path = self.config.directories.dir_out.join(&base_filename);
         
-        let query = format!(
-            "SELECT 
-                substr(LG.Data, 9, 2) || '-' || substr(LG.Data, 6, 2) || '-' || substr(LG.Data, 1, 4) AS Quando,
-                LG.DIA_SEMANA as 'Dia da Semana',
-                LG.TIPO as 'Tipo',
-                LG.DESCRICAO as 'Descricao/Lancamento',
-                replace(LG.Credito, '.', ',') as 'Credito',
-                replace(LG.Debito, '.', ',') as 'Debito',
-                char(39) || cast(Mes as text) as 'Mes',
-                char(39) || cast(Ano as text) as 'Ano',
-                char(39) || MES_EXTENSO as 'Mes(Por Extenso)',
-                char(39) || cast(AnoMes as text) as 'Ano/Mes',
-                LG.Origem as Origem
-            FROM {} LG 
-            ORDER BY Data DESC",
-            self.config.settings.general_entries_table
-        );
+        let (query, parquet_query) = match &self.config.settings.export_profile {
+            Some(profile) => {
+                let query = self.profile_query(profile)?;
+                (query.clone(), query)
+            }
+            None => (self.general_entries_query(), format!(
+                "SELECT * FROM {} ORDER BY Data DESC{}",
+                self.config.settings.general_entries_table,
+                deterministic::tie_break(&["Origem", "TIPO", "DESCRICAO", "Credito", "Debito"])
+            )),
+        };
         
         // Export CSV
         let csv_path = base_path.with_extension("csv");
@@ -293,56 +880,61 @@ impl ReportGenerator {
             self.export_xml(&query, &xml_path)?;
         }
         
+        // Parquet keeps the stored types instead of the Portuguese-formatted text
+        if self.config.settings.export_parquet {
+            self.export_parquet(&parquet_query, &base_path.with_extension("parquet"))?;
+        }
+        
         Ok(())
     }
     
-    /// Create variable substitution map
-    fn create_variable_map(&self) -> HashMap<String, String> {
-        let mut variables = HashMap::new();
-        
-        variables.insert("entries_table".to_string(), self.config.settings.general_entries_table.clone());
-        variables.insert("full_hist".to_string(), self.config.settings.full_pivot_table.clone());
-        variables.insert("anual_hist".to_string(), self.config.settings.anual_pivot_table.clone());
-        variables.insert("day_prog".to_string(), self.config.settings.dayly_progress.clone());
-        variables.insert("splt_pmnt_res".to_string(), self.config.settings.out_res_pmnt_tab.clone());
-        variables.insert("mont_summ".to_string(), self.config.settings.monthly_summaties.clone());
-        variables.insert("dyn_rep_tab".to_string(), self.config.settings.din_report_guiding.clone());
+    /// General entries with the columns of an export profile, newest first
+    fn profile_query(&self, name: &str) -> Result<String, PdwError> {
+        let profile = export_profiles::find(&self.config.export_profiles, name)?;
+        let table = &self.config.settings.general_entries_table;
+        let unknown = profile.unknown_columns(&self.database.table_columns(table)?);
+        if !unknown.is_empty() {
+            return Err(ReportError::QueryProcessing {
+                query_name: format!("export profile {}", name),
+                reason: format!("{} has no column {}", table, unknown.join(", ")),
+            }.into());
+        }
         
-        variables
+        Ok(format!(
+            "SELECT {} FROM {} ORDER BY Data DESC{}",
+            profile.select_list(),
+            table,
+            deterministic::tie_break(&["Origem", "TIPO", "DESCRICAO", "Credito", "Debito"])
+        ))
     }
     
-    /// Substitute variables in SQL query
-    fn substitute_variables(&self, template: &str, variables: &HashMap<String, String>) -> String {
-        let mut result = template.to_string();
-        
-        for (key, value) in variables {
-            let placeholder = format!("{{{}}}", key);
-            result = result.replace(&placeholder, value);
-        }
-        
-        result
+    /// General entries in the formatted Portuguese layout, newest first
+    fn general_entries_query(&self) -> String {
+        let separator = self.config.locale.decimal_separator.to_string().replace('\'', "''");
+        format!(
+            "SELECT
+                {date} AS Quando,
+                LG.DIA_SEMANA as 'Dia da Semana',
+                LG.TIPO as 'Tipo',
+                LG.DESCRICAO as 'Descricao/Lancamento',
+                replace(LG.Credito, '.', '{separator}') as 'Credito',
+                replace(LG.Debito, '.', '{separator}') as 'Debito',
+                char(39) || cast(Mes as text) as 'Mes',
+                char(39) || cast(Ano as text) as 'Ano',
+                char(39) || MES_EXTENSO as 'Mes(Por Extenso)',
+                char(39) || cast(AnoMes as text) as 'Ano/Mes',
+                LG.Origem as Origem
+            FROM {table} LG
+            ORDER BY Data DESC{ties}",
+            date = self.config.locale.sql_date("LG.Data"),
+            table = self.config.settings.general_entries_table,
+            ties = deterministic::tie_break(&["LG.Origem", "LG.TIPO", "LG.DESCRICAO", "LG.Credito", "LG.Debito"])
+        )
     }
     
-    /// Compress file using gzip
-    fn compress_file(&self, file_path: &Path) -> Result<(), PdwError> {
-        use flate2::write::GzEncoder;
-        use flate2::Compression;
-        
-        let input_data = std::fs::read(file_path)?;
-        let compressed_path = file_path.with_extension(
-            format!("{}.gz", file_path.extension().unwrap_or_default().to_string_lossy())
-        );
-        
-        let output_file = File::create(&compressed_path)?;
-        let mut encoder = GzEncoder::new(output_file, Compression::default());
-        encoder.write_all(&input_data)?;
-        encoder.finish()?;
-        
-        // Remove original file
-        std::fs::remove_file(file_path)?;
-        
-        log::info!("Compressed file created: {}", compressed_path.display());
-        Ok(())
+    /// Create variable substitution map
+    fn create_variable_map(&self) -> HashMap<String, String> {
+        query_variables(&self.config.settings, self.database.collation().suffix())
     }
 }
 
@@ -372,8 +964,15 @@ impl ReportOperations for ReportGenerator {
     }
 }
 
+/// File name for a report or origin, with anything but letters, digits, '-' and '_' replaced
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
 /// Escape XML special characters
-fn xml_escape(input: &str) -> String {
+pub(crate) fn xml_escape(input: &str) -> String {
     input
         .replace("&", "&amp;")
         .replace("<", "&lt;")
@@ -404,26 +1003,232 @@ mod tests {
         let variables = generator.create_variable_map();
         
         let template = "SELECT * FROM {entries_table} WHERE date > '{full_hist}'";
-        let result = generator.substitute_variables(template, &variables);
+        let result = substitute_variables(template, &variables);
         
         assert!(result.contains("LANCAMENTOS_GERAIS"));
         assert!(result.contains("HistoricoGeral"));
     }
     
+    #[test]
+    fn test_ofx_export() {
+        let mut config = PdwConfig::default();
+        config.fx.base_currency = "eur".to_string();
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let database = DatabaseManager::new(&db_path).unwrap();
+        database.create_tables().unwrap();
+        database.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem)
+             VALUES ('2024-01-15', 'ALM', 'Mercado', 0, 42.5, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem)
+             VALUES ('2024-01-16', 'SAL', 'Salario', 1000, 0, 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem)
+             VALUES ('2024-01-17', 'ALM', 'Padaria', 0, 10, 'Cartao Visa');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem)
+             VALUES ('2024-01-18', 'ALM', 'Feira', 0, 7, 'Cartão Visa');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem)
+             VALUES ('2024-01-19', 'ALM', 'Acougue', 0, 3, 'Cartao Visa');"
+        ).unwrap();
+        
+        let generator = ReportGenerator::new(database, config);
+        let out_dir = temp_dir.path().join("ofx");
+        let files = generator.export_ofx(&out_dir).unwrap();
+        
+        assert_eq!(files.len(), 3);
+        let visa = std::fs::read_to_string(out_dir.join("Cartao_Visa.ofx")).unwrap();
+        assert_eq!(visa.matches("<STMTTRN>").count(), 2);
+        let content = std::fs::read_to_string(out_dir.join("Conta.ofx")).unwrap();
+        assert!(content.contains("<BALAMT>957.50</BALAMT>"));
+        assert!(content.contains("<CURDEF>EUR</CURDEF>"));
+        
+        // An earlier entry does not renumber the FITIDs already exported
+        let fit_ids = |content: &str| content.lines()
+            .filter(|line| line.starts_with("<FITID>"))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let before = fit_ids(&content);
+        generator.database.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, Origem)
+             VALUES ('2024-01-01', 'ALM', 'Feira', 0, 5, 'Conta');"
+        ).unwrap();
+        generator.export_ofx(&out_dir).unwrap();
+        let after = fit_ids(&std::fs::read_to_string(out_dir.join("Conta.ofx")).unwrap());
+        assert_eq!(after.len(), 3);
+        assert!(before.iter().all(|id| after.contains(id)));
+    }
+    
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_general_entries_parquet_export() {
+        use arrow::array::{Array, Float64Array};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_out = temp_dir.path().to_path_buf();
+        config.settings.export_parquet = true;
+        let database = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        database.create_tables().unwrap();
+        database.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem)
+             VALUES ('2024-01-15', 'ALM', 'Mercado', 0, 42.5, '2024/01', 'Conta');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem)
+             VALUES ('2024-02-01', 'SAL', 'Salario', 1000, 0, '2024/02', 'Conta');"
+        ).unwrap();
+        
+        let generator = ReportGenerator::new(database, config);
+        generator.export_general_entries().unwrap();
+        
+        let file = std::fs::File::open(temp_dir.path().join("LANCAMENTOS_GERAIS.parquet")).unwrap();
+        let batches: Vec<_> = ParquetRecordBatchReaderBuilder::try_new(file).unwrap()
+            .build().unwrap()
+            .map(|batch| batch.unwrap())
+            .collect();
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 2);
+        
+        // Newest first, amounts kept as numbers
+        let schema = batch.schema();
+        let debit = batch.column(schema.index_of("Debito").unwrap())
+            .as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(debit.value(1), 42.5);
+        assert_eq!(file_stem("Gastos/Mês 2024"), "Gastos_Mês_2024");
+    }
+    
     #[test]
     fn test_query_config_deserialization() {
         let yaml_content = r#"
 queries_padrao:
   - sql: "SELECT * FROM test"
     sheet_name: "TestSheet"
+    style:
+      number_format: "R$ #,##0.00"
+      currency_columns: [Valor]
 queries_gera_hist:
   - sql: "SELECT * FROM {entries_table}"
     sheet_name: "HistorySheet"
+    shape: long
+    shape_keys: [AnoMes]
 "#;
         
         let config: QueryConfig = serde_yaml::from_str(yaml_content).unwrap();
         assert_eq!(config.queries_padrao.len(), 1);
         assert_eq!(config.queries_gera_hist.len(), 1);
         assert_eq!(config.queries_padrao[0].sheet_name, "TestSheet");
+        assert_eq!(config.queries_padrao[0].style.number_format.as_deref(), Some("R$ #,##0.00"));
+        assert_eq!(config.queries_gera_hist[0].style, SheetStyle::default());
+        assert_eq!(config.queries_gera_hist[0].shape, Some(Shape::Long));
+        assert_eq!(config.queries_gera_hist[0].shape_keys, vec!["AnoMes".to_string()]);
+        assert!(config.queries_padrao[0].shape.is_none());
+    }
+    
+    #[test]
+    fn test_styled_sheet_types_and_header() {
+        use calamine::{DataType, Reader};
+        
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("styled.xlsx");
+        let columns = vec!["TIPO".to_string(), "Ano".to_string(), "Total".to_string(), "Conta".to_string()];
+        let rows = vec![
+            vec![Value::from("ALM"), Value::from(2024), Value::from(10.5), Value::from(123)],
+            vec![Value::from("LAZ"), Value::from(2024), Value::from(3.0), Value::Null],
+        ];
+        let style: SheetStyle = serde_yaml::from_str("text_columns: [Conta]\nautofilter: false").unwrap();
+        assert!(!style.autofilter && style.freeze_header && style.number_format.is_none());
+        
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name("Resumo").unwrap();
+        write_styled_sheet(worksheet, &columns, &rows, &style, "#,##0.00").unwrap();
+        workbook.save(&path).unwrap();
+        
+        let mut reader: calamine::Xlsx<_> = calamine::open_workbook(&path).unwrap();
+        let range = reader.worksheet_range("Resumo").unwrap().unwrap();
+        assert_eq!(range.get_value((0, 2)), Some(&DataType::String("Total".to_string())));
+        assert_eq!(range.get_value((1, 1)), Some(&DataType::Float(2024.0)));
+        assert_eq!(range.get_value((1, 2)), Some(&DataType::Float(10.5)));
+        assert_eq!(range.get_value((1, 3)), Some(&DataType::String("123".to_string())));
+    }
+    
+    #[test]
+    fn test_chart_sheets_in_report() {
+        use calamine::Reader;
+        
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.directories.dir_out = temp_dir.path().to_path_buf();
+        config.settings.run_dynamic_report = false;
+        config.settings.generate_charts = true;
+        std::fs::write(config.get_yaml_queries_path(), r#"
+queries_padrao:
+  - sql: "SELECT Origem, SUM(DEBITO) AS Debito FROM Resumido_In_Out GROUP BY Origem"
+    sheet_name: "Gastos por Origem"
+    chart: pie
+  - sql: "SELECT AnoMes, Origem FROM Resumido_In_Out"
+    sheet_name: "Sem Numeros"
+    chart: bar
+"#).unwrap();
+        
+        let database = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        database.connection().execute_batch(
+            "CREATE TABLE Resumido_In_Out (AnoMes TEXT, Origem TEXT, CREDITO REAL, DEBITO REAL);
+             INSERT INTO Resumido_In_Out VALUES ('2024/01', 'Conta', 1000, 42.5), ('2024/01', 'Cartao', 0, 10),
+                                                ('2024/02', 'Conta', 1000, 80);
+             CREATE TABLE HistoricoGeral (AnoMes TEXT, ALM REAL, LAZ REAL);
+             INSERT INTO HistoricoGeral VALUES ('2024/01', 52.5, 0), ('2024/02', 30, 50);"
+        ).unwrap();
+        
+        let report_path = config.get_report_path();
+        ReportGenerator::new(database, config).generate_excel_reports().unwrap();
+        
+        let reader: calamine::Xlsx<_> = calamine::open_workbook(&report_path).unwrap();
+        assert_eq!(reader.sheet_names().to_vec(), vec![
+            "Gastos por Origem", "Sem Numeros", report_charts::MONTHLY_CHART_SHEET, report_charts::PIVOT_CHART_SHEET,
+            report_dictionary::DICTIONARY_SHEET,
+        ]);
+        
+        // Pie, line and stacked column; the sheet without numbers gets none
+        let content = std::fs::read(&report_path).unwrap();
+        let has = |name: &str| content.windows(name.len()).any(|window| window == name.as_bytes());
+        assert!(has("xl/charts/chart3.xml") && !has("xl/charts/chart4.xml"));
+    }
+    
+    #[test]
+    fn test_report_engine_setting() {
+        let mut config = PdwConfig::default();
+        assert_eq!(config.settings.report_engine, ReportEngine::Sqlite);
+        
+        config.settings.report_engine = ReportEngine::DataFusion;
+        let toml_text = toml::to_string(&config).unwrap();
+        assert!(toml_text.contains("report_engine = \"datafusion\""));
+        
+        let variables = query_variables(&config.settings, String::new());
+        assert_eq!(substitute_variables("{entries_table}{collate}", &variables), "LANCAMENTOS_GERAIS");
+    }
+    
+    #[test]
+    fn test_query_params_and_dates() {
+        let variables = date_variables(NaiveDate::from_ymd_opt(2024, 3, 15).unwrap());
+        assert_eq!(
+            substitute_variables("{today} {current_year} {last_year} {current_month} {last_month}", &variables),
+            "2024-03-15 2024 2023 2024/03 2024/02"
+        );
+        assert_eq!(
+            substitute_variables("{current_month_start} {last_month_start}..{last_month_end}", &variables),
+            "2024-03-01 2024-02-01..2024-02-29"
+        );
+        let january = date_variables(NaiveDate::from_ymd_opt(2025, 1, 10).unwrap());
+        assert_eq!(substitute_variables("{last_month_start}..{last_month_end}", &january), "2024-12-01..2024-12-31");
+        
+        let yaml = "sql: \"SELECT * FROM {entries_table} WHERE Data >= '{start}' AND TIPO = '{tipo}'\"\n\
+                    sheet_name: \"Saude{current_year}\"\n\
+                    params:\n  start: \"{current_year}-01-01\"\n  tipo: SAUDE";
+        let query: QueryDefinition = serde_yaml::from_str(yaml).unwrap();
+        let mut variables = variables;
+        variables.insert("entries_table".to_string(), "LANCAMENTOS_GERAIS".to_string());
+        let expanded = query.expand(&variables);
+        assert_eq!(expanded.sql, "SELECT * FROM LANCAMENTOS_GERAIS WHERE Data >= '2024-01-01' AND TIPO = 'SAUDE'");
+        assert_eq!(expanded.sheet_name, "Saude2024");
     }
 }
\ No newline at end of file
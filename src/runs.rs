@@ -0,0 +1,182 @@
+/*!
+# Run History Module
+
+Every pipeline run is recorded in the PDW_RUNS table of the warehouse: command,
+start and end timestamps, PDW version, hostname, rows loaded per sheet, rows
+loaded and discarded in total, duration and exit status (with the error of a
+failed run). Unlike the flat log file, the history can be queried, and
+`pdw history` lists the latest runs.
+*/
+
+use crate::database::DatabaseManager;
+use crate::deterministic;
+use crate::error::{DatabaseError, PdwError};
+use chrono::{DateTime, Local};
+use rusqlite::params;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Table the runs are recorded in
+pub const RUNS_TABLE: &str = "PDW_RUNS";
+
+/// Run in progress: what it loaded so far
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub started_at: DateTime<Local>,
+    started: Instant,
+    /// Rows read from each sheet or source, by name
+    pub sheets: BTreeMap<String, usize>,
+    /// Entries inserted into the general entries table
+    pub rows_loaded: usize,
+    /// Entries removed by validation (no date or TIPO)
+    pub rows_discarded: usize,
+}
+
+impl Default for RunRecord {
+    fn default() -> Self {
+        Self::start()
+    }
+}
+
+impl RunRecord {
+    /// Start recording a run now
+    pub fn start() -> Self {
+        Self {
+            started_at: deterministic::now(),
+            started: Instant::now(),
+            sheets: BTreeMap::new(),
+            rows_loaded: 0,
+            rows_discarded: 0,
+        }
+    }
+    
+    /// Note the rows read from a sheet or source
+    pub fn record_sheet(&mut self, name: &str, rows: usize) {
+        *self.sheets.entry(name.trim().to_string()).or_default() += rows;
+    }
+    
+    /// Time since the run started
+    pub fn elapsed(&self) -> Duration {
+        deterministic::elapsed(self.started)
+    }
+    
+    /// Rows read per sheet or source, with their total, for the summary closing a run
+    pub fn summary(&self) -> (Vec<String>, Vec<Vec<Value>>) {
+        let mut rows: Vec<Vec<Value>> = self.sheets.iter()
+            .map(|(name, rows)| vec![Value::from(name.as_str()), Value::from(*rows)])
+            .collect();
+        rows.push(vec![Value::from("Total"), Value::from(self.sheets.values().sum::<usize>())]);
+        (vec!["Planilha".to_string(), "Linhas".to_string()], rows)
+    }
+    
+    /// Write the run, ending now; `error` is the failure of a failed run. Returns its id
+    pub fn write(&self, database: &DatabaseManager, command: &str, error: Option<&str>) -> Result<i64, PdwError> {
+        ensure_table(database)?;
+        
+        let hostname = hostname::get()
+            .unwrap_or_else(|_| "unknown".into())
+            .to_string_lossy()
+            .to_string();
+        let sheets = serde_json::to_string(&self.sheets).map_err(|e| DatabaseError::DataInsertion {
+            table: RUNS_TABLE.to_string(),
+            reason: e.to_string(),
+        })?;
+        let status = if error.is_some() { "failed" } else { "ok" };
+        
+        database.connection().execute(
+            &format!(
+                "INSERT INTO {} (Comando, Inicio, Fim, Versao, Host, Planilhas, Linhas, Descartadas, Duracao, Status, Erro)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                RUNS_TABLE
+            ),
+            params![
+                command,
+                self.started_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                env!("CARGO_PKG_VERSION"),
+                hostname,
+                sheets,
+                self.rows_loaded as i64,
+                self.rows_discarded as i64,
+                (self.elapsed().as_secs_f64() * 100.0).round() / 100.0,
+                status,
+                error,
+            ],
+        ).map_err(|e| DatabaseError::DataInsertion {
+            table: RUNS_TABLE.to_string(),
+            reason: e.to_string(),
+        })?;
+        
+        Ok(database.connection().last_insert_rowid())
+    }
+}
+
+/// Columns and rows of the latest runs, newest first; empty before the first recorded run
+pub fn recent_runs(database: &DatabaseManager, limit: usize) -> Result<(Vec<String>, Vec<Vec<Value>>), PdwError> {
+    if database.table_columns(RUNS_TABLE)?.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    
+    let query = format!(
+        "SELECT Id, Comando, Inicio, Duracao, Linhas, Descartadas, Status, Erro FROM {} ORDER BY Id DESC LIMIT {}",
+        RUNS_TABLE, limit
+    );
+    Ok((database.query_columns(&query)?, database.execute_query(&query)?))
+}
+
+fn ensure_table(database: &DatabaseManager) -> Result<(), PdwError> {
+    let query = format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            Id INTEGER PRIMARY KEY AUTOINCREMENT,
+            Comando TEXT NOT NULL,
+            Inicio TEXT NOT NULL,
+            Fim TEXT NOT NULL,
+            Versao TEXT,
+            Host TEXT,
+            Planilhas TEXT,
+            Linhas INTEGER,
+            Descartadas INTEGER,
+            Duracao REAL,
+            Status TEXT NOT NULL,
+            Erro TEXT
+        )",
+        RUNS_TABLE
+    );
+    database.connection().execute(&query, [])
+        .map_err(|e| DatabaseError::SqlExecution { query, reason: e.to_string() })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_write_and_list_runs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("runs.db")).unwrap();
+        assert_eq!(recent_runs(&db, 10).unwrap(), (Vec::new(), Vec::new()));
+        
+        let mut run = RunRecord::start();
+        run.record_sheet("Conta ", 10);
+        run.record_sheet("Conta", 2);
+        run.rows_loaded = 11;
+        run.rows_discarded = 1;
+        assert_eq!(run.summary().1, vec![vec![json!("Conta"), json!(12)], vec![json!("Total"), json!(12)]]);
+        assert_eq!(run.write(&db, "load", None).unwrap(), 1);
+        assert_eq!(RunRecord::start().write(&db, "report", Some("disk full")).unwrap(), 2);
+        
+        let (columns, rows) = recent_runs(&db, 10).unwrap();
+        assert_eq!(columns[1], "Comando");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][6], json!("failed"));
+        assert_eq!(rows[0][7], json!("disk full"));
+        assert_eq!((&rows[1][4], &rows[1][5], &rows[1][6]), (&json!(11), &json!(1), &json!("ok")));
+        
+        let sheets = db.execute_query(&format!("SELECT Planilhas, Versao FROM {} WHERE Id = 1", RUNS_TABLE)).unwrap();
+        assert_eq!(sheets[0], vec![json!("{\"Conta\":12}"), json!(env!("CARGO_PKG_VERSION"))]);
+        assert_eq!(recent_runs(&db, 1).unwrap().1.len(), 1);
+    }
+}
\ No newline at end of file
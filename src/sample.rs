@@ -0,0 +1,310 @@
+/*!
+# Sample Data Module
+
+`pdw gen-sample`: a synthetic input workbook and matching report queries, to try
+the pipeline without a ledger of one's own and to attach to bug reports without
+exposing real finances.
+
+The workbook has the GUIDING, TiposLancamentos and dynamic reports sheets under
+their configured names, and three accounting sheets covering the last `months`
+months up to today:
+
+- ContaCorrente: salary, rent, utility bills and a monthly transfer to savings
+- CartaoCredito: everyday purchases at made-up merchants
+- Poupanca: the transfers and their monthly yield
+
+Amounts, days and merchants come from a seeded generator, so a seed and a month
+count give the same entries for the same end month (`--deterministic` fixes that
+month too). The queries summarize spending by TIPO, month and origin, leaving
+the transfers between the sample's own accounts (TRF) out of the totals.
+*/
+
+use crate::config::SettingsConfig;
+use crate::deterministic;
+use crate::error::{PdwError, ReportError};
+use chrono::{Datelike, Months, NaiveDate};
+use rust_xlsxwriter::{ExcelDateTime, Format, Worksheet};
+use std::path::Path;
+
+/// Transaction types of the sample (Código, Descrição)
+pub const SAMPLE_TYPES: [(&str, &str); 10] = [
+    ("SAL", "Salário"),
+    ("MOR", "Moradia"),
+    ("SER", "Contas e Serviços"),
+    ("MER", "Mercado"),
+    ("ALM", "Alimentação"),
+    ("TRP", "Transporte"),
+    ("SAU", "Saúde"),
+    ("LAZ", "Lazer"),
+    ("REN", "Rendimentos"),
+    ("TRF", "Transferências"),
+];
+
+/// Report queries over the sample, written as the YAML queries file
+pub const SAMPLE_QUERIES: &str = r#"# Queries dos dados de exemplo (pdw gen-sample)
+# TRF são transferências entre as contas do exemplo e ficam fora dos totais
+
+queries_gera_hist:
+  - sql: "select * from {full_hist};"
+    sheet_name: "{full_hist}"
+  
+  - sql: "select * from {anual_hist};"
+    sheet_name: "{anual_hist}"
+
+queries_padrao:
+  - sql: >
+      select TIPO as Categoria, round(sum(Debito), 2) as Valor, count(1) as QTD
+      from {entries_table}
+      where Debito > 0 and TIPO <> 'TRF'
+      group by TIPO
+      order by 2 desc;
+    sheet_name: "Gastos por Categoria"
+    style:
+      currency_columns: [Valor]
+    chart: pie
+  
+  - sql: >
+      select AnoMes as Referencia,
+      round(sum(Credito), 2) as Creditos,
+      round(sum(Debito), 2) as Debitos,
+      round(sum(Credito) - sum(Debito), 2) as Saldo
+      from {entries_table}
+      where TIPO <> 'TRF'
+      group by AnoMes
+      order by AnoMes;
+    sheet_name: "Entradas e Saidas"
+    style:
+      currency_columns: [Creditos, Debitos, Saldo]
+    chart: bar
+  
+  - sql: >
+      select Origem, count(1) as Lancamentos, round(sum(Credito) - sum(Debito), 2) as Saldo
+      from {entries_table}
+      group by Origem
+      order by Origem;
+    sheet_name: "Saldo por Origem"
+    style:
+      currency_columns: [Saldo]
+  
+  - sql: >
+      select Data, Origem, TIPO, DESCRICAO, Debito
+      from {entries_table}
+      where Debito > 0 and TIPO <> 'TRF'
+      order by Debito desc, Data
+      limit 10;
+    sheet_name: "Maiores Despesas"
+    style:
+      currency_columns: [Debito]
+"#;
+
+/// Everyday purchases of the card sheet: TIPO, merchant, lowest and highest amount
+const PURCHASES: [(&str, &str, f64, f64); 9] = [
+    ("MER", "SUPERMERCADO EXEMPLO", 80.0, 450.0),
+    ("MER", "HORTIFRUTI DA ESQUINA", 25.0, 90.0),
+    ("ALM", "RESTAURANTE MODELO", 35.0, 120.0),
+    ("ALM", "PADARIA FICTICIA", 8.0, 40.0),
+    ("ALM", "APP DE ENTREGAS", 30.0, 95.0),
+    ("TRP", "POSTO DE COMBUSTIVEL", 120.0, 280.0),
+    ("TRP", "APP DE TRANSPORTE", 12.0, 60.0),
+    ("SAU", "FARMACIA POPULAR", 15.0, 180.0),
+    ("LAZ", "CINEMA CENTRO", 30.0, 90.0),
+];
+
+/// Header row of the accounting sheets
+const ACCOUNTING_HEADERS: [&str; 5] = ["Data", "TIPO", "DESCRICAO", "Credito", "Debito"];
+
+/// What to generate
+#[derive(Debug, Clone, Copy)]
+pub struct SampleOptions {
+    /// Months of entries, the end's month included
+    pub months: u32,
+    pub seed: u64,
+    /// Last day with entries
+    pub end: NaiveDate,
+}
+
+/// Entry of a sample accounting sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleEntry {
+    pub date: NaiveDate,
+    pub tipo: &'static str,
+    pub description: &'static str,
+    pub credit: f64,
+    pub debit: f64,
+}
+
+/// Seeded generator (SplitMix64): the same seed always gives the same sample
+struct SampleRng(u64);
+
+impl SampleRng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    
+    /// Number from 0 to `n` - 1
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+    
+    /// Amount from `low` to `high`, in whole cents
+    fn amount(&mut self, low: f64, high: f64) -> f64 {
+        let cents = ((high - low) * 100.0).round() as u64;
+        ((low * 100.0).round() + self.below(cents + 1) as f64) / 100.0
+    }
+}
+
+/// Accounting sheets of the sample by origin, each oldest entry first
+pub fn sample_entries(options: &SampleOptions) -> Vec<(&'static str, Vec<SampleEntry>)> {
+    let mut rng = SampleRng(options.seed);
+    let (mut checking, mut card, mut savings) = (Vec::new(), Vec::new(), Vec::new());
+    let entry = |date, tipo, description, credit, debit| SampleEntry { date, tipo, description, credit, debit };
+    let mut savings_balance = 5_000.0;
+    
+    let end_month = options.end.with_day(1).unwrap_or(options.end);
+    for back in (0..options.months.max(1)).rev() {
+        let month = end_month - Months::new(back);
+        let last_day = (month + Months::new(1)).pred_opt().map_or(28, |day| day.day());
+        let day = |day: u32| month.with_day(day.min(last_day)).unwrap_or(month);
+        
+        checking.push(entry(day(5), "SAL", "SALARIO EMPRESA EXEMPLO", rng.amount(6_200.0, 6_600.0), 0.0));
+        checking.push(entry(day(6), "TRF", "TRANSFERENCIA PARA POUPANCA", 0.0, 500.0));
+        checking.push(entry(day(10), "MOR", "ALUGUEL", 0.0, 2_200.0));
+        checking.push(entry(day(15), "SER", "CONTA DE LUZ", 0.0, rng.amount(140.0, 320.0)));
+        checking.push(entry(day(18), "SER", "CONTA DE AGUA", 0.0, rng.amount(60.0, 140.0)));
+        checking.push(entry(day(20), "SER", "INTERNET FIBRA", 0.0, 119.9));
+        
+        card.push(entry(day(3), "LAZ", "STREAMING DE VIDEO", 0.0, 39.9));
+        for _ in 0..15 + rng.below(16) {
+            let (tipo, merchant, low, high) = PURCHASES[rng.below(PURCHASES.len() as u64) as usize];
+            card.push(entry(day(1 + rng.below(last_day as u64) as u32), tipo, merchant, 0.0, rng.amount(low, high)));
+        }
+        
+        let interest = (savings_balance * 0.006 * 100.0_f64).round() / 100.0;
+        savings.push(entry(day(6), "TRF", "TRANSFERENCIA DA CONTA CORRENTE", 500.0, 0.0));
+        savings.push(entry(day(last_day), "REN", "RENDIMENTO POUPANCA", interest, 0.0));
+        savings_balance += 500.0 + interest;
+    }
+    
+    let mut sheets = vec![("ContaCorrente", checking), ("CartaoCredito", card), ("Poupanca", savings)];
+    for (_, entries) in &mut sheets {
+        entries.retain(|entry| entry.date <= options.end);
+        entries.sort_by_key(|entry| entry.date);
+    }
+    sheets
+}
+
+/// Write the sample input workbook; returns the number of entries
+pub fn write_sample_workbook(path: &Path, settings: &SettingsConfig, options: &SampleOptions) -> Result<usize, PdwError> {
+    let sheets = sample_entries(options);
+    let mut workbook = deterministic::workbook();
+    let header_format = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("dd/mm/yyyy");
+    let amount_format = Format::new().set_num_format("#,##0.00");
+    
+    // GUIDING: the accounting sheets, then the reference ones
+    let mut guiding_rows: Vec<[&str; 3]> = sheets.iter().map(|(name, _)| [*name, "X", "X"]).collect();
+    guiding_rows.push([settings.types_of_entries.as_str(), "", "X"]);
+    guiding_rows.push([settings.din_report_guiding.as_str(), "", "X"]);
+    let guiding = workbook.add_worksheet().set_name(&settings.guiding_table).map_err(ReportError::ExcelWriter)?;
+    write_text_rows(guiding, &["TABLE_NAME", "ACCOUNTING", "LOADABLE"], &guiding_rows, &header_format)?;
+    
+    let types: Vec<[&str; 2]> = SAMPLE_TYPES.iter().map(|(code, description)| [*code, *description]).collect();
+    let types_sheet = workbook.add_worksheet().set_name(&settings.types_of_entries).map_err(ReportError::ExcelWriter)?;
+    write_text_rows(types_sheet, &["Código", "Descrição"], &types, &header_format)?;
+    
+    // One dynamic report, the TIPO history pivot (table, then sheet name)
+    let reports = workbook.add_worksheet().set_name(&settings.din_report_guiding).map_err(ReportError::ExcelWriter)?;
+    write_text_rows(reports, &["TABELA", "RELATORIO"], &[[settings.full_pivot_table.as_str(), "Historico por TIPO"]], &header_format)?;
+    
+    let mut written = 0;
+    for (name, entries) in &sheets {
+        let worksheet = workbook.add_worksheet().set_name(*name).map_err(ReportError::ExcelWriter)?;
+        write_text_rows(worksheet, &ACCOUNTING_HEADERS, &[] as &[[&str; 5]], &header_format)?;
+        for (idx, entry) in entries.iter().enumerate() {
+            let row = idx as u32 + 1;
+            let date = ExcelDateTime::from_ymd(entry.date.year() as u16, entry.date.month() as u8, entry.date.day() as u8)
+                .map_err(ReportError::ExcelWriter)?;
+            worksheet.write_datetime_with_format(row, 0, &date, &date_format).map_err(ReportError::ExcelWriter)?;
+            worksheet.write_string(row, 1, entry.tipo).map_err(ReportError::ExcelWriter)?;
+            worksheet.write_string(row, 2, entry.description).map_err(ReportError::ExcelWriter)?;
+            for (col, amount) in [(3, entry.credit), (4, entry.debit)] {
+                if amount != 0.0 {
+                    worksheet.write_number_with_format(row, col, amount, &amount_format).map_err(ReportError::ExcelWriter)?;
+                }
+            }
+        }
+        written += entries.len();
+    }
+    
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    workbook.save(path).map_err(ReportError::ExcelWriter)?;
+    Ok(written)
+}
+
+/// Bold header and rows of text
+fn write_text_rows<const N: usize>(worksheet: &mut Worksheet, header: &[&str], rows: &[[&str; N]],
+                                   header_format: &Format) -> Result<(), PdwError> {
+    for (col, name) in header.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *name, header_format).map_err(ReportError::ExcelWriter)?;
+    }
+    for (idx, cells) in rows.iter().enumerate() {
+        for (col, text) in cells.iter().enumerate().filter(|(_, text)| !text.is_empty()) {
+            worksheet.write_string(idx as u32 + 1, col as u16, *text).map_err(ReportError::ExcelWriter)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PdwConfig;
+    use crate::etl::EtlPipeline;
+    use crate::reporting::{QueryConfig, ReportGenerator};
+    use calamine::Reader;
+    
+    #[test]
+    fn test_sample_is_reproducible_and_loads() {
+        let options = SampleOptions { months: 3, seed: 7, end: NaiveDate::from_ymd_opt(2024, 3, 12).unwrap() };
+        let sheets = sample_entries(&options);
+        assert_eq!(sheets, sample_entries(&options));
+        assert_ne!(sheets, sample_entries(&SampleOptions { seed: 8, ..options }));
+        
+        let (_, checking) = &sheets[0];
+        assert_eq!(checking.first().map(|e| e.date), NaiveDate::from_ymd_opt(2024, 1, 5));
+        assert!(sheets.iter().flat_map(|(_, entries)| entries).all(|e| e.date <= options.end));
+        assert_eq!(checking.iter().filter(|e| e.tipo == "SAL").count(), 3);
+        // The end's month stops at its day: March has the rent but not the bills of the 15th on
+        assert_eq!(checking.iter().filter(|e| e.tipo == "MOR").count(), 3);
+        assert_eq!(checking.iter().filter(|e| e.tipo == "SER").count(), 6);
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = temp_dir.path().to_path_buf();
+        config.directories.dir_out = temp_dir.path().to_path_buf();
+        let written = write_sample_workbook(&config.get_input_file_path(), &config.settings, &options).unwrap();
+        assert_eq!(written, sheets.iter().map(|(_, entries)| entries.len()).sum::<usize>());
+        
+        let mut pipeline = EtlPipeline::in_memory(config.clone()).unwrap();
+        pipeline.execute_data_loading().unwrap();
+        pipeline.create_pivot_tables().unwrap();
+        let database = pipeline.into_database();
+        let count = database.execute_query("SELECT COUNT(*) FROM LANCAMENTOS_GERAIS").unwrap();
+        assert_eq!(count, vec![vec![serde_json::json!(written)]]);
+        
+        // The queries run against the loaded sample, dynamic report included
+        let queries: QueryConfig = serde_yaml::from_str(SAMPLE_QUERIES).unwrap();
+        let report_path = config.get_report_path();
+        ReportGenerator::new(database, config).with_queries(queries).generate_excel_reports().unwrap();
+        let report: calamine::Xlsx<_> = calamine::open_workbook(&report_path).unwrap();
+        let names = report.sheet_names().to_vec();
+        assert!(names.iter().any(|name| name == "Gastos por Categoria"));
+        assert!(names.iter().any(|name| name == "Historico por TIPO"));
+    }
+}
\ No newline at end of file
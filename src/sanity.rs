@@ -0,0 +1,464 @@
+/*!
+# Sanity Module
+
+Closing check of the accounting equation over the loaded warehouse (`[sanity]`),
+run after the summaries and pivots are built. Each invariant can be turned on
+or off in `checks`:
+
+- `transfer_pairs`: every transfer between own accounts (the `transfer_types`,
+  or the TIPOs of the `[[category_groups]]` of kind transfer) leaves one origin
+  and reaches another with the same amount within `transfer_days`, so the pair
+  nets to zero
+- `monthly_totals`: each row of the monthly summaries (Resumido_In_Out) equals
+  the sum of the days of its month and origin
+- `pivot_totals`: the TIPO columns of each month of the full pivot add up to the
+  month's debits in the monthly summaries
+
+Differences above `tolerance` are logged and stored in the SANIDADE table,
+exported as the "Sanidade" report sheet, each with a query that shows the
+entries behind it (`pdw query "<Consulta>"`).
+*/
+
+use crate::category_groups::{CategoryGroup, GroupKind};
+use crate::database::{quote_identifier, DatabaseManager};
+use crate::error::{DatabaseError, PdwError};
+use crate::money::MoneyMode;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Invariant of the sanity report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Invariant {
+    /// Transfers leave one origin and reach another
+    TransferPairs,
+    /// Monthly summaries equal the sum of their days
+    MonthlyTotals,
+    /// Pivot rows add up to the monthly debits
+    PivotTotals,
+}
+
+impl Invariant {
+    /// Label used in the report table
+    pub fn label(&self) -> &'static str {
+        match self {
+            Invariant::TransferPairs => "Transferencia sem contrapartida",
+            Invariant::MonthlyTotals => "Resumo mensal difere dos dias",
+            Invariant::PivotTotals => "Pivot difere do resumo mensal",
+        }
+    }
+}
+
+/// Sanity report settings (`[sanity]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SanityConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Invariants checked
+    #[serde(default = "default_checks")]
+    pub checks: Vec<Invariant>,
+    /// TIPOs of the transfers between own accounts; the transfer category groups when empty
+    #[serde(default)]
+    pub transfer_types: Vec<String>,
+    /// Most days between the two sides of a transfer
+    #[serde(default = "default_transfer_days")]
+    pub transfer_days: u32,
+    /// Largest difference still taken as equal
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f64,
+    #[serde(default = "default_sanity_table")]
+    pub table: String,
+}
+
+fn default_checks() -> Vec<Invariant> {
+    vec![Invariant::TransferPairs, Invariant::MonthlyTotals, Invariant::PivotTotals]
+}
+
+fn default_transfer_days() -> u32 {
+    3
+}
+
+fn default_tolerance() -> f64 {
+    0.01
+}
+
+fn default_sanity_table() -> String {
+    "SANIDADE".to_string()
+}
+
+impl Default for SanityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            checks: default_checks(),
+            transfer_types: Vec::new(),
+            transfer_days: default_transfer_days(),
+            tolerance: default_tolerance(),
+            table: default_sanity_table(),
+        }
+    }
+}
+
+impl SanityConfig {
+    /// TIPOs of the transfers, from the setting or the transfer category groups
+    pub fn transfer_types(&self, groups: &[CategoryGroup]) -> Vec<String> {
+        let types: Vec<&String> = if self.transfer_types.is_empty() {
+            groups.iter()
+                .filter(|group| group.kind == GroupKind::Transfer)
+                .flat_map(|group| &group.types)
+                .collect()
+        } else {
+            self.transfer_types.iter().collect()
+        };
+        types.into_iter().map(|tipo| tipo.trim().to_uppercase()).filter(|tipo| !tipo.is_empty()).collect()
+    }
+}
+
+/// A broken invariant
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub invariant: Invariant,
+    /// Day or AnoMes
+    pub period: String,
+    pub item: String,
+    pub expected: f64,
+    pub found: f64,
+    /// Query listing what is behind the difference
+    pub drill_down: String,
+}
+
+/// Tables the invariants read
+#[derive(Debug, Clone, Copy)]
+pub struct SanityTables<'a> {
+    pub entries: &'a str,
+    pub monthly_summaries: &'a str,
+    pub full_pivot: &'a str,
+}
+
+/// Check the configured invariants
+pub fn check(database: &DatabaseManager, tables: SanityTables, config: &SanityConfig,
+             groups: &[CategoryGroup]) -> Result<Vec<Violation>, PdwError> {
+    if database.money_mode() == MoneyMode::Decimal {
+        database.add_money_columns(tables.entries)?;
+    }
+    
+    let mut violations = Vec::new();
+    if config.checks.contains(&Invariant::TransferPairs) {
+        let types = config.transfer_types(groups);
+        if types.is_empty() {
+            tracing::info!("Sanity: no transfer TIPOs configured - transfer pairs not checked");
+        } else {
+            let transfers = read_transfers(database, tables.entries, &types)?;
+            violations.extend(unpaired_transfers(&transfers, config.transfer_days, config.tolerance).into_iter()
+                .map(|transfer| transfer_violation(transfer, tables.entries, &types, config.transfer_days)));
+        }
+    }
+    if config.checks.contains(&Invariant::MonthlyTotals) {
+        violations.extend(check_monthly_totals(database, tables, config.tolerance)?);
+    }
+    if config.checks.contains(&Invariant::PivotTotals) {
+        violations.extend(check_pivot_totals(database, tables, config.tolerance)?);
+    }
+    
+    Ok(violations)
+}
+
+/// Entry of a transfer TIPO
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub date: NaiveDate,
+    pub origin: String,
+    pub description: String,
+    /// Credit minus debit: negative leaving the origin, positive reaching it
+    pub amount: f64,
+}
+
+fn read_transfers(database: &DatabaseManager, entries_table: &str, types: &[String]) -> Result<Vec<Transfer>, PdwError> {
+    let money = database.money_mode();
+    let query = format!(
+        "SELECT date(Data), TRIM(COALESCE(Origem, '')), COALESCE(DESCRICAO, ''), COALESCE({}, 0) - COALESCE({}, 0)
+         FROM {} WHERE Data IS NOT NULL AND UPPER(TRIM(TIPO)) IN ({})
+         GROUP BY rowid ORDER BY date(Data), rowid",
+        money.sum_sql("Credito", None), money.sum_sql("Debito", None), entries_table, sql_list(types)
+    );
+    
+    Ok(database.execute_query(&query)?.into_iter()
+        .filter_map(|row| {
+            let text = |index: usize| row.get(index).and_then(Value::as_str).unwrap_or_default().to_string();
+            let date = NaiveDate::parse_from_str(&text(0), "%Y-%m-%d").ok()?;
+            let amount = row.get(3).and_then(Value::as_f64).filter(|amount| *amount != 0.0)?;
+            Some(Transfer { date, origin: text(1), description: text(2), amount })
+        })
+        .collect())
+}
+
+/// Transfers without a counterpart: the same amount with the other sign, in another origin, within `days`.
+/// Each entry pairs with at most one other, the closest in date first
+pub fn unpaired_transfers(transfers: &[Transfer], days: u32, tolerance: f64) -> Vec<&Transfer> {
+    let mut paired = vec![false; transfers.len()];
+    for (index, transfer) in transfers.iter().enumerate() {
+        if paired[index] || transfer.amount > 0.0 {
+            continue;
+        }
+        let counterpart = transfers.iter().enumerate()
+            .filter(|(other, candidate)| !paired[*other]
+                && candidate.amount > 0.0
+                && candidate.origin != transfer.origin
+                && (candidate.amount + transfer.amount).abs() <= tolerance
+                && (candidate.date - transfer.date).num_days().abs() <= days as i64)
+            .min_by_key(|(_, candidate)| (candidate.date - transfer.date).num_days().abs())
+            .map(|(other, _)| other);
+        if let Some(other) = counterpart {
+            paired[index] = true;
+            paired[other] = true;
+        }
+    }
+    
+    transfers.iter().zip(paired).filter(|(_, paired)| !paired).map(|(transfer, _)| transfer).collect()
+}
+
+fn transfer_violation(transfer: &Transfer, entries_table: &str, types: &[String], days: u32) -> Violation {
+    let date = transfer.date.format("%Y-%m-%d");
+    Violation {
+        invariant: Invariant::TransferPairs,
+        period: date.to_string(),
+        item: format!("{} - {}", transfer.origin, transfer.description.trim()),
+        expected: 0.0,
+        found: transfer.amount,
+        drill_down: format!(
+            "SELECT Data, Origem, TIPO, DESCRICAO, Credito, Debito FROM {} \
+             WHERE UPPER(TRIM(TIPO)) IN ({}) AND date(Data) BETWEEN date('{date}', '-{days} days') AND date('{date}', '+{days} days') \
+             ORDER BY Data, Origem",
+            entries_table, sql_list(types), date = date, days = days
+        ),
+    }
+}
+
+/// Monthly summary rows against the days of their month, both ways
+fn check_monthly_totals(database: &DatabaseManager, tables: SanityTables, tolerance: f64) -> Result<Vec<Violation>, PdwError> {
+    if database.table_columns(tables.monthly_summaries)?.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    let money = database.money_mode();
+    let summaries = amounts_by_key(database, &format!(
+        "SELECT AnoMes, TRIM(COALESCE(Origem, '')), SUM(CREDITO), SUM(DEBITO) FROM {} GROUP BY 1, 2",
+        tables.monthly_summaries
+    ))?;
+    let days = amounts_by_key(database, &format!(
+        "SELECT strftime('%Y/%m', Dia), Origem, SUM(Credito), SUM(Debito) FROM (
+             SELECT date(Data) AS Dia, TRIM(COALESCE(Origem, '')) AS Origem, {} AS Credito, {} AS Debito
+             FROM {} WHERE Data IS NOT NULL GROUP BY 1, 2
+         ) GROUP BY 1, 2",
+        money.sum_sql("Credito", None), money.sum_sql("Debito", None), tables.entries
+    ))?;
+    
+    let mut keys: Vec<&(String, String)> = summaries.keys().chain(days.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    
+    let mut violations = Vec::new();
+    for key @ (month, origin) in keys {
+        let summary = summaries.get(key).copied().unwrap_or_default();
+        let daily = days.get(key).copied().unwrap_or_default();
+        for (side, found, expected) in [("Credito", summary.0, daily.0), ("Debito", summary.1, daily.1)] {
+            if (found - expected).abs() <= tolerance {
+                continue;
+            }
+            violations.push(Violation {
+                invariant: Invariant::MonthlyTotals,
+                period: month.clone(),
+                item: format!("{} - {}", origin, side),
+                expected,
+                found,
+                drill_down: format!(
+                    "SELECT date(Data) AS Dia, AnoMes, COUNT(*) AS Lancamentos, SUM({side}) AS {side} FROM {} \
+                     WHERE TRIM(COALESCE(Origem, '')) = '{}' AND (AnoMes = '{month}' OR strftime('%Y/%m', Data) = '{month}') \
+                     GROUP BY 1, 2 ORDER BY 1",
+                    tables.entries, origin.replace('\'', "''"), side = side, month = month.replace('\'', "''")
+                ),
+            });
+        }
+    }
+    Ok(violations)
+}
+
+/// Pivot rows against the debits of the monthly summaries
+fn check_pivot_totals(database: &DatabaseManager, tables: SanityTables, tolerance: f64) -> Result<Vec<Violation>, PdwError> {
+    let type_columns: Vec<String> = database.table_columns(tables.full_pivot)?.into_iter()
+        .filter(|column| column != "AnoMes")
+        .collect();
+    if type_columns.is_empty() || database.table_columns(tables.monthly_summaries)?.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    let row_total = type_columns.iter()
+        .map(|column| format!("COALESCE({}, 0)", quote_identifier(column)))
+        .collect::<Vec<_>>()
+        .join(" + ");
+    let pivot = amounts_by_key(database, &format!(
+        "SELECT AnoMes, '', SUM({}), 0 FROM {} GROUP BY 1", row_total, tables.full_pivot
+    ))?;
+    let summaries = amounts_by_key(database, &format!(
+        "SELECT AnoMes, '', SUM(DEBITO), 0 FROM {} GROUP BY 1", tables.monthly_summaries
+    ))?;
+    
+    let mut keys: Vec<&(String, String)> = pivot.keys().chain(summaries.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    
+    Ok(keys.into_iter()
+        .filter_map(|key| {
+            let found = pivot.get(key).map_or(0.0, |amounts| amounts.0);
+            let expected = summaries.get(key).map_or(0.0, |amounts| amounts.0);
+            ((found - expected).abs() > tolerance).then(|| Violation {
+                invariant: Invariant::PivotTotals,
+                period: key.0.clone(),
+                item: tables.full_pivot.to_string(),
+                expected,
+                found,
+                // Debits of TIPOs the pivot has no column for
+                drill_down: format!(
+                    "SELECT TIPO, COUNT(*) AS Lancamentos, SUM(Debito) AS Debito FROM {} \
+                     WHERE AnoMes = '{}' AND Debito <> 0 AND TIPO NOT IN ({}) GROUP BY TIPO ORDER BY TIPO",
+                    tables.entries, key.0.replace('\'', "''"), sql_list(&type_columns)
+                ),
+            })
+        })
+        .collect())
+}
+
+/// Credit and debit by period and item
+type KeyedAmounts = BTreeMap<(String, String), (f64, f64)>;
+
+/// Credit and debit columns of a query keyed by its first two columns
+fn amounts_by_key(database: &DatabaseManager, query: &str) -> Result<KeyedAmounts, PdwError> {
+    Ok(database.execute_query(query)?.into_iter()
+        .map(|row| {
+            let text = |index: usize| match row.get(index) {
+                Some(Value::String(text)) => text.clone(),
+                Some(Value::Null) | None => String::new(),
+                Some(value) => value.to_string(),
+            };
+            let amount = |index: usize| row.get(index).and_then(Value::as_f64).unwrap_or(0.0);
+            ((text(0), text(1)), (amount(2), amount(3)))
+        })
+        .collect())
+}
+
+/// Quoted SQL list of texts
+fn sql_list(values: &[String]) -> String {
+    values.iter().map(|value| format!("'{}'", value.replace('\'', "''"))).collect::<Vec<_>>().join(", ")
+}
+
+/// Log the violations, or that there are none
+pub fn log_violations(violations: &[Violation]) {
+    if violations.is_empty() {
+        tracing::info!("Sanity check: all invariants hold");
+        return;
+    }
+    
+    for violation in violations {
+        tracing::warn!(
+            "Sanity: {} -> {} {} (expected {:.2}, found {:.2})",
+            violation.invariant.label(), violation.period, violation.item, violation.expected, violation.found
+        );
+    }
+}
+
+/// Store the violations, replacing the previous run's table
+pub fn write_sanity_table(database: &DatabaseManager, table: &str, violations: &[Violation]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (Verificacao TEXT, Periodo TEXT, Item TEXT, Esperado REAL, Encontrado REAL,
+                          Diferenca REAL, Consulta TEXT)",
+        table
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)", table);
+    for violation in violations {
+        database.connection().execute(&insert_query, rusqlite::params![
+            violation.invariant.label(),
+            violation.period,
+            violation.item,
+            cents(violation.expected),
+            cents(violation.found),
+            cents(violation.found - violation.expected),
+            violation.drill_down,
+        ]).map_err(|e| DatabaseError::DataInsertion {
+            table: table.to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    Ok(violations.len())
+}
+
+/// Amount rounded to cents, without the float noise of the sums
+fn cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_invariants_flag_broken_totals() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, AnoMes, Origem) VALUES
+                 ('2024-01-05', 'TRF', 'Para poupanca', 0, 500, '2024/01', 'Conta'),
+                 ('2024-01-06', 'trf', 'Da conta', 500, 0, '2024/01', 'Poupanca'),
+                 ('2024-01-20', 'TRF', 'Para corretora', 0, 300, '2024/01', 'Conta'),
+                 ('2024-01-10', 'ALM', 'Restaurante', 0, 80, '2024/01', 'Conta'),
+                 ('2024-02-01', 'LAZ', 'Cinema', 0, 40, '2024/01', 'Conta');
+             CREATE TABLE Resumido_In_Out (AnoMes TEXT, Origem TEXT, CREDITO REAL, DEBITO REAL);
+             INSERT INTO Resumido_In_Out VALUES ('2024/01', 'Conta', 0, 920), ('2024/01', 'Poupanca', 500, 0);
+             CREATE TABLE PIVOT (AnoMes TEXT, ALM REAL, LAZ REAL, TRF REAL);
+             INSERT INTO PIVOT VALUES ('2024/01', 80, 40, 800);"
+        ).unwrap();
+        let tables = SanityTables { entries: "LANCAMENTOS_GERAIS", monthly_summaries: "Resumido_In_Out", full_pivot: "PIVOT" };
+        
+        // Transfers come from the transfer category groups unless listed
+        let groups: Vec<CategoryGroup> = vec![toml::from_str("name = \"Transferências\"\nkind = \"transfer\"\ntypes = [\"trf \"]").unwrap()];
+        let config = SanityConfig { enabled: true, ..Default::default() };
+        assert_eq!(config.transfer_types(&groups), vec!["TRF"]);
+        
+        let violations = check(&db, tables, &config, &groups).unwrap();
+        let found: Vec<_> = violations.iter()
+            .map(|v| (v.invariant, v.period.as_str(), v.item.as_str(), v.expected, v.found))
+            .collect();
+        assert_eq!(found, vec![
+            // The 300 sent to the brokerage never arrived anywhere
+            (Invariant::TransferPairs, "2024-01-20", "Conta - Para corretora", 0.0, -300.0),
+            // The cinema entry is dated February but summarized under January
+            (Invariant::MonthlyTotals, "2024/01", "Conta - Debito", 880.0, 920.0),
+            (Invariant::MonthlyTotals, "2024/02", "Conta - Debito", 40.0, 0.0),
+        ]);
+        
+        // A TIPO missing from the pivot: its debits are the difference
+        db.connection().execute_batch("INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, Credito, Debito, AnoMes, Origem) VALUES ('2024-01-15', 'SAU', 0, 60, '2024/01', 'Conta');
+                                       UPDATE Resumido_In_Out SET DEBITO = 980 WHERE Origem = 'Conta';").unwrap();
+        let pivot_only = SanityConfig { checks: vec![Invariant::PivotTotals], ..config };
+        let violations = check(&db, tables, &pivot_only, &groups).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!((violations[0].expected, violations[0].found), (980.0, 920.0));
+        let drill_down = db.execute_query(&violations[0].drill_down).unwrap();
+        assert_eq!(drill_down, vec![vec![Value::from("SAU"), Value::from(1), Value::from(60.0)]]);
+        
+        assert_eq!(write_sanity_table(&db, "SANIDADE", &violations).unwrap(), 1);
+        let rows = db.execute_query("SELECT Verificacao, Diferenca FROM SANIDADE").unwrap();
+        assert_eq!(rows, vec![vec![Value::from("Pivot difere do resumo mensal"), Value::from(-60.0)]]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,312 @@
+/*!
+# Self Update Module
+
+`pdw self-update` replaces the running binary with the latest GitHub release,
+for installs where no package manager does it. The release holds:
+
+| Asset | Content |
+|-------|---------|
+| `pdw-<os>-<arch>[.exe]` | The binary, e.g. `pdw-linux-x86_64`, `pdw-windows-x86_64.exe` |
+| `SHA256SUMS` | `sha256sum` output covering the binaries |
+| `SHA256SUMS.sig` | Ed25519 signature of `SHA256SUMS`, hex encoded |
+
+The binary is only installed when its SHA-256 matches `SHA256SUMS` and the
+signature of `SHA256SUMS` verifies against the publishing key release builds
+embed (`PDW_RELEASE_PUBLIC_KEY`, hex, at compile time). A checksum published
+with the binary proves nothing about who published it, so builds without a key
+refuse to install unless run with `--insecure`, and then only from the default
+repository; `--repository` is meant for forks whose builds embed their own key.
+
+The new binary is written next to the current one and renamed over it, so an
+interrupted update leaves the old binary in place. The replaced binary is kept
+as `<name>.old` until the next update on Windows, where a running executable
+cannot be deleted. `--check-only` reports the latest release without
+installing. Downloads need a build with `--features self-update`.
+*/
+
+use crate::error::PdwError;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Repository whose releases are installed
+pub const DEFAULT_REPOSITORY: &str = "lcarlin/PDW_RST";
+
+/// Checksums asset of a release
+pub const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// Signature of the checksums asset
+pub const SIGNATURE_ASSET: &str = "SHA256SUMS.sig";
+
+/// Ed25519 key the releases are signed with (hex), embedded by release builds
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("PDW_RELEASE_PUBLIC_KEY");
+
+/// A published release
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    /// Tag name, e.g. `v9.12.0`
+    pub tag: String,
+    pub assets: Vec<ReleaseAsset>,
+}
+
+/// A file attached to a release
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReleaseAsset {
+    pub name: String,
+    /// Download URL
+    pub url: String,
+}
+
+impl Release {
+    /// Release from a GitHub `releases/latest` response
+    pub fn parse(response: &Value) -> Result<Self, PdwError> {
+        let tag = response["tag_name"].as_str()
+            .ok_or_else(|| PdwError::Update("Unexpected release response: no tag_name".to_string()))?;
+        let assets = response["assets"].as_array()
+            .map(|assets| assets.iter()
+                .filter_map(|asset| Some(ReleaseAsset {
+                    name: asset["name"].as_str()?.to_string(),
+                    url: asset["browser_download_url"].as_str()?.to_string(),
+                }))
+                .collect())
+            .unwrap_or_default();
+        
+        Ok(Self { tag: tag.to_string(), assets })
+    }
+    
+    pub fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+    
+    /// Whether the release is newer than the running binary
+    pub fn is_newer(&self) -> bool {
+        is_newer(&self.tag, env!("CARGO_PKG_VERSION"))
+    }
+}
+
+/// Binary asset for this platform
+pub fn asset_name() -> String {
+    format!("pdw-{}-{}{}", std::env::consts::OS, std::env::consts::ARCH, std::env::consts::EXE_SUFFIX)
+}
+
+/// Whether version `latest` (a tag such as `v9.12.0`) comes after `current`;
+/// pre-release and build suffixes are ignored
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    let numbers = |version: &str| -> Option<Vec<u64>> {
+        version.trim().trim_start_matches(['v', 'V'])
+            .split(['-', '+']).next()?
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect()
+    };
+    match (numbers(latest), numbers(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// SHA-256 listed for a file in `sha256sum` output (lowercase hex)
+pub fn checksum_for(sums: &str, file_name: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == file_name).then(|| hash.to_lowercase())
+    })
+}
+
+/// Replace an executable with new contents, renaming them over it; the permissions
+/// of the old binary are kept
+pub fn replace_executable(executable: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let file_name = executable.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let staged = executable.with_file_name(format!("{}.new", file_name));
+    let replaced = executable.with_file_name(format!("{}.old", file_name));
+    
+    std::fs::write(&staged, contents)?;
+    std::fs::set_permissions(&staged, std::fs::metadata(executable)?.permissions())?;
+    // Left by the previous update on Windows
+    let _ = std::fs::remove_file(&replaced);
+    
+    std::fs::rename(executable, &replaced)?;
+    if let Err(e) = std::fs::rename(&staged, executable) {
+        let _ = std::fs::rename(&replaced, executable);
+        let _ = std::fs::remove_file(&staged);
+        return Err(e);
+    }
+    // Fails on Windows while the old binary runs; removed by the next update
+    let _ = std::fs::remove_file(&replaced);
+    Ok(())
+}
+
+/// Latest release of a repository (`owner/name`)
+#[cfg(feature = "self-update")]
+pub fn latest_release(repository: &str) -> Result<Release, PdwError> {
+    let url = format!("https://api.github.com/repos/{}/releases/latest", repository);
+    let response: Value = serde_json::from_slice(&download(&url)?)
+        .map_err(|e| PdwError::Update(format!("Invalid release response: {}", e)))?;
+    Release::parse(&response)
+}
+
+#[cfg(not(feature = "self-update"))]
+pub fn latest_release(_repository: &str) -> Result<Release, PdwError> {
+    Err(PdwError::Update("pdw self-update requires a build with --features self-update".to_string()))
+}
+
+/// Whether this build may install releases of `repository`: without a release key (`public_key`)
+/// only with `insecure`, and only from the default repository
+pub fn check_source(repository: &str, public_key: Option<&str>, insecure: bool) -> Result<(), PdwError> {
+    if public_key.is_some() {
+        return Ok(());
+    }
+    if !insecure {
+        return Err(PdwError::Update(
+            "This build has no release key to verify the signature of the download - \
+             rebuild with PDW_RELEASE_PUBLIC_KEY, or pass --insecure to trust the checksum only".to_string()
+        ));
+    }
+    if repository != DEFAULT_REPOSITORY {
+        return Err(PdwError::Update(format!(
+            "Releases of {} can only be installed by a build with a release key (--insecure covers {} only)",
+            repository, DEFAULT_REPOSITORY
+        )));
+    }
+    Ok(())
+}
+
+/// Download the binary of a release for this platform, verify it and install it over
+/// the running executable; returns the executable's path. Without a release key the
+/// download is only checked against its checksum, which takes `insecure`
+#[cfg(feature = "self-update")]
+pub fn install(release: &Release, insecure: bool) -> Result<PathBuf, PdwError> {
+    let name = asset_name();
+    let missing = |asset: &str| PdwError::Update(format!("Release {} has no {} asset", release.tag, asset));
+    let binary_asset = release.asset(&name).ok_or_else(|| missing(&name))?;
+    let sums_asset = release.asset(CHECKSUMS_ASSET).ok_or_else(|| missing(CHECKSUMS_ASSET))?;
+    
+    let sums = download(&sums_asset.url)?;
+    match RELEASE_PUBLIC_KEY {
+        Some(key) => {
+            let signature_asset = release.asset(SIGNATURE_ASSET).ok_or_else(|| missing(SIGNATURE_ASSET))?;
+            let signature = download(&signature_asset.url)?;
+            verify_signature(&sums, &String::from_utf8_lossy(&signature), key)?;
+        }
+        None if insecure => tracing::warn!("This build has no release key: only the checksum of the download is verified (--insecure)"),
+        None => return Err(PdwError::Update("This build has no release key - pass --insecure to trust the checksum only".to_string())),
+    }
+    let sums = String::from_utf8_lossy(&sums);
+    let expected = checksum_for(&sums, &name)
+        .ok_or_else(|| PdwError::Update(format!("{} does not list {}", CHECKSUMS_ASSET, name)))?;
+    
+    tracing::info!("Downloading {} ({})", name, release.tag);
+    let binary = download(&binary_asset.url)?;
+    verify_checksum(&binary, &expected)?;
+    
+    let executable = std::env::current_exe()?;
+    replace_executable(&executable, &binary)
+        .map_err(|e| PdwError::Update(format!("Could not replace {}: {}", executable.display(), e)))?;
+    Ok(executable)
+}
+
+#[cfg(not(feature = "self-update"))]
+pub fn install(_release: &Release, _insecure: bool) -> Result<PathBuf, PdwError> {
+    Err(PdwError::Update("pdw self-update requires a build with --features self-update".to_string()))
+}
+
+/// Check a download against its expected SHA-256 (hex)
+#[cfg(feature = "self-update")]
+pub fn verify_checksum(contents: &[u8], expected: &str) -> Result<(), PdwError> {
+    use sha2::{Digest, Sha256};
+    
+    let actual: String = Sha256::digest(contents).iter().map(|byte| format!("{:02x}", byte)).collect();
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(PdwError::Update(format!("Checksum mismatch: expected {}, downloaded {}", expected.trim(), actual)));
+    }
+    Ok(())
+}
+
+/// Check the hex Ed25519 signature of a message against a hex public key
+#[cfg(feature = "self-update")]
+pub fn verify_signature(message: &[u8], signature: &str, public_key: &str) -> Result<(), PdwError> {
+    use ed25519_dalek::{Signature, VerifyingKey};
+    
+    let invalid = |what: &str| PdwError::Update(format!("Invalid {}", what));
+    let key: [u8; 32] = decode_hex(public_key).and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| invalid("release public key"))?;
+    let signature: [u8; 64] = decode_hex(signature).and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| invalid(SIGNATURE_ASSET))?;
+    
+    VerifyingKey::from_bytes(&key)
+        .map_err(|_| invalid("release public key"))?
+        .verify_strict(message, &Signature::from_bytes(&signature))
+        .map_err(|_| PdwError::Update(format!("{} is not signed by the release key", CHECKSUMS_ASSET)))
+}
+
+#[cfg(feature = "self-update")]
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    text.trim().as_bytes().chunks(2)
+        .map(|pair| match pair {
+            [high, low] => u8::from_str_radix(std::str::from_utf8(&[*high, *low]).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(feature = "self-update")]
+fn download(url: &str) -> Result<Vec<u8>, PdwError> {
+    use std::io::Read;
+    
+    let response = ureq::get(url)
+        .set("User-Agent", concat!("pdw/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .map_err(|e| PdwError::Update(format!("Request to {} failed: {}", url, e)))?;
+    let mut contents = Vec::new();
+    response.into_reader().read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_release_checked_and_binary_replaced() {
+        let release = Release::parse(&json!({
+            "tag_name": "v9.12.0",
+            "assets": [
+                { "name": asset_name(), "browser_download_url": "https://example.com/pdw" },
+                { "name": "SHA256SUMS", "browser_download_url": "https://example.com/SHA256SUMS" },
+            ],
+        })).unwrap();
+        assert_eq!(release.asset(&asset_name()).unwrap().url, "https://example.com/pdw");
+        assert!(release.asset(SIGNATURE_ASSET).is_none());
+        assert!(is_newer("v9.12.0", "9.11.0") && is_newer("10.0", "9.11.0"));
+        assert!(!is_newer("v9.11.0", "9.11.0") && !is_newer("v9.2.0", "9.11.0") && !is_newer("nightly", "9.11.0"));
+        
+        let hash = "2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824";
+        let sums = format!("{}  pdw-linux-x86_64\n{} *pdw-windows-x86_64.exe\n", "ab".repeat(32), hash);
+        assert_eq!(checksum_for(&sums, "pdw-windows-x86_64.exe"), Some(hash.to_lowercase()));
+        assert_eq!(checksum_for(&sums, "pdw-macos-aarch64"), None);
+        
+        // Keyless builds need --insecure, and then install from the default repository only
+        let key = Some("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        assert!(check_source(DEFAULT_REPOSITORY, None, false).is_err());
+        assert!(check_source(DEFAULT_REPOSITORY, None, true).is_ok());
+        assert!(check_source("someone/fork", None, true).is_err());
+        assert!(check_source("someone/fork", key, false).is_ok());
+        #[cfg(feature = "self-update")]
+        {
+            assert!(verify_checksum(b"hello", hash).is_ok());
+            assert!(verify_checksum(b"hello!", hash).is_err());
+            // RFC 8032 test vector 1: empty message
+            let key = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+            let signature = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+            assert!(verify_signature(b"", signature, key).is_ok());
+            assert!(verify_signature(b"tampered", signature, key).is_err());
+        }
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let executable = temp_dir.path().join("pdw");
+        std::fs::write(&executable, "old").unwrap();
+        replace_executable(&executable, b"new").unwrap();
+        assert_eq!(std::fs::read(&executable).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,335 @@
+/*!
+# Server Module
+
+Read-only JSON access to the warehouse for dashboards. `pdw serve` answers:
+
+- `GET /transactions` - general entries, filtered with `from`/`to`
+  (YYYY-MM-DD), `type` (TIPO), `origin` and `limit` (1000 by default, at most
+  10000)
+- `GET /summaries/monthly` - the monthly summaries table
+- `GET /pivot` and `GET /pivot/annual` - the full and annual pivot tables
+- `POST /run` - run the configured pipeline phases (409 while one is running);
+  needs `Authorization: Bearer <token>`, so web pages open in a browser on the
+  machine cannot trigger it
+
+Responses use the layout of the JSON exports (`schema_version`, `columns`,
+`rows`); failures are `{"error": ...}`. Every request opens the database on its
+own connection and reads it with writes refused, so the API never changes the
+warehouse except through `/run`. Serving needs a build with `--features serve`.
+*/
+
+use crate::config::PdwConfig;
+use crate::database::{quote_identifier, DatabaseManager};
+use crate::error::PdwError;
+use crate::export_schema::ExportDocument;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+/// Entries `/transactions` returns when the request sets no limit
+pub const DEFAULT_LIMIT: usize = 1000;
+/// Most entries a single `/transactions` request returns
+pub const MAX_LIMIT: usize = 10_000;
+
+/// Filters of `/transactions`, from the query string
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct TransactionFilter {
+    /// First day included
+    pub from: Option<NaiveDate>,
+    /// Last day included
+    pub to: Option<NaiveDate>,
+    /// TIPO of the entries
+    #[serde(rename = "type")]
+    pub tipo: Option<String>,
+    /// Origem of the entries
+    pub origin: Option<String>,
+    /// Most entries returned, newest first
+    pub limit: Option<usize>,
+}
+
+/// Queries behind the endpoints, each on a read-only connection of its own
+pub struct WarehouseApi {
+    config: PdwConfig,
+}
+
+impl TransactionFilter {
+    /// SQL selecting the filtered entries and its bound parameters
+    fn query(&self, entries_table: &str) -> (String, Vec<String>) {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        if let Some(from) = self.from {
+            conditions.push("date(Data) >= ?");
+            params.push(from.format("%Y-%m-%d").to_string());
+        }
+        if let Some(to) = self.to {
+            conditions.push("date(Data) <= ?");
+            params.push(to.format("%Y-%m-%d").to_string());
+        }
+        if let Some(tipo) = &self.tipo {
+            conditions.push("TRIM(TIPO) = ?");
+            params.push(tipo.trim().to_string());
+        }
+        if let Some(origin) = &self.origin {
+            conditions.push("TRIM(Origem) = ?");
+            params.push(origin.trim().to_string());
+        }
+        
+        let mut sql = format!("SELECT * FROM {}", quote_identifier(entries_table));
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        sql.push_str(" ORDER BY date(Data) DESC, rowid DESC");
+        sql.push_str(&format!(" LIMIT {}", self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)));
+        (sql, params)
+    }
+}
+
+impl WarehouseApi {
+    pub fn new(config: PdwConfig) -> Self {
+        Self { config }
+    }
+    
+    /// General entries matching the filter, newest first
+    pub fn transactions(&self, filter: &TransactionFilter) -> Result<ExportDocument, PdwError> {
+        let (sql, params) = filter.query(&self.config.settings.general_entries_table);
+        self.query(&sql, &params)
+    }
+    
+    /// The monthly summaries table
+    pub fn monthly_summaries(&self) -> Result<ExportDocument, PdwError> {
+        self.table(&self.config.settings.monthly_summaries)
+    }
+    
+    /// The full pivot table, or the annual one
+    pub fn pivot(&self, annual: bool) -> Result<ExportDocument, PdwError> {
+        let settings = &self.config.settings;
+        self.table(if annual { &settings.anual_pivot_table } else { &settings.full_pivot_table })
+    }
+    
+    fn table(&self, table_name: &str) -> Result<ExportDocument, PdwError> {
+        self.query(&format!("SELECT * FROM {}", quote_identifier(table_name)), &[])
+    }
+    
+    fn query(&self, sql: &str, params: &[String]) -> Result<ExportDocument, PdwError> {
+        let database = DatabaseManager::open_configured(
+            &self.config.get_database_path(),
+            &self.config.settings,
+            &self.config.database.sqlite,
+        )?;
+        database.read_only(|database| {
+            let columns = database.query_columns(sql)?;
+            let rows = database.execute_query_with_params(sql, rusqlite::params_from_iter(params))?;
+            Ok(ExportDocument::new(columns, rows))
+        })
+    }
+}
+
+/// Random token for `POST /run` when none is configured: 32 bytes from the OS random
+/// source, hex-encoded
+#[cfg(feature = "serve")]
+pub fn generate_token() -> Result<String, PdwError> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| PdwError::Server(format!("Cannot generate a token: {}", e)))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[cfg(not(feature = "serve"))]
+pub fn generate_token() -> Result<String, PdwError> {
+    Err(PdwError::Server("pdw serve requires a build with --features serve".to_string()))
+}
+
+/// Whether an `Authorization` header carries the bearer token
+pub fn authorized(header: Option<&str>, token: &str) -> bool {
+    let Some(given) = header.and_then(|value| value.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    // Compare every byte so the time taken does not reveal the matching prefix
+    given.len() == token.len()
+        && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Serve the API on `bind` until stopped; `run` runs the pipeline for `POST /run`
+/// requests carrying `token`
+#[cfg(feature = "serve")]
+pub fn serve<F>(api: WarehouseApi, bind: &str, token: String, run: F) -> Result<(), PdwError>
+where
+    F: Fn() -> Result<(), String> + Send + Sync + 'static,
+{
+    use axum::routing::{get, post};
+    use std::sync::{Arc, Mutex};
+    
+    let state = Arc::new(routes::ServerState { api, token, run: Box::new(run), running: Mutex::new(()) });
+    let app = axum::Router::new()
+        .route("/transactions", get(routes::transactions))
+        .route("/summaries/monthly", get(routes::monthly_summaries))
+        .route("/pivot", get(routes::pivot))
+        .route("/pivot/annual", get(routes::annual_pivot))
+        .route("/run", post(routes::run))
+        .with_state(state);
+    
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(bind).await?;
+        tracing::info!("Serving the warehouse on http://{}", listener.local_addr()?);
+        axum::serve(listener, app).await
+    }).map_err(|e| PdwError::Server(format!("{}: {}", bind, e)))
+}
+
+#[cfg(not(feature = "serve"))]
+pub fn serve<F>(_api: WarehouseApi, _bind: &str, _token: String, _run: F) -> Result<(), PdwError>
+where
+    F: Fn() -> Result<(), String> + Send + Sync + 'static,
+{
+    Err(PdwError::Server("pdw serve requires a build with --features serve".to_string()))
+}
+
+#[cfg(feature = "serve")]
+mod routes {
+    use super::{authorized, TransactionFilter, WarehouseApi};
+    use crate::error::PdwError;
+    use crate::export_schema::ExportDocument;
+    use axum::extract::{Query, State};
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+    
+    pub struct ServerState {
+        pub api: WarehouseApi,
+        /// Bearer token `POST /run` must carry
+        pub token: String,
+        pub run: Box<dyn Fn() -> Result<(), String> + Send + Sync>,
+        /// Held while a pipeline run is in progress
+        pub running: Mutex<()>,
+    }
+    
+    type SharedState = State<Arc<ServerState>>;
+    
+    pub async fn transactions(State(state): SharedState, Query(filter): Query<TransactionFilter>) -> Response {
+        respond(state, move |api| api.transactions(&filter)).await
+    }
+    
+    pub async fn monthly_summaries(State(state): SharedState) -> Response {
+        respond(state, |api| api.monthly_summaries()).await
+    }
+    
+    pub async fn pivot(State(state): SharedState) -> Response {
+        respond(state, |api| api.pivot(false)).await
+    }
+    
+    pub async fn annual_pivot(State(state): SharedState) -> Response {
+        respond(state, |api| api.pivot(true)).await
+    }
+    
+    pub async fn run(State(state): SharedState, headers: HeaderMap) -> Response {
+        let header = headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok());
+        if !authorized(header, &state.token) {
+            return error(StatusCode::UNAUTHORIZED, "POST /run needs the bearer token of pdw serve".to_string());
+        }
+        let outcome = tokio::task::spawn_blocking(move || {
+            let Ok(_running) = state.running.try_lock() else {
+                return Err((StatusCode::CONFLICT, "A pipeline run is already in progress".to_string()));
+            };
+            let started = Instant::now();
+            tracing::info!("Pipeline run requested through the API");
+            (state.run)()
+                .map(|_| started.elapsed().as_secs_f64())
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+        }).await;
+        
+        match outcome {
+            Ok(Ok(seconds)) => json(StatusCode::OK, serde_json::json!({ "status": "ok", "seconds": seconds }).to_string()),
+            Ok(Err((status, message))) => error(status, message),
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        }
+    }
+    
+    /// Run a query off the async workers and answer with its document
+    async fn respond<F>(state: Arc<ServerState>, query: F) -> Response
+    where
+        F: FnOnce(&WarehouseApi) -> Result<ExportDocument, PdwError> + Send + 'static,
+    {
+        match tokio::task::spawn_blocking(move || query(&state.api).and_then(|document| document.to_json())).await {
+            Ok(Ok(body)) => json(StatusCode::OK, body),
+            Ok(Err(e)) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        }
+    }
+    
+    fn json(status: StatusCode, body: String) -> Response {
+        (status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+    }
+    
+    fn error(status: StatusCode, message: String) -> Response {
+        tracing::warn!("API request failed: {}", message);
+        json(status, serde_json::json!({ "error": message }).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_transactions_filtered_read_only() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = PdwConfig::default();
+        config.directories.database_dir = temp_dir.path().to_path_buf();
+        let database = DatabaseManager::new(&config.get_database_path()).unwrap();
+        database.connection().execute_batch(
+            "CREATE TABLE LANCAMENTOS_GERAIS (Data DATE, TIPO TEXT, DESCRICAO TEXT, Debito REAL, Origem TEXT);
+             INSERT INTO LANCAMENTOS_GERAIS VALUES
+                ('2024-01-15', 'ALM', 'Mercado', 10.0, 'Conta'),
+                ('2024-02-03', 'ALM', 'Padaria', 4.5, 'Cartao'),
+                ('2024-02-20', 'LUZ', 'Energia', 80.0, 'Conta'),
+                ('2024-03-01', 'ALM', 'Mercado', 12.0, 'Conta');"
+        ).unwrap();
+        drop(database);
+        
+        let api = WarehouseApi::new(config);
+        let filter = TransactionFilter {
+            from: NaiveDate::from_ymd_opt(2024, 2, 1),
+            tipo: Some("ALM".to_string()),
+            ..TransactionFilter::default()
+        };
+        let document = api.transactions(&filter).unwrap();
+        let descriptions: Vec<_> = document.rows.iter().map(|row| row[2].clone()).collect();
+        assert_eq!(descriptions, vec![json!("Mercado"), json!("Padaria")]);
+        
+        let filter = TransactionFilter { origin: Some("Conta".to_string()), limit: Some(1), ..TransactionFilter::default() };
+        assert_eq!(api.transactions(&filter).unwrap().rows[0][0], json!("2024-03-01"));
+        
+        // Filter values are bound, never spliced into the SQL
+        let filter = TransactionFilter { tipo: Some("x'; DROP TABLE LANCAMENTOS_GERAIS; --".to_string()), ..TransactionFilter::default() };
+        assert!(api.transactions(&filter).unwrap().rows.is_empty());
+        assert_eq!(api.transactions(&TransactionFilter::default()).unwrap().rows.len(), 4);
+        
+        // No limit means the default one, and larger limits are capped
+        let (sql, _) = TransactionFilter::default().query("LANCAMENTOS_GERAIS");
+        assert!(sql.ends_with(" LIMIT 1000"), "{}", sql);
+        let (sql, _) = TransactionFilter { limit: Some(1_000_000), ..TransactionFilter::default() }.query("LANCAMENTOS_GERAIS");
+        assert!(sql.ends_with(" LIMIT 10000"), "{}", sql);
+    }
+    
+    #[cfg(feature = "serve")]
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 64);
+        assert!(token.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token().unwrap());
+    }
+    
+    #[test]
+    fn test_run_token() {
+        let token = "0f".repeat(32);
+        
+        assert!(authorized(Some(&format!("Bearer {}", token)), &token));
+        assert!(!authorized(Some("Bearer wrong"), &token));
+        assert!(!authorized(Some(&token), &token));
+        assert!(!authorized(None, &token));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,324 @@
+/*!
+# SQL Shell Module
+
+Ad-hoc SQL against the generated warehouse, behind `pdw query`. A statement
+given on the command line runs once; without one the binary reads statements
+line by line until a `;` ends them, so a query may span several lines. Lines
+starting with a dot are meta-commands (`.tables`, `.schema [TABLE]`,
+`.mode table|csv|json`, `.help`, `.quit`). Results are printed as a table, CSV
+or a JSON array of objects.
+
+The table layout is shared by every terminal listing (`pdw query`,
+`pdw history`, `pdw schema` and the summary closing a run): columns sized to
+their contents, wrapped to the terminal width when one is known, and numeric
+columns aligned to the right.
+*/
+
+use crate::database::{quote_identifier, DatabaseManager};
+use crate::error::{PdwError, ReportError};
+use comfy_table::{presets, CellAlignment, ContentArrangement, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Layout of query results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Bordered table, numbers aligned to the right
+    #[default]
+    Table,
+    /// Comma separated values with a header row
+    Csv,
+    /// Array of objects keyed by column name
+    Json,
+}
+
+/// What the shell did with an input line
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellStep {
+    /// The statement continues on the next line
+    Continue,
+    /// Text to print
+    Output(String),
+    /// `.quit` or `.exit`
+    Quit,
+}
+
+/// Line-fed SQL session over an open database
+pub struct SqlShell<'a> {
+    database: &'a DatabaseManager,
+    format: OutputFormat,
+    buffer: String,
+}
+
+impl<'a> SqlShell<'a> {
+    pub fn new(database: &'a DatabaseManager, format: OutputFormat) -> Self {
+        Self { database, format, buffer: String::new() }
+    }
+    
+    /// Prompt for the next line: a fresh one or a statement continuation
+    pub fn prompt(&self) -> &'static str {
+        if self.buffer.is_empty() { "pdw> " } else { "...> " }
+    }
+    
+    /// Feed one input line; a failing statement is discarded so the session can go on
+    pub fn feed(&mut self, line: &str) -> Result<ShellStep, PdwError> {
+        let trimmed = line.trim();
+        if self.buffer.is_empty() {
+            if trimmed.is_empty() {
+                return Ok(ShellStep::Continue);
+            }
+            if let Some(command) = trimmed.strip_prefix('.') {
+                return self.meta_command(command);
+            }
+        }
+        
+        if !self.buffer.is_empty() {
+            self.buffer.push('\n');
+        }
+        self.buffer.push_str(line);
+        if !trimmed.ends_with(';') {
+            return Ok(ShellStep::Continue);
+        }
+        
+        let sql = std::mem::take(&mut self.buffer);
+        run_statement(self.database, &sql, self.format).map(ShellStep::Output)
+    }
+    
+    /// Drop a partially typed statement (Ctrl+C)
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+    }
+    
+    fn meta_command(&mut self, command: &str) -> Result<ShellStep, PdwError> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let argument = words.next();
+        
+        let output = match name {
+            "quit" | "exit" => return Ok(ShellStep::Quit),
+            "tables" => {
+                let rows = self.database.execute_query(
+                    "SELECT name FROM sqlite_master WHERE type IN ('table', 'view') \
+                     AND name NOT LIKE 'sqlite_%' ORDER BY name"
+                )?;
+                rows.iter().map(|row| row.first().map(cell_text).unwrap_or_default()).collect::<Vec<_>>().join("\n")
+            }
+            "schema" => {
+                let rows = match argument {
+                    Some(table) => self.database.execute_query_with_params(
+                        "SELECT sql FROM sqlite_master WHERE sql IS NOT NULL AND name = ?1 COLLATE NOCASE",
+                        [table],
+                    )?,
+                    None => self.database.execute_query(
+                        "SELECT sql FROM sqlite_master WHERE sql IS NOT NULL \
+                         AND name NOT LIKE 'sqlite_%' ORDER BY type DESC, name"
+                    )?,
+                };
+                rows.iter().map(|row| format!("{};", row.first().map(cell_text).unwrap_or_default())).collect::<Vec<_>>().join("\n")
+            }
+            "mode" => match argument.map(str::to_ascii_lowercase).as_deref() {
+                Some("table") => { self.format = OutputFormat::Table; String::new() }
+                Some("csv") => { self.format = OutputFormat::Csv; String::new() }
+                Some("json") => { self.format = OutputFormat::Json; String::new() }
+                _ => return Err(ReportError::UnsupportedFormat {
+                    format: argument.unwrap_or_default().to_string(),
+                }.into()),
+            },
+            "help" => HELP.to_string(),
+            _ => return Err(ReportError::QueryProcessing {
+                query_name: format!(".{}", name),
+                reason: "unknown command, see .help".to_string(),
+            }.into()),
+        };
+        
+        Ok(ShellStep::Output(output))
+    }
+}
+
+const HELP: &str = "\
+.tables            List tables and views
+.schema [TABLE]    Show CREATE statements
+.mode FORMAT       Output as table, csv or json
+.quit              Leave the shell
+SQL statements end with ';' and may span several lines";
+
+/// Run one statement and render its result; statements without a result set print nothing
+pub fn run_statement(database: &DatabaseManager, sql: &str, format: OutputFormat) -> Result<String, PdwError> {
+    let columns = database.query_columns(sql)?;
+    let rows = database.execute_query(sql)?;
+    if columns.is_empty() {
+        return Ok(String::new());
+    }
+    render(&columns, &rows, format)
+}
+
+/// Tables and views with their column and row counts, or the columns of one table
+pub fn schema(database: &DatabaseManager, table: Option<&str>) -> Result<(Vec<String>, Vec<Vec<Value>>), PdwError> {
+    let header = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+    let Some(table) = table else {
+        let mut rows = database.execute_query(
+            "SELECT name, type FROM sqlite_master WHERE type IN ('table', 'view') \
+             AND name NOT LIKE 'sqlite_%' ORDER BY name"
+        )?;
+        for row in &mut rows {
+            let name = row.first().map(cell_text).unwrap_or_default();
+            let count = database.execute_query(&format!("SELECT COUNT(*) FROM {}", quote_identifier(&name)))?;
+            row.push(Value::from(database.table_columns(&quote_identifier(&name))?.len()));
+            row.push(count.first().and_then(|r| r.first()).cloned().unwrap_or(Value::Null));
+        }
+        return Ok((header(&["Tabela", "Tipo", "Colunas", "Linhas"]), rows));
+    };
+    
+    let rows: Vec<Vec<Value>> = database.execute_query(&format!("PRAGMA table_info({})", quote_identifier(table)))?
+        .into_iter()
+        .map(|row| vec![
+            row.get(1).cloned().unwrap_or(Value::Null),
+            row.get(2).cloned().unwrap_or(Value::Null),
+            Value::from(if row.get(3).and_then(Value::as_i64) == Some(1) { "NOT NULL" } else { "" }),
+            Value::from(if row.get(5).and_then(Value::as_i64).unwrap_or(0) > 0 { "PK" } else { "" }),
+        ])
+        .collect();
+    if rows.is_empty() {
+        return Err(ReportError::QueryProcessing {
+            query_name: table.to_string(),
+            reason: "no such table or view".to_string(),
+        }.into());
+    }
+    Ok((header(&["Coluna", "Tipo", "Nulo", "Chave"]), rows))
+}
+
+/// Render a result set in the given format
+pub fn render(columns: &[String], rows: &[Vec<Value>], format: OutputFormat) -> Result<String, PdwError> {
+    match format {
+        OutputFormat::Table => Ok(render_table(columns, rows)),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(columns).map_err(ReportError::from)?;
+            for row in rows {
+                writer.write_record(row.iter().map(cell_text)).map_err(ReportError::from)?;
+            }
+            let bytes = writer.into_inner().map_err(|e| ReportError::OutputGeneration {
+                format: "csv".to_string(),
+                reason: e.to_string(),
+            })?;
+            Ok(String::from_utf8_lossy(&bytes).trim_end().to_string())
+        }
+        OutputFormat::Json => {
+            // Built by hand to keep the column order of the query
+            let objects: Vec<String> = rows.iter()
+                .map(|row| {
+                    let fields: Vec<String> = columns.iter().zip(row)
+                        .map(|(column, value)| format!("{}: {}", Value::String(column.clone()), value))
+                        .collect();
+                    format!("  {{{}}}", fields.join(", "))
+                })
+                .collect();
+            if objects.is_empty() {
+                Ok("[]".to_string())
+            } else {
+                Ok(format!("[\n{}\n]", objects.join(",\n")))
+            }
+        }
+    }
+}
+
+fn render_table(columns: &[String], rows: &[Vec<Value>]) -> String {
+    let mut table = Table::new();
+    table.load_preset(presets::ASCII_MARKDOWN)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(columns);
+    for row in rows {
+        table.add_row(row.iter().map(cell_text));
+    }
+    
+    // Columns holding only numbers (and NULLs) line up on the right
+    for (index, column) in table.column_iter_mut().enumerate() {
+        let values = || rows.iter().filter_map(|row| row.get(index)).filter(|value| !value.is_null());
+        if values().next().is_some() && values().all(Value::is_number) {
+            column.set_cell_alignment(CellAlignment::Right);
+        }
+    }
+    
+    format!("{}\n({} row{})", table, rows.len(), if rows.len() == 1 { "" } else { "s" })
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn database() -> (tempfile::TempDir, DatabaseManager) {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let database = DatabaseManager::new(&temp_dir.path().join("shell.db")).unwrap();
+        database.connection().execute_batch(
+            "CREATE TABLE contas (Nome TEXT, Saldo REAL);
+             INSERT INTO contas VALUES ('Nubank', 10.5), ('Itaú', NULL);"
+        ).unwrap();
+        (temp_dir, database)
+    }
+    
+    #[test]
+    fn test_output_formats() {
+        let (_temp_dir, database) = database();
+        let sql = "SELECT Nome, Saldo FROM contas ORDER BY Nome";
+        
+        let table = run_statement(&database, sql, OutputFormat::Table).unwrap();
+        assert_eq!(table, "| Nome   | Saldo |\n|--------|-------|\n| Itaú   |       |\n| Nubank |  10.5 |\n(2 rows)");
+        
+        let csv = run_statement(&database, sql, OutputFormat::Csv).unwrap();
+        assert_eq!(csv, "Nome,Saldo\nItaú,\nNubank,10.5");
+        
+        let json = run_statement(&database, sql, OutputFormat::Json).unwrap();
+        assert_eq!(json, "[\n  {\"Nome\": \"Itaú\", \"Saldo\": null},\n  {\"Nome\": \"Nubank\", \"Saldo\": 10.5}\n]");
+    }
+    
+    #[test]
+    fn test_multi_line_statements() {
+        let (_temp_dir, database) = database();
+        let mut shell = SqlShell::new(&database, OutputFormat::Csv);
+        
+        assert_eq!(shell.feed("SELECT COUNT(*) AS n").unwrap(), ShellStep::Continue);
+        assert_eq!(shell.prompt(), "...> ");
+        assert_eq!(shell.feed("FROM contas;").unwrap(), ShellStep::Output("n\n2".to_string()));
+        assert_eq!(shell.prompt(), "pdw> ");
+        
+        // A failing statement is dropped, the session goes on
+        assert!(shell.feed("SELECT * FROM missing;").is_err());
+        assert_eq!(shell.prompt(), "pdw> ");
+        
+        assert_eq!(shell.feed("DELETE FROM contas;").unwrap(), ShellStep::Output(String::new()));
+        assert_eq!(shell.feed("SELECT COUNT(*) AS n FROM contas;").unwrap(), ShellStep::Output("n\n0".to_string()));
+    }
+    
+    #[test]
+    fn test_meta_commands() {
+        let (_temp_dir, database) = database();
+        let mut shell = SqlShell::new(&database, OutputFormat::Table);
+        
+        assert_eq!(shell.feed(".tables").unwrap(), ShellStep::Output("contas".to_string()));
+        assert_eq!(
+            shell.feed(".schema CONTAS").unwrap(),
+            ShellStep::Output("CREATE TABLE contas (Nome TEXT, Saldo REAL);".to_string())
+        );
+        shell.feed(".mode json").unwrap();
+        assert_eq!(shell.feed("SELECT 1 AS x;").unwrap(), ShellStep::Output("[\n  {\"x\": 1}\n]".to_string()));
+        assert!(shell.feed(".mode xml").is_err());
+        assert!(shell.feed(".frobnicate").is_err());
+        assert_eq!(shell.feed(".quit").unwrap(), ShellStep::Quit);
+        
+        let (columns, rows) = schema(&database, None).unwrap();
+        assert_eq!(columns, vec!["Tabela", "Tipo", "Colunas", "Linhas"]);
+        assert_eq!(rows, vec![vec![Value::from("contas"), Value::from("table"), Value::from(2), Value::from(2)]]);
+        let (_, rows) = schema(&database, Some("contas")).unwrap();
+        assert_eq!(rows[1], vec![Value::from("Saldo"), Value::from("REAL"), Value::from(""), Value::from("")]);
+        assert!(schema(&database, Some("missing")).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,221 @@
+/*!
+# Splits Module
+
+One purchase often covers several TIPOs: a supermarket receipt with pharmacy
+items lands entirely on the supermarket's TIPO. With `[splits]` enabled, a
+SPLITS sheet of the input workbook lists the parts of such entries, one row per
+part:
+
+| DATA | ORIGEM | DESCRICAO | TIPO | VALOR |
+|------|--------|-----------|------|-------|
+| 15/01/2024 | Cartão | SUPERMERCADO BOM PRECO | FARMACIA | 45,90 |
+
+Entries of that day, origin and description (case and accents ignored) are
+expanded before they are transformed: one entry per part, with the part's TIPO
+and amount on the entry's side (debit or credit), plus one with the rest of the
+amount under the entry's own TIPO. Categorization rules fill a missing TIPO but
+never replace the TIPO of a split, so pivots and budgets see the split
+categories. Parts adding up to more than the entry leave it whole, with a
+warning.
+*/
+
+use crate::budgets;
+use crate::collation;
+use crate::error::{ConfigError, PdwError};
+use crate::excel::{self, Transaction};
+use crate::money::{self, Decimal};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Split settings (`[splits]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Input sheet with the parts, read when present
+    #[serde(default = "default_splits_sheet")]
+    pub sheet: String,
+}
+
+fn default_splits_sheet() -> String {
+    "SPLITS".to_string()
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sheet: default_splits_sheet(),
+        }
+    }
+}
+
+/// Share of a split entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitPart {
+    pub tipo: String,
+    pub amount: Decimal,
+}
+
+/// Parts of the split entries, by day, origin and description
+#[derive(Debug, Clone, Default)]
+pub struct SplitRules {
+    parts: HashMap<(NaiveDate, String, String), Vec<SplitPart>>,
+}
+
+impl SplitRules {
+    /// Parts of a SPLITS sheet (header first)
+    pub fn from_sheet(rows: &[Vec<String>], sheet: &str) -> Result<Self, PdwError> {
+        let mut rules = Self::default();
+        let Some((header, rows)) = rows.split_first() else {
+            return Ok(rules);
+        };
+        
+        let column = |names: &[&str], field: &str| header.iter()
+            .position(|h| names.contains(&collation::fold(h.trim()).as_str()))
+            .ok_or_else(|| ConfigError::MissingField {
+                field: format!("{} column of the {} sheet", field, sheet),
+            });
+        let date_column = column(&["data", "date"], "DATA")?;
+        let origin_column = column(&["origem", "origin"], "ORIGEM")?;
+        let description_column = column(&["descricao", "description"], "DESCRICAO")?;
+        let tipo_column = column(&["tipo"], "TIPO")?;
+        let amount_column = column(&["valor", "amount"], "VALOR")?;
+        
+        for row in rows {
+            let cell = |column: usize| row.get(column).map(|c| c.trim()).unwrap_or_default();
+            if cell(tipo_column).is_empty() {
+                continue;
+            }
+            let date = parse_split_date(cell(date_column)).ok_or_else(|| ConfigError::InvalidFormat {
+                message: format!("Invalid date '{}' in the {} sheet", cell(date_column), sheet),
+            })?;
+            let amount = budgets::parse_limit(cell(amount_column))
+                .and_then(money::from_f64)
+                .filter(|amount| *amount > Decimal::ZERO)
+                .ok_or_else(|| ConfigError::InvalidFormat {
+                    message: format!("Invalid amount '{}' for {} in the {} sheet", cell(amount_column), cell(tipo_column), sheet),
+                })?;
+            
+            rules.parts.entry(key(date, cell(origin_column), cell(description_column)))
+                .or_default()
+                .push(SplitPart { tipo: cell(tipo_column).to_string(), amount });
+        }
+        
+        Ok(rules)
+    }
+    
+    /// Number of entries with parts
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+    
+    /// Whether no entry is split
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+    
+    /// Entries replacing a split entry (its parts, then the rest of its amount), None when it is not split
+    pub fn split(&self, transaction: &Transaction) -> Option<Vec<Transaction>> {
+        let description = transaction.description.as_deref().unwrap_or_default();
+        let parts = self.parts.get(&key(transaction.date?, &transaction.origin, description))?;
+        
+        let debit = transaction.debit.unwrap_or_default();
+        let (amount, is_debit) = if debit.is_zero() {
+            (transaction.credit.unwrap_or_default(), false)
+        } else {
+            (debit, true)
+        };
+        let total: Decimal = parts.iter().map(|part| part.amount).sum();
+        if amount.is_zero() || total > amount.abs() {
+            tracing::warn!(
+                "Split parts of '{}' on {} ({}) add up to {}, more than its {} - entry kept whole",
+                description.trim(), transaction.date?, transaction.origin.trim(), total, amount.abs()
+            );
+            return None;
+        }
+        
+        // Parts take the sign of the entry, so a refund splits into refunds
+        let sign = if amount.is_sign_negative() { -Decimal::ONE } else { Decimal::ONE };
+        let entry = |tipo: Option<String>, value: Decimal| Transaction {
+            transaction_type: tipo,
+            credit: if is_debit { None } else { Some(value) },
+            debit: if is_debit { Some(value) } else { None },
+            ..transaction.clone()
+        };
+        let mut entries: Vec<Transaction> = parts.iter()
+            .map(|part| entry(Some(part.tipo.clone()), part.amount * sign))
+            .collect();
+        let rest = amount - total * sign;
+        if !rest.is_zero() {
+            entries.push(entry(transaction.transaction_type.clone(), rest));
+        }
+        
+        Some(entries)
+    }
+}
+
+fn key(date: NaiveDate, origin: &str, description: &str) -> (NaiveDate, String, String) {
+    (date, collation::fold(origin.trim()), collation::fold(description.trim()))
+}
+
+/// Day typed as a date or kept by the sheet as a serial number
+fn parse_split_date(text: &str) -> Option<NaiveDate> {
+    excel::parse_date(text).or_else(|| {
+        excel::DateSystem::Excel1900.serial_date(text.parse().ok()?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn rows(cells: &[&[&str]]) -> Vec<Vec<String>> {
+        cells.iter().map(|row| row.iter().map(|c| c.to_string()).collect()).collect()
+    }
+    
+    fn purchase(description: &str, debit: i64) -> Transaction {
+        Transaction {
+            date: NaiveDate::from_ymd_opt(2024, 1, 15),
+            transaction_type: Some("MERCADO".to_string()),
+            description: Some(description.to_string()),
+            credit: None,
+            debit: Some(Decimal::from(debit)),
+            origin: "Cartão".to_string(),
+            currency: None,
+            tags: None,
+            row: Some(7),
+            invalid_amount: None,
+        }
+    }
+    
+    #[test]
+    fn test_entries_split_into_parts() {
+        let sheet = rows(&[
+            &["Data", "Origem", "Descrição", "TIPO", "Valor"],
+            &["15/01/2024", "cartao", "Supermercado Bom Preço", "FARMACIA", "45,90"],
+            &["45306", "Cartão", "SUPERMERCADO BOM PREÇO", "LIMPEZA", "20"],
+            &["", "", "", "", ""],
+            &["16/01/2024", "Cartão", "Padaria", "LANCHE", "50"],
+        ]);
+        let rules = SplitRules::from_sheet(&sheet, "SPLITS").unwrap();
+        assert_eq!(rules.len(), 2);
+        
+        let entries = rules.split(&purchase(" supermercado bom preco", 100)).unwrap();
+        let parts: Vec<_> = entries.iter()
+            .map(|e| (e.transaction_type.clone().unwrap(), e.debit.unwrap().to_string(), e.row))
+            .collect();
+        assert_eq!(parts, vec![
+            ("FARMACIA".to_string(), "45.9".to_string(), Some(7)),
+            ("LIMPEZA".to_string(), "20".to_string(), Some(7)),
+            ("MERCADO".to_string(), "34.1".to_string(), Some(7)),
+        ]);
+        
+        // Other entries, and parts larger than the entry, are left whole
+        assert!(rules.split(&purchase("Feira", 100)).is_none());
+        assert!(rules.split(&purchase("Supermercado Bom Preço", 50)).is_none());
+        assert!(SplitRules::from_sheet(&rows(&[&["DATA", "ORIGEM", "DESCRICAO", "TIPO"]]), "SPLITS").is_err());
+        assert!(SplitRules::from_sheet(&rows(&[&["DATA", "ORIGEM", "DESCRICAO", "TIPO", "VALOR"], &["15/01/2024", "Conta", "x", "A", "-5"]]), "SPLITS").is_err());
+    }
+}
\ No newline at end of file
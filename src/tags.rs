@@ -0,0 +1,188 @@
+/*!
+# Tags Module
+
+Free-form labels on entries, independent of TIPO. With `[tags]` enabled,
+accounting sheets (and CSV files) may carry a Tags column after the expected
+ones, holding tags separated by semicolons ("viagem-2024; ferias"). The tags of
+an entry are kept trimmed, lower case and without repeats in the Tags column of
+the general entries, and the summary phase normalizes them into:
+
+- TAGS - one row per tag (Id, Tag, Lancamentos)
+- TRANSACTION_TAGS - the tags of each entry (RowHash, TagId)
+- TAGS_MENSAL - credits, debits and entries per tag and AnoMes
+- TAGS_TIPO - credits, debits and entries per tag and TIPO
+
+YAML queries slice by tag through `{tags}`, a (RowHash, Tag) row source:
+`SELECT e.* FROM {entries_table} e JOIN {tags} t ON t.RowHash = e.RowHash WHERE t.Tag = 'viagem-2024'`.
+*/
+
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, PdwError};
+use crate::money::MoneyMode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Column of the general entries holding an entry's tags
+pub const TAGS_COLUMN: &str = "Tags";
+/// One row per tag
+pub const TAGS_TABLE: &str = "TAGS";
+/// Tags of each entry, by RowHash
+pub const TRANSACTION_TAGS_TABLE: &str = "TRANSACTION_TAGS";
+/// Totals per tag and AnoMes
+pub const TAGS_MONTHLY_TABLE: &str = "TAGS_MENSAL";
+/// Totals per tag and TIPO
+pub const TAGS_TYPE_TABLE: &str = "TAGS_TIPO";
+
+/// Tag settings (`[tags]`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TagsConfig {
+    /// Load the Tags column and build the tag tables
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Row source `{tags}` expands to in YAML queries
+pub fn tagged_entries() -> String {
+    format!(
+        "(SELECT tt.RowHash AS RowHash, t.Tag AS Tag FROM {} tt JOIN {} t ON t.Id = tt.TagId)",
+        TRANSACTION_TAGS_TABLE, TAGS_TABLE
+    )
+}
+
+/// Header of the optional tags column of accounting sheets
+pub(crate) fn is_tags_header(name: &str) -> bool {
+    matches!(name.trim().to_lowercase().as_str(), "tags" | "etiquetas")
+}
+
+/// Tags of a cell, trimmed, lower case, without repeats and in their first order; None when empty
+pub fn normalize(text: &str) -> Option<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(';').map(|tag| tag.trim().to_lowercase()).filter(|tag| !tag.is_empty()) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    (!tags.is_empty()).then(|| tags.join(";"))
+}
+
+/// Rebuild the tag tables from the Tags column of the general entries; returns the tags found
+pub fn write_tables(database: &DatabaseManager, entries_table: &str) -> Result<usize, PdwError> {
+    let tagged = if database.table_columns(entries_table)?.iter().any(|c| c.eq_ignore_ascii_case(TAGS_COLUMN)) {
+        database.execute_query(&format!(
+            "SELECT RowHash, {} FROM {} WHERE RowHash IS NOT NULL AND {} IS NOT NULL",
+            TAGS_COLUMN, entries_table, TAGS_COLUMN
+        ))?
+    } else {
+        Vec::new()
+    };
+    
+    // Ids follow the tags' alphabetical order, so reloads keep them stable
+    let mut links: Vec<(String, String)> = Vec::new();
+    let mut counts: BTreeMap<String, i64> = BTreeMap::new();
+    for row in &tagged {
+        let (Some(hash), Some(tags)) = (row.first().and_then(Value::as_str), row.get(1).and_then(Value::as_str)) else {
+            continue;
+        };
+        for tag in normalize(tags).iter().flat_map(|tags| tags.split(';')) {
+            *counts.entry(tag.to_string()).or_insert(0) += 1;
+            links.push((hash.to_string(), tag.to_string()));
+        }
+    }
+    let ids: BTreeMap<&str, i64> = counts.keys().enumerate().map(|(index, tag)| (tag.as_str(), index as i64 + 1)).collect();
+    
+    for table in [TAGS_TYPE_TABLE, TAGS_MONTHLY_TABLE, TRANSACTION_TAGS_TABLE, TAGS_TABLE] {
+        database.drop_table(table)?;
+    }
+    execute(database, &format!("CREATE TABLE {} (Id INTEGER PRIMARY KEY, Tag TEXT NOT NULL UNIQUE, Lancamentos INTEGER)", TAGS_TABLE))?;
+    execute(database, &format!("CREATE TABLE {} (RowHash TEXT NOT NULL, TagId INTEGER NOT NULL REFERENCES {}(Id))", TRANSACTION_TAGS_TABLE, TAGS_TABLE))?;
+    
+    let insert = |query: &str, params: &[&dyn rusqlite::ToSql], table: &str| {
+        database.connection().execute(query, params)
+            .map_err(|e| DatabaseError::DataInsertion { table: table.to_string(), reason: e.to_string() })
+    };
+    let tag_query = format!("INSERT INTO {} (Id, Tag, Lancamentos) VALUES (?1, ?2, ?3)", TAGS_TABLE);
+    for (tag, count) in &counts {
+        insert(&tag_query, &[&ids[tag.as_str()], tag, count], TAGS_TABLE)?;
+    }
+    let link_query = format!("INSERT INTO {} (RowHash, TagId) VALUES (?1, ?2)", TRANSACTION_TAGS_TABLE);
+    for (hash, tag) in &links {
+        insert(&link_query, &[hash, &ids[tag.as_str()]], TRANSACTION_TAGS_TABLE)?;
+    }
+    execute(database, &format!("CREATE INDEX IF NOT EXISTS idx_{0}_RowHash ON {0}(RowHash)", TRANSACTION_TAGS_TABLE))?;
+    
+    write_summaries(database, entries_table)?;
+    Ok(counts.len())
+}
+
+/// Totals per tag and AnoMes, and per tag and TIPO
+fn write_summaries(database: &DatabaseManager, entries_table: &str) -> Result<(), PdwError> {
+    let money = database.money_mode();
+    if money == MoneyMode::Decimal {
+        database.add_money_columns(entries_table)?;
+    }
+    let (credit, debit) = (money.sum_sql("e.Credito", None), money.sum_sql("e.Debito", None));
+    
+    for (table, column) in [(TAGS_MONTHLY_TABLE, "AnoMes"), (TAGS_TYPE_TABLE, "TIPO")] {
+        execute(database, &format!(
+            "CREATE TABLE {table} AS
+             SELECT t.Tag, e.{column}, COUNT(*) as Lancamentos,
+                    ROUND({credit}, 2) as Credito,
+                    ROUND({debit}, 2) as Debito
+             FROM {entries} e
+             JOIN {tagged} t ON t.RowHash = e.RowHash
+             GROUP BY t.Tag, e.{column}
+             ORDER BY t.Tag, e.{column}",
+            table = table,
+            column = column,
+            credit = credit,
+            debit = debit,
+            entries = entries_table,
+            tagged = tagged_entries(),
+        ))?;
+    }
+    Ok(())
+}
+
+fn execute(database: &DatabaseManager, query: &str) -> Result<(), PdwError> {
+    database.connection().execute(query, [])
+        .map(|_| ())
+        .map_err(|e| DatabaseError::SqlExecution { query: query.to_string(), reason: e.to_string() }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    #[test]
+    fn test_tag_tables() {
+        assert_eq!(normalize(" Viagem-2024 ;ferias; viagem-2024;"), Some("viagem-2024;ferias".to_string()));
+        assert_eq!(normalize(" ; "), None);
+        
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        
+        // Without a Tags column the tables exist and are empty, so `{tags}` queries still run
+        assert_eq!(write_tables(&db, "LANCAMENTOS_GERAIS").unwrap(), 0);
+        assert_eq!(db.execute_query(&format!("SELECT COUNT(*) FROM {}", tagged_entries())).unwrap(), vec![vec![json!(0)]]);
+        
+        db.add_derived_columns("LANCAMENTOS_GERAIS", &[(TAGS_COLUMN.to_string(), "TEXT")]).unwrap();
+        db.connection().execute_batch(
+            "INSERT INTO LANCAMENTOS_GERAIS (TIPO, Credito, Debito, AnoMes, RowHash, Tags) VALUES ('HOTEL', 0, 600, '2024/07', 'a-1', 'viagem-2024;ferias');
+             INSERT INTO LANCAMENTOS_GERAIS (TIPO, Credito, Debito, AnoMes, RowHash, Tags) VALUES ('ALM', 0, 80, '2024/07', 'b-1', 'Viagem-2024');
+             INSERT INTO LANCAMENTOS_GERAIS (TIPO, Credito, Debito, AnoMes, RowHash, Tags) VALUES ('ALM', 0, 50, '2024/08', 'c-1', NULL);"
+        ).unwrap();
+        assert_eq!(write_tables(&db, "LANCAMENTOS_GERAIS").unwrap(), 2);
+        assert_eq!(db.execute_query("SELECT Id, Tag, Lancamentos FROM TAGS ORDER BY Id").unwrap(),
+                   vec![vec![json!(1), json!("ferias"), json!(1)], vec![json!(2), json!("viagem-2024"), json!(2)]]);
+        assert_eq!(db.execute_query("SELECT Tag, TIPO, Lancamentos, Debito FROM TAGS_TIPO").unwrap(), vec![
+            vec![json!("ferias"), json!("HOTEL"), json!(1), json!(600.0)],
+            vec![json!("viagem-2024"), json!("ALM"), json!(1), json!(80.0)],
+            vec![json!("viagem-2024"), json!("HOTEL"), json!(1), json!(600.0)],
+        ]);
+        assert_eq!(db.execute_query("SELECT Tag, AnoMes, Debito FROM TAGS_MENSAL WHERE Tag = 'viagem-2024'").unwrap(),
+                   vec![vec![json!("viagem-2024"), json!("2024/07"), json!(680.0)]]);
+    }
+}
\ No newline at end of file
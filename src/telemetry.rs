@@ -0,0 +1,391 @@
+/*!
+# Telemetry Module
+
+Run metrics for monitoring scheduled runs (`[telemetry]`). While enabled, the
+"phase" and "sheet" spans of a run are timed, and at its end the run's metrics
+are pushed to a Prometheus pushgateway and/or an OpenTelemetry collector (OTLP
+over HTTP, JSON encoded):
+
+- pdw_run_rows_loaded / pdw_run_rows_discarded - entries loaded and discarded
+- pdw_run_duration_seconds - duration of the run
+- pdw_run_failed - 1 when the run failed
+- pdw_run_timestamp_seconds - when the run ended (Unix time)
+- pdw_sheet_rows{sheet} - rows read from each sheet or source
+- pdw_phase_duration_seconds{phase} / pdw_sheet_duration_seconds{sheet}
+
+Every metric carries the command as a label, so an alert rule such as
+`pdw_run_rows_loaded < 0.9 * pdw_run_rows_loaded offset 1d` catches a load that
+shrank. The metrics are always collected; pushing them needs a build with
+`--features telemetry`. Push failures are logged and never abort a run.
+*/
+
+use crate::error::{ConfigError, PdwError};
+use crate::runs::RunRecord;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Metrics push settings (`[telemetry]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Pushgateway base URL ("http://localhost:9091")
+    #[serde(default)]
+    pub pushgateway: Option<String>,
+    /// OTLP/HTTP collector base URL ("http://localhost:4318")
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+    /// Pushgateway job and OTLP service name
+    #[serde(default = "default_job")]
+    pub job: String,
+    /// Pushgateway instance; the hostname when not set
+    #[serde(default)]
+    pub instance: Option<String>,
+    /// Seconds to wait for each endpoint
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_job() -> String {
+    "pdw".to_string()
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            pushgateway: None,
+            otlp_endpoint: None,
+            job: default_job(),
+            instance: None,
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    /// Check the endpoints before a run
+    pub fn check(&self) -> Result<(), PdwError> {
+        if self.pushgateway.is_none() && self.otlp_endpoint.is_none() {
+            return Err(ConfigError::MissingField {
+                field: "telemetry.pushgateway or telemetry.otlp_endpoint".to_string(),
+            }.into());
+        }
+        for (field, url) in [("pushgateway", &self.pushgateway), ("otlp_endpoint", &self.otlp_endpoint)] {
+            if let Some(url) = url.as_deref().filter(|url| !url.starts_with("http://") && !url.starts_with("https://")) {
+                return Err(ConfigError::InvalidFormat {
+                    message: format!("telemetry.{} '{}' is not an http(s) URL", field, url),
+                }.into());
+            }
+        }
+        if !cfg!(feature = "telemetry") {
+            return Err(ConfigError::InvalidFormat {
+                message: "[telemetry] requires a build with --features telemetry".to_string(),
+            }.into());
+        }
+        Ok(())
+    }
+    
+    /// Instance label of the pushed metrics
+    pub fn instance_name(&self) -> String {
+        self.instance.clone().unwrap_or_else(|| {
+            hostname::get().unwrap_or_else(|_| "unknown".into()).to_string_lossy().to_string()
+        })
+    }
+}
+
+/// Time a timed span took
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanTiming {
+    /// "phase" or "sheet"
+    pub kind: &'static str,
+    pub name: String,
+    pub seconds: f64,
+}
+
+/// Spans timed while recording
+const TIMED_SPANS: [&str; 2] = ["phase", "sheet"];
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static TIMINGS: Mutex<Vec<SpanTiming>> = Mutex::new(Vec::new());
+
+/// Start or stop timing the phase and sheet spans
+pub fn record_spans(enabled: bool) {
+    RECORDING.store(enabled, Ordering::Relaxed);
+}
+
+/// Timings recorded since the last call, in the order the spans closed
+pub fn take_timings() -> Vec<SpanTiming> {
+    TIMINGS.lock().map(|mut timings| std::mem::take(&mut *timings)).unwrap_or_default()
+}
+
+/// Name and start of a timed span
+struct SpanStart {
+    kind: &'static str,
+    name: String,
+    started: Instant,
+}
+
+/// Value of the field naming a span (`phase = "..."`, `sheet = %...`)
+struct SpanName {
+    field: &'static str,
+    name: String,
+}
+
+impl Visit for SpanName {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.name = value.to_string();
+        }
+    }
+    
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == self.field {
+            self.name = format!("{:?}", value);
+        }
+    }
+}
+
+/// Layer timing the phase and sheet spans while recording
+pub(crate) struct SpanTimer;
+
+impl<S> Layer<S> for SpanTimer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let kind = attrs.metadata().name();
+        let Some(kind) = TIMED_SPANS.into_iter().find(|timed| *timed == kind) else {
+            return;
+        };
+        if !RECORDING.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut name = SpanName { field: kind, name: String::new() };
+        attrs.record(&mut name);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanStart { kind, name: name.name, started: Instant::now() });
+        }
+    }
+    
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(start) = span.extensions_mut().remove::<SpanStart>() else {
+            return;
+        };
+        if let Ok(mut timings) = TIMINGS.lock() {
+            timings.push(SpanTiming { kind: start.kind, name: start.name, seconds: start.started.elapsed().as_secs_f64() });
+        }
+    }
+}
+
+/// One sample: metric name, labels and value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: &'static str,
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+/// Metrics of a finished run
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunMetrics {
+    pub command: String,
+    /// Unix time the run ended, in seconds
+    pub finished_at: i64,
+    pub samples: Vec<Sample>,
+}
+
+impl RunMetrics {
+    /// Metrics of a run, with the span timings recorded during it
+    pub fn new(run: &RunRecord, command: &str, failed: bool, timings: &[SpanTiming]) -> Self {
+        let labels = |extra: Option<(&'static str, &str)>| {
+            let mut labels = vec![("command", command.to_string())];
+            labels.extend(extra.map(|(name, value)| (name, value.to_string())));
+            labels
+        };
+        let finished_at = crate::deterministic::now().timestamp();
+        
+        let mut samples = vec![
+            Sample { name: "pdw_run_rows_loaded", labels: labels(None), value: run.rows_loaded as f64 },
+            Sample { name: "pdw_run_rows_discarded", labels: labels(None), value: run.rows_discarded as f64 },
+            Sample { name: "pdw_run_duration_seconds", labels: labels(None), value: run.elapsed().as_secs_f64() },
+            Sample { name: "pdw_run_failed", labels: labels(None), value: if failed { 1.0 } else { 0.0 } },
+            Sample { name: "pdw_run_timestamp_seconds", labels: labels(None), value: finished_at as f64 },
+        ];
+        samples.extend(run.sheets.iter().map(|(sheet, rows)| Sample {
+            name: "pdw_sheet_rows",
+            labels: labels(Some(("sheet", sheet))),
+            value: *rows as f64,
+        }));
+        // A phase or sheet timed more than once in a run (e.g. reloads) adds up
+        for timing in timings {
+            let name = if timing.kind == "phase" { "pdw_phase_duration_seconds" } else { "pdw_sheet_duration_seconds" };
+            let sample_labels = labels(Some((timing.kind, &timing.name)));
+            match samples.iter_mut().find(|s| s.name == name && s.labels == sample_labels) {
+                Some(sample) => sample.value += timing.seconds,
+                None => samples.push(Sample { name, labels: sample_labels, value: timing.seconds }),
+            }
+        }
+        
+        Self { command: command.to_string(), finished_at, samples }
+    }
+    
+    /// Prometheus text exposition of the metrics, every one a gauge
+    pub fn prometheus_text(&self) -> String {
+        let mut text = String::new();
+        let mut declared: Vec<&str> = Vec::new();
+        for sample in &self.samples {
+            if !declared.contains(&sample.name) {
+                declared.push(sample.name);
+                text.push_str(&format!("# TYPE {} gauge\n", sample.name));
+            }
+            let labels: Vec<String> = sample.labels.iter()
+                .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+                .collect();
+            text.push_str(&format!("{}{{{}}} {}\n", sample.name, labels.join(","), sample.value));
+        }
+        text
+    }
+    
+    /// OTLP/HTTP JSON request with the metrics as gauges
+    pub fn otlp_json(&self, config: &TelemetryConfig) -> Value {
+        let attribute = |key: &str, value: &str| json!({ "key": key, "value": { "stringValue": value } });
+        let time = (self.finished_at * 1_000_000_000).to_string();
+        
+        let mut metrics: Vec<Value> = Vec::new();
+        for sample in &self.samples {
+            let point = json!({
+                "attributes": sample.labels.iter().map(|(name, value)| attribute(name, value)).collect::<Vec<_>>(),
+                "timeUnixNano": time,
+                "asDouble": sample.value,
+            });
+            match metrics.iter_mut().find(|metric| metric["name"] == sample.name) {
+                Some(metric) => metric["gauge"]["dataPoints"].as_array_mut().into_iter().for_each(|points| points.push(point.clone())),
+                None => metrics.push(json!({ "name": sample.name, "gauge": { "dataPoints": [point] } })),
+            }
+        }
+        
+        json!({
+            "resourceMetrics": [{
+                "resource": { "attributes": [
+                    attribute("service.name", &config.job),
+                    attribute("service.instance.id", &config.instance_name()),
+                    attribute("service.version", env!("CARGO_PKG_VERSION")),
+                ] },
+                "scopeMetrics": [{
+                    "scope": { "name": "pdw", "version": env!("CARGO_PKG_VERSION") },
+                    "metrics": metrics,
+                }],
+            }],
+        })
+    }
+}
+
+/// Escape a label value of the text exposition format
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Pushgateway URL of the run's group; pushing replaces the group's previous metrics,
+/// so each command has a group of its own and a `report` run keeps the last `run` metrics
+pub fn pushgateway_url(config: &TelemetryConfig, base: &str, command: &str) -> String {
+    format!(
+        "{}/metrics/job/{}/instance/{}/command/{}",
+        base.trim_end_matches('/'), config.job, config.instance_name(), command
+    )
+}
+
+/// OTLP/HTTP metrics URL of a collector
+pub fn otlp_url(base: &str) -> String {
+    let base = base.trim_end_matches('/');
+    if base.ends_with("/v1/metrics") {
+        base.to_string()
+    } else {
+        format!("{}/v1/metrics", base)
+    }
+}
+
+/// Push the metrics to the configured endpoints; returns the endpoints reached
+#[cfg(feature = "telemetry")]
+pub fn push(config: &TelemetryConfig, metrics: &RunMetrics) -> Result<usize, PdwError> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(std::time::Duration::from_secs(config.timeout_secs))
+        .user_agent(concat!("pdw/", env!("CARGO_PKG_VERSION")))
+        .build();
+    let failed = |endpoint: &str, e: ureq::Error| PdwError::Notification(format!("Metrics push to {} failed: {}", endpoint, e));
+    
+    let mut pushed = 0;
+    if let Some(base) = &config.pushgateway {
+        let url = pushgateway_url(config, base, &metrics.command);
+        agent.put(&url)
+            .set("Content-Type", "text/plain; version=0.0.4")
+            .send_string(&metrics.prometheus_text())
+            .map_err(|e| failed(&url, e))?;
+        pushed += 1;
+    }
+    if let Some(base) = &config.otlp_endpoint {
+        let url = otlp_url(base);
+        agent.post(&url)
+            .send_json(metrics.otlp_json(config))
+            .map_err(|e| failed(&url, e))?;
+        pushed += 1;
+    }
+    Ok(pushed)
+}
+
+#[cfg(not(feature = "telemetry"))]
+pub fn push(_config: &TelemetryConfig, _metrics: &RunMetrics) -> Result<usize, PdwError> {
+    Err(PdwError::Notification("[telemetry] requires a build with --features telemetry".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_run_metrics() {
+        let mut run = RunRecord::start();
+        run.record_sheet("Conta Corrente", 120);
+        run.rows_loaded = 118;
+        run.rows_discarded = 2;
+        let timings = [
+            SpanTiming { kind: "sheet", name: "Conta Corrente".to_string(), seconds: 0.25 },
+            SpanTiming { kind: "phase", name: "Creating pivot Tables".to_string(), seconds: 1.5 },
+            SpanTiming { kind: "sheet", name: "Conta Corrente".to_string(), seconds: 0.5 },
+        ];
+        let metrics = RunMetrics::new(&run, "run", false, &timings);
+        
+        let text = metrics.prometheus_text();
+        assert!(text.starts_with("# TYPE pdw_run_rows_loaded gauge\npdw_run_rows_loaded{command=\"run\"} 118\n"), "{}", text);
+        assert!(text.contains("pdw_run_failed{command=\"run\"} 0\n"));
+        assert!(text.contains("pdw_sheet_rows{command=\"run\",sheet=\"Conta Corrente\"} 120\n"));
+        assert!(text.contains("pdw_sheet_duration_seconds{command=\"run\",sheet=\"Conta Corrente\"} 0.75\n"));
+        assert_eq!(text.matches("# TYPE").count(), 8);
+        
+        let config = TelemetryConfig { instance: Some("nas".to_string()), ..Default::default() };
+        assert_eq!(pushgateway_url(&config, "http://gw:9091/", "run"), "http://gw:9091/metrics/job/pdw/instance/nas/command/run");
+        assert_eq!(otlp_url("http://otel:4318"), "http://otel:4318/v1/metrics");
+        let otlp = metrics.otlp_json(&config);
+        let sent = &otlp["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(sent.as_array().unwrap().len(), 8);
+        assert_eq!(sent[7]["name"], "pdw_phase_duration_seconds");
+        assert_eq!(sent[7]["gauge"]["dataPoints"][0]["attributes"][1]["value"]["stringValue"], "Creating pivot Tables");
+        
+        assert!(TelemetryConfig { enabled: true, ..Default::default() }.check().is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,221 @@
+/*!
+# Trash Module
+
+Soft delete of general entries: instead of being deleted, the entries a purge
+run with `--to-trash` removes are moved to the PDW_TRASH table, stamped with when
+they were excluded (`Excluido`) and the purge that did it (`Purga`, the Id in
+PDW_PURGES). Every summary, pivot and report reads the general entries table, so
+they leave the trashed entries out without a filter of their own, while the rows
+themselves are kept for the audit trail. Purges are the only deletions that can
+go to the trash; the entries a load replaces or quarantines are not kept here.
+
+`pdw trash list` shows the trashed entries and `pdw trash restore <PURGE_ID>`
+(or `--all`) moves a purge's entries back and marks the purge as restored
+(`Restaurado` in PDW_PURGES), so later loads no longer apply it. `pdw trash
+empty <PURGE_ID>` (or `--all`) deletes a purge's entries for good; later loads
+then delete the rows the purge matches instead of trashing them.
+*/
+
+use crate::database::{quote_identifier, DatabaseManager};
+use crate::deterministic;
+use crate::error::{DatabaseError, PdwError};
+use crate::purge::{self, PURGES_TABLE};
+use rusqlite::types::Value as SqlValue;
+use serde_json::Value;
+
+/// Table the soft-deleted entries are kept in
+pub const TRASH_TABLE: &str = "PDW_TRASH";
+
+/// Columns `pdw trash list` shows
+const LIST_COLUMNS: &str = "Id, Excluido, Purga, Data, TIPO, DESCRICAO, Credito, Debito, Origem";
+
+/// Move the entries matching `condition` (with its `?` parameters) to the trash under
+/// purge `purge_id`; returns the entries removed from the general entries.
+/// Entries already trashed by the same purge (same RowHash, e.g. after a reload) are not copied twice
+pub(crate) fn move_entries(database: &DatabaseManager, entries_table: &str, condition: &str,
+                           params: Vec<SqlValue>, purge_id: i64) -> Result<usize, PdwError> {
+    let columns = ensure_table(database, entries_table)?;
+    let column_list = columns.iter().map(|c| quote_identifier(c)).collect::<Vec<_>>().join(", ");
+    
+    let copy_query = format!(
+        "INSERT INTO {trash} (Excluido, Purga, {columns})
+         SELECT ?, ?, {columns} FROM {entries} WHERE {condition}
+         AND (RowHash IS NULL OR RowHash NOT IN (SELECT RowHash FROM {trash} WHERE Purga = ? AND RowHash IS NOT NULL))",
+        trash = TRASH_TABLE,
+        columns = column_list,
+        entries = entries_table,
+        condition = condition,
+    );
+    let copy_params = [
+        SqlValue::Text(deterministic::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+        SqlValue::Integer(purge_id),
+    ].into_iter().chain(params.iter().cloned()).chain([SqlValue::Integer(purge_id)]);
+    database.connection().execute(&copy_query, rusqlite::params_from_iter(copy_params))
+        .map_err(|e| DatabaseError::SqlExecution { query: copy_query, reason: e.to_string() })?;
+    
+    let delete_query = format!("DELETE FROM {} WHERE {}", entries_table, condition);
+    database.connection().execute(&delete_query, rusqlite::params_from_iter(params))
+        .map_err(|e| DatabaseError::SqlExecution { query: delete_query, reason: e.to_string() }.into())
+}
+
+/// Trashed entries, of the given purges or all of them when empty; no columns while nothing was trashed
+pub fn list(database: &DatabaseManager, purges: &[i64]) -> Result<(Vec<String>, Vec<Vec<Value>>), PdwError> {
+    if database.table_columns(TRASH_TABLE)?.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let query = format!("SELECT {} FROM {} WHERE {} ORDER BY Id", LIST_COLUMNS, TRASH_TABLE, purge_condition(purges));
+    Ok((database.query_columns(&query)?, database.execute_query(&query)?))
+}
+
+/// Move the trashed entries of the given purges (all when empty) back to the general entries
+/// and mark those purges as restored, in one transaction; returns the entries restored
+pub fn restore(database: &DatabaseManager, entries_table: &str, purges: &[i64]) -> Result<usize, PdwError> {
+    let trash_columns = database.table_columns(TRASH_TABLE)?;
+    if trash_columns.is_empty() {
+        return Ok(0);
+    }
+    let column_list = database.table_columns(entries_table)?.iter()
+        .filter(|column| trash_columns.iter().any(|c| c.eq_ignore_ascii_case(column)))
+        .map(|column| quote_identifier(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let selected = purge_condition(purges);
+    
+    let statements = [
+        format!(
+            "UPDATE {} SET Restaurado = '{}' WHERE Id IN (SELECT DISTINCT Purga FROM {} WHERE {})",
+            PURGES_TABLE, deterministic::now().format("%Y-%m-%d %H:%M:%S"), TRASH_TABLE, selected
+        ),
+        format!("INSERT INTO {} ({cols}) SELECT {cols} FROM {} WHERE {}", entries_table, TRASH_TABLE, selected, cols = column_list),
+        format!("DELETE FROM {} WHERE {}", TRASH_TABLE, selected),
+    ];
+    
+    database.connection().execute_batch("BEGIN")
+        .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+    let mut restored = 0;
+    for (index, statement) in statements.iter().enumerate() {
+        match database.connection().execute(statement, []) {
+            Ok(rows) if index == 1 => restored = rows,
+            Ok(_) => {}
+            Err(e) => {
+                let _ = database.connection().execute_batch("ROLLBACK");
+                return Err(DatabaseError::SqlExecution { query: statement.clone(), reason: e.to_string() }.into());
+            }
+        }
+    }
+    database.connection().execute_batch("COMMIT")
+        .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+    
+    tracing::info!("Restored {} entries from {}", restored, TRASH_TABLE);
+    Ok(restored)
+}
+
+/// Delete the trashed entries of the given purges (all when empty) for good; those purges
+/// delete instead of trashing from then on. Returns the entries deleted
+pub fn empty(database: &DatabaseManager, purges: &[i64]) -> Result<usize, PdwError> {
+    if database.table_columns(TRASH_TABLE)?.is_empty() {
+        return Ok(0);
+    }
+    purge::secure_delete(database)?;
+    let selected = purge_condition(purges);
+    let statements = [
+        format!("UPDATE {} SET Lixeira = 0 WHERE Id IN (SELECT DISTINCT Purga FROM {} WHERE {})", PURGES_TABLE, TRASH_TABLE, selected),
+        format!("DELETE FROM {} WHERE {}", TRASH_TABLE, selected),
+    ];
+    
+    database.connection().execute_batch("BEGIN")
+        .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+    let mut deleted = 0;
+    for (index, statement) in statements.iter().enumerate() {
+        match database.connection().execute(statement, []) {
+            Ok(rows) if index == 1 => deleted = rows,
+            Ok(_) => {}
+            Err(e) => {
+                let _ = database.connection().execute_batch("ROLLBACK");
+                return Err(DatabaseError::SqlExecution { query: statement.clone(), reason: e.to_string() }.into());
+            }
+        }
+    }
+    database.connection().execute_batch("COMMIT")
+        .map_err(|e| DatabaseError::TransactionFailed { reason: e.to_string() })?;
+    
+    tracing::info!("Deleted {} entries from {}", deleted, TRASH_TABLE);
+    Ok(deleted)
+}
+
+/// Trash table with the columns of the entries table (added as the entries gain columns);
+/// returns the entries table's columns
+fn ensure_table(database: &DatabaseManager, entries_table: &str) -> Result<Vec<String>, PdwError> {
+    let create_query = format!(
+        "CREATE TABLE IF NOT EXISTS {} (Id INTEGER PRIMARY KEY AUTOINCREMENT, Excluido TEXT NOT NULL, Purga INTEGER)",
+        TRASH_TABLE
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution { query: create_query, reason: e.to_string() })?;
+    
+    let columns: Vec<(String, String)> = database.execute_query(&format!("PRAGMA table_info({})", entries_table))?
+        .into_iter()
+        .filter_map(|row| Some((row.get(1)?.as_str()?.to_string(), row.get(2)?.as_str().unwrap_or_default().to_string())))
+        .collect();
+    let schema: Vec<(String, &str)> = columns.iter().map(|(name, sql_type)| (name.clone(), sql_type.as_str())).collect();
+    database.add_derived_columns(TRASH_TABLE, &schema)?;
+    Ok(columns.into_iter().map(|(name, _)| name).collect())
+}
+
+/// Trash rows of the given purges, every row when empty
+fn purge_condition(purges: &[i64]) -> String {
+    if purges.is_empty() {
+        return "1 = 1".to_string();
+    }
+    format!("Purga IN ({})", purges.iter().map(i64::to_string).collect::<Vec<_>>().join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::purge::PurgeFilter;
+    use serde_json::json;
+    
+    #[test]
+    fn test_trash_and_restore() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let db = DatabaseManager::new(&temp_dir.path().join("test.db")).unwrap();
+        db.create_tables().unwrap();
+        let entries = "INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, RowHash) VALUES ('2004-03-01', 'SAUDE', 'Consulta', 0, 80, 'a1');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, RowHash) VALUES ('2004-05-01', 'LAZER', 'Cinema', 0, 20, 'b2');
+             INSERT INTO LANCAMENTOS_GERAIS (Data, TIPO, DESCRICAO, Credito, Debito, RowHash) VALUES ('2006-01-01', 'SAUDE', 'Farmacia', 0, 50, 'c3');";
+        db.connection().execute_batch(entries).unwrap();
+        assert_eq!(list(&db, &[]).unwrap(), (Vec::new(), Vec::new()));
+        
+        let saude = PurgeFilter::new(None, &["saude".to_string()]).unwrap();
+        let before = PurgeFilter::new(chrono::NaiveDate::from_ymd_opt(2005, 1, 1), &[]).unwrap();
+        purge::purge(&db, "LANCAMENTOS_GERAIS", &saude, true).unwrap();
+        purge::purge(&db, "LANCAMENTOS_GERAIS", &before, true).unwrap();
+        assert_eq!(db.execute_query("SELECT COUNT(*) FROM LANCAMENTOS_GERAIS").unwrap(), vec![vec![json!(0)]]);
+        
+        let (columns, rows) = list(&db, &[1]).unwrap();
+        assert_eq!(columns[..3], ["Id", "Excluido", "Purga"]);
+        assert_eq!(rows.iter().map(|row| row[5].clone()).collect::<Vec<_>>(), vec![json!("Consulta"), json!("Farmacia")]);
+        
+        // A reload does not trash the same entries twice
+        db.connection().execute_batch(entries).unwrap();
+        assert_eq!(purge::reapply(&db, "LANCAMENTOS_GERAIS", &[]).unwrap(), 3);
+        assert_eq!(list(&db, &[]).unwrap().1.len(), 3);
+        
+        // Restored purges are not applied by later loads
+        assert_eq!(restore(&db, "LANCAMENTOS_GERAIS", &[1]).unwrap(), 2);
+        assert_eq!(db.execute_query("SELECT DESCRICAO FROM LANCAMENTOS_GERAIS ORDER BY Data").unwrap(),
+                   vec![vec![json!("Consulta")], vec![json!("Farmacia")]]);
+        assert_eq!(purge::reapply(&db, "LANCAMENTOS_GERAIS", &[]).unwrap(), 1);
+        assert_eq!(db.execute_query("SELECT Id FROM PDW_PURGES WHERE Restaurado IS NOT NULL").unwrap(), vec![vec![json!(1)]]);
+        assert_eq!(list(&db, &[]).unwrap().1.len(), 2);
+        
+        // An emptied purge keeps deleting on later loads, with nothing left to restore
+        assert_eq!(empty(&db, &[]).unwrap(), 2);
+        assert_eq!(list(&db, &[]).unwrap().1.len(), 0);
+        db.connection().execute_batch(entries).unwrap();
+        assert_eq!(purge::reapply(&db, "LANCAMENTOS_GERAIS", &[]).unwrap(), 2);
+        assert_eq!(list(&db, &[]).unwrap().1.len(), 0);
+        assert_eq!(restore(&db, "LANCAMENTOS_GERAIS", &[2]).unwrap(), 0);
+    }
+}
\ No newline at end of file
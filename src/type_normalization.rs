@@ -0,0 +1,274 @@
+/*!
+# Type Normalization Module
+
+Folds spelling variants of TIPO ("Mercado", "MERCADO", "mercado", "Mercâdo") into a
+single canonical name before grouping and pivoting, and records which variants
+were merged so the user can fix the source workbook.
+*/
+
+use crate::collation;
+use crate::database::DatabaseManager;
+use crate::error::{DatabaseError, PdwError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// TIPO normalization settings (`[type_normalization]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypeNormalizationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub casing: TypeCasing,
+    #[serde(default = "default_fold_accents")]
+    pub fold_accents: bool,
+    /// Variant -> canonical name, matched after casing/accent folding
+    #[serde(default)]
+    pub canonical: BTreeMap<String, String>,
+    #[serde(default = "default_report_table")]
+    pub report_table: String,
+}
+
+fn default_fold_accents() -> bool {
+    true
+}
+
+fn default_report_table() -> String {
+    "TIPOS_MESCLADOS".to_string()
+}
+
+impl Default for TypeNormalizationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            casing: TypeCasing::default(),
+            fold_accents: default_fold_accents(),
+            canonical: BTreeMap::new(),
+            report_table: default_report_table(),
+        }
+    }
+}
+
+/// Casing applied to normalized types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeCasing {
+    Preserve,
+    #[default]
+    Upper,
+    Lower,
+    Title,
+}
+
+/// Applies casing, accent folding and the canonical-name map to TIPO values
+#[derive(Debug, Clone)]
+pub struct TypeNormalizer {
+    casing: TypeCasing,
+    fold_accents: bool,
+    canonical: HashMap<String, String>,
+}
+
+/// Variants merged into one canonical type
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedType {
+    pub canonical: String,
+    /// Original spelling and number of occurrences
+    pub variants: Vec<(String, usize)>,
+}
+
+/// Collects the original spellings seen for each canonical type
+#[derive(Debug, Default)]
+pub struct VariantTracker {
+    seen: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl TypeNormalizer {
+    /// Build a normalizer from configuration, or None when disabled
+    pub fn from_config(config: &TypeNormalizationConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        
+        let mut normalizer = Self {
+            casing: config.casing,
+            fold_accents: config.fold_accents,
+            canonical: HashMap::new(),
+        };
+        
+        for (variant, canonical) in &config.canonical {
+            let key = normalizer.fold(variant);
+            normalizer.canonical.insert(key, canonical.trim().to_string());
+        }
+        
+        Some(normalizer)
+    }
+    
+    /// Normalize a TIPO value
+    pub fn normalize(&self, raw: &str) -> String {
+        let folded = self.fold(raw);
+        match self.canonical.get(&folded) {
+            Some(canonical) => canonical.clone(),
+            None => folded,
+        }
+    }
+    
+    /// Apply whitespace, accent and casing rules
+    fn fold(&self, raw: &str) -> String {
+        let collapsed = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+        let text = if self.fold_accents {
+            collation::strip_accents(&collapsed)
+        } else {
+            collapsed
+        };
+        
+        match self.casing {
+            TypeCasing::Preserve => text,
+            TypeCasing::Upper => text.to_uppercase(),
+            TypeCasing::Lower => text.to_lowercase(),
+            TypeCasing::Title => title_case(&text),
+        }
+    }
+}
+
+impl VariantTracker {
+    /// Record that `original` was normalized to `canonical`
+    pub fn record(&mut self, original: &str, canonical: &str) {
+        *self.seen.entry(canonical.to_string()).or_default()
+            .entry(original.to_string()).or_insert(0) += 1;
+    }
+    
+    /// Add the spellings collected by another tracker
+    pub fn merge(&mut self, other: VariantTracker) {
+        for (canonical, variants) in other.seen {
+            let seen = self.seen.entry(canonical).or_default();
+            for (original, count) in variants {
+                *seen.entry(original).or_insert(0) += count;
+            }
+        }
+    }
+    
+    /// Canonical types that absorbed a spelling different from their own; casing
+    /// alone is no merge, since the loader matches types case-insensitively
+    pub fn merged(&self) -> Vec<MergedType> {
+        self.seen.iter()
+            .filter(|(canonical, variants)| {
+                let canonical = canonical.to_lowercase();
+                variants.keys().any(|v| v.to_lowercase() != canonical)
+            })
+            .map(|(canonical, variants)| MergedType {
+                canonical: canonical.clone(),
+                variants: variants.iter().map(|(v, n)| (v.clone(), *n)).collect(),
+            })
+            .collect()
+    }
+}
+
+/// Capitalize the first letter of each word
+fn title_case(text: &str) -> String {
+    text.split(' ')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Log merged variants and persist them into the report table
+pub fn write_merge_report(database: &DatabaseManager, table: &str, merged: &[MergedType]) -> Result<usize, PdwError> {
+    database.drop_table(table)?;
+    
+    let create_query = format!(
+        "CREATE TABLE {} (TIPO TEXT, Variante TEXT, Ocorrencias INTEGER)",
+        table
+    );
+    database.connection().execute(&create_query, [])
+        .map_err(|e| DatabaseError::SqlExecution {
+            query: create_query.clone(),
+            reason: e.to_string(),
+        })?;
+    
+    let insert_query = format!("INSERT INTO {} (TIPO, Variante, Ocorrencias) VALUES (?1, ?2, ?3)", table);
+    let mut count = 0;
+    
+    for group in merged {
+        let spellings: Vec<String> = group.variants.iter()
+            .map(|(variant, n)| format!("'{}' x{}", variant, n))
+            .collect();
+        tracing::info!("TIPO '{}' merged from {}", group.canonical, spellings.join(", "));
+        
+        for (variant, occurrences) in &group.variants {
+            database.connection()
+                .execute(&insert_query, rusqlite::params![group.canonical, variant, *occurrences as i64])
+                .map_err(|e| DatabaseError::DataInsertion {
+                    table: table.to_string(),
+                    reason: e.to_string(),
+                })?;
+            count += 1;
+        }
+    }
+    
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn normalizer(casing: TypeCasing) -> TypeNormalizer {
+        let mut config = TypeNormalizationConfig {
+            enabled: true,
+            casing,
+            ..Default::default()
+        };
+        config.canonical.insert("supermercado".to_string(), "MERCADO".to_string());
+        TypeNormalizer::from_config(&config).unwrap()
+    }
+    
+    #[test]
+    fn test_disabled_by_default() {
+        assert!(TypeNormalizer::from_config(&TypeNormalizationConfig::default()).is_none());
+    }
+    
+    #[test]
+    fn test_casing_and_accent_folding() {
+        let upper = normalizer(TypeCasing::Upper);
+        assert_eq!(upper.normalize("Mercado"), "MERCADO");
+        assert_eq!(upper.normalize("  mercâdo "), "MERCADO");
+        assert_eq!(upper.normalize("Saúde  Família"), "SAUDE FAMILIA");
+        
+        let title = normalizer(TypeCasing::Title);
+        assert_eq!(title.normalize("SAÚDE família"), "Saude Familia");
+    }
+    
+    #[test]
+    fn test_canonical_map() {
+        let upper = normalizer(TypeCasing::Upper);
+        assert_eq!(upper.normalize("SuperMercado"), "MERCADO");
+        assert_eq!(upper.normalize("Súpermercado"), "MERCADO");
+    }
+    
+    #[test]
+    fn test_variant_tracking() {
+        let upper = normalizer(TypeCasing::Upper);
+        let mut tracker = VariantTracker::default();
+        for raw in ["Mercado", "MERCADO", "mercado", "MERCADO", "LAZER", "Lazer"] {
+            tracker.record(raw, &upper.normalize(raw));
+        }
+        // Spellings differing only in case are not reported
+        assert!(tracker.merged().is_empty());
+        
+        tracker.record("Mercâdo", &upper.normalize("Mercâdo"));
+        let merged = tracker.merged();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].canonical, "MERCADO");
+        assert_eq!(merged[0].variants, vec![
+            ("MERCADO".to_string(), 2),
+            ("Mercado".to_string(), 1),
+            ("Mercâdo".to_string(), 1),
+            ("mercado".to_string(), 1),
+        ]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,232 @@
+/*!
+# Watch Module
+
+Keeps the warehouse in step with the input spreadsheet. `pdw watch` monitors
+the input directory for changes to the input workbook (or the CSV sheet files)
+and the YAML report queries, waits until writes settle and then re-runs the
+configured pipeline phases. Watching the filesystem needs a build with
+`--features watch`.
+*/
+
+use crate::config::PdwConfig;
+use crate::error::PdwError;
+use crate::input_files;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Files whose changes trigger a pipeline run
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchSet {
+    /// Directories registered with the watcher
+    pub directories: Vec<PathBuf>,
+    /// Individual files that trigger a run
+    pub files: Vec<PathBuf>,
+    /// Directory whose CSV files all trigger a run (CSV input)
+    pub csv_directory: Option<PathBuf>,
+}
+
+/// Collapses bursts of change events into a single run
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    quiet: Duration,
+    last_event: Option<Instant>,
+    changed: Vec<PathBuf>,
+}
+
+impl WatchSet {
+    /// Input workbook (or CSV directory), YAML queries and categorization rules of a configuration
+    pub fn from_config(config: &PdwConfig) -> Self {
+        let input_path = config.get_input_file_path();
+        let mut watch_set = Self {
+            directories: vec![config.directories.dir_in.clone()],
+            files: vec![config.get_yaml_queries_path()],
+            csv_directory: None,
+        };
+        if let Some(rules_file) = config.get_category_rules_path() {
+            watch_set.files.push(rules_file);
+        }
+        
+        if config.file_types.type_in.eq_ignore_ascii_case("csv") {
+            watch_set.directories.push(input_path.clone());
+            watch_set.csv_directory = Some(input_path);
+        } else {
+            watch_set.files.push(input_path);
+        }
+        
+        watch_set
+    }
+    
+    /// Whether a changed path should trigger a run
+    pub fn is_relevant(&self, path: &Path) -> bool {
+        // Office lock files, editor backups and partial downloads
+        if input_files::is_temporary(path) {
+            return false;
+        }
+        
+        if self.files.iter().any(|file| same_file(file, path)) {
+            return true;
+        }
+        
+        self.csv_directory.as_ref().is_some_and(|dir| {
+            path.parent().is_some_and(|parent| same_directory(dir, parent))
+                && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+        })
+    }
+}
+
+impl Debouncer {
+    /// Debouncer waiting `quiet` after the last event
+    pub fn new(quiet: Duration) -> Self {
+        Self { quiet, last_event: None, changed: Vec::new() }
+    }
+    
+    /// Record a change seen at `now`
+    pub fn record(&mut self, path: PathBuf, now: Instant) {
+        self.last_event = Some(now);
+        if !self.changed.contains(&path) {
+            self.changed.push(path);
+        }
+    }
+    
+    /// Time left until the pending changes settle (None when idle)
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.last_event.map(|last| (last + self.quiet).saturating_duration_since(now))
+    }
+    
+    /// Changed paths once writes have settled, resetting the debouncer
+    pub fn take_ready(&mut self, now: Instant) -> Option<Vec<PathBuf>> {
+        match self.remaining(now) {
+            Some(remaining) if remaining.is_zero() => {
+                self.last_event = None;
+                Some(std::mem::take(&mut self.changed))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Compare directories, resolving relative paths and links
+fn same_directory(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Compare files by name and directory; the file itself may be gone mid-save
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || (a.file_name() == b.file_name() && match (a.parent(), b.parent()) {
+        (Some(pa), Some(pb)) => same_directory(pa, pb),
+        _ => false,
+    })
+}
+
+/// Block watching the input files, calling `on_change` with the changed paths after each settled burst
+#[cfg(feature = "watch")]
+pub fn watch<F>(watch_set: &WatchSet, quiet: Duration, mut on_change: F) -> Result<(), PdwError>
+where
+    F: FnMut(&[PathBuf]),
+{
+    use notify::{EventKind, RecursiveMode, Watcher};
+    use std::sync::mpsc::{channel, RecvTimeoutError};
+    
+    let watch_error = |e: notify::Error| PdwError::Watch(e.to_string());
+    
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+    for directory in &watch_set.directories {
+        watcher.watch(directory, RecursiveMode::NonRecursive).map_err(watch_error)?;
+        tracing::info!("Watching {}", directory.display());
+    }
+    
+    let mut debouncer = Debouncer::new(quiet);
+    loop {
+        let event = match debouncer.remaining(Instant::now()) {
+            Some(remaining) => receiver.recv_timeout(remaining),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        
+        match event {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Access(_)) {
+                    continue;
+                }
+                for path in event.paths.into_iter().filter(|p| watch_set.is_relevant(p)) {
+                    tracing::debug!("Change detected: {}", path.display());
+                    debouncer.record(path, Instant::now());
+                }
+            }
+            Ok(Err(e)) => tracing::warn!("Watcher error: {}", e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(PdwError::Watch("File watcher stopped unexpectedly".to_string()));
+            }
+        }
+        
+        if let Some(changed) = debouncer.take_ready(Instant::now()) {
+            on_change(&changed);
+        }
+    }
+}
+
+#[cfg(not(feature = "watch"))]
+pub fn watch<F>(_watch_set: &WatchSet, _quiet: Duration, _on_change: F) -> Result<(), PdwError>
+where
+    F: FnMut(&[PathBuf]),
+{
+    Err(PdwError::Watch("pdw watch requires a build with --features watch".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn test_config(dir_in: &Path, type_in: &str) -> PdwConfig {
+        let mut config = PdwConfig::default();
+        config.directories.dir_in = dir_in.to_path_buf();
+        config.file_types.type_in = type_in.to_string();
+        config
+    }
+    
+    #[test]
+    fn test_workbook_watch_set() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path(), "xlsx");
+        let watch_set = WatchSet::from_config(&config);
+        assert_eq!(watch_set.directories, vec![temp_dir.path().to_path_buf()]);
+        
+        assert!(watch_set.is_relevant(&config.get_input_file_path()));
+        assert!(watch_set.is_relevant(&config.get_yaml_queries_path()));
+        assert!(!watch_set.is_relevant(&temp_dir.path().join("~$PDW.xlsx")));
+        assert!(!watch_set.is_relevant(&temp_dir.path().join("other.xlsx")));
+    }
+    
+    #[test]
+    fn test_csv_watch_set() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path(), "csv");
+        let csv_dir = config.get_input_file_path();
+        std::fs::create_dir_all(&csv_dir).unwrap();
+        
+        let watch_set = WatchSet::from_config(&config);
+        assert_eq!(watch_set.directories.len(), 2);
+        assert!(watch_set.is_relevant(&csv_dir.join("CONTA.csv")));
+        assert!(!watch_set.is_relevant(&csv_dir.join("notes.txt")));
+        assert!(!watch_set.is_relevant(&temp_dir.path().join("CONTA.csv")));
+    }
+    
+    #[test]
+    fn test_debouncer_waits_for_quiet_period() {
+        let start = Instant::now();
+        let quiet = Duration::from_millis(500);
+        let mut debouncer = Debouncer::new(quiet);
+        assert_eq!(debouncer.remaining(start), None);
+        
+        let file = PathBuf::from("PDW.xlsx");
+        debouncer.record(file.clone(), start);
+        debouncer.record(file.clone(), start + Duration::from_millis(300));
+        assert_eq!(debouncer.take_ready(start + Duration::from_millis(600)), None);
+        
+        let changed = debouncer.take_ready(start + Duration::from_millis(800)).unwrap();
+        assert_eq!(changed, vec![file]);
+        assert_eq!(debouncer.remaining(start + Duration::from_secs(2)), None);
+    }
+}
\ No newline at end of file
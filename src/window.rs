@@ -0,0 +1,111 @@
+/*!
+# Date Window Module
+
+Restricts a load to the entries of a range of months (`pdw --window
+2024-01..2024-03`), to reproduce an issue reported for a given month without
+reading a huge workbook into the database again. Rows dated outside the window
+(and undated rows) are dropped right after reading; in the database only the
+window's entries are replaced, every other period and the reference tables are
+left as they are.
+*/
+
+use crate::database::PeriodSet;
+use crate::error::{ConfigError, PdwError};
+use crate::excel::Transaction;
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Inclusive range of whole months
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateWindow {
+    /// First day of the first month
+    pub first: NaiveDate,
+    /// Last day of the last month
+    pub last: NaiveDate,
+}
+
+impl DateWindow {
+    /// Parse "2024-01..2024-03", or a single month "2024-02"
+    pub fn parse(text: &str) -> Result<Self, PdwError> {
+        let (from, to) = text.split_once("..").unwrap_or((text, text));
+        let first = parse_month(from)?;
+        let last = parse_month(to)?;
+        if last < first {
+            return Err(ConfigError::InvalidFormat {
+                message: format!("Window '{}' ends before it starts", text),
+            }.into());
+        }
+        
+        let last = last.checked_add_months(Months::new(1)).and_then(|next| next.pred_opt()).unwrap_or(last);
+        Ok(Self { first, last })
+    }
+    
+    /// Whether a date falls inside the window
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.first <= date && date <= self.last
+    }
+    
+    /// Drop the rows outside the window (undated ones included); returns how many were dropped
+    pub fn retain(&self, transactions: &mut Vec<Transaction>) -> usize {
+        let before = transactions.len();
+        transactions.retain(|transaction| transaction.date.is_some_and(|date| self.contains(date)));
+        before - transactions.len()
+    }
+    
+    /// AnoMes periods of the window, for the pivot refresh
+    pub fn periods(&self) -> PeriodSet {
+        let mut months = Vec::new();
+        let mut month = self.first;
+        while month <= self.last {
+            months.push(format!("{}/{:02}", month.year(), month.month()));
+            month = match month.checked_add_months(Months::new(1)) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        PeriodSet::from_months(months)
+    }
+}
+
+impl fmt::Display for DateWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.first.format("%Y-%m"), self.last.format("%Y-%m"))
+    }
+}
+
+/// First day of a "YYYY-MM" month
+fn parse_month(text: &str) -> Result<NaiveDate, PdwError> {
+    NaiveDate::parse_from_str(&format!("{}-01", text.trim()), "%Y-%m-%d")
+        .map_err(|_| ConfigError::InvalidFormat {
+            message: format!("Invalid window month '{}' (expected YYYY-MM)", text),
+        }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+    
+    #[test]
+    fn test_window_parsing_and_periods() {
+        let window = DateWindow::parse("2023-12..2024-02").unwrap();
+        assert_eq!((window.first, window.last), (date("2023-12-01"), date("2024-02-29")));
+        assert_eq!(window.to_string(), "2023-12..2024-02");
+        assert!(window.contains(date("2024-02-29")) && !window.contains(date("2024-03-01")));
+        
+        let periods = window.periods();
+        assert_eq!(periods.months.into_iter().collect::<Vec<_>>(), vec!["2023/12", "2024/01", "2024/02"]);
+        assert_eq!(periods.years.len(), 2);
+        
+        let single = DateWindow::parse("2024-04").unwrap();
+        assert_eq!((single.first, single.last), (date("2024-04-01"), date("2024-04-30")));
+        
+        assert!(DateWindow::parse("2024-03..2024-01").is_err());
+        assert!(DateWindow::parse("2024-13").is_err());
+        assert!(DateWindow::parse("janeiro").is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,255 @@
+/*!
+# Workbook Writer Module
+
+Inverse of the Excel extraction: regenerates a normalized master workbook from
+the warehouse database, with one accounting sheet per origin plus freshly built
+GUIDING and TiposLancamentos sheets that the loader can consume directly.
+*/
+
+use crate::config::PdwConfig;
+use crate::database::DatabaseManager;
+use crate::error::{PdwError, ReportError};
+use chrono::{Datelike, NaiveDate};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+use serde_json::Value;
+use std::path::Path;
+
+/// Header row of every regenerated accounting sheet (matches the loader's expectations)
+const ACCOUNTING_HEADERS: [&str; 5] = ["Data", "TIPO", "DESCRICAO", "Credito", "Debito"];
+
+/// Writer that rebuilds a clean input workbook from the database
+pub struct WorkbookWriter {
+    database: DatabaseManager,
+    config: PdwConfig,
+}
+
+/// Cleaned accounting rows grouped by origin
+#[derive(Debug, Clone)]
+struct OriginSheet {
+    name: String,
+    rows: Vec<Vec<Value>>,
+}
+
+impl WorkbookWriter {
+    /// Create new workbook writer
+    pub fn new(database: DatabaseManager, config: PdwConfig) -> Self {
+        Self { database, config }
+    }
+    
+    /// Write the master workbook and return the number of sheets created
+    pub fn write_master_workbook(&self, output_path: &Path) -> Result<usize, PdwError> {
+        let origins = self.read_origin_sheets()?;
+        let mut workbook = Workbook::new();
+        
+        let header_format = Format::new().set_bold();
+        let date_format = Format::new().set_num_format("dd/mm/yyyy");
+        let amount_format = Format::new().set_num_format("#,##0.00");
+        
+        // GUIDING sheet: every origin is an accounting sheet, the types sheet is reference data
+        let guiding = workbook.add_worksheet();
+        guiding.set_name(&self.config.settings.guiding_table)
+            .map_err(ReportError::ExcelWriter)?;
+        for (col, header) in ["TABLE_NAME", "ACCOUNTING", "LOADABLE"].iter().enumerate() {
+            guiding.write_string_with_format(0, col as u16, *header, &header_format)
+                .map_err(ReportError::ExcelWriter)?;
+        }
+        let mut guiding_row = 1;
+        for origin in &origins {
+            guiding.write_string(guiding_row, 0, &origin.name).map_err(ReportError::ExcelWriter)?;
+            guiding.write_string(guiding_row, 1, "X").map_err(ReportError::ExcelWriter)?;
+            guiding.write_string(guiding_row, 2, "X").map_err(ReportError::ExcelWriter)?;
+            guiding_row += 1;
+        }
+        guiding.write_string(guiding_row, 0, &self.config.settings.types_of_entries)
+            .map_err(ReportError::ExcelWriter)?;
+        guiding.write_string(guiding_row, 2, "X").map_err(ReportError::ExcelWriter)?;
+        
+        // TiposLancamentos sheet
+        let types = self.read_types()?;
+        let types_sheet = workbook.add_worksheet();
+        types_sheet.set_name(&self.config.settings.types_of_entries)
+            .map_err(ReportError::ExcelWriter)?;
+        types_sheet.write_string_with_format(0, 0, "Código", &header_format)
+            .map_err(ReportError::ExcelWriter)?;
+        types_sheet.write_string_with_format(0, 1, "Descrição", &header_format)
+            .map_err(ReportError::ExcelWriter)?;
+        for (idx, (code, description)) in types.iter().enumerate() {
+            let row = idx as u32 + 1;
+            types_sheet.write_string(row, 0, code).map_err(ReportError::ExcelWriter)?;
+            types_sheet.write_string(row, 1, description).map_err(ReportError::ExcelWriter)?;
+        }
+        
+        // One accounting sheet per origin
+        for origin in &origins {
+            let worksheet = workbook.add_worksheet();
+            worksheet.set_name(&origin.name).map_err(ReportError::ExcelWriter)?;
+            
+            for (col, header) in ACCOUNTING_HEADERS.iter().enumerate() {
+                worksheet.write_string_with_format(0, col as u16, *header, &header_format)
+                    .map_err(ReportError::ExcelWriter)?;
+            }
+            
+            for (idx, row_data) in origin.rows.iter().enumerate() {
+                let row = idx as u32 + 1;
+                
+                if let Some(Value::String(date)) = row_data.first() {
+                    match parse_iso_date(date) {
+                        Some(excel_date) => {
+                            worksheet.write_datetime_with_format(row, 0, &excel_date, &date_format)
+                                .map_err(ReportError::ExcelWriter)?;
+                        }
+                        None => {
+                            worksheet.write_string(row, 0, date).map_err(ReportError::ExcelWriter)?;
+                        }
+                    }
+                }
+                
+                for col in 1..=2 {
+                    if let Some(Value::String(text)) = row_data.get(col) {
+                        worksheet.write_string(row, col as u16, text)
+                            .map_err(ReportError::ExcelWriter)?;
+                    }
+                }
+                
+                for col in 3..=4 {
+                    let amount = row_data.get(col).and_then(Value::as_f64).unwrap_or(0.0);
+                    if amount != 0.0 {
+                        worksheet.write_number_with_format(row, col as u16, amount, &amount_format)
+                            .map_err(ReportError::ExcelWriter)?;
+                    }
+                }
+            }
+        }
+        
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        
+        workbook.save(output_path).map_err(ReportError::ExcelWriter)?;
+        
+        tracing::info!("Master workbook regenerated: {}", output_path.display());
+        Ok(origins.len() + 2)
+    }
+    
+    /// Read cleaned transactions grouped by origin
+    fn read_origin_sheets(&self) -> Result<Vec<OriginSheet>, PdwError> {
+        let query = format!(
+            "SELECT Data, TIPO, DESCRICAO, Credito, Debito, Origem
+             FROM {}
+             ORDER BY {}, Data, {}",
+            self.config.settings.general_entries_table,
+            self.database.order_by("Origem"),
+            self.database.order_by("TIPO")
+        );
+        
+        let results = self.database.execute_query(&query)?;
+        let mut sheets: Vec<OriginSheet> = Vec::new();
+        
+        for mut row in results {
+            let origin = match row.pop() {
+                Some(Value::String(origin)) => origin,
+                _ => continue,
+            };
+            
+            match sheets.last_mut() {
+                Some(sheet) if sheet.name == origin => sheet.rows.push(row),
+                _ => sheets.push(OriginSheet { name: origin, rows: vec![row] }),
+            }
+        }
+        
+        Ok(sheets)
+    }
+    
+    /// Read transaction type codes and descriptions
+    fn read_types(&self) -> Result<Vec<(String, String)>, PdwError> {
+        let query = format!(
+            "SELECT Código, Descrição FROM {} WHERE Código IS NOT NULL ORDER BY {}",
+            self.config.settings.types_of_entries,
+            self.database.order_by("Código")
+        );
+        
+        let results = self.database.execute_query(&query)?;
+        
+        Ok(results.into_iter()
+            .filter_map(|row| {
+                let code = value_to_string(row.first()?);
+                let description = row.get(1).map(value_to_string).unwrap_or_default();
+                if code.is_empty() || code == "Código" {
+                    None
+                } else {
+                    Some((code, description))
+                }
+            })
+            .collect())
+    }
+}
+
+/// Convert a stored ISO date (YYYY-MM-DD) into an Excel date
+fn parse_iso_date(date: &str) -> Option<ExcelDateTime> {
+    let parsed = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    ExcelDateTime::from_ymd(parsed.year() as u16, parsed.month() as u8, parsed.day() as u8).ok()
+}
+
+/// Render a query value as plain text
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::ProcessedTransaction;
+    use tempfile::TempDir;
+    
+    fn sample_transaction(origin: &str, day: u32) -> ProcessedTransaction {
+        ProcessedTransaction {
+            date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            day_of_week: "Segunda-feira".to_string(),
+            transaction_type: "ALM".to_string(),
+            description: "Mercado".to_string(),
+            credit: crate::money::Decimal::ZERO,
+            debit: crate::money::Decimal::new(425, 1),
+            month: "01".to_string(),
+            year: "2024".to_string(),
+            month_name: "01-Janeiro".to_string(),
+            year_month: "2024/01".to_string(),
+            origin: origin.to_string(),
+            derived: Vec::new(),
+            currency: None,
+        }
+    }
+    
+    #[test]
+    fn test_parse_iso_date() {
+        assert!(parse_iso_date("2024-01-15").is_some());
+        assert!(parse_iso_date("15/01/2024").is_none());
+    }
+    
+    #[test]
+    fn test_master_workbook_generation() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        
+        let database = DatabaseManager::new(&db_path).unwrap();
+        database.create_tables().unwrap();
+        database.insert_transactions(&[
+            sample_transaction("Conta", 15),
+            sample_transaction("Cartao", 16),
+            sample_transaction("Conta", 17),
+        ]).unwrap();
+        
+        let writer = WorkbookWriter::new(database, PdwConfig::default());
+        let origins = writer.read_origin_sheets().unwrap();
+        assert_eq!(origins.len(), 2);
+        assert_eq!(origins[1].rows.len(), 2);
+        
+        let output_path = temp_dir.path().join("clean.xlsx");
+        let sheet_count = writer.write_master_workbook(&output_path).unwrap();
+        assert_eq!(sheet_count, 4);
+        assert!(output_path.exists());
+    }
+}
\ No newline at end of file